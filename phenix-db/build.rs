@@ -0,0 +1,113 @@
+//! Generates the gRPC service stubs for `proto/phenix.proto`
+//!
+//! Message types are hand-written in `src/api/protocol.rs`, so the stubs are
+//! produced with tonic's manual builder and no `protoc` is needed at build
+//! time. Each service is written to `$OUT_DIR/phenix.v1.<Service>.rs`.
+
+use tonic_build::manual::{Builder, Method, Service};
+
+const PACKAGE: &str = "phenix.v1";
+const PROTOCOL: &str = "crate::api::protocol";
+
+/// How messages flow through a method
+#[derive(Clone, Copy)]
+enum Streaming {
+    Unary,
+    Client,
+    Server,
+}
+
+/// (service, [(method, route, input, output, streaming)])
+type ServiceSpec = (&'static str, &'static [(&'static str, &'static str, &'static str, &'static str, Streaming)]);
+
+const SERVICES: &[ServiceSpec] = &[
+    (
+        "EntityService",
+        &[
+            ("create_entity", "CreateEntity", "WriteEntityRequest", "Entity", Streaming::Unary),
+            ("get_entity", "GetEntity", "EntityRef", "Entity", Streaming::Unary),
+            ("update_entity", "UpdateEntity", "WriteEntityRequest", "Entity", Streaming::Unary),
+            ("delete_entity", "DeleteEntity", "EntityRef", "DeleteEntityResponse", Streaming::Unary),
+            ("batch_upsert", "BatchUpsert", "BatchUpsertRequest", "BatchUpsertResponse", Streaming::Unary),
+            ("import_entities", "ImportEntities", "BatchUpsertRequest", "BatchUpsertResponse", Streaming::Client),
+        ],
+    ),
+    (
+        "SearchService",
+        &[("search", "Search", "SearchRequest", "SearchResponse", Streaming::Unary)],
+    ),
+    (
+        "GraphService",
+        &[("traverse", "Traverse", "TraverseRequest", "TraverseResponse", Streaming::Unary)],
+    ),
+    (
+        "QueryService",
+        &[("query", "Query", "QueryRequest", "QueryResponse", Streaming::Unary)],
+    ),
+    (
+        "AdminService",
+        &[
+            ("create_collection", "CreateCollection", "CreateCollectionRequest", "CollectionInfo", Streaming::Unary),
+            ("list_collections", "ListCollections", "Empty", "ListCollectionsResponse", Streaming::Unary),
+            ("describe_collection", "DescribeCollection", "CollectionRef", "CollectionInfo", Streaming::Unary),
+            ("drop_collection", "DropCollection", "CollectionRef", "Empty", Streaming::Unary),
+            ("set_alias", "SetAlias", "SetAliasRequest", "Empty", Streaming::Unary),
+            ("drop_alias", "DropAlias", "DropAliasRequest", "Empty", Streaming::Unary),
+            ("cluster_status", "ClusterStatus", "Empty", "ClusterStatusResponse", Streaming::Unary),
+        ],
+    ),
+    (
+        "TransactionService",
+        &[
+            ("begin", "Begin", "CollectionRef", "TransactionRef", Streaming::Unary),
+            ("get", "Get", "TransactionEntityRef", "Entity", Streaming::Unary),
+            ("put", "Put", "TransactionWriteRequest", "Entity", Streaming::Unary),
+            ("delete", "Delete", "TransactionEntityRef", "DeleteEntityResponse", Streaming::Unary),
+            ("commit", "Commit", "TransactionRef", "CommitTransactionResponse", Streaming::Unary),
+            ("abort", "Abort", "TransactionRef", "Empty", Streaming::Unary),
+        ],
+    ),
+    (
+        "ChangeFeedService",
+        &[("subscribe", "Subscribe", "SubscribeRequest", "ChangeEvent", Streaming::Server)],
+    ),
+    (
+        "CdcService",
+        &[("stream", "Stream", "CdcRequest", "CdcEvent", Streaming::Server)],
+    ),
+];
+
+fn message_path(name: &str) -> String {
+    format!("{}::{}", PROTOCOL, name)
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=proto/phenix.proto");
+
+    let services: Vec<Service> = SERVICES
+        .iter()
+        .map(|(name, methods)| {
+            methods.iter().fold(
+                Service::builder().name(*name).package(PACKAGE),
+                |service, (method, route, input, output, streaming)| {
+                    let builder = Method::builder()
+                        .name(*method)
+                        .route_name(*route)
+                        .input_type(message_path(input))
+                        .output_type(message_path(output))
+                        .codec_path("tonic::codec::ProstCodec");
+                    let builder = match streaming {
+                        Streaming::Unary => builder,
+                        Streaming::Client => builder.client_streaming(),
+                        Streaming::Server => builder.server_streaming(),
+                    };
+                    service.method(builder.build())
+                },
+            )
+            .build()
+        })
+        .collect();
+
+    Builder::new().compile(&services);
+}
@@ -0,0 +1,21 @@
+//! Decode arbitrary bytes as a Parquet file
+//!
+//! Run with `cargo fuzz run parquet_read`. Every input must decode to rows
+//! or errors without panicking, and within the row group memory budget.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use phenix_db::core::config::BulkConfig;
+use phenix_db::storage::bulk::parquet;
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+    let config = BulkConfig {
+        max_decode_bytes: 64 * 1024 * 1024,
+        ..BulkConfig::default()
+    };
+    if let Ok(rows) = parquet::read_from(Cursor::new(data.to_vec()), &config) {
+        rows.for_each(drop);
+    }
+});
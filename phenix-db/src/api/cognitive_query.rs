@@ -1 +1,2172 @@
-// TODO: Implement cognitive query language
+//! Cognitive query language
+//!
+//! One declarative statement combines similarity search, metadata filters,
+//! graph expansion, ranking and tier restrictions:
+//!
+//! ```text
+//! EXPLAIN FIND IN papers
+//!     NEAR [0.12, 0.40, 0.33] K 50
+//!     WHERE lang = "en" AND year >= 2020
+//!     EXPAND 2 HOPS ALONG "cites" MIN PROBABILITY 0.6
+//!     RANK BY PAGERANK
+//!     TIERS hot, warm
+//!     LIMIT 10
+//! ```
+//!
+//! Text is parsed into a `CognitiveQuery`, lowered to a `LogicalPlan` and
+//! then either executed against a `CollectionView` or costed by a `CostModel`.
+//! `CognitiveQuery::plan` is the straightforward lowering; the query planner
+//! picks among the equivalent plans of `CognitiveQuery::plan_with`.
+//! Keywords are case-insensitive, and the clauses after the collection name
+//! may appear in any order, each at most once.
+//!
+//! Grammar:
+//!
+//! ```text
+//! query      := [EXPLAIN] FIND IN name clause* [";"]
+//! clause     := NEAR (vector | ENTITY string) [K int]
+//!             | WHERE predicate
+//!             | WITHIN int (HOP | HOPS) OF ENTITY string edges
+//!             | EXPAND int (HOP | HOPS) edges
+//!             | RANK BY (DISTANCE | PAGERANK)
+//!             | TIERS tier ("," tier)*
+//!             | LIMIT int
+//! edges      := [ALONG string ("," string)*] [MIN PROBABILITY number]
+//! predicate  := conjunct (OR conjunct)*
+//! conjunct   := negation (AND negation)*
+//! negation   := NOT negation | "(" predicate ")" | field comparison
+//! comparison := ("=" | "!=" | "<" | "<=" | ">" | ">=") value
+//!             | IN "(" value ("," value)* ")"
+//!             | EXISTS
+//! ```
+//!
+//! Fields use `FieldPath` syntax (`author.name`, `tags[0]`); values are JSON
+//! strings, numbers, `true`, `false` or `null`.
+//!
+//! Semantics:
+//! - `NEAR` seeds the result with the `K` nearest entities (default
+//!   `4 × LIMIT`); `NEAR ENTITY` excludes the anchor entity itself. Without
+//!   `NEAR` every entity is a seed
+//! - `WHERE` restricts the seeds; entities reached by `EXPAND` are kept
+//!   whatever their metadata
+//! - `WITHIN` restricts the seeds to entities reachable from the anchor in
+//!   at most that many hops, the anchor included
+//! - `TIERS` restricts every result, seeds and expanded entities alike
+//! - `RANK BY DISTANCE` (the default with `NEAR`) orders by distance to the
+//!   query vector; `RANK BY PAGERANK` orders by PageRank over the edges
+//!   between the candidates, weighted by learned probability
+//! - `EXPLAIN` returns the plan and its estimated cost instead of rows
+
+use crate::core::config::CostWeights;
+use crate::core::error::{CollectionError, MemorySubstrateError, QueryError, Result};
+use crate::core::metadata::{FieldPath, MetadataFilter};
+use crate::core::query::{FilterStrategy, GraphQuery, VectorQuery};
+use crate::core::{EntityId, MemoryTier, Vector};
+use crate::index::FilterConfig;
+use crate::storage::collection::{Collection, CollectionView};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Add;
+use std::str::FromStr;
+
+/// Result count when no `LIMIT` is given
+pub const DEFAULT_LIMIT: usize = 10;
+
+/// Seeds fetched per requested result when `NEAR` has no `K`
+pub const CANDIDATE_FACTOR: usize = 4;
+
+/// Deepest `EXPAND` or `WITHIN` accepted
+pub const MAX_EXPAND_HOPS: usize = 5;
+
+/// Deepest nesting of parentheses and `NOT` accepted in a `WHERE` clause
+pub const MAX_FILTER_NESTING: usize = 64;
+
+const KEYWORDS: &[&str] = &[
+    "explain", "find", "in", "near", "entity", "k", "where", "and", "or", "not", "exists", "expand",
+    "hop", "hops", "along", "min", "probability", "rank", "by", "distance", "pagerank", "tiers", "limit",
+    "within", "of",
+];
+
+const PAGERANK_DAMPING: f64 = 0.85;
+const PAGERANK_ITERATIONS: usize = 20;
+
+/// Where the seed set of a query comes from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Near {
+    /// Literal query vector
+    Vector(Vec<f32>),
+
+    /// Vector of a stored entity
+    Entity(EntityId),
+}
+
+/// Graph expansion from the seed set
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Expansion {
+    /// Maximum number of hops from a seed
+    pub hops: usize,
+
+    /// Only follow edges with one of these labels; empty follows all
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+
+    /// Only follow edges whose learned probability is at least this
+    #[serde(default)]
+    pub min_probability: f32,
+}
+
+/// Entities reachable from an anchor entity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Neighborhood {
+    /// Entity the walk starts from
+    pub anchor: EntityId,
+
+    /// Hops and edges the walk may take
+    pub edges: Expansion,
+}
+
+/// Order of the results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ranking {
+    /// Closest to the query vector first
+    Distance,
+
+    /// Highest PageRank within the candidate subgraph first
+    PageRank,
+}
+
+/// Parsed cognitive query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CognitiveQuery {
+    /// Return the plan and its cost instead of rows
+    pub explain: bool,
+
+    /// Collection name or alias
+    pub collection: String,
+
+    /// Similarity seed, if any
+    pub near: Option<Near>,
+
+    /// Number of nearest seeds; defaults to `CANDIDATE_FACTOR × limit`
+    pub k: Option<usize>,
+
+    /// Predicate the seeds must satisfy
+    pub filter: Option<MetadataFilter>,
+
+    /// Graph neighbourhood the seeds must lie in
+    pub within: Option<Neighborhood>,
+
+    /// Graph expansion from the seeds
+    pub expand: Option<Expansion>,
+
+    /// Explicit result order
+    pub rank: Option<Ranking>,
+
+    /// Tiers results must live in; empty allows all
+    pub tiers: Vec<MemoryTier>,
+
+    /// Maximum number of results
+    pub limit: usize,
+}
+
+impl CognitiveQuery {
+    /// Parse query text
+    ///
+    /// # Errors
+    /// * `Query(Syntax)` with the byte offset of the offending token
+    /// * `Query(Invalid)` for well-formed queries with out-of-range values
+    pub fn parse(text: &str) -> Result<Self> {
+        let tokens = tokenize(text)?;
+        Parser {
+            tokens,
+            pos: 0,
+            end: text.len(),
+            depth: 0,
+        }
+        .query()
+    }
+
+    /// Number of seeds fetched by `NEAR`
+    pub fn candidates(&self) -> usize {
+        self.k.unwrap_or(self.limit.saturating_mul(CANDIDATE_FACTOR))
+    }
+
+    /// How `plan` produces the seeds
+    pub fn default_choice(&self) -> PlanChoice {
+        let access = match (&self.near, &self.within) {
+            (Some(_), Some(_)) => AccessPath::GraphFirst,
+            (Some(_), None) => AccessPath::VectorSearch { strategy: None },
+            (None, Some(_)) => AccessPath::Neighborhood,
+            (None, None) => AccessPath::Scan,
+        };
+        PlanChoice {
+            access,
+            prune_cold: None,
+        }
+    }
+
+    /// Lower the query to a logical plan
+    ///
+    /// With `NEAR` the filter is pushed into the vector search, which picks
+    /// pre-, in-graph or post-filtering from the filter's selectivity.
+    pub fn plan(&self) -> LogicalPlan {
+        self.plan_with(&self.default_choice())
+    }
+
+    /// Lower the query with the given seed access path
+    ///
+    /// Access paths that do not fit the query (say, a vector search for a
+    /// query without `NEAR`) fall back to `default_choice`. Cold pruning only
+    /// applies to queries ranked by distance.
+    pub fn plan_with(&self, choice: &PlanChoice) -> LogicalPlan {
+        let mut operators = Vec::new();
+        let filter = || {
+            self.filter.clone().map(|predicate| Operator::Filter { predicate })
+        };
+        match (&choice.access, &self.near, &self.within) {
+            (AccessPath::Scan, None, None) => {
+                operators.push(Operator::Scan);
+                operators.extend(filter());
+            }
+            (AccessPath::IndexScan, None, None) => operators.push(match &self.filter {
+                Some(filter) => Operator::IndexScan { filter: filter.clone() },
+                None => Operator::Scan,
+            }),
+            (AccessPath::Neighborhood, None, Some(within)) => {
+                operators.push(Operator::Neighborhood(within.clone()));
+                operators.extend(filter());
+            }
+            (AccessPath::VectorSearch { strategy }, Some(near), None) => operators.push(Operator::VectorSearch {
+                near: near.clone(),
+                k: self.candidates(),
+                filter: self.filter.clone(),
+                strategy: *strategy,
+            }),
+            (AccessPath::GraphFirst, Some(near), Some(within)) => {
+                operators.push(Operator::Neighborhood(within.clone()));
+                operators.extend(filter());
+                operators.push(Operator::Nearest {
+                    near: near.clone(),
+                    k: self.candidates(),
+                });
+            }
+            (AccessPath::VectorFirst { strategy, fetch }, Some(near), Some(within)) => {
+                operators.push(Operator::VectorSearch {
+                    near: near.clone(),
+                    k: (*fetch).max(self.candidates()),
+                    filter: self.filter.clone(),
+                    strategy: *strategy,
+                });
+                operators.push(Operator::WithinNeighborhood(within.clone()));
+                operators.push(Operator::Limit {
+                    count: self.candidates(),
+                });
+            }
+            _ => {
+                return self.plan_with(&PlanChoice {
+                    prune_cold: choice.prune_cold,
+                    ..self.default_choice()
+                })
+            }
+        }
+
+        let rank = self.rank.or(self.near.as_ref().map(|_| Ranking::Distance));
+        if let (Some(slack), Some(Ranking::Distance)) = (choice.prune_cold, rank) {
+            operators.push(Operator::PruneCold {
+                keep: self.limit,
+                slack,
+            });
+        }
+        if let Some(expand) = &self.expand {
+            operators.push(Operator::Expand(expand.clone()));
+        }
+        let all_tiers = [MemoryTier::Hot, MemoryTier::Warm, MemoryTier::Cold];
+        if !self.tiers.is_empty() && !all_tiers.iter().all(|tier| self.tiers.contains(tier)) {
+            operators.push(Operator::TierFilter {
+                tiers: self.tiers.clone(),
+            });
+        }
+        if let Some(rank) = rank {
+            operators.push(Operator::Rank(rank));
+        }
+        operators.push(Operator::Limit { count: self.limit });
+
+        LogicalPlan {
+            collection: self.collection.clone(),
+            operators,
+        }
+    }
+}
+
+/// How a plan produces its seed rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "path", rename_all = "snake_case")]
+pub enum AccessPath {
+    /// Read every entity, then apply `WHERE`
+    Scan,
+
+    /// Answer `WHERE` from the secondary metadata indexes
+    IndexScan,
+
+    /// Walk the `WITHIN` neighbourhood, then apply `WHERE`
+    Neighborhood,
+
+    /// Nearest-neighbour search with `WHERE` applied by `strategy`
+    /// (chosen by the index from selectivity when unset)
+    VectorSearch {
+        /// Filter strategy
+        strategy: Option<FilterStrategy>,
+    },
+
+    /// Walk the `WITHIN` neighbourhood, then rank it exactly by distance
+    GraphFirst,
+
+    /// Over-fetch `fetch` nearest neighbours, then keep those inside the
+    /// `WITHIN` neighbourhood
+    VectorFirst {
+        /// Filter strategy
+        strategy: Option<FilterStrategy>,
+        /// Neighbours fetched before the neighbourhood check
+        fetch: usize,
+    },
+}
+
+impl fmt::Display for AccessPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccessPath::Scan => f.write_str("scan"),
+            AccessPath::IndexScan => f.write_str("index scan"),
+            AccessPath::Neighborhood => f.write_str("neighbourhood walk"),
+            AccessPath::VectorSearch { strategy } => write!(f, "vector search{}", strategy_text(strategy)),
+            AccessPath::GraphFirst => f.write_str("graph first"),
+            AccessPath::VectorFirst { strategy, fetch } => {
+                write!(f, "vector first{} fetching {}", strategy_text(strategy), fetch)
+            }
+        }
+    }
+}
+
+/// Physical choices a logical plan is lowered with
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlanChoice {
+    /// How seeds are produced
+    pub access: AccessPath,
+
+    /// Drop cold seeds when hot and warm seeds are within this relative
+    /// distance of the best answer
+    pub prune_cold: Option<f32>,
+}
+
+impl fmt::Display for PlanChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.access)?;
+        match self.prune_cold {
+            Some(slack) => write!(f, ", prune cold (slack {})", slack),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromStr for CognitiveQuery {
+    type Err = MemorySubstrateError;
+
+    fn from_str(text: &str) -> Result<Self> {
+        Self::parse(text)
+    }
+}
+
+/// One step of a logical plan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operator {
+    /// Nearest-neighbour search producing the seeds
+    VectorSearch {
+        /// Query vector source
+        near: Near,
+        /// Number of seeds
+        k: usize,
+        /// Predicate applied during the search
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        filter: Option<MetadataFilter>,
+        /// How the predicate meets the search; chosen by the index when unset
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        strategy: Option<FilterStrategy>,
+    },
+
+    /// Every entity of the collection as a seed
+    Scan,
+
+    /// Entities matching a predicate, looked up in the metadata indexes
+    IndexScan {
+        /// Predicate seeds must satisfy
+        filter: MetadataFilter,
+    },
+
+    /// Entities of a neighbourhood as seeds
+    Neighborhood(Neighborhood),
+
+    /// Drop rows outside a neighbourhood
+    WithinNeighborhood(Neighborhood),
+
+    /// Keep the rows closest to the query vector
+    Nearest {
+        /// Query vector source
+        near: Near,
+        /// Number of rows kept
+        k: usize,
+    },
+
+    /// Drop cold rows when enough hot and warm rows are nearly as close
+    PruneCold {
+        /// Rows the answer needs
+        keep: usize,
+        /// Allowed relative distance gap to the best `keep` rows
+        slack: f32,
+    },
+
+    /// Drop rows whose metadata does not match
+    Filter {
+        /// Predicate rows must satisfy
+        predicate: MetadataFilter,
+    },
+
+    /// Add entities reachable from the current rows
+    Expand(Expansion),
+
+    /// Drop rows outside the given tiers
+    TierFilter {
+        /// Allowed tiers
+        tiers: Vec<MemoryTier>,
+    },
+
+    /// Order the rows
+    Rank(Ranking),
+
+    /// Keep the first rows
+    Limit {
+        /// Maximum number of rows
+        count: usize,
+    },
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operator::VectorSearch {
+                near,
+                k,
+                filter,
+                strategy,
+            } => {
+                write!(f, "VectorSearch near {} k={}", near_text(near), k)?;
+                if let Some(filter) = filter {
+                    write!(f, " where {}", filter_text(filter))?;
+                }
+                f.write_str(strategy_text(strategy))
+            }
+            Operator::Scan => f.write_str("Scan"),
+            Operator::IndexScan { filter } => write!(f, "IndexScan {}", filter_text(filter)),
+            Operator::Neighborhood(neighborhood) => {
+                write!(f, "Neighborhood of {} {}", neighborhood.anchor, edges_text(&neighborhood.edges))
+            }
+            Operator::WithinNeighborhood(neighborhood) => {
+                write!(f, "WithinNeighborhood of {} {}", neighborhood.anchor, edges_text(&neighborhood.edges))
+            }
+            Operator::Nearest { near, k } => write!(f, "Nearest to {} k={}", near_text(near), k),
+            Operator::PruneCold { keep, slack } => write!(f, "PruneCold keep={} slack={}", keep, slack),
+            Operator::Filter { predicate } => write!(f, "Filter {}", filter_text(predicate)),
+            Operator::Expand(expansion) => write!(f, "Expand {}", edges_text(expansion)),
+            Operator::TierFilter { tiers } => {
+                let tiers: Vec<&str> = tiers.iter().map(|tier| tier_name(*tier)).collect();
+                write!(f, "TierFilter {}", tiers.join(", "))
+            }
+            Operator::Rank(Ranking::Distance) => f.write_str("Rank by distance"),
+            Operator::Rank(Ranking::PageRank) => f.write_str("Rank by pagerank"),
+            Operator::Limit { count } => write!(f, "Limit {}", count),
+        }
+    }
+}
+
+/// Operators of a query in execution order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogicalPlan {
+    /// Collection name or alias
+    pub collection: String,
+
+    /// Operators; the first one produces the rows
+    pub operators: Vec<Operator>,
+}
+
+impl fmt::Display for LogicalPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Plan for {:?}", self.collection)?;
+        for (i, operator) in self.operators.iter().enumerate() {
+            write!(f, "\n  {}. {}", i + 1, operator)?;
+        }
+        Ok(())
+    }
+}
+
+fn near_text(near: &Near) -> String {
+    match near {
+        Near::Vector(values) => format!("[{} dims]", values.len()),
+        Near::Entity(id) => format!("entity {}", id),
+    }
+}
+
+fn strategy_text(strategy: &Option<FilterStrategy>) -> &'static str {
+    match strategy {
+        Some(FilterStrategy::PreFilter) => " (pre-filter)",
+        Some(FilterStrategy::InGraph) => " (in-graph filter)",
+        Some(FilterStrategy::PostFilter) => " (post-filter)",
+        None => "",
+    }
+}
+
+fn edges_text(expansion: &Expansion) -> String {
+    let mut text = format!("{} hops", expansion.hops);
+    if !expansion.labels.is_empty() {
+        let labels: Vec<String> = expansion.labels.iter().map(|l| format!("{:?}", l)).collect();
+        text.push_str(&format!(" along {}", labels.join(", ")));
+    }
+    if expansion.min_probability > 0.0 {
+        text.push_str(&format!(" min probability {}", expansion.min_probability));
+    }
+    text
+}
+
+fn tier_name(tier: MemoryTier) -> &'static str {
+    match tier {
+        MemoryTier::Hot => "hot",
+        MemoryTier::Warm => "warm",
+        MemoryTier::Cold => "cold",
+    }
+}
+
+fn tier_slot(tier: MemoryTier) -> usize {
+    match tier {
+        MemoryTier::Hot => 0,
+        MemoryTier::Warm => 1,
+        MemoryTier::Cold => 2,
+    }
+}
+
+/// Render a filter in query syntax
+fn filter_text(filter: &MetadataFilter) -> String {
+    let grouped = |filter: &MetadataFilter| match filter {
+        MetadataFilter::And { filters } | MetadataFilter::Or { filters } if filters.len() > 1 => {
+            format!("({})", filter_text(filter))
+        }
+        MetadataFilter::Range {
+            lower: Some(_),
+            upper: Some(_),
+            ..
+        } => format!("({})", filter_text(filter)),
+        _ => filter_text(filter),
+    };
+
+    match filter {
+        MetadataFilter::Eq { field, value } => format!("{} = {}", field, value),
+        MetadataFilter::Ne { field, value } => format!("{} != {}", field, value),
+        MetadataFilter::Range { field, lower, upper } => {
+            let lower = lower
+                .as_ref()
+                .map(|b| format!("{} {} {}", field, if b.inclusive { ">=" } else { ">" }, b.value));
+            let upper = upper
+                .as_ref()
+                .map(|b| format!("{} {} {}", field, if b.inclusive { "<=" } else { "<" }, b.value));
+            lower.into_iter().chain(upper).collect::<Vec<_>>().join(" AND ")
+        }
+        MetadataFilter::In { field, values } => {
+            let values: Vec<String> = values.iter().map(Value::to_string).collect();
+            format!("{} IN ({})", field, values.join(", "))
+        }
+        MetadataFilter::Exists { field } => format!("{} EXISTS", field),
+        MetadataFilter::And { filters } if filters.is_empty() => "<all>".to_string(),
+        MetadataFilter::Or { filters } if filters.is_empty() => "<none>".to_string(),
+        MetadataFilter::And { filters } => {
+            let parts: Vec<String> = filters
+                .iter()
+                .map(|filter| match filter {
+                    MetadataFilter::Or { filters } if filters.len() > 1 => format!("({})", filter_text(filter)),
+                    _ => filter_text(filter),
+                })
+                .collect();
+            parts.join(" AND ")
+        }
+        MetadataFilter::Or { filters } => filters.iter().map(filter_text).collect::<Vec<_>>().join(" OR "),
+        MetadataFilter::Not { filter } => format!("NOT {}", grouped(filter)),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Lexer
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Keyword, name or field path
+    Word(String),
+    /// Double-quoted string with escapes resolved
+    Str(String),
+    /// Numeric literal as written
+    Number(String),
+    /// Punctuation or comparison operator
+    Symbol(&'static str),
+}
+
+#[derive(Debug, Clone)]
+struct Lexeme {
+    token: Token,
+    position: usize,
+}
+
+fn syntax(position: usize, message: impl Into<String>) -> MemorySubstrateError {
+    QueryError::Syntax {
+        position,
+        message: message.into(),
+    }
+    .into()
+}
+
+fn invalid(reason: impl Into<String>) -> MemorySubstrateError {
+    QueryError::Invalid { reason: reason.into() }.into()
+}
+
+fn is_keyword(word: &str) -> bool {
+    KEYWORDS.iter().any(|keyword| keyword.eq_ignore_ascii_case(word))
+}
+
+fn tokenize(text: &str) -> Result<Vec<Lexeme>> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let offset = |i: usize| chars.get(i).map_or(text.len(), |(position, _)| *position);
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (position, c) = chars[i];
+        let starts_number = c.is_ascii_digit()
+            || ((c == '-' || c == '+' || c == '.')
+                && chars.get(i + 1).is_some_and(|(_, next)| next.is_ascii_digit() || *next == '.'));
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '"' {
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(syntax(position, "unterminated string")),
+                    Some((_, '"')) => break,
+                    Some((_, '\\')) => {
+                        let escaped = match chars.get(i + 1) {
+                            Some((_, 'n')) => '\n',
+                            Some((_, 't')) => '\t',
+                            Some((_, c @ ('"' | '\\'))) => *c,
+                            _ => return Err(syntax(offset(i), "invalid escape")),
+                        };
+                        value.push(escaped);
+                        i += 2;
+                    }
+                    Some((_, c)) => {
+                        value.push(*c);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+            tokens.push(Lexeme {
+                token: Token::Str(value),
+                position,
+            });
+        } else if starts_number {
+            let start = i;
+            i += 1;
+            while let Some((_, c)) = chars.get(i) {
+                let exponent_sign = (*c == '-' || *c == '+') && matches!(chars[i - 1].1, 'e' | 'E');
+                if c.is_ascii_digit() || *c == '.' || *c == 'e' || *c == 'E' || exponent_sign {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            let literal = &text[position..offset(i)];
+            if literal.parse::<f64>().is_err() {
+                return Err(syntax(offset(start), format!("invalid number '{}'", literal)));
+            }
+            tokens.push(Lexeme {
+                token: Token::Number(literal.to_string()),
+                position,
+            });
+        } else if c.is_alphabetic() || c == '_' {
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    Some((_, c)) if c.is_alphanumeric() || matches!(c, '_' | '-' | '.') => i += 1,
+                    // Index and quoted-key segments of a field path, written
+                    // directly after the field name
+                    Some((_, '[')) if !is_keyword(&text[position..offset(i)]) => {
+                        let open = i;
+                        let mut quoted = false;
+                        i += 1;
+                        loop {
+                            match chars.get(i) {
+                                None => return Err(syntax(offset(open), "unterminated '['")),
+                                Some((_, '\\')) if quoted => i += 2,
+                                Some((_, '"')) => {
+                                    quoted = !quoted;
+                                    i += 1;
+                                }
+                                Some((_, ']')) if !quoted => break,
+                                Some(_) => i += 1,
+                            }
+                        }
+                        i += 1;
+                    }
+                    _ => break,
+                }
+            }
+            tokens.push(Lexeme {
+                token: Token::Word(text[position..offset(i)].to_string()),
+                position,
+            });
+        } else {
+            let next = chars.get(i + 1).map(|(_, c)| *c);
+            let (symbol, width) = match (c, next) {
+                ('!', Some('=')) => ("!=", 2),
+                ('<', Some('=')) => ("<=", 2),
+                ('>', Some('=')) => (">=", 2),
+                ('=', _) => ("=", 1),
+                ('<', _) => ("<", 1),
+                ('>', _) => (">", 1),
+                ('[', _) => ("[", 1),
+                (']', _) => ("]", 1),
+                ('(', _) => ("(", 1),
+                (')', _) => (")", 1),
+                (',', _) => (",", 1),
+                (';', _) => (";", 1),
+                _ => return Err(syntax(position, format!("unexpected character '{}'", c))),
+            };
+            tokens.push(Lexeme {
+                token: Token::Symbol(symbol),
+                position,
+            });
+            i += width;
+        }
+    }
+    Ok(tokens)
+}
+
+// ---------------------------------------------------------------------------
+// Parser
+// ---------------------------------------------------------------------------
+
+struct Parser {
+    tokens: Vec<Lexeme>,
+    pos: usize,
+    end: usize,
+    /// Open parentheses and `NOT`s around the current predicate
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|lexeme| &lexeme.token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |lexeme| lexeme.position)
+    }
+
+    fn error(&self, message: impl Into<String>) -> MemorySubstrateError {
+        let message = message.into();
+        match self.tokens.get(self.pos) {
+            Some(lexeme) => syntax(lexeme.position, format!("{}, found {}", message, describe(&lexeme.token))),
+            None => syntax(self.end, format!("{}, found end of query", message)),
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {}", keyword.to_uppercase())))
+        }
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<()> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", symbol)))
+        }
+    }
+
+    fn expect_string(&mut self, what: &str) -> Result<String> {
+        match self.peek() {
+            Some(Token::Str(value)) => {
+                let value = value.clone();
+                self.pos += 1;
+                Ok(value)
+            }
+            _ => Err(self.error(format!("expected {} as a quoted string", what))),
+        }
+    }
+
+    fn expect_number(&mut self, what: &str) -> Result<f64> {
+        match self.peek() {
+            Some(Token::Number(literal)) => {
+                // The lexer only emits literals that parse
+                let value = literal.parse().unwrap_or_default();
+                self.pos += 1;
+                Ok(value)
+            }
+            _ => Err(self.error(format!("expected {}", what))),
+        }
+    }
+
+    fn expect_count(&mut self, what: &str) -> Result<usize> {
+        match self.peek() {
+            Some(Token::Number(literal)) => match literal.parse::<usize>() {
+                Ok(value) => {
+                    self.pos += 1;
+                    Ok(value)
+                }
+                Err(_) => Err(self.error(format!("expected {} as a non-negative integer", what))),
+            },
+            _ => Err(self.error(format!("expected {}", what))),
+        }
+    }
+
+    fn query(mut self) -> Result<CognitiveQuery> {
+        let explain = self.eat_keyword("explain");
+        self.expect_keyword("find")?;
+        self.expect_keyword("in")?;
+        let collection = match self.peek() {
+            Some(Token::Word(name)) if !is_keyword(name) => name.clone(),
+            Some(Token::Str(name)) => name.clone(),
+            _ => return Err(self.error("expected collection name")),
+        };
+        self.pos += 1;
+
+        let mut query = CognitiveQuery {
+            explain,
+            collection,
+            near: None,
+            k: None,
+            filter: None,
+            within: None,
+            expand: None,
+            rank: None,
+            tiers: Vec::new(),
+            limit: DEFAULT_LIMIT,
+        };
+        let mut seen: HashSet<String> = HashSet::new();
+
+        while self.pos < self.tokens.len() {
+            if self.eat_symbol(";") {
+                if self.pos < self.tokens.len() {
+                    return Err(self.error("expected end of query after ';'"));
+                }
+                break;
+            }
+            let clause = match self.peek() {
+                Some(Token::Word(word)) => word.to_lowercase(),
+                _ => String::new(),
+            };
+            if !matches!(
+                clause.as_str(),
+                "near" | "where" | "within" | "expand" | "rank" | "tiers" | "limit"
+            ) {
+                return Err(self.error("expected NEAR, WHERE, WITHIN, EXPAND, RANK, TIERS or LIMIT"));
+            }
+            if !seen.insert(clause.clone()) {
+                return Err(syntax(self.position(), format!("duplicate {} clause", clause.to_uppercase())));
+            }
+            self.pos += 1;
+
+            match clause.as_str() {
+                "near" => {
+                    query.near = Some(self.near()?);
+                    if self.eat_keyword("k") {
+                        query.k = Some(self.expect_count("K")?);
+                    }
+                }
+                "where" => query.filter = Some(self.predicate()?),
+                "within" => {
+                    let hops = self.hops()?;
+                    self.expect_keyword("of")?;
+                    self.expect_keyword("entity")?;
+                    let anchor = self.entity_id()?;
+                    query.within = Some(Neighborhood {
+                        anchor,
+                        edges: self.edges(hops)?,
+                    });
+                }
+                "expand" => {
+                    let hops = self.hops()?;
+                    query.expand = Some(self.edges(hops)?);
+                }
+                "rank" => {
+                    self.expect_keyword("by")?;
+                    query.rank = Some(if self.eat_keyword("distance") {
+                        Ranking::Distance
+                    } else if self.eat_keyword("pagerank") {
+                        Ranking::PageRank
+                    } else {
+                        return Err(self.error("expected DISTANCE or PAGERANK"));
+                    });
+                }
+                "tiers" => loop {
+                    let tier = match self.peek() {
+                        Some(Token::Word(word)) => match word.to_lowercase().as_str() {
+                            "hot" => MemoryTier::Hot,
+                            "warm" => MemoryTier::Warm,
+                            "cold" => MemoryTier::Cold,
+                            _ => return Err(self.error("expected hot, warm or cold")),
+                        },
+                        _ => return Err(self.error("expected hot, warm or cold")),
+                    };
+                    self.pos += 1;
+                    if !query.tiers.contains(&tier) {
+                        query.tiers.push(tier);
+                    }
+                    if !self.eat_symbol(",") {
+                        break;
+                    }
+                },
+                _ => query.limit = self.expect_count("LIMIT")?,
+            }
+        }
+
+        validate(&query)?;
+        Ok(query)
+    }
+
+    fn near(&mut self) -> Result<Near> {
+        if self.eat_keyword("entity") {
+            return Ok(Near::Entity(self.entity_id()?));
+        }
+
+        self.expect_symbol("[")?;
+        let mut values = Vec::new();
+        if !self.eat_symbol("]") {
+            loop {
+                values.push(self.expect_number("vector component")? as f32);
+                if self.eat_symbol("]") {
+                    break;
+                }
+                self.expect_symbol(",")?;
+            }
+        }
+        Ok(Near::Vector(values))
+    }
+
+    fn entity_id(&mut self) -> Result<EntityId> {
+        let position = self.position();
+        let id = self.expect_string("entity id")?;
+        uuid::Uuid::parse_str(&id)
+            .map(EntityId::from_uuid)
+            .map_err(|error| syntax(position, format!("invalid entity id '{}': {}", id, error)))
+    }
+
+    fn hops(&mut self) -> Result<usize> {
+        let hops = self.expect_count("hop count")?;
+        if !self.eat_keyword("hops") {
+            self.expect_keyword("hop")?;
+        }
+        Ok(hops)
+    }
+
+    fn edges(&mut self, hops: usize) -> Result<Expansion> {
+        let mut labels = Vec::new();
+        if self.eat_keyword("along") {
+            loop {
+                labels.push(self.expect_string("edge label")?);
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+        }
+        let mut min_probability = 0.0;
+        if self.eat_keyword("min") {
+            self.expect_keyword("probability")?;
+            min_probability = self.expect_number("probability")? as f32;
+        }
+        Ok(Expansion {
+            hops,
+            labels,
+            min_probability,
+        })
+    }
+
+    fn predicate(&mut self) -> Result<MetadataFilter> {
+        let mut filter = self.conjunct()?;
+        while self.eat_keyword("or") {
+            filter = filter.or(self.conjunct()?);
+        }
+        Ok(filter)
+    }
+
+    fn conjunct(&mut self) -> Result<MetadataFilter> {
+        let mut filter = self.negation()?;
+        while self.eat_keyword("and") {
+            filter = filter.and(self.negation()?);
+        }
+        Ok(filter)
+    }
+
+    /// Parse a nested predicate, refusing nesting that would exhaust the stack
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth == MAX_FILTER_NESTING {
+            return Err(syntax(
+                self.position(),
+                format!("predicate nests deeper than {} levels", MAX_FILTER_NESTING),
+            ));
+        }
+        self.depth += 1;
+        let parsed = parse(self);
+        self.depth -= 1;
+        parsed
+    }
+
+    fn negation(&mut self) -> Result<MetadataFilter> {
+        if self.eat_keyword("not") {
+            return Ok(!self.nested(Self::negation)?);
+        }
+        if self.eat_symbol("(") {
+            let filter = self.nested(Self::predicate)?;
+            self.expect_symbol(")")?;
+            return Ok(filter);
+        }
+
+        let position = self.position();
+        let field = match self.peek() {
+            Some(Token::Word(word)) if !is_keyword(word) => word.clone(),
+            _ => return Err(self.error("expected field name")),
+        };
+        let field = FieldPath::parse(&field).map_err(|error| syntax(position, error.to_string()))?;
+        self.pos += 1;
+
+        if self.eat_keyword("exists") {
+            return Ok(MetadataFilter::exists(field));
+        }
+        if self.eat_keyword("in") {
+            self.expect_symbol("(")?;
+            let mut values = Vec::new();
+            loop {
+                values.push(self.value()?);
+                if self.eat_symbol(")") {
+                    break;
+                }
+                self.expect_symbol(",")?;
+            }
+            return Ok(MetadataFilter::in_values(field, values));
+        }
+
+        let op = match self.peek() {
+            Some(Token::Symbol(op @ ("=" | "!=" | "<" | "<=" | ">" | ">="))) => *op,
+            _ => return Err(self.error("expected comparison, IN or EXISTS")),
+        };
+        self.pos += 1;
+        let value = self.value()?;
+        Ok(match op {
+            "=" => MetadataFilter::eq(field, value),
+            "!=" => MetadataFilter::ne(field, value),
+            "<" => MetadataFilter::lt(field, value),
+            "<=" => MetadataFilter::lte(field, value),
+            ">" => MetadataFilter::gt(field, value),
+            _ => MetadataFilter::gte(field, value),
+        })
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        let value = match self.peek() {
+            Some(Token::Str(value)) => Value::from(value.clone()),
+            Some(Token::Number(literal)) => match literal.parse::<i64>() {
+                Ok(integer) => Value::from(integer),
+                Err(_) => Value::from(literal.parse::<f64>().unwrap_or_default()),
+            },
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("true") => Value::Bool(true),
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("false") => Value::Bool(false),
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("null") => Value::Null,
+            _ => return Err(self.error("expected value")),
+        };
+        self.pos += 1;
+        Ok(value)
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(word) => format!("'{}'", word),
+        Token::Str(value) => format!("string {:?}", value),
+        Token::Number(literal) => format!("number {}", literal),
+        Token::Symbol(symbol) => format!("'{}'", symbol),
+    }
+}
+
+fn validate(query: &CognitiveQuery) -> Result<()> {
+    if query.limit == 0 {
+        return Err(invalid("LIMIT must be positive"));
+    }
+    if query.k == Some(0) {
+        return Err(invalid("K must be positive"));
+    }
+    if matches!(&query.near, Some(Near::Vector(values)) if values.is_empty()) {
+        return Err(invalid("NEAR vector must not be empty"));
+    }
+    let walks = [
+        ("WITHIN", query.within.as_ref().map(|within| &within.edges)),
+        ("EXPAND", query.expand.as_ref()),
+    ];
+    for (clause, edges) in walks {
+        let Some(edges) = edges else { continue };
+        if edges.hops == 0 || edges.hops > MAX_EXPAND_HOPS {
+            return Err(invalid(format!("{} must be between 1 and {} hops", clause, MAX_EXPAND_HOPS)));
+        }
+        if !(0.0..=1.0).contains(&edges.min_probability) {
+            return Err(invalid("MIN PROBABILITY must be within [0, 1]"));
+        }
+    }
+    if query.rank == Some(Ranking::Distance) && query.near.is_none() {
+        return Err(invalid("RANK BY DISTANCE requires NEAR"));
+    }
+    if let Some(filter) = &query.filter {
+        filter
+            .validate()
+            .map_err(|error| invalid(format!("invalid WHERE clause: {}", error)))?;
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Cost model
+// ---------------------------------------------------------------------------
+
+/// Dimensions whose distance computation makes one latency unit
+const DISTANCE_UNIT_DIMENSIONS: f64 = 128.0;
+
+/// Latency of reading one entity during a scan, in latency units
+const SCAN_COST: f64 = 0.01;
+
+/// Latency of evaluating a predicate on one entity, in latency units
+const PREDICATE_COST: f64 = 0.05;
+
+/// Latency of following one edge, in latency units
+const EDGE_COST: f64 = 0.02;
+
+/// Latency of testing one entity against a metadata index bitmap
+const BITMAP_COST: f64 = 0.001;
+
+/// Per-entity bytes besides the vector
+const ENTITY_OVERHEAD_BYTES: f64 = 256.0;
+
+/// Lowest selectivity used when estimating filtered search breadth
+const MIN_SELECTIVITY: f64 = 0.01;
+
+/// Buckets of the edge probability histogram
+const PROBABILITY_BUCKETS: usize = 10;
+
+/// Collection statistics the cost model works from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanStatistics {
+    /// Number of entities
+    pub entities: usize,
+
+    /// Vector dimension
+    pub dimension: usize,
+
+    /// Default search breadth of the vector index
+    pub ef_search: usize,
+
+    /// Number of edges between stored entities
+    pub edges: usize,
+
+    /// Entities per tier, indexed hot, warm, cold
+    pub tiers: [usize; 3],
+
+    /// Edges per label
+    pub edge_labels: HashMap<String, usize>,
+
+    /// Edges per learned-probability decile
+    pub probability_histogram: [usize; PROBABILITY_BUCKETS],
+}
+
+impl PlanStatistics {
+    /// Gather statistics with one pass over the collection
+    pub fn collect(collection: &Collection) -> Self {
+        let mut stats = Self {
+            entities: collection.len(),
+            dimension: collection.config().dimension,
+            ef_search: collection.config().index.ef_search,
+            edges: 0,
+            tiers: [0; 3],
+            edge_labels: HashMap::new(),
+            probability_histogram: [0; PROBABILITY_BUCKETS],
+        };
+        for entity in collection.entities() {
+            stats.tiers[tier_slot(entity.tier)] += 1;
+            for edge in entity.edges.iter().flatten() {
+                if !collection.contains(&edge.target_id) {
+                    continue;
+                }
+                stats.edges += 1;
+                *stats.edge_labels.entry(edge.label.clone()).or_default() += 1;
+                let bucket = (edge.probability().clamp(0.0, 1.0) * PROBABILITY_BUCKETS as f32) as usize;
+                stats.probability_histogram[bucket.min(PROBABILITY_BUCKETS - 1)] += 1;
+            }
+        }
+        stats
+    }
+
+    /// Mean number of edges leaving an entity
+    pub fn average_out_degree(&self) -> f64 {
+        if self.entities == 0 {
+            0.0
+        } else {
+            self.edges as f64 / self.entities as f64
+        }
+    }
+
+    /// Fraction of entities in each tier, indexed hot, warm, cold
+    pub fn tier_fractions(&self) -> [f64; 3] {
+        if self.entities == 0 {
+            // Nothing stored yet: new entities start hot
+            return [1.0, 0.0, 0.0];
+        }
+        self.tiers.map(|count| count as f64 / self.entities as f64)
+    }
+
+    /// Estimated fraction of edges an expansion follows
+    ///
+    /// Label and probability restrictions are assumed independent, and
+    /// probabilities uniform within a decile.
+    pub fn follow_fraction(&self, expansion: &Expansion) -> f64 {
+        if self.edges == 0 {
+            return 0.0;
+        }
+        let labels = if expansion.labels.is_empty() {
+            1.0
+        } else {
+            let matching: usize = expansion
+                .labels
+                .iter()
+                .filter_map(|label| self.edge_labels.get(label))
+                .sum();
+            matching as f64 / self.edges as f64
+        };
+
+        let threshold = expansion.min_probability as f64 * PROBABILITY_BUCKETS as f64;
+        let passing: f64 = self
+            .probability_histogram
+            .iter()
+            .enumerate()
+            .map(|(bucket, count)| {
+                let share = (bucket as f64 + 1.0 - threshold).clamp(0.0, 1.0);
+                share * *count as f64
+            })
+            .sum();
+        labels * passing / self.edges as f64
+    }
+    /// Estimated entities reached and edges followed walking `edges` from
+    /// `seeds` entities, seeds included
+    pub fn walk(&self, seeds: f64, edges: &Expansion) -> (f64, f64) {
+        let n = self.entities as f64;
+        let degree = self.average_out_degree();
+        let branching = degree * self.follow_fraction(edges);
+        let mut frontier = seeds;
+        let mut reached = seeds;
+        let mut followed = 0.0;
+        for _ in 0..edges.hops {
+            followed += frontier * degree;
+            frontier *= branching;
+            reached = (reached + frontier).min(n);
+        }
+        (reached, followed)
+    }
+}
+
+/// Estimated resource use, in the units `CostWeights` applies to
+///
+/// - `latency`: distance computations over 128 dimensions, or equivalent work
+/// - `memory`: KiB of entity data touched
+/// - `io`: cold-tier fetches; hot and warm reads count by their latency
+///   relative to cold
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostEstimate {
+    /// CPU work
+    pub latency: f64,
+
+    /// Memory traffic
+    pub memory: f64,
+
+    /// Storage reads
+    pub io: f64,
+}
+
+impl CostEstimate {
+    /// Collapse to a single cost
+    pub fn weighted(&self, weights: &CostWeights) -> f64 {
+        self.latency * weights.latency + self.memory * weights.memory + self.io * weights.io
+    }
+}
+
+impl Add for CostEstimate {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            latency: self.latency + other.latency,
+            memory: self.memory + other.memory,
+            io: self.io + other.io,
+        }
+    }
+}
+
+/// Estimate for one plan operator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepEstimate {
+    /// Operator
+    pub operator: Operator,
+
+    /// Rows entering the operator
+    pub rows_in: f64,
+
+    /// Rows leaving the operator
+    pub rows_out: f64,
+
+    /// Resources the operator uses
+    pub cost: CostEstimate,
+
+    /// `cost` under the model's weights
+    pub weighted_cost: f64,
+}
+
+/// Plan annotated with estimated costs, as returned by `EXPLAIN`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Explain {
+    /// Collection name or alias
+    pub collection: String,
+
+    /// Statistics the estimate is based on
+    pub statistics: PlanStatistics,
+
+    /// Per-operator estimates in execution order
+    pub steps: Vec<StepEstimate>,
+
+    /// Sum of the operator costs
+    pub total: CostEstimate,
+
+    /// `total` under `weights`
+    pub weighted_cost: f64,
+
+    /// Weights the costs were combined with
+    pub weights: CostWeights,
+
+    /// Plans the planner weighed, cheapest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternatives: Vec<Alternative>,
+
+    /// Whether the plan came from the plan cache
+    #[serde(default)]
+    pub cached: bool,
+}
+
+/// A candidate plan the planner costed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alternative {
+    /// Physical choices of the candidate
+    pub choice: PlanChoice,
+
+    /// Estimated cost under the model's weights
+    pub weighted_cost: f64,
+
+    /// Whether this candidate was executed
+    pub chosen: bool,
+}
+
+impl fmt::Display for Explain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Plan for {:?} ({} entities; weights latency {}, memory {}, io {})",
+            self.collection, self.statistics.entities, self.weights.latency, self.weights.memory, self.weights.io
+        )?;
+        for (i, step) in self.steps.iter().enumerate() {
+            write!(
+                f,
+                "\n  {}. {}\n     rows {:.1} -> {:.1}, latency {:.2}, memory {:.2}, io {:.2}, cost {:.2}",
+                i + 1,
+                step.operator,
+                step.rows_in,
+                step.rows_out,
+                step.cost.latency,
+                step.cost.memory,
+                step.cost.io,
+                step.weighted_cost
+            )?;
+        }
+        write!(
+            f,
+            "\nTotal: latency {:.2}, memory {:.2}, io {:.2}, cost {:.2}",
+            self.total.latency, self.total.memory, self.total.io, self.weighted_cost
+        )?;
+        if self.cached {
+            f.write_str(" (cached plan)")?;
+        }
+        if !self.alternatives.is_empty() {
+            f.write_str("\nAlternatives:")?;
+            for alternative in &self.alternatives {
+                let marker = if alternative.chosen { "*" } else { "-" };
+                write!(f, "\n  {} {}: cost {:.2}", marker, alternative.choice, alternative.weighted_cost)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Estimates plan costs and combines them with `BellmanConfig::cost_weights`
+#[derive(Debug, Clone)]
+pub struct CostModel {
+    weights: CostWeights,
+}
+
+impl CostModel {
+    /// Create a model combining costs with `weights`
+    pub fn new(weights: CostWeights) -> Self {
+        Self { weights }
+    }
+
+    /// Weights costs are combined with
+    pub fn weights(&self) -> &CostWeights {
+        &self.weights
+    }
+
+    /// Estimate a plan against a collection's current contents
+    pub fn explain(&self, plan: &LogicalPlan, collection: &Collection) -> Explain {
+        let statistics = PlanStatistics::collect(collection);
+        let index = collection.index();
+        let metadata = index.metadata();
+        self.estimate(plan, statistics, |filter| metadata.estimate_selectivity(filter))
+    }
+
+    /// Estimate a plan from statistics and a filter selectivity estimator
+    pub fn estimate(
+        &self,
+        plan: &LogicalPlan,
+        statistics: PlanStatistics,
+        selectivity: impl Fn(&MetadataFilter) -> f64,
+    ) -> Explain {
+        let n = statistics.entities as f64;
+        let dimension = statistics.dimension as f64;
+        let distance_cost = dimension / DISTANCE_UNIT_DIMENSIONS;
+        let entity_kib = (dimension * 4.0 + ENTITY_OVERHEAD_BYTES) / 1024.0;
+        let degree = statistics.average_out_degree();
+        let search_depth = 1.0 + (n + 1.0).log2();
+        let filters = FilterConfig::default();
+        let cold = MemoryTier::Cold.latency_characteristics().as_secs_f64();
+        let tier_io = [MemoryTier::Hot, MemoryTier::Warm, MemoryTier::Cold]
+            .map(|tier| tier.latency_characteristics().as_secs_f64() / cold);
+
+        let walk = |seeds: f64, edges: &Expansion| statistics.walk(seeds, edges);
+
+        // Vectors and graph adjacency are index-resident; storage is read
+        // when rows need their stored vector or are returned. `mix` is the
+        // tier mix of the current rows, `undistanced` the rows without a
+        // distance to the query vector yet.
+        let mut mix = statistics.tier_fractions();
+        let io_per_entity = |mix: &[f64; 3]| mix.iter().zip(tier_io).map(|(share, io)| share * io).sum::<f64>();
+        let mut rows = 0.0_f64;
+        let mut undistanced = 0.0_f64;
+        let mut steps = Vec::with_capacity(plan.operators.len());
+
+        for operator in &plan.operators {
+            let rows_in = rows;
+            let mut cost = CostEstimate::default();
+            match operator {
+                Operator::VectorSearch {
+                    k, filter, strategy, ..
+                } => {
+                    let selectivity = filter.as_ref().map_or(1.0, |f| selectivity(f).clamp(0.0, 1.0));
+                    let breadth = (*k).max(statistics.ef_search) as f64;
+                    let graph_walk = (breadth * search_depth).min(n);
+                    let visited = match filter {
+                        None => {
+                            cost.latency = graph_walk * distance_cost;
+                            graph_walk
+                        }
+                        Some(_) => match strategy.unwrap_or_else(|| filters.choose(selectivity, statistics.entities)) {
+                            FilterStrategy::PreFilter => {
+                                let matching = n * selectivity;
+                                cost.latency = n * BITMAP_COST + matching * distance_cost;
+                                matching
+                            }
+                            FilterStrategy::InGraph => {
+                                let visited = (graph_walk / selectivity.max(MIN_SELECTIVITY)).min(n);
+                                cost.latency = visited * (distance_cost + PREDICATE_COST);
+                                visited
+                            }
+                            FilterStrategy::PostFilter => {
+                                let fetch = *k as f64 * filters.post_filter_overfetch / selectivity.max(MIN_SELECTIVITY);
+                                let visited = (fetch.max(statistics.ef_search as f64) * search_depth).min(n);
+                                cost.latency = visited * distance_cost + fetch.min(n) * PREDICATE_COST;
+                                visited
+                            }
+                        },
+                    };
+                    rows = (*k as f64).min(n * selectivity);
+                    undistanced = 0.0;
+                    cost.memory = visited * entity_kib;
+                }
+                Operator::Scan => {
+                    rows = n;
+                    undistanced = rows;
+                    cost.latency = n * SCAN_COST;
+                    cost.memory = n * entity_kib;
+                }
+                Operator::IndexScan { filter } => {
+                    rows = n * selectivity(filter).clamp(0.0, 1.0);
+                    undistanced = rows;
+                    cost.latency = n * BITMAP_COST;
+                    cost.memory = rows * entity_kib;
+                }
+                Operator::Neighborhood(neighborhood) => {
+                    let (reached, followed) = walk(1.0_f64.min(n), &neighborhood.edges);
+                    rows = reached;
+                    undistanced = rows;
+                    cost.latency = followed * EDGE_COST;
+                    cost.memory = reached * entity_kib;
+                }
+                Operator::WithinNeighborhood(neighborhood) => {
+                    let (reached, followed) = walk(1.0_f64.min(n), &neighborhood.edges);
+                    let inside = if n > 0.0 { reached / n } else { 0.0 };
+                    cost.latency = followed * EDGE_COST + rows * PREDICATE_COST;
+                    rows *= inside;
+                    undistanced *= inside;
+                }
+                Operator::Nearest { k, .. } => {
+                    cost.latency = rows * distance_cost;
+                    cost.io = rows * io_per_entity(&mix);
+                    rows = rows.min(*k as f64);
+                    undistanced = 0.0;
+                }
+                Operator::PruneCold { keep, .. } => {
+                    cost.latency = rows * PREDICATE_COST;
+                    let warm_or_hot = 1.0 - mix[2];
+                    if rows * warm_or_hot >= *keep as f64 && warm_or_hot > 0.0 {
+                        rows *= warm_or_hot;
+                        undistanced *= warm_or_hot;
+                        mix = [mix[0] / warm_or_hot, mix[1] / warm_or_hot, 0.0];
+                    }
+                }
+                Operator::Filter { predicate } => {
+                    let selectivity = selectivity(predicate).clamp(0.0, 1.0);
+                    cost.latency = rows * PREDICATE_COST;
+                    rows *= selectivity;
+                    undistanced *= selectivity;
+                }
+                Operator::Expand(expansion) => {
+                    let (reached, followed) = walk(rows, expansion);
+                    let added = (reached - rows).max(0.0);
+                    rows = reached;
+                    undistanced += added;
+                    cost.latency = followed * EDGE_COST;
+                    cost.memory = added * entity_kib;
+                }
+                Operator::TierFilter { tiers } => {
+                    let kept: f64 = tiers.iter().map(|tier| mix[tier_slot(*tier)]).sum();
+                    cost.latency = rows * PREDICATE_COST;
+                    rows *= kept;
+                    undistanced *= kept;
+                    for (slot, share) in mix.iter_mut().enumerate() {
+                        let allowed = tiers.iter().any(|tier| tier_slot(*tier) == slot);
+                        *share = if allowed && kept > 0.0 { *share / kept } else { 0.0 };
+                    }
+                }
+                Operator::Rank(Ranking::Distance) => {
+                    cost.latency = undistanced * distance_cost;
+                    cost.io = undistanced * io_per_entity(&mix);
+                    undistanced = 0.0;
+                }
+                Operator::Rank(Ranking::PageRank) => {
+                    cost.latency = PAGERANK_ITERATIONS as f64 * rows * (1.0 + degree) * EDGE_COST;
+                }
+                Operator::Limit { count } => {
+                    rows = rows.min(*count as f64);
+                    undistanced = undistanced.min(rows);
+                    cost.io = rows * io_per_entity(&mix);
+                }
+            }
+            steps.push(StepEstimate {
+                operator: operator.clone(),
+                rows_in,
+                rows_out: rows,
+                cost,
+                weighted_cost: cost.weighted(&self.weights),
+            });
+        }
+
+        let total = steps.iter().fold(CostEstimate::default(), |total, step| total + step.cost);
+        Explain {
+            collection: plan.collection.clone(),
+            statistics,
+            steps,
+            total,
+            weighted_cost: total.weighted(&self.weights),
+            weights: self.weights.clone(),
+            alternatives: Vec::new(),
+            cached: false,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Execution
+// ---------------------------------------------------------------------------
+
+/// One query result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryRow {
+    /// Entity
+    pub id: EntityId,
+
+    /// Hops from the nearest seed (0 for seeds)
+    pub depth: usize,
+
+    /// Distance to the query vector, when the query has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distance: Option<f32>,
+
+    /// PageRank within the candidates, when ranked by PageRank
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pagerank: Option<f32>,
+}
+
+/// Run a plan against a collection
+///
+/// # Errors
+/// * `Collection(EntityNotFound)` if `NEAR ENTITY` or `WITHIN` names a
+///   missing entity
+/// * `DimensionMismatch` if the query vector does not fit the collection
+/// * `Query(Invalid)` if the plan ranks by distance without a query vector
+pub fn execute(plan: &LogicalPlan, view: &CollectionView) -> Result<Vec<QueryRow>> {
+    let mut query_vector: Option<Vector> = None;
+    let mut rows: Vec<QueryRow> = Vec::new();
+    let row = |id: EntityId, depth: usize, distance: Option<f32>| QueryRow {
+        id,
+        depth,
+        distance,
+        pagerank: None,
+    };
+
+    for operator in &plan.operators {
+        match operator {
+            Operator::VectorSearch {
+                near,
+                k,
+                filter,
+                strategy,
+            } => {
+                let (vector, anchor) = resolve_near(near, view)?;
+                let mut search = VectorQuery::new(vector.clone(), k + usize::from(anchor.is_some()));
+                if let Some(filter) = filter {
+                    search = search.with_filter(filter.clone());
+                }
+                if let Some(strategy) = strategy {
+                    search = search.with_strategy(*strategy);
+                }
+                rows = view
+                    .search(&search)?
+                    .0
+                    .hits
+                    .into_iter()
+                    .filter(|hit| Some(hit.id) != anchor)
+                    .take(*k)
+                    .map(|hit| row(hit.id, 0, Some(hit.distance)))
+                    .collect();
+                query_vector = Some(vector);
+            }
+            Operator::Scan => {
+                let mut ids: Vec<EntityId> = view.entities().iter().map(|entity| entity.id).collect();
+                ids.sort();
+                rows = ids.into_iter().map(|id| row(id, 0, None)).collect();
+            }
+            Operator::IndexScan { filter } => {
+                let mut ids = view.collection().index().metadata().matching_ids(filter)?;
+                ids.sort();
+                rows = ids.into_iter().map(|id| row(id, 0, None)).collect();
+            }
+            Operator::Neighborhood(neighborhood) => {
+                let mut ids: Vec<EntityId> = neighborhood_of(neighborhood, view)?.into_iter().collect();
+                ids.sort();
+                rows = ids.into_iter().map(|id| row(id, 0, None)).collect();
+            }
+            Operator::WithinNeighborhood(neighborhood) => {
+                let inside = neighborhood_of(neighborhood, view)?;
+                rows.retain(|r| inside.contains(&r.id));
+            }
+            Operator::Nearest { near, k } => {
+                let (vector, anchor) = resolve_near(near, view)?;
+                let metric = view.collection().config().metric;
+                rows.retain(|r| Some(r.id) != anchor);
+                for r in rows.iter_mut().filter(|r| r.distance.is_none()) {
+                    r.distance = view
+                        .get(&r.id)
+                        .and_then(|entity| entity.vector.as_ref().map(|v| metric.distance(&vector, v)));
+                }
+                sort_by_distance(&mut rows);
+                rows.truncate(*k);
+                query_vector = Some(vector);
+            }
+            Operator::PruneCold { keep, slack } => {
+                let tier = |r: &QueryRow| view.get(&r.id).map(|entity| entity.tier);
+                let kth = |rows: &mut dyn Iterator<Item = &QueryRow>| {
+                    rows.filter_map(|r| r.distance).nth(keep.saturating_sub(1))
+                };
+                let best = kth(&mut rows.iter());
+                let warm = kth(&mut rows.iter().filter(|r| tier(r) != Some(MemoryTier::Cold)));
+                if let (Some(best), Some(warm)) = (best, warm) {
+                    if warm - best <= slack * best.abs() {
+                        rows.retain(|r| tier(r) != Some(MemoryTier::Cold));
+                    }
+                }
+            }
+            Operator::Filter { predicate } => {
+                rows.retain(|r| {
+                    view
+                        .get(&r.id)
+                        .is_some_and(|entity| predicate.matches(entity.metadata.as_ref()))
+                });
+            }
+            Operator::Expand(expansion) => {
+                let mut known: HashSet<EntityId> = rows.iter().map(|r| r.id).collect();
+                let traversal = GraphQuery::new(rows.iter().map(|r| r.id).collect(), expansion.hops)
+                    .with_labels(expansion.labels.clone())
+                    .with_min_probability(expansion.min_probability)
+                    .with_limit(view.len());
+                for step in view.traverse(&traversal).0 {
+                    if known.insert(step.id) {
+                        rows.push(row(step.id, step.depth, None));
+                    }
+                }
+            }
+            Operator::TierFilter { tiers } => {
+                rows.retain(|r| view.get(&r.id).is_some_and(|entity| tiers.contains(&entity.tier)));
+            }
+            Operator::Rank(Ranking::Distance) => {
+                let vector = query_vector
+                    .as_ref()
+                    .ok_or_else(|| invalid("RANK BY DISTANCE requires NEAR"))?;
+                let metric = view.collection().config().metric;
+                for r in rows.iter_mut().filter(|r| r.distance.is_none()) {
+                    r.distance = view
+                        .get(&r.id)
+                        .and_then(|entity| entity.vector.as_ref().map(|v| metric.distance(vector, v)));
+                }
+                sort_by_distance(&mut rows);
+            }
+            Operator::Rank(Ranking::PageRank) => {
+                let ids: Vec<EntityId> = rows.iter().map(|r| r.id).collect();
+                for (r, score) in rows.iter_mut().zip(pagerank(view, &ids)) {
+                    r.pagerank = Some(score as f32);
+                }
+                rows.sort_by(|a, b| {
+                    let a = a.pagerank.unwrap_or_default();
+                    let b = b.pagerank.unwrap_or_default();
+                    b.total_cmp(&a)
+                });
+            }
+            Operator::Limit { count } => rows.truncate(*count),
+        }
+    }
+    Ok(rows)
+}
+
+/// Query vector of a `NEAR` clause, with the anchor entity if any
+fn resolve_near(near: &Near, view: &CollectionView) -> Result<(Vector, Option<EntityId>)> {
+    let (vector, anchor) = match near {
+        Near::Vector(values) => (Vector::new(values.clone()), None),
+        Near::Entity(id) => {
+            let entity = view.get(id).ok_or_else(|| not_found(id, view))?;
+            let vector = entity
+                .vector
+                .clone()
+                .ok_or_else(|| invalid(format!("entity {} has no vector", id)))?;
+            (vector, Some(*id))
+        }
+    };
+    if vector.values.len() != view.collection().config().dimension {
+        return Err(MemorySubstrateError::DimensionMismatch {
+            expected: view.collection().config().dimension,
+            actual: vector.values.len(),
+        });
+    }
+    Ok((vector, anchor))
+}
+
+/// Entities of a `WITHIN` neighbourhood, anchor included
+fn neighborhood_of(neighborhood: &Neighborhood, view: &CollectionView) -> Result<HashSet<EntityId>> {
+    if !view.contains(&neighborhood.anchor) {
+        return Err(not_found(&neighborhood.anchor, view));
+    }
+    let edges = &neighborhood.edges;
+    let traversal = GraphQuery::new(vec![neighborhood.anchor], edges.hops)
+        .with_labels(edges.labels.clone())
+        .with_min_probability(edges.min_probability)
+        .with_limit(view.len());
+    Ok(view.traverse(&traversal).0.into_iter().map(|step| step.id).collect())
+}
+
+fn not_found(id: &EntityId, view: &CollectionView) -> MemorySubstrateError {
+    CollectionError::EntityNotFound {
+        collection: view.collection().name().to_string(),
+        entity_id: id.to_string(),
+    }
+    .into()
+}
+
+/// Order rows by distance, rows without one last
+fn sort_by_distance(rows: &mut [QueryRow]) {
+    rows.sort_by(|a, b| {
+        let a = a.distance.unwrap_or(f32::INFINITY);
+        let b = b.distance.unwrap_or(f32::INFINITY);
+        a.total_cmp(&b)
+    });
+}
+
+/// PageRank of `ids` over the edges between them
+///
+/// Edges are weighted by learned probability. Rank of entities without
+/// outgoing weight is spread evenly, so scores always sum to 1.
+fn pagerank(view: &CollectionView, ids: &[EntityId]) -> Vec<f64> {
+    let n = ids.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    let slots: HashMap<EntityId, usize> = ids.iter().enumerate().map(|(slot, id)| (*id, slot)).collect();
+    let links: Vec<Vec<(usize, f64)>> = ids
+        .iter()
+        .map(|id| {
+            let Some(entity) = view.get(id) else {
+                return Vec::new();
+            };
+            entity
+                .edges
+                .iter()
+                .flatten()
+                .filter_map(|edge| {
+                    let weight = edge.probability() as f64;
+                    let target = slots.get(&edge.target_id)?;
+                    (weight > 0.0).then_some((*target, weight))
+                })
+                .collect()
+        })
+        .collect();
+    let totals: Vec<f64> = links.iter().map(|out| out.iter().map(|(_, w)| w).sum()).collect();
+
+    let mut rank = vec![1.0 / n as f64; n];
+    for _ in 0..PAGERANK_ITERATIONS {
+        let mut next = vec![(1.0 - PAGERANK_DAMPING) / n as f64; n];
+        let mut dangling = 0.0;
+        for (source, out) in links.iter().enumerate() {
+            if totals[source] > 0.0 {
+                for (target, weight) in out {
+                    next[*target] += PAGERANK_DAMPING * rank[source] * weight / totals[source];
+                }
+            } else {
+                dangling += rank[source];
+            }
+        }
+        for score in &mut next {
+            *score += PAGERANK_DAMPING * dangling / n as f64;
+        }
+        rank = next;
+    }
+    rank
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::collection::{CollectionConfig, IndexConfig};
+    use crate::core::{CollectionId, Edge, Entity};
+    use serde_json::json;
+
+    fn collection() -> (Collection, Vec<EntityId>) {
+        let config = CollectionConfig::new(2).with_index(IndexConfig {
+            m: 8,
+            ef_construction: 32,
+            ef_search: 32,
+        });
+        let collection = Collection::new(CollectionId::new(), "papers".to_string(), config, 0);
+
+        // 0 and 1 sit near [1, 0]; 2 is cited by both; 3 is far away and cold
+        let vectors = [[1.0, 0.0], [0.9, 0.1], [0.0, 1.0], [-1.0, 0.0]];
+        let langs = ["en", "de", "en", "en"];
+        let mut nodes: Vec<Entity> = vectors
+            .iter()
+            .zip(langs)
+            .map(|(v, lang)| Entity::new(Some(Vector::new(v.to_vec())), Some(json!({"lang": lang})), None))
+            .collect();
+        let ids: Vec<EntityId> = nodes.iter().map(|e| e.id).collect();
+        let edge = |from: usize, to: usize, label: &str, weight: f32| {
+            Edge::new(ids[from], ids[to], label.to_string(), weight, None)
+        };
+        nodes[0].edges = Some(vec![edge(0, 2, "cites", 0.9), edge(0, 3, "mentions", 0.9)]);
+        nodes[1].edges = Some(vec![edge(1, 2, "cites", 0.8)]);
+        nodes[3].tier = MemoryTier::Cold;
+        for node in nodes {
+            collection.upsert(node).unwrap();
+        }
+        (collection, ids)
+    }
+
+    #[test]
+    fn test_parse_full_query() {
+        let query = CognitiveQuery::parse(
+            r#"explain FIND IN papers
+                NEAR [0.5, -1e-1] k 20
+                WHERE (lang = "en" OR lang IN ("de", "fr")) AND NOT draft = true AND meta.tags[0] EXISTS
+                EXPAND 2 HOPS ALONG "cites", "extends" MIN PROBABILITY 0.5
+                RANK BY PAGERANK
+                TIERS hot, Warm
+                LIMIT 5;"#,
+        )
+        .unwrap();
+
+        assert!(query.explain);
+        assert_eq!(query.collection, "papers");
+        assert_eq!(query.near, Some(Near::Vector(vec![0.5, -0.1])));
+        assert_eq!(query.k, Some(20));
+        let lang = || FieldPath::parse("lang").unwrap();
+        let expected = MetadataFilter::eq(lang(), "en")
+            .or(MetadataFilter::in_values(lang(), ["de", "fr"]))
+            .and(!MetadataFilter::eq(FieldPath::parse("draft").unwrap(), true))
+            .and(MetadataFilter::exists(FieldPath::parse("meta.tags[0]").unwrap()));
+        assert_eq!(query.filter, Some(expected));
+        assert_eq!(
+            query.expand,
+            Some(Expansion {
+                hops: 2,
+                labels: vec!["cites".to_string(), "extends".to_string()],
+                min_probability: 0.5,
+            })
+        );
+        assert_eq!(query.rank, Some(Ranking::PageRank));
+        assert_eq!(query.tiers, vec![MemoryTier::Hot, MemoryTier::Warm]);
+        assert_eq!(query.limit, 5);
+
+        let id = EntityId::new();
+        let query: CognitiveQuery = format!("find in docs near entity \"{}\"", id).parse().unwrap();
+        assert_eq!(query.near, Some(Near::Entity(id)));
+        assert_eq!(query.limit, DEFAULT_LIMIT);
+        assert_eq!(query.candidates(), DEFAULT_LIMIT * CANDIDATE_FACTOR);
+
+        let query: CognitiveQuery = format!("FIND IN docs WITHIN 2 HOPS OF ENTITY \"{}\" ALONG \"cites\" LIMIT 3", id)
+            .parse()
+            .unwrap();
+        assert_eq!(
+            query.within,
+            Some(Neighborhood {
+                anchor: id,
+                edges: Expansion {
+                    hops: 2,
+                    labels: vec!["cites".to_string()],
+                    min_probability: 0.0,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_report_position() {
+        let cases = [
+            ("FIND papers", 5, "expected IN"),
+            ("FIND IN papers LIMIT 5 LIMIT 6", 23, "duplicate LIMIT"),
+            ("FIND IN papers WHERE year >", 27, "found end of query"),
+            ("FIND IN papers NEAR [1, x]", 24, "vector component"),
+            ("FIND IN papers WHERE lang = \"en", 28, "unterminated"),
+        ];
+        for (text, position, message) in cases {
+            match CognitiveQuery::parse(text) {
+                Err(MemorySubstrateError::Query {
+                    error: QueryError::Syntax { position: at, message: m },
+                    ..
+                }) => {
+                    assert_eq!(at, position, "{}", text);
+                    assert!(m.contains(message), "{}: {}", text, m);
+                }
+                other => panic!("{}: {:?}", text, other),
+            }
+        }
+
+        for text in [
+            "FIND IN papers LIMIT 0",
+            "FIND IN papers EXPAND 9 HOPS",
+            "FIND IN papers WITHIN 0 HOPS OF ENTITY \"0190a0a4-0000-7000-8000-000000000000\"",
+            "FIND IN papers RANK BY DISTANCE",
+            "FIND IN papers NEAR []",
+        ] {
+            assert!(
+                matches!(
+                    CognitiveQuery::parse(text),
+                    Err(MemorySubstrateError::Query {
+                        error: QueryError::Invalid { .. },
+                        ..
+                    })
+                ),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_deep_nesting_is_a_syntax_error() {
+        let nested = |open: &str, depth: usize, close: &str| {
+            format!("FIND IN papers WHERE {}a = 1{}", open.repeat(depth), close.repeat(depth))
+        };
+        assert!(CognitiveQuery::parse(&nested("(", MAX_FILTER_NESTING, ")")).is_ok());
+        assert!(CognitiveQuery::parse(&nested("NOT ", MAX_FILTER_NESTING, "")).is_ok());
+
+        for text in [nested("(", 2000, ")"), nested("NOT ", 3000, ""), nested("NOT (", 1000, ")")] {
+            match CognitiveQuery::parse(&text) {
+                Err(MemorySubstrateError::Query {
+                    error: QueryError::Syntax { message, .. },
+                    ..
+                }) => assert!(message.contains("nests deeper"), "{}", message),
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_plan_lowering() {
+        let query = CognitiveQuery::parse("FIND IN papers WHERE lang = \"en\" TIERS hot LIMIT 3").unwrap();
+        let plan = query.plan();
+        assert_eq!(plan.operators.len(), 4);
+        assert_eq!(plan.operators[0], Operator::Scan);
+        assert!(matches!(plan.operators[1], Operator::Filter { .. }));
+        assert_eq!(plan.to_string(), "Plan for \"papers\"\n  1. Scan\n  2. Filter lang = \"en\"\n  3. TierFilter hot\n  4. Limit 3");
+
+        // The filter is pushed into the search, and distance ranking is implied
+        let query = CognitiveQuery::parse("FIND IN papers NEAR [1, 0] WHERE lang = \"en\" TIERS cold, warm, hot").unwrap();
+        let plan = query.plan();
+        assert!(matches!(&plan.operators[0], Operator::VectorSearch { k: 40, filter: Some(_), .. }));
+        assert_eq!(plan.operators[1], Operator::Rank(Ranking::Distance));
+        assert_eq!(plan.operators[2], Operator::Limit { count: 10 });
+    }
+
+    #[test]
+    fn test_explain_costs_follow_weights() {
+        let (collection, _) = collection();
+        let query = CognitiveQuery::parse(
+            "EXPLAIN FIND IN papers NEAR [1, 0] K 2 EXPAND 1 HOP ALONG \"cites\" RANK BY PAGERANK LIMIT 2",
+        )
+        .unwrap();
+        let plan = query.plan();
+
+        let explain = CostModel::new(CostWeights::default()).explain(&plan, &collection);
+        assert_eq!(explain.steps.len(), plan.operators.len());
+        assert_eq!(explain.statistics.edges, 3);
+        assert_eq!(explain.statistics.tiers, [3, 0, 1]);
+        assert!((explain.steps[0].rows_out - 2.0).abs() < 1e-9);
+        // Two of three edges are "cites", followed from two seeds
+        assert!((explain.steps[1].rows_out - (2.0 + 2.0 * 0.75 * 2.0 / 3.0)).abs() < 1e-9);
+        assert!(explain.total.latency > 0.0 && explain.total.memory > 0.0 && explain.total.io > 0.0);
+        let weights = CostWeights::default();
+        let expected = explain.total.latency * weights.latency
+            + explain.total.memory * weights.memory
+            + explain.total.io * weights.io;
+        assert!((explain.weighted_cost - expected).abs() < 1e-9);
+
+        let io_heavy = CostModel::new(CostWeights {
+            latency: 1.0,
+            memory: 0.5,
+            io: 20.0,
+        })
+        .explain(&plan, &collection);
+        assert!(io_heavy.weighted_cost > explain.weighted_cost);
+
+        let text = explain.to_string();
+        assert!(text.starts_with("Plan for \"papers\" (4 entities"));
+        assert!(text.contains("2. Expand 1 hops along \"cites\""));
+        assert!(text.contains("Total: latency"));
+    }
+
+    #[test]
+    fn test_execute_near_expand_and_rank() {
+        let (collection, ids) = collection();
+
+        let query = CognitiveQuery::parse("FIND IN papers NEAR [1, 0] K 2 LIMIT 5").unwrap();
+        let rows = execute(&query.plan(), &collection.view()).unwrap();
+        assert_eq!(rows.iter().map(|r| r.id).collect::<Vec<_>>(), vec![ids[0], ids[1]]);
+
+        // Seeds filtered to English, expanded along any edge, cold tier excluded
+        let query = CognitiveQuery::parse(
+            "FIND IN papers NEAR [1, 0] K 1 WHERE lang = \"en\" EXPAND 1 HOP TIERS hot, warm",
+        )
+        .unwrap();
+        let rows = execute(&query.plan(), &collection.view()).unwrap();
+        assert_eq!(rows.iter().map(|r| (r.id, r.depth)).collect::<Vec<_>>(), vec![(ids[0], 0), (ids[2], 1)]);
+        assert!(rows.iter().all(|r| r.distance.is_some()));
+
+        // Entity 2 is cited by both seeds and ranks first
+        let query = CognitiveQuery::parse(
+            "FIND IN papers NEAR [1, 0] K 2 EXPAND 1 HOP ALONG \"cites\" RANK BY PAGERANK LIMIT 2",
+        )
+        .unwrap();
+        let rows = execute(&query.plan(), &collection.view()).unwrap();
+        assert_eq!(rows[0].id, ids[2]);
+        assert!(rows[0].pagerank.unwrap() > rows[1].pagerank.unwrap());
+        assert_eq!(rows.len(), 2);
+
+        let query: CognitiveQuery = format!("FIND IN papers NEAR ENTITY \"{}\" K 1", ids[0]).parse().unwrap();
+        let rows = execute(&query.plan(), &collection.view()).unwrap();
+        assert_eq!(rows.iter().map(|r| r.id).collect::<Vec<_>>(), vec![ids[1]]);
+
+        // The neighbourhood of 0 along citations, closest first
+        let query: CognitiveQuery = format!("FIND IN papers NEAR [0, 1] WITHIN 1 HOP OF ENTITY \"{}\" ALONG \"cites\"", ids[0])
+            .parse()
+            .unwrap();
+        let rows = execute(&query.plan(), &collection.view()).unwrap();
+        assert_eq!(rows.iter().map(|r| r.id).collect::<Vec<_>>(), vec![ids[2], ids[0]]);
+
+        let query: CognitiveQuery = format!("FIND IN papers WITHIN 1 HOP OF ENTITY \"{}\"", EntityId::new()).parse().unwrap();
+        assert!(matches!(
+            execute(&query.plan(), &collection.view()),
+            Err(MemorySubstrateError::Collection { .. })
+        ));
+
+        let query = CognitiveQuery::parse("FIND IN papers NEAR [1, 0, 0]").unwrap();
+        assert!(matches!(
+            execute(&query.plan(), &collection.view()),
+            Err(MemorySubstrateError::DimensionMismatch { .. })
+        ));
+    }
+}
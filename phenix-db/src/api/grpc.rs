@@ -1 +1,1140 @@
-// TODO: Implement gRPC interface
+//! gRPC front end
+//!
+//! Serves the `phenix.v1` services of `proto/phenix.proto` on top of an
+//! `ApiService`. Handlers decode the wire messages from `api::protocol`,
+//! call the service on the blocking thread pool and translate `MemorySubstrateError` into a
+//! `tonic::Status`; every error status carries an `x-correlation-id` that
+//! is also logged, so client reports can be matched with server logs.
+//!
+//! The change feed is a server stream per subscriber. It ends with
+//! `DATA_LOSS` when the subscriber falls further behind than the configured
+//! change buffer, and ends cleanly when the server shuts down so that
+//! graceful shutdown is not held up by idle subscribers. The change data
+//! capture stream behaves the same way, and fails with `OUT_OF_RANGE` when
+//! asked to resume from changes that are no longer available.
+
+use crate::api::protocol::{self, parse_entity_id};
+use crate::api::service::ApiService;
+use crate::core::config::ApiConfig;
+use crate::core::error::{CdcError, CollectionError, ConcurrencyError, ConsensusError, MemorySubstrateError, Result};
+use crate::core::Entity;
+use futures::{Stream, StreamExt, TryStreamExt};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_util::sync::CancellationToken;
+use tonic::metadata::MetadataValue;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
+use tonic::{Code, Request, Response, Status, Streaming};
+
+/// Generated clients and servers of the `phenix.v1` services
+#[allow(missing_docs, clippy::all)]
+pub mod services {
+    include!(concat!(env!("OUT_DIR"), "/phenix.v1.EntityService.rs"));
+    include!(concat!(env!("OUT_DIR"), "/phenix.v1.SearchService.rs"));
+    include!(concat!(env!("OUT_DIR"), "/phenix.v1.GraphService.rs"));
+    include!(concat!(env!("OUT_DIR"), "/phenix.v1.QueryService.rs"));
+    include!(concat!(env!("OUT_DIR"), "/phenix.v1.AdminService.rs"));
+    include!(concat!(env!("OUT_DIR"), "/phenix.v1.TransactionService.rs"));
+    include!(concat!(env!("OUT_DIR"), "/phenix.v1.ChangeFeedService.rs"));
+    include!(concat!(env!("OUT_DIR"), "/phenix.v1.CdcService.rs"));
+}
+
+use services::admin_service_server::{AdminService, AdminServiceServer};
+use services::cdc_service_server::{CdcService, CdcServiceServer};
+use services::change_feed_service_server::{ChangeFeedService, ChangeFeedServiceServer};
+use services::entity_service_server::{EntityService, EntityServiceServer};
+use services::graph_service_server::{GraphService, GraphServiceServer};
+use services::query_service_server::{QueryService, QueryServiceServer};
+use services::search_service_server::{SearchService, SearchServiceServer};
+use services::transaction_service_server::{TransactionService, TransactionServiceServer};
+
+/// Metadata key carrying the correlation id of a failed call
+pub const CORRELATION_ID_KEY: &str = "x-correlation-id";
+
+/// Status code for an error
+pub fn status_code(error: &MemorySubstrateError) -> Code {
+    use CollectionError as C;
+    use MemorySubstrateError as E;
+
+    match error {
+        E::Collection { error: C::NotFound { .. } | C::EntityNotFound { .. }, .. }
+        | E::Concurrency { error: ConcurrencyError::TransactionNotFound { .. }, .. } => Code::NotFound,
+        E::Collection { error: C::AlreadyExists { .. } | C::EntityAlreadyExists { .. }, .. } => {
+            Code::AlreadyExists
+        }
+        E::Collection {
+            error: C::InvalidName { .. } | C::InvalidConfig { .. } | C::InvalidEntity { .. },
+            ..
+        }
+        | E::Metadata { .. }
+        | E::Query { .. }
+        | E::DimensionMismatch { .. }
+        | E::Bulk { .. }
+        | E::InvalidRequest(_) => Code::InvalidArgument,
+        E::Concurrency { .. } => Code::Aborted,
+        E::Cdc { error: CdcError::Expired { .. }, .. } => Code::OutOfRange,
+        E::Cdc { error: CdcError::Lagged { .. }, .. } => Code::DataLoss,
+        E::Consensus {
+            error:
+                ConsensusError::NotLeader { .. }
+                | ConsensusError::ReplicaBehind { .. }
+                | ConsensusError::QuorumNotReached { .. }
+                | ConsensusError::MembershipChangePending { .. }
+                | ConsensusError::PlacementUnavailable { .. },
+            ..
+        } => Code::Unavailable,
+        E::Configuration(_) => Code::FailedPrecondition,
+        _ => Code::Internal,
+    }
+}
+
+/// Translate an error into a status carrying its correlation id
+///
+/// Errors without a correlation id get a fresh one. Internal errors are
+/// logged at `error`, everything else at `debug`.
+pub fn to_status(error: MemorySubstrateError) -> Status {
+    let code = status_code(&error);
+    let correlation_id = error.correlation_id().unwrap_or_default();
+    if code == Code::Internal {
+        tracing::error!(%correlation_id, %error, "gRPC request failed");
+    } else {
+        tracing::debug!(%correlation_id, %error, ?code, "gRPC request rejected");
+    }
+
+    let mut status = Status::new(code, error.to_string());
+    if let Ok(value) = MetadataValue::try_from(correlation_id.to_string()) {
+        status.metadata_mut().insert(CORRELATION_ID_KEY, value);
+    }
+    status
+}
+
+/// Handler of every `phenix.v1` service
+#[derive(Clone)]
+pub struct GrpcApi {
+    service: Arc<ApiService>,
+    shutdown: CancellationToken,
+}
+
+impl GrpcApi {
+    /// Serve `service`; change feeds end when `shutdown` is cancelled
+    pub fn new(service: Arc<ApiService>, shutdown: CancellationToken) -> Self {
+        Self { service, shutdown }
+    }
+
+    /// Call the service on the blocking thread pool, so a call waiting on a
+    /// lock or a log fsync never holds up the runtime
+    async fn call<T, F>(&self, f: F) -> std::result::Result<T, Status>
+    where
+        F: FnOnce(&ApiService) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.service.blocking(f).await.map_err(to_status)
+    }
+}
+
+/// Wire form of the entities read with a result, or all `None` unless
+/// `include` is set
+fn attach<'a>(entities: impl Iterator<Item = Option<&'a Entity>>, include: bool) -> Vec<Option<protocol::Entity>> {
+    entities
+        .map(|entity| entity.filter(|_| include).map(protocol::Entity::from))
+        .collect()
+}
+
+fn required<T>(field: Option<T>, name: &str) -> Result<T> {
+    field.ok_or_else(|| MemorySubstrateError::InvalidRequest(format!("missing field '{}'", name)))
+}
+
+fn decode_entity(entity: Option<protocol::Entity>) -> Result<Entity> {
+    Entity::try_from(required(entity, "entity")?)
+}
+
+#[tonic::async_trait]
+impl EntityService for GrpcApi {
+    async fn create_entity(
+        &self,
+        request: Request<protocol::WriteEntityRequest>,
+    ) -> std::result::Result<Response<protocol::Entity>, Status> {
+        let request = request.into_inner();
+        let entity = decode_entity(request.entity).map_err(to_status)?;
+        let created = self
+            .call(move |service| service.create_entity(&request.collection, entity))
+            .await?;
+        Ok(Response::new((&created).into()))
+    }
+
+    async fn get_entity(
+        &self,
+        request: Request<protocol::EntityRef>,
+    ) -> std::result::Result<Response<protocol::Entity>, Status> {
+        let request = request.into_inner();
+        let id = parse_entity_id(&request.id).map_err(to_status)?;
+        let entity = self.call(move |service| service.get_entity(&request.collection, &id)).await?;
+        Ok(Response::new((&entity).into()))
+    }
+
+    async fn update_entity(
+        &self,
+        request: Request<protocol::WriteEntityRequest>,
+    ) -> std::result::Result<Response<protocol::Entity>, Status> {
+        let request = request.into_inner();
+        if request.entity.as_ref().is_some_and(|entity| entity.id.is_empty()) {
+            return Err(Status::invalid_argument("update requires an entity id"));
+        }
+        let entity = decode_entity(request.entity).map_err(to_status)?;
+        let updated = self
+            .call(move |service| service.update_entity(&request.collection, entity))
+            .await?;
+        Ok(Response::new((&updated).into()))
+    }
+
+    async fn delete_entity(
+        &self,
+        request: Request<protocol::EntityRef>,
+    ) -> std::result::Result<Response<protocol::DeleteEntityResponse>, Status> {
+        let request = request.into_inner();
+        let id = parse_entity_id(&request.id).map_err(to_status)?;
+        let deleted = self.call(move |service| service.delete_entity(&request.collection, &id)).await?;
+        Ok(Response::new(protocol::DeleteEntityResponse { deleted }))
+    }
+
+    async fn batch_upsert(
+        &self,
+        request: Request<protocol::BatchUpsertRequest>,
+    ) -> std::result::Result<Response<protocol::BatchUpsertResponse>, Status> {
+        let request = request.into_inner();
+        let entities = request
+            .entities
+            .into_iter()
+            .map(Entity::try_from)
+            .collect::<Result<Vec<_>>>()
+            .map_err(to_status)?;
+        let outcome = self
+            .call(move |service| service.batch_upsert(&request.collection, entities))
+            .await?;
+        Ok(Response::new(outcome.into()))
+    }
+
+    async fn import_entities(
+        &self,
+        request: Request<Streaming<protocol::BatchUpsertRequest>>,
+    ) -> std::result::Result<Response<protocol::BatchUpsertResponse>, Status> {
+        let mut stream = request.into_inner();
+        let mut collection: Option<String> = None;
+        let mut outcome = crate::api::service::BatchOutcome::default();
+        let mut offset = 0;
+
+        while let Some(batch) = stream.message().await? {
+            match &collection {
+                None => collection = Some(batch.collection.clone()),
+                Some(name) if *name != batch.collection => {
+                    return Err(Status::invalid_argument(format!(
+                        "import started on collection '{}' but a batch targets '{}'",
+                        name, batch.collection
+                    )));
+                }
+                Some(_) => {}
+            }
+            let count = batch.entities.len();
+            let entities = batch
+                .entities
+                .into_iter()
+                .map(Entity::try_from)
+                .collect::<Result<Vec<_>>>()
+                .map_err(to_status)?;
+            let applied = self
+                .call(move |service| service.batch_upsert(&batch.collection, entities))
+                .await?;
+            outcome.merge(applied, offset);
+            offset += count;
+        }
+        Ok(Response::new(outcome.into()))
+    }
+}
+
+#[tonic::async_trait]
+impl SearchService for GrpcApi {
+    async fn search(
+        &self,
+        request: Request<protocol::SearchRequest>,
+    ) -> std::result::Result<Response<protocol::SearchResponse>, Status> {
+        let request = request.into_inner();
+        let query = request.to_query().map_err(to_status)?;
+        let collection = request.collection.clone();
+        let (result, entities) = self.call(move |service| service.search(&collection, &query)).await?;
+        let mut entities = attach(entities.iter().map(|entity| Some(&**entity)), request.include_entities);
+        let hits = result
+            .hits
+            .iter()
+            .zip(entities.iter_mut())
+            .map(|(hit, entity)| protocol::SearchHit {
+                id: hit.id.to_string(),
+                distance: hit.distance,
+                entity: entity.take(),
+            })
+            .collect();
+
+        Ok(Response::new(protocol::SearchResponse {
+            hits,
+            strategy: protocol::FilterStrategy::from(result.strategy) as i32,
+            selectivity: result.selectivity,
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl GraphService for GrpcApi {
+    async fn traverse(
+        &self,
+        request: Request<protocol::TraverseRequest>,
+    ) -> std::result::Result<Response<protocol::TraverseResponse>, Status> {
+        let request = request.into_inner();
+        let query = request.to_query().map_err(to_status)?;
+        let collection = request.collection.clone();
+        let (steps, entities) = self.call(move |service| service.traverse(&collection, &query)).await?;
+        let mut entities = attach(entities.iter().map(|entity| Some(&**entity)), request.include_entities);
+        let nodes = steps
+            .iter()
+            .zip(entities.iter_mut())
+            .map(|(step, entity)| protocol::TraversalNode {
+                id: step.id.to_string(),
+                depth: step.depth as u32,
+                parent_id: step.parent.map(|parent| parent.to_string()).unwrap_or_default(),
+                label: step.label.clone().unwrap_or_default(),
+                path_probability: step.path_probability,
+                entity: entity.take(),
+            })
+            .collect();
+        Ok(Response::new(protocol::TraverseResponse { nodes }))
+    }
+}
+
+#[tonic::async_trait]
+impl QueryService for GrpcApi {
+    async fn query(
+        &self,
+        request: Request<protocol::QueryRequest>,
+    ) -> std::result::Result<Response<protocol::QueryResponse>, Status> {
+        let request = request.into_inner();
+        let text = request.query;
+        let output = self.call(move |service| service.query(&text)).await?;
+
+        let mut entities = attach(output.entities.iter().map(Option::as_deref), request.include_entities);
+        let rows = output
+            .rows
+            .iter()
+            .zip(entities.iter_mut())
+            .map(|(row, entity)| protocol::QueryRow {
+                id: row.id.to_string(),
+                depth: row.depth as u32,
+                distance: row.distance,
+                pagerank: row.pagerank,
+                entity: entity.take(),
+            })
+            .collect();
+
+        let (plan, explain_json, estimated_cost) = match &output.explain {
+            Some(explain) => (
+                explain.to_string(),
+                serde_json::to_string(explain)
+                    .map_err(|error| to_status(MemorySubstrateError::Serialization(error.to_string())))?,
+                explain.weighted_cost,
+            ),
+            None => (String::new(), String::new(), 0.0),
+        };
+        Ok(Response::new(protocol::QueryResponse {
+            collection: output.collection,
+            rows,
+            plan,
+            explain_json,
+            estimated_cost,
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl AdminService for GrpcApi {
+    async fn create_collection(
+        &self,
+        request: Request<protocol::CreateCollectionRequest>,
+    ) -> std::result::Result<Response<protocol::CollectionInfo>, Status> {
+        let request = request.into_inner();
+        let config = required(request.config, "config").and_then(TryInto::try_into).map_err(to_status)?;
+        let info = self
+            .call(move |service| service.create_collection(&request.name, config))
+            .await?;
+        Ok(Response::new((&info).into()))
+    }
+
+    async fn list_collections(
+        &self,
+        _request: Request<protocol::Empty>,
+    ) -> std::result::Result<Response<protocol::ListCollectionsResponse>, Status> {
+        let collections = self
+            .call(|service| Ok(service.list_collections()))
+            .await?
+            .iter()
+            .map(Into::into)
+            .collect();
+        Ok(Response::new(protocol::ListCollectionsResponse { collections }))
+    }
+
+    async fn describe_collection(
+        &self,
+        request: Request<protocol::CollectionRef>,
+    ) -> std::result::Result<Response<protocol::CollectionInfo>, Status> {
+        let name = request.into_inner().name;
+        let info = self.call(move |service| service.describe_collection(&name)).await?;
+        Ok(Response::new((&info).into()))
+    }
+
+    async fn drop_collection(
+        &self,
+        request: Request<protocol::CollectionRef>,
+    ) -> std::result::Result<Response<protocol::Empty>, Status> {
+        let name = request.into_inner().name;
+        self.call(move |service| service.drop_collection(&name)).await?;
+        Ok(Response::new(protocol::Empty {}))
+    }
+
+    async fn set_alias(
+        &self,
+        request: Request<protocol::SetAliasRequest>,
+    ) -> std::result::Result<Response<protocol::Empty>, Status> {
+        let request = request.into_inner();
+        self.call(move |service| service.set_alias(&request.alias, &request.target))
+            .await?;
+        Ok(Response::new(protocol::Empty {}))
+    }
+
+    async fn drop_alias(
+        &self,
+        request: Request<protocol::DropAliasRequest>,
+    ) -> std::result::Result<Response<protocol::Empty>, Status> {
+        let alias = request.into_inner().alias;
+        self.call(move |service| service.drop_alias(&alias)).await?;
+        Ok(Response::new(protocol::Empty {}))
+    }
+
+    async fn cluster_status(
+        &self,
+        _request: Request<protocol::Empty>,
+    ) -> std::result::Result<Response<protocol::ClusterStatusResponse>, Status> {
+        let status = self.call(|service| Ok(service.cluster_status())).await?;
+        Ok(Response::new((&status).into()))
+    }
+}
+
+/// Server stream of change events
+pub type ChangeStream = Pin<Box<dyn Stream<Item = std::result::Result<protocol::ChangeEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl TransactionService for GrpcApi {
+    async fn begin(
+        &self,
+        request: Request<protocol::CollectionRef>,
+    ) -> std::result::Result<Response<protocol::TransactionRef>, Status> {
+        let collection = request.into_inner().name;
+        let name = collection.clone();
+        let transaction_id = self.call(move |service| service.begin_transaction(&name)).await?;
+        Ok(Response::new(protocol::TransactionRef {
+            collection,
+            transaction_id,
+        }))
+    }
+
+    async fn get(
+        &self,
+        request: Request<protocol::TransactionEntityRef>,
+    ) -> std::result::Result<Response<protocol::Entity>, Status> {
+        let request = request.into_inner();
+        let id = parse_entity_id(&request.id).map_err(to_status)?;
+        let entity = self
+            .call(move |service| service.transaction_get(&request.collection, request.transaction_id, &id))
+            .await?;
+        Ok(Response::new((&entity).into()))
+    }
+
+    async fn put(
+        &self,
+        request: Request<protocol::TransactionWriteRequest>,
+    ) -> std::result::Result<Response<protocol::Entity>, Status> {
+        let request = request.into_inner();
+        let entity = decode_entity(request.entity).map_err(to_status)?;
+        let written = self
+            .call(move |service| service.transaction_put(&request.collection, request.transaction_id, entity))
+            .await?;
+        Ok(Response::new((&written).into()))
+    }
+
+    async fn delete(
+        &self,
+        request: Request<protocol::TransactionEntityRef>,
+    ) -> std::result::Result<Response<protocol::DeleteEntityResponse>, Status> {
+        let request = request.into_inner();
+        let id = parse_entity_id(&request.id).map_err(to_status)?;
+        let deleted = self
+            .call(move |service| service.transaction_delete(&request.collection, request.transaction_id, &id))
+            .await?;
+        Ok(Response::new(protocol::DeleteEntityResponse { deleted }))
+    }
+
+    async fn commit(
+        &self,
+        request: Request<protocol::TransactionRef>,
+    ) -> std::result::Result<Response<protocol::CommitTransactionResponse>, Status> {
+        let request = request.into_inner();
+        let commit_ts = self
+            .call(move |service| service.commit_transaction(&request.collection, request.transaction_id))
+            .await?;
+        Ok(Response::new(protocol::CommitTransactionResponse { commit_ts }))
+    }
+
+    async fn abort(
+        &self,
+        request: Request<protocol::TransactionRef>,
+    ) -> std::result::Result<Response<protocol::Empty>, Status> {
+        let request = request.into_inner();
+        self.call(move |service| service.abort_transaction(&request.collection, request.transaction_id))
+            .await?;
+        Ok(Response::new(protocol::Empty {}))
+    }
+}
+
+#[tonic::async_trait]
+impl ChangeFeedService for GrpcApi {
+    type SubscribeStream = ChangeStream;
+
+    async fn subscribe(
+        &self,
+        request: Request<protocol::SubscribeRequest>,
+    ) -> std::result::Result<Response<ChangeStream>, Status> {
+        // Resolve aliases up front: events carry primary names
+        let names = request.into_inner().collections;
+        let collections = self
+            .call(move |service| {
+                names
+                    .iter()
+                    .map(|name| service.describe_collection(name).map(|info| info.name))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .await?;
+        let receiver = self.service.subscribe();
+        let shutdown = self.shutdown.clone();
+
+        let stream = futures::stream::unfold(Some(receiver), move |receiver| {
+            let collections = collections.clone();
+            let shutdown = shutdown.clone();
+            async move {
+                let mut receiver = receiver?;
+                loop {
+                    let received = tokio::select! {
+                        _ = shutdown.cancelled() => return None,
+                        received = receiver.recv() => received,
+                    };
+                    match received {
+                        Ok(event) if collections.is_empty() || collections.contains(&event.collection) => {
+                            return Some((Ok((&event).into()), Some(receiver)));
+                        }
+                        Ok(_) => continue,
+                        Err(RecvError::Lagged(missed)) => {
+                            let status = Status::data_loss(format!(
+                                "subscriber fell behind and missed {} change events",
+                                missed
+                            ));
+                            return Some((Err(status), None));
+                        }
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            }
+        });
+        Ok(Response::new(stream.boxed()))
+    }
+}
+
+/// Server stream of change data capture events
+pub type CdcEventStream = Pin<Box<dyn Stream<Item = std::result::Result<protocol::CdcEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl CdcService for GrpcApi {
+    type StreamStream = CdcEventStream;
+
+    async fn stream(
+        &self,
+        request: Request<protocol::CdcRequest>,
+    ) -> std::result::Result<Response<CdcEventStream>, Status> {
+        let request = request.into_inner();
+        let events = self
+            .call(move |service| service.cdc(request.after_sequence, &request.collections))
+            .await?;
+        let stream = events
+            .map_ok(|event| (&event).into())
+            .map_err(to_status)
+            .take_until(self.shutdown.clone().cancelled_owned());
+        Ok(Response::new(stream.boxed()))
+    }
+}
+
+/// Bind `config.grpc_addr` and serve until `shutdown` resolves
+pub async fn serve(
+    service: Arc<ApiService>,
+    config: &ApiConfig,
+    shutdown: impl Future<Output = ()> + Send,
+) -> Result<()> {
+    let listener = TcpListener::bind(&config.grpc_addr).await?;
+    serve_with_listener(service, config, listener, shutdown).await
+}
+
+/// Serve on an already bound listener until `shutdown` resolves
+///
+/// In-flight calls are allowed to finish; open change feeds are ended.
+///
+/// # Errors
+/// * `Io` if TLS files cannot be read
+/// * `Configuration` if the TLS material is invalid
+pub async fn serve_with_listener(
+    service: Arc<ApiService>,
+    config: &ApiConfig,
+    listener: TcpListener,
+    shutdown: impl Future<Output = ()> + Send,
+) -> Result<()> {
+    let mut builder = Server::builder();
+    if let Some(tls) = &config.tls {
+        let identity = Identity::from_pem(std::fs::read(&tls.cert_path)?, std::fs::read(&tls.key_path)?);
+        let mut tls_config = ServerTlsConfig::new().identity(identity);
+        if let Some(ca) = &tls.client_ca_path {
+            tls_config = tls_config.client_ca_root(Certificate::from_pem(std::fs::read(ca)?));
+        }
+        builder = builder
+            .tls_config(tls_config)
+            .map_err(|error| MemorySubstrateError::Configuration(format!("invalid TLS configuration: {}", error)))?;
+    }
+
+    let address = listener.local_addr()?;
+    let incoming = TcpIncoming::from_listener(listener, true, None)
+        .map_err(|error| MemorySubstrateError::Internal(format!("cannot accept on {}: {}", address, error)))?;
+
+    let feeds = CancellationToken::new();
+    let api = GrpcApi::new(service, feeds.clone());
+    let limit = config.max_message_bytes;
+    let signal = async move {
+        shutdown.await;
+        feeds.cancel();
+    };
+
+    tracing::info!(%address, tls = config.tls.is_some(), "gRPC API listening");
+    builder
+        .add_service(
+            EntityServiceServer::new(api.clone())
+                .max_decoding_message_size(limit)
+                .max_encoding_message_size(limit),
+        )
+        .add_service(
+            SearchServiceServer::new(api.clone())
+                .max_decoding_message_size(limit)
+                .max_encoding_message_size(limit),
+        )
+        .add_service(
+            GraphServiceServer::new(api.clone())
+                .max_decoding_message_size(limit)
+                .max_encoding_message_size(limit),
+        )
+        .add_service(
+            QueryServiceServer::new(api.clone())
+                .max_decoding_message_size(limit)
+                .max_encoding_message_size(limit),
+        )
+        .add_service(
+            AdminServiceServer::new(api.clone())
+                .max_decoding_message_size(limit)
+                .max_encoding_message_size(limit),
+        )
+        .add_service(
+            TransactionServiceServer::new(api.clone())
+                .max_decoding_message_size(limit)
+                .max_encoding_message_size(limit),
+        )
+        .add_service(
+            ChangeFeedServiceServer::new(api.clone())
+                .max_decoding_message_size(limit)
+                .max_encoding_message_size(limit),
+        )
+        .add_service(
+            CdcServiceServer::new(api)
+                .max_decoding_message_size(limit)
+                .max_encoding_message_size(limit),
+        )
+        .serve_with_incoming_shutdown(incoming, signal)
+        .await
+        .map_err(|error| MemorySubstrateError::Internal(format!("gRPC server failed: {}", error)))?;
+    tracing::info!(%address, "gRPC API stopped");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::services::admin_service_client::AdminServiceClient;
+    use super::services::cdc_service_client::CdcServiceClient;
+    use super::services::change_feed_service_client::ChangeFeedServiceClient;
+    use super::services::entity_service_client::EntityServiceClient;
+    use super::services::graph_service_client::GraphServiceClient;
+    use super::services::query_service_client::QueryServiceClient;
+    use super::services::search_service_client::SearchServiceClient;
+    use super::services::transaction_service_client::TransactionServiceClient;
+    use super::*;
+    use crate::core::config::TlsConfig;
+    use crate::core::NodeId;
+    use crate::storage::catalog::Catalog;
+    use tokio::sync::oneshot;
+    use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+
+    struct TestServer {
+        channel: Channel,
+        stop: oneshot::Sender<()>,
+        handle: tokio::task::JoinHandle<Result<()>>,
+    }
+
+    impl TestServer {
+        async fn shutdown(self) {
+            let _ = self.stop.send(());
+            self.handle.await.unwrap().unwrap();
+        }
+    }
+
+    async fn start(config: ApiConfig, tls: Option<ClientTlsConfig>) -> TestServer {
+        let service = Arc::new(ApiService::new(NodeId::new(), Catalog::in_memory(), config.change_buffer));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (stop, stopped) = oneshot::channel::<()>();
+        let handle = tokio::spawn(async move {
+            serve_with_listener(service, &config, listener, async {
+                let _ = stopped.await;
+            })
+            .await
+        });
+
+        let endpoint = match tls {
+            Some(tls) => Endpoint::from_shared(format!("https://localhost:{}", port))
+                .unwrap()
+                .tls_config(tls)
+                .unwrap(),
+            None => Endpoint::from_shared(format!("http://127.0.0.1:{}", port)).unwrap(),
+        };
+        let channel = endpoint.connect().await.unwrap();
+        TestServer { channel, stop, handle }
+    }
+
+    fn collection_config(dimension: u32) -> protocol::CollectionConfig {
+        protocol::CollectionConfig {
+            dimension,
+            ..Default::default()
+        }
+    }
+
+    fn entity(vector: Vec<f32>, metadata: &str) -> protocol::Entity {
+        protocol::Entity {
+            vector,
+            metadata: metadata.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_entity_search_and_traverse_over_grpc() {
+        let server = start(ApiConfig::default(), None).await;
+        let mut admin = AdminServiceClient::new(server.channel.clone());
+        let mut entities = EntityServiceClient::new(server.channel.clone());
+        let mut search = SearchServiceClient::new(server.channel.clone());
+        let mut graph = GraphServiceClient::new(server.channel.clone());
+        let mut query = QueryServiceClient::new(server.channel.clone());
+
+        admin
+            .create_collection(protocol::CreateCollectionRequest {
+                name: "docs".into(),
+                config: Some(collection_config(2)),
+            })
+            .await
+            .unwrap();
+        admin
+            .set_alias(protocol::SetAliasRequest {
+                alias: "live".into(),
+                target: "docs".into(),
+            })
+            .await
+            .unwrap();
+
+        let first = entities
+            .create_entity(protocol::WriteEntityRequest {
+                collection: "live".into(),
+                entity: Some(entity(vec![1.0, 0.0], r#"{"lang":"en"}"#)),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(first.version, 1);
+
+        let mut second = entity(vec![0.0, 1.0], r#"{"lang":"fr"}"#);
+        second.edges.push(protocol::Edge {
+            target_id: first.id.clone(),
+            label: "translates".into(),
+            weight: 0.8,
+            ..Default::default()
+        });
+        let second = entities
+            .create_entity(protocol::WriteEntityRequest {
+                collection: "docs".into(),
+                entity: Some(second),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+
+        let mut changed = first.clone();
+        changed.metadata = r#"{"lang":"de"}"#.into();
+        let updated = entities
+            .update_entity(protocol::WriteEntityRequest {
+                collection: "docs".into(),
+                entity: Some(changed),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(updated.version, 2);
+
+        let response = search
+            .search(protocol::SearchRequest {
+                collection: "docs".into(),
+                vector: vec![1.0, 0.1],
+                k: 2,
+                filter: r#"{"op":"eq","field":"lang","value":"de"}"#.into(),
+                include_entities: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.hits.len(), 1);
+        assert_eq!(response.hits[0].id, first.id);
+        assert_eq!(response.hits[0].entity.as_ref().unwrap().metadata, r#"{"lang":"de"}"#);
+
+        let nodes = graph
+            .traverse(protocol::TraverseRequest {
+                collection: "docs".into(),
+                start_ids: vec![second.id.clone()],
+                max_depth: 2,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .nodes;
+        let reached: Vec<_> = nodes.iter().map(|node| (node.id.clone(), node.depth)).collect();
+        assert_eq!(reached, vec![(second.id.clone(), 0), (first.id.clone(), 1)]);
+        assert_eq!(nodes[1].label, "translates");
+
+        let answer = query
+            .query(protocol::QueryRequest {
+                query: "FIND IN live NEAR [0, 1] K 1 EXPAND 1 HOP RANK BY PAGERANK".into(),
+                include_entities: true,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(answer.collection, "docs");
+        let ranked: Vec<_> = answer.rows.iter().map(|row| (row.id.clone(), row.depth)).collect();
+        assert_eq!(ranked, vec![(first.id.clone(), 1), (second.id.clone(), 0)]);
+        assert!(answer.rows.iter().all(|row| row.pagerank.is_some()));
+        assert!(answer.rows[0].entity.is_some());
+        assert!(answer.plan.is_empty());
+
+        let explained = query
+            .query(protocol::QueryRequest {
+                query: "EXPLAIN FIND IN docs WHERE lang = \"fr\"".into(),
+                include_entities: false,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(explained.rows.is_empty());
+        assert!(explained.plan.contains("1. IndexScan lang = \"fr\""));
+        assert!(explained.estimated_cost > 0.0);
+        let malformed_query = query
+            .query(protocol::QueryRequest {
+                query: "FIND IN docs LIMIT".into(),
+                include_entities: false,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(malformed_query.code(), Code::InvalidArgument);
+
+        let deleted = entities
+            .delete_entity(protocol::EntityRef {
+                collection: "docs".into(),
+                id: first.id.clone(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(deleted.deleted);
+
+        let missing = entities
+            .get_entity(protocol::EntityRef {
+                collection: "docs".into(),
+                id: first.id.clone(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(missing.code(), Code::NotFound);
+        assert!(missing.metadata().get(CORRELATION_ID_KEY).is_some());
+
+        let malformed = entities
+            .get_entity(protocol::EntityRef {
+                collection: "docs".into(),
+                id: "nope".into(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(malformed.code(), Code::InvalidArgument);
+
+        server.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_import_stream_and_change_feed() {
+        let server = start(ApiConfig::default(), None).await;
+        let mut admin = AdminServiceClient::new(server.channel.clone());
+        let mut entities = EntityServiceClient::new(server.channel.clone());
+        let mut feed = ChangeFeedServiceClient::new(server.channel.clone());
+
+        for name in ["a", "b"] {
+            admin
+                .create_collection(protocol::CreateCollectionRequest {
+                    name: name.into(),
+                    config: Some(collection_config(2)),
+                })
+                .await
+                .unwrap();
+        }
+        let duplicate = admin
+            .create_collection(protocol::CreateCollectionRequest {
+                name: "a".into(),
+                config: Some(collection_config(2)),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(duplicate.code(), Code::AlreadyExists);
+
+        let mut changes = feed
+            .subscribe(protocol::SubscribeRequest {
+                collections: vec!["a".into()],
+            })
+            .await
+            .unwrap()
+            .into_inner();
+
+        let batches = vec![
+            protocol::BatchUpsertRequest {
+                collection: "a".into(),
+                entities: vec![entity(vec![1.0, 0.0], ""), entity(vec![1.0, 2.0, 3.0], "")],
+            },
+            protocol::BatchUpsertRequest {
+                collection: "a".into(),
+                entities: vec![entity(vec![0.0, 1.0], "")],
+            },
+        ];
+        let imported = entities
+            .import_entities(futures::stream::iter(batches))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(imported.upserted, 2);
+        assert_eq!(imported.rejected.len(), 1);
+        assert_eq!(imported.rejected[0].index, 1);
+
+        entities
+            .batch_upsert(protocol::BatchUpsertRequest {
+                collection: "b".into(),
+                entities: vec![entity(vec![0.5, 0.5], "")],
+            })
+            .await
+            .unwrap();
+
+        let first = changes.message().await.unwrap().unwrap();
+        let second = changes.message().await.unwrap().unwrap();
+        assert_eq!((first.sequence, second.sequence), (1, 2));
+        assert!([&first, &second].iter().all(|event| event.collection == "a"));
+        assert_eq!(first.kind(), protocol::ChangeKind::Upsert);
+
+        let status = admin.cluster_status(protocol::Empty {}).await.unwrap().into_inner();
+        assert_eq!((status.collections, status.entities), (2, 3));
+
+        // Shutdown ends open change feeds instead of waiting on them
+        server.shutdown().await;
+        assert!(changes.message().await.map(|event| event.is_none()).unwrap_or(true));
+    }
+
+    #[tokio::test]
+    async fn test_transactions_over_grpc() {
+        let server = start(ApiConfig::default(), None).await;
+        let mut admin = AdminServiceClient::new(server.channel.clone());
+        let mut entities = EntityServiceClient::new(server.channel.clone());
+        let mut transactions = TransactionServiceClient::new(server.channel.clone());
+        admin
+            .create_collection(protocol::CreateCollectionRequest {
+                name: "docs".into(),
+                config: Some(collection_config(2)),
+            })
+            .await
+            .unwrap();
+
+        let txn = transactions
+            .begin(protocol::CollectionRef { name: "docs".into() })
+            .await
+            .unwrap()
+            .into_inner();
+        let written = transactions
+            .put(protocol::TransactionWriteRequest {
+                collection: txn.collection.clone(),
+                transaction_id: txn.transaction_id,
+                entity: Some(entity(vec![1.0, 0.0], "")),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let outside = protocol::EntityRef {
+            collection: "docs".into(),
+            id: written.id.clone(),
+        };
+        assert_eq!(entities.get_entity(outside.clone()).await.unwrap_err().code(), Code::NotFound);
+
+        let committed = transactions.commit(txn.clone()).await.unwrap().into_inner();
+        assert!(committed.commit_ts > 0);
+        assert_eq!(entities.get_entity(outside).await.unwrap().into_inner().version, 1);
+
+        // Closed transactions are gone
+        let missing = transactions
+            .get(protocol::TransactionEntityRef {
+                collection: txn.collection.clone(),
+                transaction_id: txn.transaction_id,
+                id: written.id,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(missing.code(), Code::NotFound);
+        assert_eq!(transactions.abort(txn).await.unwrap_err().code(), Code::NotFound);
+        server.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_cdc_stream_resumes_after_sequence() {
+        let server = start(ApiConfig::default(), None).await;
+        let mut admin = AdminServiceClient::new(server.channel.clone());
+        let mut entities = EntityServiceClient::new(server.channel.clone());
+        let mut cdc = CdcServiceClient::new(server.channel.clone());
+
+        admin
+            .create_collection(protocol::CreateCollectionRequest {
+                name: "docs".into(),
+                config: Some(collection_config(2)),
+            })
+            .await
+            .unwrap();
+        let target = entities
+            .create_entity(protocol::WriteEntityRequest {
+                collection: "docs".into(),
+                entity: Some(entity(vec![0.0, 1.0], "")),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let mut source = entity(vec![1.0, 0.0], "");
+        source.edges = vec![protocol::Edge {
+            target_id: target.id.clone(),
+            label: "cites".into(),
+            weight: 0.5,
+            ..Default::default()
+        }];
+        entities
+            .create_entity(protocol::WriteEntityRequest {
+                collection: "docs".into(),
+                entity: Some(source),
+            })
+            .await
+            .unwrap();
+
+        // Resume after the first change
+        let mut events = cdc
+            .stream(protocol::CdcRequest {
+                after_sequence: 1,
+                collections: vec!["docs".into()],
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let upsert = events.message().await.unwrap().unwrap();
+        assert_eq!(upsert.sequence, 2);
+        assert!(matches!(upsert.change, Some(protocol::cdc_event::Change::EntityUpsert(_))));
+        let created = events.message().await.unwrap().unwrap();
+        match created.change {
+            Some(protocol::cdc_event::Change::EdgeCreate(change)) => {
+                assert_eq!(change.edge.unwrap().target_id, target.id);
+            }
+            other => panic!("unexpected change {:?}", other),
+        }
+
+        entities
+            .delete_entity(protocol::EntityRef {
+                collection: "docs".into(),
+                id: target.id.clone(),
+            })
+            .await
+            .unwrap();
+        let deleted = events.message().await.unwrap().unwrap();
+        assert_eq!(deleted.sequence, 4);
+        assert_eq!(deleted.change, Some(protocol::cdc_event::Change::EntityDelete(target.id)));
+
+        let unknown = cdc
+            .stream(protocol::CdcRequest {
+                after_sequence: 0,
+                collections: vec!["missing".into()],
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(unknown.code(), Code::NotFound);
+
+        server.shutdown().await;
+        assert!(events.message().await.map(|event| event.is_none()).unwrap_or(true));
+    }
+
+    #[tokio::test]
+    async fn test_tls_listener() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert_path = dir.path().join("server.pem");
+        let key_path = dir.path().join("server.key");
+        std::fs::write(&cert_path, include_str!("testdata/server.pem")).unwrap();
+        std::fs::write(&key_path, include_str!("testdata/server.key")).unwrap();
+
+        let config = ApiConfig {
+            tls: Some(TlsConfig {
+                cert_path,
+                key_path,
+                client_ca_path: None,
+            }),
+            ..ApiConfig::default()
+        };
+        let client_tls = ClientTlsConfig::new()
+            .ca_certificate(Certificate::from_pem(include_str!("testdata/ca.pem")))
+            .domain_name("localhost");
+        let server = start(config, Some(client_tls)).await;
+
+        let mut admin = AdminServiceClient::new(server.channel.clone());
+        let collections = admin.list_collections(protocol::Empty {}).await.unwrap().into_inner();
+        assert!(collections.collections.is_empty());
+
+        server.shutdown().await;
+    }
+}
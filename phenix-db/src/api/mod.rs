@@ -1,6 +1,25 @@
 // API layer
 //
 // This module will be fully implemented in Phase 20.
-// Placeholder for now to allow compilation.
+// Implemented so far:
+// - Service: Transport-independent catalog operations, change events and cluster status
+// - Protocol: Protobuf wire messages of the phenix.v1 package and their conversions
+// - gRPC: Entity, search, graph, admin and change-feed services with optional TLS
+// - REST: JSON mirror of the gRPC services with an OpenAPI document
+// - Cognitive query: Query language over similarity, filters, graph hops and tiers, with EXPLAIN
+// - Query planner: Cost-based choice between vector-first, graph-first and scan plans, with a plan cache
+// - Workers: Background tiering, edge normalization and learning, and entropy sampling
 
-// Placeholder - will be implemented in Phase 20
+pub mod service;
+pub mod protocol;
+pub mod grpc;
+pub mod rest;
+pub mod cognitive_query;
+pub mod query_planner;
+pub mod workers;
+
+pub use service::{ApiService, BatchOutcome, ChangeEvent, ChangeKind, ClusterStatus, QueryOutput, Rejection};
+pub use grpc::GrpcApi;
+pub use cognitive_query::{CognitiveQuery, CostModel, Explain, LogicalPlan, QueryRow};
+pub use query_planner::{PlanCacheStats, PlannedQuery, QueryPlanner};
+pub use workers::Workers;
@@ -1 +1,1070 @@
-// TODO: Implement protocol definitions
+//! Wire messages of the `phenix.v1` protobuf package
+//!
+//! These are the prost encodings of the messages in `proto/phenix.proto`,
+//! written out by hand so the build does not depend on `protoc`. Field tags
+//! must match the proto file. Metadata, schemas, tiering policies and
+//! filters travel as JSON strings, and ids as UUID strings; the conversions
+//! at the bottom of this file turn them into domain types and report
+//! malformed input as `MemorySubstrateError::InvalidRequest`.
+
+use crate::api::service::{BatchOutcome, ChangeKind as DomainChangeKind};
+use crate::api::service::{ChangeEvent as DomainChangeEvent, ClusterStatus};
+use crate::core::collection::{
+    CollectionConfig as DomainCollectionConfig, CollectionInfo as DomainCollectionInfo,
+    IndexConfig as DomainIndexConfig,
+};
+use crate::core::config::TieringConfig;
+use crate::core::error::{MemorySubstrateError, Result};
+use crate::core::query::{FilterStrategy as DomainFilterStrategy, GraphQuery, VectorQuery};
+use crate::core::{DistanceMetric, EntityId, MemoryTier, Vector};
+use crate::core::{Edge as DomainEdge, Entity as DomainEntity};
+use crate::distributed::node::{Member, MemberState};
+use crate::storage::cdc::{CdcEvent as DomainCdcEvent, Change as DomainChange};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Message without fields, encoded like `google.protobuf.Empty`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Empty {}
+
+/// Memory tier of an entity
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Tier {
+    /// Not set; new entities start hot
+    Unspecified = 0,
+    /// Hot tier
+    Hot = 1,
+    /// Warm tier
+    Warm = 2,
+    /// Cold tier
+    Cold = 3,
+}
+
+/// Outgoing edge of an entity
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Edge {
+    /// Target entity id
+    #[prost(string, tag = "1")]
+    pub target_id: String,
+    /// Relationship label
+    #[prost(string, tag = "2")]
+    pub label: String,
+    /// Static weight in [0, 1]
+    #[prost(float, tag = "3")]
+    pub weight: f32,
+    /// JSON metadata
+    #[prost(string, tag = "4")]
+    pub metadata: String,
+    /// Learned probability; ignored on write
+    #[prost(float, tag = "5")]
+    pub probability: f32,
+}
+
+/// An entity
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Entity {
+    /// Entity id; generated by the server when empty on create
+    #[prost(string, tag = "1")]
+    pub id: String,
+    /// Vector embedding
+    #[prost(float, repeated, tag = "2")]
+    pub vector: Vec<f32>,
+    /// JSON metadata
+    #[prost(string, tag = "3")]
+    pub metadata: String,
+    /// Outgoing edges
+    #[prost(message, repeated, tag = "4")]
+    pub edges: Vec<Edge>,
+    /// Creation time; server-maintained
+    #[prost(uint64, tag = "5")]
+    pub created_at: u64,
+    /// Last update time; server-maintained
+    #[prost(uint64, tag = "6")]
+    pub updated_at: u64,
+    /// MVCC version; server-maintained
+    #[prost(uint64, tag = "7")]
+    pub version: u64,
+    /// Memory tier
+    #[prost(enumeration = "Tier", tag = "8")]
+    pub tier: i32,
+}
+
+/// Address of one entity
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EntityRef {
+    /// Collection name or alias
+    #[prost(string, tag = "1")]
+    pub collection: String,
+    /// Entity id
+    #[prost(string, tag = "2")]
+    pub id: String,
+}
+
+/// Create or replace one entity
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WriteEntityRequest {
+    /// Collection name or alias
+    #[prost(string, tag = "1")]
+    pub collection: String,
+    /// Entity to write
+    #[prost(message, optional, tag = "2")]
+    pub entity: Option<Entity>,
+}
+
+/// Result of a delete
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DeleteEntityResponse {
+    /// Whether the entity existed
+    #[prost(bool, tag = "1")]
+    pub deleted: bool,
+}
+
+/// Upsert many entities into one collection
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BatchUpsertRequest {
+    /// Collection name or alias
+    #[prost(string, tag = "1")]
+    pub collection: String,
+    /// Entities to write
+    #[prost(message, repeated, tag = "2")]
+    pub entities: Vec<Entity>,
+}
+
+/// An entity rejected from a batch
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RejectedEntity {
+    /// Position in the batch or import stream
+    #[prost(uint64, tag = "1")]
+    pub index: u64,
+    /// Entity id
+    #[prost(string, tag = "2")]
+    pub id: String,
+    /// Why it was rejected
+    #[prost(string, tag = "3")]
+    pub reason: String,
+}
+
+/// Result of a batch upsert or import
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BatchUpsertResponse {
+    /// Number of entities written
+    #[prost(uint64, tag = "1")]
+    pub upserted: u64,
+    /// Entities that failed validation
+    #[prost(message, repeated, tag = "2")]
+    pub rejected: Vec<RejectedEntity>,
+}
+
+/// Filter strategy of a search
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum FilterStrategy {
+    /// Chosen by the server
+    Unspecified = 0,
+    /// Filter first, then rank exactly
+    PreFilter = 1,
+    /// Search, then drop non-matching hits
+    PostFilter = 2,
+    /// Filter while walking the graph
+    InGraph = 3,
+}
+
+/// k-NN query
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SearchRequest {
+    /// Collection name or alias
+    #[prost(string, tag = "1")]
+    pub collection: String,
+    /// Query embedding
+    #[prost(float, repeated, tag = "2")]
+    pub vector: Vec<f32>,
+    /// Number of results
+    #[prost(uint32, tag = "3")]
+    pub k: u32,
+    /// JSON metadata filter
+    #[prost(string, tag = "4")]
+    pub filter: String,
+    /// Pinned filter strategy
+    #[prost(enumeration = "FilterStrategy", tag = "5")]
+    pub strategy: i32,
+    /// Search breadth override; 0 uses the collection default
+    #[prost(uint32, tag = "6")]
+    pub ef: u32,
+    /// Attach the stored entity to each hit
+    #[prost(bool, tag = "7")]
+    pub include_entities: bool,
+}
+
+/// One search result
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SearchHit {
+    /// Entity id
+    #[prost(string, tag = "1")]
+    pub id: String,
+    /// Distance to the query
+    #[prost(float, tag = "2")]
+    pub distance: f32,
+    /// Stored entity, when requested
+    #[prost(message, optional, tag = "3")]
+    pub entity: Option<Entity>,
+}
+
+/// Search results
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SearchResponse {
+    /// Hits ordered from closest to farthest
+    #[prost(message, repeated, tag = "1")]
+    pub hits: Vec<SearchHit>,
+    /// Strategy that produced the hits
+    #[prost(enumeration = "FilterStrategy", tag = "2")]
+    pub strategy: i32,
+    /// Estimated filter selectivity
+    #[prost(double, tag = "3")]
+    pub selectivity: f64,
+}
+
+/// Breadth-first edge traversal
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TraverseRequest {
+    /// Collection name or alias
+    #[prost(string, tag = "1")]
+    pub collection: String,
+    /// Entities to start from
+    #[prost(string, repeated, tag = "2")]
+    pub start_ids: Vec<String>,
+    /// Maximum number of hops
+    #[prost(uint32, tag = "3")]
+    pub max_depth: u32,
+    /// Edge labels to follow; empty follows all
+    #[prost(string, repeated, tag = "4")]
+    pub labels: Vec<String>,
+    /// Minimum learned edge probability
+    #[prost(float, tag = "5")]
+    pub min_probability: f32,
+    /// Maximum number of nodes; 0 uses the server default
+    #[prost(uint32, tag = "6")]
+    pub limit: u32,
+    /// Attach the stored entity to each node
+    #[prost(bool, tag = "7")]
+    pub include_entities: bool,
+}
+
+/// One reached entity
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TraversalNode {
+    /// Entity id
+    #[prost(string, tag = "1")]
+    pub id: String,
+    /// Hops from the nearest start entity
+    #[prost(uint32, tag = "2")]
+    pub depth: u32,
+    /// Entity it was reached from; empty for start entities
+    #[prost(string, tag = "3")]
+    pub parent_id: String,
+    /// Label of the edge followed
+    #[prost(string, tag = "4")]
+    pub label: String,
+    /// Product of edge probabilities along the path
+    #[prost(float, tag = "5")]
+    pub path_probability: f32,
+    /// Stored entity, when requested
+    #[prost(message, optional, tag = "6")]
+    pub entity: Option<Entity>,
+}
+
+/// Traversal results
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TraverseResponse {
+    /// Reached entities in breadth-first order
+    #[prost(message, repeated, tag = "1")]
+    pub nodes: Vec<TraversalNode>,
+}
+
+/// Cognitive query
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct QueryRequest {
+    /// Query text
+    #[prost(string, tag = "1")]
+    pub query: String,
+    /// Attach the stored entity to each row
+    #[prost(bool, tag = "2")]
+    pub include_entities: bool,
+}
+
+/// One cognitive query result
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct QueryRow {
+    /// Entity id
+    #[prost(string, tag = "1")]
+    pub id: String,
+    /// Hops from the nearest seed
+    #[prost(uint32, tag = "2")]
+    pub depth: u32,
+    /// Distance to the query vector, when the query has one
+    #[prost(float, optional, tag = "3")]
+    pub distance: Option<f32>,
+    /// PageRank within the candidates, when ranked by PageRank
+    #[prost(float, optional, tag = "4")]
+    pub pagerank: Option<f32>,
+    /// Stored entity, when requested
+    #[prost(message, optional, tag = "5")]
+    pub entity: Option<Entity>,
+}
+
+/// Cognitive query results, or the costed plan for `EXPLAIN`
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct QueryResponse {
+    /// Primary name of the queried collection
+    #[prost(string, tag = "1")]
+    pub collection: String,
+    /// Results; empty for `EXPLAIN`
+    #[prost(message, repeated, tag = "2")]
+    pub rows: Vec<QueryRow>,
+    /// Rendered plan; empty unless `EXPLAIN`
+    #[prost(string, tag = "3")]
+    pub plan: String,
+    /// Per-operator estimates as JSON; empty unless `EXPLAIN`
+    #[prost(string, tag = "4")]
+    pub explain_json: String,
+    /// Weighted total cost; 0 unless `EXPLAIN`
+    #[prost(double, tag = "5")]
+    pub estimated_cost: f64,
+}
+
+/// Distance metric of a collection
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Metric {
+    /// Server default (cosine)
+    Unspecified = 0,
+    /// Euclidean distance
+    Euclidean = 1,
+    /// Cosine distance
+    Cosine = 2,
+    /// Negated dot product
+    DotProduct = 3,
+}
+
+/// Vector index parameters
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct IndexConfig {
+    /// Graph links per node; 0 uses the default
+    #[prost(uint32, tag = "1")]
+    pub m: u32,
+    /// Build beam width; 0 uses the default
+    #[prost(uint32, tag = "2")]
+    pub ef_construction: u32,
+    /// Query beam width; 0 uses the default
+    #[prost(uint32, tag = "3")]
+    pub ef_search: u32,
+}
+
+/// Collection configuration
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CollectionConfig {
+    /// Vector dimension
+    #[prost(uint32, tag = "1")]
+    pub dimension: u32,
+    /// Distance metric
+    #[prost(enumeration = "Metric", tag = "2")]
+    pub metric: i32,
+    /// Index parameters
+    #[prost(message, optional, tag = "3")]
+    pub index: Option<IndexConfig>,
+    /// JSON metadata schema
+    #[prost(string, tag = "4")]
+    pub schema: String,
+    /// JSON tiering policy
+    #[prost(string, tag = "5")]
+    pub tiering: String,
+    /// JSON transaction concurrency mode
+    #[prost(string, tag = "6")]
+    pub concurrency: String,
+}
+
+/// Description of a collection
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CollectionInfo {
+    /// Collection id
+    #[prost(string, tag = "1")]
+    pub id: String,
+    /// Primary name
+    #[prost(string, tag = "2")]
+    pub name: String,
+    /// Aliases pointing at the collection
+    #[prost(string, repeated, tag = "3")]
+    pub aliases: Vec<String>,
+    /// Creation configuration
+    #[prost(message, optional, tag = "4")]
+    pub config: Option<CollectionConfig>,
+    /// Creation time
+    #[prost(uint64, tag = "5")]
+    pub created_at: u64,
+    /// Number of stored entities
+    #[prost(uint64, tag = "6")]
+    pub entity_count: u64,
+}
+
+/// Create a collection
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CreateCollectionRequest {
+    /// Collection name
+    #[prost(string, tag = "1")]
+    pub name: String,
+    /// Configuration
+    #[prost(message, optional, tag = "2")]
+    pub config: Option<CollectionConfig>,
+}
+
+/// Address of a collection
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CollectionRef {
+    /// Collection name (or alias, where accepted)
+    #[prost(string, tag = "1")]
+    pub name: String,
+}
+
+/// All collections
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListCollectionsResponse {
+    /// Collections ordered by name
+    #[prost(message, repeated, tag = "1")]
+    pub collections: Vec<CollectionInfo>,
+}
+
+/// Point an alias at a collection
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SetAliasRequest {
+    /// Alias to create or re-point
+    #[prost(string, tag = "1")]
+    pub alias: String,
+    /// Target collection name or alias
+    #[prost(string, tag = "2")]
+    pub target: String,
+}
+
+/// Remove an alias
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DropAliasRequest {
+    /// Alias to remove
+    #[prost(string, tag = "1")]
+    pub alias: String,
+}
+
+/// A cluster member
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ClusterMember {
+    /// Node id
+    #[prost(string, tag = "1")]
+    pub id: String,
+    /// Network address
+    #[prost(string, tag = "2")]
+    pub address: String,
+    /// Zone label
+    #[prost(string, tag = "3")]
+    pub zone: String,
+    /// alive, suspect, dead or left
+    #[prost(string, tag = "4")]
+    pub state: String,
+    /// Incarnation number
+    #[prost(uint64, tag = "5")]
+    pub incarnation: u64,
+    /// Whether the member is draining
+    #[prost(bool, tag = "6")]
+    pub decommissioning: bool,
+}
+
+/// Status of the serving node and cluster
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ClusterStatusResponse {
+    /// Serving node id
+    #[prost(string, tag = "1")]
+    pub node_id: String,
+    /// Server version
+    #[prost(string, tag = "2")]
+    pub version: String,
+    /// Uptime in milliseconds
+    #[prost(uint64, tag = "3")]
+    pub uptime_ms: u64,
+    /// Number of collections
+    #[prost(uint64, tag = "4")]
+    pub collections: u64,
+    /// Number of entities
+    #[prost(uint64, tag = "5")]
+    pub entities: u64,
+    /// Known members
+    #[prost(message, repeated, tag = "6")]
+    pub members: Vec<ClusterMember>,
+}
+
+/// Address of an open transaction
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TransactionRef {
+    /// Collection name or alias
+    #[prost(string, tag = "1")]
+    pub collection: String,
+    /// Id returned by `Begin`
+    #[prost(uint64, tag = "2")]
+    pub transaction_id: u64,
+}
+
+/// Address of an entity within an open transaction
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TransactionEntityRef {
+    /// Collection name or alias
+    #[prost(string, tag = "1")]
+    pub collection: String,
+    /// Transaction id
+    #[prost(uint64, tag = "2")]
+    pub transaction_id: u64,
+    /// Entity id
+    #[prost(string, tag = "3")]
+    pub id: String,
+}
+
+/// Write an entity within an open transaction
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TransactionWriteRequest {
+    /// Collection name or alias
+    #[prost(string, tag = "1")]
+    pub collection: String,
+    /// Transaction id
+    #[prost(uint64, tag = "2")]
+    pub transaction_id: u64,
+    /// Entity to insert or replace
+    #[prost(message, optional, tag = "3")]
+    pub entity: Option<Entity>,
+}
+
+/// Result of a commit
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CommitTransactionResponse {
+    /// Commit timestamp of every written entity
+    #[prost(uint64, tag = "1")]
+    pub commit_ts: u64,
+}
+
+/// Kind of change
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum ChangeKind {
+    /// Not set
+    Unspecified = 0,
+    /// Entity created or replaced
+    Upsert = 1,
+    /// Entity removed
+    Delete = 2,
+}
+
+/// Subscribe to the change feed
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SubscribeRequest {
+    /// Collections to follow; empty follows all
+    #[prost(string, repeated, tag = "1")]
+    pub collections: Vec<String>,
+}
+
+/// A committed change
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ChangeEvent {
+    /// Position in the server's change sequence
+    #[prost(uint64, tag = "1")]
+    pub sequence: u64,
+    /// Primary collection name
+    #[prost(string, tag = "2")]
+    pub collection: String,
+    /// Kind of change
+    #[prost(enumeration = "ChangeKind", tag = "3")]
+    pub kind: i32,
+    /// Affected entity id
+    #[prost(string, tag = "4")]
+    pub entity_id: String,
+    /// New entity state; absent for deletes
+    #[prost(message, optional, tag = "5")]
+    pub entity: Option<Entity>,
+    /// Commit time
+    #[prost(uint64, tag = "6")]
+    pub timestamp: u64,
+}
+
+/// Resume the change data capture stream
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CdcRequest {
+    /// Last sequence already processed; 0 starts from the first change
+    #[prost(uint64, tag = "1")]
+    pub after_sequence: u64,
+    /// Collections to follow; empty follows all
+    #[prost(string, repeated, tag = "2")]
+    pub collections: Vec<String>,
+}
+
+/// An edge together with its source entity
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EdgeChange {
+    /// Source entity id
+    #[prost(string, tag = "1")]
+    pub source_id: String,
+    /// Edge state
+    #[prost(message, optional, tag = "2")]
+    pub edge: Option<Edge>,
+}
+
+/// Identity of an edge
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EdgeKey {
+    /// Source entity id
+    #[prost(string, tag = "1")]
+    pub source_id: String,
+    /// Target entity id
+    #[prost(string, tag = "2")]
+    pub target_id: String,
+    /// Relationship label
+    #[prost(string, tag = "3")]
+    pub label: String,
+}
+
+/// Entity moved between tiers
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TierMove {
+    /// Moved entity id
+    #[prost(string, tag = "1")]
+    pub entity_id: String,
+    /// Previous tier
+    #[prost(enumeration = "Tier", tag = "2")]
+    pub from: i32,
+    /// New tier
+    #[prost(enumeration = "Tier", tag = "3")]
+    pub to: i32,
+}
+
+/// A captured change
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CdcEvent {
+    /// Position in the change sequence
+    #[prost(uint64, tag = "1")]
+    pub sequence: u64,
+    /// Log record the change was derived from
+    #[prost(uint64, tag = "2")]
+    pub lsn: u64,
+    /// Primary collection name
+    #[prost(string, tag = "3")]
+    pub collection: String,
+    /// Commit time
+    #[prost(uint64, tag = "4")]
+    pub timestamp: u64,
+    /// What changed
+    #[prost(oneof = "cdc_event::Change", tags = "5, 6, 7, 8, 9, 10")]
+    pub change: Option<cdc_event::Change>,
+}
+
+/// Nested types of `CdcEvent`
+pub mod cdc_event {
+    /// What changed
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Change {
+        /// Entity created or replaced
+        #[prost(message, tag = "5")]
+        EntityUpsert(super::Entity),
+        /// Id of the removed entity
+        #[prost(string, tag = "6")]
+        EntityDelete(String),
+        /// Edge added
+        #[prost(message, tag = "7")]
+        EdgeCreate(super::EdgeChange),
+        /// Edge weight, probability or metadata changed
+        #[prost(message, tag = "8")]
+        EdgeUpdate(super::EdgeChange),
+        /// Edge removed
+        #[prost(message, tag = "9")]
+        EdgePrune(super::EdgeKey),
+        /// Entity moved between tiers
+        #[prost(message, tag = "10")]
+        TierMove(super::TierMove),
+    }
+}
+
+/// Parse a UUID string into an entity id
+pub fn parse_entity_id(id: &str) -> Result<EntityId> {
+    uuid::Uuid::parse_str(id)
+        .map(EntityId::from_uuid)
+        .map_err(|error| MemorySubstrateError::InvalidRequest(format!("invalid entity id '{}': {}", id, error)))
+}
+
+fn parse_json<T: DeserializeOwned>(field: &str, json: &str) -> Result<Option<T>> {
+    if json.is_empty() {
+        return Ok(None);
+    }
+    serde_json::from_str(json)
+        .map(Some)
+        .map_err(|error| MemorySubstrateError::InvalidRequest(format!("invalid {} JSON: {}", field, error)))
+}
+
+fn to_json<T: Serialize>(value: Option<&T>) -> String {
+    value
+        .and_then(|value| serde_json::to_string(value).ok())
+        .unwrap_or_default()
+}
+
+impl From<MemoryTier> for Tier {
+    fn from(tier: MemoryTier) -> Self {
+        match tier {
+            MemoryTier::Hot => Self::Hot,
+            MemoryTier::Warm => Self::Warm,
+            MemoryTier::Cold => Self::Cold,
+        }
+    }
+}
+
+impl From<&DomainEdge> for Edge {
+    fn from(edge: &DomainEdge) -> Self {
+        Self {
+            target_id: edge.target_id.to_string(),
+            label: edge.label.clone(),
+            weight: edge.weight,
+            metadata: to_json(edge.metadata.as_ref()),
+            probability: edge.probability(),
+        }
+    }
+}
+
+impl From<&DomainEntity> for Entity {
+    fn from(entity: &DomainEntity) -> Self {
+        Self {
+            id: entity.id.to_string(),
+            vector: entity.vector.as_ref().map(|v| v.values.clone()).unwrap_or_default(),
+            metadata: to_json(entity.metadata.as_ref()),
+            edges: entity.edges.iter().flatten().map(Edge::from).collect(),
+            created_at: entity.created_at,
+            updated_at: entity.updated_at,
+            version: entity.version,
+            tier: Tier::from(entity.tier) as i32,
+        }
+    }
+}
+
+impl TryFrom<Entity> for DomainEntity {
+    type Error = MemorySubstrateError;
+
+    fn try_from(wire: Entity) -> Result<Self> {
+        let id = if wire.id.is_empty() {
+            EntityId::new()
+        } else {
+            parse_entity_id(&wire.id)?
+        };
+        let edges = wire
+            .edges
+            .iter()
+            .map(|edge| {
+                Ok(DomainEdge::new(
+                    id,
+                    parse_entity_id(&edge.target_id)?,
+                    edge.label.clone(),
+                    edge.weight,
+                    parse_json("edge metadata", &edge.metadata)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let tier = match wire.tier() {
+            Tier::Unspecified | Tier::Hot => MemoryTier::Hot,
+            Tier::Warm => MemoryTier::Warm,
+            Tier::Cold => MemoryTier::Cold,
+        };
+
+        let mut entity = DomainEntity::new(
+            (!wire.vector.is_empty()).then(|| Vector::new(wire.vector)),
+            parse_json("metadata", &wire.metadata)?,
+            (!edges.is_empty()).then_some(edges),
+        );
+        entity.id = id;
+        entity.tier = tier;
+        Ok(entity)
+    }
+}
+
+impl From<Option<DomainFilterStrategy>> for FilterStrategy {
+    fn from(strategy: Option<DomainFilterStrategy>) -> Self {
+        match strategy {
+            None => Self::Unspecified,
+            Some(DomainFilterStrategy::PreFilter) => Self::PreFilter,
+            Some(DomainFilterStrategy::PostFilter) => Self::PostFilter,
+            Some(DomainFilterStrategy::InGraph) => Self::InGraph,
+        }
+    }
+}
+
+impl From<FilterStrategy> for Option<DomainFilterStrategy> {
+    fn from(strategy: FilterStrategy) -> Self {
+        match strategy {
+            FilterStrategy::Unspecified => None,
+            FilterStrategy::PreFilter => Some(DomainFilterStrategy::PreFilter),
+            FilterStrategy::PostFilter => Some(DomainFilterStrategy::PostFilter),
+            FilterStrategy::InGraph => Some(DomainFilterStrategy::InGraph),
+        }
+    }
+}
+
+impl SearchRequest {
+    /// Decode into a vector query
+    pub fn to_query(&self) -> Result<VectorQuery> {
+        Ok(VectorQuery {
+            vector: Vector::new(self.vector.clone()),
+            k: self.k as usize,
+            filter: parse_json("filter", &self.filter)?,
+            strategy: self.strategy().into(),
+            ef: (self.ef > 0).then_some(self.ef as usize),
+        })
+    }
+}
+
+impl TraverseRequest {
+    /// Decode into a graph query
+    pub fn to_query(&self) -> Result<GraphQuery> {
+        let start = self
+            .start_ids
+            .iter()
+            .map(|id| parse_entity_id(id))
+            .collect::<Result<Vec<_>>>()?;
+        let mut query = GraphQuery::new(start, self.max_depth as usize)
+            .with_labels(self.labels.clone())
+            .with_min_probability(self.min_probability);
+        if self.limit > 0 {
+            query = query.with_limit(self.limit as usize);
+        }
+        Ok(query)
+    }
+}
+
+impl TryFrom<CollectionConfig> for DomainCollectionConfig {
+    type Error = MemorySubstrateError;
+
+    fn try_from(wire: CollectionConfig) -> Result<Self> {
+        let mut config = DomainCollectionConfig::new(wire.dimension as usize);
+        config.metric = match wire.metric() {
+            Metric::Unspecified => DistanceMetric::default(),
+            Metric::Euclidean => DistanceMetric::Euclidean,
+            Metric::Cosine => DistanceMetric::Cosine,
+            Metric::DotProduct => DistanceMetric::DotProduct,
+        };
+        if let Some(index) = wire.index {
+            let defaults = DomainIndexConfig::default();
+            let or_default = |value: u32, default: usize| if value == 0 { default } else { value as usize };
+            config.index = DomainIndexConfig {
+                m: or_default(index.m, defaults.m),
+                ef_construction: or_default(index.ef_construction, defaults.ef_construction),
+                ef_search: or_default(index.ef_search, defaults.ef_search),
+            };
+        }
+        config.schema = parse_json("schema", &wire.schema)?;
+        if let Some(tiering) = parse_json::<TieringConfig>("tiering", &wire.tiering)? {
+            config.tiering = tiering;
+        }
+        if let Some(concurrency) = parse_json("concurrency", &wire.concurrency)? {
+            config.concurrency = concurrency;
+        }
+        Ok(config)
+    }
+}
+
+impl From<&DomainCollectionConfig> for CollectionConfig {
+    fn from(config: &DomainCollectionConfig) -> Self {
+        let metric = match config.metric {
+            DistanceMetric::Euclidean => Metric::Euclidean,
+            DistanceMetric::Cosine => Metric::Cosine,
+            DistanceMetric::DotProduct => Metric::DotProduct,
+        };
+        Self {
+            dimension: config.dimension as u32,
+            metric: metric as i32,
+            index: Some(IndexConfig {
+                m: config.index.m as u32,
+                ef_construction: config.index.ef_construction as u32,
+                ef_search: config.index.ef_search as u32,
+            }),
+            schema: to_json(config.schema.as_ref()),
+            tiering: to_json(Some(&config.tiering)),
+            concurrency: to_json(Some(&config.concurrency)),
+        }
+    }
+}
+
+impl From<&DomainCollectionInfo> for CollectionInfo {
+    fn from(info: &DomainCollectionInfo) -> Self {
+        Self {
+            id: info.id.to_string(),
+            name: info.name.clone(),
+            aliases: info.aliases.clone(),
+            config: Some((&info.config).into()),
+            created_at: info.created_at,
+            entity_count: info.entity_count as u64,
+        }
+    }
+}
+
+impl From<BatchOutcome> for BatchUpsertResponse {
+    fn from(outcome: BatchOutcome) -> Self {
+        Self {
+            upserted: outcome.upserted as u64,
+            rejected: outcome
+                .rejected
+                .into_iter()
+                .map(|rejection| RejectedEntity {
+                    index: rejection.index as u64,
+                    id: rejection.entity_id.to_string(),
+                    reason: rejection.reason,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&Member> for ClusterMember {
+    fn from(member: &Member) -> Self {
+        let state = match member.state {
+            MemberState::Alive => "alive",
+            MemberState::Suspect => "suspect",
+            MemberState::Dead => "dead",
+            MemberState::Left => "left",
+        };
+        Self {
+            id: member.id.to_string(),
+            address: member.address.clone(),
+            zone: member.zone.clone(),
+            state: state.to_string(),
+            incarnation: member.incarnation,
+            decommissioning: member.decommissioning,
+        }
+    }
+}
+
+impl From<&ClusterStatus> for ClusterStatusResponse {
+    fn from(status: &ClusterStatus) -> Self {
+        Self {
+            node_id: status.node_id.to_string(),
+            version: status.version.clone(),
+            uptime_ms: status.uptime_ms,
+            collections: status.collections as u64,
+            entities: status.entities as u64,
+            members: status.members.iter().map(ClusterMember::from).collect(),
+        }
+    }
+}
+
+impl From<&DomainChangeEvent> for ChangeEvent {
+    fn from(event: &DomainChangeEvent) -> Self {
+        let kind = match event.kind {
+            DomainChangeKind::Upsert => ChangeKind::Upsert,
+            DomainChangeKind::Delete => ChangeKind::Delete,
+        };
+        Self {
+            sequence: event.sequence,
+            collection: event.collection.clone(),
+            kind: kind as i32,
+            entity_id: event.entity_id.to_string(),
+            entity: event.entity.as_ref().map(Entity::from),
+            timestamp: event.timestamp,
+        }
+    }
+}
+
+impl From<&DomainCdcEvent> for CdcEvent {
+    fn from(event: &DomainCdcEvent) -> Self {
+        let edge_change = |edge: &DomainEdge| EdgeChange {
+            source_id: edge.source_id.to_string(),
+            edge: Some(edge.into()),
+        };
+        let change = match &event.change {
+            DomainChange::EntityUpsert { entity } => cdc_event::Change::EntityUpsert(entity.as_ref().into()),
+            DomainChange::EntityDelete { entity_id } => cdc_event::Change::EntityDelete(entity_id.to_string()),
+            DomainChange::EdgeCreate { edge } => cdc_event::Change::EdgeCreate(edge_change(edge)),
+            DomainChange::EdgeUpdate { edge } => cdc_event::Change::EdgeUpdate(edge_change(edge)),
+            DomainChange::EdgePrune {
+                source_id,
+                target_id,
+                label,
+            } => cdc_event::Change::EdgePrune(EdgeKey {
+                source_id: source_id.to_string(),
+                target_id: target_id.to_string(),
+                label: label.clone(),
+            }),
+            DomainChange::TierMove { entity_id, from, to } => cdc_event::Change::TierMove(TierMove {
+                entity_id: entity_id.to_string(),
+                from: Tier::from(*from) as i32,
+                to: Tier::from(*to) as i32,
+            }),
+        };
+        Self {
+            sequence: event.sequence,
+            lsn: event.lsn,
+            collection: event.collection.clone(),
+            timestamp: event.timestamp,
+            change: Some(change),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::collection::ConcurrencyMode;
+    use prost::Message;
+    use serde_json::json;
+
+    #[test]
+    fn test_entity_round_trips_through_wire_encoding() {
+        let target = EntityId::new();
+        let mut entity = DomainEntity::new(
+            Some(Vector::new(vec![0.25, -1.0])),
+            Some(json!({"kind": "note"})),
+            None,
+        );
+        entity.edges = Some(vec![DomainEdge::new(entity.id, target, "cites".into(), 0.5, None)]);
+        entity.tier = MemoryTier::Warm;
+
+        let bytes = Entity::from(&entity).encode_to_vec();
+        let decoded = DomainEntity::try_from(Entity::decode(bytes.as_slice()).unwrap()).unwrap();
+
+        assert_eq!(decoded.id, entity.id);
+        assert_eq!(decoded.vector.unwrap().values, vec![0.25, -1.0]);
+        assert_eq!(decoded.metadata, Some(json!({"kind": "note"})));
+        assert_eq!(decoded.tier, MemoryTier::Warm);
+        let edges = decoded.edges.unwrap();
+        assert_eq!((edges[0].source_id, edges[0].target_id), (entity.id, target));
+        assert_eq!(edges[0].label, "cites");
+    }
+
+    #[test]
+    fn test_malformed_requests_are_rejected() {
+        let bad_id = Entity {
+            id: "not-a-uuid".into(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            DomainEntity::try_from(bad_id),
+            Err(MemorySubstrateError::InvalidRequest(_))
+        ));
+
+        let search = SearchRequest {
+            vector: vec![1.0],
+            k: 3,
+            filter: "{".into(),
+            ..Default::default()
+        };
+        assert!(search.to_query().is_err());
+
+        let config = CollectionConfig {
+            dimension: 8,
+            index: Some(IndexConfig {
+                m: 8,
+                ..Default::default()
+            }),
+            concurrency: r#"{"Pessimistic": {"lock_timeout_ms": 100}}"#.into(),
+            ..Default::default()
+        };
+        let decoded = DomainCollectionConfig::try_from(config).unwrap();
+        assert_eq!(decoded.index.m, 8);
+        assert_eq!(decoded.index.ef_search, DomainIndexConfig::default().ef_search);
+        assert_eq!(decoded.concurrency, ConcurrencyMode::Pessimistic { lock_timeout_ms: 100 });
+        assert_eq!(CollectionConfig::from(&decoded).concurrency, r#"{"Pessimistic":{"lock_timeout_ms":100}}"#);
+    }
+}
@@ -0,0 +1,544 @@
+//! Cost-based planner for cognitive queries
+//!
+//! A query has several equivalent physical plans. Without `NEAR` the seeds
+//! come from a scan or from the metadata indexes; with `NEAR` the filter can
+//! be applied before, during or after the vector search; and with both
+//! `NEAR` and `WITHIN` the planner chooses between walking the neighbourhood
+//! and ranking it exactly (graph first) or over-fetching nearest neighbours
+//! and keeping those inside it (vector first).
+//!
+//! Every candidate is costed with [`CostModel`] against the collection's
+//! current statistics and the cheapest one wins. Queries ranked by distance
+//! may additionally drop cold seeds when hot and warm seeds are nearly as
+//! close, which avoids cold-tier reads for a bounded loss in distance.
+//!
+//! Chosen plans are cached by collection and query *shape* (the query with
+//! its vector, entity and filter values blanked), so repeated queries skip
+//! statistics collection. A cached plan is re-planned once the collection's
+//! size drifts by more than `PlannerConfig::replan_drift`.
+
+use crate::api::cognitive_query::{
+    AccessPath, Alternative, CognitiveQuery, CostModel, Explain, LogicalPlan, Near, PlanChoice, PlanStatistics,
+    Ranking,
+};
+use crate::core::config::{CostWeights, PlannerConfig};
+use crate::core::metadata::{MetadataFilter, RangeBound};
+use crate::core::query::FilterStrategy;
+use crate::core::{CollectionId, EntityId, MemoryTier};
+use crate::storage::collection::Collection;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Neighbours fetched per expected match when searching vector first
+const VECTOR_FIRST_OVERFETCH: f64 = 2.0;
+
+/// Filter strategies weighed for filtered vector searches
+const STRATEGIES: [FilterStrategy; 3] = [
+    FilterStrategy::PreFilter,
+    FilterStrategy::InGraph,
+    FilterStrategy::PostFilter,
+];
+
+/// A plan ready to execute
+#[derive(Debug, Clone)]
+pub struct PlannedQuery {
+    /// Logical plan lowered with `choice`
+    pub plan: LogicalPlan,
+
+    /// Physical choices of the cheapest candidate
+    pub choice: PlanChoice,
+
+    /// Candidates weighed, cheapest first
+    pub alternatives: Vec<Alternative>,
+
+    /// Whether the choice came from the plan cache
+    pub cached: bool,
+}
+
+/// Plan cache counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanCacheStats {
+    /// Lookups answered from the cache
+    pub hits: u64,
+
+    /// Lookups that had to plan, including drift re-plans
+    pub misses: u64,
+
+    /// Plans currently cached
+    pub entries: usize,
+}
+
+#[derive(Debug)]
+struct CachedPlan {
+    choice: PlanChoice,
+    alternatives: Vec<Alternative>,
+    entities: usize,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct PlanCache {
+    plans: HashMap<(CollectionId, String), CachedPlan>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+/// Chooses the cheapest physical plan of a query and caches the choice
+#[derive(Debug)]
+pub struct QueryPlanner {
+    cost_model: CostModel,
+    config: PlannerConfig,
+    cache: Mutex<PlanCache>,
+}
+
+impl QueryPlanner {
+    /// Create a planner combining costs with `weights`
+    pub fn new(weights: CostWeights, config: PlannerConfig) -> Self {
+        Self {
+            cost_model: CostModel::new(weights),
+            config,
+            cache: Mutex::new(PlanCache::default()),
+        }
+    }
+
+    /// Model candidates are costed with
+    pub fn cost_model(&self) -> &CostModel {
+        &self.cost_model
+    }
+
+    /// Planner settings
+    pub fn config(&self) -> &PlannerConfig {
+        &self.config
+    }
+
+    /// Physical plans equivalent to `query`, up to cold pruning
+    pub fn candidates(&self, query: &CognitiveQuery, statistics: &PlanStatistics) -> Vec<PlanChoice> {
+        let accesses: Vec<AccessPath> = match (&query.near, &query.within, &query.filter) {
+            (None, None, None) => vec![AccessPath::Scan],
+            (None, None, Some(_)) => vec![AccessPath::Scan, AccessPath::IndexScan],
+            (None, Some(_), _) => vec![AccessPath::Neighborhood],
+            (Some(_), None, None) => vec![AccessPath::VectorSearch { strategy: None }],
+            (Some(_), None, Some(_)) => STRATEGIES
+                .iter()
+                .map(|strategy| AccessPath::VectorSearch {
+                    strategy: Some(*strategy),
+                })
+                .collect(),
+            (Some(_), Some(within), filter) => {
+                let k = query.candidates();
+                let n = statistics.entities.max(k);
+                let (reached, _) = statistics.walk(1.0, &within.edges);
+                let inside = reached / statistics.entities.max(1) as f64;
+                let fetch = ((k as f64 / inside.max(f64::MIN_POSITIVE)) * VECTOR_FIRST_OVERFETCH).ceil();
+                let fetch = (fetch.min(n as f64) as usize).clamp(k, n);
+                let strategies: Vec<Option<FilterStrategy>> = match filter {
+                    Some(_) => STRATEGIES.iter().copied().map(Some).collect(),
+                    None => vec![None],
+                };
+                std::iter::once(AccessPath::GraphFirst)
+                    .chain(
+                        strategies
+                            .into_iter()
+                            .map(|strategy| AccessPath::VectorFirst { strategy, fetch }),
+                    )
+                    .collect()
+            }
+        };
+
+        // Cold pruning only makes sense when distance decides the answer
+        // and cold entities may be part of it
+        let ranked_by_distance = query.near.is_some() && matches!(query.rank, None | Some(Ranking::Distance));
+        let cold_allowed = query.tiers.is_empty() || query.tiers.contains(&MemoryTier::Cold);
+        let prune = self.config.cold_prune_slack.filter(|_| ranked_by_distance && cold_allowed);
+
+        let mut choices = Vec::with_capacity(accesses.len() * 2);
+        for access in accesses {
+            choices.push(PlanChoice {
+                access,
+                prune_cold: None,
+            });
+            if let Some(slack) = prune {
+                choices.push(PlanChoice {
+                    access,
+                    prune_cold: Some(slack),
+                });
+            }
+        }
+        choices
+    }
+
+    /// Cost every candidate of `query` against `collection`, cheapest first
+    ///
+    /// Ties keep candidate order, so the unpruned and more exact plan wins.
+    pub fn optimize(&self, query: &CognitiveQuery, collection: &Collection) -> Vec<Alternative> {
+        let statistics = PlanStatistics::collect(collection);
+        let index = collection.index();
+        let metadata = index.metadata();
+        let mut alternatives: Vec<Alternative> = self
+            .candidates(query, &statistics)
+            .into_iter()
+            .map(|choice| {
+                let plan = query.plan_with(&choice);
+                let explain =
+                    self.cost_model
+                        .estimate(&plan, statistics.clone(), |filter| metadata.estimate_selectivity(filter));
+                Alternative {
+                    choice,
+                    weighted_cost: explain.weighted_cost,
+                    chosen: false,
+                }
+            })
+            .collect();
+        alternatives.sort_by(|a, b| a.weighted_cost.total_cmp(&b.weighted_cost));
+        if let Some(best) = alternatives.first_mut() {
+            best.chosen = true;
+        }
+        alternatives
+    }
+
+    /// Choose the plan to run `query` with, from the cache when possible
+    pub fn plan(&self, query: &CognitiveQuery, collection: &Collection) -> PlannedQuery {
+        let key = (collection.id(), query_shape(query));
+        let entities = collection.len();
+
+        let cached = {
+            let mut cache = self.cache.lock();
+            cache.tick += 1;
+            let tick = cache.tick;
+            let drift = self.config.replan_drift;
+            let fresh = cache.plans.get_mut(&key).and_then(|entry| {
+                let changed = entities.abs_diff(entry.entities) as f64 / entry.entities.max(1) as f64;
+                (changed <= drift).then(|| {
+                    entry.last_used = tick;
+                    (entry.choice, entry.alternatives.clone())
+                })
+            });
+            match fresh {
+                Some(_) => cache.hits += 1,
+                None => cache.misses += 1,
+            }
+            fresh
+        };
+        if let Some((choice, alternatives)) = cached {
+            return PlannedQuery {
+                plan: query.plan_with(&choice),
+                choice,
+                alternatives,
+                cached: true,
+            };
+        }
+
+        let alternatives = self.optimize(query, collection);
+        let choice = alternatives
+            .first()
+            .map_or_else(|| query.default_choice(), |best| best.choice);
+        if self.config.plan_cache_capacity > 0 {
+            let mut cache = self.cache.lock();
+            if !cache.plans.contains_key(&key) && cache.plans.len() >= self.config.plan_cache_capacity {
+                let oldest = cache
+                    .plans
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    cache.plans.remove(&oldest);
+                }
+            }
+            let last_used = cache.tick;
+            cache.plans.insert(
+                key,
+                CachedPlan {
+                    choice,
+                    alternatives: alternatives.clone(),
+                    entities,
+                    last_used,
+                },
+            );
+        }
+        PlannedQuery {
+            plan: query.plan_with(&choice),
+            choice,
+            alternatives,
+            cached: false,
+        }
+    }
+
+    /// Cost the plan `query` would run with, listing the alternatives
+    pub fn explain(&self, query: &CognitiveQuery, collection: &Collection) -> Explain {
+        let planned = self.plan(query, collection);
+        let mut explain = self.cost_model.explain(&planned.plan, collection);
+        explain.alternatives = planned.alternatives;
+        explain.cached = planned.cached;
+        explain
+    }
+
+    /// Plan cache counters
+    pub fn cache_stats(&self) -> PlanCacheStats {
+        let cache = self.cache.lock();
+        PlanCacheStats {
+            hits: cache.hits,
+            misses: cache.misses,
+            entries: cache.plans.len(),
+        }
+    }
+
+    /// Forget every cached plan, for example after a schema change
+    pub fn clear_cache(&self) {
+        self.cache.lock().plans.clear();
+    }
+}
+
+/// Cache key of a query: the query with its literal values blanked
+///
+/// Queries differing only in the query vector, anchor entity or compared
+/// values share a plan; the vector dimension is fixed per collection.
+fn query_shape(query: &CognitiveQuery) -> String {
+    let blank_id = EntityId::from_uuid(uuid::Uuid::nil());
+    let mut shape = query.clone();
+    shape.explain = false;
+    shape.near = shape.near.map(|near| match near {
+        Near::Vector(_) => Near::Vector(Vec::new()),
+        Near::Entity(_) => Near::Entity(blank_id),
+    });
+    if let Some(within) = &mut shape.within {
+        within.anchor = blank_id;
+    }
+    shape.filter = shape.filter.as_ref().map(blank_values);
+    serde_json::to_string(&shape).unwrap_or_default()
+}
+
+fn blank_values(filter: &MetadataFilter) -> MetadataFilter {
+    let bound = |bound: &Option<RangeBound>| {
+        bound.as_ref().map(|bound| RangeBound {
+            value: Value::Null,
+            inclusive: bound.inclusive,
+        })
+    };
+    match filter {
+        MetadataFilter::Eq { field, .. } => MetadataFilter::Eq {
+            field: field.clone(),
+            value: Value::Null,
+        },
+        MetadataFilter::Ne { field, .. } => MetadataFilter::Ne {
+            field: field.clone(),
+            value: Value::Null,
+        },
+        MetadataFilter::Range { field, lower, upper } => MetadataFilter::Range {
+            field: field.clone(),
+            lower: bound(lower),
+            upper: bound(upper),
+        },
+        MetadataFilter::In { field, values } => MetadataFilter::In {
+            field: field.clone(),
+            values: vec![Value::Null; values.len()],
+        },
+        MetadataFilter::Exists { field } => MetadataFilter::Exists { field: field.clone() },
+        MetadataFilter::And { filters } => MetadataFilter::And {
+            filters: filters.iter().map(blank_values).collect(),
+        },
+        MetadataFilter::Or { filters } => MetadataFilter::Or {
+            filters: filters.iter().map(blank_values).collect(),
+        },
+        MetadataFilter::Not { filter } => MetadataFilter::Not {
+            filter: Box::new(blank_values(filter)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::cognitive_query::{execute, Operator};
+    use crate::core::collection::{CollectionConfig, IndexConfig};
+    use crate::core::{Edge, Entity, Vector};
+    use serde_json::json;
+
+    /// 40 entities on a circle, each citing the next; a quarter are cold
+    fn collection() -> (Collection, Vec<EntityId>) {
+        let config = CollectionConfig::new(2).with_index(IndexConfig {
+            m: 8,
+            ef_construction: 32,
+            ef_search: 32,
+        });
+        let collection = Collection::new(CollectionId::new(), "papers".to_string(), config, 0);
+        let ids: Vec<EntityId> = (0..40).map(|_| EntityId::new()).collect();
+        for (i, id) in ids.iter().enumerate() {
+            let angle = i as f32 * std::f32::consts::TAU / 40.0;
+            let mut entity = Entity::new(
+                Some(Vector::new(vec![angle.cos(), angle.sin()])),
+                Some(json!({"lang": if i % 2 == 0 { "en" } else { "de" }, "year": 2000 + i})),
+                None,
+            );
+            entity.id = *id;
+            entity.edges = Some(vec![Edge::new(*id, ids[(i + 1) % 40], "cites".to_string(), 0.9, None)]);
+            if i % 4 == 3 {
+                entity.tier = MemoryTier::Cold;
+            }
+            collection.upsert(entity).unwrap();
+        }
+        (collection, ids)
+    }
+
+    fn planner() -> QueryPlanner {
+        QueryPlanner::new(CostWeights::default(), PlannerConfig::default())
+    }
+
+    #[test]
+    fn test_equivalent_plans_agree() {
+        let (collection, ids) = collection();
+        let planner = planner();
+
+        let text = format!(
+            "FIND IN papers NEAR [1, 0] K 3 WITHIN 5 HOPS OF ENTITY \"{}\" ALONG \"cites\" WHERE lang = \"en\" LIMIT 3",
+            ids[0]
+        );
+        let query = CognitiveQuery::parse(&text).unwrap();
+        let statistics = PlanStatistics::collect(&collection);
+        let candidates = planner.candidates(&query, &statistics);
+        assert!(candidates.iter().any(|c| c.access == AccessPath::GraphFirst));
+        assert!(candidates
+            .iter()
+            .any(|c| matches!(c.access, AccessPath::VectorFirst { fetch, .. } if fetch >= 3)));
+
+        // Entities 0..=5 are in the neighbourhood; the English ones closest to [1, 0]
+        let expected = vec![ids[0], ids[2], ids[4]];
+        for choice in candidates.iter().filter(|c| c.prune_cold.is_none()) {
+            let rows = execute(&query.plan_with(choice), &collection.view()).unwrap();
+            assert_eq!(rows.iter().map(|r| r.id).collect::<Vec<_>>(), expected, "{}", choice);
+        }
+
+        let query = CognitiveQuery::parse("FIND IN papers NEAR [1, 0] K 4 WHERE year >= 2030 LIMIT 4").unwrap();
+        let mut results = Vec::new();
+        for choice in planner.candidates(&query, &statistics) {
+            assert!(matches!(choice.access, AccessPath::VectorSearch { strategy: Some(_) }));
+            if choice.prune_cold.is_none() {
+                let rows = execute(&query.plan_with(&choice), &collection.view()).unwrap();
+                results.push(rows.iter().map(|r| r.id).collect::<Vec<_>>());
+            }
+        }
+        assert_eq!(results.len(), 3);
+        assert!(results.windows(2).all(|pair| pair[0] == pair[1]));
+        assert_eq!(results[0], vec![ids[39], ids[38], ids[37], ids[36]]);
+
+        let query = CognitiveQuery::parse("FIND IN papers WHERE lang = \"de\" LIMIT 100").unwrap();
+        let scan_plan = query.plan_with(&PlanChoice { access: AccessPath::Scan, prune_cold: None });
+        let scan = execute(&scan_plan, &collection.view());
+        let indexed = execute(
+            &query.plan_with(&PlanChoice {
+                access: AccessPath::IndexScan,
+                prune_cold: None,
+            }),
+            &collection.view(),
+        );
+        assert_eq!(scan.unwrap(), indexed.unwrap());
+    }
+
+    #[test]
+    fn test_prune_cold_drops_cold_seeds() {
+        let (collection, ids) = collection();
+        let query = CognitiveQuery::parse("FIND IN papers NEAR [1, 0] K 4 LIMIT 3").unwrap();
+        let exact = execute(&query.plan(), &collection.view()).unwrap();
+        assert!(exact.iter().any(|r| r.id == ids[39]));
+
+        // Entity 39 is cold; replacing it with entity 2, 18 degrees off instead
+        // of 9, costs about three times the cosine distance
+        let plan = query.plan_with(&PlanChoice {
+            access: AccessPath::VectorSearch { strategy: None },
+            prune_cold: Some(4.0),
+        });
+        assert!(plan.operators.contains(&Operator::PruneCold { keep: 3, slack: 4.0 }));
+        let pruned = execute(&plan, &collection.view()).unwrap();
+        assert_eq!(pruned.len(), 3);
+        assert!(pruned.iter().all(|r| collection.get(&r.id).unwrap().tier != MemoryTier::Cold));
+
+        // Without slack the cold entity is part of the exact answer and stays
+        let plan = query.plan_with(&PlanChoice {
+            access: AccessPath::VectorSearch { strategy: None },
+            prune_cold: Some(0.0),
+        });
+        assert_eq!(execute(&plan, &collection.view()).unwrap(), exact);
+
+        // The model credits pruning with fewer cold reads
+        let alternatives = planner().optimize(&query, &collection);
+        let cost = |pruned: bool| {
+            alternatives
+                .iter()
+                .find(|a| a.choice.prune_cold.is_some() == pruned)
+                .unwrap()
+                .weighted_cost
+        };
+        assert!(cost(true) < cost(false));
+        assert!(alternatives[0].chosen && alternatives[0].choice.prune_cold.is_some());
+    }
+
+    #[test]
+    fn test_plan_cache_hits_and_drift() {
+        let (collection, _) = collection();
+        let planner = planner();
+
+        let first = planner.plan(&CognitiveQuery::parse("FIND IN papers NEAR [1, 0] WHERE lang = \"en\"").unwrap(), &collection);
+        assert!(!first.cached);
+        // Same shape, different values
+        let second = planner.plan(&CognitiveQuery::parse("FIND IN papers NEAR [0, 1] WHERE lang = \"de\"").unwrap(), &collection);
+        assert!(second.cached);
+        assert_eq!(second.choice, first.choice);
+        let other = planner.plan(&CognitiveQuery::parse("FIND IN papers NEAR [0, 1] LIMIT 3").unwrap(), &collection);
+        assert!(!other.cached);
+        assert_eq!(planner.cache_stats(), PlanCacheStats { hits: 1, misses: 2, entries: 2 });
+
+        // Growing the collection by half re-plans
+        for i in 0..20 {
+            let angle = i as f32 * 0.3;
+            collection
+                .upsert(Entity::new(Some(Vector::new(vec![angle.cos(), angle.sin()])), None, None))
+                .unwrap();
+        }
+        let replanned = planner.plan(&CognitiveQuery::parse("FIND IN papers NEAR [1, 0] WHERE lang = \"en\"").unwrap(), &collection);
+        assert!(!replanned.cached);
+        assert_eq!(planner.cache_stats().entries, 2);
+
+        planner.clear_cache();
+        assert_eq!(planner.cache_stats().entries, 0);
+
+        let tiny = QueryPlanner::new(
+            CostWeights::default(),
+            PlannerConfig {
+                plan_cache_capacity: 1,
+                ..PlannerConfig::default()
+            },
+        );
+        for text in ["FIND IN papers LIMIT 1", "FIND IN papers LIMIT 2", "FIND IN papers LIMIT 1"] {
+            assert!(!tiny.plan(&CognitiveQuery::parse(text).unwrap(), &collection).cached);
+        }
+        assert_eq!(tiny.cache_stats().entries, 1);
+    }
+
+    #[test]
+    fn test_explain_lists_alternatives() {
+        let (collection, ids) = collection();
+        let planner = planner();
+        let text = format!("EXPLAIN FIND IN papers NEAR [1, 0] WITHIN 2 HOPS OF ENTITY \"{}\"", ids[0]);
+        let query = CognitiveQuery::parse(&text).unwrap();
+
+        let explain = planner.explain(&query, &collection);
+        assert!(!explain.cached);
+        assert!(explain.alternatives.len() >= 2);
+        assert_eq!(explain.alternatives.iter().filter(|a| a.chosen).count(), 1);
+        assert!(explain
+            .alternatives
+            .windows(2)
+            .all(|pair| pair[0].weighted_cost <= pair[1].weighted_cost));
+
+        // A small neighbourhood is cheaper to rank exactly than to search for
+        assert_eq!(explain.alternatives[0].choice.access, AccessPath::GraphFirst);
+        assert!(matches!(explain.steps[0].operator, Operator::Neighborhood(_)));
+        let text = explain.to_string();
+        assert!(text.contains("Alternatives:\n  * graph first"));
+        assert!(text.contains("- vector first"));
+
+        assert!(planner.explain(&query, &collection).cached);
+    }
+}
@@ -1 +1,1381 @@
-// TODO: Implement REST API
+//! REST/JSON front end
+//!
+//! Mirrors the gRPC services over plain HTTP for clients without gRPC
+//! tooling. Routes live under `/v1`, bodies are JSON, and the OpenAPI
+//! document describing them is served at `/openapi.json`.
+//!
+//! Service calls run on the blocking thread pool, as they do for gRPC, so a
+//! request waiting on a lock or a log fsync never holds up hyper's runtime.
+//!
+//! Errors use the same classification as the gRPC API, translated with the
+//! standard gRPC-to-HTTP mapping (NOT_FOUND → 404, ALREADY_EXISTS and
+//! ABORTED → 409, INVALID_ARGUMENT and FAILED_PRECONDITION → 400,
+//! OUT_OF_RANGE → 400, UNAVAILABLE → 503, everything else → 500). Every
+//! error response carries its correlation id in the body and in the
+//! `x-correlation-id` header.
+//!
+//! Transactions are opened with `POST /v1/collections/{c}/transactions`,
+//! which returns the transaction id. Entity reads and writes under
+//! `/v1/collections/{c}/transactions/{t}/entities/{id}` then go through the
+//! transaction, and `.../commit` or `.../abort` closes it.
+//!
+//! `GET /v1/cdc` streams change data capture events as newline-delimited
+//! JSON. The stream stays open for new changes; if it fails after the
+//! response has started, the last line is an error document. Open streams
+//! are ended when the server shuts down.
+
+use crate::api::cognitive_query::{Explain, QueryRow};
+use crate::api::grpc::{status_code, CORRELATION_ID_KEY};
+use crate::api::service::ApiService;
+use crate::core::collection::CollectionConfig;
+use crate::core::config::ApiConfig;
+use crate::core::error::{CorrelationId, MemorySubstrateError, Result};
+use crate::core::query::{FilterStrategy, GraphQuery, TraversalStep, VectorQuery};
+use crate::core::transaction::TransactionId;
+use crate::storage::cdc::{CdcEvent, CdcStream, Change};
+use crate::core::{Edge, Entity, EntityId, MemoryTier, MetadataFilter, Vector};
+use hyper::body::HttpBody;
+use hyper::header::{HeaderValue, ALLOW, CONTENT_TYPE};
+use hyper::server::conn::AddrIncoming;
+use hyper::service::{make_service_fn, service_fn};
+use futures::StreamExt;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tonic::Code;
+
+const OPENAPI: &str = include_str!("openapi.json");
+
+/// OpenAPI 3 description of the REST API
+pub fn openapi() -> Value {
+    let mut document: Value = serde_json::from_str(OPENAPI).expect("bundled OpenAPI document is valid JSON");
+    document["info"]["version"] = Value::from(crate::VERSION);
+    document
+}
+
+/// Outgoing edge as exchanged over REST
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonEdge {
+    /// Target entity
+    pub target_id: EntityId,
+
+    /// Relationship label
+    pub label: String,
+
+    /// Static weight in [0, 1]
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+
+    /// Edge metadata
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+
+    /// Learned probability; ignored on write
+    #[serde(default)]
+    pub probability: f32,
+}
+
+impl From<&Edge> for JsonEdge {
+    fn from(edge: &Edge) -> Self {
+        Self {
+            target_id: edge.target_id,
+            label: edge.label.clone(),
+            weight: edge.weight,
+            metadata: edge.metadata.clone(),
+            probability: edge.probability(),
+        }
+    }
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+/// Entity as exchanged over REST
+///
+/// Timestamps and version are maintained by the server and ignored on
+/// write.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JsonEntity {
+    /// Entity id; generated by the server when absent on create
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<EntityId>,
+
+    /// Vector embedding
+    pub vector: Vec<f32>,
+
+    /// Metadata document
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+
+    /// Outgoing edges
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub edges: Vec<JsonEdge>,
+
+    /// Creation time (Unix epoch milliseconds)
+    pub created_at: u64,
+
+    /// Last update time (Unix epoch milliseconds)
+    pub updated_at: u64,
+
+    /// MVCC version
+    pub version: u64,
+
+    /// Memory tier; new entities start hot
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tier: Option<MemoryTier>,
+}
+
+impl From<&Entity> for JsonEntity {
+    fn from(entity: &Entity) -> Self {
+        Self {
+            id: Some(entity.id),
+            vector: entity.vector.as_ref().map(|v| v.values.clone()).unwrap_or_default(),
+            metadata: entity.metadata.clone(),
+            edges: entity.edges.iter().flatten().map(JsonEdge::from).collect(),
+            created_at: entity.created_at,
+            updated_at: entity.updated_at,
+            version: entity.version,
+            tier: Some(entity.tier),
+        }
+    }
+}
+
+impl JsonEntity {
+    /// Build the domain entity, generating an id if none was given
+    pub fn into_entity(self) -> Entity {
+        let id = self.id.unwrap_or_default();
+        let edges: Vec<_> = self
+            .edges
+            .into_iter()
+            .map(|edge| Edge::new(id, edge.target_id, edge.label, edge.weight, edge.metadata))
+            .collect();
+        let mut entity = Entity::new(
+            (!self.vector.is_empty()).then(|| Vector::new(self.vector)),
+            self.metadata,
+            (!edges.is_empty()).then_some(edges),
+        );
+        entity.id = id;
+        entity.tier = self.tier.unwrap_or(MemoryTier::Hot);
+        entity
+    }
+}
+
+/// Body of `POST /v1/collections`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateCollectionBody {
+    /// Collection name
+    pub name: String,
+
+    /// Creation configuration
+    pub config: CollectionConfig,
+}
+
+/// Body of `PUT /v1/aliases/{alias}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetAliasBody {
+    /// Collection name or alias to point at
+    pub target: String,
+}
+
+/// Body of `POST /v1/collections/{collection}/batch`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchBody {
+    /// Entities to upsert
+    pub entities: Vec<JsonEntity>,
+}
+
+/// Body of `POST /v1/collections/{collection}/search`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchBody {
+    /// Query embedding
+    pub vector: Vec<f32>,
+
+    /// Number of results
+    pub k: usize,
+
+    /// Metadata predicate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<MetadataFilter>,
+
+    /// Pinned filter strategy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<FilterStrategy>,
+
+    /// Search breadth override
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ef: Option<usize>,
+
+    /// Attach the stored entity to each hit
+    #[serde(default)]
+    pub include_entities: bool,
+}
+
+/// Body of `POST /v1/collections/{collection}/traverse`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraverseBody {
+    /// Entities to start from
+    pub start: Vec<EntityId>,
+
+    /// Maximum number of hops
+    pub max_depth: usize,
+
+    /// Edge labels to follow; empty follows all
+    #[serde(default)]
+    pub labels: Vec<String>,
+
+    /// Minimum learned edge probability
+    #[serde(default)]
+    pub min_probability: f32,
+
+    /// Maximum number of reached entities
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+
+    /// Attach the stored entity to each node
+    #[serde(default)]
+    pub include_entities: bool,
+}
+
+/// One search hit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonHit {
+    /// Matching entity
+    pub id: EntityId,
+
+    /// Distance to the query
+    pub distance: f32,
+
+    /// Stored entity, when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity: Option<JsonEntity>,
+}
+
+/// Response of a search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResponse {
+    /// Hits ordered from closest to farthest
+    pub hits: Vec<JsonHit>,
+
+    /// Strategy that produced the hits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<FilterStrategy>,
+
+    /// Estimated filter selectivity
+    pub selectivity: f64,
+}
+
+/// One entity reached by a traversal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonNode {
+    /// Position in the traversal
+    #[serde(flatten)]
+    pub step: TraversalStep,
+
+    /// Stored entity, when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity: Option<JsonEntity>,
+}
+
+/// Response of a traversal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraverseResponse {
+    /// Reached entities in breadth-first order
+    pub nodes: Vec<JsonNode>,
+}
+
+/// Body of `POST /v1/query`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryBody {
+    /// Cognitive query text
+    pub query: String,
+
+    /// Attach the stored entity to each row
+    #[serde(default)]
+    pub include_entities: bool,
+}
+
+/// One cognitive query result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRow {
+    /// Position and scores of the result
+    #[serde(flatten)]
+    pub row: QueryRow,
+
+    /// Stored entity, when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity: Option<JsonEntity>,
+}
+
+/// Response of a cognitive query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryResponse {
+    /// Primary name of the queried collection
+    pub collection: String,
+
+    /// Results; empty for `EXPLAIN`
+    pub rows: Vec<JsonRow>,
+
+    /// Costed plan, for `EXPLAIN`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explain: Option<Explain>,
+
+    /// `explain` rendered as text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan: Option<String>,
+}
+
+/// What a change data capture event changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JsonChange {
+    /// Entity created or replaced
+    EntityUpsert {
+        /// New entity state
+        entity: JsonEntity,
+    },
+
+    /// Entity removed, with its outgoing edges
+    EntityDelete {
+        /// Removed entity
+        entity_id: EntityId,
+    },
+
+    /// Edge added
+    EdgeCreate {
+        /// Source entity
+        source_id: EntityId,
+        /// New edge
+        edge: JsonEdge,
+    },
+
+    /// Edge weight, probability or metadata changed
+    EdgeUpdate {
+        /// Source entity
+        source_id: EntityId,
+        /// New edge state
+        edge: JsonEdge,
+    },
+
+    /// Edge removed
+    EdgePrune {
+        /// Source entity
+        source_id: EntityId,
+        /// Target entity
+        target_id: EntityId,
+        /// Relationship label
+        label: String,
+    },
+
+    /// Entity moved between tiers
+    TierMove {
+        /// Moved entity
+        entity_id: EntityId,
+        /// Previous tier
+        from: MemoryTier,
+        /// New tier
+        to: MemoryTier,
+    },
+}
+
+/// One line of the `GET /v1/cdc` stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonCdcEvent {
+    /// Position in the change sequence
+    pub sequence: u64,
+
+    /// Log record the change was derived from
+    pub lsn: u64,
+
+    /// Primary collection name
+    pub collection: String,
+
+    /// Commit time (Unix epoch milliseconds)
+    pub timestamp: u64,
+
+    /// What changed
+    #[serde(flatten)]
+    pub change: JsonChange,
+}
+
+impl From<&CdcEvent> for JsonCdcEvent {
+    fn from(event: &CdcEvent) -> Self {
+        let change = match &event.change {
+            Change::EntityUpsert { entity } => JsonChange::EntityUpsert {
+                entity: JsonEntity::from(entity.as_ref()),
+            },
+            Change::EntityDelete { entity_id } => JsonChange::EntityDelete { entity_id: *entity_id },
+            Change::EdgeCreate { edge } => JsonChange::EdgeCreate {
+                source_id: edge.source_id,
+                edge: edge.into(),
+            },
+            Change::EdgeUpdate { edge } => JsonChange::EdgeUpdate {
+                source_id: edge.source_id,
+                edge: edge.into(),
+            },
+            Change::EdgePrune {
+                source_id,
+                target_id,
+                label,
+            } => JsonChange::EdgePrune {
+                source_id: *source_id,
+                target_id: *target_id,
+                label: label.clone(),
+            },
+            Change::TierMove { entity_id, from, to } => JsonChange::TierMove {
+                entity_id: *entity_id,
+                from: *from,
+                to: *to,
+            },
+        };
+        Self {
+            sequence: event.sequence,
+            lsn: event.lsn,
+            collection: event.collection.clone(),
+            timestamp: event.timestamp,
+            change,
+        }
+    }
+}
+
+/// HTTP status for an error
+pub fn http_status(error: &MemorySubstrateError) -> StatusCode {
+    match status_code(error) {
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => StatusCode::BAD_REQUEST,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// A failed request, rendered as an error document
+#[derive(Debug)]
+struct HttpError {
+    status: StatusCode,
+    message: String,
+    correlation_id: CorrelationId,
+    allow: Option<&'static str>,
+}
+
+impl HttpError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            correlation_id: CorrelationId::new(),
+            allow: None,
+        }
+    }
+
+    fn document(&self) -> Value {
+        serde_json::json!({
+            "error": {
+                "status": self.status.as_u16(),
+                "message": self.message,
+                "correlation_id": self.correlation_id.to_string(),
+            }
+        })
+    }
+
+    fn into_response(self) -> Response<Body> {
+        let mut response = json_response(self.status, &self.document());
+        if let Ok(value) = HeaderValue::from_str(&self.correlation_id.to_string()) {
+            response.headers_mut().insert(CORRELATION_ID_KEY, value);
+        }
+        if let Some(allow) = self.allow {
+            response.headers_mut().insert(ALLOW, HeaderValue::from_static(allow));
+        }
+        response
+    }
+}
+
+impl From<MemorySubstrateError> for HttpError {
+    fn from(error: MemorySubstrateError) -> Self {
+        let status = http_status(&error);
+        let correlation_id = error.correlation_id().unwrap_or_default();
+        if status.is_server_error() {
+            tracing::error!(%correlation_id, %error, "REST request failed");
+        } else {
+            tracing::debug!(%correlation_id, %error, %status, "REST request rejected");
+        }
+        Self {
+            status,
+            message: error.to_string(),
+            correlation_id,
+            allow: None,
+        }
+    }
+}
+
+/// A matched endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Route {
+    OpenApi,
+    ClusterStatus,
+    ListCollections,
+    CreateCollection,
+    DescribeCollection(String),
+    DropCollection(String),
+    CollectionStats(String),
+    SetAlias(String),
+    DropAlias(String),
+    CreateEntity(String),
+    GetEntity(String, String),
+    UpdateEntity(String, String),
+    DeleteEntity(String, String),
+    BatchUpsert(String),
+    Search(String),
+    Traverse(String),
+    BeginTransaction(String),
+    TransactionGet(String, String, String),
+    TransactionPut(String, String, String),
+    TransactionDelete(String, String, String),
+    CommitTransaction(String, String),
+    AbortTransaction(String, String),
+    Query,
+    Cdc,
+}
+
+/// Match a request line; unknown paths are 404, known paths with another
+/// method are 405 with the allowed methods
+fn route(method: &Method, path: &str) -> std::result::Result<Route, HttpError> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let owned = |segment: &&str| segment.to_string();
+
+    let (allowed, route): (&'static str, Option<Route>) = match segments.as_slice() {
+        ["openapi.json"] => ("GET", (method == Method::GET).then_some(Route::OpenApi)),
+        ["v1", "cluster", "status"] => ("GET", (method == Method::GET).then_some(Route::ClusterStatus)),
+        ["v1", "collections"] => (
+            "GET, POST",
+            match *method {
+                Method::GET => Some(Route::ListCollections),
+                Method::POST => Some(Route::CreateCollection),
+                _ => None,
+            },
+        ),
+        ["v1", "collections", name] => (
+            "GET, DELETE",
+            match *method {
+                Method::GET => Some(Route::DescribeCollection(owned(name))),
+                Method::DELETE => Some(Route::DropCollection(owned(name))),
+                _ => None,
+            },
+        ),
+        ["v1", "collections", name, "stats"] => (
+            "GET",
+            (method == Method::GET).then(|| Route::CollectionStats(owned(name))),
+        ),
+        ["v1", "aliases", alias] => (
+            "PUT, DELETE",
+            match *method {
+                Method::PUT => Some(Route::SetAlias(owned(alias))),
+                Method::DELETE => Some(Route::DropAlias(owned(alias))),
+                _ => None,
+            },
+        ),
+        ["v1", "collections", name, "entities"] => (
+            "POST",
+            (method == Method::POST).then(|| Route::CreateEntity(owned(name))),
+        ),
+        ["v1", "collections", name, "entities", id] => (
+            "GET, PUT, DELETE",
+            match *method {
+                Method::GET => Some(Route::GetEntity(owned(name), owned(id))),
+                Method::PUT => Some(Route::UpdateEntity(owned(name), owned(id))),
+                Method::DELETE => Some(Route::DeleteEntity(owned(name), owned(id))),
+                _ => None,
+            },
+        ),
+        ["v1", "collections", name, "batch"] => (
+            "POST",
+            (method == Method::POST).then(|| Route::BatchUpsert(owned(name))),
+        ),
+        ["v1", "collections", name, "search"] => (
+            "POST",
+            (method == Method::POST).then(|| Route::Search(owned(name))),
+        ),
+        ["v1", "collections", name, "traverse"] => (
+            "POST",
+            (method == Method::POST).then(|| Route::Traverse(owned(name))),
+        ),
+        ["v1", "collections", name, "transactions"] => (
+            "POST",
+            (method == Method::POST).then(|| Route::BeginTransaction(owned(name))),
+        ),
+        ["v1", "collections", name, "transactions", txn, "entities", id] => (
+            "GET, PUT, DELETE",
+            match *method {
+                Method::GET => Some(Route::TransactionGet(owned(name), owned(txn), owned(id))),
+                Method::PUT => Some(Route::TransactionPut(owned(name), owned(txn), owned(id))),
+                Method::DELETE => Some(Route::TransactionDelete(owned(name), owned(txn), owned(id))),
+                _ => None,
+            },
+        ),
+        ["v1", "collections", name, "transactions", txn, "commit"] => (
+            "POST",
+            (method == Method::POST).then(|| Route::CommitTransaction(owned(name), owned(txn))),
+        ),
+        ["v1", "collections", name, "transactions", txn, "abort"] => (
+            "POST",
+            (method == Method::POST).then(|| Route::AbortTransaction(owned(name), owned(txn))),
+        ),
+        ["v1", "query"] => ("POST", (method == Method::POST).then_some(Route::Query)),
+        ["v1", "cdc"] => ("GET", (method == Method::GET).then_some(Route::Cdc)),
+        _ => return Err(HttpError::new(StatusCode::NOT_FOUND, format!("no route for {}", path))),
+    };
+
+    route.ok_or_else(|| HttpError {
+        allow: Some(allowed),
+        ..HttpError::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            format!("{} is not allowed on {}", method, path),
+        )
+    })
+}
+
+fn json_response<T: Serialize>(status: StatusCode, value: &T) -> Response<Body> {
+    // Serializing our own response types cannot fail
+    let body = serde_json::to_vec(value).unwrap_or_default();
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+fn empty_response() -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = StatusCode::NO_CONTENT;
+    response
+}
+
+async fn read_json<T: DeserializeOwned>(mut body: Body, limit: usize) -> std::result::Result<T, HttpError> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|error| HttpError::new(StatusCode::BAD_REQUEST, error.to_string()))?;
+        if bytes.len() + chunk.len() > limit {
+            return Err(HttpError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("request body exceeds {} bytes", limit),
+            ));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    serde_json::from_slice(&bytes).map_err(|error| {
+        MemorySubstrateError::InvalidRequest(format!("invalid request body: {}", error)).into()
+    })
+}
+
+/// Query parameters of `GET /v1/cdc`: `after` (default 0) and repeated
+/// `collection`
+fn cdc_params(query: Option<&str>) -> std::result::Result<(u64, Vec<String>), HttpError> {
+    let mut after = 0;
+    let mut collections = Vec::new();
+    for pair in query.unwrap_or_default().split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        match key {
+            "after" => {
+                after = value.parse().map_err(|_| {
+                    HttpError::new(StatusCode::BAD_REQUEST, format!("invalid sequence number '{}'", value))
+                })?
+            }
+            "collection" => collections.push(value.to_string()),
+            _ => {
+                return Err(HttpError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("unknown query parameter '{}'", key),
+                ))
+            }
+        }
+    }
+    Ok((after, collections))
+}
+
+/// Stream change events as NDJSON until the stream fails or `shutdown`
+/// is cancelled
+fn cdc_response(events: CdcStream, shutdown: &CancellationToken) -> Response<Body> {
+    let lines = events
+        .take_until(shutdown.clone().cancelled_owned())
+        .map(|event| {
+            let value = match event {
+                Ok(event) => serde_json::to_value(JsonCdcEvent::from(&event)),
+                Err(error) => Ok(HttpError::from(error).document()),
+            };
+            let mut line = value.map(|value| value.to_string()).unwrap_or_default();
+            line.push('\n');
+            Ok::<_, Infallible>(line)
+        });
+    let mut response = Response::new(Body::wrap_stream(lines));
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/x-ndjson"));
+    response
+}
+
+/// Call the service on the blocking thread pool
+async fn blocking<T, F>(service: &Arc<ApiService>, f: F) -> std::result::Result<T, HttpError>
+where
+    F: FnOnce(&ApiService) -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    Ok(service.blocking(f).await?)
+}
+
+fn parse_id(id: &str) -> std::result::Result<EntityId, HttpError> {
+    Ok(crate::api::protocol::parse_entity_id(id)?)
+}
+
+fn parse_transaction(id: &str) -> std::result::Result<TransactionId, HttpError> {
+    id.parse()
+        .map_err(|_| HttpError::new(StatusCode::BAD_REQUEST, format!("invalid transaction id '{}'", id)))
+}
+
+/// Body of `PUT /v1/collections/{collection}/entities/{id}` and its
+/// transactional twin, with the path id filled in
+fn entity_at(id: EntityId, mut body: JsonEntity) -> std::result::Result<Entity, HttpError> {
+    if body.id.is_some_and(|body_id| body_id != id) {
+        return Err(HttpError::new(
+            StatusCode::BAD_REQUEST,
+            "entity id in the body does not match the path",
+        ));
+    }
+    body.id = Some(id);
+    Ok(body.into_entity())
+}
+
+/// Wire form of the entities read with a result, or all `None` unless
+/// `include` is set
+fn attach<'a>(entities: impl Iterator<Item = Option<&'a Entity>>, include: bool) -> Vec<Option<JsonEntity>> {
+    entities
+        .map(|entity| entity.filter(|_| include).map(JsonEntity::from))
+        .collect()
+}
+
+async fn dispatch(
+    service: &Arc<ApiService>,
+    route: Route,
+    request: Request<Body>,
+    limit: usize,
+    shutdown: &CancellationToken,
+) -> std::result::Result<Response<Body>, HttpError> {
+    let (parts, body) = request.into_parts();
+    let response = match route {
+        Route::OpenApi => json_response(StatusCode::OK, &openapi()),
+        Route::ClusterStatus => {
+            let status = blocking(service, |service| Ok(service.cluster_status())).await?;
+            json_response(StatusCode::OK, &status)
+        }
+        Route::ListCollections => {
+            let collections = blocking(service, |service| Ok(service.list_collections())).await?;
+            json_response(StatusCode::OK, &collections)
+        }
+        Route::CreateCollection => {
+            let body: CreateCollectionBody = read_json(body, limit).await?;
+            let info = blocking(service, move |service| service.create_collection(&body.name, body.config)).await?;
+            json_response(StatusCode::CREATED, &info)
+        }
+        Route::DescribeCollection(name) => {
+            let info = blocking(service, move |service| service.describe_collection(&name)).await?;
+            json_response(StatusCode::OK, &info)
+        }
+        Route::DropCollection(name) => {
+            blocking(service, move |service| service.drop_collection(&name)).await?;
+            empty_response()
+        }
+        Route::CollectionStats(name) => {
+            let stats = blocking(service, move |service| service.collection_stats(&name)).await?;
+            json_response(StatusCode::OK, &stats)
+        }
+        Route::SetAlias(alias) => {
+            let body: SetAliasBody = read_json(body, limit).await?;
+            blocking(service, move |service| service.set_alias(&alias, &body.target)).await?;
+            empty_response()
+        }
+        Route::DropAlias(alias) => {
+            blocking(service, move |service| service.drop_alias(&alias)).await?;
+            empty_response()
+        }
+        Route::CreateEntity(collection) => {
+            let body: JsonEntity = read_json(body, limit).await?;
+            let entity = body.into_entity();
+            let created = blocking(service, move |service| service.create_entity(&collection, entity)).await?;
+            json_response(StatusCode::CREATED, &JsonEntity::from(&created))
+        }
+        Route::GetEntity(collection, id) => {
+            let id = parse_id(&id)?;
+            let entity = blocking(service, move |service| service.get_entity(&collection, &id)).await?;
+            json_response(StatusCode::OK, &JsonEntity::from(&entity))
+        }
+        Route::UpdateEntity(collection, id) => {
+            let id = parse_id(&id)?;
+            let entity = entity_at(id, read_json(body, limit).await?)?;
+            let updated = blocking(service, move |service| service.update_entity(&collection, entity)).await?;
+            json_response(StatusCode::OK, &JsonEntity::from(&updated))
+        }
+        Route::DeleteEntity(collection, id) => {
+            let id = parse_id(&id)?;
+            let deleted = blocking(service, move |service| service.delete_entity(&collection, &id)).await?;
+            json_response(StatusCode::OK, &serde_json::json!({ "deleted": deleted }))
+        }
+        Route::BatchUpsert(collection) => {
+            let body: BatchBody = read_json(body, limit).await?;
+            let entities = body.entities.into_iter().map(JsonEntity::into_entity).collect();
+            let outcome = blocking(service, move |service| service.batch_upsert(&collection, entities)).await?;
+            json_response(StatusCode::OK, &outcome)
+        }
+        Route::Search(collection) => {
+            let body: SearchBody = read_json(body, limit).await?;
+            let query = VectorQuery {
+                vector: Vector::new(body.vector),
+                k: body.k,
+                filter: body.filter,
+                strategy: body.strategy,
+                ef: body.ef,
+            };
+            let (result, entities) = blocking(service, move |service| service.search(&collection, &query)).await?;
+            let entities = attach(entities.iter().map(|entity| Some(&**entity)), body.include_entities);
+            let hits = result
+                .hits
+                .iter()
+                .zip(entities)
+                .map(|(hit, entity)| JsonHit {
+                    id: hit.id,
+                    distance: hit.distance,
+                    entity,
+                })
+                .collect();
+            json_response(
+                StatusCode::OK,
+                &SearchResponse {
+                    hits,
+                    strategy: result.strategy,
+                    selectivity: result.selectivity,
+                },
+            )
+        }
+        Route::Traverse(collection) => {
+            let body: TraverseBody = read_json(body, limit).await?;
+            let mut query = GraphQuery::new(body.start, body.max_depth)
+                .with_labels(body.labels)
+                .with_min_probability(body.min_probability);
+            if let Some(limit) = body.limit {
+                query = query.with_limit(limit);
+            }
+            let (steps, entities) = blocking(service, move |service| service.traverse(&collection, &query)).await?;
+            let entities = attach(entities.iter().map(|entity| Some(&**entity)), body.include_entities);
+            let nodes = steps
+                .into_iter()
+                .zip(entities)
+                .map(|(step, entity)| JsonNode { step, entity })
+                .collect();
+            json_response(StatusCode::OK, &TraverseResponse { nodes })
+        }
+        Route::Query => {
+            let body: QueryBody = read_json(body, limit).await?;
+            let text = body.query;
+            let output = blocking(service, move |service| service.query(&text)).await?;
+            let entities = attach(output.entities.iter().map(Option::as_deref), body.include_entities);
+            let rows = output
+                .rows
+                .into_iter()
+                .zip(entities)
+                .map(|(row, entity)| JsonRow { row, entity })
+                .collect();
+            json_response(
+                StatusCode::OK,
+                &QueryResponse {
+                    collection: output.collection,
+                    rows,
+                    plan: output.explain.as_ref().map(ToString::to_string),
+                    explain: output.explain,
+                },
+            )
+        }
+        Route::BeginTransaction(collection) => {
+            let id = blocking(service, move |service| service.begin_transaction(&collection)).await?;
+            json_response(StatusCode::CREATED, &serde_json::json!({ "id": id }))
+        }
+        Route::TransactionGet(collection, txn, id) => {
+            let (txn, id) = (parse_transaction(&txn)?, parse_id(&id)?);
+            let entity = blocking(service, move |service| service.transaction_get(&collection, txn, &id)).await?;
+            json_response(StatusCode::OK, &JsonEntity::from(&entity))
+        }
+        Route::TransactionPut(collection, txn, id) => {
+            let txn = parse_transaction(&txn)?;
+            let entity = entity_at(parse_id(&id)?, read_json(body, limit).await?)?;
+            let written = blocking(service, move |service| service.transaction_put(&collection, txn, entity)).await?;
+            json_response(StatusCode::OK, &JsonEntity::from(&written))
+        }
+        Route::TransactionDelete(collection, txn, id) => {
+            let (txn, id) = (parse_transaction(&txn)?, parse_id(&id)?);
+            let deleted =
+                blocking(service, move |service| service.transaction_delete(&collection, txn, &id)).await?;
+            json_response(StatusCode::OK, &serde_json::json!({ "deleted": deleted }))
+        }
+        Route::CommitTransaction(collection, txn) => {
+            let txn = parse_transaction(&txn)?;
+            let commit_ts = blocking(service, move |service| service.commit_transaction(&collection, txn)).await?;
+            json_response(StatusCode::OK, &serde_json::json!({ "commit_ts": commit_ts }))
+        }
+        Route::AbortTransaction(collection, txn) => {
+            let txn = parse_transaction(&txn)?;
+            blocking(service, move |service| service.abort_transaction(&collection, txn)).await?;
+            empty_response()
+        }
+        Route::Cdc => {
+            let (after, collections) = cdc_params(parts.uri.query())?;
+            let events = blocking(service, move |service| service.cdc(after, &collections)).await?;
+            cdc_response(events, shutdown)
+        }
+    };
+    Ok(response)
+}
+
+/// Answer one HTTP request
+///
+/// Request bodies larger than `body_limit` bytes are refused with 413.
+pub async fn handle(service: &Arc<ApiService>, request: Request<Body>, body_limit: usize) -> Response<Body> {
+    respond(service, request, body_limit, &CancellationToken::new()).await
+}
+
+/// Answer one HTTP request; change streams end when `shutdown` is cancelled
+async fn respond(
+    service: &Arc<ApiService>,
+    request: Request<Body>,
+    body_limit: usize,
+    shutdown: &CancellationToken,
+) -> Response<Body> {
+    let result = match route(request.method(), request.uri().path()) {
+        Ok(route) => dispatch(service, route, request, body_limit, shutdown).await,
+        Err(error) => Err(error),
+    };
+    result.unwrap_or_else(HttpError::into_response)
+}
+
+/// Bind `config.rest_addr` and serve until `shutdown` resolves
+pub async fn serve(
+    service: Arc<ApiService>,
+    config: &ApiConfig,
+    shutdown: impl Future<Output = ()> + Send,
+) -> Result<()> {
+    let listener = TcpListener::bind(&config.rest_addr).await?;
+    serve_with_listener(service, config, listener, shutdown).await
+}
+
+/// Serve on an already bound listener until `shutdown` resolves
+///
+/// In-flight requests are allowed to finish; open change streams are ended.
+pub async fn serve_with_listener(
+    service: Arc<ApiService>,
+    config: &ApiConfig,
+    listener: TcpListener,
+    shutdown: impl Future<Output = ()> + Send,
+) -> Result<()> {
+    let address = listener.local_addr()?;
+    let incoming = AddrIncoming::from_listener(listener)
+        .map_err(|error| MemorySubstrateError::Internal(format!("cannot accept on {}: {}", address, error)))?;
+    let limit = config.max_message_bytes;
+    let streams = CancellationToken::new();
+    let signal = {
+        let streams = streams.clone();
+        async move {
+            shutdown.await;
+            streams.cancel();
+        }
+    };
+
+    let make_service = make_service_fn(move |_connection| {
+        let service = service.clone();
+        let streams = streams.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let service = service.clone();
+                let streams = streams.clone();
+                async move { Ok::<_, Infallible>(respond(&service, request, limit, &streams).await) }
+            }))
+        }
+    });
+
+    tracing::info!(%address, "REST API listening");
+    hyper::Server::builder(incoming)
+        .serve(make_service)
+        .with_graceful_shutdown(signal)
+        .await
+        .map_err(|error| MemorySubstrateError::Internal(format!("REST server failed: {}", error)))?;
+    tracing::info!(%address, "REST API stopped");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::NodeId;
+    use crate::storage::catalog::Catalog;
+    use serde_json::json;
+
+    fn service() -> Arc<ApiService> {
+        Arc::new(ApiService::new(NodeId::new(), Catalog::in_memory(), 16))
+    }
+
+    async fn call(service: &Arc<ApiService>, method: Method, path: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let body = match body {
+            Some(value) => Body::from(value.to_string()),
+            None => Body::empty(),
+        };
+        let request = Request::builder().method(method).uri(path).body(body).unwrap();
+        let response = handle(service, request, 1024 * 1024).await;
+        let status = response.status();
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let value = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap()
+        };
+        (status, value)
+    }
+
+    #[tokio::test]
+    async fn test_rest_round_trip() {
+        let service = service();
+        let (status, info) = call(
+            &service,
+            Method::POST,
+            "/v1/collections",
+            Some(json!({"name": "docs", "config": {"dimension": 2}})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(info["name"], "docs");
+        let (status, _) = call(&service, Method::PUT, "/v1/aliases/live", Some(json!({"target": "docs"}))).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (status, first) = call(
+            &service,
+            Method::POST,
+            "/v1/collections/live/entities",
+            Some(json!({"vector": [1.0, 0.0], "metadata": {"lang": "en"}})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(first["version"], 1);
+        let first_id = first["id"].as_str().unwrap().to_string();
+
+        let (_, second) = call(
+            &service,
+            Method::POST,
+            "/v1/collections/docs/entities",
+            Some(json!({"vector": [0.0, 1.0], "edges": [{"target_id": first_id, "label": "cites"}]})),
+        )
+        .await;
+        let second_id = second["id"].as_str().unwrap().to_string();
+
+        let (status, updated) = call(
+            &service,
+            Method::PUT,
+            &format!("/v1/collections/docs/entities/{}", first_id),
+            Some(json!({"vector": [1.0, 0.1], "metadata": {"lang": "de"}})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(updated["version"], 2);
+
+        let (status, found) = call(
+            &service,
+            Method::POST,
+            "/v1/collections/docs/search",
+            Some(json!({
+                "vector": [1.0, 0.0],
+                "k": 2,
+                "filter": {"op": "eq", "field": "lang", "value": "de"},
+                "include_entities": true
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(found["hits"].as_array().unwrap().len(), 1);
+        assert_eq!(found["hits"][0]["entity"]["metadata"]["lang"], "de");
+
+        let (_, walked) = call(
+            &service,
+            Method::POST,
+            "/v1/collections/docs/traverse",
+            Some(json!({"start": [second_id], "max_depth": 1})),
+        )
+        .await;
+        let nodes = walked["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[1]["id"], first_id.as_str());
+        assert_eq!(nodes[1]["label"], "cites");
+
+        let (status, answer) = call(
+            &service,
+            Method::POST,
+            "/v1/query",
+            Some(json!({"query": "FIND IN live NEAR [0, 1] K 1 EXPAND 1 HOP", "include_entities": true})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(answer["collection"], "docs");
+        assert_eq!(answer["rows"][0]["id"], second_id.as_str());
+        assert_eq!(answer["rows"][1]["depth"], 1);
+        assert_eq!(answer["rows"][1]["entity"]["metadata"]["lang"], "de");
+        let (_, explained) = call(
+            &service,
+            Method::POST,
+            "/v1/query",
+            Some(json!({"query": "EXPLAIN FIND IN docs NEAR [0, 1] K 1 EXPAND 1 HOP"})),
+        )
+        .await;
+        assert!(explained["rows"].as_array().unwrap().is_empty());
+        assert!(explained["explain"]["weighted_cost"].as_f64().unwrap() > 0.0);
+        assert!(explained["plan"].as_str().unwrap().contains("Expand 1 hops"));
+
+        let (_, batch) = call(
+            &service,
+            Method::POST,
+            "/v1/collections/docs/batch",
+            Some(json!({"entities": [{"vector": [0.5, 0.5]}, {"vector": [1.0]}]})),
+        )
+        .await;
+        assert_eq!(batch["upserted"], 1);
+        assert_eq!(batch["rejected"][0]["index"], 1);
+
+        let (_, deleted) = call(
+            &service,
+            Method::DELETE,
+            &format!("/v1/collections/docs/entities/{}", first_id),
+            None,
+        )
+        .await;
+        assert_eq!(deleted["deleted"], true);
+
+        let (_, status) = call(&service, Method::GET, "/v1/cluster/status", None).await;
+        assert_eq!(status["entities"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_transactions_over_rest() {
+        let service = service();
+        service.create_collection("docs", CollectionConfig::new(2)).unwrap();
+        let id = EntityId::new();
+
+        let (status, opened) = call(&service, Method::POST, "/v1/collections/docs/transactions", None).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let txn = format!("/v1/collections/docs/transactions/{}", opened["id"]);
+        let entity = format!("{}/entities/{}", txn, id);
+
+        let (status, written) = call(&service, Method::PUT, &entity, Some(json!({"vector": [1.0, 0.0]}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(written["version"], 1);
+        let (status, _) = call(&service, Method::GET, &entity, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let outside = format!("/v1/collections/docs/entities/{}", id);
+        assert_eq!(call(&service, Method::GET, &outside, None).await.0, StatusCode::NOT_FOUND);
+
+        let (status, committed) = call(&service, Method::POST, &format!("{}/commit", txn), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(committed["commit_ts"].as_u64().unwrap() > 0);
+        let (_, stored) = call(&service, Method::GET, &outside, None).await;
+        assert_eq!(stored["version"], 1);
+
+        // The transaction is closed, and ids must be numeric
+        assert_eq!(call(&service, Method::POST, &format!("{}/abort", txn), None).await.0, StatusCode::NOT_FOUND);
+        let (status, _) = call(&service, Method::POST, "/v1/collections/docs/transactions/x/commit", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_errors_map_to_status_codes_with_correlation_ids() {
+        let service = service();
+        service.create_collection("docs", CollectionConfig::new(2)).unwrap();
+        let missing = format!("/v1/collections/docs/entities/{}", EntityId::new());
+
+        let cases = [
+            (Method::GET, "/v1/collections/nope".to_string(), None, StatusCode::NOT_FOUND),
+            (Method::GET, missing, None, StatusCode::NOT_FOUND),
+            (
+                Method::GET,
+                "/v1/collections/docs/entities/not-a-uuid".to_string(),
+                None,
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                Method::POST,
+                "/v1/collections".to_string(),
+                Some(json!({"name": "docs", "config": {"dimension": 2}})),
+                StatusCode::CONFLICT,
+            ),
+            (
+                Method::POST,
+                "/v1/collections/docs/entities".to_string(),
+                Some(json!({"vector": [1.0, 2.0, 3.0]})),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                Method::POST,
+                "/v1/collections/docs/search".to_string(),
+                Some(json!({"k": 3})),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                Method::POST,
+                "/v1/query".to_string(),
+                Some(json!({"query": "FIND IN docs WHERE"})),
+                StatusCode::BAD_REQUEST,
+            ),
+            (Method::GET, "/v2/anything".to_string(), None, StatusCode::NOT_FOUND),
+            (Method::PATCH, "/v1/collections".to_string(), None, StatusCode::METHOD_NOT_ALLOWED),
+        ];
+        let not_allowed = Request::builder()
+            .method(Method::PATCH)
+            .uri("/v1/collections")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(handle(&service, not_allowed, 1024).await.headers()[ALLOW], "GET, POST");
+        for (method, path, body, expected) in cases {
+            let request = Request::builder()
+                .method(method.clone())
+                .uri(&path)
+                .body(body.map(|b: Value| Body::from(b.to_string())).unwrap_or_default())
+                .unwrap();
+            let response = handle(&service, request, 1024).await;
+            assert_eq!(response.status(), expected, "{} {}", method, path);
+
+            let header = response.headers()[CORRELATION_ID_KEY].to_str().unwrap().to_string();
+            let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            let error: Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(error["error"]["status"], expected.as_u16());
+            assert_eq!(error["error"]["correlation_id"], header.as_str());
+        }
+
+        let oversized = Request::builder()
+            .method(Method::POST)
+            .uri("/v1/collections/docs/entities")
+            .body(Body::from(vec![b' '; 64]))
+            .unwrap();
+        assert_eq!(handle(&service, oversized, 16).await.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // Only requests that fail to decode are the client's fault; failing
+        // to encode our own data is a server error
+        let undecodable = MemorySubstrateError::InvalidRequest("missing field 'entity'".to_string());
+        assert_eq!(http_status(&undecodable), StatusCode::BAD_REQUEST);
+        let unencodable = MemorySubstrateError::Serialization("key must be a string".to_string());
+        assert_eq!(http_status(&unencodable), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_openapi_documents_every_route() {
+        let document = openapi();
+        assert_eq!(document["info"]["version"], crate::VERSION);
+
+        let paths = document["paths"].as_object().unwrap();
+        for (path, operations) in paths {
+            let concrete = path
+                .replace("{collection}", "docs")
+                .replace("{alias}", "live")
+                .replace("{transaction}", "7")
+                .replace("{id}", &EntityId::new().to_string());
+            for method in operations.as_object().unwrap().keys().filter(|key| *key != "parameters") {
+                let method = Method::from_bytes(method.to_uppercase().as_bytes()).unwrap();
+                assert!(route(&method, &concrete).is_ok(), "{} {} is documented but not routed", method, path);
+            }
+        }
+
+        let (status, served) = call(&service(), Method::GET, "/openapi.json", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(served, document);
+    }
+
+    #[tokio::test]
+    async fn test_serves_over_tcp_until_shutdown() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            serve_with_listener(service(), &ApiConfig::default(), listener, async {
+                let _ = stopped.await;
+            })
+            .await
+        });
+
+        let client = hyper::Client::new();
+        let uri: hyper::Uri = format!("http://{}/v1/collections", address).parse().unwrap();
+        let response = client.get(uri).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(bytes.as_ref(), b"[]");
+
+        // An idle change stream does not hold up shutdown
+        let uri: hyper::Uri = format!("http://{}/v1/cdc", address).parse().unwrap();
+        let stream = client.get(uri).await.unwrap();
+        assert_eq!(stream.status(), StatusCode::OK);
+
+        stop.send(()).unwrap();
+        server.await.unwrap().unwrap();
+        assert!(hyper::body::to_bytes(stream.into_body()).await.map_or(true, |rest| rest.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_cdc_streams_ndjson() {
+        let service = service();
+        call(
+            &service,
+            Method::POST,
+            "/v1/collections",
+            Some(json!({"name": "docs", "config": {"dimension": 2}})),
+        )
+        .await;
+        let (_, target) = call(
+            &service,
+            Method::POST,
+            "/v1/collections/docs/entities",
+            Some(json!({"vector": [0.0, 1.0]})),
+        )
+        .await;
+        let (_, source) = call(
+            &service,
+            Method::POST,
+            "/v1/collections/docs/entities",
+            Some(json!({"vector": [1.0, 0.0], "edges": [{"target_id": target["id"], "label": "cites"}]})),
+        )
+        .await;
+
+        let request = Request::get("/v1/cdc?after=1&collection=docs").body(Body::empty()).unwrap();
+        let response = handle(&service, request, 1024).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/x-ndjson");
+        let mut body = response.into_body();
+        let mut lines = Vec::new();
+        while lines.len() < 2 {
+            let chunk = body.data().await.unwrap().unwrap();
+            let text = String::from_utf8(chunk.to_vec()).unwrap();
+            lines.extend(text.lines().map(|line| serde_json::from_str::<Value>(line).unwrap()));
+        }
+        assert_eq!((lines[0]["sequence"].clone(), lines[0]["type"].clone()), (json!(2), json!("entity_upsert")));
+        assert_eq!(lines[0]["entity"]["id"], source["id"]);
+        assert_eq!(lines[1]["type"], "edge_create");
+        assert_eq!(lines[1]["source_id"], source["id"]);
+        assert_eq!(lines[1]["edge"]["target_id"], target["id"]);
+
+        // Live changes follow the replayed ones
+        let path = format!("/v1/collections/docs/entities/{}", source["id"].as_str().unwrap());
+        call(&service, Method::DELETE, &path, None).await;
+        let chunk = body.data().await.unwrap().unwrap();
+        let deleted: Value = serde_json::from_slice(chunk.trim_ascii_end()).unwrap();
+        assert_eq!((deleted["sequence"].clone(), deleted["type"].clone()), (json!(4), json!("entity_delete")));
+
+        let (status, _) = call(&service, Method::GET, "/v1/cdc?after=soon", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = call(&service, Method::GET, "/v1/cdc?collection=missing", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
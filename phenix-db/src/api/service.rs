@@ -0,0 +1,997 @@
+//! Transport-independent API operations
+//!
+//! `ApiService` is the single entry point shared by the network front ends.
+//! It owns the collection catalog behind a lock, stamps entity versions and
+//! timestamps, publishes a `ChangeEvent` for every committed mutation and
+//! reports cluster status. Front ends only translate wire types and map
+//! `MemorySubstrateError` onto their own status codes.
+//!
+//! Only collection and alias changes take the catalog lock exclusively.
+//! Entity writes share it and serialize on the collection they touch, so a
+//! commit waiting on a log fsync holds up writers of that collection only.
+//! Calls block, so async front ends make them through `blocking`, which
+//! runs them on the blocking thread pool.
+//!
+//! Searches, traversals and queries each run against one MVCC snapshot and
+//! hand back the entity versions they read, so entities attached to a
+//! result always match the scores and edges that produced it.
+//!
+//! Besides the live `ChangeEvent` feed, `cdc` exposes the catalog's
+//! resumable change data capture stream.
+//!
+//! Clients can also group reads and writes of one collection into a
+//! transaction: `begin_transaction` opens it and returns its id, later
+//! calls name that id, and `commit_transaction` logs and applies the whole
+//! write set at once. Transactions left idle for longer than the
+//! transaction timeout are aborted.
+
+use crate::api::cognitive_query::{self, CognitiveQuery, Explain, QueryRow};
+use crate::api::query_planner::QueryPlanner;
+use crate::concurrency::access_tracker::AccessBatch;
+use crate::concurrency::transaction_coordinator::Transaction;
+use crate::core::collection::{CollectionConfig, CollectionInfo, CollectionStats};
+use crate::core::config::{CostWeights, PlannerConfig};
+use crate::core::error::{CollectionError, ConcurrencyError, MemorySubstrateError, Result};
+use crate::core::mvcc::{MvccWrite, Timestamp};
+use crate::core::query::{GraphQuery, TraversalStep, VectorQuery};
+use crate::core::transaction::TransactionId;
+use crate::core::{CollectionId, Entity, EntityId, NodeId};
+use crate::distributed::node::{Member, Membership};
+use crate::index::filtered::FilteredSearchResult;
+use crate::storage::catalog::Catalog;
+use crate::storage::cdc::CdcStream;
+use crate::storage::checkpoint::Checkpoint;
+use futures::{future, StreamExt};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+/// Kind of entity mutation carried by a change event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Entity was created or replaced
+    Upsert,
+
+    /// Entity was removed
+    Delete,
+}
+
+/// A committed entity mutation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeEvent {
+    /// Position in this server's change sequence, starting at 1
+    pub sequence: u64,
+
+    /// Primary name of the collection, even if written through an alias
+    pub collection: String,
+
+    /// What happened
+    pub kind: ChangeKind,
+
+    /// Affected entity
+    pub entity_id: EntityId,
+
+    /// New state of the entity; `None` for deletes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity: Option<Entity>,
+
+    /// Commit time (Unix epoch milliseconds)
+    pub timestamp: u64,
+}
+
+/// An entity rejected from a batch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rejection {
+    /// Position of the entity in the batch
+    pub index: usize,
+
+    /// Id of the rejected entity
+    pub entity_id: EntityId,
+
+    /// Why it was rejected
+    pub reason: String,
+}
+
+/// Result of a batch upsert
+///
+/// Valid entities are applied even when others are rejected.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BatchOutcome {
+    /// Number of entities written
+    pub upserted: usize,
+
+    /// Entities that failed validation
+    pub rejected: Vec<Rejection>,
+}
+
+impl BatchOutcome {
+    /// Fold another batch into this one, offsetting its rejection indexes
+    pub fn merge(&mut self, other: BatchOutcome, offset: usize) {
+        self.upserted += other.upserted;
+        self.rejected.extend(other.rejected.into_iter().map(|mut rejection| {
+            rejection.index += offset;
+            rejection
+        }));
+    }
+}
+
+/// Snapshot of the serving node and its view of the cluster
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterStatus {
+    /// Serving node
+    pub node_id: NodeId,
+
+    /// Server version
+    pub version: String,
+
+    /// Time since the service started (milliseconds)
+    pub uptime_ms: u64,
+
+    /// Number of collections
+    pub collections: usize,
+
+    /// Number of entities across all collections
+    pub entities: usize,
+
+    /// Known members; empty for a standalone node
+    pub members: Vec<Member>,
+}
+
+/// Answer to a cognitive query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryOutput {
+    /// Primary name of the queried collection
+    pub collection: String,
+
+    /// Results; empty for `EXPLAIN`
+    pub rows: Vec<QueryRow>,
+
+    /// Entity version behind each row, read from the snapshot the query ran
+    /// against
+    #[serde(skip)]
+    pub entities: Vec<Option<Arc<Entity>>>,
+
+    /// Costed plan, for `EXPLAIN` queries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explain: Option<Explain>,
+}
+
+/// A client transaction between calls
+struct OpenTransaction {
+    txn: Transaction,
+    last_used: Instant,
+}
+
+/// Catalog operations shared by the gRPC and REST front ends
+pub struct ApiService {
+    node_id: NodeId,
+    catalog: RwLock<Catalog>,
+    changes: broadcast::Sender<ChangeEvent>,
+    sequence: Mutex<u64>,
+    transactions: Mutex<HashMap<(CollectionId, TransactionId), OpenTransaction>>,
+    transaction_timeout: Duration,
+    membership: Option<Arc<Mutex<Membership>>>,
+    planner: QueryPlanner,
+    started: Instant,
+}
+
+impl ApiService {
+    /// Serve `catalog`, buffering up to `change_buffer` events per subscriber
+    pub fn new(node_id: NodeId, catalog: Catalog, change_buffer: usize) -> Self {
+        let (changes, _) = broadcast::channel(change_buffer.max(1));
+        Self {
+            node_id,
+            catalog: RwLock::new(catalog),
+            changes,
+            sequence: Mutex::new(0),
+            transactions: Mutex::new(HashMap::new()),
+            transaction_timeout: Duration::from_secs(60),
+            membership: None,
+            planner: QueryPlanner::new(CostWeights::default(), PlannerConfig::default()),
+            started: Instant::now(),
+        }
+    }
+
+    /// Report cluster members from `membership` in `cluster_status`
+    pub fn with_membership(mut self, membership: Arc<Mutex<Membership>>) -> Self {
+        self.membership = Some(membership);
+        self
+    }
+
+    /// Weigh plan cost estimates with `weights`
+    /// (normally `BellmanConfig::cost_weights`)
+    pub fn with_cost_weights(mut self, weights: CostWeights) -> Self {
+        self.planner = QueryPlanner::new(weights, self.planner.config().clone());
+        self
+    }
+
+    /// Cache and prune query plans as `config` says
+    pub fn with_planner_config(mut self, config: PlannerConfig) -> Self {
+        self.planner = QueryPlanner::new(self.planner.cost_model().weights().clone(), config);
+        self
+    }
+
+    /// Abort client transactions left idle for longer than `timeout`
+    /// (normally `ApiConfig::transaction_timeout_ms`)
+    pub fn with_transaction_timeout(mut self, timeout: Duration) -> Self {
+        self.transaction_timeout = timeout;
+        self
+    }
+
+    /// Planner choosing how cognitive queries run
+    pub fn planner(&self) -> &QueryPlanner {
+        &self.planner
+    }
+
+    /// Serving node
+    pub fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
+    /// Run `f` against the service on the blocking thread pool
+    ///
+    /// # Errors
+    /// * `Internal` if `f` panicked
+    /// * Any error returned by `f`
+    pub async fn blocking<T, F>(self: &Arc<Self>, f: F) -> Result<T>
+    where
+        F: FnOnce(&ApiService) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let service = Arc::clone(self);
+        tokio::task::spawn_blocking(move || f(&service))
+            .await
+            .map_err(|error| MemorySubstrateError::Internal(format!("service call did not complete: {}", error)))?
+    }
+
+    /// Create a collection
+    pub fn create_collection(&self, name: &str, config: CollectionConfig) -> Result<CollectionInfo> {
+        self.catalog.write().create_collection(name, config)
+    }
+
+    /// Drop a collection by primary name
+    pub fn drop_collection(&self, name: &str) -> Result<()> {
+        self.catalog.write().drop_collection(name)
+    }
+
+    /// Describe every collection, ordered by name
+    pub fn list_collections(&self) -> Vec<CollectionInfo> {
+        self.catalog.read().list_collections()
+    }
+
+    /// Describe a collection by name or alias
+    pub fn describe_collection(&self, name: &str) -> Result<CollectionInfo> {
+        self.catalog.read().describe_collection(name)
+    }
+
+    /// Tier sizes, edge counts and access entropy of a collection
+    pub fn collection_stats(&self, name: &str) -> Result<CollectionStats> {
+        Ok(self.catalog.read().collection(name)?.stats())
+    }
+
+    /// Point `alias` at `target`
+    pub fn set_alias(&self, alias: &str, target: &str) -> Result<()> {
+        self.catalog.write().set_alias(alias, target)
+    }
+
+    /// Remove an alias
+    pub fn drop_alias(&self, alias: &str) -> Result<()> {
+        self.catalog.write().drop_alias(alias)
+    }
+
+    /// Insert a new entity at version 1
+    ///
+    /// # Errors
+    /// * `EntityAlreadyExists` if the id is taken
+    pub fn create_entity(&self, collection: &str, mut entity: Entity) -> Result<Entity> {
+        let catalog = self.catalog.read();
+        let stored = catalog.collection(collection)?;
+        let _updates = stored.lock_updates();
+        let name = stored.name().to_string();
+        if stored.contains(&entity.id) {
+            return Err(CollectionError::EntityAlreadyExists {
+                collection: name,
+                entity_id: entity.id.to_string(),
+            }
+            .into());
+        }
+        let now = now_ms();
+        entity.created_at = now;
+        entity.updated_at = now;
+        entity.version = 1;
+        entity.commit_ts = catalog.upsert(collection, entity.clone())?;
+        self.publish(name, ChangeKind::Upsert, entity.id, Some(entity.clone()));
+        Ok(entity)
+    }
+
+    /// Replace an existing entity, bumping its version
+    ///
+    /// The creation time of the stored entity is kept.
+    ///
+    /// # Errors
+    /// * `EntityNotFound` if there is nothing to replace
+    pub fn update_entity(&self, collection: &str, mut entity: Entity) -> Result<Entity> {
+        let catalog = self.catalog.read();
+        let stored = catalog.collection(collection)?;
+        let _updates = stored.lock_updates();
+        let name = stored.name().to_string();
+        let current = stored.get(&entity.id).ok_or_else(|| CollectionError::EntityNotFound {
+            collection: name.clone(),
+            entity_id: entity.id.to_string(),
+        })?;
+        entity.created_at = current.created_at;
+        entity.updated_at = now_ms().max(current.updated_at);
+        entity.version = current.version + 1;
+        entity.commit_ts = catalog.upsert(collection, entity.clone())?;
+        self.publish(name, ChangeKind::Upsert, entity.id, Some(entity.clone()));
+        Ok(entity)
+    }
+
+    /// Upsert many entities while holding off other updates of the
+    /// collection
+    ///
+    /// Entities that fail validation are reported in the outcome instead of
+    /// failing the batch.
+    ///
+    /// # Errors
+    /// * `NotFound` if the collection does not resolve
+    pub fn batch_upsert(&self, collection: &str, entities: Vec<Entity>) -> Result<BatchOutcome> {
+        let catalog = self.catalog.read();
+        let stored = catalog.collection(collection)?;
+        let _updates = stored.lock_updates();
+        let name = stored.name().to_string();
+        let mut outcome = BatchOutcome::default();
+        for (index, mut entity) in entities.into_iter().enumerate() {
+            let now = now_ms();
+            let current = stored.get(&entity.id);
+            match current {
+                Some(current) => {
+                    entity.created_at = current.created_at;
+                    entity.version = current.version + 1;
+                }
+                None => {
+                    entity.created_at = now;
+                    entity.version = 1;
+                }
+            }
+            entity.updated_at = now;
+
+            match catalog.upsert(collection, entity.clone()) {
+                Ok(commit_ts) => {
+                    entity.commit_ts = commit_ts;
+                    outcome.upserted += 1;
+                    self.publish(name.clone(), ChangeKind::Upsert, entity.id, Some(entity));
+                }
+                Err(error @ (MemorySubstrateError::Collection { .. }
+                | MemorySubstrateError::Metadata { .. }
+                | MemorySubstrateError::DimensionMismatch { .. })) => {
+                    outcome.rejected.push(Rejection {
+                        index,
+                        entity_id: entity.id,
+                        reason: error.to_string(),
+                    });
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(outcome)
+    }
+
+    /// Look up an entity, recording the access
+    ///
+    /// # Errors
+    /// * `EntityNotFound` if the id is not stored
+    pub fn get_entity(&self, collection: &str, id: &EntityId) -> Result<Entity> {
+        let catalog = self.catalog.read();
+        match catalog.get(collection, id)? {
+            Some(entity) => Ok((*entity).clone()),
+            None => Err(CollectionError::EntityNotFound {
+                collection: collection.to_string(),
+                entity_id: id.to_string(),
+            }
+            .into()),
+        }
+    }
+
+    /// Delete an entity, returning whether it existed
+    pub fn delete_entity(&self, collection: &str, id: &EntityId) -> Result<bool> {
+        let catalog = self.catalog.read();
+        let stored = catalog.collection(collection)?;
+        let _updates = stored.lock_updates();
+        let name = stored.name().to_string();
+        let deleted = catalog.delete(collection, id)?;
+        if deleted {
+            self.publish(name, ChangeKind::Delete, *id, None);
+        }
+        Ok(deleted)
+    }
+
+    /// Open a transaction on a collection, returning its id
+    ///
+    /// Reads in the transaction see the collection as of this call plus the
+    /// transaction's own writes. Open transactions that have been idle for
+    /// longer than the transaction timeout are aborted here.
+    pub fn begin_transaction(&self, collection: &str) -> Result<TransactionId> {
+        let (id, txn) = {
+            let catalog = self.catalog.read();
+            let stored = catalog.collection(collection)?;
+            (stored.id(), stored.transactions().begin())
+        };
+        let txn_id = txn.id();
+        let mut open = self.transactions.lock();
+        open.retain(|_, open| open.last_used.elapsed() < self.transaction_timeout);
+        open.insert(
+            (id, txn_id),
+            OpenTransaction {
+                txn,
+                last_used: Instant::now(),
+            },
+        );
+        Ok(txn_id)
+    }
+
+    /// Read an entity within a transaction
+    ///
+    /// # Errors
+    /// * `TransactionNotFound` if the transaction is not open
+    /// * `EntityNotFound` if the transaction does not see the entity
+    pub fn transaction_get(&self, collection: &str, txn_id: TransactionId, id: &EntityId) -> Result<Entity> {
+        self.with_transaction(collection, txn_id, |txn| {
+            txn.get(id).ok_or_else(|| {
+                CollectionError::EntityNotFound {
+                    collection: collection.to_string(),
+                    entity_id: id.to_string(),
+                }
+                .into()
+            })
+        })
+    }
+
+    /// Buffer an insert or replace in a transaction
+    ///
+    /// Versions and timestamps are stamped as `create_entity` and
+    /// `update_entity` would; writing the same entity twice in one
+    /// transaction bumps its version once. The returned entity gets its
+    /// `commit_ts` on commit.
+    pub fn transaction_put(&self, collection: &str, txn_id: TransactionId, mut entity: Entity) -> Result<Entity> {
+        self.with_transaction(collection, txn_id, |txn| {
+            let rewrite = txn.writes().any(|write| write.id() == entity.id);
+            let now = now_ms();
+            match txn.get(&entity.id) {
+                Some(current) => {
+                    entity.created_at = current.created_at;
+                    entity.updated_at = now.max(current.updated_at);
+                    entity.version = if rewrite { current.version } else { current.version + 1 };
+                }
+                None => {
+                    entity.created_at = now;
+                    entity.updated_at = now;
+                    entity.version = 1;
+                }
+            }
+            entity.commit_ts = 0;
+            txn.put(entity.clone())?;
+            Ok(entity)
+        })
+    }
+
+    /// Buffer a delete in a transaction, returning whether the transaction
+    /// saw the entity
+    pub fn transaction_delete(&self, collection: &str, txn_id: TransactionId, id: &EntityId) -> Result<bool> {
+        self.with_transaction(collection, txn_id, |txn| {
+            let existed = txn.get(id).is_some();
+            if existed {
+                txn.delete(*id)?;
+            }
+            Ok(existed)
+        })
+    }
+
+    /// Commit a transaction, returning its commit timestamp
+    ///
+    /// The transaction is closed whether or not the commit succeeds.
+    ///
+    /// # Errors
+    /// * `TransactionNotFound` if the transaction is not open
+    /// * `TransactionConflict` if an entity it read or wrote was changed by
+    ///   another commit; nothing is applied
+    pub fn commit_transaction(&self, collection: &str, txn_id: TransactionId) -> Result<Timestamp> {
+        let catalog = self.catalog.read();
+        let stored = catalog.collection(collection)?;
+        let _updates = stored.lock_updates();
+        let (id, name) = (stored.id(), stored.name().to_string());
+        let mut txn = self.take_transaction(id, txn_id)?;
+        let writes: Vec<MvccWrite> = txn.writes().cloned().collect();
+
+        let commit_ts = catalog.commit_transaction(id, &mut txn)?;
+        self.publish_writes(&name, writes, commit_ts);
+        Ok(commit_ts)
+    }
+
+    /// Run `body` in a transaction on a collection, retrying it on conflicts
+    ///
+    /// The committed attempt is logged and published like
+    /// `commit_transaction`. `body` stamps versions itself; it is re-run from
+    /// a fresh snapshot after every conflict.
+    ///
+    /// # Returns
+    /// * What `body` returned on the committed attempt, with the commit
+    ///   timestamp
+    ///
+    /// # Errors
+    /// * `RetryLimitExceeded` once every attempt has conflicted
+    /// * Any other error from `body` or the commit; nothing is applied
+    pub fn run_transaction<T, F>(&self, collection: &str, mut body: F) -> Result<(T, Timestamp)>
+    where
+        F: FnMut(&mut Transaction) -> Result<T>,
+    {
+        let catalog = self.catalog.read();
+        let stored = catalog.collection(collection)?;
+        let _updates = stored.lock_updates();
+        let name = stored.name().to_string();
+        let ((value, writes), commit_ts) = catalog.run_transaction(collection, |txn| {
+            let value = body(txn)?;
+            Ok((value, txn.writes().cloned().collect::<Vec<MvccWrite>>()))
+        })?;
+        self.publish_writes(&name, writes, commit_ts);
+        Ok((value, commit_ts))
+    }
+
+    /// Discard a transaction and its writes
+    ///
+    /// # Errors
+    /// * `TransactionNotFound` if the transaction is not open
+    pub fn abort_transaction(&self, collection: &str, txn_id: TransactionId) -> Result<()> {
+        let catalog = self.catalog.read();
+        let stored = catalog.collection(collection)?;
+        let mut txn = self.take_transaction(stored.id(), txn_id)?;
+        stored.transactions().rollback(&mut txn);
+        Ok(())
+    }
+
+    /// Run a k-NN query against a snapshot, returning the hits with the
+    /// entity versions they were scored against
+    pub fn search(&self, collection: &str, query: &VectorQuery) -> Result<(FilteredSearchResult, Vec<Arc<Entity>>)> {
+        self.catalog.read().search(collection, query)
+    }
+
+    /// Traverse edges within a snapshot, returning the steps with the
+    /// entity version reached at each
+    pub fn traverse(&self, collection: &str, query: &GraphQuery) -> Result<(Vec<TraversalStep>, Vec<Arc<Entity>>)> {
+        self.catalog.read().traverse(collection, query)
+    }
+
+    /// Run a cognitive query, or cost its plan for `EXPLAIN`
+    ///
+    /// Returned rows are recorded as accessed, and as co-accessed with the
+    /// first row.
+    pub fn query(&self, text: &str) -> Result<QueryOutput> {
+        let query = CognitiveQuery::parse(text)?;
+        let catalog = self.catalog.read();
+        let collection = catalog.collection(&query.collection)?;
+        if query.explain {
+            return Ok(QueryOutput {
+                collection: collection.name().to_string(),
+                rows: Vec::new(),
+                entities: Vec::new(),
+                explain: Some(self.planner.explain(&query, collection)),
+            });
+        }
+
+        let planned = self.planner.plan(&query, collection);
+        let view = collection.view();
+        let rows = cognitive_query::execute(&planned.plan, &view)?;
+        let entities = rows.iter().map(|row| view.get(&row.id)).collect();
+        if let Some(first) = rows.first() {
+            let mut batch = AccessBatch::new();
+            for row in &rows {
+                batch.record(row.id);
+                batch.record_co_access(first.id, row.id);
+            }
+            collection.access().apply(&mut batch);
+        }
+        Ok(QueryOutput {
+            collection: collection.name().to_string(),
+            rows,
+            entities,
+            explain: None,
+        })
+    }
+
+    /// Receive every change committed after this call
+    ///
+    /// A subscriber that falls more than `change_buffer` events behind
+    /// observes `RecvError::Lagged` and should resynchronise.
+    pub fn subscribe(&self) -> broadcast::Receiver<ChangeEvent> {
+        self.changes.subscribe()
+    }
+
+    /// Stream change data capture events after sequence `after`
+    ///
+    /// Events are limited to `collections` (names or aliases) unless it is
+    /// empty. Sequence numbers are global, so a filtered consumer resumes
+    /// with the last sequence it received like any other.
+    ///
+    /// # Errors
+    /// * `Collection(NotFound)` if a collection does not resolve
+    /// * `Cdc(Expired)` if the requested events are no longer available
+    pub fn cdc(&self, after: u64, collections: &[String]) -> Result<CdcStream> {
+        let (changes, ids) = {
+            let catalog = self.catalog.read();
+            let ids = collections
+                .iter()
+                .map(|name| catalog.resolve(name))
+                .collect::<Result<Vec<_>>>()?;
+            (catalog.changes(), ids)
+        };
+        let stream = changes.subscribe(after)?;
+        if ids.is_empty() {
+            return Ok(stream);
+        }
+        Ok(stream
+            .filter(move |item| {
+                future::ready(match item {
+                    Ok(event) => ids.contains(&event.collection_id),
+                    Err(_) => true,
+                })
+            })
+            .boxed())
+    }
+
+    /// Describe this node and the cluster it belongs to
+    pub fn cluster_status(&self) -> ClusterStatus {
+        let (collections, entities) = {
+            let catalog = self.catalog.read();
+            let infos = catalog.list_collections();
+            let entities = infos.iter().map(|info| info.entity_count).sum();
+            (infos.len(), entities)
+        };
+        let members = match &self.membership {
+            Some(membership) => membership.lock().members().cloned().collect(),
+            None => Vec::new(),
+        };
+        ClusterStatus {
+            node_id: self.node_id,
+            version: crate::VERSION.to_string(),
+            uptime_ms: self.started.elapsed().as_millis() as u64,
+            collections,
+            entities,
+            members,
+        }
+    }
+
+    /// Flush the catalog's write-ahead log
+    pub fn sync(&self) -> Result<()> {
+        self.catalog.read().sync()
+    }
+
+    /// Flush the write-ahead log, snapshot the catalog into a checkpoint
+    /// next to it and reset the log; `None` for an in-memory catalog
+    pub fn checkpoint(&self) -> Result<Option<Checkpoint>> {
+        self.catalog.write().checkpoint()
+    }
+
+    /// Statistics of every collection, ordered by name
+    pub fn all_collection_stats(&self) -> Vec<CollectionStats> {
+        self.catalog.read().collection_stats()
+    }
+
+    /// Run `f` against a consistent view of the catalog
+    ///
+    /// Holds the read lock for the duration of `f`; background workers use
+    /// it to plan changes, then commit them with `apply_maintenance`.
+    pub fn inspect<R>(&self, f: impl FnOnce(&Catalog) -> R) -> R {
+        f(&self.catalog.read())
+    }
+
+    /// Write entities rewritten by background maintenance, returning how
+    /// many were applied
+    ///
+    /// Each entity must carry the version it was planned from. Entities
+    /// that were since updated or deleted are skipped, so maintenance never
+    /// overwrites a client write.
+    pub fn apply_maintenance(&self, collection: &str, entities: Vec<Entity>) -> Result<usize> {
+        let catalog = self.catalog.read();
+        let stored = catalog.collection(collection)?;
+        let _updates = stored.lock_updates();
+        let name = stored.name().to_string();
+        let mut applied = 0;
+        for mut entity in entities {
+            let Some(current) = stored.get(&entity.id) else {
+                continue;
+            };
+            if current.version != entity.version {
+                continue;
+            }
+            entity.created_at = current.created_at;
+            entity.updated_at = now_ms().max(current.updated_at);
+            entity.version = current.version + 1;
+            catalog.upsert(collection, entity.clone())?;
+            self.publish(name.clone(), ChangeKind::Upsert, entity.id, Some(entity));
+            applied += 1;
+        }
+        Ok(applied)
+    }
+
+    /// Run `f` on an open transaction, refreshing its idle time
+    fn with_transaction<R>(
+        &self,
+        collection: &str,
+        txn_id: TransactionId,
+        f: impl FnOnce(&mut Transaction) -> Result<R>,
+    ) -> Result<R> {
+        let id = self.catalog.read().resolve(collection)?;
+        let mut open = self.transactions.lock();
+        match open.get_mut(&(id, txn_id)) {
+            Some(entry) if entry.last_used.elapsed() < self.transaction_timeout => {
+                entry.last_used = Instant::now();
+                f(&mut entry.txn)
+            }
+            _ => {
+                // Dropping an expired transaction aborts it
+                open.remove(&(id, txn_id));
+                Err(ConcurrencyError::TransactionNotFound { id: txn_id }.into())
+            }
+        }
+    }
+
+    /// Close an open, unexpired transaction and hand it to the caller
+    fn take_transaction(&self, collection: CollectionId, txn_id: TransactionId) -> Result<Transaction> {
+        match self.transactions.lock().remove(&(collection, txn_id)) {
+            Some(entry) if entry.last_used.elapsed() < self.transaction_timeout => Ok(entry.txn),
+            _ => Err(ConcurrencyError::TransactionNotFound { id: txn_id }.into()),
+        }
+    }
+
+    /// Publish the writes of a committed transaction
+    fn publish_writes(&self, collection: &str, writes: Vec<MvccWrite>, commit_ts: Timestamp) {
+        for write in writes {
+            match write {
+                MvccWrite::Put(mut entity) => {
+                    entity.commit_ts = commit_ts;
+                    self.publish(collection.to_string(), ChangeKind::Upsert, entity.id, Some(*entity));
+                }
+                MvccWrite::Delete(entity_id) => {
+                    self.publish(collection.to_string(), ChangeKind::Delete, entity_id, None)
+                }
+            }
+        }
+    }
+
+    // Called with the collection's update lock held, so sequence order
+    // matches commit order within a collection.
+    fn publish(&self, collection: String, kind: ChangeKind, entity_id: EntityId, entity: Option<Entity>) {
+        let mut sequence = self.sequence.lock();
+        *sequence += 1;
+        // Sending only fails when nobody is subscribed.
+        let _ = self.changes.send(ChangeEvent {
+            sequence: *sequence,
+            collection,
+            kind,
+            entity_id,
+            entity,
+            timestamp: now_ms(),
+        });
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{MemoryTier, Vector};
+
+    fn service() -> ApiService {
+        let service = ApiService::new(NodeId::new(), Catalog::in_memory(), 16);
+        service.create_collection("docs", CollectionConfig::new(2)).unwrap();
+        service.set_alias("current", "docs").unwrap();
+        service
+    }
+
+    fn entity(x: f32) -> Entity {
+        Entity::new(Some(Vector::new(vec![x, 1.0])), None, None)
+    }
+
+    #[test]
+    fn test_entity_lifecycle_versions_and_changes() {
+        let service = service();
+        let mut changes = service.subscribe();
+
+        let created = service.create_entity("current", entity(0.5)).unwrap();
+        assert_eq!(created.version, 1);
+        let duplicate = service.create_entity("docs", created.clone());
+        assert!(matches!(
+            duplicate,
+            Err(MemorySubstrateError::Collection {
+                error: CollectionError::EntityAlreadyExists { .. },
+                ..
+            })
+        ));
+
+        let mut replacement = entity(0.7);
+        replacement.id = created.id;
+        let updated = service.update_entity("docs", replacement).unwrap();
+        assert_eq!(updated.version, 2);
+        assert_eq!(updated.created_at, created.created_at);
+        // The write counter and the MVCC commit timestamp move independently
+        assert!(updated.commit_ts > created.commit_ts);
+        assert_eq!(service.get_entity("docs", &created.id).unwrap().commit_ts, updated.commit_ts);
+        assert!(service.update_entity("docs", entity(0.1)).is_err());
+
+        assert!(service.delete_entity("docs", &created.id).unwrap());
+        assert!(!service.delete_entity("docs", &created.id).unwrap());
+        assert!(service.get_entity("docs", &created.id).is_err());
+
+        let events: Vec<_> = std::iter::from_fn(|| changes.try_recv().ok()).collect();
+        let kinds: Vec<_> = events.iter().map(|event| (event.sequence, event.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![(1, ChangeKind::Upsert), (2, ChangeKind::Upsert), (3, ChangeKind::Delete)]
+        );
+        assert!(events.iter().all(|event| event.collection == "docs"));
+    }
+
+    #[test]
+    fn test_batch_upsert_reports_rejections() {
+        let service = service();
+        let bad = Entity::new(Some(Vector::new(vec![1.0, 2.0, 3.0])), None, None);
+        let outcome = service
+            .batch_upsert("docs", vec![entity(0.1), bad.clone(), entity(0.2)])
+            .unwrap();
+
+        assert_eq!(outcome.upserted, 2);
+        assert_eq!(outcome.rejected.len(), 1);
+        assert_eq!(outcome.rejected[0].index, 1);
+        assert_eq!(outcome.rejected[0].entity_id, bad.id);
+        assert_eq!(service.cluster_status().entities, 2);
+        assert!(service.batch_upsert("missing", vec![entity(0.3)]).is_err());
+    }
+
+    #[test]
+    fn test_query_through_alias() {
+        let service = service();
+        let near = service.create_entity("docs", entity(0.1)).unwrap();
+        service.create_entity("docs", entity(-5.0)).unwrap();
+
+        let output = service.query("FIND IN current NEAR [0.1, 1] LIMIT 1").unwrap();
+        assert_eq!(output.collection, "docs");
+        assert_eq!(output.rows.len(), 1);
+        assert_eq!(output.rows[0].id, near.id);
+        assert!(output.explain.is_none());
+
+        let output = service.query("EXPLAIN FIND IN current NEAR [0.1, 1] LIMIT 1").unwrap();
+        assert!(output.rows.is_empty());
+        assert_eq!(output.explain.unwrap().statistics.entities, 2);
+
+        assert!(matches!(
+            service.query("FIND IN missing"),
+            Err(MemorySubstrateError::Collection { .. })
+        ));
+        assert!(matches!(service.query("FIND docs"), Err(MemorySubstrateError::Query { .. })));
+    }
+
+    #[test]
+    fn test_maintenance_never_overwrites_newer_writes() {
+        let service = service();
+        let stale = service.create_entity("docs", entity(0.1)).unwrap();
+        let fresh = service.create_entity("docs", entity(0.2)).unwrap();
+        let planned: Vec<Entity> = service.inspect(|catalog| {
+            let docs = catalog.collection("docs").unwrap();
+            [stale.id, fresh.id]
+                .iter()
+                .map(|id| {
+                    let mut entity = (*docs.get(id).unwrap()).clone();
+                    entity.tier = MemoryTier::Cold;
+                    entity
+                })
+                .collect()
+        });
+        let mut update = stale.clone();
+        update.metadata = Some(serde_json::json!({"edited": true}));
+        service.update_entity("docs", update).unwrap();
+
+        assert_eq!(service.apply_maintenance("current", planned).unwrap(), 1);
+        let stale = service.get_entity("docs", &stale.id).unwrap();
+        assert_eq!((stale.version, stale.tier), (2, MemoryTier::Hot));
+        let fresh = service.get_entity("docs", &fresh.id).unwrap();
+        assert_eq!((fresh.version, fresh.tier), (2, MemoryTier::Cold));
+    }
+
+    fn is_missing_transaction<T: std::fmt::Debug>(result: Result<T>) -> bool {
+        matches!(
+            result,
+            Err(MemorySubstrateError::Concurrency {
+                error: ConcurrencyError::TransactionNotFound { .. },
+                ..
+            })
+        )
+    }
+
+    #[test]
+    fn test_transactions_commit_atomically() {
+        let service = service();
+        let mut changes = service.subscribe();
+        let kept = service.create_entity("docs", entity(1.0)).unwrap();
+        let removed = service.create_entity("docs", entity(2.0)).unwrap();
+        changes.try_recv().unwrap();
+        changes.try_recv().unwrap();
+
+        let txn = service.begin_transaction("current").unwrap();
+        let mut update = kept.clone();
+        update.metadata = Some(serde_json::json!({"edited": true}));
+        assert_eq!(service.transaction_put("docs", txn, update.clone()).unwrap().version, 2);
+        assert_eq!(service.transaction_put("docs", txn, update).unwrap().version, 2);
+        let added = service.transaction_put("docs", txn, entity(3.0)).unwrap();
+        assert!(service.transaction_delete("docs", txn, &removed.id).unwrap());
+        assert!(service.transaction_get("docs", txn, &removed.id).is_err());
+
+        // Nothing is visible before commit
+        assert_eq!(service.get_entity("docs", &kept.id).unwrap().version, 1);
+        assert!(service.get_entity("docs", &added.id).is_err());
+
+        let commit_ts = service.commit_transaction("docs", txn).unwrap();
+        let stored = service.get_entity("docs", &kept.id).unwrap();
+        assert_eq!((stored.version, stored.commit_ts), (2, commit_ts));
+        assert!(service.get_entity("docs", &removed.id).is_err());
+        assert_eq!(service.get_entity("docs", &added.id).unwrap().version, 1);
+
+        let mut kinds: Vec<_> = (0..3).map(|_| changes.try_recv().unwrap().kind).collect();
+        kinds.sort_by_key(|kind| *kind == ChangeKind::Delete);
+        assert_eq!(kinds, vec![ChangeKind::Upsert, ChangeKind::Upsert, ChangeKind::Delete]);
+
+        // Committed transactions are closed
+        assert!(is_missing_transaction(service.commit_transaction("docs", txn)));
+
+        // Retried transactions are published like committed ones
+        let (version, commit_ts) = service
+            .run_transaction("current", |txn| {
+                let mut stored = txn.get(&kept.id).unwrap();
+                stored.version += 1;
+                txn.put_if_version(stored.clone(), stored.version - 1)?;
+                Ok(stored.version)
+            })
+            .unwrap();
+        assert_eq!(version, 3);
+        let event = changes.try_recv().unwrap();
+        assert_eq!((event.collection.as_str(), event.entity.unwrap().commit_ts), ("docs", commit_ts));
+    }
+
+    #[test]
+    fn test_transaction_conflicts_abort_and_expiry() {
+        let service = service();
+        let stored = service.create_entity("docs", entity(1.0)).unwrap();
+
+        let txn = service.begin_transaction("docs").unwrap();
+        service.transaction_get("docs", txn, &stored.id).unwrap();
+        service.transaction_put("docs", txn, entity(2.0)).unwrap();
+        service.update_entity("docs", stored.clone()).unwrap();
+        assert!(matches!(
+            service.commit_transaction("docs", txn),
+            Err(MemorySubstrateError::Concurrency {
+                error: ConcurrencyError::TransactionConflict { .. },
+                ..
+            })
+        ));
+        assert_eq!(service.collection_stats("docs").unwrap().entity_count, 1);
+
+        let txn = service.begin_transaction("docs").unwrap();
+        service.transaction_put("docs", txn, entity(2.0)).unwrap();
+        service.abort_transaction("docs", txn).unwrap();
+        assert!(is_missing_transaction(service.commit_transaction("docs", txn)));
+        assert_eq!(service.collection_stats("docs").unwrap().entity_count, 1);
+
+        let service = service.with_transaction_timeout(Duration::ZERO);
+        let txn = service.begin_transaction("docs").unwrap();
+        assert!(is_missing_transaction(service.transaction_put("docs", txn, entity(2.0))));
+    }
+}
@@ -0,0 +1,311 @@
+//! Background maintenance workers
+//!
+//! Each worker runs one pass per interval until it is stopped:
+//!
+//! * **tiering** moves entities between hot, warm and cold per [`TierPolicy`]
+//! * **pgm** prunes faded edges and renormalizes edge probabilities
+//! * **learning** reinforces edges between entities read together
+//! * **entropy** samples collection statistics for the metrics endpoint and
+//!   warns when reads concentrate on a few entities
+//!
+//! Passes plan under the catalog read lock and commit through
+//! [`ApiService::apply_maintenance`], so they are logged, published as
+//! change events and never overwrite a concurrent client write. Passes run
+//! on the blocking pool; stopping waits for a pass in progress to finish.
+
+use crate::api::service::ApiService;
+use crate::core::config::PhenixConfig;
+use crate::core::error::Result;
+use crate::core::Entity;
+use crate::memory::{EntropyMonitor, ProbabilisticGraphMemory};
+use crate::observability::metrics::{CollectionSample, Metrics};
+use crate::storage::collection::CollectionView;
+use crate::storage::tiering::TierPolicy;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+
+/// Running background workers
+pub struct Workers {
+    handles: Vec<JoinHandle<()>>,
+    stop: CancellationToken,
+}
+
+impl Workers {
+    /// Start every worker with the intervals and parameters of `config`
+    pub fn spawn(service: Arc<ApiService>, config: &PhenixConfig, metrics: Arc<Metrics>) -> Self {
+        let stop = CancellationToken::new();
+        let seconds = |secs: u64| Duration::from_secs(secs.max(1));
+        let mut workers = Self {
+            handles: Vec::new(),
+            stop,
+        };
+
+        let policy = TierPolicy::new(config.tiering.clone());
+        workers.every("tiering", seconds(config.server.tiering_interval_secs), &service, &metrics, {
+            move |service: &ApiService| rebalance_tiers(service, &policy)
+        });
+
+        let pgm = ProbabilisticGraphMemory::with_config(config.pgm.clone());
+        workers.every("pgm", seconds(config.pgm.normalization_interval_secs), &service, &metrics, {
+            let pgm = pgm.clone();
+            move |service: &ApiService| normalize_edges(service, &pgm)
+        });
+
+        let learning_rate = config.learning.learning_rate;
+        workers.every("learning", seconds(config.learning.feedback_interval_secs), &service, &metrics, {
+            move |service: &ApiService| reinforce_edges(service, &pgm, learning_rate)
+        });
+
+        let mut monitor = EntropyMonitor::new();
+        workers.every("entropy", seconds(config.server.entropy_interval_secs), &service, &metrics, {
+            let metrics = metrics.clone();
+            move |service: &ApiService| Ok(sample_entropy(service, &mut monitor, &metrics))
+        });
+        workers
+    }
+
+    /// Run `pass` every `period`, the first time one period after startup
+    fn every<F>(
+        &mut self,
+        name: &'static str,
+        period: Duration,
+        service: &Arc<ApiService>,
+        metrics: &Arc<Metrics>,
+        pass: F,
+    ) where
+        F: FnMut(&ApiService) -> Result<usize> + Send + 'static,
+    {
+        let (service, metrics, stop) = (service.clone(), metrics.clone(), self.stop.clone());
+        self.handles.push(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            let mut pass = Some(pass);
+            loop {
+                tokio::select! {
+                    _ = stop.cancelled() => break,
+                    _ = ticker.tick() => {}
+                }
+                let (mut run, service) = (pass.take().expect("pass is returned after every run"), service.clone());
+                let joined = tokio::task::spawn_blocking(move || {
+                    let outcome = run(&service);
+                    (run, outcome)
+                })
+                .await;
+                let Ok((run, outcome)) = joined else {
+                    tracing::error!(worker = name, "background worker panicked; stopping it");
+                    break;
+                };
+                pass = Some(run);
+                match &outcome {
+                    Ok(0) => {}
+                    Ok(changes) => tracing::debug!(worker = name, changes, "background pass changed entities"),
+                    Err(error) => tracing::warn!(worker = name, %error, "background pass failed"),
+                }
+                metrics.record_pass(name, &outcome);
+            }
+        }));
+    }
+
+    /// Stop every worker, waiting for passes in progress
+    pub async fn stop(self) {
+        self.stop.cancel();
+        for handle in self.handles {
+            let _ = handle.await;
+        }
+    }
+}
+
+/// Move entities of every collection to the tier `policy` assigns them,
+/// returning how many moved
+pub fn rebalance_tiers(service: &ApiService, policy: &TierPolicy) -> Result<usize> {
+    let now = now_ms();
+    maintain(service, |view| {
+        policy
+            .plan(view.collection(), now)
+            .into_iter()
+            .filter_map(|(id, tier)| {
+                let mut entity = (*view.get(&id)?).clone();
+                entity.tier = tier;
+                Some(entity)
+            })
+            .collect()
+    })
+}
+
+/// Prune and renormalize the edges of every entity, returning how many
+/// entities changed
+pub fn normalize_edges(service: &ApiService, pgm: &ProbabilisticGraphMemory) -> Result<usize> {
+    let now = now_ms();
+    maintain(service, |view| {
+        view.entities()
+            .into_iter()
+            .filter(|entity| entity.edges.as_ref().is_some_and(|edges| !edges.is_empty()))
+            .filter_map(|entity| {
+                let mut entity = (*entity).clone();
+                let edges = entity.edges.as_mut()?;
+                pgm.normalize(edges, now).then_some(entity)
+            })
+            .collect()
+    })
+}
+
+/// Reinforce edges between co-accessed entities, returning how many
+/// entities changed
+pub fn reinforce_edges(service: &ApiService, pgm: &ProbabilisticGraphMemory, learning_rate: f32) -> Result<usize> {
+    let now = now_ms();
+    maintain(service, |view| {
+        view.entities()
+            .into_iter()
+            .filter(|entity| entity.edges.as_ref().is_some_and(|edges| !edges.is_empty()))
+            .filter_map(|entity| {
+                let stats = view.collection().access().stats(&entity.id)?;
+                if stats.co_access_entities.is_empty() {
+                    return None;
+                }
+                // Edges are atomics; reinforce a copy, never the stored entity
+                let mut entity = (*entity).clone();
+                let edges = entity.edges.as_mut()?;
+                if !pgm.reinforce(edges, &stats, learning_rate) {
+                    return None;
+                }
+                pgm.normalize(edges, now);
+                Some(entity)
+            })
+            .collect()
+    })
+}
+
+/// Sample every collection into `metrics`, warning about collections whose
+/// reads concentrated; returns the number of collections sampled
+pub fn sample_entropy(service: &ApiService, monitor: &mut EntropyMonitor, metrics: &Metrics) -> usize {
+    let stats = service.all_collection_stats();
+    for drop in monitor.observe(&stats) {
+        tracing::warn!(
+            collection = %drop.collection,
+            entropy = drop.entropy,
+            baseline = drop.baseline,
+            "reads are concentrating on few entities"
+        );
+    }
+    let sampled = stats.len();
+    metrics.set_collections(
+        stats
+            .into_iter()
+            .map(|stats| CollectionSample {
+                entropy_baseline: monitor.baseline(&stats.name),
+                stats,
+            })
+            .collect(),
+    );
+    sampled
+}
+
+/// Plan rewrites of each collection from a snapshot, then apply them
+fn maintain(service: &ApiService, plan: impl Fn(&CollectionView) -> Vec<Entity>) -> Result<usize> {
+    let mut applied = 0;
+    for info in service.list_collections() {
+        let rewrites = service.inspect(|catalog| catalog.collection(&info.name).map(|c| plan(&c.view())))?;
+        if !rewrites.is_empty() {
+            applied += service.apply_maintenance(&info.name, rewrites)?;
+        }
+    }
+    Ok(applied)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::collection::CollectionConfig;
+    use crate::core::{Edge, EntityId, MemoryTier, NodeId, Vector};
+    use crate::storage::catalog::Catalog;
+
+    fn service() -> Arc<ApiService> {
+        let service = ApiService::new(NodeId::new(), Catalog::in_memory(), 64);
+        service.create_collection("docs", CollectionConfig::new(2)).unwrap();
+        Arc::new(service)
+    }
+
+    fn entity(edges: Vec<Edge>) -> Entity {
+        Entity::new(Some(Vector::new(vec![1.0, 0.0])), None, Some(edges))
+    }
+
+    #[test]
+    fn test_passes_rewrite_entities_through_the_service() {
+        let service = service();
+        let (a, b) = (EntityId::new(), EntityId::new());
+        let mut source = entity(vec![
+            Edge::new(EntityId::new(), a, "cites".into(), 0.9, None),
+            Edge::new(EntityId::new(), b, "cites".into(), 0.9, None),
+        ]);
+        source.tier = MemoryTier::Cold;
+        let source = service.create_entity("docs", source).unwrap();
+        let other = service.create_entity("docs", entity(Vec::new())).unwrap();
+        let mut changes = service.subscribe();
+
+        let pgm = ProbabilisticGraphMemory::new();
+        assert_eq!(normalize_edges(&service, &pgm).unwrap(), 1);
+        assert_eq!(normalize_edges(&service, &pgm).unwrap(), 0);
+        let stored = service.get_entity("docs", &source.id).unwrap();
+        assert_eq!(stored.version, 2);
+        assert!(stored.edges.unwrap().iter().all(|edge| (edge.probability() - 0.5).abs() < 1e-6));
+        assert_eq!(changes.try_recv().unwrap().entity_id, source.id);
+
+        // Reading the source together with `a` shifts probability towards it
+        std::thread::sleep(Duration::from_millis(2));
+        service.inspect(|catalog| catalog.collection("docs").unwrap().access().record_co_access(source.id, a));
+        service.get_entity("docs", &source.id).unwrap();
+        assert_eq!(reinforce_edges(&service, &pgm, 0.5).unwrap(), 1);
+        // Before the read below, which is itself a newer access
+        assert_eq!(reinforce_edges(&service, &pgm, 0.5).unwrap(), 0);
+        let edges = service.get_entity("docs", &source.id).unwrap().edges.unwrap();
+        assert!(edges[0].probability() > edges[1].probability());
+        let sum: f32 = edges.iter().map(Edge::probability).sum();
+        assert!((sum - 1.0).abs() < 1e-5);
+
+        let config = crate::core::config::TieringConfig {
+            warm_promotion_threshold: 0.0001,
+            hot_promotion_threshold: 1e9,
+            ..crate::core::config::TieringConfig::default()
+        };
+        std::thread::sleep(Duration::from_millis(2));
+        service.get_entity("docs", &source.id).unwrap();
+        assert_eq!(rebalance_tiers(&service, &TierPolicy::new(config)).unwrap(), 1);
+        assert_eq!(service.get_entity("docs", &source.id).unwrap().tier, MemoryTier::Warm);
+
+        service.get_entity("docs", &other.id).unwrap();
+        let metrics = Metrics::new();
+        let mut monitor = EntropyMonitor::new();
+        assert_eq!(sample_entropy(&service, &mut monitor, &metrics), 1);
+        assert!(monitor.baseline("docs").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_workers_run_on_their_intervals_and_stop() {
+        let service = service();
+        let mut config = PhenixConfig::default();
+        config.server.entropy_interval_secs = 1;
+        let metrics = Arc::new(Metrics::new());
+
+        let workers = Workers::spawn(service, &config, metrics.clone());
+        assert!(metrics.worker("entropy").is_none(), "first pass waits one interval");
+        for _ in 0..300 {
+            if metrics.worker("entropy").is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(metrics.worker("entropy").unwrap().passes, 1);
+        assert!(metrics.worker("tiering").is_none());
+        workers.stop().await;
+    }
+}
@@ -1,24 +1,1262 @@
-//! Phenix-DB CLI tool
+//! Phenix-DB command line client
+//!
+//! Usage: `phenix-cli [OPTIONS] <COMMAND> [ARGS]`. Data commands talk to a
+//! running server over its REST API; `config` works on local files and
+//! needs no server. Without a command, or with `repl`, the client starts an
+//! interactive session that runs cognitive queries and commands line by
+//! line and keeps a history file.
 
+use hyper::client::HttpConnector;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Client, Method, Request, StatusCode};
+use phenix_db::api::rest::{JsonEntity, QueryBody, QueryResponse, SearchBody, SearchResponse};
+use phenix_db::core::collection::{CollectionInfo, CollectionStats};
+use phenix_db::core::{EntityId, MemoryTier, PhenixConfig};
 use phenix_db::{BUILD_INFO, VERSION};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("{}", BUILD_INFO);
-    println!("Phenix-DB CLI v{}", VERSION);
-    println!();
-    println!("Usage: phenix-cli <command> [options]");
-    println!();
-    println!("Commands:");
-    println!("  query      Execute a cognitive query");
-    println!("  insert     Insert an entity");
-    println!("  update     Update an entity");
-    println!("  delete     Delete an entity");
-    println!("  stats      Show system statistics");
-    println!("  config     Manage configuration");
-    println!();
-    println!("Run 'phenix-cli <command> --help' for more information on a command.");
-
-    // TODO: Implement CLI commands
+type CliResult<T> = Result<T, Box<dyn std::error::Error>>;
 
+const DEFAULT_SERVER: &str = "http://127.0.0.1:8080";
+
+/// History entries kept across REPL sessions
+const HISTORY_LIMIT: usize = 1000;
+
+const USAGE: &str = "\
+Usage: phenix-cli [OPTIONS] <COMMAND> [ARGS]
+
+Options:
+  -s, --server <URL>     REST endpoint (default: $PHENIX_SERVER or http://127.0.0.1:8080)
+  -o, --output <MODE>    Output mode: table (default) or json
+      --timeout <SECS>   Request timeout in seconds (default: 30)
+  -h, --help             Show this help
+  -V, --version          Show the version
+
+Commands:
+  query      Execute a cognitive query
+  get        Fetch an entity
+  insert     Insert an entity
+  update     Update an entity
+  delete     Delete an entity
+  search     Search a collection by vector
+  stats      Show tier sizes, edge counts and access entropy
+  config     Validate, show or diff configuration files
+  repl       Start an interactive session (the default)
+
+Run 'phenix-cli <command> --help' for more information on a command.";
+
+/// Usage of a single command
+fn command_usage(command: &str) -> Option<&'static str> {
+    Some(match command {
+        "query" => {
+            "\
+Usage: phenix-cli query [--entities] <QUERY>...
+
+Execute a cognitive query, e.g.
+  phenix-cli query 'FIND IN papers NEAR [0.1, 0.4] K 20 LIMIT 5'
+Prefix the query with EXPLAIN to show its plan and estimated cost.
+
+Options:
+  --entities    Attach the stored entity to each row"
+        }
+        "get" => "Usage: phenix-cli get <COLLECTION> <ID>",
+        "insert" => {
+            "\
+Usage: phenix-cli insert <COLLECTION> [OPTIONS]
+
+Options:
+  --vector <VALUES>    Vector as '0.1,0.2' or '[0.1, 0.2]'
+  --metadata <JSON>    Metadata document
+  --id <ID>            Entity id (default: generated)
+  --tier <TIER>        hot, warm or cold (default: hot)
+  --json <ENTITY>      Whole entity as JSON, edges included; other options
+                       override its fields"
+        }
+        "update" => {
+            "\
+Usage: phenix-cli update <COLLECTION> <ID> [OPTIONS]
+
+Fetch the entity, apply the changes and store it as a new version.
+
+Options:
+  --vector <VALUES>    Replace the vector
+  --metadata <JSON>    Replace the metadata document
+  --merge              Merge --metadata into the existing document instead
+  --tier <TIER>        Move to hot, warm or cold"
+        }
+        "delete" => "Usage: phenix-cli delete <COLLECTION> <ID>",
+        "search" => {
+            "\
+Usage: phenix-cli search <COLLECTION> --vector <VALUES> [OPTIONS]
+
+Options:
+  --k <N>             Number of results (default: 10)
+  --filter <JSON>     Metadata filter, e.g. '{\"op\": \"eq\", \"field\": \"lang\", \"value\": \"en\"}'
+  --entities          Attach the stored entity to each hit"
+        }
+        "stats" => {
+            "\
+Usage: phenix-cli stats [COLLECTION]
+
+Show entities per tier, edge counts and the access entropy of one or every
+collection. Access entropy is 1 when reads are spread evenly over the
+entities and approaches 0 when a few entities take all of them."
+        }
+        "config" => {
+            "\
+Usage: phenix-cli config <SUBCOMMAND>
+
+Subcommands:
+  validate <FILE>           Parse and validate a configuration file
+  show [FILE]               Print the effective configuration (defaults without FILE)
+  diff <FILE> [OTHER]       Show settings that differ from OTHER (defaults without OTHER)"
+        }
+        "repl" => {
+            "\
+Usage: phenix-cli repl
+
+Lines starting with FIND or EXPLAIN are cognitive queries; a query runs at a
+line ending in ';' or at an empty line. Other lines are commands without the
+'phenix-cli' prefix. Session commands:
+  history          List the history
+  !N, !!           Run history entry N, or the last entry
+  output <MODE>    Switch between table and json output
+  help             Show the commands
+  exit, quit       Leave (as does end of input)
+History is kept in $PHENIX_CLI_HISTORY (default: ~/.phenix_history)."
+        }
+        _ => return None,
+    })
+}
+
+/// Output mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Table,
+    Json,
+}
+
+impl std::str::FromStr for Output {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, String> {
+        match mode {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            other => Err(format!("unknown output mode '{}' (expected table or json)", other)),
+        }
+    }
+}
+
+/// Global options
+#[derive(Debug, Clone, PartialEq)]
+struct Options {
+    server: String,
+    output: Output,
+    timeout: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            server: std::env::var("PHENIX_SERVER").unwrap_or_else(|_| DEFAULT_SERVER.to_string()),
+            output: Output::Table,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A parsed command
+#[derive(Debug, Clone, PartialEq)]
+enum Command {
+    Query {
+        text: String,
+        entities: bool,
+    },
+    Get {
+        collection: String,
+        id: EntityId,
+    },
+    Insert {
+        collection: String,
+        entity: JsonEntity,
+    },
+    Update {
+        collection: String,
+        id: EntityId,
+        vector: Option<Vec<f32>>,
+        metadata: Option<Value>,
+        merge: bool,
+        tier: Option<MemoryTier>,
+    },
+    Delete {
+        collection: String,
+        id: EntityId,
+    },
+    Search {
+        collection: String,
+        vector: Vec<f32>,
+        k: usize,
+        filter: Option<Value>,
+        entities: bool,
+    },
+    Stats {
+        collection: Option<String>,
+    },
+    ConfigValidate(PathBuf),
+    ConfigShow(Option<PathBuf>),
+    ConfigDiff(PathBuf, Option<PathBuf>),
+    Repl,
+    Help(Option<String>),
+    Version,
+}
+
+/// Positional arguments and `--flag [value]` options of one command
+struct Args {
+    positional: Vec<String>,
+    options: BTreeMap<String, Option<String>>,
+}
+
+impl Args {
+    /// Split `args`; `switches` take no value, `valued` take one
+    fn parse(args: &[String], switches: &[&str], valued: &[&str]) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut options = BTreeMap::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let Some(flag) = arg.strip_prefix("--").filter(|flag| !flag.is_empty()) else {
+                positional.push(arg.clone());
+                continue;
+            };
+            let (name, inline) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (flag, None),
+            };
+            let value = if switches.contains(&name) && inline.is_none() {
+                None
+            } else if valued.contains(&name) {
+                Some(match inline {
+                    Some(value) => value,
+                    None => iter.next().cloned().ok_or_else(|| format!("--{} needs a value", name))?,
+                })
+            } else {
+                return Err(format!("unknown option --{}", name));
+            };
+            if options.insert(name.to_string(), value).is_some() {
+                return Err(format!("--{} given twice", name));
+            }
+        }
+        Ok(Self { positional, options })
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+
+    fn value(&self, name: &str) -> Option<&str> {
+        self.options.get(name).and_then(|value| value.as_deref())
+    }
+
+    /// Exactly `names.len()` positional arguments
+    fn exact<const N: usize>(&self, names: [&str; N]) -> Result<[String; N], String> {
+        <[String; N]>::try_from(self.positional.clone()).map_err(|_| {
+            let names: Vec<String> = names.iter().map(|name| format!("<{}>", name)).collect();
+            format!("expected {}", names.join(" "))
+        })
+    }
+}
+
+fn parse_id(text: &str) -> Result<EntityId, String> {
+    uuid::Uuid::parse_str(text)
+        .map(EntityId::from_uuid)
+        .map_err(|_| format!("invalid entity id '{}'", text))
+}
+
+/// Parse `0.1,0.2`, `0.1 0.2` or `[0.1, 0.2]`
+fn parse_vector(text: &str) -> Result<Vec<f32>, String> {
+    let inner = text.trim().trim_start_matches('[').trim_end_matches(']');
+    inner
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|value| !value.is_empty())
+        .map(|value| value.parse::<f32>().map_err(|_| format!("invalid vector value '{}'", value)))
+        .collect()
+}
+
+fn parse_json(name: &str, text: &str) -> Result<Value, String> {
+    serde_json::from_str(text).map_err(|error| format!("--{} is not valid JSON: {}", name, error))
+}
+
+fn parse_tier(text: &str) -> Result<MemoryTier, String> {
+    match text.to_ascii_lowercase().as_str() {
+        "hot" => Ok(MemoryTier::Hot),
+        "warm" => Ok(MemoryTier::Warm),
+        "cold" => Ok(MemoryTier::Cold),
+        _ => Err(format!("unknown tier '{}' (expected hot, warm or cold)", text)),
+    }
+}
+
+/// Parse one command and its arguments
+fn parse_command(args: &[String]) -> Result<Command, String> {
+    let Some((name, rest)) = args.split_first() else {
+        return Ok(Command::Repl);
+    };
+    if rest.iter().any(|arg| arg == "--help" || arg == "-h") {
+        return Ok(Command::Help(Some(name.clone())));
+    }
+    let name = name.as_str();
+    let command = match name {
+        "query" => {
+            let args = Args::parse(rest, &["entities"], &[])?;
+            if args.positional.is_empty() {
+                return Err("expected <QUERY>".to_string());
+            }
+            Command::Query {
+                text: args.positional.join(" "),
+                entities: args.flag("entities"),
+            }
+        }
+        "get" | "delete" => {
+            let args = Args::parse(rest, &[], &[])?;
+            let [collection, id] = args.exact(["COLLECTION", "ID"])?;
+            let id = parse_id(&id)?;
+            match name {
+                "get" => Command::Get { collection, id },
+                _ => Command::Delete { collection, id },
+            }
+        }
+        "insert" => {
+            let args = Args::parse(rest, &[], &["vector", "metadata", "id", "tier", "json"])?;
+            let [collection] = args.exact(["COLLECTION"])?;
+            let mut entity: JsonEntity = match args.value("json") {
+                Some(text) => serde_json::from_value(parse_json("json", text)?)
+                    .map_err(|error| format!("--json is not an entity: {}", error))?,
+                None => JsonEntity::default(),
+            };
+            if let Some(vector) = args.value("vector") {
+                entity.vector = parse_vector(vector)?;
+            }
+            if let Some(metadata) = args.value("metadata") {
+                entity.metadata = Some(parse_json("metadata", metadata)?);
+            }
+            if let Some(id) = args.value("id") {
+                entity.id = Some(parse_id(id)?);
+            }
+            if let Some(tier) = args.value("tier") {
+                entity.tier = Some(parse_tier(tier)?);
+            }
+            Command::Insert { collection, entity }
+        }
+        "update" => {
+            let args = Args::parse(rest, &["merge"], &["vector", "metadata", "tier"])?;
+            let [collection, id] = args.exact(["COLLECTION", "ID"])?;
+            let command = Command::Update {
+                collection,
+                id: parse_id(&id)?,
+                vector: args.value("vector").map(parse_vector).transpose()?,
+                metadata: args.value("metadata").map(|text| parse_json("metadata", text)).transpose()?,
+                merge: args.flag("merge"),
+                tier: args.value("tier").map(parse_tier).transpose()?,
+            };
+            if matches!(command, Command::Update { vector: None, metadata: None, tier: None, .. }) {
+                return Err("nothing to update; give --vector, --metadata or --tier".to_string());
+            }
+            command
+        }
+        "search" => {
+            let args = Args::parse(rest, &["entities"], &["vector", "k", "filter"])?;
+            let [collection] = args.exact(["COLLECTION"])?;
+            let vector = args.value("vector").ok_or("--vector is required")?;
+            Command::Search {
+                collection,
+                vector: parse_vector(vector)?,
+                k: match args.value("k") {
+                    Some(k) => k.parse().map_err(|_| format!("invalid --k '{}'", k))?,
+                    None => 10,
+                },
+                filter: args.value("filter").map(|text| parse_json("filter", text)).transpose()?,
+                entities: args.flag("entities"),
+            }
+        }
+        "stats" => {
+            let args = Args::parse(rest, &[], &[])?;
+            match args.positional.as_slice() {
+                [] => Command::Stats { collection: None },
+                [collection] => Command::Stats {
+                    collection: Some(collection.clone()),
+                },
+                _ => return Err("expected at most one <COLLECTION>".to_string()),
+            }
+        }
+        "config" => {
+            let args = Args::parse(rest, &[], &[])?;
+            let paths: Vec<PathBuf> = args.positional.iter().skip(1).map(PathBuf::from).collect();
+            match (args.positional.first().map(String::as_str), paths.as_slice()) {
+                (Some("validate"), [file]) => Command::ConfigValidate(file.clone()),
+                (Some("show"), [] | [_]) => Command::ConfigShow(paths.first().cloned()),
+                (Some("diff"), [file]) => Command::ConfigDiff(file.clone(), None),
+                (Some("diff"), [file, other]) => Command::ConfigDiff(file.clone(), Some(other.clone())),
+                _ => return Err("expected validate <FILE>, show [FILE] or diff <FILE> [OTHER]".to_string()),
+            }
+        }
+        "repl" => Command::Repl,
+        "help" => Command::Help(rest.first().cloned()),
+        other => return Err(format!("unknown command '{}'", other)),
+    };
+    Ok(command)
+}
+
+/// Parse global options followed by a command
+fn parse(args: &[String]) -> Result<(Options, Command), String> {
+    let mut options = Options::default();
+    let mut index = 0;
+    while let Some(arg) = args.get(index) {
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || -> Result<String, String> {
+            match inline.clone() {
+                Some(value) => Ok(value),
+                None => {
+                    index += 1;
+                    args.get(index).cloned().ok_or_else(|| format!("{} needs a value", name))
+                }
+            }
+        };
+        match name {
+            "-s" | "--server" => options.server = value()?.trim_end_matches('/').to_string(),
+            "-o" | "--output" => options.output = value()?.parse()?,
+            "--timeout" => {
+                let seconds = value()?;
+                let seconds: u64 = seconds.parse().map_err(|_| format!("invalid --timeout '{}'", seconds))?;
+                options.timeout = Duration::from_secs(seconds.max(1));
+            }
+            "-h" | "--help" => return Ok((options, Command::Help(None))),
+            "-V" | "--version" => return Ok((options, Command::Version)),
+            flag if flag.starts_with('-') => return Err(format!("unknown option {}", flag)),
+            _ => break,
+        }
+        index += 1;
+    }
+    Ok((options, parse_command(&args[index..])?))
+}
+
+/// Split a REPL line into words, honouring single and double quotes
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') => word.extend(chars.next()),
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err("unterminated quote".to_string());
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// REST client for one server
+struct Api {
+    http: Client<HttpConnector>,
+    base: String,
+    timeout: Duration,
+}
+
+impl Api {
+    fn new(options: &Options) -> CliResult<Self> {
+        if options.server.starts_with("https://") {
+            return Err("https servers are not supported; reach the server over http or through a local proxy".into());
+        }
+        if !options.server.starts_with("http://") {
+            return Err(format!("server '{}' is not an http:// URL", options.server).into());
+        }
+        Ok(Self {
+            http: Client::new(),
+            base: options.server.trim_end_matches('/').to_string(),
+            timeout: options.timeout,
+        })
+    }
+
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&impl Serialize>,
+    ) -> CliResult<T> {
+        let body = match body {
+            Some(body) => Body::from(serde_json::to_vec(body)?),
+            None => Body::empty(),
+        };
+        let request = Request::builder()
+            .method(method)
+            .uri(format!("{}{}", self.base, path))
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)?;
+        let exchange = async {
+            let response = self.http.request(request).await?;
+            let status = response.status();
+            let bytes = hyper::body::to_bytes(response.into_body()).await?;
+            Ok::<_, hyper::Error>((status, bytes))
+        };
+        let (status, bytes) = tokio::time::timeout(self.timeout, exchange)
+            .await
+            .map_err(|_| format!("no answer from {} within {:?}", self.base, self.timeout))?
+            .map_err(|error| format!("cannot reach {}: {}", self.base, error))?;
+
+        if !status.is_success() {
+            let document: Value = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+            let message = document["error"]["message"].as_str().unwrap_or("no details");
+            return Err(match document["error"]["correlation_id"].as_str() {
+                Some(correlation_id) => format!("{}: {} (correlation id {})", status, message, correlation_id),
+                None => format!("{}: {}", status, message),
+            }
+            .into());
+        }
+        let bytes = if status == StatusCode::NO_CONTENT { &b"null"[..] } else { &bytes[..] };
+        Ok(serde_json::from_slice(bytes)?)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> CliResult<T> {
+        self.call(Method::GET, path, None::<&()>).await
+    }
+}
+
+/// Render rows as an aligned text table
+fn table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        padded.join("  ").trim_end().to_string()
+    };
+    let mut lines = vec![
+        line(headers.to_vec()),
+        line(widths.iter().map(|&width| "-".repeat(width)).collect::<Vec<_>>().iter().map(String::as_str).collect()),
+    ];
+    lines.extend(rows.iter().map(|row| line(row.iter().map(String::as_str).collect())));
+    lines.push(format!("({} row{})", rows.len(), if rows.len() == 1 { "" } else { "s" }));
+    lines.join("\n")
+}
+
+/// Render field/value pairs, one per line
+fn record(fields: &[(&str, String)]) -> String {
+    let width = fields.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    fields
+        .iter()
+        .map(|(name, value)| format!("{:<width$}  {}", name, value, width = width))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn timestamp(ms: u64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms as i64)
+        .map(|time| time.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+        .unwrap_or_else(|| ms.to_string())
+}
+
+/// Vector shortened to its first values
+fn preview(vector: &[f32]) -> String {
+    const SHOWN: usize = 6;
+    let values: Vec<String> = vector.iter().take(SHOWN).map(|value| format!("{:.4}", value)).collect();
+    match vector.len() {
+        0 => "-".to_string(),
+        len if len <= SHOWN => format!("[{}]", values.join(", ")),
+        len => format!("[{}, … ({} values)]", values.join(", "), len),
+    }
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| value.to_string())
+}
+
+fn entity_record(entity: &JsonEntity) -> String {
+    let edges: Vec<String> = entity
+        .edges
+        .iter()
+        .map(|edge| format!("{} -{}-> {} (p={:.2})", edge.label, edge.weight, edge.target_id, edge.probability))
+        .collect();
+    record(&[
+        ("id", optional(entity.id)),
+        ("version", entity.version.to_string()),
+        ("tier", optional(entity.tier.map(|tier| format!("{:?}", tier).to_lowercase()))),
+        ("vector", preview(&entity.vector)),
+        ("metadata", optional(entity.metadata.as_ref())),
+        ("edges", if edges.is_empty() { "-".to_string() } else { edges.join("\n          ") }),
+        ("created_at", timestamp(entity.created_at)),
+        ("updated_at", timestamp(entity.updated_at)),
+    ])
+}
+
+/// Flatten a TOML document into dotted keys
+fn flatten(prefix: &str, value: &toml::Value, settings: &mut BTreeMap<String, String>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten(&key, value, settings);
+            }
+        }
+        other => {
+            settings.insert(prefix.to_string(), other.to_string());
+        }
+    }
+}
+
+fn settings(config: &PhenixConfig) -> CliResult<BTreeMap<String, String>> {
+    let mut settings = BTreeMap::new();
+    flatten("", &toml::Value::try_from(config)?, &mut settings);
+    Ok(settings)
+}
+
+fn load_config(path: &Path) -> CliResult<PhenixConfig> {
+    PhenixConfig::from_file(path).map_err(|error| format!("{}: {}", path.display(), error).into())
+}
+
+/// Keys set in the file at `path` that the configuration does not know
+fn unknown_keys(path: &Path, config: &PhenixConfig) -> CliResult<Vec<String>> {
+    let raw: toml::Value = toml::from_str(&std::fs::read_to_string(path)?)?;
+    let mut given = BTreeMap::new();
+    flatten("", &raw, &mut given);
+    let known = settings(config)?;
+    Ok(given.into_keys().filter(|key| !known.contains_key(key)).collect())
+}
+
+/// Settings that differ between two configurations
+fn diff(left: &PhenixConfig, right: &PhenixConfig) -> CliResult<Vec<(String, String, String)>> {
+    let (left, right) = (settings(left)?, settings(right)?);
+    let mut keys: Vec<&String> = left.keys().chain(right.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    Ok(keys
+        .into_iter()
+        .filter(|key| left.get(*key) != right.get(*key))
+        .map(|key| {
+            let value = |settings: &BTreeMap<String, String>| settings.get(key).cloned().unwrap_or_else(|| "-".into());
+            (key.clone(), value(&left), value(&right))
+        })
+        .collect())
+}
+
+fn json_output(value: &impl Serialize) -> CliResult<String> {
+    Ok(serde_json::to_string_pretty(value)?)
+}
+
+/// Run one command and render its result
+async fn execute(options: &Options, command: Command) -> CliResult<String> {
+    let output = options.output;
+    let api = || Api::new(options);
+    Ok(match command {
+        Command::Query { text, entities } => {
+            let body = QueryBody {
+                query: text,
+                include_entities: entities,
+            };
+            let response: QueryResponse = api()?.call(Method::POST, "/v1/query", Some(&body)).await?;
+            match (output, &response.plan) {
+                (Output::Json, _) => json_output(&response)?,
+                (Output::Table, Some(plan)) => plan.clone(),
+                (Output::Table, None) => {
+                    let mut headers = vec!["id", "depth", "distance", "pagerank"];
+                    if entities {
+                        headers.push("metadata");
+                    }
+                    let rows: Vec<Vec<String>> = response
+                        .rows
+                        .iter()
+                        .map(|row| {
+                            let mut cells = vec![
+                                row.row.id.to_string(),
+                                row.row.depth.to_string(),
+                                optional(row.row.distance.map(|distance| format!("{:.4}", distance))),
+                                optional(row.row.pagerank.map(|rank| format!("{:.4}", rank))),
+                            ];
+                            if entities {
+                                cells.push(optional(row.entity.as_ref().and_then(|entity| entity.metadata.as_ref())));
+                            }
+                            cells
+                        })
+                        .collect();
+                    table(&headers, &rows)
+                }
+            }
+        }
+        Command::Get { collection, id } => {
+            let entity: JsonEntity = api()?.get(&format!("/v1/collections/{}/entities/{}", collection, id)).await?;
+            match output {
+                Output::Json => json_output(&entity)?,
+                Output::Table => entity_record(&entity),
+            }
+        }
+        Command::Insert { collection, entity } => {
+            let path = format!("/v1/collections/{}/entities", collection);
+            let created: JsonEntity = api()?.call(Method::POST, &path, Some(&entity)).await?;
+            match output {
+                Output::Json => json_output(&created)?,
+                Output::Table => entity_record(&created),
+            }
+        }
+        Command::Update {
+            collection,
+            id,
+            vector,
+            metadata,
+            merge,
+            tier,
+        } => {
+            let api = api()?;
+            let path = format!("/v1/collections/{}/entities/{}", collection, id);
+            let mut entity: JsonEntity = api.get(&path).await?;
+            if let Some(vector) = vector {
+                entity.vector = vector;
+            }
+            match (metadata, &mut entity.metadata) {
+                (Some(Value::Object(fields)), Some(Value::Object(existing))) if merge => existing.extend(fields),
+                (Some(metadata), _) => entity.metadata = Some(metadata),
+                (None, _) => {}
+            }
+            if tier.is_some() {
+                entity.tier = tier;
+            }
+            let updated: JsonEntity = api.call(Method::PUT, &path, Some(&entity)).await?;
+            match output {
+                Output::Json => json_output(&updated)?,
+                Output::Table => entity_record(&updated),
+            }
+        }
+        Command::Delete { collection, id } => {
+            let path = format!("/v1/collections/{}/entities/{}", collection, id);
+            let response: Value = api()?.call(Method::DELETE, &path, None::<&()>).await?;
+            match (output, response["deleted"].as_bool()) {
+                (Output::Json, _) => json_output(&response)?,
+                (Output::Table, Some(true)) => format!("deleted {}", id),
+                (Output::Table, _) => format!("{} not found", id),
+            }
+        }
+        Command::Search {
+            collection,
+            vector,
+            k,
+            filter,
+            entities,
+        } => {
+            let body = SearchBody {
+                vector,
+                k,
+                filter: filter
+                    .map(serde_json::from_value)
+                    .transpose()
+                    .map_err(|error| format!("invalid --filter: {}", error))?,
+                strategy: None,
+                ef: None,
+                include_entities: entities,
+            };
+            let path = format!("/v1/collections/{}/search", collection);
+            let response: SearchResponse = api()?.call(Method::POST, &path, Some(&body)).await?;
+            match output {
+                Output::Json => json_output(&response)?,
+                Output::Table => {
+                    let rows: Vec<Vec<String>> = response
+                        .hits
+                        .iter()
+                        .map(|hit| {
+                            vec![
+                                hit.id.to_string(),
+                                format!("{:.4}", hit.distance),
+                                optional(hit.entity.as_ref().and_then(|entity| entity.metadata.as_ref())),
+                            ]
+                        })
+                        .collect();
+                    table(&["id", "distance", "metadata"], &rows)
+                }
+            }
+        }
+        Command::Stats { collection } => {
+            let api = api()?;
+            let names = match collection {
+                Some(name) => vec![name],
+                None => {
+                    let collections: Vec<CollectionInfo> = api.get("/v1/collections").await?;
+                    collections.into_iter().map(|info| info.name).collect()
+                }
+            };
+            let mut stats = Vec::with_capacity(names.len());
+            for name in names {
+                let collection: CollectionStats = api.get(&format!("/v1/collections/{}/stats", name)).await?;
+                stats.push(collection);
+            }
+            match output {
+                Output::Json => json_output(&stats)?,
+                Output::Table => {
+                    let rows: Vec<Vec<String>> = stats
+                        .iter()
+                        .map(|stats| {
+                            vec![
+                                stats.name.clone(),
+                                stats.entity_count.to_string(),
+                                stats.tiers.hot.to_string(),
+                                stats.tiers.warm.to_string(),
+                                stats.tiers.cold.to_string(),
+                                stats.edge_count.to_string(),
+                                stats.edge_labels.to_string(),
+                                format!("{:.3}", stats.access_entropy),
+                            ]
+                        })
+                        .collect();
+                    let headers = ["collection", "entities", "hot", "warm", "cold", "edges", "labels", "entropy"];
+                    table(&headers, &rows)
+                }
+            }
+        }
+        Command::ConfigValidate(path) => {
+            let config = load_config(&path)?;
+            let unknown = unknown_keys(&path, &config)?;
+            match output {
+                Output::Json => json_output(&json!({ "file": path, "valid": true, "unknown_keys": unknown }))?,
+                Output::Table => {
+                    let mut lines = vec![format!("{}: valid", path.display())];
+                    lines.extend(unknown.iter().map(|key| format!("warning: unknown setting '{}' is ignored", key)));
+                    lines.join("\n")
+                }
+            }
+        }
+        Command::ConfigShow(path) => {
+            let config = match path {
+                Some(path) => load_config(&path)?,
+                None => PhenixConfig::default(),
+            };
+            match output {
+                Output::Json => json_output(&config)?,
+                Output::Table => toml::to_string_pretty(&config)?.trim_end().to_string(),
+            }
+        }
+        Command::ConfigDiff(path, other) => {
+            let left = load_config(&path)?;
+            let (right, right_name) = match other {
+                Some(other) => (load_config(&other)?, other.display().to_string()),
+                None => (PhenixConfig::default(), "defaults".to_string()),
+            };
+            let changes = diff(&left, &right)?;
+            match output {
+                Output::Json => {
+                    let changes: Vec<Value> = changes
+                        .iter()
+                        .map(|(key, left, right)| json!({ "key": key, "left": left, "right": right }))
+                        .collect();
+                    json_output(&changes)?
+                }
+                Output::Table if changes.is_empty() => format!("no differences from {}", right_name),
+                Output::Table => {
+                    let rows: Vec<Vec<String>> =
+                        changes.into_iter().map(|(key, left, right)| vec![key, left, right]).collect();
+                    let left_name = path.display().to_string();
+                    table(&["setting", &left_name, &right_name], &rows)
+                }
+            }
+        }
+        Command::Help(None) => USAGE.to_string(),
+        Command::Help(Some(command)) => command_usage(&command)
+            .ok_or_else(|| format!("unknown command '{}'", command))?
+            .to_string(),
+        Command::Version => format!("phenix-cli {}\n{}", VERSION, BUILD_INFO),
+        Command::Repl => return Err("already in an interactive session".into()),
+    })
+}
+
+/// Persistent REPL history
+struct History {
+    path: Option<PathBuf>,
+    entries: Vec<String>,
+}
+
+impl History {
+    fn load() -> Self {
+        let path = std::env::var_os("PHENIX_CLI_HISTORY")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".phenix_history")));
+        let entries = path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .map(|text| text.lines().filter(|line| !line.is_empty()).map(String::from).collect())
+            .unwrap_or_default();
+        Self { path, entries }
+    }
+
+    fn push(&mut self, entry: &str) {
+        if self.entries.last().map(String::as_str) != Some(entry) {
+            // Continuation lines are kept on one line
+            self.entries.push(entry.replace('\n', " "));
+        }
+    }
+
+    /// Resolve `!!` and `!N`
+    fn expand(&self, line: &str) -> Result<Option<String>, String> {
+        let Some(reference) = line.strip_prefix('!') else {
+            return Ok(None);
+        };
+        let entry = match reference {
+            "!" => self.entries.last(),
+            number => {
+                let index: usize = number.parse().map_err(|_| format!("invalid history reference '{}'", line))?;
+                index.checked_sub(1).and_then(|index| self.entries.get(index))
+            }
+        };
+        entry.cloned().map(Some).ok_or_else(|| format!("no history entry '{}'", line))
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let start = self.entries.len().saturating_sub(HISTORY_LIMIT);
+        let mut text = self.entries[start..].join("\n");
+        text.push('\n');
+        if let Err(error) = std::fs::write(path, text) {
+            eprintln!("warning: cannot save history to {}: {}", path.display(), error);
+        }
+    }
+}
+
+fn is_query(line: &str) -> bool {
+    let first = line.split_whitespace().next().unwrap_or_default();
+    first.eq_ignore_ascii_case("find") || first.eq_ignore_ascii_case("explain")
+}
+
+/// Interactive session
+fn repl(runtime: &tokio::runtime::Runtime, mut options: Options) -> CliResult<()> {
+    println!("Phenix-DB CLI v{} connected to {}", VERSION, options.server);
+    println!("Type 'help' for commands, end queries with ';', 'exit' to leave.");
+    let mut history = History::load();
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut pending = String::new();
+
+    loop {
+        print!("{}", if pending.is_empty() { "phenix> " } else { "   ...> " });
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next().transpose()? else {
+            break;
+        };
+        let line = line.trim();
+
+        if !pending.is_empty() || is_query(line) {
+            if !line.is_empty() {
+                pending.push_str(if pending.is_empty() { "" } else { "\n" });
+                pending.push_str(line);
+            }
+            if !line.is_empty() && !line.ends_with(';') {
+                continue;
+            }
+            let text = std::mem::take(&mut pending);
+            history.push(&text);
+            let command = Command::Query { text, entities: false };
+            report(runtime.block_on(execute(&options, command)));
+            continue;
+        }
+
+        let entry = match history.expand(line) {
+            Ok(Some(entry)) => {
+                println!("{}", entry);
+                entry
+            }
+            Ok(None) => line.to_string(),
+            Err(error) => {
+                eprintln!("error: {}", error);
+                continue;
+            }
+        };
+        if entry.is_empty() {
+            continue;
+        }
+        history.push(&entry);
+        if is_query(&entry) {
+            report(runtime.block_on(execute(&options, Command::Query { text: entry, entities: false })));
+            continue;
+        }
+
+        let words = match split_words(&entry) {
+            Ok(words) => words,
+            Err(error) => {
+                eprintln!("error: {}", error);
+                continue;
+            }
+        };
+        match words.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+            ["exit" | "quit"] => break,
+            ["history"] => {
+                for (index, entry) in history.entries.iter().enumerate() {
+                    println!("{:>5}  {}", index + 1, entry);
+                }
+            }
+            ["output", mode] => match mode.parse() {
+                Ok(mode) => options.output = mode,
+                Err(error) => eprintln!("error: {}", error),
+            },
+            ["help"] => println!("{}\n\n{}", USAGE, command_usage("repl").unwrap_or_default()),
+            _ => match parse_command(&words) {
+                Ok(Command::Repl) => eprintln!("error: already in an interactive session"),
+                Ok(command) => report(runtime.block_on(execute(&options, command))),
+                Err(error) => eprintln!("error: {}", error),
+            },
+        }
+    }
+    history.save();
     Ok(())
 }
+
+fn report(result: CliResult<String>) {
+    match result {
+        Ok(text) => println!("{}", text),
+        Err(error) => eprintln!("error: {}", error),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (options, command) = match parse(&args) {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+        Ok(runtime) => runtime,
+        Err(error) => {
+            eprintln!("error: cannot start the runtime: {}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let result = match command {
+        Command::Repl => repl(&runtime, options).map(|()| None),
+        command => runtime.block_on(execute(&options, command)).map(Some),
+    };
+    match result {
+        Ok(text) => {
+            if let Some(text) = text {
+                println!("{}", text);
+            }
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use phenix_db::api::{rest, ApiService};
+    use phenix_db::core::collection::CollectionConfig;
+    use phenix_db::core::config::ApiConfig;
+    use phenix_db::core::NodeId;
+    use phenix_db::storage::catalog::Catalog;
+    use std::sync::Arc;
+
+    fn words(line: &str) -> Vec<String> {
+        split_words(line).unwrap()
+    }
+
+    #[test]
+    fn test_parses_options_and_commands() {
+        let (options, command) = parse(&words("-o json --server=http://db:9000/ stats docs")).unwrap();
+        assert_eq!(options.output, Output::Json);
+        assert_eq!(options.server, "http://db:9000");
+        assert_eq!(command, Command::Stats { collection: Some("docs".into()) });
+
+        let id = EntityId::new();
+        let line = format!(r#"insert docs --vector "[1, 2.5]" --metadata '{{"lang": "en"}}' --id {} --tier warm"#, id);
+        let Command::Insert { collection, entity } = parse_command(&words(&line)).unwrap() else {
+            panic!("not an insert");
+        };
+        assert_eq!(collection, "docs");
+        assert_eq!(entity.vector, vec![1.0, 2.5]);
+        assert_eq!(entity.metadata, Some(json!({"lang": "en"})));
+        assert_eq!((entity.id, entity.tier), (Some(id), Some(MemoryTier::Warm)));
+
+        assert_eq!(
+            parse_command(&words("query --entities FIND IN docs LIMIT 3")).unwrap(),
+            Command::Query { text: "FIND IN docs LIMIT 3".into(), entities: true }
+        );
+        assert_eq!(
+            parse_command(&words("config diff a.toml")).unwrap(),
+            Command::ConfigDiff("a.toml".into(), None)
+        );
+        assert_eq!(parse_command(&words("update --help")).unwrap(), Command::Help(Some("update".into())));
+        assert_eq!(parse_command(&[]).unwrap(), Command::Repl);
+
+        for bad in [
+            "frobnicate",
+            "get docs not-an-id",
+            "search docs",
+            "search docs --vector 1,x",
+            "insert docs --tier lukewarm",
+            "insert docs --vector 1 --vector 2",
+            "update docs 7f1c2b9e-0000-4000-8000-000000000000",
+            "config validate",
+            "stats a b",
+        ] {
+            assert!(parse_command(&words(bad)).is_err(), "{}", bad);
+        }
+        assert!(parse(&words("--output yaml stats")).is_err());
+        assert!(split_words("query 'FIND").is_err());
+    }
+
+    #[test]
+    fn test_renders_tables() {
+        let rows = vec![vec!["a".to_string(), "1".to_string()], vec!["bbb".to_string(), "22".to_string()]];
+        assert_eq!(table(&["name", "n"], &rows), "name  n\n----  --\na     1\nbbb   22\n(2 rows)");
+        assert_eq!(preview(&[1.0, 2.0]), "[1.0000, 2.0000]");
+        assert!(preview(&[0.0; 10]).ends_with("… (10 values)]"));
+    }
+
+    #[test]
+    fn test_history_expansion() {
+        let mut history = History { path: None, entries: Vec::new() };
+        history.push("stats");
+        history.push("stats");
+        history.push("FIND IN docs\nLIMIT 1;");
+        assert_eq!(history.entries, vec!["stats", "FIND IN docs LIMIT 1;"]);
+        assert_eq!(history.expand("!1").unwrap().as_deref(), Some("stats"));
+        assert_eq!(history.expand("!!").unwrap().as_deref(), Some("FIND IN docs LIMIT 1;"));
+        assert_eq!(history.expand("get docs").unwrap(), None);
+        assert!(history.expand("!3").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_config_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("phenix.toml");
+        let mut document = toml::Value::try_from(PhenixConfig::default()).unwrap();
+        let api = document["api"].as_table_mut().unwrap();
+        api.insert("rest_addr".into(), "127.0.0.1:9090".into());
+        api.insert("change_bufer".into(), 5.into());
+        std::fs::write(&path, toml::to_string(&document).unwrap()).unwrap();
+        let options = Options::default();
+
+        let validated = execute(&options, Command::ConfigValidate(path.clone())).await.unwrap();
+        assert!(validated.contains("valid"));
+        assert!(validated.contains("unknown setting 'api.change_bufer'"));
+
+        let shown = execute(&options, Command::ConfigShow(Some(path.clone()))).await.unwrap();
+        assert!(shown.contains("rest_addr = \"127.0.0.1:9090\""));
+        let reparsed: PhenixConfig = toml::from_str(&shown).unwrap();
+        assert_eq!(reparsed.api.rest_addr, "127.0.0.1:9090");
+
+        let changes = diff(&load_config(&path).unwrap(), &PhenixConfig::default()).unwrap();
+        assert_eq!(
+            changes,
+            vec![("api.rest_addr".to_string(), "\"127.0.0.1:9090\"".to_string(), "\"0.0.0.0:8080\"".to_string())]
+        );
+
+        document["api"]["change_buffer"] = 0.into();
+        std::fs::write(&path, toml::to_string(&document).unwrap()).unwrap();
+        assert!(execute(&options, Command::ConfigValidate(path)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_commands_against_a_server() {
+        let mut catalog = Catalog::in_memory();
+        catalog.create_collection("docs", CollectionConfig::new(2)).unwrap();
+        let service = Arc::new(ApiService::new(NodeId::new(), catalog, 16));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let shutdown = tokio_util::sync::CancellationToken::new();
+        let server = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move {
+                rest::serve_with_listener(service, &ApiConfig::default(), listener, shutdown.cancelled_owned()).await
+            }
+        });
+
+        let options = Options {
+            server: format!("http://{}", address),
+            ..Options::default()
+        };
+        let run = |line: &str| {
+            let command = parse_command(&words(line)).unwrap();
+            let options = options.clone();
+            async move { execute(&options, command).await }
+        };
+
+        let id = EntityId::new();
+        let inserted = run(&format!(r#"insert docs --id {} --vector 1,0 --metadata '{{"lang": "en"}}'"#, id)).await;
+        assert!(inserted.unwrap().contains("version     1"));
+        run(r#"insert docs --vector 0,1 --metadata '{"lang": "de"}'"#).await.unwrap();
+
+        let updated = run(&format!(r#"update docs {} --merge --metadata '{{"year": 2024}}' --tier cold"#, id)).await;
+        let updated = updated.unwrap();
+        assert!(updated.contains(r#"{"lang":"en","year":2024}"#), "{}", updated);
+        assert!(updated.contains("cold"));
+
+        let hits = run("search docs --vector 1,0 --k 1").await.unwrap();
+        assert!(hits.contains(&id.to_string()) && hits.ends_with("(1 row)"), "{}", hits);
+        let rows = run(r#"query 'FIND IN docs WHERE lang = "en"'"#).await.unwrap();
+        assert!(rows.contains(&id.to_string()), "{}", rows);
+        let plan = run("query EXPLAIN FIND IN docs LIMIT 1").await.unwrap();
+        assert!(!plan.contains("(0 rows)"), "{}", plan);
+
+        let stats = run("stats").await.unwrap();
+        let line = stats.lines().find(|line| line.starts_with("docs")).unwrap();
+        let cells: Vec<&str> = line.split_whitespace().collect();
+        assert_eq!(&cells[..5], ["docs", "2", "1", "0", "1"]);
+
+        let json_options = Options {
+            output: Output::Json,
+            ..options.clone()
+        };
+        let stats = execute(&json_options, Command::Stats { collection: Some("docs".into()) }).await.unwrap();
+        let stats: Vec<CollectionStats> = serde_json::from_str(&stats).unwrap();
+        assert_eq!(stats[0].tiers.cold, 1);
+
+        assert_eq!(run(&format!("delete docs {}", id)).await.unwrap(), format!("deleted {}", id));
+        let missing = run(&format!("get docs {}", id)).await.unwrap_err().to_string();
+        assert!(missing.starts_with("404"), "{}", missing);
+        assert!(run("stats nope").await.is_err());
+
+        shutdown.cancel();
+        server.await.unwrap().unwrap();
+        let offline = run("stats").await.unwrap_err().to_string();
+        assert!(offline.contains("cannot reach"), "{}", offline);
+    }
+}
@@ -1,25 +1,462 @@
 //! Phenix-DB memory substrate server
+//!
+//! Usage: `phenix-server [OPTIONS] [CONFIG_FILE]`. Settings come from the
+//! defaults, then the configuration file, then `PHENIX_DB_*` environment
+//! variables, then command line flags, each overriding the one before.
+//!
+//! Startup recovers the catalog from its last checkpoint and the
+//! write-ahead log records after it, then starts the gRPC, REST and metrics
+//! listeners and the background workers. On SIGTERM or ctrl-c the listeners
+//! stop accepting and drain in-flight requests within the shutdown timeout
+//! while the workers finish their current pass.
+//! Only once no worker can write any more is the write-ahead log flushed,
+//! a checkpoint written and the log reset.
+//!
+//! The node id is generated on first start and kept in the data directory.
 
+use phenix_db::api::{grpc, rest, ApiService, Workers};
+use phenix_db::core::config::{parse_override, ConfigError};
+use phenix_db::core::{NodeId, PhenixConfig};
+use phenix_db::observability::metrics::{self, Metrics};
+use phenix_db::storage::catalog::Catalog;
 use phenix_db::{BUILD_INFO, VERSION};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+type ServerResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Write-ahead log file inside the data directory
+const WAL_FILE: &str = "phenix.wal";
+
+/// Node id file inside the data directory
+const NODE_ID_FILE: &str = "node_id";
+
+const USAGE: &str = "\
+Usage: phenix-server [OPTIONS] [CONFIG_FILE]
+
+Options:
+  -c, --config <FILE>        Configuration file (TOML)
+      --data-dir <DIR>       Directory for the write-ahead log, checkpoint and node id
+      --in-memory            Keep all data in memory; nothing survives a restart
+      --grpc-addr <ADDR>     gRPC listen address
+      --rest-addr <ADDR>     REST listen address
+      --metrics-addr <ADDR>  Prometheus metrics listen address ('' disables it)
+      --set <KEY=VALUE>      Override any setting, e.g. --set tiering.hot_tier_size_pct=0.2
+  -h, --help                 Show this help
+  -V, --version              Show the version
+
+Environment variables named PHENIX_DB_<SECTION>_<KEY> override the file, and
+flags override both.";
+
+/// What the command line asks for
+#[derive(Debug, Clone, PartialEq)]
+enum Invocation {
+    Run {
+        file: Option<PathBuf>,
+        overrides: Vec<(String, toml::Value)>,
+    },
+    Help,
+    Version,
+}
+
+/// Parse the command line (without the program name)
+fn parse_args(args: &[String]) -> Result<Invocation, String> {
+    let mut file = None;
+    let mut overrides = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| iter.next().cloned())
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+        let setting = match flag {
+            "-h" | "--help" => return Ok(Invocation::Help),
+            "-V" | "--version" => return Ok(Invocation::Version),
+            "-c" | "--config" => {
+                file = Some(PathBuf::from(value()?));
+                continue;
+            }
+            "--data-dir" => "server.data_dir",
+            "--grpc-addr" => "api.grpc_addr",
+            "--rest-addr" => "api.rest_addr",
+            "--metrics-addr" => "server.metrics_addr",
+            "--in-memory" => {
+                overrides.push(("server.in_memory".to_string(), toml::Value::Boolean(true)));
+                continue;
+            }
+            "--set" => {
+                let assignment = value()?;
+                let (key, value) = assignment
+                    .split_once('=')
+                    .ok_or_else(|| format!("--set expects KEY=VALUE, got '{}'", assignment))?;
+                overrides.push((key.trim().to_string(), parse_override(value.trim())));
+                continue;
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown option {}", flag)),
+            path if file.is_none() => {
+                file = Some(PathBuf::from(path));
+                continue;
+            }
+            extra => return Err(format!("unexpected argument '{}'", extra)),
+        };
+        // Addresses and paths are plain strings, never TOML values
+        overrides.push((setting.to_string(), toml::Value::String(value()?)));
+    }
+    Ok(Invocation::Run { file, overrides })
+}
+
+/// Layer the file, environment and flag settings over the defaults
+fn load_config(
+    file: Option<&PathBuf>,
+    env: impl IntoIterator<Item = (String, String)>,
+    overrides: &[(String, toml::Value)],
+) -> Result<PhenixConfig, ConfigError> {
+    let config = match file {
+        Some(path) => PhenixConfig::from_file(path)?,
+        None => PhenixConfig::default(),
+    };
+    let config = config
+        .with_overrides(PhenixConfig::env_overrides(env)?)?
+        .with_values(overrides.iter().cloned())?;
+    config.validate()?;
+    Ok(config)
+}
+
+/// Open the catalog the configuration asks for, replaying its log
+fn open_catalog(config: &PhenixConfig) -> ServerResult<Catalog> {
+    if config.server.in_memory {
+        tracing::warn!("running in memory; data will not survive a restart");
+        return Ok(Catalog::in_memory_with_cdc(&config.cdc));
+    }
+    let data_dir = &config.server.data_dir;
+    std::fs::create_dir_all(data_dir)
+        .map_err(|error| format!("cannot create data directory {}: {}", data_dir.display(), error))?;
+    let catalog = Catalog::open_with_cdc(data_dir.join(WAL_FILE), &config.cdc)?;
+
+    let stats = catalog.collection_stats();
+    let tiers = stats.iter().fold([0; 3], |[hot, warm, cold], stats| {
+        [hot + stats.tiers.hot, warm + stats.tiers.warm, cold + stats.tiers.cold]
+    });
+    tracing::info!(
+        data_dir = %data_dir.display(),
+        collections = stats.len(),
+        hot = tiers[0],
+        warm = tiers[1],
+        cold = tiers[2],
+        "storage tiers opened"
+    );
+    Ok(catalog)
+}
+
+/// Node id kept in the data directory, created on first start
+fn node_id(config: &PhenixConfig) -> ServerResult<NodeId> {
+    if config.server.in_memory {
+        return Ok(NodeId::new());
+    }
+    let path = config.server.data_dir.join(NODE_ID_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(text) => {
+            let id = text
+                .trim()
+                .parse::<uuid::Uuid>()
+                .map_err(|error| format!("invalid node id in {}: {}", path.display(), error))?;
+            return Ok(NodeId::from_uuid(id));
+        }
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => return Err(error.into()),
+        Err(_) => {}
+    }
+    let id = NodeId::new();
+    write_durably(&path, format!("{}\n", id).as_bytes())?;
+    tracing::info!(node_id = %id, path = %path.display(), "node id created");
+    Ok(id)
+}
+
+/// Replace `path` atomically with a synced temporary file
+fn write_durably(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let staged = path.with_extension("tmp");
+    let mut file = std::fs::File::create(&staged)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    std::fs::rename(&staged, path)?;
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Serve until `shutdown` is cancelled or a listener fails, then shut down
+/// gracefully
+async fn run(config: PhenixConfig, shutdown: CancellationToken) -> ServerResult<()> {
+    let catalog = open_catalog(&config)?;
+    let service = Arc::new(
+        ApiService::new(node_id(&config)?, catalog, config.api.change_buffer)
+            .with_cost_weights(config.bellman.cost_weights.clone())
+            .with_planner_config(config.planner.clone())
+            .with_transaction_timeout(Duration::from_millis(config.api.transaction_timeout_ms)),
+    );
+    let metrics = Arc::new(Metrics::new());
+    let workers = Workers::spawn(service.clone(), &config, metrics.clone());
+
+    let mut listeners: Vec<(&str, JoinHandle<phenix_db::Result<()>>)> = Vec::new();
+    listeners.push(("gRPC", {
+        let (service, api, stop) = (service.clone(), config.api.clone(), shutdown.clone());
+        tokio::spawn(async move { grpc::serve(service, &api, stop.cancelled_owned()).await })
+    }));
+    listeners.push(("REST", {
+        let (service, api, stop) = (service.clone(), config.api.clone(), shutdown.clone());
+        tokio::spawn(async move { rest::serve(service, &api, stop.cancelled_owned()).await })
+    }));
+    if !config.server.metrics_addr.is_empty() {
+        let (service, addr, stop) = (service.clone(), config.server.metrics_addr.clone(), shutdown.clone());
+        let metrics = metrics.clone();
+        listeners.push((
+            "metrics",
+            tokio::spawn(async move { metrics::serve(metrics, service, &addr, stop.cancelled_owned()).await }),
+        ));
+    }
+    tracing::info!(node_id = %service.node_id(), "Phenix-DB server started");
+
+    // A listener only returns early when it fails; bring the rest down too
+    let exited = {
+        let exits = futures::future::select_all(listeners.iter_mut().map(|(_, handle)| handle));
+        tokio::select! {
+            _ = shutdown.cancelled() => None,
+            (exit, index, _) = exits => Some((index, exit)),
+        }
+    };
+    let failure = exited.map(|(index, exit)| {
+        let (name, _) = listeners.remove(index);
+        shutdown.cancel();
+        match exit {
+            Ok(Ok(())) => format!("{} listener stopped unexpectedly", name),
+            Ok(Err(error)) => format!("{} listener failed: {}", name, error),
+            Err(error) => format!("{} listener panicked: {}", name, error),
+        }
+    });
+
+    let deadline = Duration::from_secs(config.server.shutdown_timeout_secs);
+    tracing::info!(timeout_secs = deadline.as_secs(), "draining requests and stopping workers");
+    let workers = tokio::spawn(workers.stop());
+    let drained = tokio::time::timeout(deadline, async {
+        for (name, handle) in listeners.iter_mut() {
+            match handle.await {
+                Ok(Err(error)) => tracing::warn!(listener = *name, %error, "listener stopped with an error"),
+                Err(error) if !error.is_cancelled() => tracing::warn!(listener = *name, %error, "listener panicked"),
+                _ => {}
+            }
+        }
+    })
+    .await;
+    if drained.is_err() {
+        tracing::warn!(timeout_secs = deadline.as_secs(), "shutdown deadline passed; abandoning in-flight requests");
+        for (_, handle) in &listeners {
+            handle.abort();
+        }
+    }
+    // A pass cannot be interrupted; the checkpoint waits for it to finish
+    if !workers.is_finished() {
+        tracing::warn!("waiting for a background pass in progress before writing the checkpoint");
+    }
+    if let Err(error) = workers.await {
+        tracing::warn!(%error, "background workers panicked while stopping");
+    }
+
+    service.sync()?;
+    match service.checkpoint()? {
+        Some(checkpoint) => tracing::info!(
+            next_lsn = checkpoint.next_lsn,
+            collections = checkpoint.collections.len(),
+            "write-ahead log flushed and checkpoint written"
+        ),
+        None => tracing::info!("in-memory catalog discarded"),
+    }
+    match failure {
+        Some(error) => Err(error.into()),
+        None => {
+            tracing::info!("Phenix-DB server stopped");
+            Ok(())
+        }
+    }
+}
+
+/// Cancel `shutdown` on ctrl-c or SIGTERM
+fn watch_signals(shutdown: CancellationToken) {
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        let terminate = async {
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(mut signal) => {
+                    signal.recv().await;
+                }
+                Err(error) => {
+                    tracing::warn!(%error, "cannot listen for SIGTERM");
+                    std::future::pending::<()>().await;
+                }
+            }
+        };
+        #[cfg(not(unix))]
+        let terminate = std::future::pending::<()>();
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => tracing::info!("interrupt received, shutting down"),
+            _ = terminate => tracing::info!("SIGTERM received, shutting down"),
+        }
+        shutdown.cancel();
+    });
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (file, overrides) = match parse_args(&args) {
+        Ok(Invocation::Run { file, overrides }) => (file, overrides),
+        Ok(Invocation::Help) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Ok(Invocation::Version) => {
+            println!("phenix-server {}\n{}", VERSION, BUILD_INFO);
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, USAGE);
+            return ExitCode::from(2);
+        }
+    };
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize tracing
     tracing_subscriber::fmt::init();
-
     tracing::info!("{}", BUILD_INFO);
     tracing::info!("Starting Phenix-DB server v{}", VERSION);
 
-    // TODO: Initialize memory substrate components
-    // TODO: Start gRPC and REST servers
-    // TODO: Initialize distributed consciousness
-    // TODO: Start observability endpoints
+    let config = match load_config(file.as_ref(), std::env::vars(), &overrides) {
+        Ok(config) => config,
+        Err(error) => {
+            tracing::error!(%error, "invalid configuration");
+            return ExitCode::from(2);
+        }
+    };
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(error) => {
+            tracing::error!(%error, "cannot start the runtime");
+            return ExitCode::FAILURE;
+        }
+    };
+    let result = runtime.block_on(async {
+        let shutdown = CancellationToken::new();
+        watch_signals(shutdown.clone());
+        run(config, shutdown).await
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            tracing::error!(%error, "Phenix-DB server failed");
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    tracing::info!("Phenix-DB server started successfully");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use phenix_db::core::collection::CollectionConfig;
+    use phenix_db::storage::checkpoint::Checkpoint;
 
-    // Keep server running
-    tokio::signal::ctrl_c().await?;
-    tracing::info!("Shutting down Phenix-DB server");
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
 
-    Ok(())
+    #[test]
+    fn test_flags_layer_over_file_and_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("phenix.toml");
+        let mut document = toml::Value::try_from(PhenixConfig::default()).unwrap();
+        document["api"]["rest_addr"] = "127.0.0.1:1000".into();
+        document["api"]["grpc_addr"] = "127.0.0.1:1001".into();
+        document["cdc"]["retained_events"] = 5.into();
+        std::fs::write(&file, toml::to_string(&document).unwrap()).unwrap();
+
+        let line = format!(
+            "{} --rest-addr 127.0.0.1:2000 --metrics-addr= --set cdc.retained_events=7 --data-dir=/srv/phenix",
+            file.display()
+        );
+        let Invocation::Run { file, overrides } = parse_args(&args(&line)).unwrap() else {
+            panic!("not a run");
+        };
+        let env = vec![
+            ("PHENIX_DB_API_REST_ADDR".to_string(), "127.0.0.1:3000".to_string()),
+            ("PHENIX_DB_API_GRPC_ADDR".to_string(), "127.0.0.1:3001".to_string()),
+        ];
+        let config = load_config(file.as_ref(), env, &overrides).unwrap();
+        assert_eq!(config.api.rest_addr, "127.0.0.1:2000");
+        assert_eq!(config.api.grpc_addr, "127.0.0.1:3001");
+        assert_eq!(config.cdc.retained_events, 7);
+        assert_eq!(config.server.metrics_addr, "");
+        assert_eq!(config.server.data_dir, PathBuf::from("/srv/phenix"));
+
+        // Flag values are taken verbatim, whatever TOML would make of them
+        let odd = "/srv/ph\u{7f}enix \"quoted\" \\n".to_string();
+        let invocation = parse_args(&["--data-dir".to_string(), odd.clone()]).unwrap();
+        let Invocation::Run { overrides, .. } = invocation else { unreachable!() };
+        let config = load_config(None, Vec::new(), &overrides).unwrap();
+        assert_eq!(config.server.data_dir, PathBuf::from(odd));
+
+        assert_eq!(parse_args(&args("-c a.toml --help")).unwrap(), Invocation::Help);
+        for bad in ["--frobnicate", "a.toml b.toml", "--set novalue", "--rest-addr"] {
+            assert!(parse_args(&args(bad)).is_err(), "{}", bad);
+        }
+        let invalid = parse_args(&args("--set server.shutdown_timeout_secs=0")).unwrap();
+        let Invocation::Run { overrides, .. } = invalid else { unreachable!() };
+        assert!(load_config(None, Vec::new(), &overrides).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_flushes_and_checkpoints() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = PhenixConfig::default();
+        config.server.data_dir = dir.path().to_path_buf();
+        config.api.grpc_addr = "127.0.0.1:0".into();
+        config.api.rest_addr = "127.0.0.1:0".into();
+        config.server.metrics_addr = "127.0.0.1:0".into();
+        config.server.shutdown_timeout_secs = 5;
+
+        // Data written before startup is recovered and covered by the checkpoint
+        let mut catalog = Catalog::open(dir.path().join(WAL_FILE)).unwrap();
+        catalog.create_collection("docs", CollectionConfig::new(2)).unwrap();
+        drop(catalog);
+
+        let shutdown = CancellationToken::new();
+        let server = tokio::spawn(run(config.clone(), shutdown.clone()));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!server.is_finished());
+        shutdown.cancel();
+        server.await.unwrap().unwrap();
+
+        let checkpoint = Checkpoint::read(&Checkpoint::path_for(&dir.path().join(WAL_FILE))).unwrap().unwrap();
+        assert_eq!(checkpoint.next_lsn, 2);
+        assert_eq!(checkpoint.collections[0].name, "docs");
+
+        // The node keeps its identity across restarts
+        let first = node_id(&config).unwrap();
+        assert_eq!(node_id(&config).unwrap(), first);
+        let stored = std::fs::read_to_string(dir.path().join(NODE_ID_FILE)).unwrap();
+        assert_eq!(stored.trim(), first.to_string());
+
+        // A listener that cannot bind stops the server with an error
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        config.api.rest_addr = taken.local_addr().unwrap().to_string();
+        let error = run(config, CancellationToken::new()).await.unwrap_err();
+        assert!(error.to_string().contains("REST listener failed"), "{}", error);
+    }
 }
@@ -0,0 +1,508 @@
+//! Access tracking
+//!
+//! Access statistics live outside `Entity` so that reading an entity never
+//! mutates it. The `AccessTracker` spreads entities over shards of lock-free
+//! `EntityIndex` maps; each entry is a set of atomic counters. Callers record
+//! accesses one at a time or collect them in an `AccessBatch`, which folds
+//! repeated accesses into a single atomic increment per entity.
+//!
+//! Recording never touches the counters directly: events are pushed onto a
+//! wait-free MPSC queue and applied in bulk, by the recording thread that
+//! finds `DRAIN_THRESHOLD` events queued, or before any read so that reads
+//! see every completed recording.
+//!
+//! Co-accessed entities are kept in a fixed-size ring buffer per entity.
+//! Every slot is guarded by its own sequence number: writers claim a slot
+//! with one CAS, and readers skip slots that are being written instead of
+//! waiting for them.
+
+use crate::concurrency::atomic_ops::AtomicF32;
+use crate::concurrency::lockfree::{mpsc_queue, EntityIndex, MpscConsumer, MpscProducer};
+use crate::core::{AccessStatistics, EntityId};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{fence, AtomicU64, AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Default number of shards
+pub const DEFAULT_SHARDS: usize = 16;
+
+/// Co-accessed entities remembered per entity
+pub const CO_ACCESS_CAPACITY: usize = 32;
+
+/// Queued events at which a recording thread applies the queue
+pub const DRAIN_THRESHOLD: usize = 1024;
+
+/// Milliseconds per hour, the unit of `access_frequency`
+const MS_PER_HOUR: f32 = 60.0 * 60.0 * 1000.0;
+
+/// Smoothing factor of the access frequency moving average
+const FREQUENCY_ALPHA: f32 = 0.1;
+
+/// Ring buffer slot holding one entity id
+///
+/// `seq` is 0 while the slot has never been written, odd while a writer owns
+/// it and even once the id halves are published.
+#[derive(Default)]
+struct CoAccessSlot {
+    seq: AtomicU64,
+    hi: AtomicU64,
+    lo: AtomicU64,
+}
+
+impl CoAccessSlot {
+    /// Publish an id, dropping it if another writer owns the slot
+    fn write(&self, id: EntityId) {
+        let seq = self.seq.load(Ordering::Relaxed);
+        if seq % 2 == 1
+            || self
+                .seq
+                .compare_exchange(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+        {
+            return;
+        }
+        fence(Ordering::Release);
+        let bits = id.as_uuid().as_u128();
+        self.hi.store((bits >> 64) as u64, Ordering::Relaxed);
+        self.lo.store(bits as u64, Ordering::Relaxed);
+        self.seq.store(seq + 2, Ordering::Release);
+    }
+
+    /// Read the id, or `None` if the slot is empty or being written
+    fn read(&self) -> Option<EntityId> {
+        let before = self.seq.load(Ordering::Acquire);
+        if before == 0 || before % 2 == 1 {
+            return None;
+        }
+        let hi = self.hi.load(Ordering::Relaxed);
+        let lo = self.lo.load(Ordering::Relaxed);
+        fence(Ordering::Acquire);
+        if self.seq.load(Ordering::Relaxed) != before {
+            return None;
+        }
+        let bits = (u128::from(hi) << 64) | u128::from(lo);
+        Some(EntityId::from_uuid(uuid::Uuid::from_u128(bits)))
+    }
+}
+
+/// Atomic access counters of one entity
+struct AccessCounters {
+    total: AtomicU64,
+    last_access: AtomicU64,
+    frequency: AtomicF32,
+    co_access: [CoAccessSlot; CO_ACCESS_CAPACITY],
+    cursor: AtomicUsize,
+}
+
+impl AccessCounters {
+    fn new(now: u64) -> Self {
+        Self {
+            total: AtomicU64::new(0),
+            last_access: AtomicU64::new(now),
+            frequency: AtomicF32::new(0.0),
+            co_access: std::array::from_fn(|_| CoAccessSlot::default()),
+            cursor: AtomicUsize::new(0),
+        }
+    }
+
+    /// Add `count` accesses observed at `now`
+    ///
+    /// The batch counts as one sample of the moving average, with an
+    /// instantaneous rate of `count` accesses since the previous sample.
+    fn record(&self, count: u64, now: u64) {
+        self.total.fetch_add(count, Ordering::Relaxed);
+        let previous = self.last_access.fetch_max(now, Ordering::AcqRel);
+        let hours_elapsed = now.saturating_sub(previous) as f32 / MS_PER_HOUR;
+        if hours_elapsed > 0.0 {
+            let instant = count as f32 / hours_elapsed;
+            self.frequency.update(Ordering::AcqRel, |frequency| {
+                (1.0 - FREQUENCY_ALPHA) * frequency + FREQUENCY_ALPHA * instant
+            });
+        }
+    }
+
+    fn record_co_access(&self, other: EntityId) {
+        let slot = self.cursor.fetch_add(1, Ordering::Relaxed) % CO_ACCESS_CAPACITY;
+        self.co_access[slot].write(other);
+    }
+
+    /// Distinct co-accessed entities, most recent first
+    fn co_accessed(&self) -> Vec<EntityId> {
+        let newest = self.cursor.load(Ordering::Relaxed);
+        let mut ids = Vec::with_capacity(CO_ACCESS_CAPACITY);
+        for back in 1..=CO_ACCESS_CAPACITY {
+            let slot = newest.wrapping_sub(back) % CO_ACCESS_CAPACITY;
+            if let Some(id) = self.co_access[slot].read() {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        ids
+    }
+
+    fn snapshot(&self) -> AccessStatistics {
+        AccessStatistics {
+            total_accesses: self.total.load(Ordering::Relaxed),
+            last_access: self.last_access.load(Ordering::Acquire),
+            access_frequency: self.frequency.load(Ordering::Acquire),
+            co_access_entities: self.co_accessed(),
+        }
+    }
+}
+
+/// Event waiting in the tracker's queue
+#[derive(Debug, Clone, Copy)]
+enum AccessEvent {
+    /// `count` accesses of an entity observed at `at`
+    Access { id: EntityId, count: u64, at: u64 },
+    /// Two entities accessed together at `at`
+    CoAccess { a: EntityId, b: EntityId, at: u64 },
+}
+
+/// Accesses buffered by one thread before being applied to a tracker
+#[derive(Debug, Clone, Default)]
+pub struct AccessBatch {
+    counts: HashMap<EntityId, u64>,
+    co_access: Vec<(EntityId, EntityId)>,
+}
+
+impl AccessBatch {
+    /// Create an empty batch
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Buffer an access
+    pub fn record(&mut self, id: EntityId) {
+        *self.counts.entry(id).or_default() += 1;
+    }
+
+    /// Buffer a co-access between two entities
+    pub fn record_co_access(&mut self, a: EntityId, b: EntityId) {
+        if a != b {
+            self.co_access.push((a, b));
+        }
+    }
+
+    /// Number of buffered events
+    pub fn len(&self) -> usize {
+        self.counts.values().sum::<u64>() as usize + self.co_access.len()
+    }
+
+    /// Whether nothing is buffered
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty() && self.co_access.is_empty()
+    }
+}
+
+/// Concurrent, sharded access statistics for a set of entities
+///
+/// All methods take `&self`; recording never blocks, and reading never
+/// blocks writers.
+pub struct AccessTracker {
+    shards: Box<[EntityIndex<AccessCounters>]>,
+    events: MpscProducer<AccessEvent>,
+    /// Held while applying queued events
+    consumer: Mutex<MpscConsumer<AccessEvent>>,
+}
+
+impl AccessTracker {
+    /// Create a tracker with `DEFAULT_SHARDS` shards
+    pub fn new() -> Self {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+
+    /// Create a tracker with `shards` shards, rounded up to a power of two
+    pub fn with_shards(shards: usize) -> Self {
+        let shards = shards.max(1).next_power_of_two();
+        let (events, consumer) = mpsc_queue();
+        Self {
+            shards: (0..shards).map(|_| EntityIndex::new()).collect(),
+            events,
+            consumer: Mutex::new(consumer),
+        }
+    }
+
+    /// Number of tracked entities
+    pub fn len(&self) -> usize {
+        self.flush();
+        self.shards.iter().map(EntityIndex::len).sum()
+    }
+
+    /// Whether no entity is tracked
+    pub fn is_empty(&self) -> bool {
+        self.flush();
+        self.shards.iter().all(EntityIndex::is_empty)
+    }
+
+    /// Approximate number of queued events not yet applied
+    pub fn pending(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Record a single access
+    pub fn record_access(&self, id: EntityId) {
+        self.push(AccessEvent::Access {
+            id,
+            count: 1,
+            at: current_timestamp_ms(),
+        });
+    }
+
+    /// Record that two entities were accessed together
+    pub fn record_co_access(&self, a: EntityId, b: EntityId) {
+        if a == b {
+            return;
+        }
+        self.push(AccessEvent::CoAccess {
+            a,
+            b,
+            at: current_timestamp_ms(),
+        });
+    }
+
+    /// Queue and clear a batch
+    pub fn apply(&self, batch: &mut AccessBatch) {
+        self.apply_at(batch, current_timestamp_ms());
+    }
+
+    fn apply_at(&self, batch: &mut AccessBatch, at: u64) {
+        for (id, count) in batch.counts.drain() {
+            self.events.push(AccessEvent::Access { id, count, at });
+        }
+        for (a, b) in batch.co_access.drain(..) {
+            self.events.push(AccessEvent::CoAccess { a, b, at });
+        }
+        self.maybe_drain();
+    }
+
+    /// Statistics of an entity, if it has been accessed
+    pub fn stats(&self, id: &EntityId) -> Option<AccessStatistics> {
+        self.flush();
+        self.shard(id).get_with(id, AccessCounters::snapshot)
+    }
+
+    /// Entities recently co-accessed with `id`, most recent first
+    pub fn co_accessed(&self, id: &EntityId) -> Vec<EntityId> {
+        self.flush();
+        self.shard(id)
+            .get_with(id, AccessCounters::co_accessed)
+            .unwrap_or_default()
+    }
+
+    /// Forget an entity, returning whether it was tracked
+    ///
+    /// Events queued for it are applied first, so they cannot bring it back.
+    pub fn remove(&self, id: &EntityId) -> bool {
+        self.flush();
+        self.shard(id).remove(id)
+    }
+
+    /// Apply every queued event
+    ///
+    /// Waits for a drain in progress on another thread, so that the caller
+    /// sees every recording that completed before the call.
+    pub fn flush(&self) {
+        if self.events.is_empty() {
+            return;
+        }
+        self.drain(&mut self.consumer.lock());
+    }
+
+    fn push(&self, event: AccessEvent) {
+        self.events.push(event);
+        self.maybe_drain();
+    }
+
+    /// Apply the queue from a recording thread once it is long enough,
+    /// unless another thread is already applying it
+    fn maybe_drain(&self) {
+        if self.events.len() < DRAIN_THRESHOLD {
+            return;
+        }
+        if let Some(mut consumer) = self.consumer.try_lock() {
+            self.drain(&mut consumer);
+        }
+    }
+
+    fn drain(&self, consumer: &mut MpscConsumer<AccessEvent>) {
+        while let Some(event) = consumer.pop() {
+            match event {
+                AccessEvent::Access { id, count, at } => {
+                    self.with_counters(id, at, |counters| counters.record(count, at));
+                }
+                AccessEvent::CoAccess { a, b, at } => {
+                    self.with_counters(a, at, |counters| counters.record_co_access(b));
+                    self.with_counters(b, at, |counters| counters.record_co_access(a));
+                }
+            }
+        }
+    }
+
+    fn shard(&self, id: &EntityId) -> &EntityIndex<AccessCounters> {
+        // UUID v7 keeps its random bits at the low end
+        let bits = id.as_uuid().as_u128() as usize;
+        &self.shards[bits & (self.shards.len() - 1)]
+    }
+
+    fn with_counters(&self, id: EntityId, now: u64, f: impl FnOnce(&AccessCounters)) {
+        self.shard(&id)
+            .get_or_insert_with(id, || AccessCounters::new(now), f)
+    }
+}
+
+impl Default for AccessTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for AccessTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessTracker")
+            .field("shards", &self.shards.len())
+            .field("entities", &self.len())
+            .field("pending", &self.pending())
+            .finish()
+    }
+}
+
+/// Get current timestamp in milliseconds since Unix epoch
+fn current_timestamp_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_record_and_stats() {
+        let tracker = AccessTracker::with_shards(3);
+        let id = EntityId::new();
+        assert!(tracker.stats(&id).is_none());
+
+        tracker.record_access(id);
+        tracker.record_access(id);
+        let stats = tracker.stats(&id).unwrap();
+        assert_eq!(stats.total_accesses, 2);
+        assert_eq!(tracker.len(), 1);
+
+        assert!(tracker.remove(&id));
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn test_batch_updates_frequency_once() {
+        let tracker = AccessTracker::new();
+        let id = EntityId::new();
+        let start = current_timestamp_ms();
+        tracker.with_counters(id, start, |_| {});
+
+        // Ten accesses over one hour: instantaneous rate of 10/hour
+        let mut batch = AccessBatch::new();
+        for _ in 0..10 {
+            batch.record(id);
+        }
+        assert_eq!(batch.len(), 10);
+        tracker.apply_at(&mut batch, start + 60 * 60 * 1000);
+        assert!(batch.is_empty());
+
+        let stats = tracker.stats(&id).unwrap();
+        assert_eq!(stats.total_accesses, 10);
+        assert!((stats.access_frequency - 1.0).abs() < 1e-4);
+        assert_eq!(stats.last_access, start + 60 * 60 * 1000);
+    }
+
+    #[test]
+    fn test_events_queue_until_read_or_threshold() {
+        let tracker = AccessTracker::new();
+        let id = EntityId::new();
+        for _ in 0..10 {
+            tracker.record_access(id);
+        }
+        assert_eq!(tracker.pending(), 10);
+        assert_eq!(tracker.stats(&id).unwrap().total_accesses, 10);
+        assert_eq!(tracker.pending(), 0);
+
+        // Recording drains the queue itself once it reaches the threshold
+        for _ in 0..DRAIN_THRESHOLD {
+            tracker.record_access(id);
+        }
+        assert_eq!(tracker.pending(), 0);
+
+        // Queued events cannot resurrect a removed entity
+        tracker.record_access(id);
+        assert!(tracker.remove(&id));
+        assert!(tracker.stats(&id).is_none());
+    }
+
+    #[test]
+    fn test_co_access_ring_is_bounded_and_recent_first() {
+        let tracker = AccessTracker::new();
+        let id = EntityId::new();
+        let others: Vec<_> = (0..CO_ACCESS_CAPACITY + 8).map(|_| EntityId::new()).collect();
+
+        for other in &others {
+            tracker.record_co_access(id, *other);
+        }
+        tracker.record_co_access(id, id);
+
+        let recent = tracker.co_accessed(&id);
+        assert_eq!(recent.len(), CO_ACCESS_CAPACITY);
+        assert_eq!(recent[0], *others.last().unwrap());
+        assert!(!recent.contains(&others[0]));
+        assert_eq!(tracker.co_accessed(&others[0]), vec![id]);
+    }
+
+    #[test]
+    fn test_concurrent_recording_is_not_lost() {
+        let tracker = Arc::new(AccessTracker::with_shards(4));
+        let ids: Arc<Vec<_>> = Arc::new((0..16).map(|_| EntityId::new()).collect());
+
+        let threads: Vec<_> = (0..8)
+            .map(|t| {
+                let tracker = Arc::clone(&tracker);
+                let ids = Arc::clone(&ids);
+                std::thread::spawn(move || {
+                    let mut batch = AccessBatch::new();
+                    for step in 0..1000 {
+                        let id = ids[(step + t) % ids.len()];
+                        if step % 2 == 0 {
+                            tracker.record_access(id);
+                        } else {
+                            batch.record(id);
+                            batch.record_co_access(id, ids[(step + 1) % ids.len()]);
+                        }
+                        if batch.len() >= 64 {
+                            tracker.apply(&mut batch);
+                        }
+                        // Readers run alongside writers without blocking
+                        let _ = tracker.co_accessed(&id);
+                    }
+                    tracker.apply(&mut batch);
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let total: u64 = ids
+            .iter()
+            .map(|id| tracker.stats(id).unwrap().total_accesses)
+            .sum();
+        assert_eq!(total, 8 * 1000);
+        for id in ids.iter() {
+            let recent = tracker.co_accessed(id);
+            assert!(recent.iter().all(|other| ids.contains(other) && other != id));
+        }
+    }
+}
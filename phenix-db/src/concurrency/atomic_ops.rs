@@ -1 +1,160 @@
-// TODO: Implement atomic operations
+//! Atomic operations
+//!
+//! Atomic primitives missing from `std::sync::atomic`. `AtomicF32` stores the
+//! IEEE-754 bits of an `f32` in an `AtomicU32`; read-modify-write operations
+//! run as compare-and-swap loops, so concurrent updates are never lost.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// `f32` that can be shared and updated between threads
+#[derive(Default)]
+pub struct AtomicF32 {
+    bits: AtomicU32,
+}
+
+impl AtomicF32 {
+    /// Create a new atomic float
+    pub fn new(value: f32) -> Self {
+        Self {
+            bits: AtomicU32::new(value.to_bits()),
+        }
+    }
+
+    /// Load the value
+    pub fn load(&self, order: Ordering) -> f32 {
+        f32::from_bits(self.bits.load(order))
+    }
+
+    /// Store a value
+    pub fn store(&self, value: f32, order: Ordering) {
+        self.bits.store(value.to_bits(), order);
+    }
+
+    /// Store a value, returning the previous one
+    pub fn swap(&self, value: f32, order: Ordering) -> f32 {
+        f32::from_bits(self.bits.swap(value.to_bits(), order))
+    }
+
+    /// Store `new` if the current value is bitwise equal to `current`
+    ///
+    /// # Returns
+    /// * `Ok(previous)` on success, `Err(actual)` otherwise
+    pub fn compare_exchange(&self, current: f32, new: f32, success: Ordering, failure: Ordering) -> Result<f32, f32> {
+        self.bits
+            .compare_exchange(current.to_bits(), new.to_bits(), success, failure)
+            .map(f32::from_bits)
+            .map_err(f32::from_bits)
+    }
+
+    /// Atomically replace the value with `f(value)`
+    ///
+    /// `f` may run several times under contention and should be pure.
+    ///
+    /// # Returns
+    /// * `(previous, new)` values of the successful update
+    pub fn update<F>(&self, order: Ordering, mut f: F) -> (f32, f32)
+    where
+        F: FnMut(f32) -> f32,
+    {
+        let mut current = self.bits.load(Ordering::Relaxed);
+        loop {
+            let old = f32::from_bits(current);
+            let new = f(old);
+            match self
+                .bits
+                .compare_exchange_weak(current, new.to_bits(), order, Ordering::Relaxed)
+            {
+                Ok(_) => return (old, new),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Add to the value, returning the previous value
+    pub fn fetch_add(&self, delta: f32, order: Ordering) -> f32 {
+        self.update(order, |v| v + delta).0
+    }
+
+    /// Consume the atomic, returning the value
+    pub fn into_inner(self) -> f32 {
+        f32::from_bits(self.bits.into_inner())
+    }
+}
+
+impl Clone for AtomicF32 {
+    fn clone(&self) -> Self {
+        Self::new(self.load(Ordering::SeqCst))
+    }
+}
+
+impl fmt::Debug for AtomicF32 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.load(Ordering::SeqCst), f)
+    }
+}
+
+impl From<f32> for AtomicF32 {
+    fn from(value: f32) -> Self {
+        Self::new(value)
+    }
+}
+
+impl Serialize for AtomicF32 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f32(self.load(Ordering::SeqCst))
+    }
+}
+
+impl<'de> Deserialize<'de> for AtomicF32 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        f32::deserialize(deserializer).map(Self::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_basic_operations() {
+        let value = AtomicF32::new(0.5);
+        assert_eq!(value.load(Ordering::SeqCst), 0.5);
+        assert_eq!(value.swap(0.25, Ordering::SeqCst), 0.5);
+        assert_eq!(value.compare_exchange(0.5, 1.0, Ordering::SeqCst, Ordering::SeqCst), Err(0.25));
+        assert_eq!(value.compare_exchange(0.25, 1.0, Ordering::SeqCst, Ordering::SeqCst), Ok(0.25));
+        assert_eq!(value.update(Ordering::SeqCst, |v| v / 4.0), (1.0, 0.25));
+        assert_eq!(value.fetch_add(0.5, Ordering::SeqCst), 0.25);
+        assert_eq!(value.into_inner(), 0.75);
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let json = serde_json::to_string(&AtomicF32::new(0.125)).unwrap();
+        assert_eq!(json, "0.125");
+        let value: AtomicF32 = serde_json::from_str(&json).unwrap();
+        assert_eq!(value.load(Ordering::SeqCst), 0.125);
+    }
+
+    #[test]
+    fn test_concurrent_adds_are_not_lost() {
+        // Integers up to 2^24 are exact in f32, so the sum is deterministic
+        let value = Arc::new(AtomicF32::new(0.0));
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let value = Arc::clone(&value);
+                std::thread::spawn(move || {
+                    for _ in 0..10_000 {
+                        value.fetch_add(1.0, Ordering::AcqRel);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(value.load(Ordering::SeqCst), 80_000.0);
+    }
+}
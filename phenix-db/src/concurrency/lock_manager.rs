@@ -0,0 +1,511 @@
+//! Lock manager for pessimistic transactions
+//!
+//! Per-entity shared/exclusive locks for workloads where optimistic retries
+//! thrash, such as heavy contention on hub entities. Requests that cannot be
+//! granted queue in FIFO order (lock upgrades jump the queue) and add edges to
+//! a wait-for graph. Every new wait is checked for a cycle; on a deadlock the
+//! youngest transaction in the cycle (the highest `TransactionId`, since ids
+//! are allocated in begin order) is aborted and its locks released.
+//!
+//! The core is non-blocking (`request` / `poll`), which lets tests script
+//! interleavings deterministically; `acquire` blocks on top of it with a
+//! timeout.
+
+use crate::core::error::{ConcurrencyError, ConcurrencyResult};
+use crate::core::transaction::TransactionId;
+use crate::core::EntityId;
+use parking_lot::{Condvar, Mutex};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Lock mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockMode {
+    /// Compatible with other shared locks
+    Shared,
+
+    /// Compatible with nothing
+    Exclusive,
+}
+
+impl LockMode {
+    fn conflicts_with(self, other: LockMode) -> bool {
+        self == LockMode::Exclusive || other == LockMode::Exclusive
+    }
+}
+
+/// Result of a non-blocking lock request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockStatus {
+    /// The lock is held
+    Granted,
+
+    /// The request is queued behind conflicting holders or waiters
+    Waiting,
+}
+
+/// Lock manager settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockManagerConfig {
+    /// How long `acquire` waits before giving up (default: 1s)
+    pub timeout: Duration,
+}
+
+impl Default for LockManagerConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct EntityLock {
+    holders: HashMap<TransactionId, LockMode>,
+    queue: VecDeque<(TransactionId, LockMode)>,
+}
+
+impl EntityLock {
+    /// Whether `txn` could hold `mode` given the current holders
+    fn compatible(&self, txn: TransactionId, mode: LockMode) -> bool {
+        self.holders
+            .iter()
+            .all(|(holder, held)| *holder == txn || !mode.conflicts_with(*held))
+    }
+
+    fn is_idle(&self) -> bool {
+        self.holders.is_empty() && self.queue.is_empty()
+    }
+}
+
+#[derive(Debug, Default)]
+struct LockTable {
+    locks: HashMap<EntityId, EntityLock>,
+    /// Entity each blocked transaction waits on
+    waiting: HashMap<TransactionId, EntityId>,
+    /// Entities each transaction holds
+    held: HashMap<TransactionId, HashSet<EntityId>>,
+    /// Deadlock victims that have not released yet, with their cycle size
+    victims: HashMap<TransactionId, usize>,
+}
+
+impl LockTable {
+    /// Transactions `txn` waits for
+    fn blockers(&self, txn: TransactionId) -> Vec<TransactionId> {
+        let Some(entity) = self.waiting.get(&txn) else {
+            return Vec::new();
+        };
+        let lock = &self.locks[entity];
+        let Some(pos) = lock.queue.iter().position(|(t, _)| *t == txn) else {
+            return Vec::new();
+        };
+        let mode = lock.queue[pos].1;
+
+        let holders = lock
+            .holders
+            .iter()
+            .filter(|(holder, held)| **holder != txn && mode.conflicts_with(**held))
+            .map(|(holder, _)| *holder);
+        let ahead = lock
+            .queue
+            .iter()
+            .take(pos)
+            .filter(|(t, queued)| *t != txn && mode.conflicts_with(*queued))
+            .map(|(t, _)| *t);
+        holders.chain(ahead).collect()
+    }
+
+    /// Find a wait-for cycle through `start`
+    fn cycle_through(&self, start: TransactionId) -> Option<Vec<TransactionId>> {
+        let mut path = vec![start];
+        let mut visited = HashSet::from([start]);
+        let mut stack = vec![self.blockers(start).into_iter()];
+
+        while let Some(next) = stack.last_mut() {
+            match next.next() {
+                Some(txn) if txn == start => return Some(path),
+                Some(txn) => {
+                    if visited.insert(txn) {
+                        path.push(txn);
+                        stack.push(self.blockers(txn).into_iter());
+                    }
+                }
+                None => {
+                    stack.pop();
+                    path.pop();
+                }
+            }
+        }
+        None
+    }
+
+    /// Grant queued requests on `entity` in order until one must keep waiting
+    fn grant_queued(&mut self, entity: EntityId) {
+        let Some(lock) = self.locks.get_mut(&entity) else {
+            return;
+        };
+        while let Some(&(txn, mode)) = lock.queue.front() {
+            if !lock.compatible(txn, mode) {
+                break;
+            }
+            lock.queue.pop_front();
+            self.waiting.remove(&txn);
+            self.held.entry(txn).or_default().insert(entity);
+            let held = lock.holders.entry(txn).or_insert(mode);
+            if mode == LockMode::Exclusive {
+                *held = LockMode::Exclusive;
+            }
+        }
+        if lock.is_idle() {
+            self.locks.remove(&entity);
+        }
+    }
+
+    /// Withdraw the queued request of `txn`, keeping the locks it holds
+    fn withdraw(&mut self, txn: TransactionId) {
+        if let Some(entity) = self.waiting.remove(&txn) {
+            if let Some(lock) = self.locks.get_mut(&entity) {
+                lock.queue.retain(|(t, _)| *t != txn);
+            }
+            self.grant_queued(entity);
+        }
+    }
+
+    /// Drop every lock and queued request of `txn`, then grant what became
+    /// compatible
+    fn release(&mut self, txn: TransactionId) {
+        self.withdraw(txn);
+        for entity in self.held.remove(&txn).into_iter().flatten() {
+            if let Some(lock) = self.locks.get_mut(&entity) {
+                lock.holders.remove(&txn);
+            }
+            self.grant_queued(entity);
+        }
+    }
+
+    fn check_victim(&self, txn: TransactionId) -> ConcurrencyResult<()> {
+        match self.victims.get(&txn) {
+            Some(&transactions) => Err(ConcurrencyError::DeadlockDetected { transactions }),
+            None => Ok(()),
+        }
+    }
+
+    fn status(&self, txn: TransactionId) -> LockStatus {
+        if self.waiting.contains_key(&txn) {
+            LockStatus::Waiting
+        } else {
+            LockStatus::Granted
+        }
+    }
+}
+
+/// Per-entity shared/exclusive lock table with deadlock detection
+#[derive(Debug, Default)]
+pub struct LockManager {
+    table: Mutex<LockTable>,
+    released: Condvar,
+    config: LockManagerConfig,
+}
+
+impl LockManager {
+    /// Create a lock manager with default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a lock manager with custom settings
+    pub fn with_config(config: LockManagerConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Request a lock without blocking
+    ///
+    /// Re-requesting a held mode is a no-op; requesting `Exclusive` while
+    /// holding `Shared` is an upgrade. A transaction can wait on one entity at
+    /// a time.
+    ///
+    /// # Errors
+    /// * `DeadlockDetected` if this request closes a wait-for cycle and the
+    ///   requester is the youngest transaction in it, or if the requester was
+    ///   already chosen as a victim; call `release_all` before reusing it
+    /// * `TransactionConflict` if the transaction is already waiting
+    pub fn request(&self, txn: TransactionId, entity: EntityId, mode: LockMode) -> ConcurrencyResult<LockStatus> {
+        let mut table = self.table.lock();
+        table.check_victim(txn)?;
+        if let Some(waiting_on) = table.waiting.get(&txn) {
+            return Err(ConcurrencyError::TransactionConflict {
+                reason: format!("transaction {} is already waiting on {}", txn, waiting_on),
+            });
+        }
+
+        let lock = table.locks.entry(entity).or_default();
+        let held = lock.holders.get(&txn).copied();
+        if held == Some(LockMode::Exclusive) || (held.is_some() && mode == LockMode::Shared) {
+            return Ok(LockStatus::Granted);
+        }
+
+        // Grant immediately only if compatible and nobody is queued ahead,
+        // except for upgrades which would otherwise deadlock behind waiters
+        let upgrade = held.is_some();
+        if lock.compatible(txn, mode) && (upgrade || lock.queue.is_empty()) {
+            lock.holders.insert(txn, mode);
+            table.held.entry(txn).or_default().insert(entity);
+            return Ok(LockStatus::Granted);
+        }
+
+        if upgrade {
+            lock.queue.push_front((txn, mode));
+        } else {
+            lock.queue.push_back((txn, mode));
+        }
+        table.waiting.insert(txn, entity);
+
+        if let Some(cycle) = table.cycle_through(txn) {
+            let victim = *cycle.iter().max().expect("cycle is non-empty");
+            tracing::warn!(?cycle, victim, "deadlock detected, aborting youngest transaction");
+            table.release(victim);
+            table.victims.insert(victim, cycle.len());
+            self.released.notify_all();
+            if victim == txn {
+                return Err(ConcurrencyError::DeadlockDetected {
+                    transactions: cycle.len(),
+                });
+            }
+        }
+        Ok(table.status(txn))
+    }
+
+    /// Check whether a waiting request has been granted
+    ///
+    /// # Errors
+    /// * `DeadlockDetected` if the transaction was aborted as a victim
+    pub fn poll(&self, txn: TransactionId) -> ConcurrencyResult<LockStatus> {
+        let table = self.table.lock();
+        table.check_victim(txn)?;
+        Ok(table.status(txn))
+    }
+
+    /// Acquire a lock, blocking up to the configured timeout
+    ///
+    /// # Errors
+    /// * `DeadlockDetected` if the transaction is chosen as a deadlock victim
+    /// * `LockAcquisitionFailed` on timeout; the queued request is withdrawn
+    ///   but locks already held are kept
+    pub fn acquire(&self, txn: TransactionId, entity: EntityId, mode: LockMode) -> ConcurrencyResult<()> {
+        if self.request(txn, entity, mode)? == LockStatus::Granted {
+            return Ok(());
+        }
+
+        let deadline = Instant::now() + self.config.timeout;
+        let mut attempts = 1;
+        let mut table = self.table.lock();
+        loop {
+            table.check_victim(txn)?;
+            if table.status(txn) == LockStatus::Granted {
+                return Ok(());
+            }
+            if self.released.wait_until(&mut table, deadline).timed_out() {
+                table.check_victim(txn)?;
+                if table.status(txn) == LockStatus::Granted {
+                    return Ok(());
+                }
+                table.withdraw(txn);
+                self.released.notify_all();
+                return Err(ConcurrencyError::LockAcquisitionFailed { attempts });
+            }
+            attempts += 1;
+        }
+    }
+
+    /// Release every lock and pending request of a transaction
+    ///
+    /// Must be called when a transaction commits or aborts, including after
+    /// it was chosen as a deadlock victim.
+    pub fn release_all(&self, txn: TransactionId) {
+        let mut table = self.table.lock();
+        table.release(txn);
+        table.victims.remove(&txn);
+        self.released.notify_all();
+    }
+
+    /// Mode `txn` holds on `entity`, if any
+    pub fn held_mode(&self, txn: TransactionId, entity: &EntityId) -> Option<LockMode> {
+        self.table.lock().locks.get(entity)?.holders.get(&txn).copied()
+    }
+
+    /// Current wait-for edges as (waiter, blocker) pairs
+    pub fn wait_for_edges(&self) -> Vec<(TransactionId, TransactionId)> {
+        let table = self.table.lock();
+        let mut edges: Vec<_> = table
+            .waiting
+            .keys()
+            .flat_map(|waiter| table.blockers(*waiter).into_iter().map(move |b| (*waiter, b)))
+            .collect();
+        edges.sort_unstable();
+        edges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn entities<const N: usize>() -> [EntityId; N] {
+        std::array::from_fn(|_| EntityId::new())
+    }
+
+    fn is_deadlock<T>(result: ConcurrencyResult<T>) -> bool {
+        matches!(result, Err(ConcurrencyError::DeadlockDetected { .. }))
+    }
+
+    #[test]
+    fn test_shared_and_exclusive_compatibility() {
+        let locks = LockManager::new();
+        let [a] = entities();
+
+        assert_eq!(locks.request(1, a, LockMode::Shared).unwrap(), LockStatus::Granted);
+        assert_eq!(locks.request(2, a, LockMode::Shared).unwrap(), LockStatus::Granted);
+        assert_eq!(locks.request(3, a, LockMode::Exclusive).unwrap(), LockStatus::Waiting);
+        // FIFO: a later shared request queues behind the exclusive waiter
+        assert_eq!(locks.request(4, a, LockMode::Shared).unwrap(), LockStatus::Waiting);
+        assert_eq!(locks.wait_for_edges(), vec![(3, 1), (3, 2), (4, 3)]);
+
+        locks.release_all(1);
+        assert_eq!(locks.poll(3).unwrap(), LockStatus::Waiting);
+        locks.release_all(2);
+        assert_eq!(locks.poll(3).unwrap(), LockStatus::Granted);
+        assert_eq!(locks.poll(4).unwrap(), LockStatus::Waiting);
+        locks.release_all(3);
+        assert_eq!(locks.poll(4).unwrap(), LockStatus::Granted);
+        assert_eq!(locks.held_mode(4, &a), Some(LockMode::Shared));
+    }
+
+    #[test]
+    fn test_upgrade_jumps_queue() {
+        let locks = LockManager::new();
+        let [a] = entities();
+
+        locks.request(1, a, LockMode::Shared).unwrap();
+        locks.request(2, a, LockMode::Shared).unwrap();
+        assert_eq!(locks.request(3, a, LockMode::Exclusive).unwrap(), LockStatus::Waiting);
+        assert_eq!(locks.request(1, a, LockMode::Exclusive).unwrap(), LockStatus::Waiting);
+
+        locks.release_all(2);
+        assert_eq!(locks.poll(1).unwrap(), LockStatus::Granted);
+        assert_eq!(locks.held_mode(1, &a), Some(LockMode::Exclusive));
+        assert_eq!(locks.poll(3).unwrap(), LockStatus::Waiting);
+    }
+
+    #[test]
+    fn test_two_way_deadlock_aborts_youngest() {
+        let locks = LockManager::new();
+        let [a, b] = entities();
+
+        locks.request(1, a, LockMode::Exclusive).unwrap();
+        locks.request(2, b, LockMode::Exclusive).unwrap();
+        assert_eq!(locks.request(1, b, LockMode::Exclusive).unwrap(), LockStatus::Waiting);
+
+        // Transaction 2 closes the cycle and is the youngest
+        assert!(is_deadlock(locks.request(2, a, LockMode::Exclusive)));
+        assert_eq!(locks.poll(1).unwrap(), LockStatus::Granted);
+        assert!(is_deadlock(locks.poll(2)));
+        assert!(is_deadlock(locks.request(2, a, LockMode::Shared)));
+
+        locks.release_all(2);
+        assert_eq!(locks.request(2, a, LockMode::Shared).unwrap(), LockStatus::Waiting);
+    }
+
+    #[test]
+    fn test_cycle_victim_other_than_requester() {
+        let locks = LockManager::new();
+        let [a, b, c] = entities();
+
+        locks.request(1, a, LockMode::Exclusive).unwrap();
+        locks.request(2, b, LockMode::Exclusive).unwrap();
+        locks.request(3, c, LockMode::Exclusive).unwrap();
+        locks.request(3, a, LockMode::Exclusive).unwrap();
+        locks.request(2, c, LockMode::Exclusive).unwrap();
+
+        // 1 -> 2 -> 3 -> 1: the requester (1) survives, 3 is aborted
+        assert_eq!(locks.request(1, b, LockMode::Exclusive).unwrap(), LockStatus::Waiting);
+        assert!(is_deadlock(locks.poll(3)));
+        assert_eq!(locks.poll(2).unwrap(), LockStatus::Granted);
+        assert_eq!(locks.wait_for_edges(), vec![(1, 2)]);
+
+        locks.release_all(2);
+        assert_eq!(locks.poll(1).unwrap(), LockStatus::Granted);
+    }
+
+    #[test]
+    fn test_shared_upgrade_deadlock() {
+        let locks = LockManager::new();
+        let [a] = entities();
+
+        locks.request(1, a, LockMode::Shared).unwrap();
+        locks.request(2, a, LockMode::Shared).unwrap();
+        assert_eq!(locks.request(1, a, LockMode::Exclusive).unwrap(), LockStatus::Waiting);
+        assert!(is_deadlock(locks.request(2, a, LockMode::Exclusive)));
+        assert_eq!(locks.poll(1).unwrap(), LockStatus::Granted);
+    }
+
+    #[test]
+    fn test_acquire_times_out_and_withdraws() {
+        let locks = LockManager::with_config(LockManagerConfig {
+            timeout: Duration::from_millis(20),
+        });
+        let [a, b] = entities();
+
+        locks.acquire(1, a, LockMode::Exclusive).unwrap();
+        locks.acquire(2, b, LockMode::Exclusive).unwrap();
+        assert!(matches!(
+            locks.acquire(2, a, LockMode::Shared),
+            Err(ConcurrencyError::LockAcquisitionFailed { .. })
+        ));
+        assert!(locks.wait_for_edges().is_empty());
+        assert_eq!(locks.held_mode(2, &b), Some(LockMode::Exclusive));
+
+        locks.release_all(1);
+        locks.acquire(2, a, LockMode::Shared).unwrap();
+    }
+
+    #[test]
+    fn test_acquire_wakes_on_release() {
+        let locks = Arc::new(LockManager::new());
+        let [a] = entities();
+        locks.acquire(1, a, LockMode::Exclusive).unwrap();
+
+        let waiter = {
+            let locks = Arc::clone(&locks);
+            std::thread::spawn(move || locks.acquire(2, a, LockMode::Exclusive))
+        };
+        while locks.wait_for_edges().is_empty() {
+            std::thread::yield_now();
+        }
+        locks.release_all(1);
+        waiter.join().unwrap().unwrap();
+        assert_eq!(locks.held_mode(2, &a), Some(LockMode::Exclusive));
+    }
+
+    #[test]
+    fn test_blocked_waiter_learns_it_was_victim() {
+        let locks = Arc::new(LockManager::new());
+        let [a, b] = entities();
+        locks.acquire(1, a, LockMode::Exclusive).unwrap();
+        locks.acquire(2, b, LockMode::Exclusive).unwrap();
+
+        // The younger transaction blocks first; the older one closes the cycle
+        let younger = {
+            let locks = Arc::clone(&locks);
+            std::thread::spawn(move || locks.acquire(2, a, LockMode::Exclusive))
+        };
+        while locks.wait_for_edges().is_empty() {
+            std::thread::yield_now();
+        }
+        locks.acquire(1, b, LockMode::Exclusive).unwrap();
+        assert!(is_deadlock(younger.join().unwrap()));
+    }
+}
@@ -1 +1,444 @@
-// TODO: Implement lock-free data structures
+//! Lock-free data structures for the hot path
+//!
+//! - `EntityIndex`: concurrent ordered map from `EntityId`, backed by an
+//!   epoch-reclaimed skiplist; readers and writers never block each other
+//! - `mpsc_queue`: unbounded multi-producer single-consumer queue that
+//!   carries access events into the `AccessTracker`; `push` is wait-free
+//!
+//! `EntityIndex` is linearizable: every operation takes effect atomically at
+//! a single point between its invocation and return. The queue is not: a
+//! `pop` may miss a value whose `push` is still in flight, although values
+//! from one producer always arrive in push order.
+
+use crate::core::EntityId;
+use crossbeam_skiplist::SkipMap;
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Concurrent map keyed by entity, iterated in `EntityId` order
+///
+/// Removed values are reclaimed once no thread can still observe them
+/// (epoch-based reclamation), so lookups never take locks.
+pub struct EntityIndex<V> {
+    map: SkipMap<EntityId, V>,
+}
+
+impl<V: Send + 'static> EntityIndex<V> {
+    /// Create an empty index
+    pub fn new() -> Self {
+        Self { map: SkipMap::new() }
+    }
+
+    /// Number of entries
+    ///
+    /// Exact when no writes are in flight, approximate otherwise.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Whether the index is empty
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Whether an entry exists
+    pub fn contains(&self, id: &EntityId) -> bool {
+        self.map.contains_key(id)
+    }
+
+    /// Insert or replace an entry
+    pub fn insert(&self, id: EntityId, value: V) {
+        self.map.insert(id, value);
+    }
+
+    /// Remove an entry, returning whether it existed
+    pub fn remove(&self, id: &EntityId) -> bool {
+        self.map.remove(id).is_some()
+    }
+
+    /// Apply `f` to an entry's value without copying it
+    pub fn get_with<R>(&self, id: &EntityId, f: impl FnOnce(&V) -> R) -> Option<R> {
+        self.map.get(id).map(|entry| f(entry.value()))
+    }
+
+    /// Apply `f` to the existing value, inserting `init()` first if absent
+    ///
+    /// When several threads race to insert, exactly one `init` result wins
+    /// and all of them see it.
+    pub fn get_or_insert_with<R>(&self, id: EntityId, init: impl FnOnce() -> V, f: impl FnOnce(&V) -> R) -> R {
+        f(self.map.get_or_insert_with(id, init).value())
+    }
+
+    /// Visit every entry in `EntityId` order
+    ///
+    /// Entries inserted or removed during the walk may or may not be visited.
+    pub fn for_each(&self, mut f: impl FnMut(&EntityId, &V)) {
+        for entry in self.map.iter() {
+            f(entry.key(), entry.value());
+        }
+    }
+}
+
+impl<V: Clone + Send + 'static> EntityIndex<V> {
+    /// Copy of an entry's value
+    pub fn get(&self, id: &EntityId) -> Option<V> {
+        self.get_with(id, V::clone)
+    }
+}
+
+impl<V: Send + 'static> Default for EntityIndex<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> fmt::Debug for EntityIndex<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EntityIndex")
+            .field("len", &self.map.len())
+            .finish()
+    }
+}
+
+/// Queue node; the consumer owns the current stub node at `tail`
+struct Node<T> {
+    next: AtomicPtr<Node<T>>,
+    value: Option<T>,
+}
+
+impl<T> Node<T> {
+    fn alloc(value: Option<T>) -> *mut Self {
+        Box::into_raw(Box::new(Self {
+            next: AtomicPtr::new(ptr::null_mut()),
+            value,
+        }))
+    }
+}
+
+/// Intrusive MPSC queue after Vyukov
+///
+/// Producers swap themselves in at `head` and then link the previous node;
+/// the consumer follows `next` pointers from `tail`.
+struct MpscQueue<T> {
+    head: AtomicPtr<Node<T>>,
+    tail: UnsafeCell<*mut Node<T>>,
+    len: AtomicUsize,
+}
+
+// Safety: values move between threads through the queue, so `T: Send` is
+// required; `tail` is only touched by the single `MpscConsumer`.
+unsafe impl<T: Send> Send for MpscQueue<T> {}
+unsafe impl<T: Send> Sync for MpscQueue<T> {}
+
+impl<T> Drop for MpscQueue<T> {
+    fn drop(&mut self) {
+        let mut node = *self.tail.get_mut();
+        while !node.is_null() {
+            // Safety: no producer or consumer is left, so every node from the
+            // tail onwards is exclusively owned here
+            let boxed = unsafe { Box::from_raw(node) };
+            node = boxed.next.load(Ordering::Relaxed);
+        }
+    }
+}
+
+/// Create an unbounded multi-producer single-consumer queue
+pub fn mpsc_queue<T: Send>() -> (MpscProducer<T>, MpscConsumer<T>) {
+    let stub = Node::alloc(None);
+    let queue = Arc::new(MpscQueue {
+        head: AtomicPtr::new(stub),
+        tail: UnsafeCell::new(stub),
+        len: AtomicUsize::new(0),
+    });
+    (
+        MpscProducer {
+            queue: Arc::clone(&queue),
+        },
+        MpscConsumer { queue },
+    )
+}
+
+/// Sending half of an MPSC queue; clone it for each producer
+pub struct MpscProducer<T> {
+    queue: Arc<MpscQueue<T>>,
+}
+
+impl<T: Send> MpscProducer<T> {
+    /// Enqueue a value (wait-free)
+    pub fn push(&self, value: T) {
+        let node = Node::alloc(Some(value));
+        self.queue.len.fetch_add(1, Ordering::Relaxed);
+        let prev = self.queue.head.swap(node, Ordering::AcqRel);
+        // Safety: `prev` stays allocated until the consumer moves past it,
+        // which requires the `next` link stored here
+        unsafe { (*prev).next.store(node, Ordering::Release) };
+    }
+
+    /// Approximate number of queued values
+    pub fn len(&self) -> usize {
+        self.queue.len.load(Ordering::Relaxed)
+    }
+
+    /// Whether the queue looks empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Clone for MpscProducer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: Arc::clone(&self.queue),
+        }
+    }
+}
+
+impl<T> fmt::Debug for MpscProducer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MpscProducer")
+            .field("len", &self.queue.len.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// Receiving half of an MPSC queue
+pub struct MpscConsumer<T> {
+    queue: Arc<MpscQueue<T>>,
+}
+
+impl<T: Send> MpscConsumer<T> {
+    /// Dequeue the oldest value
+    ///
+    /// Values from one producer are received in push order. May return `None`
+    /// while a concurrent `push` is between publishing its node and linking
+    /// it; the value becomes visible once that push returns.
+    pub fn pop(&mut self) -> Option<T> {
+        // Safety: `&mut self` makes this the only access to `tail`, and the
+        // node it points to is owned by the consumer
+        unsafe {
+            let tail = *self.queue.tail.get();
+            let next = (*tail).next.load(Ordering::Acquire);
+            if next.is_null() {
+                return None;
+            }
+            *self.queue.tail.get() = next;
+            let value = (*next).value.take();
+            drop(Box::from_raw(tail));
+            self.queue.len.fetch_sub(1, Ordering::Relaxed);
+            value
+        }
+    }
+
+    /// Dequeue up to `max` values into `out`, returning how many were moved
+    pub fn drain_into(&mut self, out: &mut Vec<T>, max: usize) -> usize {
+        let mut moved = 0;
+        while moved < max {
+            match self.pop() {
+                Some(value) => {
+                    out.push(value);
+                    moved += 1;
+                }
+                None => break,
+            }
+        }
+        moved
+    }
+
+    /// Approximate number of queued values
+    pub fn len(&self) -> usize {
+        self.queue.len.load(Ordering::Relaxed)
+    }
+
+    /// Whether the queue looks empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> fmt::Debug for MpscConsumer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MpscConsumer")
+            .field("len", &self.queue.len.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Barrier;
+
+    #[test]
+    fn test_entity_index_operations() {
+        let index = EntityIndex::new();
+        let (a, b) = (EntityId::new(), EntityId::new());
+
+        index.insert(a, 1u64);
+        index.insert(b, 2);
+        index.insert(a, 3);
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.get(&a), Some(3));
+        assert_eq!(index.get_with(&b, |v| v * 10), Some(20));
+        assert_eq!(index.get_or_insert_with(a, || 99, |v| *v), 3);
+
+        let mut seen = Vec::new();
+        index.for_each(|id, _| seen.push(*id));
+        assert_eq!(seen, vec![a, b]);
+
+        assert!(index.remove(&a));
+        assert!(!index.remove(&a));
+        assert!(!index.contains(&a));
+        assert_eq!(index.len(), 1);
+    }
+
+    #[test]
+    fn test_entity_index_stress_matches_sequential_model() {
+        // Each thread owns a key range and mirrors its writes in a local
+        // model; the final map must equal the union of the models
+        let index = Arc::new(EntityIndex::new());
+        let threads = 8;
+        let keys: Vec<Vec<EntityId>> = (0..threads).map(|_| (0..64).map(|_| EntityId::new()).collect()).collect();
+        let barrier = Arc::new(Barrier::new(threads));
+
+        let handles: Vec<_> = keys
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(t, keys)| {
+                let index = Arc::clone(&index);
+                let barrier = Arc::clone(&barrier);
+                std::thread::spawn(move || {
+                    let mut model = HashMap::new();
+                    barrier.wait();
+                    for step in 0..4000usize {
+                        let key = keys[(step * 7 + t) % keys.len()];
+                        if step % 3 == 0 {
+                            index.remove(&key);
+                            model.remove(&key);
+                        } else {
+                            index.insert(key, step);
+                            model.insert(key, step);
+                        }
+                        // A thread always reads its own latest write
+                        assert_eq!(index.get(&key), model.get(&key).copied());
+                    }
+                    model
+                })
+            })
+            .collect();
+
+        let mut expected = HashMap::new();
+        for handle in handles {
+            expected.extend(handle.join().unwrap());
+        }
+        assert_eq!(index.len(), expected.len());
+        for (key, value) in expected {
+            assert_eq!(index.get(&key), Some(value));
+        }
+    }
+
+    #[test]
+    fn test_get_or_insert_with_has_single_winner() {
+        let index = Arc::new(EntityIndex::new());
+        let key = EntityId::new();
+        let barrier = Arc::new(Barrier::new(8));
+
+        let handles: Vec<_> = (0..8usize)
+            .map(|t| {
+                let index = Arc::clone(&index);
+                let barrier = Arc::clone(&barrier);
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    index.get_or_insert_with(key, || t, |v| *v)
+                })
+            })
+            .collect();
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        assert!(results.iter().all(|r| *r == results[0]));
+        assert_eq!(index.get(&key), Some(results[0]));
+    }
+
+    #[test]
+    fn test_mpsc_fifo_single_producer() {
+        let (producer, mut consumer) = mpsc_queue();
+        assert!(consumer.pop().is_none());
+        for i in 0..5 {
+            producer.push(i);
+        }
+        assert_eq!(consumer.len(), 5);
+
+        let mut out = Vec::new();
+        assert_eq!(consumer.drain_into(&mut out, 3), 3);
+        assert_eq!(out, vec![0, 1, 2]);
+        assert_eq!(consumer.pop(), Some(3));
+        assert_eq!(consumer.pop(), Some(4));
+        assert!(consumer.pop().is_none());
+        assert!(producer.is_empty());
+    }
+
+    #[test]
+    fn test_mpsc_stress_preserves_per_producer_order() {
+        let producers = 4;
+        let per_producer = 20_000u64;
+        let (producer, mut consumer) = mpsc_queue::<(usize, u64)>();
+        let done = Arc::new(AtomicBool::new(false));
+
+        let handles: Vec<_> = (0..producers)
+            .map(|p| {
+                let producer = producer.clone();
+                std::thread::spawn(move || {
+                    for seq in 0..per_producer {
+                        producer.push((p, seq));
+                    }
+                })
+            })
+            .collect();
+        drop(producer);
+
+        let finisher = {
+            let done = Arc::clone(&done);
+            std::thread::spawn(move || {
+                for handle in handles {
+                    handle.join().unwrap();
+                }
+                done.store(true, Ordering::Release);
+            })
+        };
+
+        // Every value arrives exactly once and in order per producer
+        let mut next = vec![0u64; producers];
+        let mut received = 0;
+        loop {
+            match consumer.pop() {
+                Some((p, seq)) => {
+                    assert_eq!(seq, next[p]);
+                    next[p] += 1;
+                    received += 1;
+                }
+                None if done.load(Ordering::Acquire) && consumer.is_empty() => break,
+                None => std::thread::yield_now(),
+            }
+        }
+        finisher.join().unwrap();
+        assert_eq!(received, producers as u64 * per_producer);
+        assert!(next.iter().all(|n| *n == per_producer));
+    }
+
+    #[test]
+    fn test_mpsc_drops_unconsumed_values() {
+        let value = Arc::new(());
+        {
+            let (producer, _consumer) = mpsc_queue();
+            for _ in 0..10 {
+                producer.push(Arc::clone(&value));
+            }
+        }
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
@@ -1,6 +1,24 @@
 // Lock-free concurrency
 //
 // This module will be fully implemented in Phase 10.
-// Placeholder for now to allow compilation.
+// Implemented so far:
+// - MVCC engine: Multi-version entity store with snapshot reads and GC
+// - Transaction coordinator: Optimistic multi-entity transactions, WAL-logged
+// - Lock manager: Shared/exclusive entity locks with deadlock detection
+// - Atomic ops: AtomicF32 for lock-free probability updates
+// - Lock-free: Epoch-reclaimed entity index and MPSC event queue
+// - Access tracker: Sharded atomic access statistics with batched updates
 
-// Placeholder - will be implemented in Phase 10
+pub mod mvcc_engine;
+pub mod transaction_coordinator;
+pub mod lock_manager;
+pub mod atomic_ops;
+pub mod lockfree;
+pub mod access_tracker;
+
+pub use mvcc_engine::{MvccEngine, MvccStats, Snapshot};
+pub use transaction_coordinator::{Transaction, TransactionCoordinator};
+pub use lock_manager::{LockManager, LockManagerConfig, LockMode, LockStatus};
+pub use atomic_ops::AtomicF32;
+pub use lockfree::{mpsc_queue, EntityIndex, MpscConsumer, MpscProducer};
+pub use access_tracker::{AccessBatch, AccessTracker};
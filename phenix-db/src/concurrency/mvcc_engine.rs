@@ -1 +1,619 @@
-// TODO: Implement MVCC engine
+//! MVCC engine
+//!
+//! Multi-version entity store with snapshot reads. Writers never overwrite:
+//! each commit appends versions stamped with the next timestamp of a global
+//! logical clock. Readers pin a `Snapshot` and see exactly the versions
+//! committed at or before it, no matter what commits afterwards.
+//!
+//! Results taken from vector and graph indexes are resolved through a
+//! snapshot (`resolve_hits`, `neighbors`): every entity, distance and edge
+//! returned comes from the one version visible to that snapshot, never from a
+//! mix of versions. Indexes that keep superseded vectors retire them once
+//! `live_snapshots` no longer includes a timestamp that can see them.
+//!
+//! Versions that neither a live snapshot nor a future one can observe are
+//! reclaimed by `gc`, or by `prune` for just the entities a writer touched.
+//!
+//! Every committed entity carries its commit timestamp in
+//! `Entity::commit_ts`; `Entity::version` is left to the caller.
+
+use crate::core::edges::Edge;
+use crate::core::error::{ConcurrencyError, ConcurrencyResult, Result};
+use crate::core::mvcc::{MvccWrite, Timestamp, VersionChain};
+use crate::core::{DistanceMetric, Entity, EntityId, Vector};
+use crate::index::simd::SearchHit;
+use parking_lot::{Mutex, RwLock};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Live snapshot timestamps with reference counts
+type SnapshotRegistry = Arc<Mutex<BTreeMap<Timestamp, usize>>>;
+
+/// Consistent read view pinned at a commit timestamp
+///
+/// While a snapshot (or any clone of it) is alive, the versions it can see
+/// are protected from garbage collection.
+#[derive(Debug)]
+pub struct Snapshot {
+    ts: Timestamp,
+    registry: SnapshotRegistry,
+}
+
+impl Snapshot {
+    /// Commit timestamp the snapshot reads at
+    pub fn timestamp(&self) -> Timestamp {
+        self.ts
+    }
+}
+
+impl Clone for Snapshot {
+    fn clone(&self) -> Self {
+        *self.registry.lock().entry(self.ts).or_insert(0) += 1;
+        Self {
+            ts: self.ts,
+            registry: Arc::clone(&self.registry),
+        }
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        let mut registry = self.registry.lock();
+        if let Some(count) = registry.get_mut(&self.ts) {
+            *count -= 1;
+            if *count == 0 {
+                registry.remove(&self.ts);
+            }
+        }
+    }
+}
+
+/// Counters reported by the engine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MvccStats {
+    /// Entities with at least one retained version
+    pub entities: usize,
+
+    /// Retained versions, tombstones included
+    pub versions: usize,
+
+    /// Live snapshots
+    pub active_snapshots: usize,
+
+    /// Latest commit timestamp
+    pub last_commit: Timestamp,
+}
+
+/// Multi-version entity store
+#[derive(Debug, Default)]
+pub struct MvccEngine {
+    chains: RwLock<HashMap<EntityId, VersionChain>>,
+    clock: AtomicU64,
+    snapshots: SnapshotRegistry,
+    /// Entities whose latest version is not a tombstone
+    live: AtomicUsize,
+}
+
+impl MvccEngine {
+    /// Create an empty engine
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Latest commit timestamp
+    pub fn last_commit(&self) -> Timestamp {
+        self.clock.load(Ordering::Acquire)
+    }
+
+    /// Number of entities as of the latest commit
+    pub fn len(&self) -> usize {
+        self.live.load(Ordering::Acquire)
+    }
+
+    /// Whether no entity exists as of the latest commit
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Pin a snapshot at the latest commit
+    pub fn snapshot(&self) -> Snapshot {
+        // Registering under the registry lock orders it against `gc`, which
+        // could otherwise prune the versions at `ts` in between
+        let mut registry = self.snapshots.lock();
+        let ts = self.last_commit();
+        *registry.entry(ts).or_insert(0) += 1;
+        Snapshot {
+            ts,
+            registry: Arc::clone(&self.snapshots),
+        }
+    }
+
+    /// Commit a batch of writes atomically under one timestamp
+    ///
+    /// Each written entity gets `commit_ts` set to the commit timestamp.
+    ///
+    /// # Arguments
+    /// * `read_snapshot` - Snapshot the writes were derived from; when given,
+    ///   the commit fails if any written entity has a version committed after
+    ///   it (first committer wins)
+    /// * `writes` - Writes to apply; later writes to the same entity win
+    ///
+    /// # Errors
+    /// * `SnapshotIsolationViolation` on a write-write conflict
+    pub fn commit(&self, read_snapshot: Option<&Snapshot>, writes: Vec<MvccWrite>) -> ConcurrencyResult<Timestamp> {
+        let mut chains = self.chains.write();
+
+        if let Some(snapshot) = read_snapshot {
+            for write in &writes {
+                let id = write.id();
+                if let Some(latest) = chains.get(&id).and_then(|c| c.latest()) {
+                    if latest.commit_ts > snapshot.ts {
+                        return Err(ConcurrencyError::SnapshotIsolationViolation {
+                            reason: format!(
+                                "entity {} was committed at {} after snapshot {}",
+                                id, latest.commit_ts, snapshot.ts
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(self.apply(&mut chains, writes))
+    }
+
+    /// Insert or replace an entity, returning its commit timestamp
+    pub fn put(&self, entity: Entity) -> Timestamp {
+        let mut chains = self.chains.write();
+        self.apply(&mut chains, vec![MvccWrite::Put(Box::new(entity))])
+    }
+
+    /// Apply a batch of writes under one timestamp without conflict checks
+    pub fn write(&self, writes: Vec<MvccWrite>) -> Timestamp {
+        let mut chains = self.chains.write();
+        self.apply(&mut chains, writes)
+    }
+
+    /// Replace an entity only if its latest version is `expected`
+    ///
+    /// Use `expected = 0` to require that the entity does not exist.
+    ///
+    /// # Errors
+    /// * `VersionConflict` if the latest version differs
+    pub fn compare_and_put(&self, entity: Entity, expected: Timestamp) -> ConcurrencyResult<Timestamp> {
+        let mut chains = self.chains.write();
+        let actual = latest_live(&chains, &entity.id);
+        if actual != expected {
+            return Err(ConcurrencyError::VersionConflict { expected, actual });
+        }
+        Ok(self.apply(&mut chains, vec![MvccWrite::Put(Box::new(entity))]))
+    }
+
+    /// Validate expected versions, make the batch durable, then apply it
+    ///
+    /// Validation, `durable` and the apply run under the commit lock, so the
+    /// order in which `durable` sees batches is the commit order.
+    ///
+    /// # Arguments
+    /// * `expected` - Latest version each entity must still have (0 for
+    ///   absent or deleted)
+    /// * `writes` - Writes to apply under one timestamp
+    /// * `durable` - Called with the validated batch before it is applied,
+    ///   typically to append it to a write-ahead log; an error aborts the
+    ///   commit with nothing applied
+    ///
+    /// # Errors
+    /// * `Concurrency(VersionConflict)` for the first mismatching entity
+    /// * Any error returned by `durable`
+    pub fn commit_validated<F>(&self, expected: &[(EntityId, Timestamp)], writes: Vec<MvccWrite>, durable: F) -> Result<Timestamp>
+    where
+        F: FnOnce(&[MvccWrite]) -> Result<()>,
+    {
+        let mut chains = self.chains.write();
+        for (id, expected) in expected {
+            let actual = latest_live(&chains, id);
+            if actual != *expected {
+                return Err(ConcurrencyError::VersionConflict {
+                    expected: *expected,
+                    actual,
+                }
+                .into());
+            }
+        }
+        durable(&writes)?;
+        Ok(self.apply(&mut chains, writes))
+    }
+
+    /// Version of an entity visible to a snapshot, or 0 if none
+    pub fn version_at(&self, snapshot: &Snapshot, id: &EntityId) -> Timestamp {
+        self.chains
+            .read()
+            .get(id)
+            .and_then(|c| c.visible_at(snapshot.ts))
+            .map_or(0, |v| v.commit_ts)
+    }
+
+    /// Latest committed version of an entity, or 0 if absent or deleted
+    pub fn latest_version(&self, id: &EntityId) -> Timestamp {
+        latest_live(&self.chains.read(), id)
+    }
+
+    /// Delete an entity, returning the commit timestamp
+    pub fn delete(&self, id: EntityId) -> Timestamp {
+        let mut chains = self.chains.write();
+        self.apply(&mut chains, vec![MvccWrite::Delete(id)])
+    }
+
+    /// Latest committed state of an entity, if it exists
+    pub fn latest(&self, id: &EntityId) -> Option<Arc<Entity>> {
+        self.chains.read().get(id)?.latest()?.entity.clone()
+    }
+
+    /// Read an entity as of a snapshot
+    pub fn get(&self, snapshot: &Snapshot, id: &EntityId) -> Option<Arc<Entity>> {
+        self.chains
+            .read()
+            .get(id)?
+            .visible_at(snapshot.ts)
+            .and_then(|v| v.entity.clone())
+    }
+
+    /// Whether an entity exists as of a snapshot
+    ///
+    /// Suitable as the predicate of `GraphIndex::search_filtered`.
+    pub fn is_visible(&self, snapshot: &Snapshot, id: &EntityId) -> bool {
+        self.chains
+            .read()
+            .get(id)
+            .is_some_and(|c| c.visible_at(snapshot.ts).is_some())
+    }
+
+    /// Every entity visible to a snapshot, in arbitrary order
+    pub fn scan(&self, snapshot: &Snapshot) -> Vec<Arc<Entity>> {
+        self.chains
+            .read()
+            .values()
+            .filter_map(|c| c.visible_at(snapshot.ts).and_then(|v| v.entity.clone()))
+            .collect()
+    }
+
+    /// Resolve index hits against a snapshot
+    ///
+    /// Hits whose entity is not visible, or whose visible version has no
+    /// vector, are dropped. Distances are recomputed from the visible version
+    /// so the score and the returned entity always belong together, and the
+    /// result is re-sorted by that distance.
+    pub fn resolve_hits(
+        &self,
+        snapshot: &Snapshot,
+        query: &Vector,
+        metric: DistanceMetric,
+        hits: &[SearchHit],
+    ) -> Vec<(SearchHit, Arc<Entity>)> {
+        let chains = self.chains.read();
+        let mut resolved: Vec<_> = hits
+            .iter()
+            .filter_map(|hit| {
+                let entity = chains.get(&hit.id)?.visible_at(snapshot.ts)?.entity.clone()?;
+                let vector = entity.vector.as_ref()?;
+                if vector.values.len() != query.values.len() {
+                    return None;
+                }
+                let distance = metric.distance(query, vector);
+                Some((SearchHit { id: hit.id, distance }, entity))
+            })
+            .collect();
+        resolved.sort_by_key(|(hit, _)| *hit);
+        resolved
+    }
+
+    /// Outgoing edges of an entity as of a snapshot
+    ///
+    /// Edges come from the visible version of the source, and edges to
+    /// targets that are not visible are omitted.
+    pub fn neighbors(&self, snapshot: &Snapshot, id: &EntityId) -> Vec<Edge> {
+        let chains = self.chains.read();
+        let Some(entity) = chains
+            .get(id)
+            .and_then(|c| c.visible_at(snapshot.ts))
+            .and_then(|v| v.entity.clone())
+        else {
+            return Vec::new();
+        };
+
+        entity
+            .edges
+            .iter()
+            .flatten()
+            .filter(|edge| {
+                chains
+                    .get(&edge.target_id)
+                    .is_some_and(|c| c.visible_at(snapshot.ts).is_some())
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Timestamps of the live snapshots, oldest first
+    pub fn live_snapshots(&self) -> Vec<Timestamp> {
+        self.snapshots.lock().keys().copied().collect()
+    }
+
+    /// Reclaim versions no live snapshot can observe
+    ///
+    /// # Returns
+    /// * Number of versions removed
+    pub fn gc(&self) -> usize {
+        let registry = self.snapshots.lock();
+        let live: Vec<Timestamp> = registry.keys().copied().collect();
+        let mut chains = self.chains.write();
+        drop(registry);
+
+        let mut removed = 0;
+        chains.retain(|_, chain| {
+            removed += chain.prune(&live);
+            !chain.is_empty()
+        });
+        if removed > 0 {
+            tracing::debug!(removed, live_snapshots = live.len(), "MVCC garbage collection");
+        }
+        removed
+    }
+
+    /// Reclaim versions of `ids` that no live snapshot can observe
+    ///
+    /// Cheaper than `gc` after a write, which only adds versions to the
+    /// entities it touched.
+    ///
+    /// # Returns
+    /// * Number of versions removed
+    pub fn prune(&self, ids: &[EntityId]) -> usize {
+        let registry = self.snapshots.lock();
+        let live: Vec<Timestamp> = registry.keys().copied().collect();
+        let mut chains = self.chains.write();
+        drop(registry);
+
+        let mut removed = 0;
+        for id in ids {
+            if let Some(chain) = chains.get_mut(id) {
+                removed += chain.prune(&live);
+                if chain.is_empty() {
+                    chains.remove(id);
+                }
+            }
+        }
+        removed
+    }
+
+    /// Append a batch under the next timestamp; the caller holds the write lock
+    fn apply(&self, chains: &mut HashMap<EntityId, VersionChain>, writes: Vec<MvccWrite>) -> Timestamp {
+        let ts = self.last_commit() + 1;
+        let mut batch: HashMap<EntityId, Option<Arc<Entity>>> = HashMap::with_capacity(writes.len());
+        for write in writes {
+            match write {
+                MvccWrite::Put(mut entity) => {
+                    entity.commit_ts = ts;
+                    batch.insert(entity.id, Some(Arc::new(*entity)));
+                }
+                MvccWrite::Delete(id) => {
+                    batch.insert(id, None);
+                }
+            }
+        }
+        for (id, entity) in batch {
+            let existed = chains.get(&id).is_some_and(|c| c.visible_at(ts).is_some());
+            // Deleting an entity that is not visible is a no-op
+            if entity.is_none() && !existed {
+                continue;
+            }
+            match (existed, entity.is_some()) {
+                (false, true) => {
+                    self.live.fetch_add(1, Ordering::AcqRel);
+                }
+                (true, false) => {
+                    self.live.fetch_sub(1, Ordering::AcqRel);
+                }
+                _ => {}
+            }
+            chains.entry(id).or_default().push(ts, entity);
+        }
+
+        // Publishing the timestamp last keeps the batch invisible until complete
+        self.clock.store(ts, Ordering::Release);
+        ts
+    }
+
+    /// Current counters
+    pub fn stats(&self) -> MvccStats {
+        // Registry before chains, the same order as `gc`
+        let active_snapshots = self.snapshots.lock().values().sum();
+        let chains = self.chains.read();
+        MvccStats {
+            entities: chains.len(),
+            versions: chains.values().map(VersionChain::len).sum(),
+            active_snapshots,
+            last_commit: self.last_commit(),
+        }
+    }
+}
+
+fn latest_live(chains: &HashMap<EntityId, VersionChain>, id: &EntityId) -> Timestamp {
+    chains
+        .get(id)
+        .and_then(|c| c.latest())
+        .filter(|v| !v.is_tombstone())
+        .map_or(0, |v| v.commit_ts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::error::ConcurrencyError;
+
+    fn entity(values: Vec<f32>) -> Entity {
+        Entity::new(Some(Vector::new(values)), None, None)
+    }
+
+    fn first_value(entity: &Entity) -> f32 {
+        entity.vector.as_ref().unwrap().values[0]
+    }
+
+    #[test]
+    fn test_snapshot_reads_are_stable() {
+        let engine = MvccEngine::new();
+        let mut e = entity(vec![1.0, 0.0]);
+        let id = e.id;
+        let v1 = engine.put(e.clone());
+
+        let before = engine.snapshot();
+        e.vector = Some(Vector::new(vec![2.0, 0.0]));
+        let v2 = engine.put(e);
+        assert!(v2 > v1);
+
+        let after = engine.snapshot();
+        assert_eq!(engine.get(&before, &id).unwrap().commit_ts, v1);
+        assert_eq!(first_value(&engine.get(&before, &id).unwrap()), 1.0);
+        assert_eq!(engine.get(&after, &id).unwrap().commit_ts, v2);
+
+        engine.delete(id);
+        assert!(engine.is_visible(&after, &id));
+        assert!(!engine.is_visible(&engine.snapshot(), &id));
+        assert_eq!(engine.scan(&before).len(), 1);
+    }
+
+    #[test]
+    fn test_batch_commit_is_atomic() {
+        let engine = MvccEngine::new();
+        let a = entity(vec![1.0]);
+        let b = entity(vec![2.0]);
+        let empty = engine.snapshot();
+
+        let ts = engine
+            .commit(None, vec![MvccWrite::Put(Box::new(a.clone())), MvccWrite::Put(Box::new(b.clone()))])
+            .unwrap();
+        let snapshot = engine.snapshot();
+        assert_eq!(snapshot.timestamp(), ts);
+        assert_eq!(engine.get(&snapshot, &a.id).unwrap().commit_ts, ts);
+        assert_eq!(engine.get(&snapshot, &b.id).unwrap().commit_ts, ts);
+        assert!(engine.scan(&empty).is_empty());
+    }
+
+    #[test]
+    fn test_write_conflicts() {
+        let engine = MvccEngine::new();
+        let e = entity(vec![1.0]);
+        let v1 = engine.put(e.clone());
+
+        let reader = engine.snapshot();
+        engine.put(e.clone());
+        assert!(matches!(
+            engine.commit(Some(&reader), vec![MvccWrite::Put(Box::new(e.clone()))]),
+            Err(ConcurrencyError::SnapshotIsolationViolation { .. })
+        ));
+
+        assert!(matches!(
+            engine.compare_and_put(e.clone(), v1),
+            Err(ConcurrencyError::VersionConflict { expected, actual }) if expected == v1 && actual == v1 + 1
+        ));
+        assert!(engine.compare_and_put(e, v1 + 1).is_ok());
+        assert!(engine.compare_and_put(entity(vec![3.0]), 0).is_ok());
+    }
+
+    #[test]
+    fn test_gc_respects_live_snapshots() {
+        let engine = MvccEngine::new();
+        let mut e = entity(vec![1.0]);
+        let id = e.id;
+        engine.put(e.clone());
+
+        let pinned = engine.snapshot();
+        for i in 2..=5 {
+            e.vector = Some(Vector::new(vec![i as f32]));
+            engine.put(e.clone());
+        }
+        assert_eq!(engine.stats().versions, 5);
+
+        // Versions 2-4 are invisible to every snapshot
+        assert_eq!(engine.gc(), 3);
+        assert_eq!(first_value(&engine.get(&pinned, &id).unwrap()), 1.0);
+
+        let copy = pinned.clone();
+        drop(pinned);
+        assert_eq!(engine.gc(), 0);
+        drop(copy);
+        assert_eq!(engine.gc(), 1);
+        assert_eq!(engine.stats().active_snapshots, 0);
+
+        engine.delete(id);
+        assert_eq!(engine.gc(), 2);
+        assert_eq!(engine.stats().entities, 0);
+    }
+
+    #[test]
+    fn test_resolve_hits_and_neighbors_use_one_version() {
+        let engine = MvccEngine::new();
+        let mut a = entity(vec![1.0, 0.0]);
+        let b = entity(vec![0.0, 1.0]);
+        a.edges = Some(vec![Edge::new(a.id, b.id, "links".to_string(), 1.0, None)]);
+        engine.put(b.clone());
+        engine.put(a.clone());
+        let snapshot = engine.snapshot();
+
+        // Later writes move `a` and drop `b`
+        a.vector = Some(Vector::new(vec![-1.0, 0.0]));
+        engine.put(a.clone());
+        engine.delete(b.id);
+
+        let query = Vector::new(vec![1.0, 0.0]);
+        let stale_hits = vec![
+            SearchHit { id: b.id, distance: 0.0 },
+            SearchHit { id: a.id, distance: 2.0 },
+        ];
+        let resolved = engine.resolve_hits(&snapshot, &query, DistanceMetric::Euclidean, &stale_hits);
+        assert_eq!(resolved[0].0.id, a.id);
+        assert!(resolved[0].0.distance.abs() < 1e-6);
+        assert_eq!(first_value(&resolved[0].1), 1.0);
+        assert_eq!(resolved[1].0.id, b.id);
+
+        assert_eq!(engine.neighbors(&snapshot, &a.id).len(), 1);
+        let latest = engine.snapshot();
+        assert!(engine.neighbors(&latest, &a.id).is_empty());
+        assert_eq!(
+            engine.resolve_hits(&latest, &query, DistanceMetric::Euclidean, &stale_hits).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_concurrent_readers_see_consistent_batches() {
+        let engine = Arc::new(MvccEngine::new());
+        let a = entity(vec![0.0]);
+        let b = entity(vec![0.0]);
+        engine.commit(None, vec![MvccWrite::Put(Box::new(a.clone())), MvccWrite::Put(Box::new(b.clone()))]).unwrap();
+
+        let writer = {
+            let engine = Arc::clone(&engine);
+            let (mut a, mut b) = (a.clone(), b.clone());
+            std::thread::spawn(move || {
+                for i in 1..200 {
+                    a.vector = Some(Vector::new(vec![i as f32]));
+                    b.vector = Some(Vector::new(vec![i as f32]));
+                    engine
+                        .commit(None, vec![MvccWrite::Put(Box::new(a.clone())), MvccWrite::Put(Box::new(b.clone()))])
+                        .unwrap();
+                }
+            })
+        };
+
+        for _ in 0..200 {
+            let snapshot = engine.snapshot();
+            let x = engine.get(&snapshot, &a.id).unwrap();
+            let y = engine.get(&snapshot, &b.id).unwrap();
+            assert_eq!(x.commit_ts, y.commit_ts);
+            assert_eq!(first_value(&x), first_value(&y));
+            engine.gc();
+        }
+        writer.join().unwrap();
+    }
+}
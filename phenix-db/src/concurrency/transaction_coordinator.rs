@@ -1 +1,804 @@
-// TODO: Implement transaction coordinator
+//! Transaction coordinator
+//!
+//! Optimistic multi-entity transactions on top of the MVCC engine. A
+//! transaction reads from its snapshot and buffers writes; commit validates
+//! that every entity it read or wrote is still at the version it observed
+//! and applies the whole write set under a single commit timestamp.
+//!
+//! The coordinator itself keeps nothing on disk, so its commit methods are
+//! crate-private: transactions on a collection are committed through
+//! `Catalog::commit_transaction` or retried with `Catalog::run_transaction`,
+//! which hand the validated write set to `commit_with` and append it to the
+//! catalog's write-ahead log as one record before applying it. A crash
+//! mid-commit leaves either the complete record or a torn tail, never a
+//! partial transaction.
+//!
+//! With a `LockManager` attached the coordinator also supports pessimistic
+//! locking: `Transaction::lock` and `get_for_update` take entity locks that
+//! are held until commit or rollback, so contended entities are serialized
+//! instead of repeatedly failing validation. A collection configured with
+//! `ConcurrencyMode::Pessimistic` attaches one lock manager that all of its
+//! transactions share.
+//!
+//! Transaction ids come from one process-wide counter, so they are unique
+//! across coordinators and follow begin order across them, which the lock
+//! manager relies on to pick the youngest transaction of a deadlock.
+
+use crate::concurrency::lock_manager::{LockManager, LockMode};
+use crate::concurrency::mvcc_engine::{MvccEngine, Snapshot};
+use crate::core::edges::Edge;
+use crate::core::error::{ConcurrencyError, GraphError, MemorySubstrateError, Result};
+use crate::core::mvcc::{MvccWrite, Timestamp};
+use crate::core::transaction::{TransactionId, TransactionOptions, TransactionState};
+use crate::core::{Entity, EntityId};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Id the next transaction begun by any coordinator receives
+static NEXT_TRANSACTION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Buffered read-write transaction
+///
+/// Obtained from `TransactionCoordinator::begin`; nothing is visible to other
+/// readers until it is committed.
+#[derive(Debug)]
+pub struct Transaction {
+    id: TransactionId,
+    engine: Arc<MvccEngine>,
+    locks: Option<Arc<LockManager>>,
+    snapshot: Snapshot,
+    state: TransactionState,
+    /// Commit timestamp each entity must still have at commit (0 = absent)
+    expected: HashMap<EntityId, Timestamp>,
+    writes: HashMap<EntityId, MvccWrite>,
+    /// Expected and actual version of the first mismatched compare-and-set,
+    /// which makes the commit fail
+    mismatch: Option<(u64, u64)>,
+}
+
+impl Transaction {
+    /// Transaction identifier
+    pub fn id(&self) -> TransactionId {
+        self.id
+    }
+
+    /// Current lifecycle state
+    pub fn state(&self) -> TransactionState {
+        self.state
+    }
+
+    /// Snapshot the transaction reads from
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    /// Number of buffered writes
+    pub fn write_count(&self) -> usize {
+        self.writes.len()
+    }
+
+    /// Buffered writes, in no particular order
+    pub fn writes(&self) -> impl Iterator<Item = &MvccWrite> {
+        self.writes.values()
+    }
+
+    /// Read an entity, seeing the transaction's own writes
+    pub fn get(&mut self, id: &EntityId) -> Option<Entity> {
+        match self.writes.get(id) {
+            Some(MvccWrite::Put(entity)) => return Some((**entity).clone()),
+            Some(MvccWrite::Delete(_)) => return None,
+            None => {}
+        }
+        self.observe(id);
+        self.engine.get(&self.snapshot, id).map(|e| (*e).clone())
+    }
+
+    /// Insert or replace an entity
+    ///
+    /// # Errors
+    /// * `TransactionConflict` if the transaction is no longer active
+    pub fn put(&mut self, entity: Entity) -> Result<()> {
+        self.ensure_active()?;
+        self.observe(&entity.id);
+        self.writes.insert(entity.id, MvccWrite::Put(Box::new(entity)));
+        Ok(())
+    }
+
+    /// Replace an entity only if its latest committed `Entity::version` is
+    /// `expected`
+    ///
+    /// Use `expected = 0` to require that the entity does not exist. The
+    /// version is compared against the latest commit, not the snapshot, so
+    /// a version read from a plain GET works. Any commit to the entity
+    /// after the comparison aborts the commit with `TransactionConflict`.
+    ///
+    /// # Errors
+    /// * `TransactionConflict` if the transaction is no longer active
+    /// * `VersionConflict` if the entity is at another version; the write is
+    ///   not buffered and the transaction can no longer commit. Retrying
+    ///   cannot help, so `run_with` returns this error instead of retrying.
+    pub fn put_if_version(&mut self, entity: Entity, expected: u64) -> Result<()> {
+        self.ensure_active()?;
+        let latest = self.engine.snapshot();
+        let actual = self.engine.get(&latest, &entity.id).map_or(0, |current| current.version);
+        if actual != expected {
+            self.mismatch.get_or_insert((expected, actual));
+            return Err(ConcurrencyError::VersionConflict { expected, actual }.into());
+        }
+        self.expected.insert(entity.id, self.engine.version_at(&latest, &entity.id));
+        self.writes.insert(entity.id, MvccWrite::Put(Box::new(entity)));
+        Ok(())
+    }
+
+    /// Delete an entity
+    ///
+    /// # Errors
+    /// * `TransactionConflict` if the transaction is no longer active
+    pub fn delete(&mut self, id: EntityId) -> Result<()> {
+        self.ensure_active()?;
+        self.observe(&id);
+        self.writes.insert(id, MvccWrite::Delete(id));
+        Ok(())
+    }
+
+    /// Insert or replace an edge on its source entity
+    ///
+    /// An existing edge with the same target and label is replaced.
+    ///
+    /// # Errors
+    /// * `Graph(EndpointNotFound)` if the source or target does not exist
+    pub fn put_edge(&mut self, edge: Edge) -> Result<()> {
+        if self.get(&edge.target_id).is_none() {
+            return Err(endpoint_not_found(&edge.target_id));
+        }
+        let mut source = self
+            .get(&edge.source_id)
+            .ok_or_else(|| endpoint_not_found(&edge.source_id))?;
+
+        let edges = source.edges.get_or_insert_with(Vec::new);
+        edges.retain(|e| !(e.target_id == edge.target_id && e.label == edge.label));
+        edges.push(edge);
+        self.put(source)?;
+        Ok(())
+    }
+
+    /// Remove an edge, returning whether it existed
+    ///
+    /// # Errors
+    /// * `Graph(EndpointNotFound)` if the source does not exist
+    pub fn remove_edge(&mut self, source_id: &EntityId, target_id: &EntityId, label: &str) -> Result<bool> {
+        let mut source = self
+            .get(source_id)
+            .ok_or_else(|| endpoint_not_found(source_id))?;
+        let Some(edges) = source.edges.as_mut() else {
+            return Ok(false);
+        };
+
+        let before = edges.len();
+        edges.retain(|e| !(e.target_id == *target_id && e.label == label));
+        if edges.len() == before {
+            return Ok(false);
+        }
+        self.put(source)?;
+        Ok(true)
+    }
+
+    /// Lock an entity until commit or rollback
+    ///
+    /// A no-op when the coordinator has no lock manager.
+    ///
+    /// # Errors
+    /// * `DeadlockDetected` if the transaction is aborted to break a deadlock
+    /// * `LockAcquisitionFailed` if the lock is not granted in time
+    pub fn lock(&mut self, id: &EntityId, mode: LockMode) -> Result<()> {
+        self.ensure_active()?;
+        if let Some(locks) = &self.locks {
+            locks.acquire(self.id, *id, mode)?;
+        }
+        Ok(())
+    }
+
+    /// Exclusively lock an entity and read its latest committed version
+    ///
+    /// Unlike `get`, the read is not limited to the snapshot: once the lock
+    /// is held no other locking transaction can change the entity, so reading
+    /// the latest version lets the commit validate instead of conflicting.
+    pub fn get_for_update(&mut self, id: &EntityId) -> Result<Option<Entity>> {
+        self.lock(id, LockMode::Exclusive)?;
+        match self.writes.get(id) {
+            Some(MvccWrite::Put(entity)) => return Ok(Some((**entity).clone())),
+            Some(MvccWrite::Delete(_)) => return Ok(None),
+            None => {}
+        }
+        let latest = self.engine.snapshot();
+        self.expected.insert(*id, self.engine.version_at(&latest, id));
+        Ok(self.engine.get(&latest, id).map(|e| (*e).clone()))
+    }
+
+    /// Mark the transaction finished and release its locks
+    fn finish(&mut self, state: TransactionState) {
+        self.state = state;
+        if let Some(locks) = &self.locks {
+            locks.release_all(self.id);
+        }
+    }
+
+    /// Record the snapshot version of an entity for commit-time validation
+    fn observe(&mut self, id: &EntityId) {
+        if !self.expected.contains_key(id) {
+            let version = self.engine.version_at(&self.snapshot, id);
+            self.expected.insert(*id, version);
+        }
+    }
+
+    fn ensure_active(&self) -> Result<()> {
+        if self.state != TransactionState::Active {
+            return Err(ConcurrencyError::TransactionConflict {
+                reason: format!("transaction {} is {:?}", self.id, self.state),
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if self.state == TransactionState::Active {
+            self.finish(TransactionState::Aborted);
+        }
+    }
+}
+
+fn endpoint_not_found(id: &EntityId) -> MemorySubstrateError {
+    GraphError::EndpointNotFound {
+        entity_id: id.to_string(),
+    }
+    .into()
+}
+
+/// Begins, commits and retries optimistic transactions
+#[derive(Debug)]
+pub struct TransactionCoordinator {
+    engine: Arc<MvccEngine>,
+    locks: Option<Arc<LockManager>>,
+    options: TransactionOptions,
+}
+
+impl TransactionCoordinator {
+    /// Create a coordinator without durability
+    pub fn new(engine: Arc<MvccEngine>) -> Self {
+        Self {
+            engine,
+            locks: None,
+            options: TransactionOptions::default(),
+        }
+    }
+
+    /// Enable pessimistic locking through `lock_manager`
+    pub fn with_lock_manager(mut self, lock_manager: Arc<LockManager>) -> Self {
+        self.locks = Some(lock_manager);
+        self
+    }
+
+    /// Override retry behaviour
+    pub fn with_options(mut self, options: TransactionOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Engine the coordinator commits to
+    pub fn engine(&self) -> &Arc<MvccEngine> {
+        &self.engine
+    }
+
+    /// Lock manager of pessimistic transactions, if any
+    pub fn lock_manager(&self) -> Option<&Arc<LockManager>> {
+        self.locks.as_ref()
+    }
+
+    /// Make sure transactions begun from now on get ids above `past`
+    ///
+    /// Called while recovering a catalog so that recovered and new
+    /// transactions never share an id.
+    pub fn advance_ids(past: TransactionId) {
+        NEXT_TRANSACTION_ID.fetch_max(past + 1, Ordering::Relaxed);
+    }
+
+    /// Identifier the next transaction begun by any coordinator will receive
+    pub fn next_id() -> TransactionId {
+        NEXT_TRANSACTION_ID.load(Ordering::Relaxed)
+    }
+
+    /// Start a transaction reading from the latest commit
+    pub fn begin(&self) -> Transaction {
+        Transaction {
+            id: NEXT_TRANSACTION_ID.fetch_add(1, Ordering::Relaxed),
+            engine: Arc::clone(&self.engine),
+            locks: self.locks.clone(),
+            snapshot: self.engine.snapshot(),
+            state: TransactionState::Active,
+            expected: HashMap::new(),
+            writes: HashMap::new(),
+            mismatch: None,
+        }
+    }
+
+    /// Commit a transaction through `apply`
+    ///
+    /// `apply` receives the version each observed entity must still have and
+    /// the buffered writes. It must validate the versions, make the writes
+    /// durable and apply them to the coordinator's engine as one step,
+    /// returning the commit timestamp; `MvccEngine::commit_validated` does
+    /// the validation and apply. A `VersionConflict` from `apply` aborts the
+    /// transaction with `TransactionConflict`.
+    ///
+    /// # Errors
+    /// * `TransactionConflict` if the transaction is not active, was begun
+    ///   by another coordinator, or conflicts
+    /// * `VersionConflict` if a `put_if_version` found another version
+    /// * Any other error returned by `apply`; the transaction is aborted
+    pub(crate) fn commit_with<F>(&self, txn: &mut Transaction, apply: F) -> Result<Timestamp>
+    where
+        F: FnOnce(&[(EntityId, Timestamp)], Vec<MvccWrite>) -> Result<Timestamp>,
+    {
+        txn.ensure_active()?;
+        if !Arc::ptr_eq(&txn.engine, &self.engine) {
+            return Err(ConcurrencyError::TransactionConflict {
+                reason: format!("transaction {} was begun by another coordinator", txn.id),
+            }
+            .into());
+        }
+        if let Some((expected, actual)) = txn.mismatch.take() {
+            txn.finish(TransactionState::Aborted);
+            return Err(ConcurrencyError::VersionConflict { expected, actual }.into());
+        }
+        if txn.writes.is_empty() {
+            txn.finish(TransactionState::Committed);
+            return Ok(txn.snapshot.timestamp());
+        }
+
+        let expected: Vec<_> = txn.expected.iter().map(|(id, v)| (*id, *v)).collect();
+        let writes: Vec<_> = txn.writes.drain().map(|(_, write)| write).collect();
+        let txn_id = txn.id;
+
+        match apply(&expected, writes) {
+            Ok(ts) => {
+                txn.finish(TransactionState::Committed);
+                Ok(ts)
+            }
+            Err(MemorySubstrateError::Concurrency {
+                error: ConcurrencyError::VersionConflict { expected, actual },
+                ..
+            }) => {
+                txn.finish(TransactionState::Aborted);
+                Err(ConcurrencyError::TransactionConflict {
+                    reason: format!(
+                        "transaction {} expected version {} but found {}",
+                        txn_id, expected, actual
+                    ),
+                }
+                .into())
+            }
+            Err(e) => {
+                txn.finish(TransactionState::Aborted);
+                Err(e)
+            }
+        }
+    }
+
+    /// Discard a transaction's writes
+    pub fn rollback(&self, txn: &mut Transaction) {
+        txn.writes.clear();
+        txn.finish(TransactionState::Aborted);
+    }
+
+    /// Run `body` in a transaction, retrying on conflicts
+    ///
+    /// Each attempt runs `body` and then `commit`, which must commit the
+    /// transaction through `commit_with`. `body` is re-run from a fresh
+    /// snapshot after each conflict, deadlock abort or lock timeout, with
+    /// exponential backoff between attempts. Other errors, including a
+    /// `VersionConflict` from `put_if_version`, roll back and are returned
+    /// immediately.
+    ///
+    /// # Returns
+    /// * What `body` returned on the committed attempt, with the commit
+    ///   timestamp
+    ///
+    /// # Errors
+    /// * `RetryLimitExceeded` once `max_attempts` attempts have conflicted
+    pub(crate) fn run_with<T, F, C>(&self, mut body: F, mut commit: C) -> Result<(T, Timestamp)>
+    where
+        F: FnMut(&mut Transaction) -> Result<T>,
+        C: FnMut(&mut Transaction) -> Result<Timestamp>,
+    {
+        for attempt in 1..=self.options.max_attempts {
+            let mut txn = self.begin();
+            let outcome = body(&mut txn).and_then(|value| Ok((value, commit(&mut txn)?)));
+
+            match outcome {
+                Ok(value) => return Ok(value),
+                Err(MemorySubstrateError::Concurrency {
+                    error:
+                        error @ (ConcurrencyError::TransactionConflict { .. }
+                        | ConcurrencyError::DeadlockDetected { .. }
+                        | ConcurrencyError::LockAcquisitionFailed { .. }),
+                    ..
+                }) => {
+                    self.rollback(&mut txn);
+                    tracing::debug!(attempt, %error, "transaction aborted, retrying");
+                    if attempt < self.options.max_attempts {
+                        std::thread::sleep(self.options.backoff(attempt));
+                    }
+                }
+                Err(e) => {
+                    self.rollback(&mut txn);
+                    return Err(e);
+                }
+            }
+        }
+
+        Err(ConcurrencyError::RetryLimitExceeded {
+            attempts: self.options.max_attempts,
+        }
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Vector;
+    use std::time::Duration;
+
+    fn entity(value: f32) -> Entity {
+        Entity::new(Some(Vector::new(vec![value])), None, None)
+    }
+
+    fn value(entity: &Entity) -> f32 {
+        entity.vector.as_ref().unwrap().values[0]
+    }
+
+    /// Commits straight to the engine; the coordinators here back no
+    /// collection, so there is nothing to log
+    trait CommitUnlogged {
+        fn commit(&self, txn: &mut Transaction) -> Result<Timestamp>;
+
+        fn run<T, F>(&self, body: F) -> Result<T>
+        where
+            F: FnMut(&mut Transaction) -> Result<T>;
+    }
+
+    impl CommitUnlogged for TransactionCoordinator {
+        fn commit(&self, txn: &mut Transaction) -> Result<Timestamp> {
+            self.commit_with(txn, |expected, writes| {
+                self.engine.commit_validated(expected, writes, |_| Ok(()))
+            })
+        }
+
+        fn run<T, F>(&self, body: F) -> Result<T>
+        where
+            F: FnMut(&mut Transaction) -> Result<T>,
+        {
+            self.run_with(body, |txn| self.commit(txn)).map(|(value, _)| value)
+        }
+    }
+
+    fn is_version_conflict<T: std::fmt::Debug>(result: Result<T>, expected: u64, actual: u64) -> bool {
+        matches!(
+            result,
+            Err(MemorySubstrateError::Concurrency {
+                error: ConcurrencyError::VersionConflict { expected: e, actual: a },
+                ..
+            }) if e == expected && a == actual
+        )
+    }
+
+    fn is_conflict<T: std::fmt::Debug>(result: Result<T>) -> bool {
+        matches!(
+            result,
+            Err(MemorySubstrateError::Concurrency {
+                error: ConcurrencyError::TransactionConflict { .. },
+                ..
+            })
+        )
+    }
+
+    #[test]
+    fn test_commit_is_atomic_and_isolated() {
+        let coordinator = TransactionCoordinator::new(Arc::new(MvccEngine::new()));
+        let (a, b) = (entity(1.0), entity(2.0));
+
+        let mut txn = coordinator.begin();
+        txn.put(a.clone()).unwrap();
+        txn.put(b.clone()).unwrap();
+        txn.put_edge(Edge::new(a.id, b.id, "cites".to_string(), 0.5, None)).unwrap();
+
+        // Nothing is visible before commit
+        let engine = coordinator.engine();
+        assert!(engine.get(&engine.snapshot(), &a.id).is_none());
+
+        let ts = coordinator.commit(&mut txn).unwrap();
+        assert_eq!(txn.state(), TransactionState::Committed);
+        let snapshot = engine.snapshot();
+        let stored = engine.get(&snapshot, &a.id).unwrap();
+        assert_eq!(stored.commit_ts, ts);
+        assert_eq!(engine.get(&snapshot, &b.id).unwrap().commit_ts, ts);
+        assert_eq!(engine.neighbors(&snapshot, &a.id).len(), 1);
+
+        // Committing twice is rejected
+        assert!(is_conflict(coordinator.commit(&mut txn)));
+    }
+
+    #[test]
+    fn test_rollback_discards_writes() {
+        let coordinator = TransactionCoordinator::new(Arc::new(MvccEngine::new()));
+        let e = entity(1.0);
+
+        let mut txn = coordinator.begin();
+        txn.put(e.clone()).unwrap();
+        assert!(txn.get(&e.id).is_some());
+        coordinator.rollback(&mut txn);
+        assert_eq!(txn.state(), TransactionState::Aborted);
+        assert!(is_conflict(coordinator.commit(&mut txn)));
+
+        let engine = coordinator.engine();
+        assert!(engine.get(&engine.snapshot(), &e.id).is_none());
+    }
+
+    #[test]
+    fn test_conflicting_transactions() {
+        let coordinator = TransactionCoordinator::new(Arc::new(MvccEngine::new()));
+        let e = entity(0.0);
+        let v1 = coordinator.engine().put(e.clone());
+
+        // Two transactions read-modify-write the same entity
+        let mut first = coordinator.begin();
+        let mut second = coordinator.begin();
+        let mut x = first.get(&e.id).unwrap();
+        let mut y = second.get(&e.id).unwrap();
+        x.vector = Some(Vector::new(vec![1.0]));
+        y.vector = Some(Vector::new(vec![2.0]));
+        first.put(x).unwrap();
+        second.put(y).unwrap();
+
+        coordinator.commit(&mut first).unwrap();
+        assert!(is_conflict(coordinator.commit(&mut second)));
+        assert_eq!(second.state(), TransactionState::Aborted);
+
+        assert!(v1 > 0);
+    }
+
+    #[test]
+    fn test_put_if_version_compares_entity_versions() {
+        let coordinator = TransactionCoordinator::new(Arc::new(MvccEngine::new()));
+        let mut e = entity(0.0);
+        e.version = 3;
+        coordinator.engine().put(e.clone());
+
+        // A client holding a stale write counter is refused, and the
+        // transaction cannot commit even if the error is ignored
+        let mut stale = coordinator.begin();
+        assert!(is_version_conflict(stale.put_if_version(e.clone(), 2), 2, 3));
+        assert!(is_version_conflict(coordinator.commit(&mut stale), 2, 3));
+        assert_eq!(stale.state(), TransactionState::Aborted);
+        assert!(stale.put(e.clone()).is_err());
+        assert!(stale.delete(e.id).is_err());
+
+        // The matching version succeeds, even from an older snapshot
+        let mut fresh = coordinator.begin();
+        let mut update = e.clone();
+        update.version = 4;
+        coordinator.engine().put(e.clone());
+        fresh.put_if_version(update.clone(), 3).unwrap();
+        coordinator.commit(&mut fresh).unwrap();
+        assert_eq!(coordinator.engine().latest(&e.id).unwrap().version, 4);
+
+        // A commit racing in after the check still conflicts
+        let mut raced = coordinator.begin();
+        raced.put_if_version(e.clone(), 4).unwrap();
+        coordinator.engine().put(update);
+        assert!(is_conflict(coordinator.commit(&mut raced)));
+
+        let mut create = coordinator.begin();
+        assert!(is_version_conflict(create.put_if_version(e.clone(), 0), 0, 4));
+        let mut create = coordinator.begin();
+        create.put_if_version(entity(1.0), 0).unwrap();
+        coordinator.commit(&mut create).unwrap();
+    }
+
+    #[test]
+    fn test_edge_endpoints_must_exist() {
+        let coordinator = TransactionCoordinator::new(Arc::new(MvccEngine::new()));
+        let a = entity(1.0);
+        coordinator.engine().put(a.clone());
+
+        let mut txn = coordinator.begin();
+        let missing = EntityId::new();
+        assert!(matches!(
+            txn.put_edge(Edge::new(a.id, missing, "x".to_string(), 1.0, None)),
+            Err(MemorySubstrateError::Graph {
+                error: GraphError::EndpointNotFound { .. },
+                ..
+            })
+        ));
+        assert!(!txn.remove_edge(&a.id, &missing, "x").unwrap());
+        assert_eq!(txn.write_count(), 0);
+    }
+
+    #[test]
+    fn test_run_retries_until_success() {
+        let coordinator = Arc::new(TransactionCoordinator::new(Arc::new(MvccEngine::new())));
+        let counter = entity(0.0);
+        coordinator.engine().put(counter.clone());
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let coordinator = Arc::clone(&coordinator);
+                let id = counter.id;
+                std::thread::spawn(move || {
+                    for _ in 0..25 {
+                        coordinator
+                            .run(|txn| {
+                                let mut c = txn.get(&id).unwrap();
+                                c.vector = Some(Vector::new(vec![value(&c) + 1.0]));
+                                txn.put(c)?;
+                                Ok(())
+                            })
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let engine = coordinator.engine();
+        assert_eq!(value(&engine.get(&engine.snapshot(), &counter.id).unwrap()), 100.0);
+    }
+
+    #[test]
+    fn test_run_reports_retry_limit() {
+        let coordinator = TransactionCoordinator::new(Arc::new(MvccEngine::new())).with_options(TransactionOptions {
+            max_attempts: 3,
+            base_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+        });
+        let e = entity(0.0);
+        coordinator.engine().put(e.clone());
+
+        let mut attempts = 0;
+        let result: Result<()> = coordinator.run(|txn| {
+            attempts += 1;
+            txn.get(&e.id);
+            // A concurrent writer sneaks in before every commit
+            coordinator.engine().put(e.clone());
+            txn.put(e.clone())?;
+            Ok(())
+        });
+        assert_eq!(attempts, 3);
+        assert!(matches!(
+            result,
+            Err(MemorySubstrateError::Concurrency {
+                error: ConcurrencyError::RetryLimitExceeded { attempts: 3 },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_pessimistic_mode_serializes_hub_updates() {
+        // A single attempt suffices: locked updates never fail validation
+        let coordinator = Arc::new(
+            TransactionCoordinator::new(Arc::new(MvccEngine::new()))
+                .with_lock_manager(Arc::new(LockManager::new()))
+                .with_options(TransactionOptions {
+                    max_attempts: 1,
+                    ..TransactionOptions::default()
+                }),
+        );
+        let hub = entity(0.0);
+        coordinator.engine().put(hub.clone());
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let coordinator = Arc::clone(&coordinator);
+                let id = hub.id;
+                std::thread::spawn(move || {
+                    for _ in 0..25 {
+                        coordinator
+                            .run(|txn| {
+                                let mut h = txn.get_for_update(&id)?.unwrap();
+                                h.vector = Some(Vector::new(vec![value(&h) + 1.0]));
+                                txn.put(h)?;
+                                Ok(())
+                            })
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let engine = coordinator.engine();
+        assert_eq!(value(&engine.get(&engine.snapshot(), &hub.id).unwrap()), 100.0);
+    }
+
+    #[test]
+    fn test_locks_released_on_rollback_and_drop() {
+        let locks = Arc::new(LockManager::new());
+        let coordinator =
+            TransactionCoordinator::new(Arc::new(MvccEngine::new())).with_lock_manager(Arc::clone(&locks));
+        let e = entity(1.0);
+
+        let mut txn = coordinator.begin();
+        txn.lock(&e.id, LockMode::Exclusive).unwrap();
+        assert_eq!(locks.held_mode(txn.id(), &e.id), Some(LockMode::Exclusive));
+        coordinator.rollback(&mut txn);
+        assert_eq!(locks.held_mode(txn.id(), &e.id), None);
+
+        let id = {
+            let mut txn = coordinator.begin();
+            txn.lock(&e.id, LockMode::Shared).unwrap();
+            txn.id()
+        };
+        assert_eq!(locks.held_mode(id, &e.id), None);
+    }
+
+    #[test]
+    fn test_transaction_ids_are_unique_across_coordinators() {
+        let a = TransactionCoordinator::new(Arc::new(MvccEngine::new()));
+        let b = TransactionCoordinator::new(Arc::new(MvccEngine::new()));
+        let (first, second, third) = (a.begin(), b.begin(), a.begin());
+        assert!(first.id() < second.id() && second.id() < third.id());
+
+        TransactionCoordinator::advance_ids(third.id() + 10);
+        assert!(b.begin().id() > third.id() + 10);
+        assert!(TransactionCoordinator::next_id() > third.id() + 11);
+    }
+
+    #[test]
+    fn test_version_mismatch_is_not_retried() {
+        let coordinator = TransactionCoordinator::new(Arc::new(MvccEngine::new()));
+        let mut e = entity(0.0);
+        e.version = 3;
+        coordinator.engine().put(e.clone());
+
+        // An expected version fixed by the caller fails the same way every
+        // attempt, so the first failure is returned as it is
+        let mut attempts = 0;
+        let result = coordinator.run(|txn| {
+            attempts += 1;
+            txn.put_if_version(e.clone(), 7)
+        });
+        assert!(is_version_conflict(result, 7, 3));
+        assert_eq!(attempts, 1);
+        assert_eq!(coordinator.engine().latest(&e.id).unwrap().version, 3);
+    }
+
+    #[test]
+    fn test_commit_with_failing_apply_aborts() {
+        let coordinator = TransactionCoordinator::new(Arc::new(MvccEngine::new()));
+        let a = entity(1.0);
+
+        let mut txn = coordinator.begin();
+        txn.put(a.clone()).unwrap();
+        let result = coordinator.commit_with(&mut txn, |_, _| {
+            Err(MemorySubstrateError::Internal("log unavailable".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(txn.state(), TransactionState::Aborted);
+        assert!(coordinator.engine().latest(&a.id).is_none());
+
+        // A transaction from another coordinator is refused
+        let other = TransactionCoordinator::new(Arc::new(MvccEngine::new()));
+        let mut txn = other.begin();
+        txn.put(a.clone()).unwrap();
+        assert!(is_conflict(coordinator.commit(&mut txn)));
+    }
+}
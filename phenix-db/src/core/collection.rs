@@ -0,0 +1,314 @@
+// Collection configuration
+//
+// A collection is an independent dataset hosted alongside others in one
+// server: it fixes the vector dimension and distance metric, and carries its
+// own index parameters, optional metadata schema, tiering policy and
+// transaction concurrency mode.
+// Collections are addressed by name or alias and identified by CollectionId.
+
+use crate::core::config::TieringConfig;
+use crate::core::error::{CollectionError, CollectionResult};
+use crate::core::metadata::MetadataSchema;
+use crate::core::types::CollectionId;
+use crate::core::vector::DistanceMetric;
+use serde::{Deserialize, Serialize};
+
+/// Maximum length of a collection name or alias
+pub const MAX_NAME_LENGTH: usize = 64;
+
+/// Maximum supported vector dimension
+pub const MAX_DIMENSION: usize = 65_536;
+
+/// Check a collection name or alias
+///
+/// Names are 1-64 characters of ASCII letters, digits, `_` and `-`, and must
+/// start with a letter or `_`, so they are safe in URLs and file names.
+pub fn validate_name(name: &str) -> CollectionResult<()> {
+    let invalid = |reason: &str| {
+        Err(CollectionError::InvalidName {
+            name: name.to_string(),
+            reason: reason.to_string(),
+        })
+    };
+
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        return invalid("must be 1-64 characters");
+    }
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        return invalid("must start with a letter or '_'");
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return invalid("may only contain letters, digits, '_' and '-'");
+    }
+    Ok(())
+}
+
+/// Vector index parameters of a collection
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndexConfig {
+    /// Maximum graph links per node on upper layers (default: 16)
+    pub m: usize,
+
+    /// Beam width while building the graph (default: 200)
+    pub ef_construction: usize,
+
+    /// Default beam width for queries (default: 64)
+    pub ef_search: usize,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+        }
+    }
+}
+
+impl IndexConfig {
+    /// Validate index parameters
+    pub fn validate(&self) -> CollectionResult<()> {
+        if self.m < 2 {
+            return Err(CollectionError::InvalidConfig {
+                reason: "index m must be >= 2".to_string(),
+            });
+        }
+        if self.ef_construction < self.m {
+            return Err(CollectionError::InvalidConfig {
+                reason: "index ef_construction must be >= m".to_string(),
+            });
+        }
+        if self.ef_search == 0 {
+            return Err(CollectionError::InvalidConfig {
+                reason: "index ef_search must be > 0".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// How transactions on a collection handle contention
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConcurrencyMode {
+    /// Validate at commit and retry on conflict (default)
+    #[default]
+    Optimistic,
+
+    /// Lock entities read for update until commit, so contended entities are
+    /// serialized instead of repeatedly failing validation
+    Pessimistic {
+        /// How long a transaction waits for a lock before giving up
+        lock_timeout_ms: u64,
+    },
+}
+
+/// Configuration fixed when a collection is created
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionConfig {
+    /// Dimension of every vector in the collection
+    pub dimension: usize,
+
+    /// Distance metric used for search
+    #[serde(default)]
+    pub metric: DistanceMetric,
+
+    /// Vector index parameters
+    #[serde(default)]
+    pub index: IndexConfig,
+
+    /// Optional metadata schema enforced on insert
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<MetadataSchema>,
+
+    /// Tiering policy for the collection's entities
+    #[serde(default = "TieringConfig::default")]
+    pub tiering: TieringConfig,
+
+    /// Concurrency control for transactions on the collection
+    #[serde(default)]
+    pub concurrency: ConcurrencyMode,
+}
+
+impl CollectionConfig {
+    /// Configuration with default metric, index and tiering
+    pub fn new(dimension: usize) -> Self {
+        Self {
+            dimension,
+            metric: DistanceMetric::default(),
+            index: IndexConfig::default(),
+            schema: None,
+            tiering: TieringConfig::default(),
+            concurrency: ConcurrencyMode::default(),
+        }
+    }
+
+    /// Set the distance metric
+    pub fn with_metric(mut self, metric: DistanceMetric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    /// Set the index parameters
+    pub fn with_index(mut self, index: IndexConfig) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Set the metadata schema
+    pub fn with_schema(mut self, schema: MetadataSchema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Set the tiering policy
+    pub fn with_tiering(mut self, tiering: TieringConfig) -> Self {
+        self.tiering = tiering;
+        self
+    }
+
+    /// Set the transaction concurrency mode
+    pub fn with_concurrency(mut self, concurrency: ConcurrencyMode) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Validate the configuration
+    pub fn validate(&self) -> CollectionResult<()> {
+        if self.dimension == 0 || self.dimension > MAX_DIMENSION {
+            return Err(CollectionError::InvalidConfig {
+                reason: format!("dimension must be in 1..={}", MAX_DIMENSION),
+            });
+        }
+        if self.concurrency == (ConcurrencyMode::Pessimistic { lock_timeout_ms: 0 }) {
+            return Err(CollectionError::InvalidConfig {
+                reason: "concurrency lock_timeout_ms must be > 0".to_string(),
+            });
+        }
+        self.index.validate()?;
+        self.tiering
+            .validate()
+            .map_err(|e| CollectionError::InvalidConfig {
+                reason: e.to_string(),
+            })
+    }
+}
+
+/// Description of a collection returned by list and describe operations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionInfo {
+    /// Stable identifier
+    pub id: CollectionId,
+
+    /// Primary name
+    pub name: String,
+
+    /// Aliases currently pointing at the collection
+    pub aliases: Vec<String>,
+
+    /// Creation configuration
+    pub config: CollectionConfig,
+
+    /// Creation timestamp (Unix epoch milliseconds)
+    pub created_at: u64,
+
+    /// Number of stored entities
+    pub entity_count: usize,
+}
+
+/// Number of entities in each memory tier
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TierSizes {
+    /// Entities in the hot tier
+    pub hot: usize,
+
+    /// Entities in the warm tier
+    pub warm: usize,
+
+    /// Entities in the cold tier
+    pub cold: usize,
+}
+
+/// Tier, graph and access statistics of a collection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CollectionStats {
+    /// Primary name
+    pub name: String,
+
+    /// Number of stored entities
+    pub entity_count: usize,
+
+    /// Entities per memory tier
+    pub tiers: TierSizes,
+
+    /// Number of outgoing edges across all entities
+    pub edge_count: usize,
+
+    /// Number of distinct edge labels
+    pub edge_labels: usize,
+
+    /// Shannon entropy of recorded accesses over entities, normalized to
+    /// [0, 1]; 1 means reads are spread evenly, values near 0 mean a few
+    /// entities take all of them. 0 when nothing has been read yet.
+    pub access_entropy: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_validation() {
+        for name in ["docs", "_tmp", "images-v2", "A1"] {
+            assert!(validate_name(name).is_ok(), "{}", name);
+        }
+        for name in ["", "1abc", "-x", "a b", "a/b", "ä", &"x".repeat(65)] {
+            assert!(validate_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert!(CollectionConfig::new(384).validate().is_ok());
+        assert!(CollectionConfig::new(0).validate().is_err());
+        assert!(CollectionConfig::new(MAX_DIMENSION + 1).validate().is_err());
+
+        let bad_index = IndexConfig {
+            m: 32,
+            ef_construction: 8,
+            ..IndexConfig::default()
+        };
+        assert!(CollectionConfig::new(8).with_index(bad_index).validate().is_err());
+
+        let mut tiering = TieringConfig::default();
+        tiering.warm_promotion_threshold = -1.0;
+        assert!(matches!(
+            CollectionConfig::new(8).with_tiering(tiering).validate(),
+            Err(CollectionError::InvalidConfig { .. })
+        ));
+
+        let no_wait = ConcurrencyMode::Pessimistic { lock_timeout_ms: 0 };
+        assert!(CollectionConfig::new(8).with_concurrency(no_wait).validate().is_err());
+    }
+
+    #[test]
+    fn test_config_deserialization_defaults() {
+        let config: CollectionConfig = serde_json::from_str(r#"{"dimension": 128}"#).unwrap();
+        assert_eq!(config.metric, DistanceMetric::Cosine);
+        assert_eq!(config.index, IndexConfig::default());
+        assert!(config.schema.is_none());
+        assert!(config.validate().is_ok());
+
+        let config: CollectionConfig =
+            serde_json::from_str(r#"{"dimension": 3, "metric": "Euclidean", "index": {"m": 8}}"#).unwrap();
+        assert_eq!(config.metric, DistanceMetric::Euclidean);
+        assert_eq!(config.index.m, 8);
+        assert_eq!(config.index.ef_search, 64);
+        assert_eq!(config.concurrency, ConcurrencyMode::Optimistic);
+
+        let config: CollectionConfig =
+            serde_json::from_str(r#"{"dimension": 3, "concurrency": {"Pessimistic": {"lock_timeout_ms": 250}}}"#).unwrap();
+        assert_eq!(config.concurrency, ConcurrencyMode::Pessimistic { lock_timeout_ms: 250 });
+    }
+}
@@ -56,6 +56,26 @@ pub struct PhenixConfig {
     
     /// Distributed consciousness configuration
     pub distributed: DistributedConfig,
+
+    /// External API configuration
+    #[serde(default)]
+    pub api: ApiConfig,
+
+    /// Query planner configuration
+    #[serde(default)]
+    pub planner: PlannerConfig,
+
+    /// Change data capture configuration
+    #[serde(default)]
+    pub cdc: CdcConfig,
+
+    /// Bulk import and export configuration
+    #[serde(default)]
+    pub bulk: BulkConfig,
+
+    /// Server process configuration
+    #[serde(default)]
+    pub server: ServerConfig,
 }
 
 impl PhenixConfig {
@@ -100,6 +120,11 @@ impl PhenixConfig {
             learning: LearningConfig::default(),
             tiering: TieringConfig::default(),
             distributed: DistributedConfig::default(),
+            api: ApiConfig::default(),
+            planner: PlannerConfig::default(),
+            cdc: CdcConfig::default(),
+            bulk: BulkConfig::default(),
+            server: ServerConfig::default(),
         }
     }
     
@@ -117,9 +142,127 @@ impl PhenixConfig {
         self.learning.validate()?;
         self.tiering.validate()?;
         self.distributed.validate()?;
+        self.api.validate()?;
+        self.planner.validate()?;
+        self.cdc.validate()?;
+        self.bulk.validate()?;
+        self.server.validate()?;
         
         Ok(())
     }
+
+    /// Apply `key = value` overrides, keys in dotted form (`api.rest_addr`)
+    ///
+    /// Values are parsed with [`parse_override`]. The result is not
+    /// validated.
+    ///
+    /// # Errors
+    /// * `ValidationError` for a key that names no setting
+    /// * `ParseError` for a value of the wrong type
+    pub fn with_overrides<K, V>(self, overrides: impl IntoIterator<Item = (K, V)>) -> Result<Self>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.with_values(
+            overrides
+                .into_iter()
+                .map(|(key, value)| (key, parse_override(value.as_ref()))),
+        )
+    }
+
+    /// Apply already typed overrides, keys in dotted form (`api.rest_addr`)
+    ///
+    /// The result is not validated.
+    ///
+    /// # Errors
+    /// * `ValidationError` for a key that names no setting
+    /// * `ParseError` for a value of the wrong type
+    pub fn with_values<K>(self, overrides: impl IntoIterator<Item = (K, toml::Value)>) -> Result<Self>
+    where
+        K: AsRef<str>,
+    {
+        let mut document = toml::Value::try_from(&self).map_err(|e| ConfigError::ParseError(e.to_string()))?;
+        let mut keys = Vec::new();
+        for (key, value) in overrides {
+            let key = key.as_ref();
+            let (path, name) = key.rsplit_once('.').map_or((None, key), |(path, name)| (Some(path), name));
+            let mut table = document.as_table_mut().expect("configuration serializes to a table");
+            for section in path.into_iter().flat_map(|path| path.split('.')) {
+                table = table
+                    .entry(section)
+                    .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                    .as_table_mut()
+                    .ok_or_else(|| ConfigError::ValidationError(format!("unknown setting '{}'", key)))?;
+            }
+            table.insert(name.to_string(), value);
+            keys.push(key.to_string());
+        }
+
+        let config: Self = document
+            .try_into()
+            .map_err(|e: toml::de::Error| ConfigError::ParseError(e.to_string()))?;
+
+        // Unknown keys are silently ignored by deserialization; a key that
+        // does not survive the round trip names no setting
+        let applied = toml::Value::try_from(&config).map_err(|e| ConfigError::ParseError(e.to_string()))?;
+        for key in keys {
+            if key.split('.').try_fold(&applied, |value, part| value.get(part)).is_none() {
+                return Err(ConfigError::ValidationError(format!("unknown setting '{}'", key)));
+            }
+        }
+        Ok(config)
+    }
+
+    /// Map `PHENIX_DB_<SECTION>_<KEY>` environment variables onto dotted keys
+    ///
+    /// `PHENIX_DB_TIERING_HOT_PROMOTION_THRESHOLD` becomes
+    /// `tiering.hot_promotion_threshold` and
+    /// `PHENIX_DB_BELLMAN_COST_WEIGHTS_IO` becomes `bellman.cost_weights.io`.
+    /// Feed the result to [`PhenixConfig::with_overrides`].
+    ///
+    /// # Errors
+    /// * `EnvError` for a prefixed variable that names no setting
+    pub fn env_overrides(vars: impl IntoIterator<Item = (String, String)>) -> Result<Vec<(String, String)>> {
+        let defaults = toml::Value::try_from(Self::default()).map_err(|e| ConfigError::ParseError(e.to_string()))?;
+        let mut overrides = Vec::new();
+        for (name, value) in vars {
+            let Some(rest) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let key = env_key(&defaults, &rest.to_ascii_lowercase())
+                .ok_or_else(|| ConfigError::EnvError(format!("{} names no setting", name)))?;
+            overrides.push((key, value));
+        }
+        Ok(overrides)
+    }
+}
+
+/// Prefix of environment variables that override settings
+pub const ENV_PREFIX: &str = "PHENIX_DB_";
+
+/// Parse an override value as TOML (`7`, `0.5`, `true`, `"text"`)
+///
+/// Anything that is not a valid TOML value is taken as a string, so
+/// `--set api.rest_addr=0.0.0.0:80` needs no quoting.
+pub fn parse_override(raw: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+/// Resolve an underscore-joined path such as `bellman_cost_weights_io`
+/// against the tables of `value`
+fn env_key(value: &toml::Value, path: &str) -> Option<String> {
+    let table = value.as_table()?;
+    if table.contains_key(path) && !table[path].is_table() {
+        return Some(path.to_string());
+    }
+    table.iter().filter(|(_, value)| value.is_table()).find_map(|(name, value)| {
+        let rest = path.strip_prefix(name.as_str())?.strip_prefix('_')?;
+        env_key(value, rest).map(|key| format!("{}.{}", name, key))
+    })
 }
 
 /// Polynomial index configuration
@@ -605,7 +748,8 @@ pub struct DistributedConfig {
     pub node_failure_timeout_ms: u64,
     
     /// Cross-datacenter latency threshold in milliseconds (default: 100)
-    /// Latency above this triggers routing optimization
+    /// Nodes with a round trip above this count as distant: reads prefer
+    /// nearer replicas and writes to them are shipped in batches
     pub cross_dc_latency_threshold_ms: u64,
     
     /// Minimum replicas (default: 3)
@@ -677,6 +821,337 @@ impl DistributedConfig {
     }
 }
 
+/// External API configuration
+///
+/// Listen addresses and transport security of the client-facing APIs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+    /// gRPC listen address (default: 0.0.0.0:50051)
+    pub grpc_addr: String,
+
+    /// REST/JSON listen address (default: 0.0.0.0:8080)
+    pub rest_addr: String,
+
+    /// TLS for the gRPC listener; plaintext when unset
+    ///
+    /// The REST listener is always plaintext and is expected to sit behind
+    /// a TLS-terminating proxy when exposed.
+    pub tls: Option<TlsConfig>,
+
+    /// Largest accepted or sent gRPC message, and largest accepted REST
+    /// request body, in bytes (default: 64 MiB)
+    pub max_message_bytes: usize,
+
+    /// Change events buffered per subscriber before it falls behind
+    /// (default: 1024)
+    pub change_buffer: usize,
+
+    /// Idle time after which an open client transaction is aborted, in
+    /// milliseconds (default: 60000)
+    pub transaction_timeout_ms: u64,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            grpc_addr: "0.0.0.0:50051".to_string(),
+            rest_addr: "0.0.0.0:8080".to_string(),
+            tls: None,
+            max_message_bytes: 64 * 1024 * 1024,
+            change_buffer: 1024,
+            transaction_timeout_ms: 60_000,
+        }
+    }
+}
+
+impl ApiConfig {
+    /// Validate listen addresses and limits
+    pub fn validate(&self) -> Result<()> {
+        for (name, addr) in [("gRPC", &self.grpc_addr), ("REST", &self.rest_addr)] {
+            if addr.parse::<std::net::SocketAddr>().is_err() {
+                return Err(ConfigError::ValidationError(format!(
+                    "{} address '{}' is not a socket address",
+                    name, addr
+                )));
+            }
+        }
+        
+        if self.max_message_bytes == 0 {
+            return Err(ConfigError::ValidationError(
+                "Maximum message size must be positive".to_string()
+            ));
+        }
+        
+        if self.change_buffer == 0 {
+            return Err(ConfigError::ValidationError(
+                "Change buffer must hold at least one event".to_string()
+            ));
+        }
+        
+        if self.transaction_timeout_ms == 0 {
+            return Err(ConfigError::ValidationError(
+                "Transaction timeout must be positive".to_string()
+            ));
+        }
+        
+        Ok(())
+    }
+}
+
+/// Query planner configuration
+///
+/// Controls plan caching and cold-tier pruning of cognitive queries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlannerConfig {
+    /// Plans cached per process, keyed by collection and query shape
+    /// (default: 256; 0 disables caching)
+    pub plan_cache_capacity: usize,
+
+    /// Relative change in a collection's entity count that invalidates its
+    /// cached plans (default: 0.25)
+    pub replan_drift: f64,
+
+    /// Drop cold seeds when hot and warm seeds are within this relative
+    /// distance of the best answer (default: 0.1; unset disables pruning)
+    pub cold_prune_slack: Option<f32>,
+}
+
+impl Default for PlannerConfig {
+    fn default() -> Self {
+        Self {
+            plan_cache_capacity: 256,
+            replan_drift: 0.25,
+            cold_prune_slack: Some(0.1),
+        }
+    }
+}
+
+impl PlannerConfig {
+    /// Validate drift and slack ranges
+    pub fn validate(&self) -> Result<()> {
+        if !(self.replan_drift > 0.0 && self.replan_drift.is_finite()) {
+            return Err(ConfigError::ValidationError(
+                "Re-plan drift must be positive".to_string()
+            ));
+        }
+        
+        if let Some(slack) = self.cold_prune_slack {
+            if !(slack >= 0.0 && slack.is_finite()) {
+                return Err(ConfigError::ValidationError(
+                    "Cold prune slack must be non-negative".to_string()
+                ));
+            }
+        }
+        
+        Ok(())
+    }
+}
+
+/// Change data capture configuration
+///
+/// Controls how many change events are kept in memory for resuming
+/// consumers and how far a live consumer may fall behind.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CdcConfig {
+    /// Most recent change events kept in memory (default: 10000)
+    ///
+    /// Consumers resuming from an older sequence are served from the
+    /// write-ahead log, or refused when the catalog has none.
+    pub retained_events: usize,
+
+    /// Change events buffered per live consumer before it falls behind
+    /// (default: 4096)
+    pub subscriber_buffer: usize,
+}
+
+impl Default for CdcConfig {
+    fn default() -> Self {
+        Self {
+            retained_events: 10_000,
+            subscriber_buffer: 4096,
+        }
+    }
+}
+
+impl CdcConfig {
+    /// Validate buffer sizes
+    pub fn validate(&self) -> Result<()> {
+        if self.subscriber_buffer == 0 {
+            return Err(ConfigError::ValidationError(
+                "CDC subscriber buffer must hold at least one event".to_string()
+            ));
+        }
+        
+        Ok(())
+    }
+}
+
+/// Bulk import and export configuration
+///
+/// Loads are committed in segments: each segment is logged as one
+/// write-ahead log record with a single fsync, instead of one per entity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BulkConfig {
+    /// Most entities per segment (default: 10000)
+    pub segment_entities: usize,
+
+    /// Approximate upper bound on the logged size of a segment in bytes
+    /// (default: 32 MiB)
+    pub segment_bytes: usize,
+
+    /// Rejected rows reported individually; the rest are only counted
+    /// (default: 100)
+    pub max_reported_rejections: usize,
+
+    /// Rows per Parquet row group on export (default: 8192)
+    pub parquet_row_group_rows: usize,
+
+    /// Most memory one Parquet row group or one Fortran-order NumPy array
+    /// may decode into on load, in bytes; larger ones are refused
+    /// (default: 1 GiB)
+    pub max_decode_bytes: usize,
+
+    /// Parquet column holding entity ids (default: "id")
+    pub id_column: String,
+
+    /// Parquet column holding vectors (default: "vector")
+    pub vector_column: String,
+}
+
+impl Default for BulkConfig {
+    fn default() -> Self {
+        Self {
+            segment_entities: 10_000,
+            segment_bytes: 32 * 1024 * 1024,
+            max_reported_rejections: 100,
+            parquet_row_group_rows: 8192,
+            max_decode_bytes: 1024 * 1024 * 1024,
+            id_column: "id".to_string(),
+            vector_column: "vector".to_string(),
+        }
+    }
+}
+
+impl BulkConfig {
+    /// Validate segment sizes and column names
+    pub fn validate(&self) -> Result<()> {
+        if self.segment_entities == 0 || self.parquet_row_group_rows == 0 {
+            return Err(ConfigError::ValidationError(
+                "Bulk segments and row groups must hold at least one row".to_string()
+            ));
+        }
+
+        if self.max_decode_bytes == 0 {
+            return Err(ConfigError::ValidationError(
+                "Bulk decode memory limit must be positive".to_string()
+            ));
+        }
+
+        // A segment is one WAL record; keep headroom below the record limit
+        if self.segment_bytes == 0 || self.segment_bytes > crate::storage::wal::MAX_RECORD_SIZE / 2 {
+            return Err(ConfigError::ValidationError(format!(
+                "Bulk segment size must be in (0, {}] bytes",
+                crate::storage::wal::MAX_RECORD_SIZE / 2
+            )));
+        }
+
+        if self.id_column.is_empty() || self.vector_column.is_empty() || self.id_column == self.vector_column {
+            return Err(ConfigError::ValidationError(
+                "Bulk id and vector columns must be distinct, non-empty names".to_string()
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Server process configuration
+///
+/// Where the server keeps its data, where it exposes metrics, how often the
+/// background workers run and how long a graceful shutdown may take.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Directory holding the write-ahead log, checkpoint and node id
+    /// (default: "data")
+    pub data_dir: std::path::PathBuf,
+
+    /// Keep everything in memory and ignore `data_dir` (default: false)
+    pub in_memory: bool,
+
+    /// Prometheus metrics listen address; empty disables the endpoint
+    /// (default: 0.0.0.0:9100)
+    pub metrics_addr: String,
+
+    /// How often entities are moved between tiers (default: 60)
+    pub tiering_interval_secs: u64,
+
+    /// How often collection statistics and access entropy are sampled
+    /// (default: 15)
+    pub entropy_interval_secs: u64,
+
+    /// Time allowed for draining requests and stopping workers on shutdown
+    /// (default: 30)
+    pub shutdown_timeout_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            data_dir: std::path::PathBuf::from("data"),
+            in_memory: false,
+            metrics_addr: "0.0.0.0:9100".to_string(),
+            tiering_interval_secs: 60,
+            entropy_interval_secs: 15,
+            shutdown_timeout_secs: 30,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Validate the metrics address and intervals
+    pub fn validate(&self) -> Result<()> {
+        if !self.metrics_addr.is_empty() && self.metrics_addr.parse::<std::net::SocketAddr>().is_err() {
+            return Err(ConfigError::ValidationError(format!(
+                "Metrics address '{}' is not a socket address",
+                self.metrics_addr
+            )));
+        }
+
+        if !self.in_memory && self.data_dir.as_os_str().is_empty() {
+            return Err(ConfigError::ValidationError(
+                "Data directory must be set unless running in memory".to_string()
+            ));
+        }
+
+        if self.tiering_interval_secs == 0 || self.entropy_interval_secs == 0 || self.shutdown_timeout_secs == 0 {
+            return Err(ConfigError::ValidationError(
+                "Worker intervals and shutdown timeout must be positive".to_string()
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// TLS certificate and key files (PEM)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// Server certificate chain
+    pub cert_path: std::path::PathBuf,
+
+    /// Server private key
+    pub key_path: std::path::PathBuf,
+
+    /// CA that client certificates must chain to; enables mutual TLS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ca_path: Option<std::path::PathBuf>,
+}
+
 /// Consistency level for distributed operations
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ConsistencyLevel {
@@ -846,6 +1321,132 @@ mod tests {
     }
 
 
+    #[test]
+    fn test_api_config_validation() {
+        let mut config = ApiConfig::default();
+        assert!(config.validate().is_ok());
+        
+        config.grpc_addr = "localhost".to_string();
+        assert!(config.validate().is_err());
+        
+        config.grpc_addr = "127.0.0.1:0".to_string();
+        config.rest_addr = "8080".to_string();
+        assert!(config.validate().is_err());
+        
+        config.rest_addr = "127.0.0.1:0".to_string();
+        config.max_message_bytes = 0;
+        assert!(config.validate().is_err());
+        
+        // Files written before the api section existed still load
+        let mut table: toml::Table = toml::from_str(&toml::to_string(&PhenixConfig::default()).unwrap()).unwrap();
+        table.remove("api");
+        let config: PhenixConfig = toml::from_str(&toml::to_string(&table).unwrap()).unwrap();
+        assert_eq!(config.api.grpc_addr, "0.0.0.0:50051");
+        assert!(config.api.tls.is_none());
+    }
+
+
+    #[test]
+    fn test_planner_config_validation() {
+        let mut config = PlannerConfig::default();
+        assert!(config.validate().is_ok());
+        
+        config.replan_drift = 0.0;
+        assert!(config.validate().is_err());
+        
+        config.replan_drift = 0.5;
+        config.cold_prune_slack = Some(-0.1);
+        assert!(config.validate().is_err());
+        
+        config.cold_prune_slack = None;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_cdc_config_validation() {
+        let mut config = CdcConfig::default();
+        assert!(config.validate().is_ok());
+        
+        config.subscriber_buffer = 0;
+        assert!(config.validate().is_err());
+        
+        // Keeping nothing in memory is allowed: resumes read the WAL
+        config.subscriber_buffer = 1;
+        config.retained_events = 0;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_bulk_config_validation() {
+        let mut config = BulkConfig::default();
+        assert!(config.validate().is_ok());
+        
+        config.segment_bytes = crate::storage::wal::MAX_RECORD_SIZE;
+        assert!(config.validate().is_err());
+        
+        config = BulkConfig::default();
+        config.vector_column = "id".to_string();
+        assert!(config.validate().is_err());
+
+        config = BulkConfig::default();
+        config.max_decode_bytes = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_server_config_validation() {
+        let mut config = ServerConfig::default();
+        assert!(config.validate().is_ok());
+
+        config.metrics_addr = String::new();
+        assert!(config.validate().is_ok());
+
+        config.metrics_addr = "metrics".to_string();
+        assert!(config.validate().is_err());
+
+        config = ServerConfig::default();
+        config.data_dir = std::path::PathBuf::new();
+        assert!(config.validate().is_err());
+        config.in_memory = true;
+        assert!(config.validate().is_ok());
+
+        config.shutdown_timeout_secs = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_overrides_from_flags_and_env() {
+        let env = vec![
+            ("PHENIX_DB_TIERING_HOT_PROMOTION_THRESHOLD".to_string(), "150".to_string()),
+            ("PHENIX_DB_BELLMAN_COST_WEIGHTS_IO".to_string(), "0.5".to_string()),
+            ("PHENIX_DB_SERVER_IN_MEMORY".to_string(), "true".to_string()),
+            ("HOME".to_string(), "/root".to_string()),
+        ];
+        let overrides = PhenixConfig::env_overrides(env).unwrap();
+        assert_eq!(
+            overrides.iter().map(|(key, _)| key.as_str()).collect::<Vec<_>>(),
+            ["tiering.hot_promotion_threshold", "bellman.cost_weights.io", "server.in_memory"]
+        );
+
+        let config = PhenixConfig::default()
+            .with_overrides(overrides)
+            .unwrap()
+            .with_overrides([("api.rest_addr", "127.0.0.1:80"), ("server.data_dir", "/var/lib/phenix")])
+            .unwrap();
+        assert_eq!(config.tiering.hot_promotion_threshold, 150.0);
+        assert_eq!(config.bellman.cost_weights.io, 0.5);
+        assert!(config.server.in_memory);
+        assert_eq!(config.api.rest_addr, "127.0.0.1:80");
+        assert_eq!(config.server.data_dir, std::path::PathBuf::from("/var/lib/phenix"));
+        assert!(config.validate().is_ok());
+
+        let unknown = PhenixConfig::env_overrides(vec![("PHENIX_DB_API_REST_ADDRESS".to_string(), "x".to_string())]);
+        assert!(matches!(unknown, Err(ConfigError::EnvError(_))));
+        for (key, value) in [("api.rest_address", "x"), ("api.rest_addr.port", "1"), ("cdc.retained_events", "many")] {
+            assert!(PhenixConfig::default().with_overrides([(key, value)]).is_err(), "{}", key);
+        }
+    }
+
     #[test]
     fn test_config_serialization() {
         let config = PhenixConfig::default();
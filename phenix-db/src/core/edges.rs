@@ -3,6 +3,7 @@
 // Edges represent relationships between entities that evolve based on access patterns.
 // The probability field is updated using Kolmogorov probability theory.
 
+use crate::concurrency::atomic_ops::AtomicF32;
 use crate::core::types::EntityId;
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicU64, Ordering};
@@ -43,8 +44,8 @@ pub struct Edge {
     
     /// Probabilistic weight learned from access patterns
     /// Range: [0.0, 1.0]
-    /// Updated on co-access within 100ms time window
-    pub probability: f32,
+    /// Updated on co-access within 100ms time window (atomic for lock-free updates)
+    pub probability: AtomicF32,
     
     /// Access count for learning (atomic for lock-free updates)
     /// Note: Serialization converts to u64, deserialization creates new AtomicU64
@@ -72,7 +73,7 @@ impl Clone for Edge {
             label: self.label.clone(),
             weight: self.weight,
             metadata: self.metadata.clone(),
-            probability: self.probability,
+            probability: self.probability.clone(),
             access_count: AtomicU64::new(self.access_count.load(Ordering::SeqCst)),
             last_accessed: AtomicU64::new(self.last_accessed.load(Ordering::SeqCst)),
         }
@@ -107,7 +108,7 @@ impl Edge {
             label,
             weight,
             metadata,
-            probability: weight, // Initialize probability to weight
+            probability: AtomicF32::new(weight), // Initialize probability to weight
             access_count: AtomicU64::new(0),
             last_accessed: AtomicU64::new(current_timestamp_ms()),
         }
@@ -132,7 +133,17 @@ impl Edge {
         self.last_accessed.load(Ordering::SeqCst)
     }
 
-    /// Update probability based on learning algorithm
+    /// Get the current probability
+    pub fn probability(&self) -> f32 {
+        self.probability.load(Ordering::SeqCst)
+    }
+
+    /// Overwrite the probability, clamped to [0.0, 1.0]
+    pub fn set_probability(&self, probability: f32) {
+        self.probability.store(probability.clamp(0.0, 1.0), Ordering::SeqCst);
+    }
+
+    /// Update probability based on learning algorithm (lock-free)
     /// 
     /// Formula: w_new = w_old + α * (target - w_old)
     /// 
@@ -142,15 +153,15 @@ impl Edge {
     /// 
     /// # Returns
     /// * New probability value
-    pub fn update_probability(&mut self, target: f32, learning_rate: f32) -> f32 {
+    pub fn update_probability(&self, target: f32, learning_rate: f32) -> f32 {
         let target = target.clamp(0.0, 1.0);
         let learning_rate = learning_rate.clamp(0.0, 1.0);
         
-        // Apply learning update
-        self.probability = self.probability + learning_rate * (target - self.probability);
-        self.probability = self.probability.clamp(0.0, 1.0);
-        
-        self.probability
+        // Apply learning update as a CAS loop so concurrent updates compose
+        let (_, new) = self.probability.update(Ordering::SeqCst, |p| {
+            (p + learning_rate * (target - p)).clamp(0.0, 1.0)
+        });
+        new
     }
 
     /// Check if edge should be pruned based on threshold and inactivity
@@ -167,7 +178,7 @@ impl Edge {
     /// * true if edge should be pruned
     pub fn should_prune(&self, threshold: f32, inactivity_days: u64) -> bool {
         // Check probability threshold
-        if self.probability < threshold {
+        if self.probability() < threshold {
             return true;
         }
         
@@ -183,7 +194,7 @@ impl Edge {
         false
     }
 
-    /// Decay probability over time (for edges not accessed)
+    /// Decay probability over time (for edges not accessed, lock-free)
     /// 
     /// Applies exponential decay: p_new = p_old * exp(-λ * Δt)
     /// 
@@ -192,17 +203,17 @@ impl Edge {
     /// 
     /// # Returns
     /// * New probability after decay
-    pub fn apply_decay(&mut self, decay_rate: f32) -> f32 {
+    pub fn apply_decay(&self, decay_rate: f32) -> f32 {
         let last_accessed = self.get_last_accessed();
         let current = current_timestamp_ms();
         let days_elapsed = (current - last_accessed) as f32 / (24.0 * 60.0 * 60.0 * 1000.0);
         
         // Exponential decay
         let decay_factor = (-decay_rate * days_elapsed).exp();
-        self.probability *= decay_factor;
-        self.probability = self.probability.clamp(0.0, 1.0);
-        
-        self.probability
+        let (_, new) = self.probability.update(Ordering::SeqCst, |p| {
+            (p * decay_factor).clamp(0.0, 1.0)
+        });
+        new
     }
 }
 
@@ -251,7 +262,7 @@ mod tests {
         assert_eq!(edge.target_id, target);
         assert_eq!(edge.label, "related_to");
         assert_eq!(edge.weight, 0.5);
-        assert_eq!(edge.probability, 0.5);
+        assert_eq!(edge.probability(), 0.5);
         assert_eq!(edge.get_access_count(), 0);
     }
 
@@ -276,7 +287,7 @@ mod tests {
 
     #[test]
     fn test_update_probability() {
-        let mut edge = Edge::new(
+        let edge = Edge::new(
             EntityId::new(),
             EntityId::new(),
             "test".to_string(),
@@ -288,12 +299,12 @@ mod tests {
         // new = 0.5 + 0.1 * (0.8 - 0.5) = 0.5 + 0.03 = 0.53
         let new_prob = edge.update_probability(0.8, 0.1);
         assert!((new_prob - 0.53).abs() < 1e-6);
-        assert_eq!(edge.probability, new_prob);
+        assert_eq!(edge.probability(), new_prob);
     }
 
     #[test]
     fn test_probability_bounds() {
-        let mut edge = Edge::new(
+        let edge = Edge::new(
             EntityId::new(),
             EntityId::new(),
             "test".to_string(),
@@ -303,15 +314,15 @@ mod tests {
         
         // Try to update beyond bounds
         edge.update_probability(1.5, 0.5); // Should clamp to 1.0
-        assert!(edge.probability <= 1.0);
+        assert!(edge.probability() <= 1.0);
         
         edge.update_probability(-0.5, 0.5); // Should clamp to 0.0
-        assert!(edge.probability >= 0.0);
+        assert!(edge.probability() >= 0.0);
     }
 
     #[test]
     fn test_should_prune_threshold() {
-        let mut edge = Edge::new(
+        let edge = Edge::new(
             EntityId::new(),
             EntityId::new(),
             "test".to_string(),
@@ -319,19 +330,19 @@ mod tests {
             None,
         );
         
-        edge.probability = 0.005;
+        edge.set_probability(0.005);
         
         // Should prune if below threshold
         assert!(edge.should_prune(0.01, 30));
         
         // Should not prune if above threshold
-        edge.probability = 0.02;
+        edge.set_probability(0.02);
         assert!(!edge.should_prune(0.01, 30));
     }
 
     #[test]
     fn test_apply_decay() {
-        let mut edge = Edge::new(
+        let edge = Edge::new(
             EntityId::new(),
             EntityId::new(),
             "test".to_string(),
@@ -339,7 +350,7 @@ mod tests {
             None,
         );
         
-        edge.probability = 1.0;
+        edge.set_probability(1.0);
         
         // Simulate 1 day passing by setting last_accessed to 1 day ago
         let one_day_ago = current_timestamp_ms() - (24 * 60 * 60 * 1000);
@@ -370,7 +381,38 @@ mod tests {
         assert_eq!(edge.source_id, deserialized.source_id);
         assert_eq!(edge.target_id, deserialized.target_id);
         assert_eq!(edge.label, deserialized.label);
-        assert_eq!(edge.probability, deserialized.probability);
+        assert_eq!(edge.probability(), deserialized.probability());
         assert_eq!(edge.get_access_count(), deserialized.get_access_count());
     }
+
+    #[test]
+    fn test_concurrent_probability_updates() {
+        let edge = std::sync::Arc::new(Edge::new(
+            EntityId::new(),
+            EntityId::new(),
+            "test".to_string(),
+            0.0,
+            None,
+        ));
+
+        // Learning rate 1.0 moves straight to the target, so every update
+        // leaves one of the targets; none is torn or lost mid-way
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let edge = std::sync::Arc::clone(&edge);
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        edge.update_probability(0.25 * (i + 1) as f32, 1.0);
+                        edge.record_access();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert!([0.25, 0.5, 0.75, 1.0].contains(&edge.probability()));
+        assert_eq!(edge.get_access_count(), 4000);
+    }
 }
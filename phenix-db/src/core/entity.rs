@@ -4,7 +4,12 @@
 // - Vector embeddings (high-dimensional representations)
 // - Metadata (flexible JSONB)
 // - Edges (probabilistic relationships)
-// - Memory substrate fields (polynomial embeddings, access statistics)
+// - Memory substrate fields (polynomial embeddings, compression metadata)
+//
+// Access statistics are not stored on the entity: reads record them in
+// concurrency::access_tracker so that reading never mutates an entity.
+// Entities logged before that carry an `access_statistics` field, which
+// deserialization ignores, so older write-ahead logs and segments still load.
 
 use crate::core::edges::Edge;
 use crate::core::types::EntityId;
@@ -44,9 +49,14 @@ pub struct Entity {
     /// Last update timestamp (Unix epoch milliseconds)
     pub updated_at: u64,
     
-    /// MVCC version for concurrency control
+    /// Write counter, bumped by every update through the API
     pub version: u64,
     
+    /// Commit timestamp of this state in its collection's MVCC engine
+    /// (0 until committed)
+    #[serde(default)]
+    pub commit_ts: u64,
+    
     /// Current memory tier (hot/warm/cold)
     pub tier: MemoryTier,
     
@@ -57,9 +67,6 @@ pub struct Entity {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub polynomial_embedding: Option<PolynomialEmbedding>,
     
-    /// Access statistics for adaptive learning
-    pub access_statistics: AccessStatistics,
-    
     /// Compression metadata for KCE (Kolmogorov Compression Engine)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub compression_metadata: Option<CompressionMetadata>,
@@ -90,33 +97,28 @@ impl Entity {
             created_at: now,
             updated_at: now,
             version: 1,
+            commit_ts: 0,
             tier: MemoryTier::Hot, // New entities start in hot tier
             polynomial_embedding: None,
-            access_statistics: AccessStatistics::new(),
             compression_metadata: None,
         }
     }
 
-    /// Record an access to this entity
-    /// 
-    /// Updates access statistics for adaptive learning and tiering decisions.
-    pub fn record_access(&mut self) {
-        self.access_statistics.record_access();
-        self.updated_at = current_timestamp_ms();
-    }
-
     /// Check if entity should be promoted to a higher tier
     /// 
     /// Promotion thresholds:
     /// - Cold → Warm: 10 accesses/hour
     /// - Warm → Hot: 100 accesses/hour
-    pub fn should_promote(&self) -> bool {
+    /// 
+    /// # Arguments
+    /// * `stats` - Access statistics of this entity, from the access tracker
+    pub fn should_promote(&self, stats: &AccessStatistics) -> bool {
         match self.tier {
             MemoryTier::Cold => {
-                self.access_statistics.access_frequency >= 10.0
+                stats.access_frequency >= 10.0
             }
             MemoryTier::Warm => {
-                self.access_statistics.access_frequency >= 100.0
+                stats.access_frequency >= 100.0
             }
             MemoryTier::Hot => false, // Already at highest tier
         }
@@ -127,15 +129,18 @@ impl Entity {
     /// Demotion thresholds:
     /// - Hot → Warm: <1 access/hour for 24 hours
     /// - Warm → Cold: <0.1 access/hour for 7 days
-    pub fn should_demote(&self) -> bool {
-        let hours_since_access = self.access_statistics.hours_since_last_access();
+    /// 
+    /// # Arguments
+    /// * `stats` - Access statistics of this entity, from the access tracker
+    pub fn should_demote(&self, stats: &AccessStatistics) -> bool {
+        let hours_since_access = stats.hours_since_last_access();
         
         match self.tier {
             MemoryTier::Hot => {
-                self.access_statistics.access_frequency < 1.0 && hours_since_access >= 24.0
+                stats.access_frequency < 1.0 && hours_since_access >= 24.0
             }
             MemoryTier::Warm => {
-                self.access_statistics.access_frequency < 0.1 && hours_since_access >= 168.0 // 7 days
+                stats.access_frequency < 0.1 && hours_since_access >= 168.0 // 7 days
             }
             MemoryTier::Cold => false, // Already at lowest tier
         }
@@ -221,7 +226,9 @@ impl MemoryTier {
     }
 }
 
-/// AccessStatistics tracks entity access patterns for adaptive learning
+/// AccessStatistics is a point-in-time view of an entity's access patterns
+/// 
+/// Produced by `AccessTracker::stats`, which maintains the live counters.
 /// 
 /// Used for:
 /// - Tier promotion/demotion decisions
@@ -242,7 +249,7 @@ pub struct AccessStatistics {
     /// Computed using exponential moving average
     pub access_frequency: f32,
     
-    /// Entities frequently co-accessed with this entity
+    /// Entities recently co-accessed with this entity, most recent first
     /// Used for PGM edge weight updates and semantic clustering
     pub co_access_entities: Vec<EntityId>,
 }
@@ -258,47 +265,10 @@ impl AccessStatistics {
         }
     }
 
-    /// Record an access and update statistics
-    /// 
-    /// Updates:
-    /// - Increments total_accesses
-    /// - Updates last_access timestamp
-    /// - Recalculates access_frequency using exponential moving average
-    pub fn record_access(&mut self) {
-        let now = current_timestamp_ms();
-        let hours_elapsed = (now - self.last_access) as f32 / (60.0 * 60.0 * 1000.0);
-        
-        self.total_accesses += 1;
-        self.last_access = now;
-        
-        // Update access frequency using exponential moving average
-        // α = 0.1 (smoothing factor)
-        if hours_elapsed > 0.0 {
-            let instant_frequency = 1.0 / hours_elapsed;
-            self.access_frequency = 0.9 * self.access_frequency + 0.1 * instant_frequency;
-        }
-    }
-
-    /// Record a co-access with another entity
-    /// 
-    /// Used for PGM edge weight updates and semantic clustering.
-    /// Maintains a limited history (max 100 entities).
-    pub fn record_co_access(&mut self, entity_id: EntityId) {
-        // Add to co-access list if not already present
-        if !self.co_access_entities.contains(&entity_id) {
-            self.co_access_entities.push(entity_id);
-            
-            // Limit history to 100 entities
-            if self.co_access_entities.len() > 100 {
-                self.co_access_entities.remove(0);
-            }
-        }
-    }
-
     /// Get hours since last access
     pub fn hours_since_last_access(&self) -> f32 {
         let now = current_timestamp_ms();
-        (now - self.last_access) as f32 / (60.0 * 60.0 * 1000.0)
+        now.saturating_sub(self.last_access) as f32 / (60.0 * 60.0 * 1000.0)
     }
 
     /// Check if entity is stagnant (no access for 90 days)
@@ -388,6 +358,14 @@ fn current_timestamp_ms() -> u64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::concurrency::access_tracker::AccessTracker;
+
+    /// An entity as logged before access statistics moved to the tracker
+    const LEGACY_ENTITY: &str = r#"{"id":"01a1502a-3546-7654-a937-55c090f206c4",
+        "vector":{"dimensions":2,"values":[1.0,0.5],"norm":1.118034},"metadata":{"lang":"en"},
+        "created_at":1792346305862,"updated_at":1792346305862,"version":1,"tier":"Hot",
+        "access_statistics":{"total_accesses":1,"last_access":1792346305862,"access_frequency":0.0,
+        "co_access_entities":[]}}"#;
 
     #[test]
     fn test_entity_creation() {
@@ -404,53 +382,80 @@ mod tests {
         assert!(entity.metadata.is_some());
         assert_eq!(entity.version, 1);
         assert_eq!(entity.tier, MemoryTier::Hot);
-        assert_eq!(entity.access_statistics.total_accesses, 0);
     }
 
     #[test]
     fn test_record_access() {
-        let mut entity = Entity::new(None, None, None);
-        
-        assert_eq!(entity.access_statistics.total_accesses, 0);
-        
-        entity.record_access();
-        assert_eq!(entity.access_statistics.total_accesses, 1);
-        
-        entity.record_access();
-        assert_eq!(entity.access_statistics.total_accesses, 2);
+        let entity = Entity::new(None, None, None);
+        let before = serde_json::to_value(&entity).unwrap();
+        let tracker = AccessTracker::new();
+        assert!(tracker.stats(&entity.id).is_none());
+
+        tracker.record_access(entity.id);
+        assert_eq!(tracker.stats(&entity.id).unwrap().total_accesses, 1);
+        tracker.record_access(entity.id);
+        assert_eq!(tracker.stats(&entity.id).unwrap().total_accesses, 2);
+
+        // Reads leave the entity itself untouched
+        assert_eq!(serde_json::to_value(&entity).unwrap(), before);
+    }
+
+    #[test]
+    fn test_access_statistics() {
+        let stats = AccessStatistics::new();
+        assert_eq!(stats.total_accesses, 0);
+        assert_eq!(stats.access_frequency, 0.0);
+        assert!(stats.co_access_entities.is_empty());
+
+        let tracker = AccessTracker::new();
+        let (a, b) = (EntityId::new(), EntityId::new());
+        tracker.record_co_access(a, b);
+        assert_eq!(tracker.stats(&a).unwrap().co_access_entities, vec![b]);
+        assert_eq!(tracker.stats(&b).unwrap().co_access_entities, vec![a]);
+    }
+
+    #[test]
+    fn test_legacy_access_statistics_are_ignored() {
+        let entity: Entity = serde_json::from_str(LEGACY_ENTITY).unwrap();
+        assert_eq!(entity.id.to_string(), "01a1502a-3546-7654-a937-55c090f206c4");
+        assert_eq!(entity.vector.as_ref().unwrap().values, vec![1.0, 0.5]);
+        assert_eq!(entity.tier, MemoryTier::Hot);
+        assert!(serde_json::to_value(&entity).unwrap().get("access_statistics").is_none());
     }
 
     #[test]
     fn test_tier_promotion() {
         let mut entity = Entity::new(None, None, None);
+        let mut stats = AccessStatistics::new();
         entity.tier = MemoryTier::Cold;
-        entity.access_statistics.access_frequency = 15.0; // Above cold→warm threshold
+        stats.access_frequency = 15.0; // Above cold→warm threshold
         
-        assert!(entity.should_promote());
+        assert!(entity.should_promote(&stats));
         
         entity.promote();
         assert_eq!(entity.tier, MemoryTier::Warm);
         
-        entity.access_statistics.access_frequency = 150.0; // Above warm→hot threshold
-        assert!(entity.should_promote());
+        stats.access_frequency = 150.0; // Above warm→hot threshold
+        assert!(entity.should_promote(&stats));
         
         entity.promote();
         assert_eq!(entity.tier, MemoryTier::Hot);
         
         // Can't promote from hot
-        assert!(!entity.should_promote());
+        assert!(!entity.should_promote(&stats));
     }
 
     #[test]
     fn test_tier_demotion() {
         let mut entity = Entity::new(None, None, None);
+        let mut stats = AccessStatistics::new();
         entity.tier = MemoryTier::Hot;
-        entity.access_statistics.access_frequency = 0.5; // Below hot→warm threshold
+        stats.access_frequency = 0.5; // Below hot→warm threshold
         
         // Simulate 24 hours passing
-        entity.access_statistics.last_access = current_timestamp_ms() - (24 * 60 * 60 * 1000);
+        stats.last_access = current_timestamp_ms() - (24 * 60 * 60 * 1000);
         
-        assert!(entity.should_demote());
+        assert!(entity.should_demote(&stats));
         
         entity.demote();
         assert_eq!(entity.tier, MemoryTier::Warm);
@@ -463,23 +468,6 @@ mod tests {
         assert!(MemoryTier::Cold.latency_characteristics() < Duration::from_millis(100));
     }
 
-    #[test]
-    fn test_access_statistics() {
-        let mut stats = AccessStatistics::new();
-        
-        assert_eq!(stats.total_accesses, 0);
-        assert_eq!(stats.access_frequency, 0.0);
-        
-        stats.record_access();
-        assert_eq!(stats.total_accesses, 1);
-        
-        // Test co-access recording
-        let entity_id = EntityId::new();
-        stats.record_co_access(entity_id);
-        assert_eq!(stats.co_access_entities.len(), 1);
-        assert_eq!(stats.co_access_entities[0], entity_id);
-    }
-
     #[test]
     fn test_stagnant_detection() {
         let mut stats = AccessStatistics::new();
@@ -33,6 +33,12 @@ pub type LearningResult<T> = std::result::Result<T, LearningError>;
 /// Result type alias for concurrency operations
 pub type ConcurrencyResult<T> = std::result::Result<T, ConcurrencyError>;
 
+/// Result type alias for metadata operations
+pub type MetadataResult<T> = std::result::Result<T, MetadataError>;
+
+/// Result type alias for collection catalog operations
+pub type CollectionResult<T> = std::result::Result<T, CollectionError>;
+
 /// Correlation ID for distributed tracing
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct CorrelationId(Uuid);
@@ -141,56 +147,110 @@ pub enum MemorySubstrateError {
     #[error("Polynomial error: {error}")]
     Polynomial {
         error: PolynomialError,
-        context: Option<ErrorContext>,
+        context: Option<Box<ErrorContext>>,
     },
 
     /// Probabilistic graph operation errors
     #[error("Graph error: {error}")]
     Graph {
         error: GraphError,
-        context: Option<ErrorContext>,
+        context: Option<Box<ErrorContext>>,
     },
 
     /// Compression operation errors
     #[error("Compression error: {error}")]
     Compression {
         error: CompressionError,
-        context: Option<ErrorContext>,
+        context: Option<Box<ErrorContext>>,
     },
 
     /// Consensus operation errors
     #[error("Consensus error: {error}")]
     Consensus {
         error: ConsensusError,
-        context: Option<ErrorContext>,
+        context: Option<Box<ErrorContext>>,
     },
 
     /// Memory tier operation errors
     #[error("Tier error: {error}")]
     Tier {
         error: TierError,
-        context: Option<ErrorContext>,
+        context: Option<Box<ErrorContext>>,
     },
 
     /// Learning algorithm errors
     #[error("Learning error: {error}")]
     Learning {
         error: LearningError,
-        context: Option<ErrorContext>,
+        context: Option<Box<ErrorContext>>,
     },
 
     /// Concurrency control errors
     #[error("Concurrency error: {error}")]
     Concurrency {
         error: ConcurrencyError,
-        context: Option<ErrorContext>,
+        context: Option<Box<ErrorContext>>,
+    },
+
+    /// Metadata filtering and indexing errors
+    #[error("Metadata error: {error}")]
+    Metadata {
+        /// Underlying metadata error
+        error: MetadataError,
+        /// Optional error context
+        context: Option<Box<ErrorContext>>,
+    },
+
+    /// Collection catalog errors
+    #[error("Collection error: {error}")]
+    Collection {
+        /// Underlying collection error
+        error: CollectionError,
+        /// Optional error context
+        context: Option<Box<ErrorContext>>,
+    },
+
+    /// Cognitive query language errors
+    #[error("Query error: {error}")]
+    Query {
+        /// Underlying query error
+        error: QueryError,
+        /// Optional error context
+        context: Option<Box<ErrorContext>>,
+    },
+
+    /// Change data capture errors
+    #[error("Change capture error: {error}")]
+    Cdc {
+        /// Underlying change capture error
+        error: CdcError,
+        /// Optional error context
+        context: Option<Box<ErrorContext>>,
+    },
+
+    /// Bulk import and export errors
+    #[error("Bulk load error: {error}")]
+    Bulk {
+        /// Underlying bulk error
+        error: BulkError,
+        /// Optional error context
+        context: Option<Box<ErrorContext>>,
     },
 
     /// Mathematical invariant violations
     #[error("Invariant violation: {message}")]
     InvariantViolation {
         message: String,
-        context: ErrorContext,
+        context: Box<ErrorContext>,
+    },
+
+    /// Vector dimensionality does not match the target index or collection
+    #[error("Dimension mismatch: expected={expected}, actual={actual}")]
+    DimensionMismatch {
+        /// Dimensionality required by the target
+        expected: usize,
+        /// Dimensionality that was supplied
+        actual: usize,
     },
 
     /// I/O errors
@@ -201,6 +261,10 @@ pub enum MemorySubstrateError {
     #[error("Serialization error: {0}")]
     Serialization(String),
 
+    /// A client request that could not be decoded
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+
     /// Configuration errors
     #[error("Configuration error: {0}")]
     Configuration(String),
@@ -274,37 +338,103 @@ impl From<ConcurrencyError> for MemorySubstrateError {
     }
 }
 
+impl From<MetadataError> for MemorySubstrateError {
+    fn from(error: MetadataError) -> Self {
+        Self::Metadata {
+            error,
+            context: None,
+        }
+    }
+}
+
+impl From<CollectionError> for MemorySubstrateError {
+    fn from(error: CollectionError) -> Self {
+        Self::Collection {
+            error,
+            context: None,
+        }
+    }
+}
+
+impl From<QueryError> for MemorySubstrateError {
+    fn from(error: QueryError) -> Self {
+        Self::Query {
+            error,
+            context: None,
+        }
+    }
+}
+
+impl From<CdcError> for MemorySubstrateError {
+    fn from(error: CdcError) -> Self {
+        Self::Cdc {
+            error,
+            context: None,
+        }
+    }
+}
+
+impl From<BulkError> for MemorySubstrateError {
+    fn from(error: BulkError) -> Self {
+        Self::Bulk {
+            error,
+            context: None,
+        }
+    }
+}
+
 impl MemorySubstrateError {
     /// Add context to the error
     pub fn with_context(self, context: ErrorContext) -> Self {
+        let context = Some(Box::new(context));
         match self {
             Self::Polynomial { error, .. } => Self::Polynomial {
                 error,
-                context: Some(context),
+                context,
             },
             Self::Graph { error, .. } => Self::Graph {
                 error,
-                context: Some(context),
+                context,
             },
             Self::Compression { error, .. } => Self::Compression {
                 error,
-                context: Some(context),
+                context,
             },
             Self::Consensus { error, .. } => Self::Consensus {
                 error,
-                context: Some(context),
+                context,
             },
             Self::Tier { error, .. } => Self::Tier {
                 error,
-                context: Some(context),
+                context,
             },
             Self::Learning { error, .. } => Self::Learning {
                 error,
-                context: Some(context),
+                context,
             },
             Self::Concurrency { error, .. } => Self::Concurrency {
                 error,
-                context: Some(context),
+                context,
+            },
+            Self::Metadata { error, .. } => Self::Metadata {
+                error,
+                context,
+            },
+            Self::Collection { error, .. } => Self::Collection {
+                error,
+                context,
+            },
+            Self::Query { error, .. } => Self::Query {
+                error,
+                context,
+            },
+            Self::Cdc { error, .. } => Self::Cdc {
+                error,
+                context,
+            },
+            Self::Bulk { error, .. } => Self::Bulk {
+                error,
+                context,
             },
             other => other,
         }
@@ -319,7 +449,12 @@ impl MemorySubstrateError {
             | Self::Consensus { context, .. }
             | Self::Tier { context, .. }
             | Self::Learning { context, .. }
-            | Self::Concurrency { context, .. } => {
+            | Self::Concurrency { context, .. }
+            | Self::Metadata { context, .. }
+            | Self::Collection { context, .. }
+            | Self::Query { context, .. }
+            | Self::Cdc { context, .. }
+            | Self::Bulk { context, .. } => {
                 context.as_ref().map(|c| c.correlation_id)
             }
             Self::InvariantViolation { context, .. } => Some(context.correlation_id),
@@ -337,9 +472,16 @@ impl MemorySubstrateError {
             Self::Tier { error, .. } => error.recovery_strategy(),
             Self::Learning { error, .. } => error.recovery_strategy(),
             Self::Concurrency { error, .. } => error.recovery_strategy(),
+            Self::Metadata { error, .. } => error.recovery_strategy(),
+            Self::Collection { error, .. } => error.recovery_strategy(),
+            Self::Query { .. } => RecoveryStrategy::Abort,
+            Self::Cdc { .. } => RecoveryStrategy::Abort,
+            Self::Bulk { .. } => RecoveryStrategy::Abort,
             Self::InvariantViolation { .. } => RecoveryStrategy::Abort,
+            Self::DimensionMismatch { .. } => RecoveryStrategy::Abort,
             Self::Io(_) => RecoveryStrategy::Retry,
             Self::Serialization(_) => RecoveryStrategy::Abort,
+            Self::InvalidRequest(_) => RecoveryStrategy::Abort,
             Self::Configuration(_) => RecoveryStrategy::Abort,
             Self::Internal(_) => RecoveryStrategy::Abort,
         }
@@ -417,6 +559,13 @@ pub enum GraphError {
     /// Co-access detection failed
     #[error("Co-access detection failed: {reason}")]
     CoAccessDetectionFailed { reason: String },
+
+    /// Edge endpoint does not exist
+    #[error("Edge endpoint not found: {entity_id}")]
+    EndpointNotFound {
+        /// Missing entity
+        entity_id: String,
+    },
 }
 
 impl GraphError {
@@ -429,6 +578,7 @@ impl GraphError {
             Self::TraversalFailed { .. } => RecoveryStrategy::Fallback,
             Self::CycleDetected => RecoveryStrategy::Skip,
             Self::CoAccessDetectionFailed { .. } => RecoveryStrategy::Skip,
+            Self::EndpointNotFound { .. } => RecoveryStrategy::Propagate,
         }
     }
 }
@@ -509,6 +659,22 @@ mod tests {
         assert_eq!(error.recovery_strategy(), RecoveryStrategy::Abort);
     }
 
+    #[test]
+    fn test_metadata_error_recovery_strategy() {
+        let error = MetadataError::FieldNotIndexed {
+            path: "lang".to_string(),
+        };
+        assert_eq!(error.recovery_strategy(), RecoveryStrategy::Fallback);
+    }
+
+    #[test]
+    fn test_collection_error_recovery_strategy() {
+        let error = CollectionError::AlreadyExists {
+            name: "docs".to_string(),
+        };
+        assert_eq!(error.recovery_strategy(), RecoveryStrategy::Skip);
+    }
+
     #[test]
     fn test_memory_substrate_error_with_context() {
         let poly_error = PolynomialError::DegreeExceeded {
@@ -538,12 +704,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_error_stays_small() {
+        // Every `Result` carries this inline; contexts are boxed to keep it
+        // below clippy's `result_large_err` threshold
+        assert!(std::mem::size_of::<MemorySubstrateError>() <= 128);
+    }
+
     #[test]
     fn test_invariant_violation() {
         let context = ErrorContext::new("PGM", "normalize_probabilities");
         let error = MemorySubstrateError::InvariantViolation {
             message: "Probability sum != 1.0".to_string(),
-            context,
+            context: Box::new(context),
         };
         
         assert!(error.correlation_id().is_some());
@@ -623,6 +796,56 @@ pub enum ConsensusError {
     /// State synchronization failed
     #[error("State synchronization failed: {reason}")]
     SynchronizationFailed { reason: String },
+
+    /// Request reached a node that is not the leader
+    #[error("Not the leader (leader: {})", leader.as_deref().unwrap_or("unknown"))]
+    NotLeader {
+        /// Current leader, if known
+        leader: Option<String>,
+    },
+
+    /// Another membership change has not committed yet
+    #[error("Membership change pending at log index {index}")]
+    MembershipChangePending {
+        /// Log index of the uncommitted change
+        index: u64,
+    },
+
+    /// Replica has not yet applied the writes a read depends on
+    #[error("Replica behind: applied {applied}, required {required}")]
+    ReplicaBehind {
+        /// Version the read requires
+        required: String,
+
+        /// Version the replica has applied
+        applied: String,
+    },
+
+    /// Replica set size is outside the configured bounds
+    #[error("Invalid replica set: {replicas} replicas, allowed {min}..={max}")]
+    InvalidReplicaSet {
+        /// Requested number of replicas
+        replicas: usize,
+
+        /// Configured minimum
+        min: usize,
+
+        /// Configured maximum
+        max: usize,
+    },
+
+    /// Not enough healthy nodes or zones to place a replica set
+    #[error("Cannot place {replicas} replicas: {nodes} nodes available in {zones} zones")]
+    PlacementUnavailable {
+        /// Requested number of replicas
+        replicas: usize,
+
+        /// Healthy nodes available
+        nodes: usize,
+
+        /// Zones those nodes span
+        zones: usize,
+    },
 }
 
 impl ConsensusError {
@@ -634,6 +857,11 @@ impl ConsensusError {
             Self::QuorumNotReached { .. } => RecoveryStrategy::Retry,
             Self::CommunicationFailed { .. } => RecoveryStrategy::Retry,
             Self::SynchronizationFailed { .. } => RecoveryStrategy::Retry,
+            Self::NotLeader { .. } => RecoveryStrategy::Retry,
+            Self::MembershipChangePending { .. } => RecoveryStrategy::Retry,
+            Self::ReplicaBehind { .. } => RecoveryStrategy::Retry,
+            Self::InvalidReplicaSet { .. } => RecoveryStrategy::Propagate,
+            Self::PlacementUnavailable { .. } => RecoveryStrategy::Retry,
         }
     }
 }
@@ -755,6 +983,13 @@ pub enum ConcurrencyError {
     /// Retry limit exceeded
     #[error("Retry limit exceeded: {attempts} attempts")]
     RetryLimitExceeded { attempts: usize },
+
+    /// Transaction is not open: never begun, finished or expired
+    #[error("Transaction {id} not found")]
+    TransactionNotFound {
+        /// Id the client named
+        id: u64,
+    },
 }
 
 impl ConcurrencyError {
@@ -767,6 +1002,193 @@ impl ConcurrencyError {
             Self::VersionConflict { .. } => RecoveryStrategy::Retry,
             Self::SnapshotIsolationViolation { .. } => RecoveryStrategy::Abort,
             Self::RetryLimitExceeded { .. } => RecoveryStrategy::Abort,
+            Self::TransactionNotFound { .. } => RecoveryStrategy::Abort,
+        }
+    }
+}
+
+/// Metadata filtering and indexing errors
+#[derive(Debug, Error, Clone)]
+pub enum MetadataError {
+    /// Field path could not be parsed
+    #[error("Invalid field path '{path}': {reason}")]
+    InvalidPath {
+        /// Path as written
+        path: String,
+        /// Why parsing failed
+        reason: String,
+    },
+
+    /// Filter expression is malformed
+    #[error("Invalid filter: {reason}")]
+    InvalidFilter {
+        /// Why the filter is invalid
+        reason: String,
+    },
+
+    /// Field has no secondary index
+    #[error("Field not indexed: {path}")]
+    FieldNotIndexed {
+        /// Field path
+        path: String,
+    },
+
+    /// Document does not conform to the collection schema
+    #[error("Schema violation at '{path}': {reason}")]
+    SchemaViolation {
+        /// Offending field path
+        path: String,
+        /// What the schema expected
+        reason: String,
+    },
+}
+
+impl MetadataError {
+    /// Get the recommended recovery strategy
+    pub fn recovery_strategy(&self) -> RecoveryStrategy {
+        match self {
+            Self::InvalidPath { .. } => RecoveryStrategy::Abort,
+            Self::InvalidFilter { .. } => RecoveryStrategy::Abort,
+            Self::FieldNotIndexed { .. } => RecoveryStrategy::Fallback,
+            Self::SchemaViolation { .. } => RecoveryStrategy::Abort,
         }
     }
 }
+
+/// Collection catalog errors
+#[derive(Debug, Error, Clone)]
+pub enum CollectionError {
+    /// No collection or alias with this name
+    #[error("Collection not found: {name}")]
+    NotFound {
+        /// Requested name or alias
+        name: String,
+    },
+
+    /// Name is already taken by a collection or alias
+    #[error("Collection already exists: {name}")]
+    AlreadyExists {
+        /// Conflicting name
+        name: String,
+    },
+
+    /// Name does not satisfy the naming rules
+    #[error("Invalid collection name '{name}': {reason}")]
+    InvalidName {
+        /// Rejected name
+        name: String,
+        /// Rule that was violated
+        reason: String,
+    },
+
+    /// Collection configuration is invalid
+    #[error("Invalid collection config: {reason}")]
+    InvalidConfig {
+        /// What is wrong with the configuration
+        reason: String,
+    },
+
+    /// Entity cannot be stored in the collection
+    #[error("Invalid entity {entity_id}: {reason}")]
+    InvalidEntity {
+        /// Offending entity
+        entity_id: String,
+        /// Why it was rejected
+        reason: String,
+    },
+
+    /// No entity with this id in the collection
+    #[error("Entity {entity_id} not found in collection {collection}")]
+    EntityNotFound {
+        /// Collection that was searched
+        collection: String,
+        /// Requested entity
+        entity_id: String,
+    },
+
+    /// Entity id is already taken in the collection
+    #[error("Entity {entity_id} already exists in collection {collection}")]
+    EntityAlreadyExists {
+        /// Target collection
+        collection: String,
+        /// Conflicting entity
+        entity_id: String,
+    },
+}
+
+impl CollectionError {
+    /// Get the recommended recovery strategy
+    pub fn recovery_strategy(&self) -> RecoveryStrategy {
+        match self {
+            Self::NotFound { .. } => RecoveryStrategy::Propagate,
+            Self::AlreadyExists { .. } => RecoveryStrategy::Skip,
+            Self::InvalidName { .. } => RecoveryStrategy::Abort,
+            Self::InvalidConfig { .. } => RecoveryStrategy::Abort,
+            Self::InvalidEntity { .. } => RecoveryStrategy::Abort,
+            Self::EntityNotFound { .. } => RecoveryStrategy::Propagate,
+            Self::EntityAlreadyExists { .. } => RecoveryStrategy::Skip,
+        }
+    }
+}
+
+/// Cognitive query language errors
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum QueryError {
+    /// Query text could not be parsed
+    #[error("Syntax error at offset {position}: {message}")]
+    Syntax {
+        /// Byte offset into the query text
+        position: usize,
+        /// What was expected or found
+        message: String,
+    },
+
+    /// Query parsed but cannot be planned or executed
+    #[error("Invalid query: {reason}")]
+    Invalid {
+        /// Why the query was rejected
+        reason: String,
+    },
+}
+
+/// Change data capture errors
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum CdcError {
+    /// The requested events are older than anything still retained
+    #[error("Change events from sequence {requested} are no longer retained (oldest is {oldest})")]
+    Expired {
+        /// First sequence number asked for
+        requested: u64,
+        /// Oldest sequence number still available
+        oldest: u64,
+    },
+
+    /// A subscriber fell further behind than its buffer
+    #[error("Subscriber fell behind and missed {missed} change events")]
+    Lagged {
+        /// Events dropped for the subscriber
+        missed: u64,
+    },
+}
+
+/// Bulk import and export errors
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum BulkError {
+    /// The input is not a valid file of its format
+    #[error("Malformed {format} input: {reason}")]
+    Malformed {
+        /// File format
+        format: String,
+        /// What is wrong
+        reason: String,
+    },
+
+    /// The input uses a feature of its format that is not implemented
+    #[error("Unsupported {format} feature: {feature}")]
+    Unsupported {
+        /// File format
+        format: String,
+        /// Feature in use
+        feature: String,
+    },
+}
@@ -1,6 +1,1723 @@
 // JSONB metadata handling
 //
-// This module will be fully implemented in later tasks.
-// Placeholder for now to allow compilation.
+// Filter expressions over entity metadata and the secondary indexes that
+// answer them. Metadata is arbitrary JSON; filters address fields with
+// JSON paths (`lang`, `author.name`, `tags[0]`, `$["key.with.dots"]`).
+//
+// Secondary indexes are opt-in per field:
+// - Keyword: exact-match bitmaps per distinct scalar value (eq, ne, in)
+// - Numeric: ordered (value, slot) set for range scans (range, eq)
+// Predicates on unindexed fields fall back to scanning stored documents, so
+// every filter can be answered; indexes only make it faster.
+//
+// An optional `MetadataSchema` types the fields of a collection, rejects
+// non-conforming documents on insert and decides which indexes are built.
+
+use crate::core::error::{MetadataError, MetadataResult};
+use crate::core::types::EntityId;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+/// Number of documents sampled to estimate selectivity of unindexed predicates
+pub const SELECTIVITY_SAMPLE_SIZE: usize = 256;
+
+/// One step of a JSON path
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    /// Object member access
+    Key(String),
+
+    /// Array element access
+    Index(usize),
+}
+
+/// Parsed JSON path addressing a field inside entity metadata
+///
+/// Accepted syntax:
+/// - Dotted keys: `author.name`
+/// - Array indexes: `tags[0]`, `matrix[1][2]`
+/// - Quoted keys for names containing `.`, `[` or `"`: `["a.b"]`
+/// - An optional leading `$` or `$.` root marker
+///
+/// Paths serialize as their canonical string form.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct FieldPath {
+    segments: Vec<PathSegment>,
+}
+
+impl FieldPath {
+    /// Parse a path expression
+    ///
+    /// # Errors
+    /// * `InvalidPath` for empty paths, empty keys, bad indexes or
+    ///   unterminated brackets and quotes
+    pub fn parse(path: &str) -> MetadataResult<Self> {
+        let invalid = |reason: &str| MetadataError::InvalidPath {
+            path: path.to_string(),
+            reason: reason.to_string(),
+        };
+
+        let mut rest = path.strip_prefix('$').unwrap_or(path);
+        rest = rest.strip_prefix('.').unwrap_or(rest);
+        let chars: Vec<char> = rest.chars().collect();
+        let mut segments = Vec::new();
+        let mut i = 0;
+        let mut expect_key = true;
+
+        while i < chars.len() {
+            match chars[i] {
+                '[' => {
+                    i += 1;
+                    if chars.get(i) == Some(&'"') {
+                        i += 1;
+                        let mut key = String::new();
+                        loop {
+                            match chars.get(i) {
+                                None => return Err(invalid("unterminated quoted key")),
+                                Some('\\') => {
+                                    let escaped = chars.get(i + 1).ok_or_else(|| invalid("dangling escape"))?;
+                                    key.push(*escaped);
+                                    i += 2;
+                                }
+                                Some('"') => {
+                                    i += 1;
+                                    break;
+                                }
+                                Some(&c) => {
+                                    key.push(c);
+                                    i += 1;
+                                }
+                            }
+                        }
+                        if chars.get(i) != Some(&']') {
+                            return Err(invalid("expected ']' after quoted key"));
+                        }
+                        segments.push(PathSegment::Key(key));
+                    } else {
+                        let start = i;
+                        while i < chars.len() && chars[i] != ']' {
+                            i += 1;
+                        }
+                        if i == chars.len() {
+                            return Err(invalid("unterminated '['"));
+                        }
+                        let digits: String = chars[start..i].iter().collect();
+                        let index = digits
+                            .parse::<usize>()
+                            .map_err(|_| invalid("array index must be a non-negative integer"))?;
+                        segments.push(PathSegment::Index(index));
+                    }
+                    i += 1;
+                    expect_key = false;
+                }
+                '.' => {
+                    if expect_key {
+                        return Err(invalid("empty key"));
+                    }
+                    i += 1;
+                    expect_key = true;
+                    if i == chars.len() {
+                        return Err(invalid("trailing '.'"));
+                    }
+                }
+                _ => {
+                    if !expect_key {
+                        return Err(invalid("expected '.' or '[' between segments"));
+                    }
+                    let start = i;
+                    while i < chars.len() && chars[i] != '.' && chars[i] != '[' {
+                        if chars[i] == ']' || chars[i] == '"' {
+                            return Err(invalid("unexpected character in key"));
+                        }
+                        i += 1;
+                    }
+                    segments.push(PathSegment::Key(chars[start..i].iter().collect()));
+                    expect_key = false;
+                }
+            }
+        }
+
+        if segments.is_empty() {
+            return Err(invalid("path is empty"));
+        }
+        Ok(Self { segments })
+    }
+
+    /// Path segments from the root
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+
+    /// Path to the member `key` of the addressed object
+    pub fn child(&self, key: &str) -> Self {
+        let mut segments = self.segments.clone();
+        segments.push(PathSegment::Key(key.to_string()));
+        Self { segments }
+    }
+
+    /// Follow the path inside `value`
+    ///
+    /// # Returns
+    /// * The addressed value, or None if any step is missing
+    pub fn resolve<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.segments.iter().try_fold(value, |current, segment| match segment {
+            PathSegment::Key(key) => current.as_object()?.get(key),
+            PathSegment::Index(index) => current.as_array()?.get(*index),
+        })
+    }
+}
+
+impl fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+                PathSegment::Key(key) if is_plain_key(key) => {
+                    if i > 0 {
+                        f.write_str(".")?;
+                    }
+                    f.write_str(key)?;
+                }
+                PathSegment::Key(key) => {
+                    f.write_str("[\"")?;
+                    for c in key.chars() {
+                        if c == '"' || c == '\\' {
+                            f.write_str("\\")?;
+                        }
+                        write!(f, "{}", c)?;
+                    }
+                    f.write_str("\"]")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_plain_key(key: &str) -> bool {
+    !key.is_empty() && !key.starts_with('$') && !key.contains(['.', '[', ']', '"', '\\'])
+}
+
+impl FromStr for FieldPath {
+    type Err = MetadataError;
+
+    fn from_str(s: &str) -> MetadataResult<Self> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<String> for FieldPath {
+    type Error = MetadataError;
+
+    fn try_from(value: String) -> MetadataResult<Self> {
+        Self::parse(&value)
+    }
+}
+
+impl From<FieldPath> for String {
+    fn from(path: FieldPath) -> Self {
+        path.to_string()
+    }
+}
+
+/// One end of a range predicate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RangeBound {
+    /// Bound value (number or string)
+    pub value: Value,
+
+    /// Whether the bound value itself is included
+    #[serde(default = "default_inclusive")]
+    pub inclusive: bool,
+}
+
+fn default_inclusive() -> bool {
+    true
+}
+
+impl RangeBound {
+    /// Bound that includes `value`
+    pub fn inclusive(value: impl Into<Value>) -> Self {
+        Self {
+            value: value.into(),
+            inclusive: true,
+        }
+    }
+
+    /// Bound that excludes `value`
+    pub fn exclusive(value: impl Into<Value>) -> Self {
+        Self {
+            value: value.into(),
+            inclusive: false,
+        }
+    }
+}
+
+/// Boolean filter expression over entity metadata
+///
+/// Semantics:
+/// - `Eq`, `In` and `Range` match when the field equals / satisfies the
+///   predicate, or when the field is an array and any element does
+/// - Numbers compare numerically (`1` equals `1.0`); ranges compare numbers
+///   with numbers and strings with strings, never across types
+/// - `Ne` is the negation of `Eq`, so entities missing the field match
+/// - `Exists` matches any present, non-null field
+/// - An empty `And` matches everything, an empty `Or` matches nothing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum MetadataFilter {
+    /// Field equals value
+    Eq {
+        /// Field to test
+        field: FieldPath,
+        /// Expected value
+        value: Value,
+    },
+
+    /// Field does not equal value
+    Ne {
+        /// Field to test
+        field: FieldPath,
+        /// Rejected value
+        value: Value,
+    },
+
+    /// Field lies within the bounds (at least one bound required)
+    Range {
+        /// Field to test
+        field: FieldPath,
+        /// Lower bound
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        lower: Option<RangeBound>,
+        /// Upper bound
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        upper: Option<RangeBound>,
+    },
+
+    /// Field equals one of the values
+    In {
+        /// Field to test
+        field: FieldPath,
+        /// Accepted values
+        values: Vec<Value>,
+    },
+
+    /// Field is present and not null
+    Exists {
+        /// Field to test
+        field: FieldPath,
+    },
+
+    /// All sub-filters match
+    And {
+        /// Conjuncts
+        filters: Vec<MetadataFilter>,
+    },
+
+    /// At least one sub-filter matches
+    Or {
+        /// Disjuncts
+        filters: Vec<MetadataFilter>,
+    },
+
+    /// Sub-filter does not match
+    Not {
+        /// Negated filter
+        filter: Box<MetadataFilter>,
+    },
+}
+
+impl MetadataFilter {
+    /// `field == value`
+    pub fn eq(field: FieldPath, value: impl Into<Value>) -> Self {
+        Self::Eq {
+            field,
+            value: value.into(),
+        }
+    }
+
+    /// `field != value`
+    pub fn ne(field: FieldPath, value: impl Into<Value>) -> Self {
+        Self::Ne {
+            field,
+            value: value.into(),
+        }
+    }
+
+    /// `lower <= field <= upper` with optional, configurable bounds
+    pub fn range(field: FieldPath, lower: Option<RangeBound>, upper: Option<RangeBound>) -> Self {
+        Self::Range { field, lower, upper }
+    }
+
+    /// `field >= value`
+    pub fn gte(field: FieldPath, value: impl Into<Value>) -> Self {
+        Self::range(field, Some(RangeBound::inclusive(value)), None)
+    }
+
+    /// `field > value`
+    pub fn gt(field: FieldPath, value: impl Into<Value>) -> Self {
+        Self::range(field, Some(RangeBound::exclusive(value)), None)
+    }
+
+    /// `field <= value`
+    pub fn lte(field: FieldPath, value: impl Into<Value>) -> Self {
+        Self::range(field, None, Some(RangeBound::inclusive(value)))
+    }
+
+    /// `field < value`
+    pub fn lt(field: FieldPath, value: impl Into<Value>) -> Self {
+        Self::range(field, None, Some(RangeBound::exclusive(value)))
+    }
+
+    /// `field IN values`
+    pub fn in_values<V: Into<Value>>(field: FieldPath, values: impl IntoIterator<Item = V>) -> Self {
+        Self::In {
+            field,
+            values: values.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// `field` is present and not null
+    pub fn exists(field: FieldPath) -> Self {
+        Self::Exists { field }
+    }
+
+    /// Geo point inside a latitude/longitude bounding box
+    ///
+    /// Expands to ranges on the `lat` and `lon` members of `field`, which are
+    /// served by the numeric indexes built for indexed `GeoPoint` fields.
+    pub fn geo_bounding_box(field: FieldPath, min: (f64, f64), max: (f64, f64)) -> Self {
+        let (min_lat, min_lon) = min;
+        let (max_lat, max_lon) = max;
+        Self::And {
+            filters: vec![
+                Self::range(
+                    field.child("lat"),
+                    Some(RangeBound::inclusive(min_lat)),
+                    Some(RangeBound::inclusive(max_lat)),
+                ),
+                Self::range(
+                    field.child("lon"),
+                    Some(RangeBound::inclusive(min_lon)),
+                    Some(RangeBound::inclusive(max_lon)),
+                ),
+            ],
+        }
+    }
+
+    /// Conjunction of this filter and `other`
+    pub fn and(self, other: MetadataFilter) -> Self {
+        match self {
+            Self::And { mut filters } => {
+                filters.push(other);
+                Self::And { filters }
+            }
+            first => Self::And {
+                filters: vec![first, other],
+            },
+        }
+    }
+
+    /// Disjunction of this filter and `other`
+    pub fn or(self, other: MetadataFilter) -> Self {
+        match self {
+            Self::Or { mut filters } => {
+                filters.push(other);
+                Self::Or { filters }
+            }
+            first => Self::Or {
+                filters: vec![first, other],
+            },
+        }
+    }
+
+    /// Check that the expression is well formed
+    ///
+    /// # Errors
+    /// * `InvalidFilter` for ranges without bounds or with bounds that are not
+    ///   numbers or strings, or with bounds of different types
+    pub fn validate(&self) -> MetadataResult<()> {
+        match self {
+            Self::Range { field, lower, upper } => {
+                if lower.is_none() && upper.is_none() {
+                    return Err(MetadataError::InvalidFilter {
+                        reason: format!("range on '{}' has no bounds", field),
+                    });
+                }
+                let bounds: Vec<&Value> = lower.iter().chain(upper.iter()).map(|b| &b.value).collect();
+                if bounds.iter().any(|v| !v.is_number() && !v.is_string()) {
+                    return Err(MetadataError::InvalidFilter {
+                        reason: format!("range bounds on '{}' must be numbers or strings", field),
+                    });
+                }
+                if bounds.len() == 2 && bounds[0].is_number() != bounds[1].is_number() {
+                    return Err(MetadataError::InvalidFilter {
+                        reason: format!("range bounds on '{}' have different types", field),
+                    });
+                }
+                Ok(())
+            }
+            Self::And { filters } | Self::Or { filters } => filters.iter().try_for_each(|f| f.validate()),
+            Self::Not { filter } => filter.validate(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Evaluate the filter against an entity's metadata
+    pub fn matches(&self, metadata: Option<&Value>) -> bool {
+        match self {
+            Self::Eq { field, value } => field_matches(metadata, field, |v| values_equal(v, value)),
+            Self::Ne { field, value } => !field_matches(metadata, field, |v| values_equal(v, value)),
+            Self::Range { field, lower, upper } => field_matches(metadata, field, |v| {
+                in_range(v, lower.as_ref(), upper.as_ref())
+            }),
+            Self::In { field, values } => field_matches(metadata, field, |v| {
+                values.iter().any(|candidate| values_equal(v, candidate))
+            }),
+            Self::Exists { field } => metadata
+                .and_then(|m| field.resolve(m))
+                .is_some_and(|v| !v.is_null()),
+            Self::And { filters } => filters.iter().all(|f| f.matches(metadata)),
+            Self::Or { filters } => filters.iter().any(|f| f.matches(metadata)),
+            Self::Not { filter } => !filter.matches(metadata),
+        }
+    }
+}
+
+impl std::ops::Not for MetadataFilter {
+    type Output = MetadataFilter;
+
+    fn not(self) -> Self::Output {
+        match self {
+            Self::Not { filter } => *filter,
+            other => Self::Not {
+                filter: Box::new(other),
+            },
+        }
+    }
+}
+
+/// Apply `predicate` to the field value, or to each element if it is an array
+fn field_matches<F>(metadata: Option<&Value>, field: &FieldPath, predicate: F) -> bool
+where
+    F: Fn(&Value) -> bool,
+{
+    let Some(value) = metadata.and_then(|m| field.resolve(m)) else {
+        return false;
+    };
+    match value {
+        Value::Array(items) => predicate(value) || items.iter().any(&predicate),
+        _ => predicate(value),
+    }
+}
+
+/// JSON equality with numeric normalization (`1 == 1.0`)
+fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64() == y.as_f64(),
+        _ => a == b,
+    }
+}
+
+/// Order two scalars of the same kind (numbers or strings)
+fn compare_scalars(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64()?.partial_cmp(&y.as_f64()?),
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        _ => None,
+    }
+}
+
+fn in_range(value: &Value, lower: Option<&RangeBound>, upper: Option<&RangeBound>) -> bool {
+    let lower_ok = lower.is_none_or(|bound| match compare_scalars(value, &bound.value) {
+        Some(Ordering::Greater) => true,
+        Some(Ordering::Equal) => bound.inclusive,
+        _ => false,
+    });
+    let upper_ok = upper.is_none_or(|bound| match compare_scalars(value, &bound.value) {
+        Some(Ordering::Less) => true,
+        Some(Ordering::Equal) => bound.inclusive,
+        _ => false,
+    });
+    lower_ok && upper_ok
+}
+
+/// Map an f64 to a u64 with the same total order
+fn ordered_bits(value: f64) -> u64 {
+    // Fold -0.0 into 0.0 so both land on the same key
+    let bits = if value == 0.0 { 0.0f64.to_bits() } else { value.to_bits() };
+    if bits >> 63 == 1 {
+        !bits
+    } else {
+        bits | (1 << 63)
+    }
+}
+
+/// Canonical posting key of a scalar value for keyword indexes
+fn keyword_key(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(format!("s:{}", s)),
+        Value::Number(n) => n.as_f64().map(|f| format!("n:{:016x}", ordered_bits(f))),
+        Value::Bool(b) => Some(format!("b:{}", b)),
+        _ => None,
+    }
+}
+
+/// Scalar values stored at a field: the value itself, or each array element
+fn scalar_values<'a>(metadata: Option<&'a Value>, field: &FieldPath) -> Vec<&'a Value> {
+    match metadata.and_then(|m| field.resolve(m)) {
+        Some(Value::Array(items)) => items.iter().collect(),
+        Some(value) => vec![value],
+        None => Vec::new(),
+    }
+}
+
+/// Dense bitmap over document slots
+///
+/// Slots are small consecutive integers assigned by `MetadataIndex`, so a
+/// plain word vector is both compact and fast for intersection and union.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bitmap {
+    words: Vec<u64>,
+}
+
+impl Bitmap {
+    /// Create an empty bitmap
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set `slot`, returning whether it was newly set
+    pub fn insert(&mut self, slot: u32) -> bool {
+        let (word, bit) = (slot as usize / 64, slot % 64);
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        let was_set = self.words[word] & (1 << bit) != 0;
+        self.words[word] |= 1 << bit;
+        !was_set
+    }
+
+    /// Clear `slot`, returning whether it was set
+    pub fn remove(&mut self, slot: u32) -> bool {
+        let (word, bit) = (slot as usize / 64, slot % 64);
+        match self.words.get_mut(word) {
+            Some(w) if *w & (1 << bit) != 0 => {
+                *w &= !(1 << bit);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether `slot` is set
+    #[inline]
+    pub fn contains(&self, slot: u32) -> bool {
+        self.words
+            .get(slot as usize / 64)
+            .is_some_and(|w| w & (1 << (slot % 64)) != 0)
+    }
+
+    /// Number of set slots
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Whether no slot is set
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    /// Keep only slots also set in `other`
+    pub fn intersect_with(&mut self, other: &Bitmap) {
+        self.words.truncate(other.words.len());
+        for (w, o) in self.words.iter_mut().zip(&other.words) {
+            *w &= o;
+        }
+    }
+
+    /// Add every slot set in `other`
+    pub fn union_with(&mut self, other: &Bitmap) {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (w, o) in self.words.iter_mut().zip(&other.words) {
+            *w |= o;
+        }
+    }
+
+    /// Remove every slot set in `other`
+    pub fn difference_with(&mut self, other: &Bitmap) {
+        for (w, o) in self.words.iter_mut().zip(&other.words) {
+            *w &= !o;
+        }
+    }
+
+    /// Iterate over set slots in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            let mut bits = word;
+            std::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+                let bit = bits.trailing_zeros();
+                bits &= bits - 1;
+                Some(i as u32 * 64 + bit)
+            })
+        })
+    }
+}
+
+impl FromIterator<u32> for Bitmap {
+    fn from_iter<I: IntoIterator<Item = u32>>(iter: I) -> Self {
+        let mut bitmap = Bitmap::new();
+        for slot in iter {
+            bitmap.insert(slot);
+        }
+        bitmap
+    }
+}
+
+/// Kind of secondary index maintained for a field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldIndexKind {
+    /// Exact-match bitmaps per distinct string, number or boolean value
+    Keyword,
+
+    /// Ordered numeric values for range scans
+    Numeric,
+}
+
+#[derive(Debug, Clone)]
+enum FieldIndex {
+    Keyword(HashMap<String, Bitmap>),
+    Numeric(BTreeSet<(u64, u32)>),
+}
+
+impl FieldIndex {
+    fn new(kind: FieldIndexKind) -> Self {
+        match kind {
+            FieldIndexKind::Keyword => Self::Keyword(HashMap::new()),
+            FieldIndexKind::Numeric => Self::Numeric(BTreeSet::new()),
+        }
+    }
+
+    fn kind(&self) -> FieldIndexKind {
+        match self {
+            Self::Keyword(_) => FieldIndexKind::Keyword,
+            Self::Numeric(_) => FieldIndexKind::Numeric,
+        }
+    }
+
+    fn add(&mut self, slot: u32, values: &[&Value]) {
+        match self {
+            Self::Keyword(postings) => {
+                for key in values.iter().filter_map(|v| keyword_key(v)) {
+                    postings.entry(key).or_default().insert(slot);
+                }
+            }
+            Self::Numeric(entries) => {
+                for f in values.iter().filter_map(|v| v.as_f64()) {
+                    entries.insert((ordered_bits(f), slot));
+                }
+            }
+        }
+    }
+
+    fn remove(&mut self, slot: u32, values: &[&Value]) {
+        match self {
+            Self::Keyword(postings) => {
+                for key in values.iter().filter_map(|v| keyword_key(v)) {
+                    if let Some(bitmap) = postings.get_mut(&key) {
+                        bitmap.remove(slot);
+                        if bitmap.is_empty() {
+                            postings.remove(&key);
+                        }
+                    }
+                }
+            }
+            Self::Numeric(entries) => {
+                for f in values.iter().filter_map(|v| v.as_f64()) {
+                    entries.remove(&(ordered_bits(f), slot));
+                }
+            }
+        }
+    }
+
+    /// Slots whose value equals `value`, if this index can answer it
+    fn lookup_eq(&self, value: &Value) -> Option<Bitmap> {
+        match self {
+            Self::Keyword(postings) => {
+                let key = keyword_key(value)?;
+                Some(postings.get(&key).cloned().unwrap_or_default())
+            }
+            Self::Numeric(_) => {
+                if !value.is_number() {
+                    return None;
+                }
+                let bound = RangeBound::inclusive(value.clone());
+                self.lookup_range(Some(&bound), Some(&bound))
+            }
+        }
+    }
+
+    /// Slots with a value inside the bounds, if this index can answer it
+    fn lookup_range(&self, lower: Option<&RangeBound>, upper: Option<&RangeBound>) -> Option<Bitmap> {
+        let Self::Numeric(entries) = self else {
+            return None;
+        };
+        let lo = match lower {
+            Some(bound) => {
+                let bits = ordered_bits(bound.value.as_f64()?);
+                if bound.inclusive {
+                    (bits, 0)
+                } else {
+                    (bits.checked_add(1)?, 0)
+                }
+            }
+            None => (0, 0),
+        };
+        let hi = match upper {
+            Some(bound) => {
+                let bits = ordered_bits(bound.value.as_f64()?);
+                if bound.inclusive {
+                    (bits, u32::MAX)
+                } else {
+                    (bits.checked_sub(1)?, u32::MAX)
+                }
+            }
+            None => (u64::MAX, u32::MAX),
+        };
+        if lo > hi {
+            return Some(Bitmap::new());
+        }
+        Some(entries.range(lo..=hi).map(|&(_, slot)| slot).collect())
+    }
+}
+
+/// Declared type of a metadata field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    /// UTF-8 string
+    String,
+
+    /// Integer number
+    Int,
+
+    /// Any number
+    Float,
+
+    /// Boolean
+    Bool,
+
+    /// Integer milliseconds since the Unix epoch
+    Timestamp,
+
+    /// Array of strings
+    KeywordArray,
+
+    /// Object `{"lat": <-90..=90>, "lon": <-180..=180>}`
+    GeoPoint,
+}
+
+impl FieldType {
+    /// Check a present, non-null value against this type
+    ///
+    /// # Returns
+    /// * None if the value conforms, otherwise the reason it does not
+    fn check(&self, value: &Value) -> Option<String> {
+        let ok = match self {
+            Self::String => value.is_string(),
+            Self::Int | Self::Timestamp => value.is_i64() || value.is_u64(),
+            Self::Float => value.is_number(),
+            Self::Bool => value.is_boolean(),
+            Self::KeywordArray => value
+                .as_array()
+                .is_some_and(|items| items.iter().all(Value::is_string)),
+            Self::GeoPoint => {
+                let coordinate = |key: &str, limit: f64| {
+                    value
+                        .get(key)
+                        .and_then(Value::as_f64)
+                        .is_some_and(|c| (-limit..=limit).contains(&c))
+                };
+                return (!(coordinate("lat", 90.0) && coordinate("lon", 180.0)))
+                    .then(|| "expected {\"lat\": -90..=90, \"lon\": -180..=180}".to_string());
+            }
+        };
+        (!ok).then(|| format!("expected {}, found {}", self.name(), json_type_name(value)))
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Int => "integer",
+            Self::Float => "number",
+            Self::Bool => "boolean",
+            Self::Timestamp => "integer epoch milliseconds",
+            Self::KeywordArray => "array of strings",
+            Self::GeoPoint => "geo point",
+        }
+    }
+
+    /// Secondary indexes backing a field of this type
+    ///
+    /// Geo points are indexed as numeric `lat` and `lon` sub-fields so that
+    /// bounding-box filters become two range scans.
+    pub fn index_layout(&self, path: &FieldPath) -> Vec<(FieldPath, FieldIndexKind)> {
+        match self {
+            Self::String | Self::Bool | Self::KeywordArray => {
+                vec![(path.clone(), FieldIndexKind::Keyword)]
+            }
+            Self::Int | Self::Float | Self::Timestamp => vec![(path.clone(), FieldIndexKind::Numeric)],
+            Self::GeoPoint => vec![
+                (path.child("lat"), FieldIndexKind::Numeric),
+                (path.child("lon"), FieldIndexKind::Numeric),
+            ],
+        }
+    }
+}
+
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "float",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Declaration of one metadata field
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldSchema {
+    /// Field location inside the metadata document
+    pub path: FieldPath,
+
+    /// Declared type
+    #[serde(rename = "type")]
+    pub field_type: FieldType,
+
+    /// Whether documents must carry a non-null value
+    #[serde(default)]
+    pub required: bool,
+
+    /// Whether the filter engine maintains a secondary index
+    #[serde(default)]
+    pub indexed: bool,
+}
+
+impl FieldSchema {
+    /// Optional, unindexed field of `field_type`
+    pub fn new(path: FieldPath, field_type: FieldType) -> Self {
+        Self {
+            path,
+            field_type,
+            required: false,
+            indexed: false,
+        }
+    }
+
+    /// Mark the field as required
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    /// Mark the field as indexed
+    pub fn indexed(mut self) -> Self {
+        self.indexed = true;
+        self
+    }
+}
+
+/// Optional per-collection metadata schema
+///
+/// Documents are checked on insert: declared fields must have their declared
+/// type (null counts as absent), required fields must be present, and with
+/// `allow_unknown_fields` off, top-level keys must belong to a declared field.
+/// Indexed fields determine the secondary indexes built by
+/// `MetadataIndex::from_schema`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetadataSchema {
+    /// Declared fields
+    #[serde(default)]
+    pub fields: Vec<FieldSchema>,
+
+    /// Accept top-level keys that no declared field covers (default: true)
+    #[serde(default = "default_allow_unknown_fields")]
+    pub allow_unknown_fields: bool,
+}
+
+fn default_allow_unknown_fields() -> bool {
+    true
+}
+
+impl Default for MetadataSchema {
+    fn default() -> Self {
+        Self {
+            fields: Vec::new(),
+            allow_unknown_fields: true,
+        }
+    }
+}
+
+impl MetadataSchema {
+    /// Create an empty schema that accepts any document
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare a field, replacing any earlier declaration of the same path
+    pub fn field(mut self, field: FieldSchema) -> Self {
+        self.fields.retain(|existing| existing.path != field.path);
+        self.fields.push(field);
+        self
+    }
+
+    /// Reject top-level keys that no declared field covers
+    pub fn strict(mut self) -> Self {
+        self.allow_unknown_fields = false;
+        self
+    }
+
+    /// Declaration for `path`
+    pub fn get(&self, path: &FieldPath) -> Option<&FieldSchema> {
+        self.fields.iter().find(|field| &field.path == path)
+    }
+
+    /// Secondary indexes required by the indexed fields
+    pub fn index_layout(&self) -> Vec<(FieldPath, FieldIndexKind)> {
+        self.fields
+            .iter()
+            .filter(|field| field.indexed)
+            .flat_map(|field| field.field_type.index_layout(&field.path))
+            .collect()
+    }
+
+    /// Check a document against the schema
+    ///
+    /// # Errors
+    /// * `SchemaViolation` naming the first offending field
+    pub fn validate(&self, metadata: Option<&Value>) -> MetadataResult<()> {
+        let violation = |path: String, reason: String| Err(MetadataError::SchemaViolation { path, reason });
+
+        if let Some(document) = metadata {
+            if !document.is_object() && !document.is_null() {
+                return violation(
+                    "$".to_string(),
+                    format!("metadata must be an object, found {}", json_type_name(document)),
+                );
+            }
+        }
+
+        for field in &self.fields {
+            match metadata.and_then(|m| field.path.resolve(m)) {
+                None | Some(Value::Null) => {
+                    if field.required {
+                        return violation(field.path.to_string(), "required field is missing".to_string());
+                    }
+                }
+                Some(value) => {
+                    if let Some(reason) = field.field_type.check(value) {
+                        return violation(field.path.to_string(), reason);
+                    }
+                }
+            }
+        }
+
+        if !self.allow_unknown_fields {
+            if let Some(Value::Object(object)) = metadata {
+                for key in object.keys() {
+                    let declared = self.fields.iter().any(|field| {
+                        matches!(field.path.segments().first(), Some(PathSegment::Key(first)) if first == key)
+                    });
+                    if !declared {
+                        return violation(
+                            FieldPath {
+                                segments: vec![PathSegment::Key(key.clone())],
+                            }
+                            .to_string(),
+                            "field is not declared in the schema".to_string(),
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Stored metadata plus secondary indexes for filter evaluation
+///
+/// Every entity gets a dense slot; filters evaluate to a `Bitmap` of slots.
+/// Slots of removed entities are recycled.
+#[derive(Debug, Clone, Default)]
+pub struct MetadataIndex {
+    schema: Option<MetadataSchema>,
+    fields: HashMap<FieldPath, FieldIndex>,
+    slots: HashMap<EntityId, u32>,
+    ids: Vec<Option<EntityId>>,
+    documents: Vec<Option<Value>>,
+    free: Vec<u32>,
+    live: Bitmap,
+}
+
+impl MetadataIndex {
+    /// Create an index with no secondary fields
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an index that enforces `schema` and indexes its indexed fields
+    pub fn from_schema(schema: MetadataSchema) -> Self {
+        let mut index = Self::new();
+        for (path, kind) in schema.index_layout() {
+            index.add_field(path, kind);
+        }
+        index.schema = Some(schema);
+        index
+    }
+
+    /// Schema enforced on insert, if any
+    pub fn schema(&self) -> Option<&MetadataSchema> {
+        self.schema.as_ref()
+    }
+
+    /// Check a document against the schema without inserting it
+    ///
+    /// # Errors
+    /// * `SchemaViolation` if the document does not conform
+    pub fn validate(&self, metadata: Option<&Value>) -> MetadataResult<()> {
+        match &self.schema {
+            Some(schema) => schema.validate(metadata),
+            None => Ok(()),
+        }
+    }
+
+    /// Builder-style `add_field`
+    pub fn with_field(mut self, path: FieldPath, kind: FieldIndexKind) -> Self {
+        self.add_field(path, kind);
+        self
+    }
+
+    /// Maintain a secondary index on `path`, indexing existing documents
+    ///
+    /// Replaces any index of a different kind on the same path.
+    pub fn add_field(&mut self, path: FieldPath, kind: FieldIndexKind) {
+        let mut index = FieldIndex::new(kind);
+        for slot in self.live.iter() {
+            let values = scalar_values(self.documents[slot as usize].as_ref(), &path);
+            index.add(slot, &values);
+        }
+        self.fields.insert(path, index);
+    }
+
+    /// Drop the secondary index on `path`, returning whether it existed
+    pub fn remove_field(&mut self, path: &FieldPath) -> bool {
+        self.fields.remove(path).is_some()
+    }
+
+    /// Indexed fields and their index kinds
+    pub fn indexed_fields(&self) -> impl Iterator<Item = (&FieldPath, FieldIndexKind)> {
+        self.fields.iter().map(|(path, index)| (path, index.kind()))
+    }
+
+    /// Number of documents
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Whether the index holds no documents
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Whether `id` has a document
+    pub fn contains(&self, id: &EntityId) -> bool {
+        self.slots.contains_key(id)
+    }
+
+    /// Stored metadata for `id`
+    pub fn get(&self, id: &EntityId) -> Option<&Value> {
+        self.slots
+            .get(id)
+            .and_then(|&slot| self.documents[slot as usize].as_ref())
+    }
+
+    /// Slot assigned to `id`
+    pub fn slot(&self, id: &EntityId) -> Option<u32> {
+        self.slots.get(id).copied()
+    }
+
+    /// Entity occupying `slot`
+    pub fn entity(&self, slot: u32) -> Option<EntityId> {
+        self.ids.get(slot as usize).copied().flatten()
+    }
+
+    /// Insert or replace the metadata document for `id`
+    ///
+    /// Entities without metadata are still registered so that negated
+    /// filters (`ne`, `not`) can match them.
+    ///
+    /// # Errors
+    /// * `SchemaViolation` if the document does not conform to the schema;
+    ///   the index is left unchanged
+    pub fn insert(&mut self, id: EntityId, metadata: Option<Value>) -> MetadataResult<()> {
+        self.validate(metadata.as_ref())?;
+
+        let slot = match self.slots.get(&id) {
+            Some(&slot) => {
+                self.unindex(slot);
+                slot
+            }
+            None => {
+                let slot = self.free.pop().unwrap_or_else(|| {
+                    self.ids.push(None);
+                    self.documents.push(None);
+                    (self.ids.len() - 1) as u32
+                });
+                self.slots.insert(id, slot);
+                self.ids[slot as usize] = Some(id);
+                self.live.insert(slot);
+                slot
+            }
+        };
+
+        for (path, index) in self.fields.iter_mut() {
+            let values = scalar_values(metadata.as_ref(), path);
+            index.add(slot, &values);
+        }
+        self.documents[slot as usize] = metadata;
+        Ok(())
+    }
+
+    /// Remove the document for `id`, returning whether it was present
+    pub fn remove(&mut self, id: &EntityId) -> bool {
+        let Some(slot) = self.slots.remove(id) else {
+            return false;
+        };
+        self.unindex(slot);
+        self.documents[slot as usize] = None;
+        self.ids[slot as usize] = None;
+        self.live.remove(slot);
+        self.free.push(slot);
+        true
+    }
+
+    fn unindex(&mut self, slot: u32) {
+        let document = self.documents[slot as usize].as_ref();
+        for (path, index) in self.fields.iter_mut() {
+            let values = scalar_values(document, path);
+            index.remove(slot, &values);
+        }
+    }
+
+    /// Whether the document of `id` satisfies `filter`
+    pub fn matches(&self, id: &EntityId, filter: &MetadataFilter) -> bool {
+        self.slots
+            .get(id)
+            .is_some_and(|&slot| filter.matches(self.documents[slot as usize].as_ref()))
+    }
+
+    /// Evaluate `filter` to the set of matching slots
+    ///
+    /// Predicates on indexed fields are answered from the secondary indexes;
+    /// everything else falls back to scanning stored documents.
+    ///
+    /// # Errors
+    /// * `InvalidFilter` if the filter fails validation
+    pub fn evaluate(&self, filter: &MetadataFilter) -> MetadataResult<Bitmap> {
+        filter.validate()?;
+        Ok(self.evaluate_node(filter))
+    }
+
+    /// Entities matching `filter`, in slot order
+    pub fn matching_ids(&self, filter: &MetadataFilter) -> MetadataResult<Vec<EntityId>> {
+        Ok(self
+            .evaluate(filter)?
+            .iter()
+            .filter_map(|slot| self.entity(slot))
+            .collect())
+    }
+
+    fn evaluate_node(&self, filter: &MetadataFilter) -> Bitmap {
+        if let Some(bitmap) = self.lookup(filter) {
+            return bitmap;
+        }
+
+        match filter {
+            MetadataFilter::Ne { field, value } => {
+                let eq = MetadataFilter::Eq {
+                    field: field.clone(),
+                    value: value.clone(),
+                };
+                let mut bitmap = self.live.clone();
+                bitmap.difference_with(&self.evaluate_node(&eq));
+                bitmap
+            }
+            MetadataFilter::And { filters } => {
+                let mut bitmap = self.live.clone();
+                for f in filters {
+                    if bitmap.is_empty() {
+                        break;
+                    }
+                    bitmap.intersect_with(&self.evaluate_node(f));
+                }
+                bitmap
+            }
+            MetadataFilter::Or { filters } => {
+                let mut bitmap = Bitmap::new();
+                for f in filters {
+                    bitmap.union_with(&self.evaluate_node(f));
+                }
+                bitmap
+            }
+            MetadataFilter::Not { filter } => {
+                let mut bitmap = self.live.clone();
+                bitmap.difference_with(&self.evaluate_node(filter));
+                bitmap
+            }
+            leaf => self
+                .live
+                .iter()
+                .filter(|&slot| leaf.matches(self.documents[slot as usize].as_ref()))
+                .collect(),
+        }
+    }
+
+    /// Answer a leaf predicate from a secondary index, if one applies
+    fn lookup(&self, filter: &MetadataFilter) -> Option<Bitmap> {
+        match filter {
+            MetadataFilter::Eq { field, value } => self.fields.get(field)?.lookup_eq(value),
+            MetadataFilter::In { field, values } => {
+                let index = self.fields.get(field)?;
+                let mut bitmap = Bitmap::new();
+                for value in values {
+                    bitmap.union_with(&index.lookup_eq(value)?);
+                }
+                Some(bitmap)
+            }
+            MetadataFilter::Range { field, lower, upper } => self
+                .fields
+                .get(field)?
+                .lookup_range(lower.as_ref(), upper.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Estimate the fraction of documents matching `filter`
+    ///
+    /// Indexed leaves are counted exactly, unindexed leaves are estimated on a
+    /// deterministic sample of about `SELECTIVITY_SAMPLE_SIZE` documents, and
+    /// boolean combinations assume independence.
+    ///
+    /// # Returns
+    /// * Selectivity in [0.0, 1.0] (1.0 for an empty index)
+    pub fn estimate_selectivity(&self, filter: &MetadataFilter) -> f64 {
+        let total = self.len();
+        if total == 0 {
+            return 1.0;
+        }
+
+        let estimate = match filter {
+            MetadataFilter::Ne { field, value } => {
+                1.0 - self.estimate_selectivity(&MetadataFilter::Eq {
+                    field: field.clone(),
+                    value: value.clone(),
+                })
+            }
+            MetadataFilter::And { filters } => filters
+                .iter()
+                .map(|f| self.estimate_selectivity(f))
+                .product(),
+            MetadataFilter::Or { filters } => {
+                1.0 - filters
+                    .iter()
+                    .map(|f| 1.0 - self.estimate_selectivity(f))
+                    .product::<f64>()
+            }
+            MetadataFilter::Not { filter } => 1.0 - self.estimate_selectivity(filter),
+            leaf => match self.lookup(leaf) {
+                Some(bitmap) => bitmap.len() as f64 / total as f64,
+                None => self.sample_selectivity(leaf),
+            },
+        };
+        estimate.clamp(0.0, 1.0)
+    }
+
+    fn sample_selectivity(&self, filter: &MetadataFilter) -> f64 {
+        let total = self.len() as u64;
+        let mut sampled = 0usize;
+        let mut matched = 0usize;
+
+        // Hash slots instead of striding so periodic data does not alias
+        let selected = |slot: u32| {
+            total <= SELECTIVITY_SAMPLE_SIZE as u64
+                || (slot as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) % total < SELECTIVITY_SAMPLE_SIZE as u64
+        };
+
+        for slot in self.live.iter().filter(|&slot| selected(slot)) {
+            sampled += 1;
+            if filter.matches(self.documents[slot as usize].as_ref()) {
+                matched += 1;
+            }
+        }
+        if sampled == 0 {
+            1.0
+        } else {
+            matched as f64 / sampled as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn path(s: &str) -> FieldPath {
+        FieldPath::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_field_path_parsing() {
+        assert_eq!(
+            path("author.name").segments(),
+            &[PathSegment::Key("author".into()), PathSegment::Key("name".into())]
+        );
+        assert_eq!(
+            path("$.tags[2]").segments(),
+            &[PathSegment::Key("tags".into()), PathSegment::Index(2)]
+        );
+        assert_eq!(path("$[\"a.b\"].c").segments()[0], PathSegment::Key("a.b".into()));
+
+        for bad in ["", "$", "a..b", "a.", "a[x]", "a[1", "a[\"b]", "a]b"] {
+            assert!(FieldPath::parse(bad).is_err(), "accepted {:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_field_path_display_round_trip() {
+        for text in ["lang", "author.name", "tags[0]", "m[1][2].x", "[\"a.b\"].c"] {
+            let parsed = path(text);
+            assert_eq!(parsed.to_string(), text);
+            assert_eq!(path(&parsed.to_string()), parsed);
+        }
+        assert_eq!(path("$.lang").to_string(), "lang");
+    }
+
+    #[test]
+    fn test_field_path_resolve() {
+        let doc = json!({"author": {"name": "ada"}, "tags": ["x", "y"], "a.b": 1});
+        assert_eq!(path("author.name").resolve(&doc), Some(&json!("ada")));
+        assert_eq!(path("tags[1]").resolve(&doc), Some(&json!("y")));
+        assert_eq!(path("[\"a.b\"]").resolve(&doc), Some(&json!(1)));
+        assert_eq!(path("tags[5]").resolve(&doc), None);
+        assert_eq!(path("author.name.first").resolve(&doc), None);
+    }
+
+    #[test]
+    fn test_filter_semantics() {
+        let doc = json!({"lang": "en", "year": 2023, "tags": ["rust", "db"], "score": 0.5, "gone": null});
+        let m = Some(&doc);
+
+        assert!(MetadataFilter::eq(path("lang"), "en").matches(m));
+        assert!(MetadataFilter::eq(path("year"), 2023.0).matches(m));
+        assert!(MetadataFilter::eq(path("tags"), "db").matches(m));
+        assert!(MetadataFilter::ne(path("lang"), "fr").matches(m));
+        assert!(MetadataFilter::ne(path("missing"), "x").matches(m));
+        assert!(MetadataFilter::gte(path("year"), 2023).matches(m));
+        assert!(!MetadataFilter::gt(path("year"), 2023).matches(m));
+        assert!(MetadataFilter::lt(path("lang"), "fr").matches(m));
+        assert!(!MetadataFilter::lt(path("year"), "3000").matches(m));
+        assert!(MetadataFilter::in_values(path("lang"), ["de", "en"]).matches(m));
+        assert!(MetadataFilter::exists(path("score")).matches(m));
+        assert!(!MetadataFilter::exists(path("gone")).matches(m));
+
+        let filter = MetadataFilter::eq(path("lang"), "en").and(MetadataFilter::gte(path("year"), 2023));
+        assert!(filter.matches(m));
+        assert!(!(!filter.clone()).matches(m));
+        assert!(MetadataFilter::eq(path("lang"), "fr").or(filter).matches(m));
+
+        assert!(!MetadataFilter::exists(path("lang")).matches(None));
+        assert!(MetadataFilter::And { filters: vec![] }.matches(None));
+        assert!(!MetadataFilter::Or { filters: vec![] }.matches(None));
+    }
+
+    #[test]
+    fn test_filter_validation() {
+        assert!(MetadataFilter::range(path("x"), None, None).validate().is_err());
+        assert!(MetadataFilter::gte(path("x"), true).validate().is_err());
+        let mixed = MetadataFilter::range(
+            path("x"),
+            Some(RangeBound::inclusive(1)),
+            Some(RangeBound::inclusive("z")),
+        );
+        assert!((!mixed).validate().is_err());
+        assert!(MetadataFilter::gte(path("x"), 1).validate().is_ok());
+    }
+
+    #[test]
+    fn test_filter_serialization() {
+        let filter = MetadataFilter::eq(path("lang"), "en")
+            .and(MetadataFilter::gte(path("meta.year"), 2023))
+            .and(!MetadataFilter::exists(path("tags[0]")));
+        let text = serde_json::to_string(&filter).unwrap();
+        assert!(text.contains("\"op\":\"and\""));
+        assert!(text.contains("\"field\":\"meta.year\""));
+
+        let parsed: MetadataFilter = serde_json::from_str(&text).unwrap();
+        assert_eq!(parsed, filter);
+
+        let bad = r#"{"op":"eq","field":"a..b","value":1}"#;
+        assert!(serde_json::from_str::<MetadataFilter>(bad).is_err());
+    }
+
+    #[test]
+    fn test_bitmap_operations() {
+        let mut a: Bitmap = [1, 5, 64, 200].into_iter().collect();
+        let b: Bitmap = [5, 64, 300].into_iter().collect();
+
+        assert_eq!(a.len(), 4);
+        assert!(a.contains(200) && !a.contains(2));
+
+        let mut union = a.clone();
+        union.union_with(&b);
+        assert_eq!(union.iter().collect::<Vec<_>>(), vec![1, 5, 64, 200, 300]);
+
+        let mut diff = a.clone();
+        diff.difference_with(&b);
+        assert_eq!(diff.iter().collect::<Vec<_>>(), vec![1, 200]);
+
+        a.intersect_with(&b);
+        assert_eq!(a.iter().collect::<Vec<_>>(), vec![5, 64]);
+        assert!(a.remove(5) && !a.remove(5));
+    }
+
+    fn sample_index(count: usize, indexed: bool) -> (MetadataIndex, Vec<EntityId>) {
+        let mut index = MetadataIndex::new();
+        if indexed {
+            index = index
+                .with_field(path("lang"), FieldIndexKind::Keyword)
+                .with_field(path("tags"), FieldIndexKind::Keyword)
+                .with_field(path("year"), FieldIndexKind::Numeric);
+        }
+
+        let langs = ["en", "de", "fr", "ja"];
+        let mut ids = Vec::new();
+        for i in 0..count {
+            let id = EntityId::new();
+            let metadata = match i % 7 {
+                6 => None,
+                5 => Some(json!({"lang": langs[i % 4]})),
+                _ => Some(json!({
+                    "lang": langs[i % 4],
+                    "year": 2000 + (i % 30) as i64,
+                    "tags": [format!("t{}", i % 3), format!("t{}", i % 5)],
+                })),
+            };
+            index.insert(id, metadata).unwrap();
+            ids.push(id);
+        }
+        (index, ids)
+    }
+
+    #[test]
+    fn test_index_matches_scan() {
+        let (indexed, ids) = sample_index(500, true);
+        let filters = vec![
+            MetadataFilter::eq(path("lang"), "en"),
+            MetadataFilter::eq(path("year"), 2010),
+            MetadataFilter::ne(path("lang"), "de"),
+            MetadataFilter::in_values(path("tags"), ["t1", "t4"]),
+            MetadataFilter::range(path("year"), Some(RangeBound::exclusive(2005)), Some(RangeBound::inclusive(2012))),
+            MetadataFilter::eq(path("lang"), "en").and(MetadataFilter::gte(path("year"), 2020)),
+            MetadataFilter::eq(path("lang"), "ja").or(!MetadataFilter::exists(path("year"))),
+            MetadataFilter::lt(path("lang"), "f"),
+        ];
+
+        for filter in &filters {
+            let got: Vec<EntityId> = indexed.matching_ids(filter).unwrap();
+            let expected: Vec<EntityId> = ids
+                .iter()
+                .copied()
+                .filter(|id| indexed.matches(id, filter))
+                .collect();
+
+            let mut got_sorted = got.clone();
+            let mut expected_sorted = expected.clone();
+            got_sorted.sort_by_key(|id| *id.as_uuid());
+            expected_sorted.sort_by_key(|id| *id.as_uuid());
+            assert_eq!(got_sorted, expected_sorted, "filter {:?}", filter);
+        }
+    }
+
+    #[test]
+    fn test_index_update_and_remove() {
+        let mut index = MetadataIndex::new().with_field(path("lang"), FieldIndexKind::Keyword);
+        let a = EntityId::new();
+        let b = EntityId::new();
+        index.insert(a, Some(json!({"lang": "en"}))).unwrap();
+        index.insert(b, Some(json!({"lang": "en"}))).unwrap();
+
+        let en = MetadataFilter::eq(path("lang"), "en");
+        assert_eq!(index.matching_ids(&en).unwrap().len(), 2);
+
+        index.insert(a, Some(json!({"lang": "de"}))).unwrap();
+        assert_eq!(index.matching_ids(&en).unwrap(), vec![b]);
+
+        assert!(index.remove(&b));
+        assert!(!index.remove(&b));
+        assert!(index.matching_ids(&en).unwrap().is_empty());
+        assert_eq!(index.len(), 1);
+
+        // The freed slot is reused without leaking the old postings
+        let c = EntityId::new();
+        index.insert(c, None).unwrap();
+        assert_eq!(index.slot(&c), Some(1));
+        assert_eq!(index.matching_ids(&!en).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_add_field_indexes_existing_documents() {
+        let (mut index, _) = sample_index(100, false);
+        let filter = MetadataFilter::gte(path("year"), 2025);
+        let before = index.matching_ids(&filter).unwrap();
+
+        index.add_field(path("year"), FieldIndexKind::Numeric);
+        assert!(index.lookup(&filter).is_some());
+        assert_eq!(index.matching_ids(&filter).unwrap(), before);
+    }
+
+    fn article_schema() -> MetadataSchema {
+        MetadataSchema::new()
+            .field(FieldSchema::new(path("lang"), FieldType::String).required().indexed())
+            .field(FieldSchema::new(path("year"), FieldType::Int).indexed())
+            .field(FieldSchema::new(path("score"), FieldType::Float))
+            .field(FieldSchema::new(path("draft"), FieldType::Bool))
+            .field(FieldSchema::new(path("published_at"), FieldType::Timestamp))
+            .field(FieldSchema::new(path("tags"), FieldType::KeywordArray).indexed())
+            .field(FieldSchema::new(path("place"), FieldType::GeoPoint).indexed())
+    }
+
+    #[test]
+    fn test_schema_validation() {
+        let schema = article_schema();
+        let valid = json!({
+            "lang": "en", "year": 2024, "score": 1, "draft": false,
+            "published_at": 1_700_000_000_000i64, "tags": ["a", "b"],
+            "place": {"lat": 52.5, "lon": 13.4}, "extra": {"x": 1}
+        });
+        assert!(schema.validate(Some(&valid)).is_ok());
+        assert!(schema.validate(Some(&json!({"lang": "de", "year": null}))).is_ok());
+
+        let cases = [
+            (json!({"year": 2024}), "lang"),
+            (json!({"lang": 5}), "lang"),
+            (json!({"lang": "en", "year": 2024.5}), "year"),
+            (json!({"lang": "en", "published_at": "2024-01-01"}), "published_at"),
+            (json!({"lang": "en", "tags": ["a", 1]}), "tags"),
+            (json!({"lang": "en", "place": {"lat": 91.0, "lon": 0.0}}), "place"),
+            (json!({"lang": "en", "place": [1.0, 2.0]}), "place"),
+        ];
+        for (doc, field) in cases {
+            match schema.validate(Some(&doc)) {
+                Err(MetadataError::SchemaViolation { path, .. }) => assert_eq!(path, field),
+                other => panic!("{} accepted: {:?}", doc, other),
+            }
+        }
+        assert!(schema.validate(None).is_err());
+        assert!(schema.validate(Some(&json!(["not", "an", "object"]))).is_err());
+
+        let strict = article_schema().strict();
+        assert!(matches!(
+            strict.validate(Some(&valid)),
+            Err(MetadataError::SchemaViolation { path, .. }) if path == "extra"
+        ));
+    }
+
+    #[test]
+    fn test_schema_serialization() {
+        let schema = article_schema().strict();
+        let text = serde_json::to_string(&schema).unwrap();
+        assert!(text.contains("\"type\":\"geo_point\""));
+        let parsed: MetadataSchema = serde_json::from_str(&text).unwrap();
+        assert_eq!(parsed, schema);
+
+        let minimal: MetadataSchema =
+            serde_json::from_str(r#"{"fields": [{"path": "lang", "type": "string"}]}"#).unwrap();
+        assert!(minimal.allow_unknown_fields);
+        assert!(!minimal.fields[0].required && !minimal.fields[0].indexed);
+    }
+
+    #[test]
+    fn test_index_from_schema() {
+        let mut index = MetadataIndex::from_schema(article_schema());
+        let mut kinds: Vec<(String, FieldIndexKind)> = index
+            .indexed_fields()
+            .map(|(path, kind)| (path.to_string(), kind))
+            .collect();
+        kinds.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            kinds,
+            vec![
+                ("lang".to_string(), FieldIndexKind::Keyword),
+                ("place.lat".to_string(), FieldIndexKind::Numeric),
+                ("place.lon".to_string(), FieldIndexKind::Numeric),
+                ("tags".to_string(), FieldIndexKind::Keyword),
+                ("year".to_string(), FieldIndexKind::Numeric),
+            ]
+        );
+
+        let berlin = EntityId::new();
+        let paris = EntityId::new();
+        index
+            .insert(berlin, Some(json!({"lang": "de", "place": {"lat": 52.52, "lon": 13.40}})))
+            .unwrap();
+        index
+            .insert(paris, Some(json!({"lang": "fr", "place": {"lat": 48.86, "lon": 2.35}})))
+            .unwrap();
+
+        let rejected = index.insert(EntityId::new(), Some(json!({"lang": "en", "year": "2024"})));
+        assert!(matches!(rejected, Err(MetadataError::SchemaViolation { .. })));
+        assert_eq!(index.len(), 2);
+
+        // Replacing with a bad document keeps the old one
+        assert!(index.insert(paris, Some(json!({"year": 1}))).is_err());
+        assert_eq!(index.get(&paris).unwrap()["lang"], json!("fr"));
+
+        let germany = MetadataFilter::geo_bounding_box(path("place"), (47.0, 5.0), (55.0, 15.0));
+        assert!(index.lookup(&MetadataFilter::gte(path("place.lat"), 47.0)).is_some());
+        assert_eq!(index.matching_ids(&germany).unwrap(), vec![berlin]);
+    }
+
+    #[test]
+    fn test_selectivity_estimation() {
+        let (index, _) = sample_index(1000, true);
+        let en = MetadataFilter::eq(path("lang"), "en");
+        let exact = index.matching_ids(&en).unwrap().len() as f64 / 1000.0;
+        assert!((index.estimate_selectivity(&en) - exact).abs() < 1e-9);
+        assert!((index.estimate_selectivity(&!en.clone()) - (1.0 - exact)).abs() < 1e-9);
+
+        // Unindexed predicate is sampled
+        let (scan_index, _) = sample_index(1000, false);
+        let sampled = scan_index.estimate_selectivity(&en);
+        assert!((sampled - exact).abs() < 0.1);
 
-// Placeholder - will be implemented in later tasks
+        let none = MetadataFilter::eq(path("lang"), "xx");
+        assert_eq!(index.estimate_selectivity(&none.and(en)), 0.0);
+        assert_eq!(MetadataIndex::new().estimate_selectivity(&MetadataFilter::exists(path("a"))), 1.0);
+    }
+}
@@ -4,8 +4,13 @@
 // - Entity: Unified data structure (vector + metadata + edges)
 // - Vector: Vector operations and distance functions
 // - Edges: Probabilistic edge management with PGM fields
-// - Types: Core type aliases (EntityId, NodeId, ShardId, ClusterId)
+// - Types: Core type aliases (EntityId, NodeId, ShardId, ClusterId, CollectionId)
 // - Traits: Shared abstractions for memory substrate components
+// - Metadata: JSON-path filter expressions and secondary indexes
+// - Query: Unified query structures
+// - Collection: Per-collection configuration (dimension, metric, index, schema, tiering)
+// - MVCC: Per-entity version chains for snapshot reads
+// - Transaction: Transaction identifiers, states and retry options
 
 pub mod entity;
 pub mod vector;
@@ -18,11 +23,14 @@ pub mod transaction;
 pub mod mvcc;
 pub mod query;
 pub mod config;
+pub mod collection;
 
 // Re-export commonly used types
 pub use entity::{Entity, MemoryTier, AccessStatistics};
-pub use vector::Vector;
+pub use vector::{Vector, DistanceMetric};
 pub use edges::Edge;
-pub use types::{EntityId, NodeId, ShardId, ClusterId};
+pub use types::{EntityId, NodeId, ShardId, ClusterId, CollectionId};
 pub use error::{Result, MemorySubstrateError};
 pub use config::PhenixConfig;
+pub use metadata::{FieldPath, MetadataFilter, MetadataIndex, MetadataSchema};
+pub use query::{FilterStrategy, GraphQuery, TraversalStep, VectorQuery};
@@ -1,6 +1,200 @@
 // Multi-version concurrency control
 //
-// This module will be fully implemented in later tasks.
-// Placeholder for now to allow compilation.
+// Every committed write of an entity appends a new version stamped with a
+// commit timestamp from a global logical clock; `Entity.commit_ts` carries
+// that timestamp. A reader holding a snapshot at timestamp `ts` sees, for each
+// entity, the newest version committed at or before `ts`. Deletes append a
+// tombstone so older snapshots keep seeing the entity.
+//
+// This module holds the per-entity version chain and the write records that
+// commits, the write-ahead log and replication exchange; the engine
+// coordinating the clock, snapshots and garbage collection lives in
+// concurrency::mvcc_engine.
+
+use crate::core::entity::Entity;
+use crate::core::types::EntityId;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Logical commit timestamp
+pub type Timestamp = u64;
+
+/// Single write within an atomic commit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MvccWrite {
+    /// Insert or replace an entity
+    Put(Box<Entity>),
+
+    /// Delete an entity
+    Delete(EntityId),
+}
+
+impl MvccWrite {
+    /// Entity the write targets
+    pub fn id(&self) -> EntityId {
+        match self {
+            Self::Put(entity) => entity.id,
+            Self::Delete(id) => *id,
+        }
+    }
+}
+
+/// One committed version of an entity
+#[derive(Debug, Clone)]
+pub struct Version {
+    /// Commit timestamp
+    pub commit_ts: Timestamp,
+
+    /// Entity state, or `None` for a delete tombstone
+    pub entity: Option<Arc<Entity>>,
+}
+
+impl Version {
+    /// Whether this version records a delete
+    pub fn is_tombstone(&self) -> bool {
+        self.entity.is_none()
+    }
+}
+
+/// Committed versions of one entity, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct VersionChain {
+    versions: Vec<Version>,
+}
+
+impl VersionChain {
+    /// Create an empty chain
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of retained versions, tombstones included
+    pub fn len(&self) -> usize {
+        self.versions.len()
+    }
+
+    /// Whether the chain holds no versions
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty()
+    }
+
+    /// Newest committed version
+    pub fn latest(&self) -> Option<&Version> {
+        self.versions.last()
+    }
+
+    /// Append a version
+    ///
+    /// # Panics
+    /// * If `commit_ts` is not newer than the latest version
+    pub fn push(&mut self, commit_ts: Timestamp, entity: Option<Arc<Entity>>) {
+        assert!(
+            self.latest().is_none_or(|v| v.commit_ts < commit_ts),
+            "versions must be appended in commit order"
+        );
+        self.versions.push(Version { commit_ts, entity });
+    }
+
+    /// Version visible to a snapshot taken at `ts`
+    ///
+    /// Returns `None` if the entity did not exist or was deleted at `ts`.
+    pub fn visible_at(&self, ts: Timestamp) -> Option<&Version> {
+        let idx = self.versions.partition_point(|v| v.commit_ts <= ts);
+        let version = self.versions[..idx].last()?;
+        (!version.is_tombstone()).then_some(version)
+    }
+
+    /// Drop versions no reader can observe
+    ///
+    /// A version survives if it is the one visible at some timestamp in
+    /// `live`, or if it is the newest version (what future snapshots read).
+    /// A chain left holding only a tombstone is emptied.
+    ///
+    /// # Arguments
+    /// * `live` - Timestamps of live snapshots
+    ///
+    /// # Returns
+    /// * Number of versions removed
+    pub fn prune(&mut self, live: &[Timestamp]) -> usize {
+        let Some(newest) = self.versions.len().checked_sub(1) else {
+            return 0;
+        };
+        let mut keep = vec![false; self.versions.len()];
+        keep[newest] = true;
+        for &ts in live {
+            let idx = self.versions.partition_point(|v| v.commit_ts <= ts);
+            if idx > 0 {
+                keep[idx - 1] = true;
+            }
+        }
+
+        let before = self.versions.len();
+        let mut flags = keep.into_iter();
+        self.versions.retain(|_| flags.next().unwrap_or(false));
+        if self.versions.len() == 1 && self.versions[0].is_tombstone() {
+            self.versions.clear();
+        }
+        before - self.versions.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(value: f32) -> Option<Arc<Entity>> {
+        Some(Arc::new(Entity::new(
+            Some(crate::core::Vector::new(vec![value])),
+            None,
+            None,
+        )))
+    }
+
+    fn value(v: &Version) -> f32 {
+        v.entity.as_ref().unwrap().vector.as_ref().unwrap().values[0]
+    }
+
+    #[test]
+    fn test_visibility() {
+        let mut chain = VersionChain::new();
+        chain.push(10, version(1.0));
+        chain.push(20, version(2.0));
+        chain.push(30, None);
+
+        assert!(chain.visible_at(5).is_none());
+        assert_eq!(value(chain.visible_at(10).unwrap()), 1.0);
+        assert_eq!(value(chain.visible_at(25).unwrap()), 2.0);
+        assert!(chain.visible_at(30).is_none());
+        assert!(chain.latest().unwrap().is_tombstone());
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut chain = VersionChain::new();
+        chain.push(10, version(1.0));
+        chain.push(20, version(2.0));
+        chain.push(30, version(3.0));
+        chain.push(40, version(4.0));
+
+        // Snapshots at 15 and 35 pin versions 10 and 30; 40 is the newest
+        assert_eq!(chain.prune(&[15, 35]), 1);
+        assert_eq!(value(chain.visible_at(15).unwrap()), 1.0);
+        assert_eq!(value(chain.visible_at(35).unwrap()), 3.0);
+        assert_eq!(chain.prune(&[5]), 2);
+        assert_eq!(chain.len(), 1);
+
+        chain.push(50, None);
+        assert_eq!(chain.prune(&[45]), 0);
+        assert_eq!(chain.prune(&[]), 2);
+        assert!(chain.is_empty());
+    }
 
-// Placeholder - will be implemented in later tasks
+    #[test]
+    #[should_panic]
+    fn test_out_of_order_push() {
+        let mut chain = VersionChain::new();
+        chain.push(10, version(1.0));
+        chain.push(10, version(2.0));
+    }
+}
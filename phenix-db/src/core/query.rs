@@ -1,6 +1,162 @@
 // Unified query structures
 //
-// This module will be fully implemented in later tasks.
-// Placeholder for now to allow compilation.
+// A vector query couples a query embedding with an optional metadata filter.
+// How the filter is combined with approximate search is decided by the index
+// from filter selectivity, unless the caller pins a strategy.
+//
+// A graph query walks entity edges breadth-first from a set of start
+// entities, optionally restricted to edge labels and a minimum learned
+// probability.
+
+use crate::core::metadata::MetadataFilter;
+use crate::core::types::EntityId;
+use crate::core::vector::Vector;
+use serde::{Deserialize, Serialize};
+
+/// How a metadata filter is combined with approximate nearest-neighbour search
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterStrategy {
+    /// Evaluate the filter first, then rank the matching entities exactly
+    ///
+    /// Best for very selective filters: the candidate set is small enough
+    /// that an exact scan is cheaper than a graph walk.
+    PreFilter,
+
+    /// Run the ANN search with over-fetching, then drop non-matching hits
+    ///
+    /// Best for permissive filters where most neighbours already match.
+    PostFilter,
+
+    /// Walk the graph through all nodes but only admit matching ones
+    ///
+    /// Covers the middle ground where neither pre- nor post-filtering is cheap.
+    InGraph,
+}
+
+/// k-nearest-neighbour query with optional metadata filter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorQuery {
+    /// Query embedding
+    pub vector: Vector,
+
+    /// Number of results to return
+    pub k: usize,
+
+    /// Metadata predicate results must satisfy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<MetadataFilter>,
+
+    /// Pin a filter strategy instead of choosing by selectivity
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<FilterStrategy>,
+
+    /// Search breadth override for graph indexes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ef: Option<usize>,
+}
+
+impl VectorQuery {
+    /// Create an unfiltered query for the `k` nearest neighbours of `vector`
+    pub fn new(vector: Vector, k: usize) -> Self {
+        Self {
+            vector,
+            k,
+            filter: None,
+            strategy: None,
+            ef: None,
+        }
+    }
+
+    /// Restrict results to entities matching `filter`
+    pub fn with_filter(mut self, filter: MetadataFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Force a filter strategy
+    pub fn with_strategy(mut self, strategy: FilterStrategy) -> Self {
+        self.strategy = Some(strategy);
+        self
+    }
+
+    /// Override the graph search breadth
+    pub fn with_ef(mut self, ef: usize) -> Self {
+        self.ef = Some(ef);
+        self
+    }
+}
+
+/// Breadth-first traversal along entity edges
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphQuery {
+    /// Entities the traversal starts from (depth 0)
+    pub start: Vec<EntityId>,
+
+    /// Maximum number of hops from a start entity
+    pub max_depth: usize,
+
+    /// Only follow edges with one of these labels; empty follows all
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+
+    /// Only follow edges whose learned probability is at least this
+    #[serde(default)]
+    pub min_probability: f32,
+
+    /// Maximum number of entities returned, start entities included
+    pub limit: usize,
+}
+
+impl GraphQuery {
+    /// Traverse up to `max_depth` hops from `start`, returning at most 1000
+    /// entities
+    pub fn new(start: Vec<EntityId>, max_depth: usize) -> Self {
+        Self {
+            start,
+            max_depth,
+            labels: Vec::new(),
+            min_probability: 0.0,
+            limit: 1000,
+        }
+    }
+
+    /// Only follow edges with one of `labels`
+    pub fn with_labels(mut self, labels: Vec<String>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Only follow edges with at least this probability
+    pub fn with_min_probability(mut self, min_probability: f32) -> Self {
+        self.min_probability = min_probability;
+        self
+    }
+
+    /// Cap the number of returned entities
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+}
+
+/// An entity reached by a graph traversal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraversalStep {
+    /// Entity reached
+    pub id: EntityId,
+
+    /// Hops from the nearest start entity
+    pub depth: usize,
+
+    /// Entity it was reached from (None for start entities)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<EntityId>,
+
+    /// Label of the edge it was reached through
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
 
-// Placeholder - will be implemented in later tasks
+    /// Product of the edge probabilities along the path (1.0 at the start)
+    pub path_probability: f32,
+}
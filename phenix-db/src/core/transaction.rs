@@ -1,6 +1,72 @@
 // ACID transaction logic
 //
-// This module will be fully implemented in later tasks.
-// Placeholder for now to allow compilation.
+// Transactions buffer writes against an MVCC snapshot and commit them
+// atomically under one commit timestamp. Concurrency control is optimistic:
+// at commit every entity the transaction read or wrote must still be at the
+// version it observed, otherwise the transaction aborts with a conflict and
+// may be retried. The coordinator lives in concurrency::transaction_coordinator.
 
-// Placeholder - will be implemented in later tasks
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Transaction identifier, unique per coordinator
+pub type TransactionId = u64;
+
+/// Lifecycle state of a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TransactionState {
+    /// Accepting reads and writes
+    Active,
+
+    /// Writes are durable and visible
+    Committed,
+
+    /// Rolled back or aborted by a conflict; no writes applied
+    Aborted,
+}
+
+/// Retry behaviour for conflicting transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionOptions {
+    /// Maximum attempts, including the first (default: 8)
+    pub max_attempts: usize,
+
+    /// Delay before the first retry, doubled on every further retry
+    /// (default: 1ms)
+    pub base_backoff: Duration,
+
+    /// Upper bound on the retry delay (default: 100ms)
+    pub max_backoff: Duration,
+}
+
+impl Default for TransactionOptions {
+    fn default() -> Self {
+        Self {
+            max_attempts: 8,
+            base_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(100),
+        }
+    }
+}
+
+impl TransactionOptions {
+    /// Delay before retry number `retry` (1-based)
+    pub fn backoff(&self, retry: usize) -> Duration {
+        let factor = 1u32.checked_shl(retry.saturating_sub(1) as u32).unwrap_or(u32::MAX);
+        self.base_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_is_exponential_and_capped() {
+        let options = TransactionOptions::default();
+        assert_eq!(options.backoff(1), Duration::from_millis(1));
+        assert_eq!(options.backoff(3), Duration::from_millis(4));
+        assert_eq!(options.backoff(20), Duration::from_millis(100));
+        assert_eq!(options.backoff(200), Duration::from_millis(100));
+    }
+}
@@ -12,7 +12,7 @@ use std::fmt;
 /// - Distributed generation without coordination
 /// - Temporal ordering (creation time embedded)
 /// - 128-bit uniqueness guarantee
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct EntityId(uuid::Uuid);
 
 impl EntityId {
@@ -48,7 +48,7 @@ impl fmt::Display for EntityId {
 /// 
 /// NodeId represents a physical or logical node in the cluster.
 /// Each node maintains partial awareness of the global memory graph.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct NodeId(uuid::Uuid);
 
 impl NodeId {
@@ -84,7 +84,7 @@ impl fmt::Display for NodeId {
 /// 
 /// ShardId represents a logical partition of the memory space.
 /// Semantically similar entities are co-located in the same shard.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct ShardId(uuid::Uuid);
 
 impl ShardId {
@@ -152,6 +152,42 @@ impl fmt::Display for ClusterId {
     }
 }
 
+/// Unique identifier for a Collection
+/// 
+/// A collection is an independent dataset with its own dimension, metric,
+/// index configuration and schema. Names and aliases resolve to this id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CollectionId(uuid::Uuid);
+
+impl CollectionId {
+    /// Generate a new CollectionId
+    pub fn new() -> Self {
+        Self(uuid::Uuid::now_v7())
+    }
+
+    /// Create CollectionId from existing UUID
+    pub fn from_uuid(uuid: uuid::Uuid) -> Self {
+        Self(uuid)
+    }
+
+    /// Get the underlying UUID
+    pub fn as_uuid(&self) -> &uuid::Uuid {
+        &self.0
+    }
+}
+
+impl Default for CollectionId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for CollectionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -184,6 +220,13 @@ mod tests {
         assert_ne!(id1, id2, "ClusterIds should be unique");
     }
 
+    #[test]
+    fn test_collection_id_creation() {
+        let id1 = CollectionId::new();
+        let id2 = CollectionId::new();
+        assert_ne!(id1, id2, "CollectionIds should be unique");
+    }
+
     #[test]
     fn test_id_serialization() {
         let id = EntityId::new();
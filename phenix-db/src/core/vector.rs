@@ -177,6 +177,39 @@ impl PartialEq for Vector {
     }
 }
 
+/// Distance metric used to compare vectors during search
+///
+/// All metrics are expressed as distances: smaller values mean closer vectors.
+/// - Euclidean: L2 distance
+/// - Cosine: 1 - cosine similarity, in range [0.0, 2.0]
+/// - DotProduct: negated inner product (maximum inner product search)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DistanceMetric {
+    /// Euclidean (L2) distance
+    Euclidean,
+
+    /// Cosine distance (1 - cosine similarity, default)
+    #[default]
+    Cosine,
+
+    /// Negated dot product
+    DotProduct,
+}
+
+impl DistanceMetric {
+    /// Compute the distance between two vectors under this metric
+    ///
+    /// # Panics
+    /// * If dimensions don't match
+    pub fn distance(&self, a: &Vector, b: &Vector) -> f32 {
+        match self {
+            DistanceMetric::Euclidean => a.euclidean_distance(b),
+            DistanceMetric::Cosine => 1.0 - a.cosine_similarity(b),
+            DistanceMetric::DotProduct => -a.dot(b),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -276,6 +309,17 @@ mod tests {
         Vector::new(vec![]);
     }
 
+    #[test]
+    fn test_distance_metrics() {
+        let v1 = Vector::new(vec![1.0, 0.0]);
+        let v2 = Vector::new(vec![0.0, 2.0]);
+        
+        assert!((DistanceMetric::Euclidean.distance(&v1, &v2) - 5.0_f32.sqrt()).abs() < 1e-6);
+        assert!((DistanceMetric::Cosine.distance(&v1, &v2) - 1.0).abs() < 1e-6);
+        assert!(DistanceMetric::DotProduct.distance(&v1, &v2).abs() < 1e-6);
+        assert!((DistanceMetric::Cosine.distance(&v1, &v1)).abs() < 1e-6);
+    }
+
     #[test]
     fn test_vector_serialization() {
         let vector = Vector::new(vec![1.0, 2.0, 3.0]);
@@ -1 +1,691 @@
-// TODO: Implement distributed consciousness
+//! Distributed consciousness
+//!
+//! Every node keeps a compact picture of the whole memory graph, built by
+//! gossip rather than by asking other nodes at query time. A node publishes a
+//! `NodeDigest` of what it stores: the routing summaries of its shards
+//! (centroids and entity counts) and a sketch of its hottest edges. Digests
+//! are versioned by their origin, and nodes exchange them push-pull with
+//! random peers, keeping the newest version of each, so every node's view
+//! converges to the same set of digests.
+//!
+//! The state of a view is condensed into its summary entropy: the Shannon
+//! entropy of the entity distribution over all known centroids. Peers report
+//! their entropy when they gossip; a node considers itself converged once it
+//! knows a digest from every member and its entropy differs from each peer's
+//! by less than `entropy_convergence_epsilon`. A view that stops changing
+//! without converging for `max_rounds` rounds (for example across a network
+//! partition) reports `EntropyConvergenceFailed`.
+//!
+//! Awareness is the fraction of the global entity population a node's view
+//! represents: the sampled share of the entities in the digests it holds,
+//! scaled by the share of members it holds digests from. With every digest
+//! known it matches the `global_awareness_pct` the summaries were built with.
+
+use crate::core::config::DistributedConfig;
+use crate::core::error::{ConsensusError, ConsensusResult};
+use crate::core::{EntityId, NodeId};
+use crate::distributed::router::{Centroid, ShardSummary};
+use crate::distributed::transport::Transport;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::Arc;
+
+/// Gossip and convergence settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConsciousnessConfig {
+    /// Interval between gossip rounds (default: heartbeat interval)
+    pub gossip_interval_ms: u64,
+
+    /// Convergence threshold on the entropy delta between peers
+    pub epsilon: f64,
+
+    /// Rounds without progress before convergence is reported failed
+    /// (default: 50)
+    pub max_rounds: u64,
+
+    /// Counters per row of the edge sketch (default: 512)
+    pub sketch_width: usize,
+
+    /// Rows of the edge sketch (default: 4)
+    pub sketch_depth: usize,
+
+    /// Hot edges tracked per sketch (default: 16)
+    pub hot_edges: usize,
+}
+
+impl Default for ConsciousnessConfig {
+    fn default() -> Self {
+        Self::from(&DistributedConfig::default())
+    }
+}
+
+impl From<&DistributedConfig> for ConsciousnessConfig {
+    fn from(config: &DistributedConfig) -> Self {
+        Self {
+            gossip_interval_ms: config.heartbeat_interval_ms,
+            epsilon: config.entropy_convergence_epsilon,
+            max_rounds: 50,
+            sketch_width: 512,
+            sketch_depth: 4,
+            hot_edges: 16,
+        }
+    }
+}
+
+/// Count-min sketch of edge traversals with a heavy-hitter list
+///
+/// Estimates never undercount; sketches of the same shape merge by adding
+/// their counters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EdgeSketch {
+    width: usize,
+    depth: usize,
+    counters: Vec<u64>,
+    capacity: usize,
+    heavy: Vec<(EntityId, EntityId, u64)>,
+}
+
+impl EdgeSketch {
+    /// Create an empty sketch tracking `capacity` hot edges
+    pub fn new(width: usize, depth: usize, capacity: usize) -> Self {
+        let (width, depth) = (width.max(1), depth.max(1));
+        Self {
+            width,
+            depth,
+            counters: vec![0; width * depth],
+            capacity,
+            heavy: Vec::new(),
+        }
+    }
+
+    /// Create an empty sketch shaped by `config`
+    pub fn with_config(config: &ConsciousnessConfig) -> Self {
+        Self::new(config.sketch_width, config.sketch_depth, config.hot_edges)
+    }
+
+    /// Record `count` traversals of `source → target`
+    pub fn record(&mut self, source: EntityId, target: EntityId, count: u64) {
+        for row in 0..self.depth {
+            let slot = self.slot(row, &source, &target);
+            self.counters[slot] += count;
+        }
+        let estimate = self.estimate(&source, &target);
+        self.offer(source, target, estimate);
+    }
+
+    /// Upper-bound estimate of an edge's traversals
+    pub fn estimate(&self, source: &EntityId, target: &EntityId) -> u64 {
+        (0..self.depth)
+            .map(|row| self.counters[self.slot(row, source, target)])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Total traversals recorded
+    pub fn total(&self) -> u64 {
+        self.counters[..self.width].iter().sum()
+    }
+
+    /// Hottest edges, most traversed first
+    pub fn hot(&self) -> &[(EntityId, EntityId, u64)] {
+        &self.heavy
+    }
+
+    /// Add another sketch's counts to this one
+    ///
+    /// Sketches of a different shape are ignored.
+    pub fn merge(&mut self, other: &EdgeSketch) {
+        if (self.width, self.depth) != (other.width, other.depth) {
+            tracing::warn!("ignoring edge sketch of a different shape");
+            return;
+        }
+        for (mine, theirs) in self.counters.iter_mut().zip(&other.counters) {
+            *mine += theirs;
+        }
+        let candidates: Vec<(EntityId, EntityId)> =
+            self.heavy.iter().chain(&other.heavy).map(|(s, t, _)| (*s, *t)).collect();
+        self.heavy.clear();
+        for (source, target) in candidates {
+            let estimate = self.estimate(&source, &target);
+            self.offer(source, target, estimate);
+        }
+    }
+
+    fn offer(&mut self, source: EntityId, target: EntityId, estimate: u64) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some(entry) = self.heavy.iter_mut().find(|(s, t, _)| *s == source && *t == target) {
+            entry.2 = estimate;
+        } else if self.heavy.len() < self.capacity {
+            self.heavy.push((source, target, estimate));
+        } else if self.heavy.last().is_some_and(|(_, _, min)| estimate > *min) {
+            self.heavy.pop();
+            self.heavy.push((source, target, estimate));
+        } else {
+            return;
+        }
+        self.heavy.sort_by_key(|entry| std::cmp::Reverse(entry.2));
+    }
+
+    fn slot(&self, row: usize, source: &EntityId, target: &EntityId) -> usize {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&(row as u64).to_le_bytes());
+        hasher.update(source.as_uuid().as_bytes());
+        hasher.update(target.as_uuid().as_bytes());
+        let hash = u64::from_le_bytes(hasher.finalize().as_bytes()[..8].try_into().expect("8 bytes"));
+        row * self.width + (hash % self.width as u64) as usize
+    }
+}
+
+/// What one node publishes about its share of the memory graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeDigest {
+    /// Publishing node
+    pub node: NodeId,
+
+    /// Publication counter of the origin; newer versions replace older ones
+    pub version: u64,
+
+    /// Routing summaries of the node's shards
+    pub shards: Vec<ShardSummary>,
+
+    /// Hot edges of the node's shards
+    pub hot_edges: EdgeSketch,
+}
+
+impl NodeDigest {
+    /// Entities in the node's shards
+    pub fn entity_count(&self) -> usize {
+        self.shards.iter().map(|s| s.entity_count).sum()
+    }
+
+    /// Entities sampled into the node's summaries
+    pub fn sampled(&self) -> usize {
+        self.shards.iter().map(|s| s.sampled).sum()
+    }
+}
+
+/// Messages of the gossip protocol
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsciousnessMessage {
+    /// Start of an exchange: the digest versions the sender holds
+    Sync {
+        /// Version held per origin
+        versions: BTreeMap<NodeId, u64>,
+
+        /// Sender's summary entropy
+        entropy: f64,
+    },
+
+    /// Digests the recipient lacks
+    Delta {
+        /// Newer digests
+        digests: Vec<NodeDigest>,
+
+        /// Version held per origin, so the recipient can send back what the
+        /// sender lacks
+        versions: BTreeMap<NodeId, u64>,
+
+        /// Sender's summary entropy after merging
+        entropy: f64,
+
+        /// Whether the recipient should answer with its own delta
+        reply: bool,
+    },
+}
+
+/// One node's gossiped view of the global memory graph
+pub struct Consciousness {
+    id: NodeId,
+    config: ConsciousnessConfig,
+    transport: Arc<dyn Transport<ConsciousnessMessage>>,
+    digests: BTreeMap<NodeId, NodeDigest>,
+    members: BTreeSet<NodeId>,
+    version: u64,
+    entropy: f64,
+    peer_deltas: BTreeMap<NodeId, f64>,
+    stalled_rounds: u64,
+    next_gossip_at: u64,
+    rng_state: u64,
+}
+
+impl Consciousness {
+    /// Create a node's view knowing only itself
+    pub fn new(
+        id: NodeId,
+        config: ConsciousnessConfig,
+        transport: Arc<dyn Transport<ConsciousnessMessage>>,
+        now: u64,
+    ) -> Self {
+        let seed = id.as_uuid().as_u64_pair();
+        let mut view = Self {
+            id,
+            config,
+            transport,
+            digests: BTreeMap::new(),
+            members: BTreeSet::from([id]),
+            version: 0,
+            entropy: 0.0,
+            peer_deltas: BTreeMap::new(),
+            stalled_rounds: 0,
+            next_gossip_at: now,
+            rng_state: (seed.0 ^ seed.1).max(1),
+        };
+        view.publish(Vec::new(), EdgeSketch::with_config(&config));
+        view
+    }
+
+    /// Node identifier
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    /// Replace this node's digest with its current shards and hot edges
+    pub fn publish(&mut self, shards: Vec<ShardSummary>, hot_edges: EdgeSketch) {
+        self.version += 1;
+        self.digests.insert(
+            self.id,
+            NodeDigest {
+                node: self.id,
+                version: self.version,
+                shards,
+                hot_edges,
+            },
+        );
+        self.view_changed();
+    }
+
+    /// Set the cluster members, usually from `Membership::active_members`
+    ///
+    /// Digests of nodes that are no longer members are dropped.
+    pub fn set_members(&mut self, members: impl IntoIterator<Item = NodeId>) {
+        self.members = members.into_iter().collect();
+        self.members.insert(self.id);
+        let before = self.digests.len();
+        self.digests.retain(|node, _| self.members.contains(node));
+        self.peer_deltas.retain(|node, _| self.members.contains(node));
+        if self.digests.len() != before {
+            self.view_changed();
+        }
+    }
+
+    /// Digests this node holds, its own included
+    pub fn digests(&self) -> impl Iterator<Item = &NodeDigest> {
+        self.digests.values()
+    }
+
+    /// Shard summaries from every known digest, e.g. to feed a router
+    pub fn shard_summaries(&self) -> impl Iterator<Item = &ShardSummary> {
+        self.digests.values().flat_map(|d| &d.shards)
+    }
+
+    /// Every known centroid with the node holding it
+    pub fn centroids(&self) -> impl Iterator<Item = (NodeId, &Centroid)> {
+        self.digests
+            .values()
+            .flat_map(|d| d.shards.iter().flat_map(move |s| s.centroids.iter().map(move |c| (d.node, c))))
+    }
+
+    /// Entities across all known digests
+    pub fn global_entity_count(&self) -> usize {
+        self.digests.values().map(NodeDigest::entity_count).sum()
+    }
+
+    /// Hottest edges cluster-wide, most traversed first
+    pub fn hot_edges(&self) -> Vec<(EntityId, EntityId, u64)> {
+        let mut merged = EdgeSketch::with_config(&self.config);
+        for digest in self.digests.values() {
+            merged.merge(&digest.hot_edges);
+        }
+        merged.hot().to_vec()
+    }
+
+    /// Shannon entropy (bits) of the entity distribution over known centroids
+    pub fn entropy(&self) -> f64 {
+        self.entropy
+    }
+
+    /// Fraction of the global entity population this view represents
+    pub fn awareness_pct(&self) -> f64 {
+        let entities = self.global_entity_count();
+        if entities == 0 {
+            return 0.0;
+        }
+        let sampled: usize = self.digests.values().map(NodeDigest::sampled).sum();
+        let coverage = self.digests.len() as f64 / self.members.len() as f64;
+        sampled as f64 / entities as f64 * coverage
+    }
+
+    /// Largest entropy difference with the peers heard from since the view
+    /// last changed, `None` if none was heard
+    pub fn entropy_delta(&self) -> Option<f64> {
+        self.peer_deltas.values().copied().reduce(f64::max)
+    }
+
+    /// Whether the view has converged
+    ///
+    /// Returns `Ok(false)` while gossip is still making progress.
+    ///
+    /// # Errors
+    /// * `EntropyConvergenceFailed` if the view stopped changing for
+    ///   `max_rounds` rounds without converging
+    pub fn convergence_status(&self) -> ConsensusResult<bool> {
+        let complete = self.members.iter().all(|m| self.digests.contains_key(m));
+        let delta = self.entropy_delta();
+        let agreed = self.members.len() == 1 || delta.is_some_and(|d| d < self.config.epsilon);
+        if complete && agreed {
+            return Ok(true);
+        }
+        if self.stalled_rounds >= self.config.max_rounds {
+            // A missing digest counts as a full bit of disagreement
+            let delta = if complete { delta.unwrap_or(f64::INFINITY) } else { delta.unwrap_or(0.0).max(1.0) };
+            return Err(ConsensusError::EntropyConvergenceFailed {
+                delta,
+                threshold: self.config.epsilon,
+            });
+        }
+        Ok(false)
+    }
+
+    /// Advance timers to `now` (milliseconds)
+    pub fn tick(&mut self, now: u64) {
+        if now < self.next_gossip_at {
+            return;
+        }
+        self.next_gossip_at = now + self.config.gossip_interval_ms;
+        self.stalled_rounds += 1;
+
+        let peers: Vec<NodeId> = self.members.iter().copied().filter(|m| *m != self.id).collect();
+        if peers.is_empty() {
+            return;
+        }
+        let peer = peers[(self.next_random() % peers.len() as u64) as usize];
+        self.send(
+            peer,
+            ConsciousnessMessage::Sync {
+                versions: self.versions(),
+                entropy: self.entropy,
+            },
+        );
+    }
+
+    /// Handle a message from another node
+    pub fn step(&mut self, from: NodeId, message: ConsciousnessMessage, _now: u64) {
+        match message {
+            ConsciousnessMessage::Sync { versions, entropy } => {
+                self.record_peer(from, entropy);
+                let digests = self.newer_than(&versions);
+                self.send(
+                    from,
+                    ConsciousnessMessage::Delta {
+                        digests,
+                        versions: self.versions(),
+                        entropy: self.entropy,
+                        reply: true,
+                    },
+                );
+            }
+            ConsciousnessMessage::Delta {
+                digests,
+                versions,
+                entropy,
+                reply,
+            } => {
+                self.merge(digests);
+                self.record_peer(from, entropy);
+                if reply {
+                    let digests = self.newer_than(&versions);
+                    self.send(
+                        from,
+                        ConsciousnessMessage::Delta {
+                            digests,
+                            versions: self.versions(),
+                            entropy: self.entropy,
+                            reply: false,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    fn versions(&self) -> BTreeMap<NodeId, u64> {
+        self.digests.iter().map(|(node, d)| (*node, d.version)).collect()
+    }
+
+    fn newer_than(&self, versions: &BTreeMap<NodeId, u64>) -> Vec<NodeDigest> {
+        self.digests
+            .values()
+            .filter(|d| versions.get(&d.node).is_none_or(|v| *v < d.version))
+            .cloned()
+            .collect()
+    }
+
+    fn merge(&mut self, digests: Vec<NodeDigest>) {
+        let mut changed = false;
+        for digest in digests {
+            if digest.node == self.id || !self.members.contains(&digest.node) {
+                continue;
+            }
+            if self.digests.get(&digest.node).is_none_or(|d| d.version < digest.version) {
+                self.digests.insert(digest.node, digest);
+                changed = true;
+            }
+        }
+        if changed {
+            self.view_changed();
+        }
+    }
+
+    fn record_peer(&mut self, peer: NodeId, entropy: f64) {
+        if self.members.contains(&peer) {
+            self.peer_deltas.insert(peer, (self.entropy - entropy).abs());
+        }
+    }
+
+    /// Recompute the entropy; earlier peer comparisons no longer apply
+    fn view_changed(&mut self) {
+        let counts: Vec<f64> = self.centroids().map(|(_, c)| c.count as f64).filter(|c| *c > 0.0).collect();
+        let total: f64 = counts.iter().sum();
+        self.entropy = if total > 0.0 {
+            -counts.iter().map(|c| c / total).map(|p| p * p.log2()).sum::<f64>()
+        } else {
+            0.0
+        };
+        self.peer_deltas.clear();
+        self.stalled_rounds = 0;
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 7;
+        self.rng_state ^= self.rng_state << 17;
+        self.rng_state
+    }
+
+    fn send(&self, to: NodeId, message: ConsciousnessMessage) {
+        self.transport.send(self.id, to, message);
+    }
+}
+
+impl fmt::Debug for Consciousness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Consciousness")
+            .field("id", &self.id)
+            .field("digests", &self.digests.len())
+            .field("members", &self.members.len())
+            .field("entropy", &self.entropy)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vector::DistanceMetric;
+    use crate::core::{ShardId, Vector};
+    use crate::distributed::router::RouterConfig;
+    use crate::distributed::transport::SimulatedNetwork;
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn uniform(&mut self) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 >> 40) as f32 / (1u64 << 24) as f32
+        }
+    }
+
+    struct Cluster {
+        network: Arc<SimulatedNetwork<ConsciousnessMessage>>,
+        nodes: BTreeMap<NodeId, Consciousness>,
+        order: Vec<NodeId>,
+        hot: (EntityId, EntityId),
+    }
+
+    impl Cluster {
+        /// Nodes holding two shards of 500 entities each, summarized at 10%
+        fn start(size: usize, seed: u64) -> Self {
+            let network = Arc::new(SimulatedNetwork::new(seed));
+            network.set_latency(1, 5);
+            let config = ConsciousnessConfig {
+                gossip_interval_ms: 20,
+                epsilon: 1e-3,
+                max_rounds: 20,
+                ..ConsciousnessConfig::default()
+            };
+            let router_config = RouterConfig {
+                metric: DistanceMetric::Euclidean,
+                centroids_per_shard: 4,
+                awareness_pct: 0.1,
+                ..RouterConfig::default()
+            };
+            let order: Vec<NodeId> = (0..size).map(|_| NodeId::new()).collect();
+            let hot = (EntityId::new(), EntityId::new());
+            let mut rng = Rng(seed);
+            let mut nodes = BTreeMap::new();
+            for (index, id) in order.iter().enumerate() {
+                let mut node = Consciousness::new(*id, config, network.clone(), 0);
+                node.set_members(order.iter().copied());
+                let shards = (0..2)
+                    .map(|s| {
+                        let offset = (index * 2 + s) as f32 * 4.0;
+                        let vectors: Vec<Vector> = (0..500)
+                            .map(|_| Vector::new((0..4).map(|_| offset + rng.uniform()).collect()))
+                            .collect();
+                        ShardSummary::build(ShardId::new(), &vectors, &router_config)
+                    })
+                    .collect();
+                // Every node sees the same hot edge, plus local noise
+                let mut sketch = EdgeSketch::with_config(&config);
+                sketch.record(hot.0, hot.1, 10);
+                for _ in 0..50 {
+                    sketch.record(EntityId::new(), EntityId::new(), 1);
+                }
+                node.publish(shards, sketch);
+                nodes.insert(*id, node);
+            }
+            Self {
+                network,
+                nodes,
+                order,
+                hot,
+            }
+        }
+
+        fn run(&mut self, millis: u64) {
+            for _ in 0..millis {
+                self.network.advance(1);
+                let now = self.network.now();
+                for (id, node) in self.nodes.iter_mut() {
+                    node.tick(now);
+                    for envelope in self.network.drain(*id) {
+                        node.step(envelope.from, envelope.message, now);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_edge_sketch_estimates_and_merges() {
+        let mut a = EdgeSketch::new(64, 4, 3);
+        let mut b = EdgeSketch::new(64, 4, 3);
+        let ids: Vec<(EntityId, EntityId)> = (0..200).map(|_| (EntityId::new(), EntityId::new())).collect();
+        for (i, (s, t)) in ids.iter().enumerate() {
+            a.record(*s, *t, 1);
+            if i < 3 {
+                a.record(*s, *t, 100);
+                b.record(*s, *t, 50);
+            }
+        }
+        for (s, t) in &ids {
+            assert!(a.estimate(s, t) >= 1);
+        }
+        let top: Vec<_> = a.hot().iter().map(|(s, t, _)| (*s, *t)).collect();
+        assert_eq!(top.len(), 3);
+        assert!(ids[..3].iter().all(|edge| top.contains(edge)));
+
+        a.merge(&b);
+        assert_eq!(a.total(), 200 + 300 + 150);
+        assert!(a.hot().iter().all(|(_, _, count)| *count >= 151));
+    }
+
+    #[test]
+    fn test_gossip_converges_on_global_summary() {
+        let mut cluster = Cluster::start(8, 1);
+        let first = cluster.order[0];
+        assert!(!cluster.nodes[&first].convergence_status().unwrap());
+        assert!(cluster.nodes[&first].awareness_pct() < 0.02);
+
+        cluster.run(1000);
+        let expected_entropy = cluster.nodes[&first].entropy();
+        for node in cluster.nodes.values() {
+            assert!(node.convergence_status().unwrap(), "{node:?} did not converge");
+            assert_eq!(node.digests().count(), 8);
+            assert_eq!(node.global_entity_count(), 8 * 2 * 500);
+            assert!((node.entropy() - expected_entropy).abs() < 1e-9);
+            assert!((node.awareness_pct() - 0.1).abs() < 0.01);
+
+            let (source, target, count) = node.hot_edges()[0];
+            assert_eq!((source, target), cluster.hot);
+            assert!(count >= 80);
+        }
+        // 16 shards of 4 centroids each, roughly evenly populated
+        assert!(expected_entropy > 5.5 && expected_entropy <= 6.0);
+    }
+
+    #[test]
+    fn test_partition_fails_convergence_until_healed() {
+        let mut cluster = Cluster::start(6, 2);
+        let (left, right) = cluster.order.split_at(3);
+        cluster.network.partition(left, right);
+        cluster.run(1000);
+
+        let first = cluster.order[0];
+        match cluster.nodes[&first].convergence_status() {
+            Err(ConsensusError::EntropyConvergenceFailed { delta, threshold }) => {
+                assert!(delta >= threshold);
+            }
+            other => panic!("expected convergence failure, got {other:?}"),
+        }
+        assert!((cluster.nodes[&first].awareness_pct() - 0.05).abs() < 0.01);
+
+        cluster.network.heal();
+        cluster.run(1000);
+        assert!(cluster.nodes.values().all(|n| n.convergence_status().unwrap()));
+
+        // A departed member's digest is forgotten
+        let gone = cluster.order[5];
+        let node = cluster.nodes.get_mut(&first).unwrap();
+        node.set_members(cluster.order[..5].iter().copied());
+        assert_eq!(node.global_entity_count(), 5 * 2 * 500);
+        assert!(node.convergence_status().is_ok());
+        assert!(node.digests().all(|d| d.node != gone));
+    }
+}
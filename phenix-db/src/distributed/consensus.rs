@@ -1 +1,1639 @@
-// TODO: Implement consensus protocol
+//! Raft consensus for cluster metadata
+//!
+//! Membership, the shard map and collection schemas are replicated through a
+//! Raft log so every node agrees on them. `RaftNode` is a deterministic state
+//! machine: the caller drives it with `tick` and `step`, which return a
+//! `Ready` holding the state to persist and the messages to send. Keeping
+//! I/O with the caller makes the node usable both over the RPC layer and
+//! inside a `SimulatedNetwork`.
+//!
+//! Implemented from the Raft paper and dissertation:
+//! - Leader election with randomized timeouts; a node that heard from a
+//!   leader within the minimum election timeout ignores vote requests, so a
+//!   removed or partitioned node cannot disrupt a healthy cluster
+//! - Log replication with fast back-off on mismatch; only entries of the
+//!   leader's own term are committed by counting replicas
+//! - Snapshots of the applied metadata once the log grows past a threshold,
+//!   sent with `InstallSnapshot` to followers that fall behind them
+//! - Single-server membership changes, one at a time, effective as soon as
+//!   they are appended
+//!
+//! A `Ready` only carries what changed: a new term or vote, a new snapshot
+//! and the log entries from the first one that changed. The caller must
+//! make these durable before sending the messages, or a node could forget
+//! a vote or an acknowledged entry in a crash, and then report them with
+//! `RaftNode::advance`. The leader counts its own copy of an entry towards
+//! a commit only once it has been reported durable. `PersistentState::apply`
+//! folds a `Ready` into a stored state, which `RaftNode::recover` restarts
+//! from.
+
+use crate::core::collection::CollectionConfig;
+use crate::core::config::DistributedConfig;
+use crate::core::error::{ConsensusError, ConsensusResult};
+use crate::core::{NodeId, ShardId};
+use crate::distributed::transport::Envelope;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Raft term
+pub type Term = u64;
+
+/// Position in the Raft log, starting at 1
+pub type LogIndex = u64;
+
+/// Change to the replicated cluster metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "op")]
+pub enum MetadataCommand {
+    /// Add or update a cluster member
+    RegisterNode {
+        /// Member to add
+        node: NodeId,
+
+        /// Address peers use to reach it
+        address: String,
+    },
+
+    /// Remove a cluster member
+    DeregisterNode {
+        /// Member to remove
+        node: NodeId,
+    },
+
+    /// Set the replicas of a shard, primary first
+    AssignShard {
+        /// Shard to assign
+        shard: ShardId,
+
+        /// Replica nodes, primary first
+        replicas: Vec<NodeId>,
+    },
+
+    /// Remove a shard from the shard map
+    RemoveShard {
+        /// Shard to remove
+        shard: ShardId,
+    },
+
+    /// Create or replace a collection schema
+    PutCollection {
+        /// Collection name
+        name: String,
+
+        /// Collection configuration
+        config: CollectionConfig,
+    },
+
+    /// Remove a collection schema
+    DropCollection {
+        /// Collection name
+        name: String,
+    },
+}
+
+/// Cluster metadata replicated by Raft
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClusterMetadata {
+    /// Cluster members and their addresses
+    pub nodes: BTreeMap<NodeId, String>,
+
+    /// Replicas of each shard, primary first
+    pub shards: BTreeMap<ShardId, Vec<NodeId>>,
+
+    /// Collection schemas by name
+    pub collections: BTreeMap<String, CollectionConfig>,
+}
+
+impl ClusterMetadata {
+    /// Apply a committed command
+    pub fn apply(&mut self, command: &MetadataCommand) {
+        match command {
+            MetadataCommand::RegisterNode { node, address } => {
+                self.nodes.insert(*node, address.clone());
+            }
+            MetadataCommand::DeregisterNode { node } => {
+                self.nodes.remove(node);
+            }
+            MetadataCommand::AssignShard { shard, replicas } => {
+                self.shards.insert(*shard, replicas.clone());
+            }
+            MetadataCommand::RemoveShard { shard } => {
+                self.shards.remove(shard);
+            }
+            MetadataCommand::PutCollection { name, config } => {
+                self.collections.insert(name.clone(), config.clone());
+            }
+            MetadataCommand::DropCollection { name } => {
+                self.collections.remove(name);
+            }
+        }
+    }
+}
+
+/// Payload of a log entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryPayload {
+    /// Appended by a new leader to commit entries of earlier terms
+    Noop,
+
+    /// Metadata change
+    Command(MetadataCommand),
+
+    /// Add a voting member
+    AddVoter(NodeId),
+
+    /// Remove a voting member
+    RemoveVoter(NodeId),
+}
+
+/// Raft log entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Position in the log
+    pub index: LogIndex,
+
+    /// Term of the leader that created the entry
+    pub term: Term,
+
+    /// Entry contents
+    pub payload: EntryPayload,
+}
+
+/// Applied state up to and including `last_index`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetadataSnapshot {
+    /// Last log index covered by the snapshot
+    pub last_index: LogIndex,
+
+    /// Term of the entry at `last_index`
+    pub last_term: Term,
+
+    /// Voting members as of `last_index`
+    pub voters: BTreeSet<NodeId>,
+
+    /// Metadata as of `last_index`
+    pub metadata: ClusterMetadata,
+}
+
+/// State that must survive a restart
+///
+/// A node that has never run starts from the default, empty state and
+/// builds it up from the `Ready`s of `RaftNode::new`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PersistentState {
+    /// Latest term seen
+    pub term: Term,
+
+    /// Candidate voted for in `term`
+    pub voted_for: Option<NodeId>,
+
+    /// Latest snapshot
+    pub snapshot: MetadataSnapshot,
+
+    /// Log entries after the snapshot
+    pub entries: Vec<LogEntry>,
+}
+
+impl PersistentState {
+    /// Fold the durable part of a `Ready` into this state
+    ///
+    /// Stores that keep entries individually do the same: replace the
+    /// snapshot and drop the entries it covers, then replace the entries
+    /// from `entries_from` on with `entries`.
+    pub fn apply(&mut self, ready: &Ready) {
+        if let Some(hard_state) = ready.hard_state {
+            self.term = hard_state.term;
+            self.voted_for = hard_state.voted_for;
+        }
+        if let Some(snapshot) = &ready.snapshot {
+            self.entries.retain(|entry| entry.index > snapshot.last_index);
+            self.snapshot = snapshot.clone();
+        }
+        if let Some(from) = ready.entries_from {
+            self.entries.retain(|entry| entry.index < from);
+            self.entries.extend(ready.entries.iter().cloned());
+        }
+    }
+}
+
+/// Term and vote of a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HardState {
+    /// Latest term seen
+    pub term: Term,
+
+    /// Candidate voted for in `term`
+    pub voted_for: Option<NodeId>,
+}
+
+/// Work produced by a node since its last `Ready`
+///
+/// Persist `hard_state`, `snapshot` and `entries` first, then send
+/// `messages` and pass the `Ready` to `RaftNode::advance`.
+#[derive(Debug, Default)]
+pub struct Ready {
+    /// New term or vote, if either changed
+    pub hard_state: Option<HardState>,
+
+    /// New snapshot; replaces the stored one and the entries it covers
+    pub snapshot: Option<MetadataSnapshot>,
+
+    /// First log index whose stored entry changed; `None` if the log did
+    /// not change apart from `snapshot`
+    pub entries_from: Option<LogIndex>,
+
+    /// Entries from `entries_from` to the end of the log; stored entries
+    /// at or after `entries_from` are replaced by these
+    pub entries: Vec<LogEntry>,
+
+    /// Messages to send once the state above is durable
+    pub messages: Vec<Envelope<RaftMessage>>,
+}
+
+impl Ready {
+    /// Whether there is nothing to persist or send
+    pub fn is_empty(&self) -> bool {
+        self.hard_state.is_none()
+            && self.snapshot.is_none()
+            && self.entries_from.is_none()
+            && self.messages.is_empty()
+    }
+}
+
+/// Messages exchanged between Raft nodes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RaftMessage {
+    /// Candidate asks for a vote
+    RequestVote {
+        /// Candidate's term
+        term: Term,
+
+        /// Index of the candidate's last log entry
+        last_log_index: LogIndex,
+
+        /// Term of the candidate's last log entry
+        last_log_term: Term,
+    },
+
+    /// Reply to `RequestVote`
+    VoteResponse {
+        /// Voter's term
+        term: Term,
+
+        /// Whether the vote was granted
+        granted: bool,
+    },
+
+    /// Leader replicates entries; empty entries act as a heartbeat
+    AppendEntries {
+        /// Leader's term
+        term: Term,
+
+        /// Index of the entry preceding `entries`
+        prev_log_index: LogIndex,
+
+        /// Term of the entry at `prev_log_index`
+        prev_log_term: Term,
+
+        /// Entries to append, possibly empty
+        entries: Vec<LogEntry>,
+
+        /// Leader's commit index
+        leader_commit: LogIndex,
+    },
+
+    /// Reply to `AppendEntries`
+    AppendResponse {
+        /// Follower's term
+        term: Term,
+
+        /// Whether the entries were appended
+        success: bool,
+
+        /// Last index now matching the leader on success; on failure a
+        /// hint of where the follower's log ends
+        match_index: LogIndex,
+    },
+
+    /// Leader replaces a lagging follower's log with its snapshot
+    InstallSnapshot {
+        /// Leader's term
+        term: Term,
+
+        /// Leader's latest snapshot
+        snapshot: MetadataSnapshot,
+    },
+
+    /// Reply to `InstallSnapshot`
+    SnapshotResponse {
+        /// Follower's term
+        term: Term,
+
+        /// Last index covered by the follower's state
+        last_index: LogIndex,
+    },
+}
+
+impl RaftMessage {
+    fn term(&self) -> Term {
+        match self {
+            Self::RequestVote { term, .. }
+            | Self::VoteResponse { term, .. }
+            | Self::AppendEntries { term, .. }
+            | Self::AppendResponse { term, .. }
+            | Self::InstallSnapshot { term, .. }
+            | Self::SnapshotResponse { term, .. } => *term,
+        }
+    }
+}
+
+/// Role of a node in its current term
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RaftRole {
+    /// Replicates the leader's log
+    Follower,
+
+    /// Campaigning for leadership
+    Candidate,
+
+    /// Accepts proposals and replicates them
+    Leader,
+}
+
+/// Raft timing and log compaction settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RaftConfig {
+    /// Lower bound of the randomized election timeout (default: 150ms)
+    pub election_timeout_min_ms: u64,
+
+    /// Upper bound of the randomized election timeout (default: 300ms)
+    pub election_timeout_max_ms: u64,
+
+    /// Interval between leader heartbeats (default: 50ms)
+    pub heartbeat_interval_ms: u64,
+
+    /// Maximum entries per `AppendEntries` message (default: 64)
+    pub max_entries_per_append: usize,
+
+    /// Applied entries kept in the log before it is compacted into a
+    /// snapshot (default: 1024)
+    pub snapshot_threshold: u64,
+}
+
+impl Default for RaftConfig {
+    fn default() -> Self {
+        Self {
+            election_timeout_min_ms: 150,
+            election_timeout_max_ms: 300,
+            heartbeat_interval_ms: 50,
+            max_entries_per_append: 64,
+            snapshot_threshold: 1024,
+        }
+    }
+}
+
+impl From<&DistributedConfig> for RaftConfig {
+    /// Elections start once a node has missed heartbeats for the failure
+    /// timeout, randomized up to twice that
+    fn from(config: &DistributedConfig) -> Self {
+        Self {
+            election_timeout_min_ms: config.node_failure_timeout_ms,
+            election_timeout_max_ms: config.node_failure_timeout_ms * 2,
+            heartbeat_interval_ms: config.heartbeat_interval_ms,
+            ..Self::default()
+        }
+    }
+}
+
+/// Log entries after a snapshot
+#[derive(Debug, Clone)]
+struct RaftLog {
+    snapshot: MetadataSnapshot,
+    entries: Vec<LogEntry>,
+}
+
+impl RaftLog {
+    fn snapshot_index(&self) -> LogIndex {
+        self.snapshot.last_index
+    }
+
+    fn last_index(&self) -> LogIndex {
+        self.entries.last().map_or(self.snapshot.last_index, |e| e.index)
+    }
+
+    fn last_term(&self) -> Term {
+        self.entries.last().map_or(self.snapshot.last_term, |e| e.term)
+    }
+
+    /// Term of the entry at `index`, if it is known
+    fn term_at(&self, index: LogIndex) -> Option<Term> {
+        if index == self.snapshot.last_index {
+            return Some(self.snapshot.last_term);
+        }
+        self.entry(index).map(|e| e.term)
+    }
+
+    fn entry(&self, index: LogIndex) -> Option<&LogEntry> {
+        if index <= self.snapshot.last_index {
+            return None;
+        }
+        self.entries.get((index - self.snapshot.last_index - 1) as usize)
+    }
+
+    /// Up to `max` entries starting at `from`
+    fn slice(&self, from: LogIndex, max: usize) -> Vec<LogEntry> {
+        let start = (from.saturating_sub(self.snapshot.last_index + 1)) as usize;
+        self.entries.iter().skip(start).take(max).cloned().collect()
+    }
+
+    /// Remove the entry at `index` and everything after it
+    fn truncate_from(&mut self, index: LogIndex) {
+        let keep = index.saturating_sub(self.snapshot.last_index + 1) as usize;
+        self.entries.truncate(keep);
+    }
+
+    /// Voting members after applying configuration entries up to `index`
+    fn voters_at(&self, index: LogIndex) -> BTreeSet<NodeId> {
+        let mut voters = self.snapshot.voters.clone();
+        for entry in self.entries.iter().take_while(|e| e.index <= index) {
+            match entry.payload {
+                EntryPayload::AddVoter(node) => {
+                    voters.insert(node);
+                }
+                EntryPayload::RemoveVoter(node) => {
+                    voters.remove(&node);
+                }
+                _ => {}
+            }
+        }
+        voters
+    }
+
+    /// Index of the newest configuration entry after `index`
+    fn config_change_after(&self, index: LogIndex) -> Option<LogIndex> {
+        self.entries
+            .iter()
+            .rev()
+            .take_while(|e| e.index > index)
+            .find(|e| matches!(e.payload, EntryPayload::AddVoter(_) | EntryPayload::RemoveVoter(_)))
+            .map(|e| e.index)
+    }
+
+    /// Replace the prefix of the log up to `snapshot.last_index`
+    fn compact(&mut self, snapshot: MetadataSnapshot) {
+        let drop = snapshot.last_index.saturating_sub(self.snapshot.last_index) as usize;
+        self.entries.drain(..drop.min(self.entries.len()));
+        self.snapshot = snapshot;
+    }
+}
+
+/// Replication progress of one follower, tracked by the leader
+#[derive(Debug, Clone, Copy)]
+struct Progress {
+    next: LogIndex,
+    matched: LogIndex,
+}
+
+/// Raft participant replicating `ClusterMetadata`
+pub struct RaftNode {
+    id: NodeId,
+    config: RaftConfig,
+    role: RaftRole,
+    term: Term,
+    voted_for: Option<NodeId>,
+    leader: Option<NodeId>,
+    log: RaftLog,
+    voters: BTreeSet<NodeId>,
+    commit_index: LogIndex,
+    last_applied: LogIndex,
+    metadata: ClusterMetadata,
+    votes: BTreeSet<NodeId>,
+    progress: BTreeMap<NodeId, Progress>,
+    term_start_index: LogIndex,
+    election_deadline: u64,
+    heartbeat_due: u64,
+    last_leader_contact: Option<u64>,
+    rng_state: u64,
+    /// Term and vote as of the last `Ready`; `None` before the first
+    persisted: Option<HardState>,
+    snapshot_changed: bool,
+    /// First log index changed since the last `Ready`
+    unstable_from: Option<LogIndex>,
+    /// Last log index reported durable through `advance`
+    stable_index: LogIndex,
+    outbox: Vec<Envelope<RaftMessage>>,
+}
+
+impl RaftNode {
+    /// Create a node with an empty log
+    ///
+    /// Founding members pass the initial voter set; nodes that will join an
+    /// existing cluster pass an empty one and wait to be added by the leader.
+    /// The initial voters are written as identical, already committed
+    /// `AddVoter` entries at term 0 on every founder, so joining nodes learn
+    /// the configuration from the log like any other change.
+    ///
+    /// The first `Ready` carries the whole initial state, to be applied to
+    /// an empty `PersistentState`.
+    pub fn new(id: NodeId, voters: impl IntoIterator<Item = NodeId>, config: RaftConfig, now: u64) -> Self {
+        let voters: BTreeSet<NodeId> = voters.into_iter().collect();
+        let entries: Vec<_> = voters
+            .iter()
+            .zip(1..)
+            .map(|(voter, index)| LogEntry {
+                index,
+                term: 0,
+                payload: EntryPayload::AddVoter(*voter),
+            })
+            .collect();
+        let bootstrapped = entries.len() as LogIndex;
+        let state = PersistentState {
+            entries,
+            ..PersistentState::default()
+        };
+        let mut node = Self::recover(id, state, config, now);
+        node.commit_index = bootstrapped;
+        node.last_applied = bootstrapped;
+        node.persisted = None;
+        node.snapshot_changed = true;
+        node.unstable_from = Some(1);
+        node.stable_index = 0;
+        node
+    }
+
+    /// Restart a node from its persistent state
+    ///
+    /// Committed entries after the snapshot are re-applied once the node
+    /// learns the commit index from the leader.
+    pub fn recover(id: NodeId, state: PersistentState, config: RaftConfig, now: u64) -> Self {
+        let log = RaftLog {
+            snapshot: state.snapshot,
+            entries: state.entries,
+        };
+        let voters = log.voters_at(log.last_index());
+        let applied = log.snapshot_index();
+        let mut node = Self {
+            id,
+            config,
+            role: RaftRole::Follower,
+            term: state.term,
+            voted_for: state.voted_for,
+            leader: None,
+            metadata: log.snapshot.metadata.clone(),
+            log,
+            voters,
+            commit_index: applied,
+            last_applied: applied,
+            votes: BTreeSet::new(),
+            progress: BTreeMap::new(),
+            term_start_index: 0,
+            election_deadline: 0,
+            heartbeat_due: 0,
+            last_leader_contact: None,
+            rng_state: (id.as_uuid().as_u128() as u64) | 1,
+            persisted: Some(HardState {
+                term: state.term,
+                voted_for: state.voted_for,
+            }),
+            snapshot_changed: false,
+            unstable_from: None,
+            stable_index: 0,
+            outbox: Vec::new(),
+        };
+        node.stable_index = node.log.last_index();
+        node.reset_election_deadline(now);
+        node
+    }
+
+    /// Node identifier
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    /// Current role
+    pub fn role(&self) -> RaftRole {
+        self.role
+    }
+
+    /// Whether this node is the leader of its current term
+    pub fn is_leader(&self) -> bool {
+        self.role == RaftRole::Leader
+    }
+
+    /// Current term
+    pub fn term(&self) -> Term {
+        self.term
+    }
+
+    /// Leader of the current term, if known
+    pub fn leader(&self) -> Option<NodeId> {
+        self.leader
+    }
+
+    /// Voting members, including uncommitted changes
+    pub fn voters(&self) -> &BTreeSet<NodeId> {
+        &self.voters
+    }
+
+    /// Highest log index known to be committed
+    pub fn commit_index(&self) -> LogIndex {
+        self.commit_index
+    }
+
+    /// Highest log index applied to the metadata
+    pub fn last_applied(&self) -> LogIndex {
+        self.last_applied
+    }
+
+    /// Index of the last log entry
+    pub fn last_log_index(&self) -> LogIndex {
+        self.log.last_index()
+    }
+
+    /// Last index covered by the snapshot
+    pub fn snapshot_index(&self) -> LogIndex {
+        self.log.snapshot_index()
+    }
+
+    /// Applied cluster metadata
+    pub fn metadata(&self) -> &ClusterMetadata {
+        &self.metadata
+    }
+
+    /// Take the state changes and messages produced since the last call
+    ///
+    /// `tick` and `step` return this themselves; call it after `propose`,
+    /// `add_voter` or `remove_voter` to get their work without waiting for
+    /// the next tick.
+    pub fn ready(&mut self) -> Ready {
+        let hard_state = HardState {
+            term: self.term,
+            voted_for: self.voted_for,
+        };
+        let hard_state = (self.persisted != Some(hard_state)).then(|| {
+            self.persisted = Some(hard_state);
+            hard_state
+        });
+        let snapshot = std::mem::take(&mut self.snapshot_changed).then(|| self.log.snapshot.clone());
+        let entries_from = self
+            .unstable_from
+            .take()
+            .map(|from| from.max(self.log.snapshot_index() + 1));
+        let entries = match entries_from {
+            Some(from) => self.log.slice(from, usize::MAX),
+            None => Vec::new(),
+        };
+        Ready {
+            hard_state,
+            snapshot,
+            entries_from,
+            entries,
+            messages: std::mem::take(&mut self.outbox),
+        }
+    }
+
+    /// Report that a `Ready` taken from this node has been persisted
+    ///
+    /// A leader may commit entries its own log holds only up to what has
+    /// been reported here; messages produced by such a commit come with the
+    /// next `Ready`.
+    pub fn advance(&mut self, ready: &Ready) {
+        let durable = match (&ready.snapshot, ready.entries_from) {
+            (_, Some(from)) => ready.entries.last().map_or(from - 1, |entry| entry.index),
+            (Some(snapshot), None) => snapshot.last_index,
+            (None, None) => return,
+        };
+        // Entries changed since the `Ready` was taken are not durable yet
+        let limit = self.unstable_from.map_or(self.log.last_index(), |from| from - 1);
+        self.stable_index = self.stable_index.max(durable).min(limit);
+        self.maybe_commit();
+    }
+
+    /// Propose a metadata change
+    ///
+    /// # Returns
+    /// * Log index of the entry; the change is applied once
+    ///   `commit_index` reaches it
+    ///
+    /// # Errors
+    /// * `NotLeader` if this node is not the leader
+    pub fn propose(&mut self, command: MetadataCommand) -> ConsensusResult<LogIndex> {
+        self.ensure_leader()?;
+        Ok(self.append(EntryPayload::Command(command)))
+    }
+
+    /// Add a voting member
+    ///
+    /// # Errors
+    /// * `NotLeader` if this node is not the leader
+    /// * `MembershipChangePending` while an earlier change is uncommitted
+    pub fn add_voter(&mut self, node: NodeId) -> ConsensusResult<LogIndex> {
+        self.ensure_config_change_allowed()?;
+        if self.voters.contains(&node) {
+            return Ok(self.log.last_index());
+        }
+        Ok(self.append(EntryPayload::AddVoter(node)))
+    }
+
+    /// Remove a voting member, possibly the leader itself
+    ///
+    /// A leader that removes itself keeps replicating until the change
+    /// commits and then steps down.
+    ///
+    /// # Errors
+    /// * `NotLeader` if this node is not the leader
+    /// * `MembershipChangePending` while an earlier change is uncommitted
+    pub fn remove_voter(&mut self, node: NodeId) -> ConsensusResult<LogIndex> {
+        self.ensure_config_change_allowed()?;
+        if !self.voters.contains(&node) {
+            return Ok(self.log.last_index());
+        }
+        Ok(self.append(EntryPayload::RemoveVoter(node)))
+    }
+
+    /// Advance timers to `now` (milliseconds)
+    pub fn tick(&mut self, now: u64) -> Ready {
+        if self.is_leader() {
+            if now >= self.heartbeat_due {
+                self.broadcast_append();
+                self.heartbeat_due = now + self.config.heartbeat_interval_ms;
+            }
+        } else if now >= self.election_deadline {
+            if self.voters.contains(&self.id) {
+                self.campaign(now);
+            } else {
+                self.reset_election_deadline(now);
+            }
+        }
+        self.ready()
+    }
+
+    /// Handle a message from another node
+    pub fn step(&mut self, from: NodeId, message: RaftMessage, now: u64) -> Ready {
+        if message.term() > self.term {
+            if let RaftMessage::RequestVote { .. } = message {
+                if self.leader_is_alive(now) {
+                    return self.ready();
+                }
+            }
+            let leader = match message {
+                RaftMessage::AppendEntries { .. } | RaftMessage::InstallSnapshot { .. } => Some(from),
+                _ => None,
+            };
+            self.become_follower(message.term(), leader, now);
+        }
+
+        match message {
+            RaftMessage::RequestVote {
+                term,
+                last_log_index,
+                last_log_term,
+            } => self.handle_request_vote(from, term, last_log_index, last_log_term, now),
+            RaftMessage::VoteResponse { term, granted } => self.handle_vote_response(from, term, granted, now),
+            RaftMessage::AppendEntries {
+                term,
+                prev_log_index,
+                prev_log_term,
+                entries,
+                leader_commit,
+            } => self.handle_append_entries(from, term, prev_log_index, prev_log_term, entries, leader_commit, now),
+            RaftMessage::AppendResponse {
+                term,
+                success,
+                match_index,
+            } => self.handle_append_response(from, term, success, match_index),
+            RaftMessage::InstallSnapshot { term, snapshot } => self.handle_install_snapshot(from, term, snapshot, now),
+            RaftMessage::SnapshotResponse { term, last_index } => self.handle_snapshot_response(from, term, last_index),
+        }
+        self.ready()
+    }
+
+    fn handle_request_vote(
+        &mut self,
+        candidate: NodeId,
+        term: Term,
+        last_log_index: LogIndex,
+        last_log_term: Term,
+        now: u64,
+    ) {
+        let up_to_date = (last_log_term, last_log_index) >= (self.log.last_term(), self.log.last_index());
+        let granted = term == self.term
+            && self.voted_for.is_none_or(|voted| voted == candidate)
+            && up_to_date;
+        if granted {
+            self.voted_for = Some(candidate);
+            self.reset_election_deadline(now);
+        }
+        self.send(
+            candidate,
+            RaftMessage::VoteResponse {
+                term: self.term,
+                granted,
+            },
+        );
+    }
+
+    fn handle_vote_response(&mut self, voter: NodeId, term: Term, granted: bool, now: u64) {
+        if self.role != RaftRole::Candidate || term != self.term || !granted {
+            return;
+        }
+        self.votes.insert(voter);
+        if self.has_quorum(|node| self.votes.contains(node)) {
+            self.become_leader(now);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn handle_append_entries(
+        &mut self,
+        leader: NodeId,
+        term: Term,
+        prev_log_index: LogIndex,
+        prev_log_term: Term,
+        mut entries: Vec<LogEntry>,
+        leader_commit: LogIndex,
+        now: u64,
+    ) {
+        if term < self.term {
+            self.send(
+                leader,
+                RaftMessage::AppendResponse {
+                    term: self.term,
+                    success: false,
+                    match_index: 0,
+                },
+            );
+            return;
+        }
+        self.accept_leader(leader, now);
+
+        let last_new = prev_log_index + entries.len() as u64;
+        let mut prev = (prev_log_index, prev_log_term);
+        if prev_log_index < self.log.snapshot_index() {
+            // Everything up to the snapshot is committed and therefore matches
+            entries.retain(|e| e.index > self.log.snapshot_index());
+            prev = (self.log.snapshot_index(), self.log.snapshot.last_term);
+        }
+
+        if self.log.term_at(prev.0) != Some(prev.1) {
+            let hint = self.log.last_index().min(prev.0.saturating_sub(1));
+            self.send(
+                leader,
+                RaftMessage::AppendResponse {
+                    term: self.term,
+                    success: false,
+                    match_index: hint,
+                },
+            );
+            return;
+        }
+
+        let mut changed = false;
+        for entry in entries {
+            match self.log.term_at(entry.index) {
+                Some(term) if term == entry.term => continue,
+                Some(_) => self.log.truncate_from(entry.index),
+                None => {}
+            }
+            self.mark_unstable(entry.index);
+            self.log.entries.push(entry);
+            changed = true;
+        }
+        if changed {
+            self.voters = self.log.voters_at(self.log.last_index());
+        }
+
+        if leader_commit > self.commit_index {
+            self.commit_index = leader_commit.min(last_new.max(self.commit_index));
+            self.apply_committed();
+        }
+        self.send(
+            leader,
+            RaftMessage::AppendResponse {
+                term: self.term,
+                success: true,
+                match_index: last_new,
+            },
+        );
+    }
+
+    fn handle_append_response(&mut self, peer: NodeId, term: Term, success: bool, match_index: LogIndex) {
+        if !self.is_leader() || term != self.term {
+            return;
+        }
+        let last_index = self.log.last_index();
+        let Some(progress) = self.progress.get_mut(&peer) else {
+            return;
+        };
+        if success {
+            progress.matched = progress.matched.max(match_index);
+            progress.next = progress.matched + 1;
+            let behind = progress.next <= last_index;
+            self.maybe_commit();
+            if behind {
+                self.send_append(peer);
+            }
+        } else {
+            progress.next = (match_index + 1).min(progress.next.saturating_sub(1)).max(1);
+            self.send_append(peer);
+        }
+    }
+
+    fn handle_install_snapshot(&mut self, leader: NodeId, term: Term, snapshot: MetadataSnapshot, now: u64) {
+        if term < self.term {
+            self.send(
+                leader,
+                RaftMessage::SnapshotResponse {
+                    term: self.term,
+                    last_index: 0,
+                },
+            );
+            return;
+        }
+        self.accept_leader(leader, now);
+
+        let last_index = snapshot.last_index;
+        if last_index > self.commit_index {
+            if self.log.term_at(last_index) == Some(snapshot.last_term) {
+                // Keep the entries that follow the snapshot
+                self.log.compact(snapshot);
+            } else {
+                self.log = RaftLog {
+                    snapshot,
+                    entries: Vec::new(),
+                };
+                self.mark_unstable(last_index + 1);
+            }
+            self.snapshot_changed = true;
+            self.metadata = self.log.snapshot.metadata.clone();
+            self.commit_index = last_index;
+            self.last_applied = last_index;
+            self.voters = self.log.voters_at(self.log.last_index());
+        }
+        self.send(
+            leader,
+            RaftMessage::SnapshotResponse {
+                term: self.term,
+                last_index,
+            },
+        );
+    }
+
+    fn handle_snapshot_response(&mut self, peer: NodeId, term: Term, last_index: LogIndex) {
+        if !self.is_leader() || term != self.term {
+            return;
+        }
+        let last_log_index = self.log.last_index();
+        if let Some(progress) = self.progress.get_mut(&peer) {
+            progress.matched = progress.matched.max(last_index);
+            progress.next = progress.matched + 1;
+            let behind = progress.next <= last_log_index;
+            self.maybe_commit();
+            if behind {
+                self.send_append(peer);
+            }
+        }
+    }
+
+    fn ensure_leader(&self) -> ConsensusResult<()> {
+        if self.is_leader() {
+            Ok(())
+        } else {
+            Err(ConsensusError::NotLeader {
+                leader: self.leader.map(|leader| leader.to_string()),
+            })
+        }
+    }
+
+    /// A leader may only change membership once an entry of its own term
+    /// has committed and no other change is in flight
+    fn ensure_config_change_allowed(&self) -> ConsensusResult<()> {
+        self.ensure_leader()?;
+        if self.commit_index < self.term_start_index {
+            return Err(ConsensusError::MembershipChangePending {
+                index: self.term_start_index,
+            });
+        }
+        match self.log.config_change_after(self.commit_index) {
+            Some(index) => Err(ConsensusError::MembershipChangePending { index }),
+            None => Ok(()),
+        }
+    }
+
+    fn append(&mut self, payload: EntryPayload) -> LogIndex {
+        let index = self.log.last_index() + 1;
+        let is_config = matches!(payload, EntryPayload::AddVoter(_) | EntryPayload::RemoveVoter(_));
+        self.mark_unstable(index);
+        self.log.entries.push(LogEntry {
+            index,
+            term: self.term,
+            payload,
+        });
+        if is_config {
+            self.voters = self.log.voters_at(index);
+            self.sync_progress();
+        }
+        self.broadcast_append();
+        self.maybe_commit();
+        index
+    }
+
+    fn campaign(&mut self, now: u64) {
+        self.term += 1;
+        self.role = RaftRole::Candidate;
+        self.voted_for = Some(self.id);
+        self.leader = None;
+        self.votes = BTreeSet::from([self.id]);
+        self.reset_election_deadline(now);
+        tracing::debug!(node = %self.id, term = self.term, "starting election");
+
+        if self.has_quorum(|node| self.votes.contains(node)) {
+            self.become_leader(now);
+            return;
+        }
+        let request = RaftMessage::RequestVote {
+            term: self.term,
+            last_log_index: self.log.last_index(),
+            last_log_term: self.log.last_term(),
+        };
+        for peer in self.peers() {
+            self.send(peer, request.clone());
+        }
+    }
+
+    fn become_follower(&mut self, term: Term, leader: Option<NodeId>, now: u64) {
+        if term > self.term {
+            self.term = term;
+            self.voted_for = None;
+        }
+        self.role = RaftRole::Follower;
+        self.leader = leader;
+        self.votes.clear();
+        self.progress.clear();
+        self.reset_election_deadline(now);
+    }
+
+    fn become_leader(&mut self, now: u64) {
+        tracing::debug!(node = %self.id, term = self.term, "elected leader");
+        self.role = RaftRole::Leader;
+        self.leader = Some(self.id);
+        self.progress.clear();
+        self.sync_progress();
+        self.heartbeat_due = now + self.config.heartbeat_interval_ms;
+        self.term_start_index = self.log.last_index() + 1;
+        self.append(EntryPayload::Noop);
+    }
+
+    fn accept_leader(&mut self, leader: NodeId, now: u64) {
+        if self.role != RaftRole::Follower || self.leader != Some(leader) {
+            self.become_follower(self.term, Some(leader), now);
+        }
+        self.last_leader_contact = Some(now);
+        self.reset_election_deadline(now);
+    }
+
+    fn leader_is_alive(&self, now: u64) -> bool {
+        match self.last_leader_contact {
+            Some(contact) if self.leader.is_some() || self.is_leader() => {
+                now < contact + self.config.election_timeout_min_ms
+            }
+            _ => self.is_leader(),
+        }
+    }
+
+    /// Track every voter other than this node
+    fn sync_progress(&mut self) {
+        let next = self.log.last_index() + 1;
+        let peers = self.peers();
+        self.progress.retain(|node, _| peers.contains(node));
+        for peer in peers {
+            self.progress.entry(peer).or_insert(Progress { next, matched: 0 });
+        }
+    }
+
+    fn peers(&self) -> Vec<NodeId> {
+        self.voters.iter().copied().filter(|node| *node != self.id).collect()
+    }
+
+    fn has_quorum(&self, acked: impl Fn(&NodeId) -> bool) -> bool {
+        let count = self.voters.iter().filter(|node| acked(node)).count();
+        count > self.voters.len() / 2
+    }
+
+    fn broadcast_append(&mut self) {
+        let peers: Vec<_> = self.progress.keys().copied().collect();
+        for peer in peers {
+            self.send_append(peer);
+        }
+    }
+
+    fn send_append(&mut self, peer: NodeId) {
+        let Some(progress) = self.progress.get(&peer) else {
+            return;
+        };
+        let message = if progress.next <= self.log.snapshot_index() {
+            RaftMessage::InstallSnapshot {
+                term: self.term,
+                snapshot: self.log.snapshot.clone(),
+            }
+        } else {
+            let prev_log_index = progress.next - 1;
+            RaftMessage::AppendEntries {
+                term: self.term,
+                prev_log_index,
+                prev_log_term: self.log.term_at(prev_log_index).unwrap_or(0),
+                entries: self.log.slice(progress.next, self.config.max_entries_per_append),
+                leader_commit: self.commit_index,
+            }
+        };
+        self.send(peer, message);
+    }
+
+    /// Commit the highest entry of the current term stored on a majority
+    fn maybe_commit(&mut self) {
+        if !self.is_leader() {
+            return;
+        }
+        let mut index = self.log.last_index();
+        while index > self.commit_index {
+            if self.log.term_at(index) == Some(self.term) {
+                let stored = |node: &NodeId| {
+                    if *node == self.id {
+                        self.stable_index >= index
+                    } else {
+                        self.progress.get(node).is_some_and(|p| p.matched >= index)
+                    }
+                };
+                if self.has_quorum(stored) {
+                    break;
+                }
+            }
+            index -= 1;
+        }
+        if index > self.commit_index {
+            self.commit_index = index;
+            self.apply_committed();
+            // Tell followers about the new commit index right away
+            if self.is_leader() {
+                self.broadcast_append();
+            }
+        }
+    }
+
+    fn apply_committed(&mut self) {
+        while self.last_applied < self.commit_index {
+            let index = self.last_applied + 1;
+            let Some(entry) = self.log.entry(index) else {
+                break;
+            };
+            match &entry.payload {
+                EntryPayload::Command(command) => self.metadata.apply(command),
+                EntryPayload::RemoveVoter(node) if *node == self.id && self.is_leader() => {
+                    tracing::debug!(node = %self.id, "leader removed from cluster, stepping down");
+                    self.role = RaftRole::Follower;
+                    self.leader = None;
+                    self.progress.clear();
+                }
+                _ => {}
+            }
+            self.last_applied = index;
+        }
+        self.maybe_snapshot();
+    }
+
+    fn maybe_snapshot(&mut self) {
+        if self.last_applied - self.log.snapshot_index() < self.config.snapshot_threshold {
+            return;
+        }
+        let snapshot = MetadataSnapshot {
+            last_index: self.last_applied,
+            last_term: self.log.term_at(self.last_applied).unwrap_or(0),
+            voters: self.log.voters_at(self.last_applied),
+            metadata: self.metadata.clone(),
+        };
+        self.log.compact(snapshot);
+        self.snapshot_changed = true;
+    }
+
+    fn mark_unstable(&mut self, index: LogIndex) {
+        self.unstable_from = Some(self.unstable_from.map_or(index, |from| from.min(index)));
+        self.stable_index = self.stable_index.min(index - 1);
+    }
+
+    fn reset_election_deadline(&mut self, now: u64) {
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 7;
+        self.rng_state ^= self.rng_state << 17;
+        let spread = self
+            .config
+            .election_timeout_max_ms
+            .saturating_sub(self.config.election_timeout_min_ms)
+            + 1;
+        self.election_deadline = now + self.config.election_timeout_min_ms + self.rng_state % spread;
+    }
+
+    fn send(&mut self, to: NodeId, message: RaftMessage) {
+        self.outbox.push(Envelope {
+            from: self.id,
+            to,
+            message,
+        });
+    }
+}
+
+impl fmt::Debug for RaftNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RaftNode")
+            .field("id", &self.id)
+            .field("role", &self.role)
+            .field("term", &self.term)
+            .field("leader", &self.leader)
+            .field("commit_index", &self.commit_index)
+            .field("last_log_index", &self.log.last_index())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distributed::transport::{SimulatedNetwork, Transport};
+    use std::collections::HashMap;
+
+    /// Raft nodes on a simulated network, stepped one millisecond at a time
+    ///
+    /// Each node's `Ready`s are persisted to its `stored` state and reported
+    /// with `advance` before their messages are sent, and a crashed node
+    /// restarts from that state alone.
+    struct Cluster {
+        network: SimulatedNetwork<RaftMessage>,
+        config: RaftConfig,
+        nodes: BTreeMap<NodeId, RaftNode>,
+        stored: BTreeMap<NodeId, PersistentState>,
+        crashed: BTreeMap<NodeId, PersistentState>,
+        leaders_by_term: HashMap<Term, NodeId>,
+    }
+
+    /// Persist a `Ready`, then hand its messages to the network
+    fn persist_and_send(
+        network: &SimulatedNetwork<RaftMessage>,
+        stored: &mut PersistentState,
+        node: &mut RaftNode,
+        ready: Ready,
+    ) {
+        stored.apply(&ready);
+        node.advance(&ready);
+        for envelope in ready.messages {
+            network.send(envelope.from, envelope.to, envelope.message);
+        }
+    }
+
+    impl Cluster {
+        fn new(size: usize, seed: u64, config: RaftConfig) -> Self {
+            let network = SimulatedNetwork::new(seed);
+            let ids: Vec<_> = (0..size).map(|_| NodeId::new()).collect();
+            let nodes = ids
+                .iter()
+                .map(|id| (*id, RaftNode::new(*id, ids.clone(), config, 0)))
+                .collect();
+            Self {
+                network,
+                config,
+                nodes,
+                stored: BTreeMap::new(),
+                crashed: BTreeMap::new(),
+                leaders_by_term: HashMap::new(),
+            }
+        }
+
+        fn ids(&self) -> Vec<NodeId> {
+            self.nodes.keys().chain(self.crashed.keys()).copied().collect()
+        }
+
+        fn step(&mut self) {
+            self.network.advance(1);
+            let now = self.network.now();
+            for (id, node) in self.nodes.iter_mut() {
+                let stored = self.stored.entry(*id).or_default();
+                let ready = node.tick(now);
+                persist_and_send(&self.network, stored, node, ready);
+                for envelope in self.network.drain(*id) {
+                    let ready = node.step(envelope.from, envelope.message, now);
+                    persist_and_send(&self.network, stored, node, ready);
+                }
+                if node.is_leader() {
+                    // Election safety: at most one leader per term
+                    let leader = *self.leaders_by_term.entry(node.term()).or_insert(*id);
+                    assert_eq!(leader, *id, "two leaders in term {}", node.term());
+                }
+            }
+        }
+
+        fn run(&mut self, millis: u64) {
+            for _ in 0..millis {
+                self.step();
+            }
+        }
+
+        fn run_until(&mut self, millis: u64, done: impl Fn(&Self) -> bool) -> bool {
+            for _ in 0..millis {
+                if done(self) {
+                    return true;
+                }
+                self.step();
+            }
+            done(self)
+        }
+
+        fn leader(&self) -> Option<NodeId> {
+            self.nodes
+                .values()
+                .filter(|node| node.is_leader())
+                .max_by_key(|node| node.term())
+                .map(|node| node.id())
+        }
+
+        fn wait_for_leader(&mut self) -> NodeId {
+            assert!(self.run_until(5_000, |c| c.leader().is_some()), "no leader elected");
+            self.leader().unwrap()
+        }
+
+        fn propose(&mut self, command: MetadataCommand) -> LogIndex {
+            let leader = self.wait_for_leader();
+            self.nodes.get_mut(&leader).unwrap().propose(command).unwrap()
+        }
+
+        fn all_applied(&self, index: LogIndex) -> bool {
+            self.nodes.values().all(|node| node.last_applied() >= index)
+        }
+
+        /// Lose everything the node did not persist, including messages
+        /// it has not sent yet
+        fn crash(&mut self, id: NodeId) {
+            self.nodes.remove(&id).unwrap();
+            self.crashed.insert(id, self.stored.remove(&id).unwrap_or_default());
+            self.network.crash(id);
+        }
+
+        fn restart(&mut self, id: NodeId) {
+            let state = self.crashed.remove(&id).unwrap();
+            self.network.restart(id);
+            let node = RaftNode::recover(id, state.clone(), self.config, self.network.now());
+            self.stored.insert(id, state);
+            self.nodes.insert(id, node);
+        }
+
+        fn assert_converged(&self) {
+            let fingerprints: Vec<_> = self
+                .nodes
+                .values()
+                .map(|node| serde_json::to_string(node.metadata()).unwrap())
+                .collect();
+            assert!(fingerprints.windows(2).all(|w| w[0] == w[1]), "metadata diverged");
+        }
+    }
+
+    fn register(name: &str) -> MetadataCommand {
+        MetadataCommand::RegisterNode {
+            node: NodeId::new(),
+            address: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_elects_single_leader() {
+        let mut cluster = Cluster::new(5, 1, RaftConfig::default());
+        let leader = cluster.wait_for_leader();
+        cluster.run(500);
+
+        assert_eq!(cluster.leader(), Some(leader));
+        let term = cluster.nodes[&leader].term();
+        for node in cluster.nodes.values() {
+            assert_eq!(node.term(), term);
+            assert_eq!(node.leader(), Some(leader));
+        }
+    }
+
+    #[test]
+    fn test_ready_persists_votes_and_new_entries_before_replies() {
+        let (a, b, c) = (NodeId::new(), NodeId::new(), NodeId::new());
+        let config = RaftConfig::default();
+        let vote = |term| RaftMessage::RequestVote {
+            term,
+            last_log_index: 3,
+            last_log_term: 0,
+        };
+        let reply_granted = |ready: &Ready| match ready.messages[..] {
+            [Envelope {
+                message: RaftMessage::VoteResponse { granted, .. },
+                ..
+            }] => granted,
+            _ => panic!("expected one vote response"),
+        };
+
+        let mut node = RaftNode::new(a, [a, b, c], config, 0);
+        let mut stored = PersistentState::default();
+        let ready = node.ready();
+        assert_eq!((ready.entries_from, ready.entries.len()), (Some(1), 3));
+        stored.apply(&ready);
+        assert!(node.ready().is_empty());
+
+        // The vote leaves in the same `Ready` as the reply that reports it
+        let ready = node.step(b, vote(1), 1_000);
+        assert_eq!(ready.hard_state, Some(HardState { term: 1, voted_for: Some(b) }));
+        assert!(reply_granted(&ready));
+        stored.apply(&ready);
+
+        // A restarted node remembers its vote and refuses a second candidate
+        let mut node = RaftNode::recover(a, stored.clone(), config, 2_000);
+        let ready = node.step(c, vote(1), 2_000);
+        assert!(ready.hard_state.is_none());
+        assert!(!reply_granted(&ready));
+
+        // Only the appended entry is handed out for persisting
+        let entry = LogEntry {
+            index: 4,
+            term: 1,
+            payload: EntryPayload::Noop,
+        };
+        let append = RaftMessage::AppendEntries {
+            term: 1,
+            prev_log_index: 3,
+            prev_log_term: 0,
+            entries: vec![entry],
+            leader_commit: 3,
+        };
+        let ready = node.step(b, append, 2_001);
+        assert_eq!((ready.entries_from, ready.entries.len()), (Some(4), 1));
+        stored.apply(&ready);
+        assert_eq!(stored.entries.len(), 4);
+    }
+
+    #[test]
+    fn test_replicates_metadata_commands() {
+        let mut cluster = Cluster::new(3, 2, RaftConfig::default());
+        let shard = ShardId::new();
+        let nodes = cluster.ids();
+        cluster.propose(MetadataCommand::AssignShard {
+            shard,
+            replicas: nodes.clone(),
+        });
+        cluster.propose(MetadataCommand::PutCollection {
+            name: "docs".to_string(),
+            config: CollectionConfig::new(4),
+        });
+        let last = cluster.propose(MetadataCommand::DropCollection {
+            name: "missing".to_string(),
+        });
+
+        assert!(cluster.run_until(1_000, |c| c.all_applied(last)));
+        cluster.assert_converged();
+        let metadata = cluster.nodes.values().next().unwrap().metadata();
+        assert_eq!(metadata.shards[&shard], nodes);
+        assert_eq!(metadata.collections["docs"].dimension, 4);
+
+        let follower = cluster.nodes.values_mut().find(|n| !n.is_leader()).unwrap();
+        assert!(matches!(
+            follower.propose(register("a")),
+            Err(ConsensusError::NotLeader { leader: Some(_) })
+        ));
+    }
+
+    #[test]
+    fn test_leader_counts_only_persisted_entries_towards_commit() {
+        let mut cluster = Cluster::new(3, 11, RaftConfig::default());
+        let leader = cluster.wait_for_leader();
+        let noop = cluster.nodes[&leader].last_log_index();
+        assert!(cluster.run_until(1_000, |c| c.all_applied(noop)));
+        let followers: Vec<_> = cluster.ids().into_iter().filter(|id| *id != leader).collect();
+        cluster.crash(followers[1]);
+
+        // The leader replicates the entry while its own write is in flight
+        let node = cluster.nodes.get_mut(&leader).unwrap();
+        let index = node.propose(register("unpersisted")).unwrap();
+        let ready = node.ready();
+        assert_eq!(ready.entries.last().map(|entry| entry.index), Some(index));
+        for envelope in ready.messages {
+            cluster.network.send(envelope.from, envelope.to, envelope.message);
+        }
+
+        // One follower's acknowledgement is not a majority on its own
+        cluster.run(200);
+        assert!(cluster.nodes[&followers[0]].last_log_index() >= index);
+        assert!(cluster.nodes[&leader].is_leader());
+        assert!(cluster.nodes[&leader].commit_index() < index);
+
+        // Crash before the `Ready` was persisted: the leader never had it
+        cluster.crash(leader);
+        assert!(cluster.crashed[&leader].entries.iter().all(|entry| entry.index < index));
+        cluster.restart(leader);
+        cluster.restart(followers[1]);
+        let last = cluster.propose(register("after"));
+        assert!(cluster.run_until(5_000, |c| c.all_applied(last)));
+        cluster.assert_converged();
+    }
+
+    #[test]
+    fn test_partitioned_leader_loses_uncommitted_entries() {
+        let mut cluster = Cluster::new(5, 3, RaftConfig::default());
+        let old_leader = cluster.wait_for_leader();
+        let ids = cluster.ids();
+        cluster.network.isolate(old_leader, &ids);
+
+        // Accepted by the isolated leader but never replicated
+        let lost = cluster.nodes.get_mut(&old_leader).unwrap().propose(register("lost")).unwrap();
+        assert!(cluster.run_until(5_000, |c| c.leader().is_some_and(|l| l != old_leader)));
+        let committed = cluster.propose(register("kept"));
+        cluster.run(200);
+        assert!(cluster.nodes[&old_leader].commit_index() < lost);
+
+        cluster.network.heal();
+        assert!(cluster.run_until(2_000, |c| c.all_applied(committed)));
+        assert!(!cluster.nodes[&old_leader].is_leader());
+        cluster.assert_converged();
+        let addresses: Vec<_> = cluster.nodes[&old_leader].metadata().nodes.values().cloned().collect();
+        assert_eq!(addresses, vec!["kept".to_string()]);
+    }
+
+    #[test]
+    fn test_lagging_follower_catches_up_from_snapshot() {
+        let config = RaftConfig {
+            snapshot_threshold: 8,
+            ..RaftConfig::default()
+        };
+        let mut cluster = Cluster::new(3, 4, config);
+        let leader = cluster.wait_for_leader();
+        let follower = cluster.ids().into_iter().find(|id| *id != leader).unwrap();
+        cluster.crash(follower);
+
+        let mut last = 0;
+        for i in 0..30 {
+            last = cluster.propose(register(&format!("node-{i}")));
+        }
+        assert!(cluster.run_until(1_000, |c| c.all_applied(last)));
+        assert!(cluster.nodes[&leader].snapshot_index() >= 8);
+
+        cluster.restart(follower);
+        assert!(cluster.run_until(2_000, |c| c.all_applied(last)));
+        cluster.assert_converged();
+        assert_eq!(cluster.nodes[&follower].metadata().nodes.len(), 30);
+        assert!(cluster.nodes[&follower].snapshot_index() >= 8);
+    }
+
+    #[test]
+    fn test_membership_changes() {
+        let mut cluster = Cluster::new(3, 5, RaftConfig::default());
+        let leader = cluster.wait_for_leader();
+        cluster.run(100);
+
+        // A joining node starts without voters and is added by the leader
+        let joiner = NodeId::new();
+        let node = RaftNode::new(joiner, [], cluster.config, cluster.network.now());
+        cluster.nodes.insert(joiner, node);
+        let added = cluster.nodes.get_mut(&leader).unwrap().add_voter(joiner).unwrap();
+        assert!(matches!(
+            cluster.nodes.get_mut(&leader).unwrap().add_voter(NodeId::new()),
+            Err(ConsensusError::MembershipChangePending { .. })
+        ));
+        assert!(cluster.run_until(1_000, |c| c.all_applied(added)));
+        assert_eq!(cluster.nodes[&joiner].voters().len(), 4);
+
+        // The leader removes itself and a new leader takes over
+        let removed = cluster.nodes.get_mut(&leader).unwrap().remove_voter(leader).unwrap();
+        assert!(cluster.run_until(1_000, |c| c.nodes[&leader].last_applied() >= removed));
+        assert!(!cluster.nodes[&leader].is_leader());
+        cluster.nodes.remove(&leader);
+        cluster.network.crash(leader);
+
+        let new_leader = cluster.wait_for_leader();
+        assert_ne!(new_leader, leader);
+        let last = cluster.propose(register("after"));
+        assert!(cluster.run_until(1_000, |c| c.all_applied(last)));
+        cluster.assert_converged();
+        assert!(cluster.nodes.values().all(|n| n.voters().len() == 3 && !n.voters().contains(&leader)));
+    }
+
+    #[test]
+    fn test_progress_under_drops_and_delays() {
+        let mut cluster = Cluster::new(5, 6, RaftConfig::default());
+        cluster.network.set_latency(1, 15);
+        cluster.network.set_drop_rate(0.2);
+
+        for i in 0..20 {
+            cluster.propose(register(&format!("n{i}")));
+            cluster.run(20);
+        }
+
+        // Entries accepted by a deposed leader may be lost, but once the
+        // network recovers every node converges on the same log
+        cluster.network.set_drop_rate(0.0);
+        cluster.run(1_000);
+        let last = cluster.propose(register("final"));
+        assert!(cluster.run_until(5_000, |c| c.all_applied(last)));
+        cluster.assert_converged();
+        assert!(cluster.network.stats().dropped > 0);
+    }
+}
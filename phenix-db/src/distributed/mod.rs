@@ -1,6 +1,27 @@
 // Distributed consciousness
 //
 // This module will be fully implemented in Phase 11.
-// Placeholder for now to allow compilation.
+// Implemented so far:
+// - Consciousness: Gossiped global summary with entropy-based convergence
+// - Node: SWIM membership with suspicion, refutation, lifecycle events, zones and RTT-based proximity
+// - Transport: Pluggable message transport and a simulated in-memory network
+// - Consensus: Raft log replicating cluster metadata
+// - Replication: Shard replication with per-level write acknowledgement, catch-up and anti-entropy
+// - Router: Semantic k-NN routing over per-shard centroid summaries
+// - Rebalancer: Shard split, merge and move planning with online, rate-limited migration
 
-// Placeholder - will be implemented in Phase 11
+pub mod consciousness;
+pub mod node;
+pub mod transport;
+pub mod consensus;
+pub mod replication;
+pub mod router;
+pub mod rebalancer;
+
+pub use consciousness::{Consciousness, ConsciousnessConfig, ConsciousnessMessage, EdgeSketch, NodeDigest};
+pub use node::{Member, MemberState, Membership, MembershipConfig, MembershipEvent, MembershipListener, MembershipMessage};
+pub use transport::{Envelope, NetworkStats, SimulatedNetwork, Transport};
+pub use consensus::{ClusterMetadata, MetadataCommand, RaftConfig, RaftMessage, RaftNode, RaftRole};
+pub use replication::{CausalToken, ReplicationConfig, ReplicationMessage, ReplicationStats, ShardReplica, ShardStorage, Version};
+pub use router::{QueryRouter, RoutePlan, RoutedSearch, RouterConfig, RouterStats, ShardSummary};
+pub use rebalancer::{ClusterLoad, MigrationPhase, RateLimiter, RebalanceAction, RebalanceConfig, RebalancePlan, RebalanceReport, Rebalancer, ShardLoad, ShardMigration, ShardSink, ShardSource};
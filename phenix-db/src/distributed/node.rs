@@ -1 +1,1291 @@
-// TODO: Implement node management
+//! Node membership and failure detection
+//!
+//! Membership follows SWIM (Das et al., 2002):
+//! - Each protocol period a node pings one member, visiting members in a
+//!   shuffled round-robin order; if no ack arrives within the ping timeout
+//!   it asks `indirect_checks` other members to ping the target for it
+//! - A member with no direct or indirect ack by the end of the period is
+//!   suspected, and declared dead once the suspicion outlives
+//!   `suspicion_timeout_ms` without being refuted
+//! - A node that learns it is suspected or dead refutes it by raising its
+//!   incarnation number; higher incarnations override older claims
+//! - Membership changes are gossiped by piggybacking on pings and acks, each
+//!   sent a bounded number of times proportional to log(cluster size)
+//!
+//! Nodes join through any member (a seed), leave gracefully, or are
+//! decommissioned first so their shards can be drained. State transitions are
+//! reported to registered `MembershipListener`s, which is where replica
+//! promotion hooks in.
+//!
+//! Members carry a zone label (datacenter or availability zone), and direct
+//! probe round trips double as RTT measurements. Together they order replicas
+//! by proximity for reads, flag members beyond
+//! `cross_dc_latency_threshold_ms` so writes to them can be shipped
+//! asynchronously, and spread new replica sets across zones so that losing
+//! one zone never loses a shard.
+
+use crate::core::config::DistributedConfig;
+use crate::core::error::{ConsensusError, ConsensusResult};
+use crate::core::{NodeId, ShardId};
+use crate::distributed::transport::Transport;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::sync::Arc;
+
+/// Liveness of a member as seen by this node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemberState {
+    /// Responding to probes
+    Alive,
+
+    /// Missed a probe; dead unless it refutes in time
+    Suspect,
+
+    /// Confirmed failed
+    Dead,
+
+    /// Left the cluster gracefully
+    Left,
+}
+
+impl MemberState {
+    /// Whether the member is still considered part of the cluster
+    pub fn is_active(&self) -> bool {
+        matches!(self, Self::Alive | Self::Suspect)
+    }
+}
+
+/// A cluster member
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Member {
+    /// Node identifier
+    pub id: NodeId,
+
+    /// Network address
+    pub address: String,
+
+    /// Datacenter or availability zone; empty when unlabelled
+    pub zone: String,
+
+    /// Incarnation; raised by the member itself to refute suspicion
+    pub incarnation: u64,
+
+    /// Liveness
+    pub state: MemberState,
+
+    /// Whether the member is being drained before it leaves
+    pub decommissioning: bool,
+
+    /// When this node last saw the state change (milliseconds)
+    pub since: u64,
+}
+
+/// Gossiped claim about a member
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemberUpdate {
+    /// Member the claim is about
+    pub id: NodeId,
+
+    /// Member's address
+    pub address: String,
+
+    /// Member's zone
+    pub zone: String,
+
+    /// Incarnation the claim refers to
+    pub incarnation: u64,
+
+    /// Claimed state
+    pub state: MemberState,
+
+    /// Claimed decommissioning flag
+    pub decommissioning: bool,
+}
+
+impl From<&Member> for MemberUpdate {
+    fn from(member: &Member) -> Self {
+        Self {
+            id: member.id,
+            address: member.address.clone(),
+            zone: member.zone.clone(),
+            incarnation: member.incarnation,
+            state: member.state,
+            decommissioning: member.decommissioning,
+        }
+    }
+}
+
+/// Messages of the membership protocol
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MembershipMessage {
+    /// New node asks a seed to admit it
+    Join {
+        /// The joining node
+        member: MemberUpdate,
+    },
+
+    /// Seed admits a node and sends the full member list
+    JoinAck {
+        /// Every member known to the seed
+        members: Vec<MemberUpdate>,
+    },
+
+    /// Liveness probe
+    Ping {
+        /// Probe sequence number of the sender
+        seq: u64,
+
+        /// Piggybacked gossip
+        updates: Vec<MemberUpdate>,
+    },
+
+    /// Answer to a probe
+    Ack {
+        /// Sequence number being acknowledged
+        seq: u64,
+
+        /// Piggybacked gossip
+        updates: Vec<MemberUpdate>,
+    },
+
+    /// Ask a member to probe `target` on the sender's behalf
+    PingReq {
+        /// Probe sequence number of the sender
+        seq: u64,
+
+        /// Member to probe
+        target: NodeId,
+
+        /// Piggybacked gossip
+        updates: Vec<MemberUpdate>,
+    },
+}
+
+/// Membership change reported to listeners
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MembershipEvent {
+    /// A member joined, or rejoined after being declared dead
+    Joined(NodeId),
+
+    /// A member missed a probe
+    Suspected(NodeId),
+
+    /// A suspected member refuted the suspicion
+    Recovered(NodeId),
+
+    /// A member was confirmed dead; its primaries need promoting
+    Failed(NodeId),
+
+    /// A member left gracefully
+    Left(NodeId),
+
+    /// A member started decommissioning; its shards need draining
+    Decommissioning(NodeId),
+}
+
+/// Receiver of membership events
+pub trait MembershipListener: Send + Sync {
+    /// Called on the thread driving the membership state machine
+    fn on_event(&self, event: &MembershipEvent);
+}
+
+impl<F: Fn(&MembershipEvent) + Send + Sync> MembershipListener for F {
+    fn on_event(&self, event: &MembershipEvent) {
+        self(event)
+    }
+}
+
+/// Failure detector settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MembershipConfig {
+    /// Length of a protocol period: one probe per period (default: 500ms)
+    pub protocol_period_ms: u64,
+
+    /// Time to wait for a direct ack before probing indirectly
+    /// (default: 200ms)
+    pub ping_timeout_ms: u64,
+
+    /// Members asked to probe indirectly (default: 3)
+    pub indirect_checks: usize,
+
+    /// Time a suspected member has to refute before it is declared dead
+    /// (default: 1500ms)
+    pub suspicion_timeout_ms: u64,
+
+    /// Time allowed to join through the seeds (default: 30s)
+    pub join_timeout_ms: u64,
+
+    /// Gossip is retransmitted `retransmit_multiplier · log2(n + 1)` times
+    /// (default: 3)
+    pub retransmit_multiplier: usize,
+
+    /// Updates piggybacked per message (default: 8)
+    pub max_piggyback: usize,
+
+    /// Round trip above which a member counts as distant (default: 100ms)
+    pub cross_dc_latency_threshold_ms: u64,
+}
+
+impl Default for MembershipConfig {
+    fn default() -> Self {
+        Self::from(&DistributedConfig::default())
+    }
+}
+
+impl From<&DistributedConfig> for MembershipConfig {
+    fn from(config: &DistributedConfig) -> Self {
+        Self {
+            protocol_period_ms: config.heartbeat_interval_ms,
+            ping_timeout_ms: (config.heartbeat_interval_ms * 2 / 5).max(1),
+            indirect_checks: 3,
+            suspicion_timeout_ms: config.node_failure_timeout_ms,
+            join_timeout_ms: config.cluster_join_timeout_secs * 1000,
+            retransmit_multiplier: 3,
+            max_piggyback: 8,
+            cross_dc_latency_threshold_ms: config.cross_dc_latency_threshold_ms,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum JoinState {
+    Joining { seeds: Vec<NodeId>, deadline: u64, retry_at: u64 },
+    Joined,
+    TimedOut,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Probe {
+    target: NodeId,
+    seq: u64,
+    sent_at: u64,
+    indirect_sent: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Relay {
+    requester: NodeId,
+    seq: u64,
+    expires: u64,
+}
+
+/// One node's view of cluster membership
+pub struct Membership {
+    id: NodeId,
+    config: MembershipConfig,
+    transport: Arc<dyn Transport<MembershipMessage>>,
+    members: BTreeMap<NodeId, Member>,
+    join: JoinState,
+    probe_order: Vec<NodeId>,
+    probe_index: usize,
+    next_probe_at: u64,
+    probe: Option<Probe>,
+    next_seq: u64,
+    relays: HashMap<u64, Relay>,
+    broadcasts: Vec<(MemberUpdate, usize)>,
+    listeners: Vec<Arc<dyn MembershipListener>>,
+    rtts: HashMap<NodeId, f64>,
+    rng_state: u64,
+}
+
+/// Weight of a new RTT sample in the moving average
+const RTT_SMOOTHING: f64 = 0.2;
+
+impl Membership {
+    /// Create a node that forms a cluster of its own
+    ///
+    /// Call `join` to enter an existing cluster instead.
+    pub fn new(
+        id: NodeId,
+        address: impl Into<String>,
+        config: MembershipConfig,
+        transport: Arc<dyn Transport<MembershipMessage>>,
+        now: u64,
+    ) -> Self {
+        let me = Member {
+            id,
+            address: address.into(),
+            zone: String::new(),
+            incarnation: 0,
+            state: MemberState::Alive,
+            decommissioning: false,
+            since: now,
+        };
+        let seed = id.as_uuid().as_u64_pair();
+        Self {
+            id,
+            config,
+            transport,
+            members: BTreeMap::from([(id, me)]),
+            join: JoinState::Joined,
+            probe_order: Vec::new(),
+            probe_index: 0,
+            next_probe_at: now + config.protocol_period_ms,
+            probe: None,
+            next_seq: 1,
+            relays: HashMap::new(),
+            broadcasts: Vec::new(),
+            listeners: Vec::new(),
+            rtts: HashMap::new(),
+            rng_state: (seed.0 ^ seed.1).max(1),
+        }
+    }
+
+    /// Label this node with its zone; call before joining
+    pub fn with_zone(mut self, zone: impl Into<String>) -> Self {
+        self.members.get_mut(&self.id).expect("own record").zone = zone.into();
+        self
+    }
+
+    /// Node identifier
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    /// This node's zone
+    pub fn zone(&self) -> &str {
+        &self.members[&self.id].zone
+    }
+
+    /// Register a listener for membership events
+    pub fn add_listener(&mut self, listener: Arc<dyn MembershipListener>) {
+        self.listeners.push(listener);
+    }
+
+    /// Join the cluster through any of `seeds`
+    ///
+    /// Seeds are retried every protocol period until one answers or the
+    /// join timeout expires; check progress with `join_status`.
+    ///
+    /// # Errors
+    /// * `CommunicationFailed` if no seed other than this node is given
+    pub fn join(&mut self, seeds: &[NodeId], now: u64) -> ConsensusResult<()> {
+        let seeds: Vec<NodeId> = seeds.iter().copied().filter(|s| *s != self.id).collect();
+        if seeds.is_empty() {
+            return Err(ConsensusError::CommunicationFailed {
+                reason: "no seed nodes to join through".to_string(),
+            });
+        }
+        self.join = JoinState::Joining {
+            seeds,
+            deadline: now + self.config.join_timeout_ms,
+            retry_at: now,
+        };
+        self.tick(now);
+        Ok(())
+    }
+
+    /// Whether this node has joined
+    ///
+    /// Returns `Ok(false)` while a join is in progress.
+    ///
+    /// # Errors
+    /// * `CommunicationFailed` if no seed answered within the join timeout
+    pub fn join_status(&self) -> ConsensusResult<bool> {
+        match &self.join {
+            JoinState::Joined => Ok(true),
+            JoinState::Joining { .. } => Ok(false),
+            JoinState::TimedOut => Err(ConsensusError::CommunicationFailed {
+                reason: format!("no seed answered within {}ms", self.config.join_timeout_ms),
+            }),
+        }
+    }
+
+    /// Start draining this node before it leaves
+    pub fn decommission(&mut self, now: u64) {
+        self.update_self(now, |me| me.decommissioning = true);
+    }
+
+    /// Leave the cluster gracefully
+    ///
+    /// The departure is sent to every active member directly, then this node
+    /// stops probing.
+    pub fn leave(&mut self, now: u64) {
+        self.update_self(now, |me| me.state = MemberState::Left);
+        let update = MemberUpdate::from(&self.members[&self.id]);
+        for member in self.active_peers() {
+            self.send(
+                member,
+                MembershipMessage::Ping {
+                    seq: 0,
+                    updates: vec![update.clone()],
+                },
+            );
+        }
+    }
+
+    /// Every member this node knows about, itself included
+    pub fn members(&self) -> impl Iterator<Item = &Member> {
+        self.members.values()
+    }
+
+    /// A member's record
+    pub fn member(&self, id: &NodeId) -> Option<&Member> {
+        self.members.get(id)
+    }
+
+    /// A member's state
+    pub fn state(&self, id: &NodeId) -> Option<MemberState> {
+        self.members.get(id).map(|m| m.state)
+    }
+
+    /// Members considered part of the cluster (alive or suspected)
+    pub fn active_members(&self) -> Vec<NodeId> {
+        self.members.values().filter(|m| m.state.is_active()).map(|m| m.id).collect()
+    }
+
+    /// First member of `candidates` that is alive and not decommissioning
+    ///
+    /// Every node computes the same answer once their views agree.
+    pub fn first_alive(&self, candidates: &[NodeId]) -> Option<NodeId> {
+        candidates.iter().copied().find(|id| {
+            self.members
+                .get(id)
+                .is_some_and(|m| m.state == MemberState::Alive && !m.decommissioning)
+        })
+    }
+
+    /// Smoothed round trip to a member in milliseconds, `None` until probed
+    pub fn rtt(&self, id: &NodeId) -> Option<f64> {
+        if *id == self.id {
+            return Some(0.0);
+        }
+        self.rtts.get(id).copied()
+    }
+
+    /// Whether a member is beyond the cross-datacenter latency threshold
+    ///
+    /// Members not probed yet count as distant when they are in another zone.
+    pub fn is_distant(&self, id: &NodeId) -> bool {
+        match self.rtt(id) {
+            Some(rtt) => rtt > self.config.cross_dc_latency_threshold_ms as f64,
+            None => self.members.get(id).is_some_and(|m| m.zone != self.zone()),
+        }
+    }
+
+    /// Alive, non-decommissioning `candidates`, nearest first
+    ///
+    /// This node comes first, then members of its own zone, each group
+    /// ordered by measured RTT with unprobed members last. Reads go to the
+    /// first entry and fall back along the list, e.g. on `ReplicaBehind`.
+    pub fn by_proximity(&self, candidates: &[NodeId]) -> Vec<NodeId> {
+        let mut nearby: Vec<(bool, f64, NodeId)> = candidates
+            .iter()
+            .filter_map(|id| self.members.get(id))
+            .filter(|m| m.state == MemberState::Alive && !m.decommissioning)
+            .map(|m| (m.zone != self.zone(), self.rtt(&m.id).unwrap_or(f64::INFINITY), m.id))
+            .collect();
+        nearby.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        nearby.into_iter().map(|(_, _, id)| id).collect()
+    }
+
+    /// Nearest healthy member of `candidates`
+    pub fn nearest_alive(&self, candidates: &[NodeId]) -> Option<NodeId> {
+        self.by_proximity(candidates).first().copied()
+    }
+
+    /// Choose `count` nodes for a new replica set of `shard`, primary first
+    ///
+    /// Replicas are dealt round-robin over the zones of alive,
+    /// non-decommissioning members, so each zone holds fewer than all of
+    /// them whenever the cluster spans more than one zone. Within a zone,
+    /// nodes are ranked by rendezvous hashing on the shard, which spreads
+    /// shards evenly and keeps placement stable as members come and go.
+    ///
+    /// # Errors
+    /// * `PlacementUnavailable` if there are fewer than `count` candidates,
+    ///   or they span fewer zones than the replica set must cover
+    pub fn place_replicas(&self, shard: ShardId, count: usize) -> ConsensusResult<Vec<NodeId>> {
+        let mut zones: BTreeMap<&str, Vec<(u64, NodeId)>> = BTreeMap::new();
+        for member in self.members.values() {
+            if member.state == MemberState::Alive && !member.decommissioning {
+                zones
+                    .entry(member.zone.as_str())
+                    .or_default()
+                    .push((rendezvous(&shard, member.id.as_uuid().as_bytes()), member.id));
+            }
+        }
+        let nodes: usize = zones.values().map(Vec::len).sum();
+        let active_zones = self
+            .members
+            .values()
+            .filter(|m| m.state.is_active())
+            .map(|m| m.zone.as_str())
+            .collect::<BTreeSet<_>>()
+            .len();
+        if nodes < count || zones.len() < count.min(active_zones) {
+            return Err(ConsensusError::PlacementUnavailable {
+                replicas: count,
+                nodes,
+                zones: zones.len(),
+            });
+        }
+
+        let mut zones: Vec<(u64, Vec<(u64, NodeId)>)> = zones
+            .into_iter()
+            .map(|(zone, mut nodes)| {
+                nodes.sort_unstable_by_key(|(rank, _)| std::cmp::Reverse(*rank));
+                (rendezvous(&shard, zone.as_bytes()), nodes)
+            })
+            .collect();
+        zones.sort_unstable_by_key(|(rank, _)| std::cmp::Reverse(*rank));
+        let mut placement = Vec::with_capacity(count);
+        let mut round = 0;
+        while placement.len() < count {
+            for (_, nodes) in &zones {
+                if let Some((_, node)) = nodes.get(round) {
+                    if placement.len() < count {
+                        placement.push(*node);
+                    }
+                }
+            }
+            round += 1;
+        }
+        Ok(placement)
+    }
+
+    /// Advance timers to `now` (milliseconds)
+    pub fn tick(&mut self, now: u64) {
+        if let JoinState::Joining { seeds, deadline, retry_at } = &mut self.join {
+            if now >= *deadline {
+                tracing::warn!(node = %self.id, "join timed out");
+                self.join = JoinState::TimedOut;
+            } else if now >= *retry_at {
+                *retry_at = now + self.config.protocol_period_ms;
+                let seeds = seeds.clone();
+                let member = MemberUpdate::from(&self.members[&self.id]);
+                for seed in seeds {
+                    self.send(seed, MembershipMessage::Join { member: member.clone() });
+                }
+            }
+            return;
+        }
+        if self.join != JoinState::Joined || self.members[&self.id].state == MemberState::Left {
+            return;
+        }
+
+        // Indirect probes once the direct ping timed out
+        if let Some(probe) = self.probe.as_mut() {
+            if !probe.indirect_sent && now >= probe.sent_at + self.config.ping_timeout_ms {
+                probe.indirect_sent = true;
+                let probe = *probe;
+                let helpers = self.pick_helpers(probe.target);
+                for helper in helpers {
+                    let updates = self.piggyback();
+                    self.send(
+                        helper,
+                        MembershipMessage::PingReq {
+                            seq: probe.seq,
+                            target: probe.target,
+                            updates,
+                        },
+                    );
+                }
+            }
+        }
+
+        if now >= self.next_probe_at {
+            self.next_probe_at = now + self.config.protocol_period_ms;
+            if let Some(probe) = self.probe.take() {
+                self.suspect(probe.target, now);
+            }
+            self.start_probe(now);
+        }
+
+        // Suspicions that were not refuted in time
+        let expired: Vec<NodeId> = self
+            .members
+            .values()
+            .filter(|m| m.state == MemberState::Suspect && now >= m.since + self.config.suspicion_timeout_ms)
+            .map(|m| m.id)
+            .collect();
+        for id in expired {
+            let member = self.members.get_mut(&id).expect("listed member");
+            member.state = MemberState::Dead;
+            member.since = now;
+            let update = MemberUpdate::from(&*member);
+            tracing::warn!(node = %self.id, failed = %id, "member confirmed dead");
+            self.queue_broadcast(update);
+            self.emit(MembershipEvent::Failed(id));
+        }
+
+        self.relays.retain(|_, relay| relay.expires > now);
+    }
+
+    /// Handle a message from another node
+    pub fn step(&mut self, from: NodeId, message: MembershipMessage, now: u64) {
+        match message {
+            MembershipMessage::Join { member } => {
+                self.apply(member, now);
+                let members = self.members.values().map(MemberUpdate::from).collect();
+                self.send(from, MembershipMessage::JoinAck { members });
+            }
+            MembershipMessage::JoinAck { members } => {
+                if matches!(self.join, JoinState::Joining { .. }) {
+                    self.join = JoinState::Joined;
+                    self.next_probe_at = now + self.config.protocol_period_ms;
+                    tracing::info!(node = %self.id, via = %from, "joined cluster");
+                }
+                for update in members {
+                    self.apply(update, now);
+                }
+            }
+            MembershipMessage::Ping { seq, updates } => {
+                for update in updates {
+                    self.apply(update, now);
+                }
+                if seq != 0 {
+                    let mut updates = self.piggyback();
+                    updates.extend(self.notice_for(from));
+                    self.send(from, MembershipMessage::Ack { seq, updates });
+                }
+            }
+            MembershipMessage::Ack { seq, updates } => {
+                for update in updates {
+                    self.apply(update, now);
+                }
+                if let Some(probe) = self.probe.filter(|probe| probe.seq == seq) {
+                    // Only a direct answer measures the round trip
+                    if from == probe.target {
+                        self.record_rtt(from, now.saturating_sub(probe.sent_at));
+                    }
+                    self.probe = None;
+                }
+                if let Some(relay) = self.relays.remove(&seq) {
+                    let updates = self.piggyback();
+                    self.send(relay.requester, MembershipMessage::Ack { seq: relay.seq, updates });
+                }
+            }
+            MembershipMessage::PingReq { seq, target, updates } => {
+                for update in updates {
+                    self.apply(update, now);
+                }
+                let own_seq = self.next_seq();
+                self.relays.insert(
+                    own_seq,
+                    Relay {
+                        requester: from,
+                        seq,
+                        expires: now + self.config.protocol_period_ms,
+                    },
+                );
+                let updates = self.piggyback();
+                self.send(target, MembershipMessage::Ping { seq: own_seq, updates });
+            }
+        }
+    }
+
+    fn start_probe(&mut self, now: u64) {
+        if self.probe_index >= self.probe_order.len() {
+            // New round in a fresh random order
+            self.probe_order = self.active_peers();
+            for i in (1..self.probe_order.len()).rev() {
+                let j = (self.next_random() % (i as u64 + 1)) as usize;
+                self.probe_order.swap(i, j);
+            }
+            self.probe_index = 0;
+        }
+        while self.probe_index < self.probe_order.len() {
+            let target = self.probe_order[self.probe_index];
+            self.probe_index += 1;
+            if !self.members.get(&target).is_some_and(|m| m.state.is_active()) {
+                continue;
+            }
+            let seq = self.next_seq();
+            self.probe = Some(Probe {
+                target,
+                seq,
+                sent_at: now,
+                indirect_sent: false,
+            });
+            let mut updates = self.piggyback();
+            updates.extend(self.notice_for(target));
+            self.send(target, MembershipMessage::Ping { seq, updates });
+            return;
+        }
+    }
+
+    fn suspect(&mut self, id: NodeId, now: u64) {
+        let Some(member) = self.members.get(&id) else { return };
+        if member.state != MemberState::Alive {
+            return;
+        }
+        let update = MemberUpdate {
+            state: MemberState::Suspect,
+            ..MemberUpdate::from(member)
+        };
+        self.apply(update, now);
+    }
+
+    /// Merge a claim into the local view, following SWIM precedence
+    fn apply(&mut self, update: MemberUpdate, now: u64) {
+        if update.id == self.id {
+            self.refute(update, now);
+            return;
+        }
+
+        let Some(current) = self.members.get(&update.id) else {
+            if update.state.is_active() {
+                self.insert(update.clone(), now);
+                self.queue_broadcast(update.clone());
+                self.emit(MembershipEvent::Joined(update.id));
+                if update.decommissioning {
+                    self.emit(MembershipEvent::Decommissioning(update.id));
+                }
+            } else {
+                // Remember departures so older gossip cannot revive them
+                self.insert(update, now);
+            }
+            return;
+        };
+
+        let newer = update.incarnation > current.incarnation;
+        let same = update.incarnation == current.incarnation;
+        let overrides = match (current.state, update.state) {
+            (MemberState::Left, _) => false,
+            (_, MemberState::Left) => true,
+            (MemberState::Dead, MemberState::Dead) => false,
+            (_, MemberState::Dead) => newer || same,
+            (MemberState::Dead, _) => newer,
+            (MemberState::Alive, MemberState::Suspect) => newer || same,
+            (MemberState::Suspect, MemberState::Suspect) => newer,
+            (_, MemberState::Alive) => newer || (same && update.decommissioning && !current.decommissioning),
+        };
+        if !overrides {
+            return;
+        }
+
+        let previous = current.state;
+        let was_decommissioning = current.decommissioning;
+        self.insert(update.clone(), now);
+        self.queue_broadcast(update.clone());
+
+        let event = match (previous, update.state) {
+            (MemberState::Suspect, MemberState::Alive) => Some(MembershipEvent::Recovered(update.id)),
+            (MemberState::Dead, MemberState::Alive | MemberState::Suspect) => Some(MembershipEvent::Joined(update.id)),
+            (MemberState::Alive, MemberState::Suspect) => Some(MembershipEvent::Suspected(update.id)),
+            (from, MemberState::Dead) if from != MemberState::Dead => Some(MembershipEvent::Failed(update.id)),
+            (_, MemberState::Left) => Some(MembershipEvent::Left(update.id)),
+            _ => None,
+        };
+        if let Some(event) = event {
+            self.emit(event);
+        }
+        if update.decommissioning && !was_decommissioning && update.state.is_active() {
+            self.emit(MembershipEvent::Decommissioning(update.id));
+        }
+    }
+
+    /// Answer a claim about this node that contradicts its own state
+    fn refute(&mut self, update: MemberUpdate, now: u64) {
+        let me = &self.members[&self.id];
+        let contradicts = matches!(update.state, MemberState::Suspect | MemberState::Dead) && me.state != MemberState::Left;
+        if contradicts && update.incarnation >= me.incarnation {
+            let incarnation = update.incarnation + 1;
+            tracing::info!(node = %self.id, incarnation, "refuting {:?}", update.state);
+            self.update_self(now, |me| me.incarnation = incarnation);
+        }
+    }
+
+    fn update_self(&mut self, now: u64, change: impl FnOnce(&mut Member)) {
+        let me = self.members.get_mut(&self.id).expect("own record");
+        let was_decommissioning = me.decommissioning;
+        change(me);
+        me.since = now;
+        let update = MemberUpdate::from(&*me);
+        let started = me.decommissioning && !was_decommissioning;
+        self.queue_broadcast(update);
+        if started {
+            self.emit(MembershipEvent::Decommissioning(self.id));
+        }
+    }
+
+    fn insert(&mut self, update: MemberUpdate, now: u64) {
+        self.members.insert(
+            update.id,
+            Member {
+                id: update.id,
+                address: update.address,
+                zone: update.zone,
+                incarnation: update.incarnation,
+                state: update.state,
+                decommissioning: update.decommissioning,
+                since: now,
+            },
+        );
+    }
+
+    fn queue_broadcast(&mut self, update: MemberUpdate) {
+        let n = self.members.values().filter(|m| m.state.is_active()).count();
+        let transmissions = self.config.retransmit_multiplier * ((n + 1) as f64).log2().ceil().max(1.0) as usize;
+        self.broadcasts.retain(|(queued, _)| queued.id != update.id);
+        self.broadcasts.push((update, transmissions));
+    }
+
+    /// Updates to attach to the next message, least transmitted first
+    fn piggyback(&mut self) -> Vec<MemberUpdate> {
+        self.broadcasts.sort_by_key(|(_, remaining)| std::cmp::Reverse(*remaining));
+        let mut updates = Vec::new();
+        for (update, remaining) in self.broadcasts.iter_mut().take(self.config.max_piggyback) {
+            updates.push(update.clone());
+            *remaining -= 1;
+        }
+        self.broadcasts.retain(|(_, remaining)| *remaining > 0);
+        updates
+    }
+
+    /// Claim that `peer` is suspected or dead, to send to the peer itself
+    ///
+    /// Gossip about a suspicion may run out of retransmissions before the
+    /// suspect is reachable again (after a partition or a restart); telling
+    /// it directly lets it refute.
+    fn notice_for(&self, peer: NodeId) -> Option<MemberUpdate> {
+        self.members
+            .get(&peer)
+            .filter(|m| matches!(m.state, MemberState::Suspect | MemberState::Dead))
+            .map(MemberUpdate::from)
+    }
+
+    fn record_rtt(&mut self, id: NodeId, sample: u64) {
+        let sample = sample as f64;
+        self.rtts
+            .entry(id)
+            .and_modify(|rtt| *rtt += RTT_SMOOTHING * (sample - *rtt))
+            .or_insert(sample);
+    }
+
+    fn active_peers(&self) -> Vec<NodeId> {
+        self.members
+            .values()
+            .filter(|m| m.id != self.id && m.state.is_active())
+            .map(|m| m.id)
+            .collect()
+    }
+
+    fn pick_helpers(&mut self, target: NodeId) -> Vec<NodeId> {
+        let mut candidates: Vec<NodeId> = self
+            .members
+            .values()
+            .filter(|m| m.id != self.id && m.id != target && m.state == MemberState::Alive)
+            .map(|m| m.id)
+            .collect();
+        let count = self.config.indirect_checks.min(candidates.len());
+        for i in 0..count {
+            let j = i + (self.next_random() % (candidates.len() - i) as u64) as usize;
+            candidates.swap(i, j);
+        }
+        candidates.truncate(count);
+        candidates
+    }
+
+    fn emit(&self, event: MembershipEvent) {
+        for listener in &self.listeners {
+            listener.on_event(&event);
+        }
+    }
+
+    fn next_seq(&mut self) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        seq
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 7;
+        self.rng_state ^= self.rng_state << 17;
+        self.rng_state
+    }
+
+    fn send(&self, to: NodeId, message: MembershipMessage) {
+        self.transport.send(self.id, to, message);
+    }
+}
+
+/// Rendezvous hash of `key` for `shard`; higher ranks are preferred
+fn rendezvous(shard: &ShardId, key: &[u8]) -> u64 {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(shard.as_uuid().as_bytes());
+    hasher.update(key);
+    u64::from_le_bytes(hasher.finalize().as_bytes()[..8].try_into().expect("8 bytes"))
+}
+
+impl fmt::Debug for Membership {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Membership")
+            .field("id", &self.id)
+            .field("join", &self.join)
+            .field("members", &self.members.len())
+            .field("active", &self.active_members().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::mvcc::MvccWrite;
+    use crate::core::collection::CollectionConfig;
+    use crate::core::config::ConsistencyLevel;
+    use crate::core::{CollectionId, Entity, ShardId, Vector};
+    use crate::distributed::replication::{ReplicationConfig, ReplicationMessage, ShardReplica, ShardStorage};
+    use crate::distributed::transport::SimulatedNetwork;
+    use crate::storage::collection::Collection;
+    use crate::storage::wal::Wal;
+    use parking_lot::Mutex;
+
+    type Events = Arc<Mutex<Vec<(NodeId, MembershipEvent)>>>;
+
+    /// Nodes on a simulated network, each recording the events it observes
+    struct Cluster {
+        network: Arc<SimulatedNetwork<MembershipMessage>>,
+        nodes: BTreeMap<NodeId, Membership>,
+        order: Vec<NodeId>,
+        down: Vec<NodeId>,
+        events: Events,
+        config: MembershipConfig,
+    }
+
+    impl Cluster {
+        fn config() -> MembershipConfig {
+            MembershipConfig {
+                protocol_period_ms: 50,
+                ping_timeout_ms: 20,
+                suspicion_timeout_ms: 300,
+                join_timeout_ms: 1000,
+                ..MembershipConfig::default()
+            }
+        }
+
+        /// Start `size` nodes; all but the first join through the first
+        fn start(size: usize, seed: u64) -> Self {
+            Self::start_zoned(&vec![""; size], seed, Self::config())
+        }
+
+        /// Start one node per entry of `zones`, labelled with that zone
+        fn start_zoned(zones: &[&str], seed: u64, config: MembershipConfig) -> Self {
+            let network = Arc::new(SimulatedNetwork::new(seed));
+            let mut cluster = Self {
+                network,
+                nodes: BTreeMap::new(),
+                order: Vec::new(),
+                down: Vec::new(),
+                events: Arc::new(Mutex::new(Vec::new())),
+                config,
+            };
+            for zone in zones {
+                cluster.add_node(zone);
+            }
+            cluster
+        }
+
+        fn add_node(&mut self, zone: &str) -> NodeId {
+            let id = NodeId::new();
+            let address = format!("node-{}", self.order.len());
+            let mut node = Membership::new(id, address, self.config, self.network.clone(), self.network.now()).with_zone(zone);
+            let events = self.events.clone();
+            node.add_listener(Arc::new(move |event: &MembershipEvent| events.lock().push((id, *event))));
+            if let Some(seed) = self.order.first() {
+                node.join(&[*seed], self.network.now()).unwrap();
+            }
+            self.nodes.insert(id, node);
+            self.order.push(id);
+            id
+        }
+
+        fn node(&self, index: usize) -> &Membership {
+            &self.nodes[&self.order[index]]
+        }
+
+        fn node_mut(&mut self, index: usize) -> &mut Membership {
+            self.nodes.get_mut(&self.order[index]).unwrap()
+        }
+
+        fn crash(&mut self, index: usize) {
+            self.down.push(self.order[index]);
+            self.network.crash(self.order[index]);
+        }
+
+        fn restart(&mut self, index: usize) {
+            let id = self.order[index];
+            self.down.retain(|d| *d != id);
+            self.network.restart(id);
+        }
+
+        fn run(&mut self, millis: u64) {
+            for _ in 0..millis {
+                self.network.advance(1);
+                let now = self.network.now();
+                for (id, node) in self.nodes.iter_mut() {
+                    if self.down.contains(id) {
+                        continue;
+                    }
+                    node.tick(now);
+                    for envelope in self.network.drain(*id) {
+                        node.step(envelope.from, envelope.message, now);
+                    }
+                }
+            }
+        }
+
+        /// Whether every running node sees `target` in `state`
+        fn all_see(&self, target: NodeId, state: MemberState) -> bool {
+            self.nodes
+                .iter()
+                .filter(|(id, _)| !self.down.contains(id) && **id != target)
+                .all(|(_, node)| node.state(&target) == Some(state))
+        }
+
+        fn events_about(&self, target: NodeId) -> Vec<MembershipEvent> {
+            self.events.lock().iter().filter(|(_, e)| event_node(e) == target).map(|(_, e)| *e).collect()
+        }
+    }
+
+    fn event_node(event: &MembershipEvent) -> NodeId {
+        match *event {
+            MembershipEvent::Joined(id)
+            | MembershipEvent::Suspected(id)
+            | MembershipEvent::Recovered(id)
+            | MembershipEvent::Failed(id)
+            | MembershipEvent::Left(id)
+            | MembershipEvent::Decommissioning(id) => id,
+        }
+    }
+
+    #[test]
+    fn test_nodes_join_through_seed() {
+        let mut cluster = Cluster::start(6, 1);
+        cluster.run(500);
+        for node in cluster.nodes.values() {
+            assert!(node.join_status().unwrap());
+            assert_eq!(node.active_members().len(), 6);
+        }
+        assert!(!cluster.events.lock().iter().any(|(_, e)| matches!(e, MembershipEvent::Failed(_))));
+
+        // A seed that never answers makes the join time out
+        let network = Arc::new(SimulatedNetwork::new(2));
+        let mut lonely = Membership::new(NodeId::new(), "lonely", Cluster::config(), network.clone(), 0);
+        assert!(lonely.join(&[], 0).is_err());
+        lonely.join(&[NodeId::new()], 0).unwrap();
+        lonely.tick(999);
+        assert!(!lonely.join_status().unwrap());
+        lonely.tick(1000);
+        assert!(lonely.join_status().is_err());
+    }
+
+    #[test]
+    fn test_crashed_node_is_suspected_then_declared_dead() {
+        let mut cluster = Cluster::start(5, 3);
+        cluster.run(500);
+        let victim = cluster.order[3];
+        cluster.crash(3);
+
+        cluster.run(800);
+        assert!(cluster.all_see(victim, MemberState::Dead));
+        let events = cluster.events_about(victim);
+        let suspected = events.iter().position(|e| *e == MembershipEvent::Suspected(victim)).unwrap();
+        let failed = events.iter().position(|e| *e == MembershipEvent::Failed(victim)).unwrap();
+        assert!(suspected < failed);
+        for (index, id) in cluster.order.iter().enumerate() {
+            if index != 3 {
+                assert!(cluster.all_see(*id, MemberState::Alive), "false positive on {id}");
+            }
+        }
+
+        // On restart the node refutes its death and rejoins
+        cluster.restart(3);
+        cluster.run(500);
+        assert!(cluster.all_see(victim, MemberState::Alive));
+        assert!(cluster.node(3).member(&victim).unwrap().incarnation > 0);
+        assert!(cluster.events_about(victim).contains(&MembershipEvent::Joined(victim)));
+    }
+
+    #[test]
+    fn test_indirect_probes_prevent_false_positives() {
+        let mut cluster = Cluster::start(5, 4);
+        cluster.run(500);
+        // Node 0 cannot reach node 1 directly, but everyone else can
+        let (a, b) = (cluster.order[0], cluster.order[1]);
+        cluster.network.partition(&[a], &[b]);
+        cluster.network.set_drop_rate(0.05);
+        cluster.run(2000);
+
+        let failures = cluster
+            .events
+            .lock()
+            .iter()
+            .filter(|(_, e)| matches!(e, MembershipEvent::Failed(_)))
+            .count();
+        assert_eq!(failures, 0);
+        assert!(cluster.nodes.values().all(|n| n.state(&b).is_some_and(|s| s.is_active())));
+    }
+
+    #[test]
+    fn test_short_partition_is_refuted() {
+        // Leave the refutation well inside the suspicion timeout after healing
+        let config = MembershipConfig {
+            suspicion_timeout_ms: 600,
+            ..Cluster::config()
+        };
+        let mut cluster = Cluster::start_zoned(&[""; 4], 5, config);
+        cluster.run(500);
+        let isolated = cluster.order[2];
+        cluster.network.isolate(isolated, &cluster.order);
+        cluster.run(200);
+        cluster.network.heal();
+        cluster.run(400);
+
+        let events = cluster.events_about(isolated);
+        assert!(events.contains(&MembershipEvent::Suspected(isolated)));
+        assert!(events.contains(&MembershipEvent::Recovered(isolated)));
+        assert!(!events.contains(&MembershipEvent::Failed(isolated)));
+        assert!(cluster.all_see(isolated, MemberState::Alive));
+    }
+
+    #[test]
+    fn test_decommission_then_leave() {
+        let mut cluster = Cluster::start(4, 6);
+        cluster.run(500);
+        let leaving = cluster.order[1];
+        let now = cluster.network.now();
+        cluster.node_mut(1).decommission(now);
+        cluster.run(300);
+        assert!(cluster.nodes.values().all(|n| n.member(&leaving).unwrap().decommissioning));
+        assert_eq!(cluster.node(0).first_alive(&[leaving, cluster.order[2]]), Some(cluster.order[2]));
+
+        let now = cluster.network.now();
+        cluster.node_mut(1).leave(now);
+        cluster.run(300);
+        assert!(cluster.all_see(leaving, MemberState::Left));
+        let events = cluster.events_about(leaving);
+        assert!(events.contains(&MembershipEvent::Decommissioning(leaving)));
+        assert!(events.contains(&MembershipEvent::Left(leaving)));
+        assert!(!events.contains(&MembershipEvent::Failed(leaving)));
+    }
+
+    #[test]
+    fn test_failure_callback_promotes_backup() {
+        let mut cluster = Cluster::start(3, 7);
+        cluster.run(500);
+
+        let replication: Arc<SimulatedNetwork<ReplicationMessage>> = Arc::new(SimulatedNetwork::new(8));
+        let config = ReplicationConfig {
+            consistency: ConsistencyLevel::Causal,
+            ..ReplicationConfig::default()
+        };
+        let shard = ShardId::new();
+        let replica_set = cluster.order.clone();
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut replicas: BTreeMap<NodeId, ShardReplica> = replica_set
+            .iter()
+            .map(|id| {
+                let collection = Collection::new(CollectionId::new(), "shard".to_string(), CollectionConfig::new(2), 0);
+                let wal = Wal::open(dir.path().join(format!("{id}.wal"))).unwrap();
+                let storage = ShardStorage::new(Arc::new(collection), wal);
+                (*id, ShardReplica::new(shard, *id, replica_set.clone(), config, replication.clone(), storage, 0).unwrap())
+            })
+            .collect();
+        let entity = Entity::new(Some(Vector::new(vec![1.0, 0.0])), Some(serde_json::json!({"k": 1})), None);
+        let id = entity.id;
+        replicas.get_mut(&replica_set[0]).unwrap().write(MvccWrite::Put(Box::new(entity))).unwrap();
+
+        // Each node asks to promote its replica once it sees the primary fail
+        let failures: Arc<Mutex<Vec<(NodeId, NodeId)>>> = Arc::new(Mutex::new(Vec::new()));
+        for (observer, node) in cluster.nodes.iter_mut() {
+            let (observer, failures) = (*observer, failures.clone());
+            node.add_listener(Arc::new(move |event: &MembershipEvent| {
+                if let MembershipEvent::Failed(dead) = event {
+                    failures.lock().push((observer, *dead));
+                }
+            }));
+        }
+
+        for step in 0..1000 {
+            if step == 50 {
+                cluster.crash(0);
+                replication.crash(replica_set[0]);
+            }
+            cluster.run(1);
+            replication.advance(1);
+            let now = replication.now();
+            for (observer, dead) in failures.lock().drain(..) {
+                let replica = &replicas[&observer];
+                if replica.primary() == Some(dead) {
+                    replicas.get_mut(&observer).unwrap().start_promotion(now);
+                }
+            }
+            for (node, replica) in replicas.iter_mut().filter(|(n, _)| step < 50 || **n != replica_set[0]) {
+                replica.tick(now);
+                for envelope in replication.drain(*node) {
+                    replica.step(envelope.from, envelope.message, now);
+                }
+            }
+        }
+
+        let new_primary = &replicas[&replica_set[1]];
+        assert!(new_primary.is_primary());
+        assert_eq!(replicas[&replica_set[2]].primary(), Some(replica_set[1]));
+        assert!(new_primary.read(&id, None).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_zones_measure_rtt_and_spread_placement() {
+        // Cross-zone round trips of 120ms, above the 100ms threshold
+        let config = MembershipConfig {
+            protocol_period_ms: 200,
+            ping_timeout_ms: 150,
+            suspicion_timeout_ms: 1000,
+            ..Cluster::config()
+        };
+        let zones = ["east", "east", "west", "west", "north", "north"];
+        let mut cluster = Cluster::start_zoned(&zones, 9, config);
+        for (a, zone_a) in cluster.order.iter().zip(zones) {
+            for (b, zone_b) in cluster.order.iter().zip(zones) {
+                cluster.network.set_link_latency(*a, *b, if zone_a == zone_b { 2 } else { 60 });
+            }
+        }
+        cluster.run(4000);
+
+        let order = cluster.order.clone();
+        let me = cluster.node(0);
+        assert_eq!(me.zone(), "east");
+        assert_eq!(me.member(&order[2]).unwrap().zone, "west");
+        assert!(me.rtt(&order[1]).unwrap() < 10.0 && !me.is_distant(&order[1]));
+        for far in &order[2..] {
+            let rtt = me.rtt(far).unwrap();
+            assert!(rtt > 100.0 && rtt < 130.0, "rtt {rtt}");
+            assert!(me.is_distant(far));
+        }
+        assert_eq!(&me.by_proximity(&order)[..2], &order[..2]);
+        assert!(order[2..].contains(&me.nearest_alive(&order[2..]).unwrap()));
+
+        // Every node computes the same zone-spread placement
+        let shard = ShardId::new();
+        let placement = me.place_replicas(shard, 3).unwrap();
+        let zone_of = |id: &NodeId| zones[order.iter().position(|o| o == id).unwrap()];
+        let placed: BTreeSet<&str> = placement.iter().map(zone_of).collect();
+        assert_eq!(placed.len(), 3);
+        assert_eq!(cluster.node(5).place_replicas(shard, 3).unwrap(), placement);
+        let four = me.place_replicas(shard, 4).unwrap();
+        assert!(["east", "west", "north"].iter().all(|z| four.iter().filter(|id| zone_of(id) == *z).count() <= 2));
+        assert!(matches!(
+            me.place_replicas(shard, 7),
+            Err(ConsensusError::PlacementUnavailable { nodes: 6, zones: 3, .. })
+        ));
+
+        // Losing a zone leaves the shard with replicas in the other two
+        cluster.crash(4);
+        cluster.crash(5);
+        cluster.run(3000);
+        let me = cluster.node(0);
+        let survivors = me.by_proximity(&placement);
+        assert_eq!(survivors.len(), 2);
+        assert_eq!(zone_of(&survivors[0]), "east");
+        let replaced = me.place_replicas(ShardId::new(), 3).unwrap();
+        let placed: BTreeSet<&str> = replaced.iter().map(zone_of).collect();
+        assert_eq!(placed.len(), 2);
+    }
+}
@@ -1 +1,1188 @@
-// TODO: Implement rebalancer
+//! Shard rebalancing
+//!
+//! `Rebalancer::plan` looks at per-shard load and proposes three kinds of
+//! action:
+//! - Split a shard that holds too many entities or serves too many queries,
+//!   along the boundary between its semantic clusters (from its routing
+//!   summary), so each half stays semantically coherent
+//! - Merge undersized shards with their semantically nearest undersized
+//!   neighbour
+//! - Move shards off nodes whose load exceeds the cluster mean by more than
+//!   the configured tolerance
+//!
+//! A plan is inert until executed; its `RebalanceReport` is the dry run.
+//!
+//! Every action is executed as one or more `ShardMigration`s. A migration
+//! streams the source shard online: it records the source's change cursor,
+//! copies a scan of the shard, then replays the writes made since the cursor
+//! until the remaining lag is small. The caller then pauses writes to the
+//! source, `cutover` replays the last few writes, and routing flips to the
+//! destination. Until the flip queries read the complete source, afterwards
+//! the complete destination, so no query sees missing data. All migrations
+//! of a plan draw from one `RateLimiter` that bounds the bytes moved per
+//! second.
+
+use crate::core::mvcc::MvccWrite;
+use crate::core::vector::DistanceMetric;
+use crate::core::{Entity, EntityId, NodeId, ShardId, Vector};
+use crate::distributed::router::{project, squared_distance, ShardSummary};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Lloyd iterations used to split a shard's centroids in two
+const SPLIT_ITERATIONS: usize = 8;
+
+/// Rebalancing thresholds and limits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RebalanceConfig {
+    /// Split shards holding more entities than this (default: 1,000,000)
+    pub max_shard_entities: usize,
+
+    /// Split shards serving more queries per second than this
+    /// (default: 1000)
+    pub max_shard_qps: f64,
+
+    /// Merge shards holding fewer entities than this (default: 10,000)
+    pub min_shard_entities: usize,
+
+    /// Move shards off nodes loaded more than this fraction above the
+    /// cluster mean (default: 0.2)
+    pub node_load_tolerance: f64,
+
+    /// Bytes moved per second across all migrations (default: 64 MiB)
+    pub bytes_per_sec: u64,
+
+    /// Most actions in one plan (default: 16)
+    pub max_actions: usize,
+
+    /// Entities or writes copied per migration step (default: 512)
+    pub batch_size: usize,
+
+    /// Pending writes below which a migration is ready for cutover
+    /// (default: 64)
+    pub cutover_lag: u64,
+
+    /// Metric the shards search with, used to assign entities when splitting
+    pub metric: DistanceMetric,
+}
+
+impl Default for RebalanceConfig {
+    fn default() -> Self {
+        Self {
+            max_shard_entities: 1_000_000,
+            max_shard_qps: 1000.0,
+            min_shard_entities: 10_000,
+            node_load_tolerance: 0.2,
+            bytes_per_sec: 64 * 1024 * 1024,
+            max_actions: 16,
+            batch_size: 512,
+            cutover_lag: 64,
+            metric: DistanceMetric::default(),
+        }
+    }
+}
+
+/// Observed load of one shard
+#[derive(Debug, Clone)]
+pub struct ShardLoad {
+    /// Shard identifier
+    pub shard: ShardId,
+
+    /// Node hosting the shard's primary
+    pub node: NodeId,
+
+    /// Number of entities
+    pub entities: usize,
+
+    /// Stored size in bytes
+    pub bytes: u64,
+
+    /// Query rate
+    pub queries_per_sec: f64,
+
+    /// Routing summary, needed to split or to merge by locality
+    pub summary: Option<ShardSummary>,
+}
+
+/// Load of the whole cluster
+#[derive(Debug, Clone, Default)]
+pub struct ClusterLoad {
+    /// Every shard
+    pub shards: Vec<ShardLoad>,
+
+    /// Every node that can host shards, including empty ones
+    pub nodes: Vec<NodeId>,
+
+    /// Zone of each node; moves stay within a zone so replica sets keep
+    /// their zone spread. Nodes without an entry share the unlabelled zone.
+    pub zones: BTreeMap<NodeId, String>,
+}
+
+/// One half of a split shard
+#[derive(Debug, Clone)]
+pub struct SplitPart {
+    /// New shard
+    pub shard: ShardId,
+
+    /// Centroids of the source shard assigned to this half
+    pub centroids: Vec<Vector>,
+
+    /// Estimated entities in this half
+    pub entities: usize,
+}
+
+/// A step of a rebalancing plan
+#[derive(Debug, Clone)]
+pub enum RebalanceAction {
+    /// Split a shard in two along its cluster boundary
+    Split {
+        /// Shard to split
+        shard: ShardId,
+
+        /// Node hosting the shard and both halves
+        node: NodeId,
+
+        /// The two halves
+        parts: Vec<SplitPart>,
+
+        /// Bytes streamed
+        bytes: u64,
+    },
+
+    /// Merge shards into a new one
+    Merge {
+        /// Shards to merge
+        sources: Vec<ShardId>,
+
+        /// New shard
+        into: ShardId,
+
+        /// Node hosting the new shard
+        node: NodeId,
+
+        /// Bytes streamed
+        bytes: u64,
+    },
+
+    /// Move a shard to another node
+    Move {
+        /// Shard to move
+        shard: ShardId,
+
+        /// Current node
+        from: NodeId,
+
+        /// Destination node
+        to: NodeId,
+
+        /// Bytes streamed
+        bytes: u64,
+    },
+}
+
+impl RebalanceAction {
+    /// Bytes the action streams
+    pub fn bytes(&self) -> u64 {
+        match self {
+            Self::Split { bytes, .. } | Self::Merge { bytes, .. } | Self::Move { bytes, .. } => *bytes,
+        }
+    }
+
+    /// Migrations that carry out the action
+    pub fn migrations(&self, config: &RebalanceConfig) -> Vec<ShardMigration> {
+        match self {
+            Self::Split { shard, parts, .. } => {
+                let targets = parts
+                    .iter()
+                    .map(|part| MigrationTarget {
+                        shard: part.shard,
+                        centroids: part.centroids.iter().map(|c| c.values.clone()).collect(),
+                    })
+                    .collect();
+                vec![ShardMigration::new(*shard, targets, config)]
+            }
+            Self::Merge { sources, into, .. } => sources
+                .iter()
+                .map(|source| ShardMigration::new(*source, vec![MigrationTarget::whole(*into)], config))
+                .collect(),
+            Self::Move { shard, .. } => vec![ShardMigration::new(*shard, vec![MigrationTarget::whole(*shard)], config)],
+        }
+    }
+}
+
+impl fmt::Display for RebalanceAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Split { shard, node, parts, bytes } => {
+                let sizes: Vec<String> = parts.iter().map(|p| format!("{} (~{} entities)", p.shard, p.entities)).collect();
+                write!(f, "split {shard} on {node} into {}, {bytes} bytes", sizes.join(" + "))
+            }
+            Self::Merge { sources, into, node, bytes } => {
+                let sources: Vec<String> = sources.iter().map(ShardId::to_string).collect();
+                write!(f, "merge {} into {into} on {node}, {bytes} bytes", sources.join(" + "))
+            }
+            Self::Move { shard, from, to, bytes } => write!(f, "move {shard} from {from} to {to}, {bytes} bytes"),
+        }
+    }
+}
+
+/// Dry-run summary of a plan
+#[derive(Debug, Clone, PartialEq)]
+pub struct RebalanceReport {
+    /// Number of splits
+    pub splits: usize,
+
+    /// Number of merges
+    pub merges: usize,
+
+    /// Number of moves
+    pub moves: usize,
+
+    /// Total bytes streamed
+    pub bytes_moved: u64,
+
+    /// Time to stream them at the configured rate
+    pub estimated_secs: f64,
+
+    /// Relative node load before the plan (1.0 = cluster mean)
+    pub load_before: BTreeMap<NodeId, f64>,
+
+    /// Relative node load after the plan
+    pub load_after: BTreeMap<NodeId, f64>,
+
+    /// One line per action
+    pub actions: Vec<String>,
+
+    /// Shards that needed an action the planner could not take
+    pub skipped: Vec<String>,
+}
+
+impl fmt::Display for RebalanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} splits, {} merges, {} moves: {} bytes, ~{:.1}s",
+            self.splits, self.merges, self.moves, self.bytes_moved, self.estimated_secs
+        )?;
+        for action in &self.actions {
+            writeln!(f, "  {action}")?;
+        }
+        for (node, before) in &self.load_before {
+            let after = self.load_after.get(node).copied().unwrap_or(0.0);
+            writeln!(f, "  load {node}: {before:.2} -> {after:.2}")?;
+        }
+        for skipped in &self.skipped {
+            writeln!(f, "  skipped: {skipped}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Actions proposed for a cluster and their dry-run report
+#[derive(Debug, Clone)]
+pub struct RebalancePlan {
+    /// Actions in execution order
+    pub actions: Vec<RebalanceAction>,
+
+    /// Dry-run report
+    pub report: RebalanceReport,
+}
+
+impl RebalancePlan {
+    /// Whether the cluster needs no change
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+/// Plans splits, merges and moves
+#[derive(Debug, Clone, Default)]
+pub struct Rebalancer {
+    config: RebalanceConfig,
+}
+
+impl Rebalancer {
+    /// Create a rebalancer
+    pub fn new(config: RebalanceConfig) -> Self {
+        Self { config }
+    }
+
+    /// Rebalancing settings
+    pub fn config(&self) -> &RebalanceConfig {
+        &self.config
+    }
+
+    /// Propose a plan for the given load
+    pub fn plan(&self, cluster: &ClusterLoad) -> RebalancePlan {
+        let config = &self.config;
+        let mut actions = Vec::new();
+        let mut skipped = Vec::new();
+        let mut busy = BTreeSet::new();
+
+        let total_bytes: u64 = cluster.shards.iter().map(|s| s.bytes).sum();
+        let total_qps: f64 = cluster.shards.iter().map(|s| s.queries_per_sec).sum();
+        let shard_load = |bytes: u64, qps: f64| {
+            let mut load = if total_bytes > 0 { bytes as f64 / total_bytes as f64 } else { 0.0 };
+            if total_qps > 0.0 {
+                load += qps / total_qps;
+            }
+            load
+        };
+        let mut node_loads: BTreeMap<NodeId, f64> = cluster.nodes.iter().map(|n| (*n, 0.0)).collect();
+        for shard in &cluster.shards {
+            *node_loads.entry(shard.node).or_default() += shard_load(shard.bytes, shard.queries_per_sec);
+        }
+        let load_before = relative(&node_loads);
+
+        // Splits: oversized or hot shards, largest first
+        let mut oversized: Vec<&ShardLoad> = cluster
+            .shards
+            .iter()
+            .filter(|s| s.entities > config.max_shard_entities || s.queries_per_sec > config.max_shard_qps)
+            .collect();
+        oversized.sort_by_key(|s| std::cmp::Reverse(s.entities));
+        for shard in oversized {
+            if actions.len() >= config.max_actions {
+                break;
+            }
+            match shard.summary.as_ref().and_then(|s| split_centroids(s, config.metric)) {
+                Some(parts) => {
+                    busy.insert(shard.shard);
+                    actions.push(RebalanceAction::Split {
+                        shard: shard.shard,
+                        node: shard.node,
+                        parts,
+                        bytes: shard.bytes,
+                    });
+                }
+                None => skipped.push(format!("{}: too large but has no cluster boundary to split on", shard.shard)),
+            }
+        }
+
+        // Merges: pair undersized shards, semantically closest pairs first
+        let small: Vec<&ShardLoad> = cluster
+            .shards
+            .iter()
+            .filter(|s| s.entities < config.min_shard_entities && !busy.contains(&s.shard))
+            .collect();
+        let mut pairs = Vec::new();
+        for (i, a) in small.iter().enumerate() {
+            for b in &small[i + 1..] {
+                if a.entities + b.entities <= config.max_shard_entities {
+                    pairs.push((semantic_distance(a, b), *a, *b));
+                }
+            }
+        }
+        pairs.sort_by(|x, y| x.0.total_cmp(&y.0).then((x.1.entities + x.2.entities).cmp(&(y.1.entities + y.2.entities))));
+        for (_, first, second) in pairs {
+            if actions.len() >= config.max_actions {
+                break;
+            }
+            if busy.contains(&first.shard) || busy.contains(&second.shard) {
+                continue;
+            }
+            let node = if first.bytes >= second.bytes { first.node } else { second.node };
+            busy.insert(first.shard);
+            busy.insert(second.shard);
+            for source in [first, second] {
+                let load = shard_load(source.bytes, source.queries_per_sec);
+                *node_loads.entry(source.node).or_default() -= load;
+                *node_loads.entry(node).or_default() += load;
+            }
+            actions.push(RebalanceAction::Merge {
+                sources: vec![first.shard, second.shard],
+                into: ShardId::new(),
+                node,
+                bytes: first.bytes + second.bytes,
+            });
+        }
+
+        // Moves: shift shards from the heaviest to the lightest node
+        if !node_loads.is_empty() {
+            let mean = node_loads.values().sum::<f64>() / node_loads.len() as f64;
+            let mut located: Vec<(&ShardLoad, NodeId)> = cluster
+                .shards
+                .iter()
+                .filter(|s| !busy.contains(&s.shard))
+                .map(|s| (s, s.node))
+                .collect();
+            while actions.len() < config.max_actions {
+                let (heavy, heavy_load) = extreme(&node_loads, true);
+                let zone = cluster.zones.get(&heavy);
+                let peers: BTreeMap<NodeId, f64> = node_loads
+                    .iter()
+                    .filter(|(node, _)| cluster.zones.get(node) == zone)
+                    .map(|(node, load)| (*node, *load))
+                    .collect();
+                let (light, light_load) = extreme(&peers, false);
+                if heavy_load <= mean * (1.0 + config.node_load_tolerance) {
+                    break;
+                }
+                let gap = heavy_load - light_load;
+                // The shard that brings both nodes closest to each other
+                let candidate = located
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, node))| *node == heavy)
+                    .map(|(index, (s, _))| (index, shard_load(s.bytes, s.queries_per_sec)))
+                    .filter(|(_, load)| *load > 0.0 && *load < gap)
+                    .min_by(|a, b| (gap - 2.0 * a.1).abs().total_cmp(&(gap - 2.0 * b.1).abs()));
+                let Some((index, load)) = candidate else {
+                    skipped.push(format!("{heavy}: overloaded but no shard move reduces its load"));
+                    break;
+                };
+                let shard = located[index].0;
+                located[index].1 = light;
+                *node_loads.get_mut(&heavy).expect("known node") -= load;
+                *node_loads.get_mut(&light).expect("known node") += load;
+                actions.push(RebalanceAction::Move {
+                    shard: shard.shard,
+                    from: heavy,
+                    to: light,
+                    bytes: shard.bytes,
+                });
+            }
+        }
+
+        let count = |f: fn(&RebalanceAction) -> bool| actions.iter().filter(|a| f(a)).count();
+        let bytes_moved: u64 = actions.iter().map(RebalanceAction::bytes).sum();
+        let report = RebalanceReport {
+            splits: count(|a| matches!(a, RebalanceAction::Split { .. })),
+            merges: count(|a| matches!(a, RebalanceAction::Merge { .. })),
+            moves: count(|a| matches!(a, RebalanceAction::Move { .. })),
+            bytes_moved,
+            estimated_secs: bytes_moved as f64 / config.bytes_per_sec.max(1) as f64,
+            load_before,
+            load_after: relative(&node_loads),
+            actions: actions.iter().map(ToString::to_string).collect(),
+            skipped,
+        };
+        RebalancePlan { actions, report }
+    }
+}
+
+/// Node loads scaled so the cluster mean is 1.0
+fn relative(loads: &BTreeMap<NodeId, f64>) -> BTreeMap<NodeId, f64> {
+    let mean = loads.values().sum::<f64>() / loads.len().max(1) as f64;
+    loads
+        .iter()
+        .map(|(node, load)| (*node, if mean > 0.0 { load / mean } else { 0.0 }))
+        .collect()
+}
+
+/// Most (`max`) or least loaded node; ties go to the lowest id
+fn extreme(loads: &BTreeMap<NodeId, f64>, max: bool) -> (NodeId, f64) {
+    let mut best: Option<(NodeId, f64)> = None;
+    for (node, load) in loads {
+        let better = best.is_none_or(|(_, b)| if max { *load > b } else { *load < b });
+        if better {
+            best = Some((*node, *load));
+        }
+    }
+    best.expect("at least one node")
+}
+
+/// Weighted mean of a shard's centroids
+fn summary_mean(summary: &ShardSummary) -> Option<Vec<f64>> {
+    let first = summary.centroids.first()?;
+    let dimensions = first.vector.dimensions;
+    let mut mean = vec![0.0; dimensions];
+    let mut total = 0.0;
+    for centroid in summary.centroids.iter().filter(|c| c.vector.dimensions == dimensions) {
+        total += centroid.count as f64;
+        for (m, x) in mean.iter_mut().zip(&centroid.vector.values) {
+            *m += *x as f64 * centroid.count as f64;
+        }
+    }
+    if total == 0.0 {
+        return None;
+    }
+    mean.iter_mut().for_each(|m| *m /= total);
+    Some(mean)
+}
+
+/// Distance between the centres of two shards; unknown locality sorts last
+fn semantic_distance(a: &ShardLoad, b: &ShardLoad) -> f64 {
+    let centre = |s: &ShardLoad| s.summary.as_ref().and_then(summary_mean);
+    match (centre(a), centre(b)) {
+        (Some(a), Some(b)) if a.len() == b.len() => a.iter().zip(&b).map(|(x, y)| (x - y).powi(2)).sum(),
+        _ => f64::MAX,
+    }
+}
+
+/// Partition a shard's centroids into two groups with weighted 2-means
+fn split_centroids(summary: &ShardSummary, metric: DistanceMetric) -> Option<Vec<SplitPart>> {
+    let centroids: Vec<(Vec<f32>, usize)> = summary
+        .centroids
+        .iter()
+        .map(|c| (project(&c.vector, metric), c.count))
+        .collect();
+    if centroids.len() < 2 {
+        return None;
+    }
+
+    // Seed with the two centroids farthest apart
+    let mut seeds = (0, 1);
+    let mut widest = -1.0;
+    for i in 0..centroids.len() {
+        for j in i + 1..centroids.len() {
+            let d = squared_distance(&centroids[i].0, &centroids[j].0);
+            if d > widest {
+                widest = d;
+                seeds = (i, j);
+            }
+        }
+    }
+    if widest <= 0.0 {
+        return None;
+    }
+    let mut centres = [centroids[seeds.0].0.clone(), centroids[seeds.1].0.clone()];
+    let mut groups = vec![0usize; centroids.len()];
+    for _ in 0..SPLIT_ITERATIONS {
+        for (group, (c, _)) in groups.iter_mut().zip(&centroids) {
+            *group = usize::from(squared_distance(c, &centres[1]) < squared_distance(c, &centres[0]));
+        }
+        for (side, centre) in centres.iter_mut().enumerate() {
+            let members: Vec<_> = centroids.iter().zip(&groups).filter(|(_, g)| **g == side).collect();
+            let weight: f64 = members.iter().map(|((_, n), _)| (*n).max(1) as f64).sum();
+            if weight == 0.0 {
+                continue;
+            }
+            for (d, value) in centre.iter_mut().enumerate() {
+                *value = (members.iter().map(|((c, n), _)| c[d] as f64 * (*n).max(1) as f64).sum::<f64>() / weight) as f32;
+            }
+        }
+    }
+
+    let parts: Vec<SplitPart> = (0..2)
+        .map(|side| {
+            let members: Vec<_> = summary.centroids.iter().zip(&groups).filter(|(_, g)| **g == side).collect();
+            SplitPart {
+                shard: ShardId::new(),
+                centroids: members.iter().map(|(c, _)| c.vector.clone()).collect(),
+                entities: members.iter().map(|(c, _)| c.count).sum(),
+            }
+        })
+        .collect();
+    parts.iter().all(|p| !p.centroids.is_empty()).then_some(parts)
+}
+
+/// Token bucket limiting the bytes streamed per second
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    tokens: f64,
+    last_ms: u64,
+    consumed: u64,
+}
+
+impl RateLimiter {
+    /// Create a limiter with a full one-second burst
+    pub fn new(bytes_per_sec: u64, now_ms: u64) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.max(1),
+            tokens: bytes_per_sec.max(1) as f64,
+            last_ms: now_ms,
+            consumed: 0,
+        }
+    }
+
+    /// Take `bytes` from the bucket if available
+    ///
+    /// A single item larger than the burst passes once the bucket is full,
+    /// so it cannot stall a migration forever.
+    pub fn try_acquire(&mut self, bytes: u64, now_ms: u64) -> bool {
+        let burst = self.bytes_per_sec as f64;
+        let elapsed = now_ms.saturating_sub(self.last_ms) as f64 / 1000.0;
+        self.tokens = (self.tokens + elapsed * burst).min(burst);
+        self.last_ms = self.last_ms.max(now_ms);
+
+        let bytes_f = bytes as f64;
+        if bytes_f <= self.tokens || (bytes_f > burst && self.tokens >= burst) {
+            self.tokens -= bytes_f;
+            self.consumed += bytes;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Bytes acquired so far
+    pub fn consumed(&self) -> u64 {
+        self.consumed
+    }
+}
+
+/// Read side of a shard being migrated
+pub trait ShardSource {
+    /// Up to `limit` entities of `shard` with ids above `after`, in id order
+    fn scan(&self, shard: ShardId, after: Option<EntityId>, limit: usize) -> Vec<Entity>;
+
+    /// Position of the latest write to `shard` in its change log
+    fn change_cursor(&self, shard: ShardId) -> u64;
+
+    /// Up to `limit` writes to `shard` after `cursor`, with their positions
+    fn changes_since(&self, shard: ShardId, cursor: u64, limit: usize) -> Vec<(u64, MvccWrite)>;
+}
+
+/// Write side of a migration: the destination node's storage
+pub trait ShardSink {
+    /// Apply a write to `shard`
+    fn apply(&mut self, shard: ShardId, write: MvccWrite);
+}
+
+/// Destination of a migration with the centroids it owns
+#[derive(Debug, Clone)]
+struct MigrationTarget {
+    shard: ShardId,
+    centroids: Vec<Vec<f32>>,
+}
+
+impl MigrationTarget {
+    fn whole(shard: ShardId) -> Self {
+        Self {
+            shard,
+            centroids: Vec::new(),
+        }
+    }
+}
+
+/// Progress of a migration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationPhase {
+    /// Copying a scan of the source
+    Copying,
+
+    /// Replaying writes made since the copy started
+    CatchingUp,
+
+    /// Lag is small; pause source writes and call `cutover`
+    ReadyForCutover,
+
+    /// Destination holds everything; routing may flip
+    Complete,
+}
+
+/// Online copy of one source shard to its destinations
+#[derive(Debug, Clone)]
+pub struct ShardMigration {
+    source: ShardId,
+    targets: Vec<MigrationTarget>,
+    metric: DistanceMetric,
+    batch_size: usize,
+    cutover_lag: u64,
+    phase: MigrationPhase,
+    cursor: Option<u64>,
+    scan_after: Option<EntityId>,
+    pending: Vec<MvccWrite>,
+    entities_copied: u64,
+    writes_replayed: u64,
+    bytes_moved: u64,
+}
+
+impl ShardMigration {
+    fn new(source: ShardId, targets: Vec<MigrationTarget>, config: &RebalanceConfig) -> Self {
+        Self {
+            source,
+            targets,
+            metric: config.metric,
+            batch_size: config.batch_size.max(1),
+            cutover_lag: config.cutover_lag,
+            phase: MigrationPhase::Copying,
+            cursor: None,
+            scan_after: None,
+            pending: Vec::new(),
+            entities_copied: 0,
+            writes_replayed: 0,
+            bytes_moved: 0,
+        }
+    }
+
+    /// Shard being copied
+    pub fn source(&self) -> ShardId {
+        self.source
+    }
+
+    /// Destination shards
+    pub fn destinations(&self) -> Vec<ShardId> {
+        self.targets.iter().map(|t| t.shard).collect()
+    }
+
+    /// Current phase
+    pub fn phase(&self) -> MigrationPhase {
+        self.phase
+    }
+
+    /// Entities copied by the scan
+    pub fn entities_copied(&self) -> u64 {
+        self.entities_copied
+    }
+
+    /// Writes replayed from the change log
+    pub fn writes_replayed(&self) -> u64 {
+        self.writes_replayed
+    }
+
+    /// Bytes streamed so far
+    pub fn bytes_moved(&self) -> u64 {
+        self.bytes_moved
+    }
+
+    /// Stream one batch within the rate limit
+    pub fn step(
+        &mut self,
+        now_ms: u64,
+        limiter: &mut RateLimiter,
+        source: &dyn ShardSource,
+        sink: &mut dyn ShardSink,
+    ) -> MigrationPhase {
+        // Writes from here on are replayed after the copy
+        let cursor = *self.cursor.get_or_insert_with(|| source.change_cursor(self.source));
+
+        match self.phase {
+            MigrationPhase::Copying => {
+                if self.pending.is_empty() {
+                    let batch = source.scan(self.source, self.scan_after, self.batch_size);
+                    if batch.is_empty() {
+                        self.phase = MigrationPhase::CatchingUp;
+                        return self.step(now_ms, limiter, source, sink);
+                    }
+                    self.scan_after = batch.last().map(|e| e.id);
+                    self.pending = batch.into_iter().rev().map(|e| MvccWrite::Put(Box::new(e))).collect();
+                }
+                let copied = self.flush(now_ms, Some(limiter), sink);
+                self.entities_copied += copied;
+            }
+            MigrationPhase::CatchingUp => {
+                if self.pending.is_empty() {
+                    let lag = source.change_cursor(self.source).saturating_sub(cursor);
+                    if lag <= self.cutover_lag {
+                        self.phase = MigrationPhase::ReadyForCutover;
+                        return self.phase;
+                    }
+                    self.fetch_changes(source, cursor);
+                }
+                let replayed = self.flush(now_ms, Some(limiter), sink);
+                self.writes_replayed += replayed;
+            }
+            MigrationPhase::ReadyForCutover | MigrationPhase::Complete => {}
+        }
+        self.phase
+    }
+
+    /// Replay the remaining writes while source writes are paused
+    ///
+    /// Ignores the rate limit: the lag is bounded by `cutover_lag`, and the
+    /// pause must stay short.
+    pub fn cutover(&mut self, source: &dyn ShardSource, sink: &mut dyn ShardSink) -> MigrationPhase {
+        let cursor = *self.cursor.get_or_insert_with(|| source.change_cursor(self.source));
+        if self.phase == MigrationPhase::Complete {
+            return self.phase;
+        }
+        if self.phase == MigrationPhase::Copying {
+            tracing::warn!(shard = %self.source, "cutover before copy finished");
+            return self.phase;
+        }
+        loop {
+            self.writes_replayed += self.flush(0, None, sink);
+            if source.change_cursor(self.source) <= self.cursor.unwrap_or(cursor) {
+                break;
+            }
+            self.fetch_changes(source, self.cursor.unwrap_or(cursor));
+        }
+        self.phase = MigrationPhase::Complete;
+        tracing::info!(
+            shard = %self.source,
+            copied = self.entities_copied,
+            replayed = self.writes_replayed,
+            bytes = self.bytes_moved,
+            "shard migration complete"
+        );
+        self.phase
+    }
+
+    fn fetch_changes(&mut self, source: &dyn ShardSource, cursor: u64) {
+        let changes = source.changes_since(self.source, cursor, self.batch_size);
+        if let Some((last, _)) = changes.last() {
+            self.cursor = Some(*last);
+        }
+        self.pending = changes.into_iter().rev().map(|(_, write)| write).collect();
+    }
+
+    /// Apply pending writes (stored in reverse) until the limiter refuses
+    fn flush(&mut self, now_ms: u64, mut limiter: Option<&mut RateLimiter>, sink: &mut dyn ShardSink) -> u64 {
+        let mut applied = 0;
+        while let Some(write) = self.pending.last() {
+            let bytes = serde_json::to_vec(write).map_or(0, |encoded| encoded.len() as u64);
+            if let Some(limiter) = limiter.as_deref_mut() {
+                if !limiter.try_acquire(bytes, now_ms) {
+                    break;
+                }
+            }
+            let write = self.pending.pop().expect("last exists");
+            for (shard, write) in self.route(write) {
+                sink.apply(shard, write);
+            }
+            self.bytes_moved += bytes;
+            applied += 1;
+        }
+        applied
+    }
+
+    /// Writes to apply per destination
+    ///
+    /// A put goes to the split half closest to its vector and deletes the
+    /// entity from the other halves, where an earlier version may have been
+    /// placed before it moved. Deletes go to every destination.
+    fn route(&self, write: MvccWrite) -> Vec<(ShardId, MvccWrite)> {
+        let entity = match write {
+            MvccWrite::Put(entity) if self.targets.len() > 1 => entity,
+            MvccWrite::Put(_) => return vec![(self.targets[0].shard, write)],
+            MvccWrite::Delete(id) => return self.targets.iter().map(|t| (t.shard, MvccWrite::Delete(id))).collect(),
+        };
+        let home = match &entity.vector {
+            Some(vector) => {
+                let point = project(vector, self.metric);
+                self.targets
+                    .iter()
+                    .map(|target| {
+                        let distance = target
+                            .centroids
+                            .iter()
+                            .filter(|c| c.len() == point.len())
+                            .map(|c| squared_distance(&point, c))
+                            .fold(f64::INFINITY, f64::min);
+                        (target.shard, distance)
+                    })
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|(shard, _)| shard)
+                    .expect("at least one target")
+            }
+            None => self.targets[0].shard,
+        };
+        let id = entity.id;
+        self.targets
+            .iter()
+            .map(|target| {
+                if target.shard == home {
+                    (home, MvccWrite::Put(entity.clone()))
+                } else {
+                    (target.shard, MvccWrite::Delete(id))
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distributed::router::RouterConfig;
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn uniform(&mut self) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 >> 40) as f32 / (1u64 << 24) as f32
+        }
+
+        fn around(&mut self, centre: f32) -> Vector {
+            Vector::new((0..8).map(|_| centre + self.uniform() - 0.5).collect())
+        }
+    }
+
+    /// Shards of one node with a per-shard change log
+    #[derive(Default)]
+    struct Store {
+        shards: BTreeMap<ShardId, BTreeMap<EntityId, Entity>>,
+        log: BTreeMap<ShardId, Vec<MvccWrite>>,
+    }
+
+    impl Store {
+        fn write(&mut self, shard: ShardId, write: MvccWrite) {
+            self.log.entry(shard).or_default().push(write.clone());
+            self.apply(shard, write);
+        }
+
+        fn ids(&self, shard: ShardId) -> BTreeSet<EntityId> {
+            self.shards.get(&shard).map(|s| s.keys().copied().collect()).unwrap_or_default()
+        }
+    }
+
+    impl ShardSource for Store {
+        fn scan(&self, shard: ShardId, after: Option<EntityId>, limit: usize) -> Vec<Entity> {
+            let Some(entities) = self.shards.get(&shard) else { return Vec::new() };
+            entities
+                .iter()
+                .filter(|(id, _)| after.is_none_or(|after| **id > after))
+                .take(limit)
+                .map(|(_, e)| e.clone())
+                .collect()
+        }
+
+        fn change_cursor(&self, shard: ShardId) -> u64 {
+            self.log.get(&shard).map_or(0, |log| log.len() as u64)
+        }
+
+        fn changes_since(&self, shard: ShardId, cursor: u64, limit: usize) -> Vec<(u64, MvccWrite)> {
+            let Some(log) = self.log.get(&shard) else { return Vec::new() };
+            log.iter()
+                .enumerate()
+                .skip(cursor as usize)
+                .take(limit)
+                .map(|(i, w)| (i as u64 + 1, w.clone()))
+                .collect()
+        }
+    }
+
+    impl ShardSink for Store {
+        fn apply(&mut self, shard: ShardId, write: MvccWrite) {
+            let entities = self.shards.entry(shard).or_default();
+            match write {
+                MvccWrite::Put(entity) => {
+                    entities.insert(entity.id, *entity);
+                }
+                MvccWrite::Delete(id) => {
+                    entities.remove(&id);
+                }
+            }
+        }
+    }
+
+    fn entity(vector: Vector) -> Entity {
+        Entity::new(Some(vector), None, None)
+    }
+
+    fn load(shard: ShardId, node: NodeId, vectors: &[Vector], bytes: u64) -> ShardLoad {
+        let config = RouterConfig {
+            metric: DistanceMetric::Euclidean,
+            centroids_per_shard: 4,
+            ..RouterConfig::default()
+        };
+        ShardLoad {
+            shard,
+            node,
+            entities: vectors.len(),
+            bytes,
+            queries_per_sec: 0.0,
+            summary: Some(ShardSummary::build(shard, vectors, &config)),
+        }
+    }
+
+    fn config() -> RebalanceConfig {
+        RebalanceConfig {
+            max_shard_entities: 500,
+            min_shard_entities: 50,
+            bytes_per_sec: 10_000,
+            batch_size: 16,
+            cutover_lag: 4,
+            metric: DistanceMetric::Euclidean,
+            ..RebalanceConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_plan_splits_merges_and_moves() {
+        let mut rng = Rng(3);
+        let nodes: Vec<NodeId> = (0..3).map(|_| NodeId::new()).collect();
+
+        // Two well separated clusters in one oversized shard
+        let big: Vec<Vector> = (0..800).map(|i| rng.around(if i % 2 == 0 { 0.0 } else { 10.0 })).collect();
+        // Three tiny shards; the two near 5.0 belong together
+        let near_a: Vec<Vector> = (0..20).map(|_| rng.around(5.0)).collect();
+        let near_b: Vec<Vector> = (0..30).map(|_| rng.around(5.2)).collect();
+        let far: Vec<Vector> = (0..10).map(|_| rng.around(-20.0)).collect();
+        // Node 1 holds three mid-sized shards, node 2 nothing
+        let mids: Vec<Vec<Vector>> = (0..3).map(|i| (0..200).map(|_| rng.around(i as f32 * 3.0)).collect()).collect();
+
+        let mut shards = vec![
+            load(ShardId::new(), nodes[0], &big, 8_000),
+            load(ShardId::new(), nodes[0], &near_a, 200),
+            load(ShardId::new(), nodes[0], &near_b, 300),
+            load(ShardId::new(), nodes[0], &far, 100),
+        ];
+        for mid in &mids {
+            shards.push(load(ShardId::new(), nodes[1], mid, 4_000));
+        }
+        let cluster = ClusterLoad {
+            shards: shards.clone(),
+            nodes: nodes.clone(),
+            zones: BTreeMap::new(),
+        };
+        let plan = Rebalancer::new(config()).plan(&cluster);
+        let report = &plan.report;
+        assert_eq!((report.splits, report.merges), (1, 1));
+        assert!(report.moves >= 1);
+
+        for action in &plan.actions {
+            match action {
+                RebalanceAction::Split { parts, .. } => {
+                    // Each half owns one cluster
+                    for part in parts {
+                        let firsts: Vec<f32> = part.centroids.iter().map(|c| c.values[0]).collect();
+                        let low = firsts.iter().all(|x| *x < 5.0);
+                        let high = firsts.iter().all(|x| *x > 5.0);
+                        assert!(low || high, "mixed half: {firsts:?}");
+                    }
+                    assert_eq!(parts.iter().map(|p| p.entities).sum::<usize>(), 800);
+                }
+                RebalanceAction::Merge { sources, .. } => {
+                    assert!(sources.contains(&shards[1].shard) && sources.contains(&shards[2].shard));
+                }
+                RebalanceAction::Move { .. } => {}
+            }
+        }
+        let first_move = plan.actions.iter().find_map(|a| match a {
+            RebalanceAction::Move { from, to, .. } => Some((*from, *to)),
+            _ => None,
+        });
+        assert_eq!(first_move, Some((nodes[1], nodes[2])));
+
+        let max_after = report.load_after.values().copied().fold(0.0, f64::max);
+        let max_before = report.load_before.values().copied().fold(0.0, f64::max);
+        assert!(max_after < max_before);
+        assert!((report.estimated_secs - report.bytes_moved as f64 / 10_000.0).abs() < 1e-9);
+        let text = report.to_string();
+        assert!(text.contains("split") && text.contains("merge") && text.contains("move"));
+        // The planner only reports; nothing ran
+        assert_eq!(cluster.shards.len(), 7);
+
+        // With node 2 in another zone, nothing may move there
+        let zone = |name: &str| name.to_string();
+        let zoned = ClusterLoad {
+            zones: BTreeMap::from([(nodes[0], zone("a")), (nodes[1], zone("a")), (nodes[2], zone("b"))]),
+            ..cluster.clone()
+        };
+        let plan = Rebalancer::new(config()).plan(&zoned);
+        for action in &plan.actions {
+            if let RebalanceAction::Move { from, to, .. } = action {
+                assert_eq!(zoned.zones[from], zoned.zones[to]);
+            }
+        }
+        assert!(!plan.report.load_after.contains_key(&nodes[2]) || plan.report.load_after[&nodes[2]] == 0.0);
+    }
+
+    #[test]
+    fn test_move_streams_online_without_missing_data() {
+        let mut rng = Rng(5);
+        let shard = ShardId::new();
+        let mut source = Store::default();
+        let mut destination = Store::default();
+        let mut live: BTreeSet<EntityId> = BTreeSet::new();
+        for _ in 0..300 {
+            let e = entity(rng.around(0.0));
+            live.insert(e.id);
+            source.write(shard, MvccWrite::Put(Box::new(e)));
+        }
+
+        let action = RebalanceAction::Move {
+            shard,
+            from: NodeId::new(),
+            to: NodeId::new(),
+            bytes: 0,
+        };
+        let config = RebalanceConfig {
+            bytes_per_sec: 50_000,
+            ..config()
+        };
+        let mut migration = action.migrations(&config).remove(0);
+        let mut limiter = RateLimiter::new(config.bytes_per_sec, 0);
+        let mut now = 0;
+        let mut routed_to_destination = false;
+
+        while migration.phase() != MigrationPhase::Complete {
+            now += 10;
+            // Writes keep arriving, slower than the migration streams
+            if now % 50 == 0 && migration.phase() != MigrationPhase::ReadyForCutover {
+                let e = entity(rng.around(0.0));
+                live.insert(e.id);
+                source.write(shard, MvccWrite::Put(Box::new(e)));
+                let victim = *live.iter().nth(now as usize % live.len()).unwrap();
+                live.remove(&victim);
+                source.write(shard, MvccWrite::Delete(victim));
+            }
+
+            // Queries read whichever copy routing points at
+            let visible = if routed_to_destination { destination.ids(shard) } else { source.ids(shard) };
+            assert_eq!(visible, live);
+
+            match migration.step(now, &mut limiter, &source, &mut destination) {
+                MigrationPhase::ReadyForCutover => {
+                    migration.cutover(&source, &mut destination);
+                    routed_to_destination = true;
+                }
+                phase => assert_ne!(phase, MigrationPhase::Complete),
+            }
+            // Bucket holds at most one second of burst
+            assert!(limiter.consumed() <= config.bytes_per_sec * now / 1000 + config.bytes_per_sec);
+        }
+
+        assert_eq!(destination.ids(shard), live);
+        assert!(migration.writes_replayed() > 0);
+        // Only the cutover bypasses the limiter
+        assert!(migration.bytes_moved() >= limiter.consumed());
+        // About 90KB through a 50KB/s limiter with a one-second burst
+        assert!(now > 500, "copy finished in {now}ms");
+    }
+
+    #[test]
+    fn test_split_and_merge_migrations_partition_data() {
+        let mut rng = Rng(9);
+        let shard = ShardId::new();
+        let mut source = Store::default();
+        let mut vectors = Vec::new();
+        for i in 0..200 {
+            let v = rng.around(if i % 2 == 0 { 0.0 } else { 10.0 });
+            vectors.push(v.clone());
+            source.write(shard, MvccWrite::Put(Box::new(entity(v))));
+        }
+        let cluster = ClusterLoad {
+            shards: vec![load(shard, NodeId::new(), &vectors, 1_000)],
+            nodes: Vec::new(),
+            zones: BTreeMap::new(),
+        };
+        let plan = Rebalancer::new(RebalanceConfig {
+            max_shard_entities: 100,
+            ..config()
+        })
+        .plan(&cluster);
+        let mut migration = plan.actions[0].migrations(&config()).remove(0);
+        let mut destination = Store::default();
+        let mut limiter = RateLimiter::new(u64::MAX / 2, 0);
+        while migration.step(0, &mut limiter, &source, &mut destination) == MigrationPhase::Copying {}
+
+        // An entity copied to one half moves across to the other one
+        let mut moved = source.shards[&shard].values().find(|e| e.vector.as_ref().unwrap().values[0] < 5.0).unwrap().clone();
+        moved.vector = Some(rng.around(10.0));
+        source.write(shard, MvccWrite::Put(Box::new(moved.clone())));
+        while migration.step(0, &mut limiter, &source, &mut destination) != MigrationPhase::ReadyForCutover {}
+        migration.cutover(&source, &mut destination);
+
+        let halves = migration.destinations();
+        assert!(destination.ids(halves[0]).is_disjoint(&destination.ids(halves[1])));
+        assert_eq!(destination.ids(halves[0]).len() + destination.ids(halves[1]).len(), 200);
+        for half in &halves {
+            let firsts: Vec<f32> = destination.shards[half].values().map(|e| e.vector.as_ref().unwrap().values[0]).collect();
+            assert!(firsts.len() == 99 || firsts.len() == 101);
+            assert!(firsts.iter().all(|x| *x < 5.0) || firsts.iter().all(|x| *x > 5.0));
+        }
+
+        // Merging the halves back yields the original contents
+        let merge = RebalanceAction::Merge {
+            sources: halves.clone(),
+            into: ShardId::new(),
+            node: NodeId::new(),
+            bytes: 0,
+        };
+        let mut merged = Store::default();
+        for mut migration in merge.migrations(&config()) {
+            while migration.step(0, &mut limiter, &destination, &mut merged) != MigrationPhase::ReadyForCutover {}
+            migration.cutover(&destination, &mut merged);
+        }
+        let RebalanceAction::Merge { into, .. } = merge else { unreachable!() };
+        assert_eq!(merged.ids(into), source.ids(shard));
+    }
+}
@@ -1 +1,1716 @@
-// TODO: Implement replication
+//! Shard replication
+//!
+//! Every shard has a replica set; the first member is the primary, which
+//! orders writes by assigning each a `Version` (promotion epoch, sequence
+//! number) and streams them to the backups. Replicas keep the newest version
+//! of each entity, so applying the same writes in any order converges.
+//!
+//! How writes are acknowledged depends on `ConsistencyLevel`:
+//! - Strong: quorum writes; the primary applies and acknowledges a write once
+//!   a majority of the replica set stored it, and only the primary serves
+//!   reads
+//! - Causal: primary-backup; writes are acknowledged by the primary and
+//!   replicated asynchronously. A write returns a `CausalToken`, and a
+//!   replica serves a read carrying a token only once it has applied the
+//!   token's version (read-your-writes)
+//! - Eventual: as Causal, plus periodic anti-entropy between replicas that
+//!   compares Merkle trees and repairs only the ranges that differ
+//!
+//! Backups in another datacenter (beyond `cross_dc_latency_threshold_ms`,
+//! see `Membership::is_distant`) are marked with `set_distant`. Under Causal
+//! and Eventual consistency the primary ships writes to them in batches every
+//! `distant_batch_interval_ms` instead of one message per write; under Strong
+//! they still receive every write at once, since a majority may need them.
+//!
+//! Each replica keeps its entities in a `Collection` and records the version
+//! of every entity beside it. The primary appends each write it orders to
+//! its `Wal` before streaming it, so the log holds the write stream of the
+//! current epoch. Backups that miss writes ask the primary to catch them up:
+//! a backup whose applied version is still in the log receives the writes
+//! read back from it, starting at the LSN after that version, in batches;
+//! one that fell behind a log reset (or followed an older primary) receives
+//! a full snapshot first.
+
+use crate::core::config::{ConsistencyLevel, DistributedConfig};
+use crate::core::error::{ConsensusError, ConsensusResult, MemorySubstrateError, Result};
+use crate::core::mvcc::MvccWrite;
+use crate::core::{Entity, EntityId, NodeId, ShardId};
+use crate::distributed::transport::Transport;
+use crate::storage::collection::Collection;
+use crate::storage::wal::{Lsn, Wal, WalOp};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+
+/// Position of a write in a shard's history
+///
+/// Writes of a newer epoch (primary promotion) always win over older ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Version {
+    /// Promotion epoch of the primary that ordered the write
+    pub epoch: u64,
+
+    /// Sequence number within the shard
+    pub seq: u64,
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.epoch, self.seq)
+    }
+}
+
+/// Proof of a write, used to read it back from any replica
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CausalToken {
+    /// Shard written to
+    pub shard: ShardId,
+
+    /// Version assigned to the write
+    pub version: Version,
+}
+
+/// Newest known state of one entity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicaEntry {
+    /// Entity identifier
+    pub id: EntityId,
+
+    /// Version of the write that produced this state
+    pub version: Version,
+
+    /// Entity contents, `None` for a deletion
+    pub entity: Option<Box<Entity>>,
+}
+
+/// Messages exchanged between the replicas of a shard
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplicationMessage {
+    /// Primary streams a write to a backup
+    Replicate {
+        /// Target shard
+        shard: ShardId,
+
+        /// Version assigned by the primary
+        version: Version,
+
+        /// The write
+        write: MvccWrite,
+    },
+
+    /// Primary announces its latest sequence number
+    Heartbeat {
+        /// Target shard
+        shard: ShardId,
+
+        /// Primary's epoch
+        epoch: u64,
+
+        /// Last sequence number assigned by the primary
+        last_seq: u64,
+
+        /// Newest write stored on a majority, under Strong consistency
+        committed: Version,
+    },
+
+    /// Backup reports the highest contiguous version it applied
+    Ack {
+        /// Target shard
+        shard: ShardId,
+
+        /// Highest contiguous version applied
+        applied: Version,
+    },
+
+    /// Backup asks for the writes after `applied`
+    CatchUp {
+        /// Target shard
+        shard: ShardId,
+
+        /// Highest contiguous version applied
+        applied: Version,
+    },
+
+    /// Consecutive writes from the primary's stream
+    WalBatch {
+        /// Target shard
+        shard: ShardId,
+
+        /// Writes in version order
+        writes: Vec<(Version, MvccWrite)>,
+    },
+
+    /// Full state of the primary
+    SnapshotTransfer {
+        /// Target shard
+        shard: ShardId,
+
+        /// Every version up to this one is included
+        applied: Version,
+
+        /// All entries, deletions included
+        entries: Vec<ReplicaEntry>,
+    },
+
+    /// Merkle tree nodes of the sender, compared level by level
+    MerkleProbe {
+        /// Target shard
+        shard: ShardId,
+
+        /// (tree position, hash) pairs
+        nodes: Vec<(usize, u64)>,
+    },
+
+    /// Entries of Merkle leaves found to differ
+    Repair {
+        /// Target shard
+        shard: ShardId,
+
+        /// Differing leaves
+        leaves: Vec<usize>,
+
+        /// Sender's entries in those leaves
+        entries: Vec<ReplicaEntry>,
+
+        /// Whether the receiver should answer with its own entries
+        reply: bool,
+    },
+
+    /// Backup asks to become primary of a new epoch
+    PromoteRequest {
+        /// Target shard
+        shard: ShardId,
+
+        /// Epoch the candidate would lead
+        epoch: u64,
+
+        /// Highest contiguous version the candidate applied
+        applied: Version,
+    },
+
+    /// Answer to a promotion request
+    PromoteVote {
+        /// Target shard
+        shard: ShardId,
+
+        /// Epoch of the request
+        epoch: u64,
+
+        /// Whether the voter accepts the candidate
+        granted: bool,
+
+        /// Highest contiguous version the voter applied
+        applied: Version,
+    },
+}
+
+impl ReplicationMessage {
+    /// Shard the message belongs to
+    pub fn shard(&self) -> ShardId {
+        match self {
+            Self::Replicate { shard, .. }
+            | Self::Heartbeat { shard, .. }
+            | Self::Ack { shard, .. }
+            | Self::CatchUp { shard, .. }
+            | Self::WalBatch { shard, .. }
+            | Self::SnapshotTransfer { shard, .. }
+            | Self::MerkleProbe { shard, .. }
+            | Self::Repair { shard, .. }
+            | Self::PromoteRequest { shard, .. }
+            | Self::PromoteVote { shard, .. } => *shard,
+        }
+    }
+}
+
+/// Replication settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplicationConfig {
+    /// How writes are acknowledged and reads served
+    pub consistency: ConsistencyLevel,
+
+    /// Smallest allowed replica set
+    pub min_replicas: usize,
+
+    /// Largest allowed replica set
+    pub max_replicas: usize,
+
+    /// Interval between primary heartbeats (default: 500ms)
+    pub heartbeat_interval_ms: u64,
+
+    /// Interval between anti-entropy rounds under Eventual consistency
+    /// (default: 1000ms)
+    pub anti_entropy_interval_ms: u64,
+
+    /// Writes the primary's log holds before it is reset once they are
+    /// committed; backups behind the reset catch up from a snapshot
+    /// (default: 10000)
+    pub retained_writes: usize,
+
+    /// Writes per catch-up batch (default: 256)
+    pub catch_up_batch: usize,
+
+    /// Depth of the Merkle tree; it has 2^depth leaves (default: 8)
+    pub merkle_depth: u32,
+
+    /// Interval between write batches to distant backups under Causal and
+    /// Eventual consistency (default: the cross-datacenter latency
+    /// threshold)
+    pub distant_batch_interval_ms: u64,
+}
+
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self::from(&DistributedConfig::default())
+    }
+}
+
+impl From<&DistributedConfig> for ReplicationConfig {
+    fn from(config: &DistributedConfig) -> Self {
+        Self {
+            consistency: config.consistency_level,
+            min_replicas: config.min_replicas,
+            max_replicas: config.max_replicas,
+            heartbeat_interval_ms: config.heartbeat_interval_ms,
+            anti_entropy_interval_ms: 1000,
+            retained_writes: 10_000,
+            catch_up_batch: 256,
+            merkle_depth: 8,
+            distant_batch_interval_ms: config.cross_dc_latency_threshold_ms,
+        }
+    }
+}
+
+/// Counters of a replica
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplicationStats {
+    /// Writes applied, from any source
+    pub applied_writes: u64,
+
+    /// Writes received in batches, for catch-up or as a distant backup
+    pub caught_up_writes: u64,
+
+    /// Snapshots installed
+    pub snapshots_installed: u64,
+
+    /// Entries changed by anti-entropy repair
+    pub repaired_entries: u64,
+
+    /// Entries sent in repair messages
+    pub repair_entries_sent: u64,
+
+    /// Write batches shipped to distant backups
+    pub distant_batches_sent: u64,
+}
+
+/// Merkle tree over hashed `EntityId` ranges
+///
+/// UUID v7 identifiers are clustered by creation time, so leaves cover
+/// ranges of `blake3(id)` instead of the raw identifier space. A leaf's value
+/// is the wrapping sum of its entry hashes, which makes updates O(depth)
+/// and independent of insertion order.
+#[derive(Debug, Clone)]
+struct MerkleTree {
+    depth: u32,
+    leaf_sums: Vec<u64>,
+    nodes: Vec<u64>,
+}
+
+impl MerkleTree {
+    fn new(depth: u32) -> Self {
+        let leaves = 1usize << depth;
+        let mut tree = Self {
+            depth,
+            leaf_sums: vec![0; leaves],
+            nodes: vec![0; 2 * leaves],
+        };
+        for pos in (1..leaves).rev() {
+            tree.nodes[pos] = combine(tree.nodes[2 * pos], tree.nodes[2 * pos + 1]);
+        }
+        tree
+    }
+
+    fn leaf_count(&self) -> usize {
+        self.leaf_sums.len()
+    }
+
+    fn leaf_of(&self, id: &EntityId) -> usize {
+        let hash = blake3::hash(id.as_uuid().as_bytes());
+        let prefix = u64::from_be_bytes(hash.as_bytes()[..8].try_into().expect("8 bytes"));
+        if self.depth == 0 {
+            0
+        } else {
+            (prefix >> (64 - self.depth)) as usize
+        }
+    }
+
+    fn root(&self) -> u64 {
+        self.nodes[1]
+    }
+
+    fn update(&mut self, leaf: usize, removed: Option<u64>, added: u64) {
+        let sum = &mut self.leaf_sums[leaf];
+        if let Some(removed) = removed {
+            *sum = sum.wrapping_sub(removed);
+        }
+        *sum = sum.wrapping_add(added);
+
+        let sum = *sum;
+
+        let mut pos = self.leaf_count() + leaf;
+        self.nodes[pos] = sum;
+        while pos > 1 {
+            pos /= 2;
+            self.nodes[pos] = combine(self.nodes[2 * pos], self.nodes[2 * pos + 1]);
+        }
+    }
+}
+
+fn combine(left: u64, right: u64) -> u64 {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&left.to_le_bytes());
+    hasher.update(&right.to_le_bytes());
+    u64::from_le_bytes(hasher.finalize().as_bytes()[..8].try_into().expect("8 bytes"))
+}
+
+fn entry_hash(id: &EntityId, version: Version, deleted: bool) -> u64 {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(id.as_uuid().as_bytes());
+    hasher.update(&version.epoch.to_le_bytes());
+    hasher.update(&version.seq.to_le_bytes());
+    hasher.update(&[deleted as u8]);
+    u64::from_le_bytes(hasher.finalize().as_bytes()[..8].try_into().expect("8 bytes"))
+}
+
+/// Version of the newest write applied to an entity
+#[derive(Debug, Clone, Copy)]
+struct StoredVersion {
+    version: Version,
+    deleted: bool,
+}
+
+/// First write of the current epoch held in the primary's log
+#[derive(Debug, Clone, Copy)]
+struct LogStart {
+    lsn: Lsn,
+    seq: u64,
+}
+
+/// Where a replica keeps its copy of a shard
+#[derive(Debug)]
+pub struct ShardStorage {
+    collection: Arc<Collection>,
+    wal: Wal,
+}
+
+impl ShardStorage {
+    /// Keep entities in `collection` and log the writes ordered as primary
+    /// to `wal`
+    ///
+    /// The log must belong to this replica alone. Records it already holds
+    /// are never sent to backups; only writes ordered from now on are.
+    pub fn new(collection: Arc<Collection>, wal: Wal) -> Self {
+        Self { collection, wal }
+    }
+}
+
+/// Votes gathered by a backup asking to become primary
+#[derive(Debug, Clone)]
+struct Candidacy {
+    epoch: u64,
+    votes: BTreeSet<NodeId>,
+    retry_at: u64,
+    /// Vote held before the candidacy, restored if it is abandoned
+    previous_vote: Option<(u64, NodeId)>,
+}
+
+/// One node's copy of a shard
+pub struct ShardReplica {
+    shard: ShardId,
+    id: NodeId,
+    config: ReplicationConfig,
+    transport: Arc<dyn Transport<ReplicationMessage>>,
+    replicas: Vec<NodeId>,
+    primary: Option<NodeId>,
+    epoch: u64,
+    storage: ShardStorage,
+    versions: BTreeMap<EntityId, StoredVersion>,
+    tree: MerkleTree,
+    max_seq_seen: u64,
+
+    // Primary state
+    log_start: LogStart,
+    next_seq: u64,
+    committed: Version,
+    pending: VecDeque<(Version, MvccWrite)>,
+    acked: BTreeMap<NodeId, Version>,
+    distant: BTreeSet<NodeId>,
+    distant_pending: Vec<(Version, MvccWrite)>,
+    distant_due: u64,
+
+    // Backup state
+    applied: Version,
+    synced: bool,
+    received: BTreeSet<u64>,
+    known_last_seq: u64,
+    catch_up_sent_at: Option<u64>,
+    staged: BTreeMap<Version, MvccWrite>,
+    known_committed: Version,
+
+    // Promotion state
+    voted: Option<(u64, NodeId)>,
+    candidacy: Option<Candidacy>,
+
+    heartbeat_due: u64,
+    anti_entropy_due: u64,
+    anti_entropy_cursor: usize,
+    stats: ReplicationStats,
+}
+
+impl ShardReplica {
+    /// Create an empty replica of `shard` on node `id`, kept in `storage`
+    ///
+    /// `replicas` lists the replica set with the primary first. The
+    /// collection is expected to be empty.
+    ///
+    /// # Errors
+    /// * `InvalidReplicaSet` if the replica set size is outside
+    ///   `min_replicas..=max_replicas` or does not contain `id`
+    pub fn new(
+        shard: ShardId,
+        id: NodeId,
+        replicas: Vec<NodeId>,
+        config: ReplicationConfig,
+        transport: Arc<dyn Transport<ReplicationMessage>>,
+        storage: ShardStorage,
+        now: u64,
+    ) -> ConsensusResult<Self> {
+        validate_replicas(&replicas, &config)?;
+        if !replicas.contains(&id) {
+            return Err(ConsensusError::InvalidReplicaSet {
+                replicas: replicas.len(),
+                min: config.min_replicas,
+                max: config.max_replicas,
+            });
+        }
+        let initial = Version { epoch: 1, seq: 0 };
+        Ok(Self {
+            shard,
+            id,
+            transport,
+            primary: replicas.first().copied(),
+            replicas,
+            epoch: 1,
+            log_start: LogStart {
+                lsn: storage.wal.next_lsn(),
+                seq: 1,
+            },
+            storage,
+            versions: BTreeMap::new(),
+            tree: MerkleTree::new(config.merkle_depth),
+            max_seq_seen: 0,
+            next_seq: 1,
+            committed: initial,
+            pending: VecDeque::new(),
+            acked: BTreeMap::new(),
+            distant: BTreeSet::new(),
+            distant_pending: Vec::new(),
+            distant_due: now,
+            applied: initial,
+            synced: true,
+            received: BTreeSet::new(),
+            known_last_seq: 0,
+            catch_up_sent_at: None,
+            staged: BTreeMap::new(),
+            known_committed: initial,
+            voted: None,
+            candidacy: None,
+            heartbeat_due: now,
+            anti_entropy_due: now + config.anti_entropy_interval_ms,
+            anti_entropy_cursor: 0,
+            stats: ReplicationStats::default(),
+            config,
+        })
+    }
+
+    /// Shard identifier
+    pub fn shard(&self) -> ShardId {
+        self.shard
+    }
+
+    /// Node hosting this replica
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    /// Current primary, if known
+    pub fn primary(&self) -> Option<NodeId> {
+        self.primary
+    }
+
+    /// Whether this replica is the primary
+    pub fn is_primary(&self) -> bool {
+        self.primary == Some(self.id)
+    }
+
+    /// Replica set
+    pub fn replicas(&self) -> &[NodeId] {
+        &self.replicas
+    }
+
+    /// Current epoch
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Highest version up to which every write has been received
+    ///
+    /// Under Strong consistency a backup keeps received writes out of its
+    /// store until the primary reports them committed.
+    pub fn applied(&self) -> Version {
+        self.applied
+    }
+
+    /// Collection holding the entities of this replica
+    pub fn collection(&self) -> &Arc<Collection> {
+        &self.storage.collection
+    }
+
+    /// Number of live entities
+    pub fn len(&self) -> usize {
+        self.storage.collection.len()
+    }
+
+    /// Whether the replica holds no live entity
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Root hash of the Merkle tree; equal roots mean equal contents
+    pub fn merkle_root(&self) -> u64 {
+        self.tree.root()
+    }
+
+    /// Counters
+    pub fn stats(&self) -> ReplicationStats {
+        self.stats
+    }
+
+    /// Replace the replica set, keeping the current primary
+    ///
+    /// # Errors
+    /// * `InvalidReplicaSet` if the new size is out of bounds
+    pub fn set_replicas(&mut self, replicas: Vec<NodeId>) -> ConsensusResult<()> {
+        validate_replicas(&replicas, &self.config)?;
+        self.acked.retain(|node, _| replicas.contains(node));
+        self.distant.retain(|node| replicas.contains(node));
+        self.replicas = replicas;
+        self.maybe_commit();
+        Ok(())
+    }
+
+    /// Mark the backups beyond the cross-datacenter latency threshold
+    ///
+    /// Replaces the previous marking; nodes outside the replica set are
+    /// ignored.
+    pub fn set_distant(&mut self, nodes: impl IntoIterator<Item = NodeId>) {
+        self.distant = nodes
+            .into_iter()
+            .filter(|node| *node != self.id && self.replicas.contains(node))
+            .collect();
+    }
+
+    /// Accept a write on the primary
+    ///
+    /// The write is logged before it is streamed to the backups. Under
+    /// Strong consistency it becomes visible once a majority stored it;
+    /// check with `is_committed`.
+    ///
+    /// # Errors
+    /// * `Consensus(NotLeader)` if this replica is not the primary
+    /// * `Collection(InvalidEntity)` / `Metadata` if the entity does not fit
+    ///   the collection
+    /// * `Io` if the write cannot be logged
+    pub fn write(&mut self, write: MvccWrite) -> Result<CausalToken> {
+        if !self.is_primary() {
+            return Err(ConsensusError::NotLeader {
+                leader: self.primary.map(|primary| primary.to_string()),
+            }
+            .into());
+        }
+        let op = match &write {
+            MvccWrite::Put(entity) => {
+                self.storage.collection.validate_entity(entity)?;
+                WalOp::Upsert { entity: entity.clone() }
+            }
+            MvccWrite::Delete(id) => WalOp::Delete { entity_id: *id },
+        };
+        self.storage.wal.append(self.storage.collection.id(), op)?;
+        let version = Version {
+            epoch: self.epoch,
+            seq: self.next_seq,
+        };
+        self.next_seq += 1;
+        self.max_seq_seen = version.seq;
+        if self.batches_distant() && !self.distant.is_empty() {
+            self.distant_pending.push((version, write.clone()));
+        }
+        for backup in self.backups() {
+            if self.batches_distant() && self.distant.contains(&backup) {
+                continue;
+            }
+            self.send(
+                backup,
+                ReplicationMessage::Replicate {
+                    shard: self.shard,
+                    version,
+                    write: write.clone(),
+                },
+            );
+        }
+
+        if self.config.consistency == ConsistencyLevel::Strong {
+            self.pending.push_back((version, write));
+            self.maybe_commit();
+        } else {
+            self.apply_write(version, write);
+            self.committed = version;
+            self.applied = version;
+            self.trim_log();
+        }
+        Ok(CausalToken {
+            shard: self.shard,
+            version,
+        })
+    }
+
+    /// Whether an acknowledged write is durable under the consistency level
+    pub fn is_committed(&self, token: &CausalToken) -> bool {
+        token.version <= self.committed
+    }
+
+    /// Read an entity
+    ///
+    /// # Errors
+    /// * `NotLeader` under Strong consistency when this is not the primary
+    /// * `ReplicaBehind` if `token` names a write not yet applied here
+    pub fn read(&self, id: &EntityId, token: Option<&CausalToken>) -> ConsensusResult<Option<Entity>> {
+        if self.config.consistency == ConsistencyLevel::Strong && !self.is_primary() {
+            return Err(ConsensusError::NotLeader {
+                leader: self.primary.map(|primary| primary.to_string()),
+            });
+        }
+        if let Some(token) = token {
+            if token.version > self.applied {
+                return Err(ConsensusError::ReplicaBehind {
+                    required: token.version.to_string(),
+                    applied: self.applied.to_string(),
+                });
+            }
+        }
+        Ok(self.storage.collection.get(id).map(|entity| (*entity).clone()))
+    }
+
+    /// Ask the other replicas to make this one primary of a new epoch
+    ///
+    /// Called on every live backup once the primary is confirmed dead. A
+    /// replica votes for a candidate that applied at least as much as itself,
+    /// ties going to the earlier member of the replica set, and votes once
+    /// per epoch. The candidate takes over with votes from a majority, so it
+    /// holds every write that was committed. Candidates outranked by a voter
+    /// give up; the others retry with a newer epoch until a primary emerges.
+    pub fn start_promotion(&mut self, now: u64) {
+        if self.is_primary() {
+            return;
+        }
+        let epoch = self.epoch.max(self.voted.map_or(0, |(epoch, _)| epoch)) + 1;
+        let previous_vote = self.candidacy.as_ref().map_or(self.voted, |candidacy| candidacy.previous_vote);
+        self.voted = Some((epoch, self.id));
+        self.candidacy = Some(Candidacy {
+            epoch,
+            votes: BTreeSet::from([self.id]),
+            retry_at: now + 2 * self.config.heartbeat_interval_ms,
+            previous_vote,
+        });
+        for peer in self.backups() {
+            self.send(
+                peer,
+                ReplicationMessage::PromoteRequest {
+                    shard: self.shard,
+                    epoch,
+                    applied: self.applied,
+                },
+            );
+        }
+        self.maybe_promote(now);
+    }
+
+    /// Start an anti-entropy round with `peer`
+    pub fn start_anti_entropy(&mut self, peer: NodeId) {
+        self.send(
+            peer,
+            ReplicationMessage::MerkleProbe {
+                shard: self.shard,
+                nodes: vec![(1, self.tree.root())],
+            },
+        );
+    }
+
+    /// Advance timers to `now` (milliseconds)
+    pub fn tick(&mut self, now: u64) {
+        if self.candidacy.as_ref().is_some_and(|candidacy| now >= candidacy.retry_at) {
+            self.start_promotion(now);
+        }
+        if self.is_primary() && now >= self.distant_due {
+            self.distant_due = now + self.config.distant_batch_interval_ms;
+            self.flush_distant();
+        }
+        if self.is_primary() && now >= self.heartbeat_due {
+            self.heartbeat_due = now + self.config.heartbeat_interval_ms;
+            // Distant backups only learn of writes once they are shipped
+            let shipped = self.distant_pending.first().map_or(self.next_seq - 1, |(version, _)| version.seq - 1);
+            for backup in self.backups() {
+                let last_seq = if self.distant.contains(&backup) { shipped } else { self.next_seq - 1 };
+                self.send(
+                    backup,
+                    ReplicationMessage::Heartbeat {
+                        shard: self.shard,
+                        epoch: self.epoch,
+                        last_seq,
+                        committed: self.committed,
+                    },
+                );
+            }
+        }
+        if self.config.consistency == ConsistencyLevel::EventualConsistency && now >= self.anti_entropy_due {
+            self.anti_entropy_due = now + self.config.anti_entropy_interval_ms;
+            let peers: Vec<_> = self.replicas.iter().copied().filter(|n| *n != self.id).collect();
+            if !peers.is_empty() {
+                let peer = peers[self.anti_entropy_cursor % peers.len()];
+                self.anti_entropy_cursor += 1;
+                self.start_anti_entropy(peer);
+            }
+        }
+    }
+
+    /// Handle a message from another replica
+    pub fn step(&mut self, from: NodeId, message: ReplicationMessage, now: u64) {
+        match message {
+            ReplicationMessage::Replicate { version, write, .. } => {
+                if self.accept_primary(from, version.epoch, now) {
+                    self.receive(version, write);
+                    self.note_seqs(&[version.seq], now);
+                }
+            }
+            ReplicationMessage::Heartbeat {
+                epoch,
+                last_seq,
+                committed,
+                ..
+            } => {
+                if self.accept_primary(from, epoch, now) {
+                    self.known_last_seq = self.known_last_seq.max(last_seq);
+                    self.known_committed = self.known_committed.max(committed);
+                    self.apply_committed();
+                    if self.synced && self.applied.seq < self.known_last_seq {
+                        self.request_catch_up(now);
+                    }
+                    self.ack();
+                }
+            }
+            ReplicationMessage::Ack { applied, .. } => {
+                if self.is_primary() && applied.epoch == self.epoch {
+                    let acked = self.acked.entry(from).or_default();
+                    *acked = (*acked).max(applied);
+                    self.maybe_commit();
+                }
+            }
+            ReplicationMessage::CatchUp { applied, .. } => self.handle_catch_up(from, applied),
+            ReplicationMessage::WalBatch { writes, .. } => {
+                let Some((first, _)) = writes.first() else {
+                    return;
+                };
+                if !self.accept_primary(from, first.epoch, now) {
+                    return;
+                }
+                self.catch_up_sent_at = None;
+                let mut seqs = Vec::with_capacity(writes.len());
+                for (version, write) in writes {
+                    self.stats.caught_up_writes += 1;
+                    self.receive(version, write);
+                    seqs.push(version.seq);
+                }
+                self.note_seqs(&seqs, now);
+            }
+            ReplicationMessage::SnapshotTransfer { applied, entries, .. } => {
+                if !self.accept_primary(from, applied.epoch, now) {
+                    return;
+                }
+                for entry in entries {
+                    self.apply_entry(entry.id, entry.version, entry.entity.map(|e| *e));
+                }
+                self.stats.snapshots_installed += 1;
+                self.applied = self.applied.max(applied);
+                self.known_committed = self.known_committed.max(applied);
+                self.synced = true;
+                self.catch_up_sent_at = None;
+                self.received.retain(|seq| *seq > applied.seq);
+                self.note_seqs(&[applied.seq], now);
+            }
+            ReplicationMessage::MerkleProbe { nodes, .. } => self.handle_probe(from, nodes),
+            ReplicationMessage::Repair {
+                leaves, entries, reply, ..
+            } => self.handle_repair(from, leaves, entries, reply),
+            ReplicationMessage::PromoteRequest { epoch, applied, .. } => {
+                self.handle_promote_request(from, epoch, applied)
+            }
+            ReplicationMessage::PromoteVote {
+                epoch, granted, applied, ..
+            } => self.handle_vote(from, epoch, granted, applied, now),
+        }
+    }
+
+    /// Whether `a` makes a better primary than `b`
+    fn outranks(&self, a: (Version, NodeId), b: (Version, NodeId)) -> bool {
+        let rank = |node: NodeId| self.replicas.iter().position(|n| *n == node).unwrap_or(usize::MAX);
+        a.0 > b.0 || (a.0 == b.0 && rank(a.1) < rank(b.1))
+    }
+
+    fn handle_promote_request(&mut self, candidate: NodeId, epoch: u64, applied: Version) {
+        let granted = !self.is_primary()
+            && epoch > self.epoch
+            && !self.outranks((self.applied, self.id), (applied, candidate))
+            && match self.voted {
+                Some((voted, node)) if voted == epoch => node == candidate || node == self.id,
+                Some((voted, _)) => voted < epoch,
+                None => true,
+            };
+        if granted {
+            self.voted = Some((epoch, candidate));
+            if self.candidacy.as_ref().is_some_and(|candidacy| candidacy.epoch <= epoch) {
+                self.candidacy = None;
+            }
+        }
+        self.send(
+            candidate,
+            ReplicationMessage::PromoteVote {
+                shard: self.shard,
+                epoch,
+                granted,
+                applied: self.applied,
+            },
+        );
+    }
+
+    fn handle_vote(&mut self, voter: NodeId, epoch: u64, granted: bool, applied: Version, now: u64) {
+        let Some(candidacy) = self.candidacy.as_mut().filter(|candidacy| candidacy.epoch == epoch) else {
+            return;
+        };
+        let previous_vote = candidacy.previous_vote;
+        if granted {
+            candidacy.votes.insert(voter);
+            self.maybe_promote(now);
+        } else if self.outranks((applied, voter), (self.applied, self.id)) {
+            // Stop fencing off the candidate this replica voted for before;
+            // it may already have won
+            self.voted = previous_vote;
+            self.candidacy = None;
+        }
+    }
+
+    fn maybe_promote(&mut self, now: u64) {
+        let Some(candidacy) = &self.candidacy else {
+            return;
+        };
+        if candidacy.votes.len() < self.replicas.len() / 2 + 1 {
+            return;
+        }
+        let epoch = candidacy.epoch;
+        self.candidacy = None;
+        self.promote(epoch, now);
+    }
+
+    /// Take over as primary of `epoch`
+    ///
+    /// Writes of the new epoch supersede any conflicting write of the old
+    /// one. Staged writes this replica holds contiguously become committed.
+    fn promote(&mut self, epoch: u64, now: u64) {
+        self.epoch = epoch;
+        self.primary = Some(self.id);
+        for (version, write) in std::mem::take(&mut self.staged) {
+            if version <= self.applied {
+                self.apply_write(version, write);
+            }
+        }
+        self.next_seq = self.max_seq_seen + 1;
+        self.committed = Version {
+            epoch: self.epoch,
+            seq: self.max_seq_seen,
+        };
+        self.applied = self.committed;
+        self.known_committed = self.committed;
+        self.synced = true;
+        self.log_start = LogStart {
+            lsn: self.storage.wal.next_lsn(),
+            seq: self.next_seq,
+        };
+        self.pending.clear();
+        self.acked.clear();
+        self.distant_pending.clear();
+        self.received.clear();
+        self.heartbeat_due = now;
+        tracing::info!(shard = %self.shard, node = %self.id, epoch = self.epoch, "promoted to primary");
+    }
+
+    /// Store a write from the primary's stream
+    ///
+    /// Under Strong consistency the write is staged until it is committed, so
+    /// the store never shows a write a failover could lose.
+    fn receive(&mut self, version: Version, write: MvccWrite) {
+        if self.config.consistency == ConsistencyLevel::Strong {
+            if version > self.applied {
+                self.staged.insert(version, write);
+            }
+        } else {
+            self.apply_write(version, write);
+        }
+    }
+
+    /// Apply staged writes that are both received contiguously and committed
+    fn apply_committed(&mut self) {
+        let upto = self.known_committed.min(self.applied);
+        while let Some(entry) = self.staged.first_entry() {
+            if *entry.key() > upto {
+                break;
+            }
+            let (version, write) = entry.remove_entry();
+            self.apply_write(version, write);
+        }
+    }
+
+    fn backups(&self) -> Vec<NodeId> {
+        self.replicas.iter().copied().filter(|node| *node != self.id).collect()
+    }
+
+    /// Whether writes to distant backups are batched rather than streamed
+    fn batches_distant(&self) -> bool {
+        self.config.consistency != ConsistencyLevel::Strong
+    }
+
+    /// Ship the writes accumulated for distant backups
+    fn flush_distant(&mut self) {
+        if self.distant_pending.is_empty() {
+            return;
+        }
+        let pending = std::mem::take(&mut self.distant_pending);
+        for batch in pending.chunks(self.config.catch_up_batch.max(1)) {
+            for backup in &self.distant {
+                self.send(
+                    *backup,
+                    ReplicationMessage::WalBatch {
+                        shard: self.shard,
+                        writes: batch.to_vec(),
+                    },
+                );
+                self.stats.distant_batches_sent += 1;
+            }
+        }
+    }
+
+    /// Follow the primary of the newest epoch and ignore older ones
+    fn accept_primary(&mut self, from: NodeId, epoch: u64, now: u64) -> bool {
+        // Having voted in an election fences off the primaries before it
+        let voted = self.voted.map_or(0, |(voted, _)| voted);
+        if epoch < self.epoch || epoch < voted || (epoch == self.epoch && self.is_primary()) {
+            return false;
+        }
+        if self.candidacy.as_ref().is_some_and(|candidacy| candidacy.epoch <= epoch) {
+            self.candidacy = None;
+        }
+        if epoch > self.epoch || self.primary != Some(from) {
+            self.epoch = epoch;
+            self.primary = Some(from);
+            self.pending.clear();
+            self.acked.clear();
+            self.distant_pending.clear();
+            self.staged.clear();
+            // Sequence numbers of the new epoch may overlap what this replica
+            // applied before, so it resynchronizes from a snapshot
+            self.synced = false;
+            self.received.clear();
+            self.catch_up_sent_at = None;
+            self.request_catch_up(now);
+        }
+        true
+    }
+
+    /// Track contiguity of the primary's stream after applying `seqs`
+    fn note_seqs(&mut self, seqs: &[u64], now: u64) {
+        for &seq in seqs {
+            self.known_last_seq = self.known_last_seq.max(seq);
+            if seq > self.applied.seq || self.applied.epoch != self.epoch {
+                self.received.insert(seq);
+            }
+        }
+        if !self.synced {
+            return;
+        }
+        while self.received.remove(&(self.applied.seq + 1)) {
+            self.applied.seq += 1;
+        }
+        self.received.retain(|s| *s > self.applied.seq);
+        self.apply_committed();
+        if self.applied.seq < self.known_last_seq {
+            self.request_catch_up(now);
+        }
+        self.ack();
+    }
+
+    fn ack(&self) {
+        if let (true, Some(primary)) = (self.synced, self.primary) {
+            self.send(
+                primary,
+                ReplicationMessage::Ack {
+                    shard: self.shard,
+                    applied: self.applied,
+                },
+            );
+        }
+    }
+
+    fn request_catch_up(&mut self, now: u64) {
+        let Some(primary) = self.primary else {
+            return;
+        };
+        if self
+            .catch_up_sent_at
+            .is_some_and(|sent| now < sent + 2 * self.config.heartbeat_interval_ms)
+        {
+            return;
+        }
+        self.catch_up_sent_at = Some(now);
+        self.send(
+            primary,
+            ReplicationMessage::CatchUp {
+                shard: self.shard,
+                applied: self.applied,
+            },
+        );
+    }
+
+    fn handle_catch_up(&mut self, backup: NodeId, applied: Version) {
+        if !self.is_primary() {
+            return;
+        }
+        let logged = self.logged_after(applied).unwrap_or_else(|error| {
+            tracing::warn!(shard = %self.shard, %error, "cannot read the log for catch-up, sending a snapshot");
+            None
+        });
+        let message = match logged {
+            Some(writes) if writes.is_empty() => return,
+            Some(writes) => ReplicationMessage::WalBatch {
+                shard: self.shard,
+                writes,
+            },
+            None => ReplicationMessage::SnapshotTransfer {
+                shard: self.shard,
+                applied: self.committed,
+                entries: self.versions.iter().map(|(id, stored)| self.entry(id, stored)).collect(),
+            },
+        };
+        self.send(backup, message);
+    }
+
+    /// Next batch of logged writes after `applied`, or `None` if the log no
+    /// longer holds them
+    fn logged_after(&self, applied: Version) -> Result<Option<Vec<(Version, MvccWrite)>>> {
+        let start = self.log_start;
+        if applied.epoch != self.epoch || applied.seq + 1 < start.seq {
+            return Ok(None);
+        }
+        let from = start.lsn + (applied.seq + 1 - start.seq);
+        self.storage
+            .wal
+            .read_all()?
+            .into_iter()
+            .skip_while(|record| record.lsn < from)
+            .take(self.config.catch_up_batch)
+            .map(|record| {
+                let version = Version {
+                    epoch: self.epoch,
+                    seq: start.seq + (record.lsn - start.lsn),
+                };
+                let write = match record.op {
+                    WalOp::Upsert { entity } => MvccWrite::Put(entity),
+                    WalOp::Delete { entity_id } => MvccWrite::Delete(entity_id),
+                    op => {
+                        return Err(MemorySubstrateError::Internal(format!(
+                            "log of shard {} holds {op:?} at LSN {}",
+                            self.shard, record.lsn
+                        )))
+                    }
+                };
+                Ok((version, write))
+            })
+            .collect::<Result<_>>()
+            .map(Some)
+    }
+
+    /// Commit Strong writes stored on a majority of the replica set
+    fn maybe_commit(&mut self) {
+        if !self.is_primary() || self.config.consistency != ConsistencyLevel::Strong {
+            return;
+        }
+        let mut stored: Vec<u64> = self
+            .replicas
+            .iter()
+            .map(|node| {
+                if *node == self.id {
+                    self.next_seq - 1
+                } else {
+                    self.acked.get(node).map_or(0, |v| v.seq)
+                }
+            })
+            .collect();
+        stored.sort_unstable_by(|a, b| b.cmp(a));
+        let quorum = self.replicas.len() / 2 + 1;
+        let commit = stored.get(quorum - 1).copied().unwrap_or(0);
+
+        while let Some((version, _)) = self.pending.front() {
+            if version.seq > commit {
+                break;
+            }
+            let (version, write) = self.pending.pop_front().expect("front exists");
+            self.apply_write(version, write);
+            self.committed = version;
+            self.applied = version;
+        }
+        self.trim_log();
+    }
+
+    /// Reset the log once it holds more than `retained_writes` writes, all
+    /// of them committed
+    fn trim_log(&mut self) {
+        let logged = self.storage.wal.next_lsn() - self.log_start.lsn;
+        if logged <= self.config.retained_writes as u64 || !self.pending.is_empty() {
+            return;
+        }
+        if let Err(error) = self.storage.wal.reset() {
+            tracing::warn!(shard = %self.shard, %error, "cannot reset the replication log");
+            return;
+        }
+        self.log_start = LogStart {
+            lsn: self.storage.wal.next_lsn(),
+            seq: self.next_seq,
+        };
+    }
+
+    fn apply_write(&mut self, version: Version, write: MvccWrite) {
+        self.max_seq_seen = self.max_seq_seen.max(version.seq);
+        let (id, entity) = match write {
+            MvccWrite::Put(entity) => (entity.id, Some(*entity)),
+            MvccWrite::Delete(id) => (id, None),
+        };
+        self.apply_entry(id, version, entity);
+    }
+
+    /// Store `entity` in the collection if `version` is newer than the
+    /// stored one
+    fn apply_entry(&mut self, id: EntityId, version: Version, entity: Option<Entity>) -> bool {
+        let previous = self.versions.get(&id).copied();
+        if previous.is_some_and(|entry| entry.version >= version) {
+            return false;
+        }
+        let deleted = entity.is_none();
+        match entity {
+            Some(entity) => {
+                if let Err(error) = self.storage.collection.upsert(entity) {
+                    tracing::warn!(shard = %self.shard, entity = %id, %error, "cannot apply replicated write");
+                    return false;
+                }
+            }
+            None => {
+                self.storage.collection.delete(&id);
+            }
+        }
+        let removed = previous.map(|entry| entry_hash(&id, entry.version, entry.deleted));
+        let leaf = self.tree.leaf_of(&id);
+        self.tree.update(leaf, removed, entry_hash(&id, version, deleted));
+        self.versions.insert(id, StoredVersion { version, deleted });
+        self.max_seq_seen = self.max_seq_seen.max(version.seq);
+        self.stats.applied_writes += 1;
+        true
+    }
+
+    /// Descend into differing subtrees; exchange entries of differing leaves
+    fn handle_probe(&mut self, peer: NodeId, nodes: Vec<(usize, u64)>) {
+        let leaf_count = self.tree.leaf_count();
+        let mut children = Vec::new();
+        let mut leaves = Vec::new();
+        for (pos, hash) in nodes {
+            if pos == 0 || pos >= self.tree.nodes.len() || self.tree.nodes[pos] == hash {
+                continue;
+            }
+            if pos >= leaf_count {
+                leaves.push(pos - leaf_count);
+            } else {
+                children.push((2 * pos, self.tree.nodes[2 * pos]));
+                children.push((2 * pos + 1, self.tree.nodes[2 * pos + 1]));
+            }
+        }
+        if !children.is_empty() {
+            self.send(
+                peer,
+                ReplicationMessage::MerkleProbe {
+                    shard: self.shard,
+                    nodes: children,
+                },
+            );
+        }
+        if !leaves.is_empty() {
+            let entries = self.entries_in(&leaves);
+            self.stats.repair_entries_sent += entries.len() as u64;
+            self.send(
+                peer,
+                ReplicationMessage::Repair {
+                    shard: self.shard,
+                    leaves,
+                    entries,
+                    reply: true,
+                },
+            );
+        }
+    }
+
+    fn handle_repair(&mut self, peer: NodeId, leaves: Vec<usize>, entries: Vec<ReplicaEntry>, reply: bool) {
+        let theirs: BTreeMap<EntityId, Version> = entries.iter().map(|e| (e.id, e.version)).collect();
+        for entry in entries {
+            if self.apply_entry(entry.id, entry.version, entry.entity.map(|e| *e)) {
+                self.stats.repaired_entries += 1;
+            }
+        }
+        if !reply {
+            return;
+        }
+        // Answer with what the peer is missing or holds an older version of
+        let newer: Vec<_> = self
+            .entries_in(&leaves)
+            .into_iter()
+            .filter(|entry| theirs.get(&entry.id).is_none_or(|version| *version < entry.version))
+            .collect();
+        if !newer.is_empty() {
+            self.stats.repair_entries_sent += newer.len() as u64;
+            self.send(
+                peer,
+                ReplicationMessage::Repair {
+                    shard: self.shard,
+                    leaves,
+                    entries: newer,
+                    reply: false,
+                },
+            );
+        }
+    }
+
+    fn entries_in(&self, leaves: &[usize]) -> Vec<ReplicaEntry> {
+        let leaves: HashSet<usize> = leaves.iter().copied().collect();
+        self.versions
+            .iter()
+            .filter(|(id, _)| leaves.contains(&self.tree.leaf_of(id)))
+            .map(|(id, stored)| self.entry(id, stored))
+            .collect()
+    }
+
+    fn entry(&self, id: &EntityId, stored: &StoredVersion) -> ReplicaEntry {
+        let entity = if stored.deleted { None } else { self.storage.collection.get(id) };
+        ReplicaEntry {
+            id: *id,
+            version: stored.version,
+            entity: entity.map(|entity| Box::new((*entity).clone())),
+        }
+    }
+
+    fn send(&self, to: NodeId, message: ReplicationMessage) {
+        self.transport.send(self.id, to, message);
+    }
+}
+
+fn validate_replicas(replicas: &[NodeId], config: &ReplicationConfig) -> ConsensusResult<()> {
+    if replicas.len() < config.min_replicas || replicas.len() > config.max_replicas {
+        return Err(ConsensusError::InvalidReplicaSet {
+            replicas: replicas.len(),
+            min: config.min_replicas,
+            max: config.max_replicas,
+        });
+    }
+    Ok(())
+}
+
+impl fmt::Debug for ShardReplica {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShardReplica")
+            .field("shard", &self.shard)
+            .field("id", &self.id)
+            .field("primary", &self.primary)
+            .field("epoch", &self.epoch)
+            .field("applied", &self.applied)
+            .field("entries", &self.versions.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::collection::CollectionConfig;
+    use crate::core::{CollectionId, Vector};
+    use crate::distributed::transport::SimulatedNetwork;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    /// Empty two-dimensional collection and a log under `dir`
+    fn storage(dir: &Path) -> ShardStorage {
+        let id = CollectionId::new();
+        let collection = Collection::new(id, "shard".to_string(), CollectionConfig::new(2), 0);
+        let wal = Wal::open(dir.join(format!("{id}.wal"))).unwrap().with_sync_on_append(false);
+        ShardStorage::new(Arc::new(collection), wal)
+    }
+
+    /// Replicas of one shard on a simulated network
+    struct Group {
+        _dir: TempDir,
+        network: Arc<SimulatedNetwork<ReplicationMessage>>,
+        nodes: Vec<NodeId>,
+        replicas: BTreeMap<NodeId, ShardReplica>,
+        down: BTreeSet<NodeId>,
+    }
+
+    impl Group {
+        fn new(consistency: ConsistencyLevel, seed: u64, tweak: impl Fn(&mut ReplicationConfig)) -> Self {
+            let network = Arc::new(SimulatedNetwork::new(seed));
+            let nodes: Vec<_> = (0..3).map(|_| NodeId::new()).collect();
+            let mut config = ReplicationConfig {
+                consistency,
+                heartbeat_interval_ms: 50,
+                anti_entropy_interval_ms: 100,
+                ..ReplicationConfig::default()
+            };
+            tweak(&mut config);
+            let shard = ShardId::new();
+            let dir = TempDir::new().unwrap();
+            let replicas = nodes
+                .iter()
+                .map(|id| {
+                    let storage = storage(dir.path());
+                    let replica = ShardReplica::new(shard, *id, nodes.clone(), config, network.clone(), storage, 0);
+                    (*id, replica.unwrap())
+                })
+                .collect();
+            Self {
+                _dir: dir,
+                network,
+                nodes,
+                replicas,
+                down: BTreeSet::new(),
+            }
+        }
+
+        fn run(&mut self, millis: u64) {
+            for _ in 0..millis {
+                self.network.advance(1);
+                let now = self.network.now();
+                for (id, replica) in self.replicas.iter_mut() {
+                    if self.down.contains(id) {
+                        continue;
+                    }
+                    replica.tick(now);
+                    for envelope in self.network.drain(*id) {
+                        replica.step(envelope.from, envelope.message, now);
+                    }
+                }
+            }
+        }
+
+        fn replica(&mut self, index: usize) -> &mut ShardReplica {
+            self.replicas.get_mut(&self.nodes[index]).unwrap()
+        }
+
+        fn crash(&mut self, index: usize) {
+            self.down.insert(self.nodes[index]);
+            self.network.crash(self.nodes[index]);
+        }
+
+        fn restart(&mut self, index: usize) {
+            self.down.remove(&self.nodes[index]);
+            self.network.restart(self.nodes[index]);
+        }
+
+        fn converged(&self) -> bool {
+            let roots: Vec<_> = self.replicas.values().map(ShardReplica::merkle_root).collect();
+            roots.windows(2).all(|w| w[0] == w[1])
+        }
+    }
+
+    fn put(value: f32) -> (EntityId, MvccWrite) {
+        let entity = Entity::new(Some(Vector::new(vec![value, 0.0])), None, None);
+        (entity.id, MvccWrite::Put(Box::new(entity)))
+    }
+
+    #[test]
+    fn test_replica_set_bounds() {
+        let network: Arc<SimulatedNetwork<ReplicationMessage>> = Arc::new(SimulatedNetwork::new(1));
+        let config = ReplicationConfig::default();
+        let nodes: Vec<_> = (0..config.max_replicas + 1).map(|_| NodeId::new()).collect();
+        let dir = TempDir::new().unwrap();
+        let create = |replicas: &[NodeId]| {
+            let storage = storage(dir.path());
+            ShardReplica::new(ShardId::new(), nodes[0], replicas.to_vec(), config, network.clone(), storage, 0)
+        };
+
+        assert!(matches!(create(&nodes[..2]), Err(ConsensusError::InvalidReplicaSet { replicas: 2, .. })));
+        assert!(create(&nodes).is_err());
+        let mut replica = create(&nodes[..3]).unwrap();
+        assert!(replica.is_primary());
+        assert!(replica.set_replicas(nodes[..1].to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_strong_writes_wait_for_quorum() {
+        let mut group = Group::new(ConsistencyLevel::Strong, 1, |_| {});
+        let (primary, backups) = (group.nodes[0], group.nodes[1..].to_vec());
+        group.network.partition(&[primary], &backups);
+
+        let (id, write) = put(1.0);
+        let token = group.replica(0).write(write).unwrap();
+        group.run(200);
+        assert!(!group.replica(0).is_committed(&token));
+        assert!(group.replica(0).read(&id, None).unwrap().is_none());
+        assert!(matches!(group.replica(1).read(&id, None), Err(ConsensusError::NotLeader { .. })));
+
+        group.network.heal();
+        group.run(200);
+        assert!(group.replica(0).is_committed(&token));
+        assert!(group.replica(0).read(&id, None).unwrap().is_some());
+        assert!(group.converged());
+    }
+
+    #[test]
+    fn test_causal_token_gives_read_your_writes() {
+        let mut group = Group::new(ConsistencyLevel::Causal, 2, |_| {});
+        let (primary, lagging) = (group.nodes[0], group.nodes[2]);
+        group.network.set_link_latency(primary, lagging, 80);
+
+        let (id, write) = put(1.0);
+        let token = group.replica(0).write(write).unwrap();
+        assert!(group.replica(0).is_committed(&token));
+        group.run(10);
+
+        assert!(group.replica(1).read(&id, Some(&token)).unwrap().is_some());
+        assert!(matches!(
+            group.replica(2).read(&id, Some(&token)),
+            Err(ConsensusError::ReplicaBehind { .. })
+        ));
+        // Without a token a lagging replica may serve stale data
+        assert!(group.replica(2).read(&id, None).unwrap().is_none());
+
+        group.run(100);
+        assert!(group.replica(2).read(&id, Some(&token)).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_distant_backup_receives_batches() {
+        let mut group = Group::new(ConsistencyLevel::Causal, 6, |_| {});
+        let (primary, distant) = (group.nodes[0], group.nodes[2]);
+        group.network.set_link_latency(primary, distant, 60);
+        group.network.set_link_latency(distant, primary, 60);
+        group.replica(0).set_distant([distant, NodeId::new()]);
+
+        let mut ids = Vec::new();
+        for i in 0..50 {
+            let (id, write) = put(i as f32);
+            ids.push(id);
+            group.replica(0).write(write).unwrap();
+            group.run(4);
+        }
+        assert!(group.replica(1).read(&ids[49], None).unwrap().is_some());
+        group.run(300);
+        assert!(group.converged());
+        // One batch per interval instead of one message per write
+        let sent = group.replica(0).stats().distant_batches_sent;
+        assert!((2..=4).contains(&sent), "{sent} batches");
+        assert_eq!(group.replica(2).stats().caught_up_writes, 50);
+        assert_eq!(group.replica(2).stats().snapshots_installed, 0);
+
+        // Strong writes may need the distant backup for a majority
+        let mut group = Group::new(ConsistencyLevel::Strong, 7, |_| {});
+        let distant = group.nodes[2];
+        group.replica(0).set_distant([distant]);
+        group.network.crash(group.nodes[1]);
+        let token = group.replica(0).write(put(1.0).1).unwrap();
+        group.run(20);
+        assert!(group.replica(0).is_committed(&token));
+        assert_eq!(group.replica(0).stats().distant_batches_sent, 0);
+    }
+
+    #[test]
+    fn test_backup_catches_up_from_stream_and_snapshot() {
+        let mut group = Group::new(ConsistencyLevel::Causal, 3, |config| config.retained_writes = 20);
+        group.crash(1);
+        for i in 0..10 {
+            group.replica(0).write(put(i as f32).1).unwrap();
+        }
+        assert_eq!(group.replica(0).storage.wal.read_all().unwrap().len(), 10);
+        group.restart(1);
+        group.run(200);
+        assert_eq!(group.replica(1).stats().caught_up_writes, 10);
+        assert_eq!(group.replica(1).stats().snapshots_installed, 0);
+        assert_eq!(group.replica(1).collection().len(), 10);
+
+        // Fall behind past the retained stream
+        group.crash(2);
+        for i in 0..60 {
+            group.replica(0).write(put(i as f32).1).unwrap();
+        }
+        // The log was reset once it held more than 20 committed writes
+        assert!(group.replica(0).storage.wal.read_all().unwrap().len() <= 20);
+        group.restart(2);
+        group.run(300);
+        assert_eq!(group.replica(2).stats().snapshots_installed, 1);
+        assert_eq!(group.replica(2).applied(), group.replica(0).applied());
+        assert_eq!(group.replica(2).len(), 70);
+        assert!(group.converged());
+    }
+
+    #[test]
+    fn test_anti_entropy_repairs_only_differing_ranges() {
+        let mut group = Group::new(ConsistencyLevel::EventualConsistency, 4, |_| {});
+        for i in 0..200 {
+            group.replica(0).write(put(i as f32).1).unwrap();
+        }
+        group.run(100);
+        assert!(group.converged());
+
+        // Diverge the backups directly, bypassing the primary's stream
+        group.crash(0);
+        let (lost, write) = put(-1.0);
+        let MvccWrite::Put(entity) = write else { unreachable!() };
+        let version = Version { epoch: 1, seq: 1_000 };
+        group.replica(1).apply_entry(lost, version, Some(*entity));
+        let deleted = *group.replica(2).versions.keys().next().unwrap();
+        group.replica(2).apply_entry(deleted, Version { epoch: 1, seq: 1_001 }, None);
+        assert!(!group.converged());
+
+        group.run(500);
+        let (a, b) = (group.nodes[1], group.nodes[2]);
+        assert_eq!(group.replicas[&a].merkle_root(), group.replicas[&b].merkle_root());
+        assert!(group.replica(2).read(&lost, None).unwrap().is_some());
+        assert!(group.replica(1).read(&deleted, None).unwrap().is_none());
+        let sent = group.replica(1).stats().repair_entries_sent + group.replica(2).stats().repair_entries_sent;
+        assert!(sent < 20, "repair sent {sent} entries for two differences");
+    }
+
+    #[test]
+    fn test_promoted_backup_supersedes_old_primary() {
+        let mut group = Group::new(ConsistencyLevel::Causal, 5, |_| {});
+        let (id, write) = put(1.0);
+        group.replica(0).write(write).unwrap();
+        group.run(50);
+
+        // The old primary accepts a write nobody else sees, then dies
+        group.crash(0);
+        let MvccWrite::Put(mut stale) = put(0.0).1 else { unreachable!() };
+        stale.id = id;
+        group.replica(0).write(MvccWrite::Put(stale)).unwrap();
+
+        let now = group.network.now();
+        group.replica(1).start_promotion(now);
+        group.replica(2).start_promotion(now);
+        group.run(20);
+        assert!(group.replica(1).is_primary());
+        assert_eq!(group.replica(2).primary(), Some(group.nodes[1]));
+        let token = group.replica(1).write(MvccWrite::Delete(id)).unwrap();
+        assert_eq!(token.version.epoch, 2);
+        assert!(matches!(group.replica(0).read(&id, None), Ok(Some(_))));
+
+        group.restart(0);
+        group.run(300);
+        assert_eq!(group.replica(0).primary(), Some(group.nodes[1]));
+        assert!(group.replica(0).read(&id, Some(&token)).unwrap().is_none());
+        assert!(group.replica(2).read(&id, Some(&token)).unwrap().is_none());
+        assert!(group.replica(0).write(put(2.0).1).is_err());
+    }
+
+    #[test]
+    fn test_abandoned_candidacy_follows_the_elected_primary() {
+        let mut group = Group::new(ConsistencyLevel::Causal, 9, |_| {});
+        group.replica(0).write(put(1.0).1).unwrap();
+        group.run(50);
+        group.crash(0);
+
+        // The second backup votes for the first, then notices the failure
+        // itself before hearing from the new primary
+        let (first, second) = (group.nodes[1], group.nodes[2]);
+        let now = group.network.now();
+        group.replica(1).start_promotion(now);
+        group.network.advance(1);
+        let now = group.network.now();
+        for envelope in group.network.drain(second) {
+            group.replica(2).step(envelope.from, envelope.message, now);
+        }
+        assert_eq!(group.replica(2).voted, Some((2, first)));
+        group.replica(2).start_promotion(now);
+        group.run(200);
+
+        assert!(group.replica(1).is_primary());
+        assert_eq!(group.replica(2).primary(), Some(first));
+        let token = group.replica(1).write(put(2.0).1).unwrap();
+        group.run(100);
+        assert!(group.replica(2).read(&EntityId::new(), Some(&token)).is_ok());
+    }
+
+    #[test]
+    fn test_failover_promotes_most_up_to_date_backup() {
+        let mut group = Group::new(ConsistencyLevel::Strong, 8, |_| {});
+        let (primary, first, second) = (group.nodes[0], group.nodes[1], group.nodes[2]);
+
+        // The quorum ack comes from the second backup; the first never sees the write
+        group.network.partition(&[primary], &[first]);
+        let (id, write) = put(1.0);
+        let token = group.replica(0).write(write).unwrap();
+        group.run(5);
+        assert!(group.replica(0).is_committed(&token));
+        // Received but not yet reported committed, so not in the store
+        assert!(group.replica(2).applied() >= token.version);
+        assert!(group.replica(2).is_empty());
+        group.run(100);
+        assert_eq!(group.replica(2).len(), 1);
+
+        // An uncommitted write stays out of every store
+        group.network.partition(&[primary], &[second]);
+        let (lost, write) = put(2.0);
+        let pending = group.replica(0).write(write).unwrap();
+        group.run(100);
+        assert!(!group.replica(0).is_committed(&pending));
+
+        group.crash(0);
+        group.network.heal();
+        let now = group.network.now();
+        group.replica(1).start_promotion(now);
+        group.replica(2).start_promotion(now);
+        group.run(200);
+
+        assert!(!group.replica(1).is_primary());
+        assert!(group.replica(2).is_primary());
+        assert_eq!(group.replica(1).primary(), Some(second));
+        assert!(group.replica(2).read(&id, None).unwrap().is_some());
+        assert!(group.replica(2).read(&lost, None).unwrap().is_none());
+        assert_eq!(group.replica(1).len(), 1);
+
+        let token = group.replica(2).write(put(3.0).1).unwrap();
+        group.run(100);
+        assert!(group.replica(2).is_committed(&token));
+        let (a, b) = (group.nodes[1], group.nodes[2]);
+        assert_eq!(group.replicas[&a].merkle_root(), group.replicas[&b].merkle_root());
+    }
+}
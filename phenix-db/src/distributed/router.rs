@@ -1 +1,725 @@
-// TODO: Implement query router
+//! Semantic query routing
+//!
+//! Each node keeps a compact summary of every shard: a handful of centroids
+//! computed from a sample of the shard's vectors (`global_awareness_pct` of
+//! them). A k-NN query is sent only to the shards whose summaries make them
+//! likely to hold its neighbours, and the partial top-k lists are merged.
+//!
+//! The summaries are read as a Gaussian mixture with one shared variance:
+//! the probability that a shard holds the query's neighbourhood is
+//! proportional to `Σ count_c · exp(-‖q - c‖² / 2σ²)` over its centroids.
+//! Shards are taken in order of probability until their combined mass
+//! reaches the routing accuracy target, so a query far from every centroid,
+//! or between several of them, automatically fans out wider.
+//!
+//! A sample of queries is also run against every shard to measure routing
+//! recall. When the measured recall falls below the target, the router
+//! raises the mixture temperature, which flattens the probabilities and
+//! widens future fan-outs; it narrows again slowly while recall is met.
+
+use crate::core::config::DistributedConfig;
+use crate::core::error::Result;
+use crate::core::vector::DistanceMetric;
+use crate::core::{EntityId, ShardId, Vector};
+use crate::index::simd::{SearchHit, TopK};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+/// Lloyd iterations used to place summary centroids
+const KMEANS_ITERATIONS: usize = 8;
+
+/// Bounds of the calibrated temperature
+const MIN_TEMPERATURE: f64 = 0.25;
+const MAX_TEMPERATURE: f64 = 256.0;
+
+/// Router settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RouterConfig {
+    /// Metric the shards search with
+    pub metric: DistanceMetric,
+
+    /// Centroids kept per shard summary (default: 8)
+    pub centroids_per_shard: usize,
+
+    /// Fraction of a shard's vectors sampled into its summary (default: 0.1)
+    pub awareness_pct: f32,
+
+    /// Probability mass a route must cover, and recall the router
+    /// calibrates towards (default: 0.9)
+    pub accuracy_target: f32,
+
+    /// Fewest shards a query is sent to (default: 1)
+    pub min_fanout: usize,
+
+    /// Most shards a query is sent to, `None` for no limit
+    pub max_fanout: Option<usize>,
+
+    /// Fraction of queries also run against every shard to measure
+    /// recall (default: 0.01)
+    pub recall_sample_rate: f64,
+
+    /// Number of recall samples averaged (default: 100)
+    pub recall_window: usize,
+}
+
+impl Default for RouterConfig {
+    fn default() -> Self {
+        Self::from(&DistributedConfig::default())
+    }
+}
+
+impl From<&DistributedConfig> for RouterConfig {
+    fn from(config: &DistributedConfig) -> Self {
+        Self {
+            metric: DistanceMetric::default(),
+            centroids_per_shard: 8,
+            awareness_pct: config.global_awareness_pct,
+            accuracy_target: config.routing_accuracy_target,
+            min_fanout: 1,
+            max_fanout: None,
+            recall_sample_rate: 0.01,
+            recall_window: 100,
+        }
+    }
+}
+
+/// Cluster of a shard's vectors
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Centroid {
+    /// Cluster centre, in the space routing distances are measured in
+    pub vector: Vector,
+
+    /// Estimated number of the shard's entities in the cluster
+    pub count: usize,
+
+    /// Mean squared distance of sampled members to the centre
+    pub variance: f32,
+}
+
+/// Partial view of one shard used for routing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShardSummary {
+    /// Summarized shard
+    pub shard: ShardId,
+
+    /// Entities in the shard when summarized
+    pub entity_count: usize,
+
+    /// Vectors sampled to build the summary
+    pub sampled: usize,
+
+    /// Clusters found in the sample
+    pub centroids: Vec<Centroid>,
+}
+
+impl ShardSummary {
+    /// Summarize a shard from its vectors
+    ///
+    /// Samples `awareness_pct` of the vectors (at least a few per centroid)
+    /// at random and clusters them with k-means. The sample is seeded from
+    /// the shard id, so rebuilding an unchanged shard gives the same summary.
+    pub fn build<'a>(shard: ShardId, vectors: impl IntoIterator<Item = &'a Vector>, config: &RouterConfig) -> Self {
+        let vectors: Vec<&Vector> = vectors.into_iter().collect();
+        let total = vectors.len();
+        let centroids = config.centroids_per_shard.max(1);
+        let wanted = ((total as f64 * config.awareness_pct as f64).round() as usize).max(centroids * 4);
+        let sample_size = wanted.min(total);
+        let dimensions = vectors.first().map_or(0, |v| v.dimensions);
+
+        // Partial Fisher-Yates shuffle; a fixed stride could alias with
+        // periodic insertion patterns
+        let mut indices: Vec<usize> = (0..total).collect();
+        let seed = shard.as_uuid().as_u64_pair();
+        let mut rng_state = (seed.0 ^ seed.1).max(1);
+        for i in 0..sample_size {
+            rng_state ^= rng_state << 13;
+            rng_state ^= rng_state >> 7;
+            rng_state ^= rng_state << 17;
+            indices.swap(i, i + (rng_state % (total - i) as u64) as usize);
+        }
+
+        let sample: Vec<Vec<f32>> = indices[..sample_size]
+            .iter()
+            .map(|i| vectors[*i])
+            .filter(|v| v.dimensions == dimensions)
+            .map(|v| project(v, config.metric))
+            .collect();
+
+        Self {
+            shard,
+            entity_count: total,
+            sampled: sample.len(),
+            centroids: kmeans(&sample, centroids, total),
+        }
+    }
+}
+
+/// Shards chosen for a query
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutePlan {
+    /// Target shards with their probability of holding the neighbourhood,
+    /// most likely first
+    pub targets: Vec<(ShardId, f32)>,
+
+    /// Probability mass covered by the targets
+    pub confidence: f32,
+
+    /// Number of shards the router knows about
+    pub total_shards: usize,
+}
+
+impl RoutePlan {
+    /// Target shard identifiers
+    pub fn shards(&self) -> impl Iterator<Item = ShardId> + '_ {
+        self.targets.iter().map(|(shard, _)| *shard)
+    }
+
+    /// Whether every known shard is targeted
+    pub fn is_full_fanout(&self) -> bool {
+        self.targets.len() == self.total_shards
+    }
+}
+
+/// Result of a routed k-NN search
+#[derive(Debug, Clone)]
+pub struct RoutedSearch {
+    /// Merged hits, closest first
+    pub hits: Vec<SearchHit>,
+
+    /// Route the query took
+    pub plan: RoutePlan,
+
+    /// Recall against full fan-out, if this query was sampled
+    pub recall: Option<f32>,
+}
+
+/// Routing counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RouterStats {
+    /// Queries routed
+    pub queries: u64,
+
+    /// Shard searches issued for routed queries
+    pub shards_queried: u64,
+
+    /// Queries sampled for recall measurement
+    pub sampled: u64,
+}
+
+/// Routes vector queries to the shards likely to hold their neighbours
+#[derive(Debug, Clone)]
+pub struct QueryRouter {
+    config: RouterConfig,
+    summaries: BTreeMap<ShardId, ShardSummary>,
+    variance: f64,
+    temperature: f64,
+    recalls: VecDeque<f32>,
+    rng_state: u64,
+    stats: RouterStats,
+}
+
+impl QueryRouter {
+    /// Create a router that knows no shards yet
+    pub fn new(config: RouterConfig) -> Self {
+        Self {
+            config,
+            summaries: BTreeMap::new(),
+            variance: 1.0,
+            temperature: 1.0,
+            recalls: VecDeque::new(),
+            rng_state: 0x9E37_79B9_7F4A_7C15,
+            stats: RouterStats::default(),
+        }
+    }
+
+    /// Router settings
+    pub fn config(&self) -> &RouterConfig {
+        &self.config
+    }
+
+    /// Add or replace the summary of a shard
+    pub fn update_summary(&mut self, summary: ShardSummary) {
+        self.summaries.insert(summary.shard, summary);
+        self.refresh_variance();
+    }
+
+    /// Forget a shard
+    pub fn remove_shard(&mut self, shard: &ShardId) -> Option<ShardSummary> {
+        let removed = self.summaries.remove(shard);
+        self.refresh_variance();
+        removed
+    }
+
+    /// Summary of a shard
+    pub fn summary(&self, shard: &ShardId) -> Option<&ShardSummary> {
+        self.summaries.get(shard)
+    }
+
+    /// Number of known shards
+    pub fn shard_count(&self) -> usize {
+        self.summaries.len()
+    }
+
+    /// Current temperature; above 1 the router fans out wider than the
+    /// summaries alone suggest
+    pub fn temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    /// Mean recall of the sampled queries, `None` before the first sample
+    pub fn recall(&self) -> Option<f32> {
+        if self.recalls.is_empty() {
+            return None;
+        }
+        Some(self.recalls.iter().sum::<f32>() / self.recalls.len() as f32)
+    }
+
+    /// Routing counters
+    pub fn stats(&self) -> RouterStats {
+        self.stats
+    }
+
+    /// Choose the shards to send a query to
+    pub fn route(&self, query: &Vector) -> RoutePlan {
+        let total_shards = self.summaries.len();
+        let point = project(query, self.config.metric);
+        let scale = 2.0 * self.variance * self.temperature;
+
+        // Log-probability of each shard under the mixture
+        let mut scores: Vec<(ShardId, f64)> = self
+            .summaries
+            .values()
+            .map(|summary| {
+                let terms: Vec<f64> = summary
+                    .centroids
+                    .iter()
+                    .filter(|c| c.vector.dimensions == query.dimensions && c.count > 0)
+                    .map(|c| (c.count as f64).ln() - squared_distance(&point, &c.vector.values) / scale)
+                    .collect();
+                (summary.shard, log_sum_exp(&terms))
+            })
+            .collect();
+
+        let norm = log_sum_exp(&scores.iter().map(|(_, s)| *s).collect::<Vec<_>>());
+        let unroutable = !norm.is_finite();
+        for (_, score) in scores.iter_mut() {
+            *score = if unroutable {
+                1.0 / total_shards as f64
+            } else {
+                (*score - norm).exp()
+            };
+        }
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let max_fanout = self.config.max_fanout.unwrap_or(usize::MAX).max(1);
+        let mut targets = Vec::new();
+        let mut confidence = 0.0;
+        for (shard, probability) in scores {
+            let covered = confidence >= self.config.accuracy_target as f64 && targets.len() >= self.config.min_fanout;
+            if covered || targets.len() >= max_fanout {
+                break;
+            }
+            confidence += probability;
+            targets.push((shard, probability as f32));
+        }
+
+        RoutePlan {
+            targets,
+            confidence: confidence.min(1.0) as f32,
+            total_shards,
+        }
+    }
+
+    /// Run a k-NN query on the routed shards and merge their results
+    ///
+    /// `search_shard` runs the query on one shard. Sampled queries are also
+    /// sent to every other shard, and their recall feeds calibration.
+    ///
+    /// # Errors
+    /// * Any error returned by `search_shard`
+    pub fn search<F>(&mut self, query: &Vector, k: usize, mut search_shard: F) -> Result<RoutedSearch>
+    where
+        F: FnMut(ShardId, &Vector, usize) -> Result<Vec<SearchHit>>,
+    {
+        let plan = self.route(query);
+        let mut partials = Vec::with_capacity(plan.targets.len());
+        for shard in plan.shards() {
+            partials.push(search_shard(shard, query, k)?);
+        }
+        self.stats.queries += 1;
+        self.stats.shards_queried += plan.targets.len() as u64;
+        let hits = merge_top_k(partials.iter().cloned(), k);
+
+        let mut recall = None;
+        if self.next_uniform() < self.config.recall_sample_rate {
+            // A full fan-out is its own reference; recording it lets an
+            // over-cautious router narrow again
+            let routed: HashSet<ShardId> = plan.shards().collect();
+            let others: Vec<ShardId> = self.summaries.keys().copied().filter(|s| !routed.contains(s)).collect();
+            for shard in others {
+                partials.push(search_shard(shard, query, k)?);
+            }
+            let full = merge_top_k(partials, k);
+            recall = Some(self.record_recall(&hits, &full));
+        }
+
+        Ok(RoutedSearch { hits, plan, recall })
+    }
+
+    /// Record the recall of a routed result against the full fan-out result
+    ///
+    /// Returns the recall of this sample: the fraction of `full` hits also
+    /// present in `routed`.
+    pub fn record_recall(&mut self, routed: &[SearchHit], full: &[SearchHit]) -> f32 {
+        let found: HashSet<EntityId> = routed.iter().map(|hit| hit.id).collect();
+        let recall = if full.is_empty() {
+            1.0
+        } else {
+            full.iter().filter(|hit| found.contains(&hit.id)).count() as f32 / full.len() as f32
+        };
+
+        self.stats.sampled += 1;
+        self.recalls.push_back(recall);
+        while self.recalls.len() > self.config.recall_window.max(1) {
+            self.recalls.pop_front();
+        }
+
+        let target = self.config.accuracy_target;
+        if recall < target {
+            self.temperature = (self.temperature * 1.5).min(MAX_TEMPERATURE);
+            tracing::debug!(recall, temperature = self.temperature, "routing recall below target");
+        } else if recall >= 1.0 && self.recall().is_some_and(|mean| mean >= target) {
+            self.temperature = (self.temperature * 0.95).max(MIN_TEMPERATURE);
+        }
+        recall
+    }
+
+    /// Recompute the shared mixture variance from all summaries
+    fn refresh_variance(&mut self) {
+        let (weighted, count) = self
+            .summaries
+            .values()
+            .flat_map(|summary| &summary.centroids)
+            .fold((0.0, 0.0), |(sum, n), c| (sum + c.variance as f64 * c.count as f64, n + c.count as f64));
+        self.variance = if count > 0.0 { (weighted / count).max(1e-6) } else { 1.0 };
+    }
+
+    /// Uniform sample in [0, 1)
+    fn next_uniform(&mut self) -> f64 {
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 7;
+        self.rng_state ^= self.rng_state << 17;
+        (self.rng_state >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Merge per-shard top-k lists into the global top `k`
+///
+/// Shards hold disjoint entities, so no deduplication is needed.
+pub fn merge_top_k(partials: impl IntoIterator<Item = Vec<SearchHit>>, k: usize) -> Vec<SearchHit> {
+    let mut top = TopK::new(k);
+    for hit in partials.into_iter().flatten() {
+        top.push(hit.id, hit.distance);
+    }
+    top.into_sorted_vec()
+}
+
+/// Map a vector into the space routing distances are measured in
+///
+/// Cosine compares directions, so vectors are normalized; Euclidean and dot
+/// product use the raw values.
+pub(crate) fn project(vector: &Vector, metric: DistanceMetric) -> Vec<f32> {
+    match metric {
+        DistanceMetric::Cosine if vector.norm > 0.0 => vector.values.iter().map(|x| x / vector.norm).collect(),
+        _ => vector.values.clone(),
+    }
+}
+
+pub(crate) fn squared_distance(a: &[f32], b: &[f32]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| ((x - y) as f64).powi(2)).sum()
+}
+
+fn log_sum_exp(terms: &[f64]) -> f64 {
+    let max = terms.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    if !max.is_finite() {
+        return f64::NEG_INFINITY;
+    }
+    max + terms.iter().map(|t| (t - max).exp()).sum::<f64>().ln()
+}
+
+/// Cluster `points` into at most `k` centroids with counts scaled to `total`
+fn kmeans(points: &[Vec<f32>], k: usize, total: usize) -> Vec<Centroid> {
+    if points.is_empty() {
+        return Vec::new();
+    }
+
+    // Farthest-point seeding spreads the initial centres over the sample
+    let mut centres = vec![points[0].clone()];
+    let mut nearest: Vec<f64> = points.iter().map(|p| squared_distance(p, &centres[0])).collect();
+    while centres.len() < k {
+        let (index, distance) = nearest
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .expect("non-empty sample");
+        if *distance == 0.0 {
+            break;
+        }
+        let centre = points[index].clone();
+        for (near, p) in nearest.iter_mut().zip(points) {
+            *near = near.min(squared_distance(p, &centre));
+        }
+        centres.push(centre);
+    }
+
+    let dimensions = points[0].len();
+    let mut assignment = vec![0; points.len()];
+    for _ in 0..KMEANS_ITERATIONS {
+        for (slot, p) in assignment.iter_mut().zip(points) {
+            *slot = closest(p, &centres);
+        }
+        let mut sums = vec![vec![0.0f64; dimensions]; centres.len()];
+        let mut counts = vec![0usize; centres.len()];
+        for (p, &c) in points.iter().zip(&assignment) {
+            counts[c] += 1;
+            for (s, x) in sums[c].iter_mut().zip(p) {
+                *s += *x as f64;
+            }
+        }
+        for ((centre, sum), count) in centres.iter_mut().zip(sums).zip(&counts) {
+            if *count > 0 {
+                *centre = sum.into_iter().map(|s| (s / *count as f64) as f32).collect();
+            }
+        }
+    }
+
+    let mut variances = vec![0.0f64; centres.len()];
+    let mut counts = vec![0usize; centres.len()];
+    for (p, &c) in points.iter().zip(&assignment) {
+        variances[c] += squared_distance(p, &centres[c]);
+        counts[c] += 1;
+    }
+    centres
+        .into_iter()
+        .zip(variances)
+        .zip(counts)
+        .filter(|(_, count)| *count > 0)
+        .map(|((centre, variance), count)| Centroid {
+            vector: Vector::new(centre),
+            count: (count * total).div_ceil(points.len()),
+            variance: (variance / count as f64) as f32,
+        })
+        .collect()
+}
+
+fn closest(point: &[f32], centres: &[Vec<f32>]) -> usize {
+    centres
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (i, squared_distance(point, c)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(0, |(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn uniform(&mut self) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 >> 40) as f32 / (1u64 << 24) as f32
+        }
+
+        /// Roughly normal with unit variance
+        fn normal(&mut self) -> f32 {
+            (0..12).map(|_| self.uniform()).sum::<f32>() - 6.0
+        }
+
+        fn around(&mut self, centre: &[f32], spread: f32) -> Vector {
+            Vector::new(centre.iter().map(|x| x + spread * self.normal()).collect())
+        }
+    }
+
+    const DIMENSIONS: usize = 16;
+
+    /// Shards of (id, vector) pairs searched exactly
+    struct Cluster {
+        shards: BTreeMap<ShardId, Vec<(EntityId, Vector)>>,
+        metric: DistanceMetric,
+    }
+
+    impl Cluster {
+        /// One Gaussian blob per shard, or uniformly scattered data
+        fn new(shards: usize, per_shard: usize, clustered: bool, rng: &mut Rng) -> (Self, Vec<Vec<f32>>) {
+            let centres: Vec<Vec<f32>> = (0..shards)
+                .map(|_| (0..DIMENSIONS).map(|_| 20.0 * rng.uniform() - 10.0).collect())
+                .collect();
+            let shards = centres
+                .iter()
+                .map(|centre| {
+                    let vectors = (0..per_shard)
+                        .map(|_| {
+                            let v = if clustered {
+                                rng.around(centre, 1.0)
+                            } else {
+                                rng.around(&[0.0; DIMENSIONS], 5.0)
+                            };
+                            (EntityId::new(), v)
+                        })
+                        .collect();
+                    (ShardId::new(), vectors)
+                })
+                .collect();
+            let cluster = Self {
+                shards,
+                metric: DistanceMetric::Euclidean,
+            };
+            (cluster, centres)
+        }
+
+        fn router(&self, config: RouterConfig) -> QueryRouter {
+            let mut router = QueryRouter::new(config);
+            for (shard, entries) in &self.shards {
+                router.update_summary(ShardSummary::build(*shard, entries.iter().map(|(_, v)| v), &config));
+            }
+            router
+        }
+
+        fn search(&self, shard: ShardId, query: &Vector, k: usize) -> Result<Vec<SearchHit>> {
+            let mut top = TopK::new(k);
+            for (id, vector) in &self.shards[&shard] {
+                top.push(*id, self.metric.distance(query, vector));
+            }
+            Ok(top.into_sorted_vec())
+        }
+    }
+
+    fn config() -> RouterConfig {
+        RouterConfig {
+            metric: DistanceMetric::Euclidean,
+            centroids_per_shard: 4,
+            ..RouterConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_summary_samples_awareness_fraction() {
+        let mut rng = Rng(7);
+        let vectors: Vec<Vector> = (0..1000).map(|_| rng.around(&[3.0; DIMENSIONS], 1.0)).collect();
+        let summary = ShardSummary::build(ShardId::new(), &vectors, &config());
+
+        assert_eq!(summary.entity_count, 1000);
+        assert_eq!(summary.sampled, 100);
+        assert_eq!(summary.centroids.len(), 4);
+        let counted: usize = summary.centroids.iter().map(|c| c.count).sum();
+        assert!((1000..1010).contains(&counted));
+        for dimension in 0..DIMENSIONS {
+            let mean = summary
+                .centroids
+                .iter()
+                .map(|c| c.vector.values[dimension] * c.count as f32)
+                .sum::<f32>()
+                / counted as f32;
+            assert!((mean - 3.0).abs() < 0.5);
+        }
+    }
+
+    #[test]
+    fn test_clustered_queries_route_to_one_shard() {
+        let mut rng = Rng(11);
+        let (cluster, centres) = Cluster::new(8, 300, true, &mut rng);
+        let mut router = cluster.router(RouterConfig {
+            recall_sample_rate: 1.0,
+            ..config()
+        });
+
+        for centre in centres.iter().cycle().take(40) {
+            let query = rng.around(centre, 0.5);
+            let result = router
+                .search(&query, 10, |shard, q, k| cluster.search(shard, q, k))
+                .unwrap();
+            assert_eq!(result.hits.len(), 10);
+            assert!(result.plan.targets.len() <= 2);
+            assert!(result.plan.confidence >= 0.9);
+        }
+        let stats = router.stats();
+        assert_eq!(stats.queries, 40);
+        assert!(stats.shards_queried < 60);
+        assert!(router.recall().unwrap() >= 0.9);
+    }
+
+    #[test]
+    fn test_low_confidence_fans_out_wider() {
+        let mut rng = Rng(13);
+        let (cluster, centres) = Cluster::new(6, 200, true, &mut rng);
+        let router = cluster.router(config());
+
+        let near = router.route(&Vector::new(centres[0].clone()));
+        assert_eq!(near.targets.len(), 1);
+
+        // Halfway between two blobs both are plausible
+        let midpoint: Vec<f32> = centres[0].iter().zip(&centres[1]).map(|(a, b)| (a + b) / 2.0).collect();
+        let between = router.route(&Vector::new(midpoint));
+        let shards: Vec<_> = between.shards().collect();
+        assert!(shards.len() >= 2);
+        assert!(between.targets[0].1 < 0.9);
+    }
+
+    #[test]
+    fn test_calibration_widens_fanout_until_recall_met() {
+        let mut rng = Rng(17);
+        let (mut cluster, centres) = Cluster::new(4, 200, true, &mut rng);
+        let mut router = cluster.router(RouterConfig {
+            recall_sample_rate: 1.0,
+            recall_window: 10,
+            ..config()
+        });
+
+        // Shard 1 grows a second blob on top of shard 0 after its summary
+        // was taken, so the summaries now understate where neighbours live
+        let stale = *cluster.shards.keys().nth(1).unwrap();
+        for _ in 0..400 {
+            let vector = rng.around(&centres[0], 1.0);
+            cluster.shards.get_mut(&stale).unwrap().push((EntityId::new(), vector));
+        }
+
+        let mut fanouts = Vec::new();
+        for _ in 0..60 {
+            let query = rng.around(&centres[0], 0.5);
+            let result = router
+                .search(&query, 10, |shard, q, k| cluster.search(shard, q, k))
+                .unwrap();
+            fanouts.push(result.plan.targets.len());
+        }
+        assert_eq!(fanouts[0], 1);
+        assert!(router.temperature() > 1.0);
+        assert!(fanouts[50..].iter().all(|n| *n >= 2), "fan-out did not widen: {fanouts:?}");
+        assert!(router.recall().unwrap() >= 0.8);
+    }
+
+    #[test]
+    fn test_merge_top_k_orders_partials() {
+        let hits = |distances: &[f32]| {
+            distances
+                .iter()
+                .map(|d| SearchHit {
+                    id: EntityId::new(),
+                    distance: *d,
+                })
+                .collect::<Vec<_>>()
+        };
+        let merged = merge_top_k(vec![hits(&[0.1, 0.5, 0.9]), hits(&[0.2, 0.3]), Vec::new()], 4);
+        let distances: Vec<f32> = merged.iter().map(|h| h.distance).collect();
+        assert_eq!(distances, vec![0.1, 0.2, 0.3, 0.5]);
+
+        let mut router = QueryRouter::new(config());
+        assert_eq!(router.record_recall(&merged[..2], &merged), 0.5);
+        assert_eq!(router.recall(), Some(0.5));
+        assert!(router.route(&Vector::new(vec![1.0])).targets.is_empty());
+    }
+}
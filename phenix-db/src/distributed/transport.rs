@@ -0,0 +1,352 @@
+//! Message transport between nodes
+//!
+//! Distributed protocols are written as deterministic state machines that
+//! hand outgoing messages to a `Transport` and receive incoming ones from
+//! whoever drives them. Production transports wrap the RPC layer; tests use
+//! `SimulatedNetwork`, an in-memory network on a logical millisecond clock
+//! that can delay, drop and partition traffic reproducibly from a seed.
+
+use crate::core::NodeId;
+use parking_lot::Mutex;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::fmt;
+
+/// Outgoing side of a node's network connection
+pub trait Transport<M>: Send + Sync {
+    /// Send a message; delivery is best effort and may be reordered
+    fn send(&self, from: NodeId, to: NodeId, message: M);
+}
+
+/// Message received from the network
+#[derive(Debug, Clone, PartialEq)]
+pub struct Envelope<M> {
+    /// Sender
+    pub from: NodeId,
+
+    /// Recipient
+    pub to: NodeId,
+
+    /// Payload
+    pub message: M,
+}
+
+/// Delivery counters of a simulated network
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NetworkStats {
+    /// Messages handed to `send`
+    pub sent: u64,
+
+    /// Messages placed in a recipient's inbox
+    pub delivered: u64,
+
+    /// Messages lost to drops, partitions or crashed nodes
+    pub dropped: u64,
+}
+
+/// Message waiting for its delivery time
+struct InFlight<M> {
+    deliver_at: u64,
+    seq: u64,
+    envelope: Envelope<M>,
+}
+
+impl<M> PartialEq for InFlight<M> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.deliver_at, self.seq) == (other.deliver_at, other.seq)
+    }
+}
+
+impl<M> Eq for InFlight<M> {}
+
+impl<M> PartialOrd for InFlight<M> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<M> Ord for InFlight<M> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.deliver_at, self.seq).cmp(&(other.deliver_at, other.seq))
+    }
+}
+
+struct NetworkState<M> {
+    now: u64,
+    seq: u64,
+    rng_state: u64,
+    min_latency: u64,
+    max_latency: u64,
+    drop_rate: f64,
+    link_latency: HashMap<(NodeId, NodeId), u64>,
+    blocked: HashSet<(NodeId, NodeId)>,
+    crashed: HashSet<NodeId>,
+    in_flight: BinaryHeap<Reverse<InFlight<M>>>,
+    inboxes: HashMap<NodeId, VecDeque<Envelope<M>>>,
+    stats: NetworkStats,
+}
+
+impl<M> NetworkState<M> {
+    /// Uniform sample in [0, 1)
+    fn next_uniform(&mut self) -> f64 {
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 7;
+        self.rng_state ^= self.rng_state << 17;
+        (self.rng_state >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn latency(&mut self, from: NodeId, to: NodeId) -> u64 {
+        if let Some(latency) = self.link_latency.get(&(from, to)) {
+            return *latency;
+        }
+        let spread = self.max_latency - self.min_latency;
+        self.min_latency + (self.next_uniform() * (spread + 1) as f64) as u64
+    }
+
+    fn reachable(&self, from: NodeId, to: NodeId) -> bool {
+        !self.crashed.contains(&from) && !self.crashed.contains(&to) && !self.blocked.contains(&(from, to))
+    }
+}
+
+/// Deterministic in-memory network for tests and simulations
+///
+/// Time only moves when `advance` is called. A message sent at time `t`
+/// becomes receivable once the clock reaches `t + latency`; links are checked
+/// both when a message is sent and when it is delivered, so a partition also
+/// cuts off traffic that is already in flight.
+pub struct SimulatedNetwork<M> {
+    state: Mutex<NetworkState<M>>,
+}
+
+impl<M: Send> SimulatedNetwork<M> {
+    /// Create a network with 1ms latency and no faults
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: Mutex::new(NetworkState {
+                now: 0,
+                seq: 0,
+                rng_state: seed.max(1),
+                min_latency: 1,
+                max_latency: 1,
+                drop_rate: 0.0,
+                link_latency: HashMap::new(),
+                blocked: HashSet::new(),
+                crashed: HashSet::new(),
+                in_flight: BinaryHeap::new(),
+                inboxes: HashMap::new(),
+                stats: NetworkStats::default(),
+            }),
+        }
+    }
+
+    /// Current logical time in milliseconds
+    pub fn now(&self) -> u64 {
+        self.state.lock().now
+    }
+
+    /// Delay every message by a uniform latency in `[min, max]` milliseconds
+    pub fn set_latency(&self, min: u64, max: u64) {
+        let mut state = self.state.lock();
+        state.min_latency = min;
+        state.max_latency = max.max(min);
+    }
+
+    /// Fix the latency of the directed link `from → to`
+    pub fn set_link_latency(&self, from: NodeId, to: NodeId, latency: u64) {
+        self.state.lock().link_latency.insert((from, to), latency);
+    }
+
+    /// Drop each message independently with probability `rate`
+    pub fn set_drop_rate(&self, rate: f64) {
+        self.state.lock().drop_rate = rate.clamp(0.0, 1.0);
+    }
+
+    /// Cut all links between two groups of nodes, in both directions
+    pub fn partition(&self, left: &[NodeId], right: &[NodeId]) {
+        let mut state = self.state.lock();
+        for a in left {
+            for b in right {
+                state.blocked.insert((*a, *b));
+                state.blocked.insert((*b, *a));
+            }
+        }
+    }
+
+    /// Cut a node off from every other node in `nodes`
+    pub fn isolate(&self, node: NodeId, nodes: &[NodeId]) {
+        let others: Vec<_> = nodes.iter().copied().filter(|n| *n != node).collect();
+        self.partition(&[node], &others);
+    }
+
+    /// Restore every link cut by `partition` or `isolate`
+    pub fn heal(&self) {
+        self.state.lock().blocked.clear();
+    }
+
+    /// Stop delivering to and from a node and discard its inbox
+    pub fn crash(&self, node: NodeId) {
+        let mut state = self.state.lock();
+        state.crashed.insert(node);
+        if let Some(inbox) = state.inboxes.remove(&node) {
+            state.stats.dropped += inbox.len() as u64;
+        }
+    }
+
+    /// Reconnect a crashed node
+    pub fn restart(&self, node: NodeId) {
+        self.state.lock().crashed.remove(&node);
+    }
+
+    /// Whether a node is currently crashed
+    pub fn is_crashed(&self, node: NodeId) -> bool {
+        self.state.lock().crashed.contains(&node)
+    }
+
+    /// Move the clock forward and deliver every message that is due
+    pub fn advance(&self, millis: u64) {
+        let mut state = self.state.lock();
+        state.now += millis;
+        while let Some(Reverse(next)) = state.in_flight.peek() {
+            if next.deliver_at > state.now {
+                break;
+            }
+            let Reverse(in_flight) = state.in_flight.pop().expect("peeked message");
+            let envelope = in_flight.envelope;
+            if state.reachable(envelope.from, envelope.to) {
+                state.stats.delivered += 1;
+                state.inboxes.entry(envelope.to).or_default().push_back(envelope);
+            } else {
+                state.stats.dropped += 1;
+            }
+        }
+    }
+
+    /// Take the next delivered message for a node
+    pub fn recv(&self, node: NodeId) -> Option<Envelope<M>> {
+        self.state.lock().inboxes.get_mut(&node)?.pop_front()
+    }
+
+    /// Take every delivered message for a node
+    pub fn drain(&self, node: NodeId) -> Vec<Envelope<M>> {
+        self.state
+            .lock()
+            .inboxes
+            .get_mut(&node)
+            .map(|inbox| inbox.drain(..).collect())
+            .unwrap_or_default()
+    }
+
+    /// Number of messages sent but not yet delivered
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().in_flight.len()
+    }
+
+    /// Delivery counters
+    pub fn stats(&self) -> NetworkStats {
+        self.state.lock().stats
+    }
+}
+
+impl<M: Send> Transport<M> for SimulatedNetwork<M> {
+    fn send(&self, from: NodeId, to: NodeId, message: M) {
+        let mut state = self.state.lock();
+        state.stats.sent += 1;
+        let dropped = state.drop_rate > 0.0 && state.next_uniform() < state.drop_rate;
+        if dropped || !state.reachable(from, to) {
+            state.stats.dropped += 1;
+            return;
+        }
+        let deliver_at = state.now + state.latency(from, to);
+        let seq = state.seq;
+        state.seq += 1;
+        state.in_flight.push(Reverse(InFlight {
+            deliver_at,
+            seq,
+            envelope: Envelope { from, to, message },
+        }));
+    }
+}
+
+impl<M> fmt::Debug for SimulatedNetwork<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock();
+        f.debug_struct("SimulatedNetwork")
+            .field("now", &state.now)
+            .field("in_flight", &state.in_flight.len())
+            .field("stats", &state.stats)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes(count: usize) -> Vec<NodeId> {
+        (0..count).map(|_| NodeId::new()).collect()
+    }
+
+    #[test]
+    fn test_messages_arrive_after_latency_in_send_order() {
+        let network = SimulatedNetwork::new(1);
+        let n = nodes(2);
+        network.set_latency(5, 5);
+        network.send(n[0], n[1], 1);
+        network.send(n[0], n[1], 2);
+
+        network.advance(4);
+        assert!(network.recv(n[1]).is_none());
+        network.advance(1);
+        let received: Vec<_> = network.drain(n[1]).into_iter().map(|e| e.message).collect();
+        assert_eq!(received, vec![1, 2]);
+        assert_eq!(network.stats().delivered, 2);
+    }
+
+    #[test]
+    fn test_partition_drops_queued_and_new_messages() {
+        let network = SimulatedNetwork::new(1);
+        let n = nodes(3);
+        network.send(n[0], n[1], "queued");
+        network.partition(&[n[0]], &[n[1], n[2]]);
+        network.send(n[1], n[0], "blocked");
+        network.send(n[1], n[2], "allowed");
+        network.advance(1);
+
+        assert!(network.recv(n[1]).is_none());
+        assert!(network.recv(n[0]).is_none());
+        assert_eq!(network.recv(n[2]).unwrap().message, "allowed");
+
+        network.heal();
+        network.send(n[1], n[0], "healed");
+        network.advance(1);
+        assert_eq!(network.recv(n[0]).unwrap().message, "healed");
+        assert_eq!(network.stats().dropped, 2);
+    }
+
+    #[test]
+    fn test_crash_and_drops_are_reproducible() {
+        let run = |seed| {
+            let network = SimulatedNetwork::new(seed);
+            let n = nodes(2);
+            network.set_latency(1, 20);
+            network.set_drop_rate(0.3);
+            for i in 0..200 {
+                network.send(n[0], n[1], i);
+            }
+            network.advance(20);
+            network.drain(n[1]).into_iter().map(|e| e.message).collect::<Vec<_>>()
+        };
+        let received = run(42);
+        assert_eq!(received, run(42));
+        assert!(received.len() > 100 && received.len() < 180);
+
+        let network = SimulatedNetwork::new(7);
+        let n = nodes(2);
+        network.crash(n[1]);
+        network.send(n[0], n[1], 1);
+        network.advance(1);
+        network.restart(n[1]);
+        assert!(network.recv(n[1]).is_none());
+        assert!(!network.is_crashed(n[1]));
+    }
+}
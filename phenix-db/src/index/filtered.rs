@@ -0,0 +1,427 @@
+//! Filtered vector search
+//!
+//! Combines the graph index with a `MetadataIndex` and chooses, per query,
+//! how the metadata filter meets the ANN search:
+//! - Pre-filter when the filter matches few entities: evaluate it through the
+//!   secondary indexes and rank the matches exactly
+//! - Post-filter when most entities match: over-fetch from the graph and drop
+//!   non-matching hits
+//! - In-graph otherwise: walk the graph admitting only matching nodes
+//!
+//! The choice is driven by `MetadataIndex::estimate_selectivity`.
+//!
+//! Metadata is kept for the latest state only. Searches of an earlier
+//! snapshot (`search_as_of`) walk the graph in-graph and leave filter
+//! evaluation to the caller, which holds the snapshot's metadata.
+
+use crate::core::error::Result;
+use crate::core::metadata::{MetadataFilter, MetadataIndex};
+use crate::core::mvcc::Timestamp;
+use crate::core::query::{FilterStrategy, VectorQuery};
+use crate::core::{DistanceMetric, EntityId, Vector};
+use crate::index::probabilistic_graph::{GraphIndex, GraphIndexConfig};
+use crate::index::simd::SearchHit;
+use serde_json::Value;
+
+/// Selectivity thresholds steering the filter strategy
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FilterConfig {
+    /// Pre-filter when at most this fraction of entities match
+    pub pre_filter_max_selectivity: f64,
+
+    /// Pre-filter when at most this many entities are expected to match,
+    /// regardless of selectivity
+    pub pre_filter_max_candidates: usize,
+
+    /// Post-filter when at least this fraction of entities match
+    pub post_filter_min_selectivity: f64,
+
+    /// Extra over-fetch factor on top of 1 / selectivity for post-filtering
+    pub post_filter_overfetch: f64,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            pre_filter_max_selectivity: 0.01,
+            pre_filter_max_candidates: 1024,
+            post_filter_min_selectivity: 0.5,
+            post_filter_overfetch: 2.0,
+        }
+    }
+}
+
+impl FilterConfig {
+    /// Pick a strategy for a filter with the given selectivity
+    ///
+    /// # Arguments
+    /// * `selectivity` - Estimated fraction of entities matching the filter
+    /// * `total` - Number of indexed entities
+    pub fn choose(&self, selectivity: f64, total: usize) -> FilterStrategy {
+        let expected = selectivity * total as f64;
+        if selectivity <= self.pre_filter_max_selectivity
+            || expected <= self.pre_filter_max_candidates as f64
+        {
+            FilterStrategy::PreFilter
+        } else if selectivity >= self.post_filter_min_selectivity {
+            FilterStrategy::PostFilter
+        } else {
+            FilterStrategy::InGraph
+        }
+    }
+}
+
+/// Hits of a filtered search with the plan that produced them
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilteredSearchResult {
+    /// Hits ordered from closest to farthest
+    pub hits: Vec<SearchHit>,
+
+    /// Strategy that produced the hits (None for unfiltered queries)
+    pub strategy: Option<FilterStrategy>,
+
+    /// Estimated selectivity of the filter (1.0 for unfiltered queries)
+    pub selectivity: f64,
+}
+
+/// Graph index with metadata storage and filter-aware search
+#[derive(Debug, Clone)]
+pub struct FilteredIndex {
+    graph: GraphIndex,
+    metadata: MetadataIndex,
+    config: FilterConfig,
+}
+
+impl FilteredIndex {
+    /// Create an empty filtered index
+    ///
+    /// # Arguments
+    /// * `dimensions` - Dimensionality of every stored vector
+    /// * `metric` - Distance metric used for search
+    /// * `graph_config` - Graph construction and search parameters
+    /// * `metadata` - Metadata index, typically with secondary fields declared
+    pub fn new(
+        dimensions: usize,
+        metric: DistanceMetric,
+        graph_config: GraphIndexConfig,
+        metadata: MetadataIndex,
+    ) -> Self {
+        Self {
+            graph: GraphIndex::new(dimensions, metric, graph_config),
+            metadata,
+            config: FilterConfig::default(),
+        }
+    }
+
+    /// Override the strategy thresholds
+    pub fn with_filter_config(mut self, config: FilterConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Underlying graph index
+    pub fn graph(&self) -> &GraphIndex {
+        &self.graph
+    }
+
+    /// Underlying metadata index
+    pub fn metadata(&self) -> &MetadataIndex {
+        &self.metadata
+    }
+
+    /// Number of indexed entities
+    pub fn len(&self) -> usize {
+        self.graph.len()
+    }
+
+    /// Whether the index is empty
+    pub fn is_empty(&self) -> bool {
+        self.graph.is_empty()
+    }
+
+    /// Insert or replace an entity's vector and metadata
+    ///
+    /// # Errors
+    /// * `Metadata` if the document violates the metadata schema
+    /// * `DimensionMismatch` if the vector dimensionality differs
+    pub fn insert(&mut self, id: EntityId, vector: &Vector, metadata: Option<Value>) -> Result<()> {
+        self.metadata.validate(metadata.as_ref())?;
+        self.graph.insert(id, vector)?;
+        self.metadata.insert(id, metadata)?;
+        Ok(())
+    }
+
+    /// Remove an entity, returning whether it was present
+    pub fn remove(&mut self, id: &EntityId) -> bool {
+        let removed = self.graph.remove(id);
+        self.metadata.remove(id);
+        removed
+    }
+
+    /// Insert or replace an entity as committed at `ts`, keeping a replaced
+    /// vector searchable as of earlier snapshots
+    ///
+    /// # Errors
+    /// * `Metadata` if the document violates the metadata schema
+    /// * `DimensionMismatch` if the vector dimensionality differs
+    pub fn insert_at(&mut self, id: EntityId, vector: &Vector, metadata: Option<Value>, ts: Timestamp) -> Result<()> {
+        self.metadata.validate(metadata.as_ref())?;
+        self.graph.insert_at(id, vector, ts)?;
+        self.metadata.insert(id, metadata)?;
+        Ok(())
+    }
+
+    /// Remove an entity as committed at `ts`, keeping its vector searchable
+    /// as of earlier snapshots; returns whether it was present
+    pub fn remove_at(&mut self, id: &EntityId, ts: Timestamp) -> bool {
+        let removed = self.graph.remove_at(id, ts);
+        self.metadata.remove(id);
+        removed
+    }
+
+    /// Retire superseded vectors that none of the `live` snapshots can see
+    ///
+    /// # Returns
+    /// Number of vectors retired
+    pub fn release(&mut self, live: &[Timestamp]) -> usize {
+        self.graph.release(live)
+    }
+
+    /// Run a k-NN query as of commit `ts`
+    ///
+    /// The query's filter is not evaluated here: `admit` decides, from the
+    /// snapshot's own metadata, which entities may be returned.
+    ///
+    /// # Errors
+    /// * `DimensionMismatch` if the query dimensionality differs
+    /// * `Metadata` if the filter is malformed
+    pub fn search_as_of<F>(&self, query: &VectorQuery, ts: Timestamp, admit: F) -> Result<FilteredSearchResult>
+    where
+        F: Fn(&EntityId) -> bool,
+    {
+        let ef = query.ef.unwrap_or(self.graph.config().ef_search);
+        let (strategy, selectivity) = match &query.filter {
+            Some(filter) => {
+                filter.validate()?;
+                (Some(FilterStrategy::InGraph), self.metadata.estimate_selectivity(filter))
+            }
+            None => (None, 1.0),
+        };
+
+        Ok(FilteredSearchResult {
+            hits: self.graph.search_as_of(&query.vector, query.k, ef, ts, admit)?,
+            strategy,
+            selectivity,
+        })
+    }
+
+    /// Run a k-NN query, applying its filter with the cheapest strategy
+    ///
+    /// # Errors
+    /// * `DimensionMismatch` if the query dimensionality differs
+    /// * `Metadata` if the filter is malformed
+    pub fn search(&self, query: &VectorQuery) -> Result<FilteredSearchResult> {
+        let ef = query.ef.unwrap_or(self.graph.config().ef_search);
+
+        let Some(filter) = &query.filter else {
+            return Ok(FilteredSearchResult {
+                hits: self.graph.search_with_ef(&query.vector, query.k, ef)?,
+                strategy: None,
+                selectivity: 1.0,
+            });
+        };
+
+        filter.validate()?;
+        let selectivity = self.metadata.estimate_selectivity(filter);
+        let strategy = query
+            .strategy
+            .unwrap_or_else(|| self.config.choose(selectivity, self.len()));
+
+        let (hits, strategy) = match strategy {
+            FilterStrategy::PreFilter => (self.pre_filter(query, filter)?, strategy),
+            FilterStrategy::InGraph => (self.in_graph(query, filter, ef)?, strategy),
+            FilterStrategy::PostFilter => match self.post_filter(query, filter, selectivity, ef)? {
+                Some(hits) => (hits, strategy),
+                // Over-fetch came up short: the estimate was too optimistic
+                None => (self.in_graph(query, filter, ef)?, FilterStrategy::InGraph),
+            },
+        };
+
+        Ok(FilteredSearchResult {
+            hits,
+            strategy: Some(strategy),
+            selectivity,
+        })
+    }
+
+    fn pre_filter(&self, query: &VectorQuery, filter: &MetadataFilter) -> Result<Vec<SearchHit>> {
+        let candidates = self.metadata.matching_ids(filter)?;
+        self.graph.rerank(&query.vector, &candidates, query.k)
+    }
+
+    fn in_graph(&self, query: &VectorQuery, filter: &MetadataFilter, ef: usize) -> Result<Vec<SearchHit>> {
+        let matching = self.metadata.evaluate(filter)?;
+        self.graph.search_filtered(&query.vector, query.k, ef, |id| {
+            self.metadata
+                .slot(id)
+                .is_some_and(|slot| matching.contains(slot))
+        })
+    }
+
+    /// Over-fetch and filter; None if fewer than k matches survived
+    fn post_filter(
+        &self,
+        query: &VectorQuery,
+        filter: &MetadataFilter,
+        selectivity: f64,
+        ef: usize,
+    ) -> Result<Option<Vec<SearchHit>>> {
+        let scale = self.config.post_filter_overfetch / selectivity.max(f64::EPSILON);
+        let fetch = ((query.k as f64 * scale).ceil() as usize).clamp(query.k, self.len().max(query.k));
+
+        let mut hits = self.graph.search_with_ef(&query.vector, fetch, ef.max(fetch))?;
+        hits.retain(|hit| self.metadata.matches(&hit.id, filter));
+
+        if hits.len() < query.k && fetch < self.len() {
+            return Ok(None);
+        }
+        hits.truncate(query.k);
+        Ok(Some(hits))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::metadata::{FieldIndexKind, FieldPath};
+    use crate::core::MemorySubstrateError;
+    use crate::index::test_util::random_vectors;
+    use crate::index::FlatIndex;
+    use serde_json::json;
+    use std::collections::HashSet;
+
+    fn path(s: &str) -> FieldPath {
+        FieldPath::parse(s).unwrap()
+    }
+
+    /// 2000 entities: lang cycles over 4 values, year over 2000..2040,
+    /// and every 250th entity is flagged `rare`
+    fn build() -> (FilteredIndex, FlatIndex, Vec<(EntityId, Value)>) {
+        let metadata = MetadataIndex::new()
+            .with_field(path("lang"), FieldIndexKind::Keyword)
+            .with_field(path("year"), FieldIndexKind::Numeric);
+        let graph_config = GraphIndexConfig {
+            m: 12,
+            ef_construction: 48,
+            ..GraphIndexConfig::default()
+        };
+        let mut index = FilteredIndex::new(12, DistanceMetric::Euclidean, graph_config, metadata);
+        let mut flat = FlatIndex::new(12, DistanceMetric::Euclidean);
+        let mut docs = Vec::new();
+
+        let langs = ["en", "de", "fr", "ja"];
+        for (i, vector) in random_vectors(2000, 12, 17).into_iter().enumerate() {
+            let id = EntityId::new();
+            let doc = json!({"lang": langs[i % 4], "year": 2000 + (i % 40), "rare": i % 250 == 0});
+            index.insert(id, &vector, Some(doc.clone())).unwrap();
+            flat.insert(id, &vector).unwrap();
+            docs.push((id, doc));
+        }
+        (index, flat, docs)
+    }
+
+    fn exact(flat: &FlatIndex, docs: &[(EntityId, Value)], filter: &MetadataFilter, query: &Vector, k: usize) -> Vec<SearchHit> {
+        let allowed: HashSet<EntityId> = docs
+            .iter()
+            .filter(|(_, doc)| filter.matches(Some(doc)))
+            .map(|(id, _)| *id)
+            .collect();
+        flat.search_filtered(query, k, |id| allowed.contains(id)).unwrap()
+    }
+
+    fn recall(hits: &[SearchHit], truth: &[SearchHit]) -> f64 {
+        let truth_ids: HashSet<EntityId> = truth.iter().map(|h| h.id).collect();
+        hits.iter().filter(|h| truth_ids.contains(&h.id)).count() as f64 / truth.len() as f64
+    }
+
+    #[test]
+    fn test_strategy_choice_by_selectivity() {
+        let config = FilterConfig::default();
+        assert_eq!(config.choose(0.005, 1_000_000), FilterStrategy::PreFilter);
+        assert_eq!(config.choose(0.2, 1000), FilterStrategy::PreFilter);
+        assert_eq!(config.choose(0.2, 1_000_000), FilterStrategy::InGraph);
+        assert_eq!(config.choose(0.8, 1_000_000), FilterStrategy::PostFilter);
+    }
+
+    #[test]
+    fn test_filtered_search_strategies() {
+        let (index, flat, docs) = build();
+        let config = FilterConfig {
+            pre_filter_max_candidates: 50,
+            ..FilterConfig::default()
+        };
+        let index = index.with_filter_config(config);
+        let queries = random_vectors(5, 12, 23);
+
+        let cases = [
+            (MetadataFilter::eq(path("rare"), true), FilterStrategy::PreFilter),
+            (
+                MetadataFilter::eq(path("lang"), "en").and(MetadataFilter::gte(path("year"), 2030)),
+                FilterStrategy::InGraph,
+            ),
+            (MetadataFilter::ne(path("lang"), "ja"), FilterStrategy::PostFilter),
+        ];
+
+        for (filter, expected_strategy) in cases {
+            let mut total = 0.0;
+            for vector in &queries {
+                let query = VectorQuery::new(vector.clone(), 10).with_filter(filter.clone());
+                let result = index.search(&query).unwrap();
+                assert_eq!(result.strategy, Some(expected_strategy));
+                assert!(result.hits.iter().all(|h| index.metadata().matches(&h.id, &filter)));
+
+                let truth = exact(&flat, &docs, &filter, vector, 10);
+                assert_eq!(result.hits.len(), truth.len());
+                total += recall(&result.hits, &truth);
+            }
+            assert!(total / queries.len() as f64 >= 0.85, "{:?}", expected_strategy);
+        }
+    }
+
+    #[test]
+    fn test_forced_strategy_and_unfiltered_query() {
+        let (index, flat, docs) = build();
+        let vector = &random_vectors(1, 12, 31)[0];
+        let filter = MetadataFilter::in_values(path("lang"), ["fr", "de"]);
+
+        let pre = index
+            .search(&VectorQuery::new(vector.clone(), 5).with_filter(filter.clone()).with_strategy(FilterStrategy::PreFilter))
+            .unwrap();
+        assert_eq!(pre.strategy, Some(FilterStrategy::PreFilter));
+        assert_eq!(pre.hits, exact(&flat, &docs, &filter, vector, 5));
+        assert!((pre.selectivity - 0.5).abs() < 1e-9);
+
+        let plain = index.search(&VectorQuery::new(vector.clone(), 5)).unwrap();
+        assert_eq!(plain.strategy, None);
+        assert_eq!(plain.hits.len(), 5);
+    }
+
+    #[test]
+    fn test_remove_and_invalid_filter() {
+        let (mut index, _, docs) = build();
+        let (rare_id, _) = docs[0];
+        let filter = MetadataFilter::eq(path("rare"), true);
+        let vector = Vector::new(index.graph().get(&rare_id).unwrap().to_vec());
+
+        assert!(index.remove(&rare_id));
+        let result = index.search(&VectorQuery::new(vector.clone(), 10).with_filter(filter)).unwrap();
+        assert_eq!(result.hits.len(), 7);
+        assert!(result.hits.iter().all(|h| h.id != rare_id));
+
+        let bad = MetadataFilter::range(path("year"), None, None);
+        assert!(matches!(
+            index.search(&VectorQuery::new(vector, 1).with_filter(bad)),
+            Err(MemorySubstrateError::Metadata { .. })
+        ));
+    }
+}
@@ -1,6 +1,21 @@
 // Indexing and search
 //
 // This module will be fully implemented in Phases 3-4.
-// Placeholder for now to allow compilation.
+// Implemented so far:
+// - SIMD: Distance kernels and the exact brute-force FlatIndex
+// - Polynomial tree: Bounding-polynomial tree backing the RPI
+// - Probabilistic graph: Navigable small-world graph for ANN search
+// - Filtered: Metadata-filtered search with selectivity-based strategy
 
-// Placeholder - will be implemented in Phases 3-4
+pub mod simd;
+pub mod polynomial_tree;
+pub mod probabilistic_graph;
+pub mod filtered;
+
+#[cfg(test)]
+mod test_util;
+
+pub use simd::{FlatIndex, SearchHit};
+pub use polynomial_tree::PolynomialTree;
+pub use probabilistic_graph::{GraphIndex, GraphIndexConfig};
+pub use filtered::{FilterConfig, FilteredIndex, FilteredSearchResult};
@@ -1 +1,1318 @@
-// TODO: Implement polynomial tree
+//! Recursive polynomial tree backing the RPI
+//!
+//! Leaves hold entity polynomial embeddings. Every node carries a
+//! `PolynomialBounds` summary: per-coefficient intervals that enclose the
+//! polynomials of its whole subtree. Evaluating the interval polynomial at a
+//! point yields a range guaranteed to contain the value of every polynomial
+//! below the node, so subtrees whose range misses a query are pruned without
+//! being visited (Al-Karaji recursive descent with interval pruning).
+//!
+//! Trees are bulk loaded bottom-up from a stream sorted by `sort_key`, and
+//! serialize to fixed-size pages (one node per page, page 0 is the header) so
+//! a warm-tier file can be memory-mapped and handed to `from_bytes` directly.
+
+use crate::core::config::PolynomialConfig;
+use crate::core::error::{PolynomialError, PolynomialResult};
+use crate::core::EntityId;
+use crate::mathematical::polynomial::PolynomialEmbedding;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+
+/// Size of every serialized page in bytes
+pub const PAGE_SIZE: usize = 4096;
+
+/// Evaluation point used to order entries for bulk loading
+///
+/// P(1) is the sum of coefficients, which keeps polynomials with similar
+/// magnitudes in neighbouring leaves and tightens node bounds.
+pub const SORT_PIVOT: f64 = 1.0;
+
+const MAGIC: &[u8; 8] = b"PHXPTREE";
+const FORMAT_VERSION: u32 = 1;
+const NODE_HEADER_SIZE: usize = 24;
+const KIND_LEAF: u8 = 1;
+const KIND_INTERNAL: u8 = 2;
+const NO_ROOT: u32 = u32::MAX;
+
+/// Sort key of an embedding for bulk loading
+pub fn sort_key(embedding: &PolynomialEmbedding) -> f64 {
+    embedding.evaluate(SORT_PIVOT)
+}
+
+/// Sort embeddings into bulk-load order
+pub fn sort_for_bulk_load(embeddings: &mut [PolynomialEmbedding]) {
+    embeddings.sort_by(|a, b| sort_key(a).total_cmp(&sort_key(b)));
+}
+
+/// Per-coefficient interval bounds over a set of polynomials
+///
+/// For every coefficient index i: lower[i] <= a_i <= upper[i] holds for all
+/// polynomials summarized by the bounds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolynomialBounds {
+    /// Lower bound of each coefficient
+    pub lower: Vec<f64>,
+
+    /// Upper bound of each coefficient
+    pub upper: Vec<f64>,
+}
+
+impl PolynomialBounds {
+    /// Bounds that enclose exactly one polynomial
+    ///
+    /// Coefficients are padded with zeros up to `degree + 1` terms.
+    pub fn from_coefficients(coefficients: &[f64], degree: usize) -> Self {
+        let mut lower = vec![0.0; degree + 1];
+        lower[..coefficients.len()].copy_from_slice(coefficients);
+        Self {
+            upper: lower.clone(),
+            lower,
+        }
+    }
+
+    /// Widen these bounds to also enclose `other`
+    pub fn union(&mut self, other: &PolynomialBounds) {
+        for (lo, &other_lo) in self.lower.iter_mut().zip(&other.lower) {
+            *lo = lo.min(other_lo);
+        }
+        for (hi, &other_hi) in self.upper.iter_mut().zip(&other.upper) {
+            *hi = hi.max(other_hi);
+        }
+    }
+
+    /// Whether these bounds enclose `other` within `tolerance`
+    pub fn contains(&self, other: &PolynomialBounds, tolerance: f64) -> bool {
+        self.lower.len() == other.lower.len()
+            && self
+                .lower
+                .iter()
+                .zip(&other.lower)
+                .all(|(&lo, &other_lo)| lo <= other_lo + tolerance)
+            && self
+                .upper
+                .iter()
+                .zip(&other.upper)
+                .all(|(&hi, &other_hi)| hi >= other_hi - tolerance)
+    }
+
+    /// Interval evaluation at `x`
+    ///
+    /// Returns (min, max) such that P(x) lies in [min, max] for every enclosed
+    /// polynomial P. Negative powers of x swap the role of the bounds.
+    pub fn evaluate(&self, x: f64) -> (f64, f64) {
+        let mut power = 1.0;
+        let mut min = 0.0;
+        let mut max = 0.0;
+
+        for (&lo, &hi) in self.lower.iter().zip(&self.upper) {
+            if power >= 0.0 {
+                min += lo * power;
+                max += hi * power;
+            } else {
+                min += hi * power;
+                max += lo * power;
+            }
+            power *= x;
+        }
+
+        (min, max)
+    }
+}
+
+/// Traversal counters reported by tree queries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueryStats {
+    /// Nodes whose bounds were evaluated
+    pub nodes_visited: usize,
+
+    /// Subtrees skipped because their bounds missed the query
+    pub nodes_pruned: usize,
+
+    /// Leaf entries evaluated exactly
+    pub entries_evaluated: usize,
+}
+
+/// Structural parameters of a tree, derived from `PolynomialConfig`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TreeParams {
+    /// Maximum polynomial degree of stored embeddings
+    pub degree: usize,
+
+    /// Maximum children per internal node
+    pub branching_factor: usize,
+
+    /// Maximum entries per leaf
+    pub leaf_capacity: usize,
+
+    /// Maximum tree height (levels including leaves)
+    pub max_depth: usize,
+
+    /// Slack applied to bounds when pruning, absorbing rounding error
+    pub precision_tolerance: f64,
+}
+
+impl TreeParams {
+    /// Derive tree parameters from the polynomial index configuration
+    pub fn from_config(config: &PolynomialConfig) -> Self {
+        Self {
+            degree: config.degree,
+            branching_factor: config.branching_factor,
+            leaf_capacity: config.node_capacity.max(1),
+            max_depth: config.max_depth,
+            precision_tolerance: config.precision_tolerance,
+        }
+    }
+
+    fn bounds_bytes(&self) -> usize {
+        2 * 8 * (self.degree + 1) + 16
+    }
+
+    fn check_page_fit(&self) -> PolynomialResult<()> {
+        let internal = NODE_HEADER_SIZE + self.bounds_bytes() + 4 * self.branching_factor;
+        if internal > PAGE_SIZE {
+            return Err(PolynomialError::InvalidStructure {
+                reason: format!(
+                    "internal node of degree {} with branching factor {} needs {} bytes, page is {}",
+                    self.degree, self.branching_factor, internal, PAGE_SIZE
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum NodeKind {
+    Leaf(Vec<PolynomialEmbedding>),
+    Internal(Vec<u32>),
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    level: u32,
+    bounds: PolynomialBounds,
+    min_key: f64,
+    max_key: f64,
+    kind: NodeKind,
+}
+
+impl Node {
+    fn len(&self) -> usize {
+        match &self.kind {
+            NodeKind::Leaf(entries) => entries.len(),
+            NodeKind::Internal(children) => children.len(),
+        }
+    }
+}
+
+/// Bounding-polynomial search tree over entity embeddings
+///
+/// Nodes live in an arena indexed by `u32`; node `n` is serialized to page
+/// `n + 1`. All leaves sit at level 0 and every internal node is exactly one
+/// level above its children.
+#[derive(Debug, Clone)]
+pub struct PolynomialTree {
+    params: TreeParams,
+    nodes: Vec<Node>,
+    root: Option<u32>,
+    entry_count: u64,
+}
+
+impl PolynomialTree {
+    /// Create an empty tree
+    pub fn new(config: &PolynomialConfig) -> PolynomialResult<Self> {
+        let params = TreeParams::from_config(config);
+        params.check_page_fit()?;
+
+        Ok(Self {
+            params,
+            nodes: Vec::new(),
+            root: None,
+            entry_count: 0,
+        })
+    }
+
+    /// Bulk load a tree bottom-up from embeddings sorted by `sort_key`
+    ///
+    /// Leaves are filled to `node_capacity` (or as many entries as fit in one
+    /// page), then parents are built level by level with at most
+    /// `branching_factor` children each.
+    ///
+    /// # Errors
+    /// * `InvalidStructure` if the stream is not sorted or the tree would
+    ///   exceed `max_depth`
+    /// * `DegreeExceeded` if an embedding has more than `degree + 1` terms
+    /// * `NumericalInstability` if a sort key is not finite
+    pub fn bulk_load<I>(config: &PolynomialConfig, embeddings: I) -> PolynomialResult<Self>
+    where
+        I: IntoIterator<Item = PolynomialEmbedding>,
+    {
+        let mut tree = Self::new(config)?;
+        let params = tree.params;
+
+        let mut leaf: Vec<PolynomialEmbedding> = Vec::new();
+        let mut leaf_bytes = NODE_HEADER_SIZE + params.bounds_bytes();
+        let mut previous_key = f64::NEG_INFINITY;
+        let mut level: Vec<u32> = Vec::new();
+
+        for embedding in embeddings {
+            if embedding.coefficients.len() > params.degree + 1 {
+                return Err(PolynomialError::DegreeExceeded {
+                    degree: embedding.coefficients.len() - 1,
+                    max_degree: params.degree,
+                });
+            }
+
+            let key = sort_key(&embedding);
+            if !key.is_finite() {
+                return Err(PolynomialError::NumericalInstability {
+                    reason: format!("non-finite sort key for entity {}", embedding.entity_id),
+                });
+            }
+            if key < previous_key {
+                return Err(PolynomialError::InvalidStructure {
+                    reason: format!(
+                        "bulk load input not sorted: key {} follows {}",
+                        key, previous_key
+                    ),
+                });
+            }
+            previous_key = key;
+
+            let size = entry_size(&embedding);
+            if NODE_HEADER_SIZE + params.bounds_bytes() + size > PAGE_SIZE {
+                return Err(PolynomialError::InvalidStructure {
+                    reason: format!(
+                        "entry for entity {} needs {} bytes and cannot fit in a page",
+                        embedding.entity_id, size
+                    ),
+                });
+            }
+
+            if !leaf.is_empty()
+                && (leaf.len() >= params.leaf_capacity || leaf_bytes + size > PAGE_SIZE)
+            {
+                level.push(tree.push_leaf(std::mem::take(&mut leaf)));
+                leaf_bytes = NODE_HEADER_SIZE + params.bounds_bytes();
+            }
+
+            leaf_bytes += size;
+            leaf.push(embedding);
+            tree.entry_count += 1;
+        }
+
+        if !leaf.is_empty() {
+            level.push(tree.push_leaf(leaf));
+        }
+
+        let mut height = usize::from(!level.is_empty());
+        while level.len() > 1 {
+            height += 1;
+            if height > params.max_depth {
+                return Err(PolynomialError::InvalidStructure {
+                    reason: format!("tree height exceeds max depth {}", params.max_depth),
+                });
+            }
+
+            // Spread children evenly so the last parent is not left nearly empty
+            let groups = level.len().div_ceil(params.branching_factor);
+            let base = level.len() / groups;
+            let extra = level.len() % groups;
+            let mut parents = Vec::with_capacity(groups);
+            let mut start = 0;
+
+            for g in 0..groups {
+                let size = base + usize::from(g < extra);
+                parents.push(tree.push_internal(level[start..start + size].to_vec()));
+                start += size;
+            }
+            level = parents;
+        }
+
+        tree.root = level.first().copied();
+        Ok(tree)
+    }
+
+    fn push_leaf(&mut self, entries: Vec<PolynomialEmbedding>) -> u32 {
+        let degree = self.params.degree;
+        let mut bounds = PolynomialBounds::from_coefficients(&entries[0].coefficients, degree);
+        for entry in &entries[1..] {
+            bounds.union(&PolynomialBounds::from_coefficients(&entry.coefficients, degree));
+        }
+
+        let node = Node {
+            level: 0,
+            bounds,
+            min_key: sort_key(&entries[0]),
+            max_key: sort_key(&entries[entries.len() - 1]),
+            kind: NodeKind::Leaf(entries),
+        };
+        self.nodes.push(node);
+        (self.nodes.len() - 1) as u32
+    }
+
+    fn push_internal(&mut self, children: Vec<u32>) -> u32 {
+        let first = &self.nodes[children[0] as usize];
+        let mut bounds = first.bounds.clone();
+        let level = first.level + 1;
+        let min_key = first.min_key;
+        for &child in &children[1..] {
+            bounds.union(&self.nodes[child as usize].bounds);
+        }
+        let max_key = self.nodes[children[children.len() - 1] as usize].max_key;
+
+        self.nodes.push(Node {
+            level,
+            bounds,
+            min_key,
+            max_key,
+            kind: NodeKind::Internal(children),
+        });
+        (self.nodes.len() - 1) as u32
+    }
+
+    /// Structural parameters of the tree
+    pub fn params(&self) -> &TreeParams {
+        &self.params
+    }
+
+    /// Number of stored embeddings
+    pub fn len(&self) -> usize {
+        self.entry_count as usize
+    }
+
+    /// Whether the tree holds no embeddings
+    pub fn is_empty(&self) -> bool {
+        self.entry_count == 0
+    }
+
+    /// Number of nodes (and data pages)
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Number of levels, including the leaf level
+    pub fn height(&self) -> usize {
+        self.root
+            .map_or(0, |root| self.nodes[root as usize].level as usize + 1)
+    }
+
+    /// Iterate over stored embeddings in sort-key order
+    pub fn iter(&self) -> impl Iterator<Item = &PolynomialEmbedding> {
+        let mut leaves = Vec::new();
+        if let Some(root) = self.root {
+            self.collect_leaves(root, &mut leaves);
+        }
+        leaves.into_iter().flat_map(|leaf| match &self.nodes[leaf as usize].kind {
+            NodeKind::Leaf(entries) => entries.iter(),
+            NodeKind::Internal(_) => [].iter(),
+        })
+    }
+
+    fn collect_leaves(&self, node: u32, out: &mut Vec<u32>) {
+        match &self.nodes[node as usize].kind {
+            NodeKind::Leaf(_) => out.push(node),
+            NodeKind::Internal(children) => {
+                for &child in children {
+                    self.collect_leaves(child, out);
+                }
+            }
+        }
+    }
+
+    /// Find all entities whose polynomial evaluates into [lower, upper] at x
+    pub fn range_query(&self, x: f64, lower: f64, upper: f64) -> Vec<EntityId> {
+        self.range_query_with_stats(x, lower, upper).0
+    }
+
+    /// `range_query` that also reports how much of the tree was pruned
+    pub fn range_query_with_stats(
+        &self,
+        x: f64,
+        lower: f64,
+        upper: f64,
+    ) -> (Vec<EntityId>, QueryStats) {
+        let mut results = Vec::new();
+        let mut stats = QueryStats::default();
+        let mut stack: Vec<u32> = self.root.into_iter().collect();
+
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id as usize];
+            stats.nodes_visited += 1;
+
+            let (min, max) = self.widen(node.bounds.evaluate(x));
+            if max < lower || min > upper {
+                stats.nodes_pruned += 1;
+                continue;
+            }
+
+            match &node.kind {
+                NodeKind::Internal(children) => stack.extend(children.iter().rev()),
+                NodeKind::Leaf(entries) => {
+                    for entry in entries {
+                        stats.entries_evaluated += 1;
+                        let value = entry.evaluate(x);
+                        if value >= lower && value <= upper {
+                            results.push(entry.entity_id);
+                        }
+                    }
+                }
+            }
+        }
+
+        (results, stats)
+    }
+
+    /// Find the `k` entities whose polynomial value at x is closest to `target`
+    ///
+    /// Best-first branch and bound: nodes are expanded in order of the lower
+    /// bound |target - [min, max]| and the search stops once that bound exceeds
+    /// the current k-th best distance.
+    ///
+    /// # Returns
+    /// * (entity, |P(x) - target|) pairs ordered from closest to farthest
+    pub fn nearest(&self, x: f64, target: f64, k: usize) -> Vec<(EntityId, f64)> {
+        self.nearest_with_stats(x, target, k).0
+    }
+
+    /// `nearest` that also reports how much of the tree was pruned
+    pub fn nearest_with_stats(
+        &self,
+        x: f64,
+        target: f64,
+        k: usize,
+    ) -> (Vec<(EntityId, f64)>, QueryStats) {
+        let mut stats = QueryStats::default();
+        if k == 0 {
+            return (Vec::new(), stats);
+        }
+
+        let mut frontier: BinaryHeap<Candidate> = BinaryHeap::new();
+        let mut best: BinaryHeap<Candidate> = BinaryHeap::new();
+        if let Some(root) = self.root {
+            frontier.push(Candidate::min_first(0.0, root as u64));
+        }
+
+        let mut entities: Vec<EntityId> = Vec::new();
+        while let Some(candidate) = frontier.pop() {
+            let bound = candidate.distance;
+            if best.len() == k && best.peek().is_some_and(|worst| bound > worst.distance) {
+                stats.nodes_pruned += frontier.len() + 1;
+                break;
+            }
+
+            let node = &self.nodes[candidate.payload as usize];
+            stats.nodes_visited += 1;
+            match &node.kind {
+                NodeKind::Internal(children) => {
+                    for &child in children {
+                        let (min, max) = self.widen(self.nodes[child as usize].bounds.evaluate(x));
+                        let lower_bound = if target < min {
+                            min - target
+                        } else if target > max {
+                            target - max
+                        } else {
+                            0.0
+                        };
+                        frontier.push(Candidate::min_first(lower_bound, child as u64));
+                    }
+                }
+                NodeKind::Leaf(entries) => {
+                    for entry in entries {
+                        stats.entries_evaluated += 1;
+                        let distance = (entry.evaluate(x) - target).abs();
+                        if best.len() < k {
+                            entities.push(entry.entity_id);
+                            best.push(Candidate::max_first(distance, (entities.len() - 1) as u64));
+                        } else if best.peek().is_some_and(|worst| distance < worst.distance) {
+                            best.pop();
+                            entities.push(entry.entity_id);
+                            best.push(Candidate::max_first(distance, (entities.len() - 1) as u64));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut results: Vec<(EntityId, f64)> = best
+            .into_iter()
+            .map(|c| (entities[c.payload as usize], c.distance))
+            .collect();
+        results.sort_by(|a, b| a.1.total_cmp(&b.1));
+        (results, stats)
+    }
+
+    fn widen(&self, (min, max): (f64, f64)) -> (f64, f64) {
+        let tol = self.params.precision_tolerance;
+        (min - tol * (1.0 + min.abs()), max + tol * (1.0 + max.abs()))
+    }
+
+    /// Check every structural invariant of the tree
+    ///
+    /// Verifies node reachability (no sharing or cycles), level ordering,
+    /// fan-out and page limits, sort-key ordering, entry counts, depth, and
+    /// that each node's bounds enclose its children.
+    ///
+    /// # Errors
+    /// * `InvalidStructure` describing the first violated invariant
+    pub fn validate(&self) -> PolynomialResult<()> {
+        let invalid = |reason: String| Err(PolynomialError::InvalidStructure { reason });
+
+        let Some(root) = self.root else {
+            if !self.nodes.is_empty() || self.entry_count != 0 {
+                return invalid("tree without root has nodes or entries".to_string());
+            }
+            return Ok(());
+        };
+
+        if root as usize >= self.nodes.len() {
+            return invalid(format!("root {} out of range", root));
+        }
+        if self.height() > self.params.max_depth {
+            return invalid(format!(
+                "height {} exceeds max depth {}",
+                self.height(),
+                self.params.max_depth
+            ));
+        }
+
+        let degree = self.params.degree;
+        let tol = self.params.precision_tolerance;
+        let mut seen: HashSet<u32> = HashSet::new();
+        let mut entries_seen: u64 = 0;
+        let mut previous_key = f64::NEG_INFINITY;
+        let mut stack = vec![root];
+
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id) {
+                return invalid(format!("node {} reachable more than once", id));
+            }
+            let node = &self.nodes[id as usize];
+
+            if node.len() == 0 {
+                return invalid(format!("node {} is empty", id));
+            }
+            if node.bounds.lower.len() != degree + 1 || node.bounds.upper.len() != degree + 1 {
+                return invalid(format!("node {} bounds do not have {} terms", id, degree + 1));
+            }
+            if node.min_key > node.max_key {
+                return invalid(format!("node {} key range is inverted", id));
+            }
+            if self.node_bytes(node) > PAGE_SIZE {
+                return invalid(format!("node {} does not fit in a page", id));
+            }
+
+            match &node.kind {
+                NodeKind::Internal(children) => {
+                    if node.level == 0 {
+                        return invalid(format!("internal node {} at leaf level", id));
+                    }
+                    if children.len() > self.params.branching_factor {
+                        return invalid(format!(
+                            "node {} has {} children, branching factor is {}",
+                            id,
+                            children.len(),
+                            self.params.branching_factor
+                        ));
+                    }
+                    for &child in children {
+                        let Some(child_node) = self.nodes.get(child as usize) else {
+                            return invalid(format!("node {} references missing child {}", id, child));
+                        };
+                        if child_node.level + 1 != node.level {
+                            return invalid(format!(
+                                "child {} at level {} under node {} at level {}",
+                                child, child_node.level, id, node.level
+                            ));
+                        }
+                        if !node.bounds.contains(&child_node.bounds, tol) {
+                            return invalid(format!(
+                                "bounds of node {} do not enclose child {}",
+                                id, child
+                            ));
+                        }
+                        if child_node.min_key < node.min_key || child_node.max_key > node.max_key {
+                            return invalid(format!(
+                                "key range of child {} escapes node {}",
+                                child, id
+                            ));
+                        }
+                    }
+                    stack.extend(children.iter().rev());
+                }
+                NodeKind::Leaf(entries) => {
+                    if node.level != 0 {
+                        return invalid(format!("leaf {} at level {}", id, node.level));
+                    }
+                    if entries.len() > self.params.leaf_capacity {
+                        return invalid(format!(
+                            "leaf {} holds {} entries, capacity is {}",
+                            id,
+                            entries.len(),
+                            self.params.leaf_capacity
+                        ));
+                    }
+                    for entry in entries {
+                        if entry.coefficients.len() > degree + 1 {
+                            return invalid(format!(
+                                "entity {} has degree {} above tree degree {}",
+                                entry.entity_id,
+                                entry.coefficients.len() - 1,
+                                degree
+                            ));
+                        }
+                        let bounds = PolynomialBounds::from_coefficients(&entry.coefficients, degree);
+                        if !node.bounds.contains(&bounds, tol) {
+                            return invalid(format!(
+                                "bounds of leaf {} do not enclose entity {}",
+                                id, entry.entity_id
+                            ));
+                        }
+                        let key = sort_key(entry);
+                        if key < previous_key || key < node.min_key || key > node.max_key {
+                            return invalid(format!(
+                                "entity {} is out of sort-key order",
+                                entry.entity_id
+                            ));
+                        }
+                        previous_key = key;
+                    }
+                    entries_seen += entries.len() as u64;
+                }
+            }
+        }
+
+        if seen.len() != self.nodes.len() {
+            return invalid(format!(
+                "{} of {} nodes unreachable from root",
+                self.nodes.len() - seen.len(),
+                self.nodes.len()
+            ));
+        }
+        if entries_seen != self.entry_count {
+            return invalid(format!(
+                "found {} entries, header records {}",
+                entries_seen, self.entry_count
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn node_bytes(&self, node: &Node) -> usize {
+        NODE_HEADER_SIZE
+            + self.params.bounds_bytes()
+            + match &node.kind {
+                NodeKind::Leaf(entries) => entries.iter().map(entry_size).sum(),
+                NodeKind::Internal(children) => 4 * children.len(),
+            }
+    }
+
+    /// Serialize the tree to page-aligned bytes
+    ///
+    /// Layout (little endian):
+    /// - Page 0: header (magic, version, parameters, root page, counts)
+    /// - Page n + 1: node n, prefixed by kind, count, level, payload length
+    ///   and a BLAKE3-derived payload checksum
+    ///
+    /// The result length is always a multiple of `PAGE_SIZE`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity((self.nodes.len() + 1) * PAGE_SIZE);
+
+        let mut header = Vec::with_capacity(PAGE_SIZE);
+        header.extend_from_slice(MAGIC);
+        put_u32(&mut header, FORMAT_VERSION);
+        put_u32(&mut header, PAGE_SIZE as u32);
+        put_u32(&mut header, self.params.degree as u32);
+        put_u32(&mut header, self.params.branching_factor as u32);
+        put_u32(&mut header, self.params.leaf_capacity as u32);
+        put_u32(&mut header, self.params.max_depth as u32);
+        put_f64(&mut header, self.params.precision_tolerance);
+        put_u32(&mut header, self.nodes.len() as u32);
+        put_u32(&mut header, self.root.map_or(NO_ROOT, |root| root + 1));
+        put_u64(&mut header, self.entry_count);
+        let header_checksum = checksum(&header);
+        put_u64(&mut header, header_checksum);
+        header.resize(PAGE_SIZE, 0);
+        out.extend_from_slice(&header);
+
+        for node in &self.nodes {
+            let mut payload = Vec::with_capacity(PAGE_SIZE);
+            for &lo in &node.bounds.lower {
+                put_f64(&mut payload, lo);
+            }
+            for &hi in &node.bounds.upper {
+                put_f64(&mut payload, hi);
+            }
+            put_f64(&mut payload, node.min_key);
+            put_f64(&mut payload, node.max_key);
+
+            let kind = match &node.kind {
+                NodeKind::Internal(children) => {
+                    for &child in children {
+                        put_u32(&mut payload, child + 1);
+                    }
+                    KIND_INTERNAL
+                }
+                NodeKind::Leaf(entries) => {
+                    for entry in entries {
+                        payload.extend_from_slice(entry.entity_id.as_uuid().as_bytes());
+                        put_u64(&mut payload, entry.metadata_hash);
+                        put_u16(&mut payload, entry.degree as u16);
+                        put_u16(&mut payload, entry.coefficients.len() as u16);
+                        for &c in &entry.coefficients {
+                            put_f64(&mut payload, c);
+                        }
+                        put_u16(&mut payload, entry.edge_signature.len() as u16);
+                        payload.extend_from_slice(&entry.edge_signature);
+                    }
+                    KIND_LEAF
+                }
+            };
+
+            let start = out.len();
+            out.push(kind);
+            out.push(0);
+            put_u16(&mut out, node.len() as u16);
+            put_u32(&mut out, node.level);
+            put_u32(&mut out, payload.len() as u32);
+            put_u32(&mut out, 0);
+            put_u64(&mut out, checksum(&payload));
+            out.extend_from_slice(&payload);
+            out.resize(start + PAGE_SIZE, 0);
+        }
+
+        out
+    }
+
+    /// Load a tree from page-aligned bytes produced by `to_bytes`
+    ///
+    /// Accepts any byte slice, including a memory-mapped warm-tier file.
+    /// Checksums are verified for every page and the decoded tree is run
+    /// through `validate`.
+    ///
+    /// # Errors
+    /// * `InvalidStructure` on truncation, corruption or invariant violations
+    pub fn from_bytes(bytes: &[u8]) -> PolynomialResult<Self> {
+        if bytes.len() < PAGE_SIZE || !bytes.len().is_multiple_of(PAGE_SIZE) {
+            return Err(PolynomialError::InvalidStructure {
+                reason: format!("{} bytes is not a whole number of pages", bytes.len()),
+            });
+        }
+
+        let mut header = PageReader::new(&bytes[..PAGE_SIZE]);
+        if header.take(8)? != MAGIC {
+            return Err(PolynomialError::InvalidStructure {
+                reason: "bad magic in header page".to_string(),
+            });
+        }
+        let version = header.u32()?;
+        if version != FORMAT_VERSION {
+            return Err(PolynomialError::InvalidStructure {
+                reason: format!("unsupported format version {}", version),
+            });
+        }
+        let page_size = header.u32()? as usize;
+        if page_size != PAGE_SIZE {
+            return Err(PolynomialError::InvalidStructure {
+                reason: format!("page size {} does not match {}", page_size, PAGE_SIZE),
+            });
+        }
+
+        let params = TreeParams {
+            degree: header.u32()? as usize,
+            branching_factor: header.u32()? as usize,
+            leaf_capacity: header.u32()? as usize,
+            max_depth: header.u32()? as usize,
+            precision_tolerance: header.f64()?,
+        };
+        let node_count = header.u32()? as usize;
+        let root_page = header.u32()?;
+        let entry_count = header.u64()?;
+        let header_len = header.position();
+        let stored = header.u64()?;
+        if stored != checksum(&bytes[..header_len]) {
+            return Err(PolynomialError::InvalidStructure {
+                reason: "header checksum mismatch".to_string(),
+            });
+        }
+
+        params.check_page_fit()?;
+        if bytes.len() != (node_count + 1) * PAGE_SIZE {
+            return Err(PolynomialError::InvalidStructure {
+                reason: format!(
+                    "expected {} pages, found {}",
+                    node_count + 1,
+                    bytes.len() / PAGE_SIZE
+                ),
+            });
+        }
+
+        let mut nodes = Vec::with_capacity(node_count);
+        for page in 1..=node_count {
+            nodes.push(decode_node(&bytes[page * PAGE_SIZE..(page + 1) * PAGE_SIZE], page, &params)?);
+        }
+
+        let root = match root_page {
+            NO_ROOT => None,
+            0 => {
+                return Err(PolynomialError::InvalidStructure {
+                    reason: "root points at the header page".to_string(),
+                })
+            }
+            page => Some(page - 1),
+        };
+
+        let tree = Self {
+            params,
+            nodes,
+            root,
+            entry_count,
+        };
+        tree.validate()?;
+        Ok(tree)
+    }
+}
+
+/// Heap entry for best-first search, ordered by distance
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    distance: f64,
+    payload: u64,
+    reverse: bool,
+}
+
+impl Candidate {
+    fn min_first(distance: f64, payload: u64) -> Self {
+        Self {
+            distance,
+            payload,
+            reverse: true,
+        }
+    }
+
+    fn max_first(distance: f64, payload: u64) -> Self {
+        Self {
+            distance,
+            payload,
+            reverse: false,
+        }
+    }
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let ord = self
+            .distance
+            .total_cmp(&other.distance)
+            .then_with(|| self.payload.cmp(&other.payload));
+        if self.reverse {
+            ord.reverse()
+        } else {
+            ord
+        }
+    }
+}
+
+fn entry_size(entry: &PolynomialEmbedding) -> usize {
+    16 + 8 + 2 + 2 + 8 * entry.coefficients.len() + 2 + entry.edge_signature.len()
+}
+
+fn checksum(bytes: &[u8]) -> u64 {
+    let hash = blake3::hash(bytes);
+    let mut first = [0u8; 8];
+    first.copy_from_slice(&hash.as_bytes()[..8]);
+    u64::from_le_bytes(first)
+}
+
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_f64(buf: &mut Vec<u8>, value: f64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Bounds-checked little-endian reader over one page
+struct PageReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PageReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    fn take(&mut self, len: usize) -> PolynomialResult<&'a [u8]> {
+        let end = self.position + len;
+        if end > self.bytes.len() {
+            return Err(PolynomialError::InvalidStructure {
+                reason: "page truncated".to_string(),
+            });
+        }
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> PolynomialResult<[u8; N]> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    fn u8(&mut self) -> PolynomialResult<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> PolynomialResult<u16> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> PolynomialResult<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> PolynomialResult<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn f64(&mut self) -> PolynomialResult<f64> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+}
+
+fn decode_node(page: &[u8], page_number: usize, params: &TreeParams) -> PolynomialResult<Node> {
+    let mut reader = PageReader::new(page);
+    let kind = reader.u8()?;
+    reader.u8()?;
+    let count = reader.u16()? as usize;
+    let level = reader.u32()?;
+    let payload_len = reader.u32()? as usize;
+    reader.u32()?;
+    let stored = reader.u64()?;
+
+    if NODE_HEADER_SIZE + payload_len > PAGE_SIZE {
+        return Err(PolynomialError::InvalidStructure {
+            reason: format!("page {} payload length {} overflows page", page_number, payload_len),
+        });
+    }
+    let payload = &page[NODE_HEADER_SIZE..NODE_HEADER_SIZE + payload_len];
+    if stored != checksum(payload) {
+        return Err(PolynomialError::InvalidStructure {
+            reason: format!("page {} checksum mismatch", page_number),
+        });
+    }
+
+    let mut reader = PageReader::new(payload);
+    let terms = params.degree + 1;
+    let mut lower = Vec::with_capacity(terms);
+    let mut upper = Vec::with_capacity(terms);
+    for _ in 0..terms {
+        lower.push(reader.f64()?);
+    }
+    for _ in 0..terms {
+        upper.push(reader.f64()?);
+    }
+    let min_key = reader.f64()?;
+    let max_key = reader.f64()?;
+
+    let kind = match kind {
+        KIND_INTERNAL => {
+            let mut children = Vec::with_capacity(count);
+            for _ in 0..count {
+                let child_page = reader.u32()?;
+                if child_page == 0 {
+                    return Err(PolynomialError::InvalidStructure {
+                        reason: format!("page {} references the header page", page_number),
+                    });
+                }
+                children.push(child_page - 1);
+            }
+            NodeKind::Internal(children)
+        }
+        KIND_LEAF => {
+            let mut entries = Vec::with_capacity(count);
+            for _ in 0..count {
+                let entity_id = EntityId::from_uuid(uuid::Uuid::from_bytes(reader.array()?));
+                let metadata_hash = reader.u64()?;
+                let degree = reader.u16()? as usize;
+                let coefficient_count = reader.u16()? as usize;
+                let mut coefficients = Vec::with_capacity(coefficient_count);
+                for _ in 0..coefficient_count {
+                    coefficients.push(reader.f64()?);
+                }
+                let signature_len = reader.u16()? as usize;
+                let edge_signature = reader.take(signature_len)?.to_vec();
+
+                entries.push(PolynomialEmbedding {
+                    coefficients,
+                    degree,
+                    entity_id,
+                    metadata_hash,
+                    edge_signature,
+                });
+            }
+            NodeKind::Leaf(entries)
+        }
+        other => {
+            return Err(PolynomialError::InvalidStructure {
+                reason: format!("page {} has unknown node kind {}", page_number, other),
+            })
+        }
+    };
+
+    Ok(Node {
+        level,
+        bounds: PolynomialBounds { lower, upper },
+        min_key,
+        max_key,
+        kind,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::test_util::uniform;
+
+    fn config(branching_factor: usize, node_capacity: usize) -> PolynomialConfig {
+        PolynomialConfig {
+            branching_factor,
+            node_capacity,
+            ..PolynomialConfig::default()
+        }
+    }
+
+    /// Deterministic sorted embeddings with coefficients in [-1, 1]
+    fn embeddings(count: usize, degree: usize, seed: u64) -> Vec<PolynomialEmbedding> {
+        let mut next = uniform(seed);
+        let mut out: Vec<PolynomialEmbedding> = (0..count)
+            .map(|i| PolynomialEmbedding {
+                coefficients: (0..=degree).map(|_| next()).collect(),
+                degree,
+                entity_id: EntityId::new(),
+                metadata_hash: i as u64,
+                edge_signature: vec![i as u8; i % 4],
+            })
+            .collect();
+        sort_for_bulk_load(&mut out);
+        out
+    }
+
+    #[test]
+    fn test_interval_evaluation_encloses_members() {
+        let a = PolynomialBounds::from_coefficients(&[1.0, -2.0, 0.5], 2);
+        let b = PolynomialBounds::from_coefficients(&[-1.0, 3.0, 0.25], 2);
+        let mut bounds = a.clone();
+        bounds.union(&b);
+
+        for x in [-2.0, -0.5, 0.0, 0.7, 3.0] {
+            let (min, max) = bounds.evaluate(x);
+            for member in [&a, &b] {
+                let value = member.evaluate(x).0;
+                assert!(min <= value + 1e-12 && value <= max + 1e-12);
+            }
+        }
+        assert!(bounds.contains(&a, 0.0));
+        assert!(!a.contains(&bounds, 0.0));
+    }
+
+    #[test]
+    fn test_bulk_load_structure() {
+        let data = embeddings(1000, 5, 7);
+        let tree = PolynomialTree::bulk_load(&config(8, 16), data.clone()).unwrap();
+
+        assert_eq!(tree.len(), 1000);
+        assert!(tree.validate().is_ok());
+        // 63 leaves of 16 entries -> 8 parents -> 1 root
+        assert_eq!(tree.height(), 3);
+
+        let order: Vec<EntityId> = tree.iter().map(|e| e.entity_id).collect();
+        let expected: Vec<EntityId> = data.iter().map(|e| e.entity_id).collect();
+        assert_eq!(order, expected);
+    }
+
+    #[test]
+    fn test_bulk_load_rejects_unsorted_input() {
+        let mut data = embeddings(10, 3, 1);
+        data.swap(0, 9);
+        let result = PolynomialTree::bulk_load(&config(4, 4), data);
+        assert!(matches!(result, Err(PolynomialError::InvalidStructure { .. })));
+    }
+
+    #[test]
+    fn test_bulk_load_rejects_excess_degree() {
+        let data = embeddings(3, 8, 1);
+        let result = PolynomialTree::bulk_load(&PolynomialConfig::default(), data);
+        assert!(matches!(
+            result,
+            Err(PolynomialError::DegreeExceeded { degree: 8, max_degree: 5 })
+        ));
+    }
+
+    #[test]
+    fn test_bulk_load_rejects_excess_height() {
+        let cfg = PolynomialConfig {
+            max_depth: 2,
+            ..config(2, 2)
+        };
+        let result = PolynomialTree::bulk_load(&cfg, embeddings(20, 2, 3));
+        assert!(matches!(result, Err(PolynomialError::InvalidStructure { .. })));
+    }
+
+    #[test]
+    fn test_range_query_matches_brute_force_and_prunes() {
+        let data = embeddings(2000, 4, 11);
+        let tree = PolynomialTree::bulk_load(&config(16, 32), data.clone()).unwrap();
+
+        for (x, lower, upper) in [(1.0, 0.5, 0.6), (0.5, -0.1, 0.1), (-1.5, 2.0, 2.5)] {
+            let (mut got, stats) = tree.range_query_with_stats(x, lower, upper);
+            let mut expected: Vec<EntityId> = data
+                .iter()
+                .filter(|e| {
+                    let v = e.evaluate(x);
+                    v >= lower && v <= upper
+                })
+                .map(|e| e.entity_id)
+                .collect();
+            got.sort_by_key(|id| *id.as_uuid());
+            expected.sort_by_key(|id| *id.as_uuid());
+            assert_eq!(got, expected);
+
+            if x == SORT_PIVOT {
+                // Leaves are sorted by P(pivot), so a narrow range touches few leaves
+                assert!(stats.nodes_pruned > 0);
+                assert!(stats.entries_evaluated < data.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_nearest_matches_brute_force() {
+        let data = embeddings(1500, 3, 5);
+        let tree = PolynomialTree::bulk_load(&config(8, 24), data.clone()).unwrap();
+
+        for (x, target) in [(1.0, 0.3), (0.25, -0.8), (2.0, 5.0)] {
+            let (got, stats) = tree.nearest_with_stats(x, target, 10);
+            let mut expected: Vec<f64> = data.iter().map(|e| (e.evaluate(x) - target).abs()).collect();
+            expected.sort_by(|a, b| a.total_cmp(b));
+
+            let distances: Vec<f64> = got.iter().map(|(_, d)| *d).collect();
+            assert_eq!(distances.len(), 10);
+            for (a, b) in distances.iter().zip(&expected[..10]) {
+                assert!((a - b).abs() < 1e-12);
+            }
+            if x == SORT_PIVOT {
+                assert!(stats.entries_evaluated < data.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_serialization_round_trip() {
+        let data = embeddings(700, 5, 21);
+        let tree = PolynomialTree::bulk_load(&config(16, 100), data).unwrap();
+
+        let bytes = tree.to_bytes();
+        assert_eq!(bytes.len() % PAGE_SIZE, 0);
+        assert_eq!(bytes.len(), (tree.node_count() + 1) * PAGE_SIZE);
+
+        let loaded = PolynomialTree::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.len(), tree.len());
+        assert_eq!(loaded.height(), tree.height());
+        assert_eq!(loaded.params(), tree.params());
+
+        for (a, b) in tree.iter().zip(loaded.iter()) {
+            assert_eq!(a.entity_id, b.entity_id);
+            assert_eq!(a.coefficients, b.coefficients);
+            assert_eq!(a.metadata_hash, b.metadata_hash);
+            assert_eq!(a.edge_signature, b.edge_signature);
+        }
+        assert_eq!(tree.range_query(0.5, 0.0, 0.2), loaded.range_query(0.5, 0.0, 0.2));
+    }
+
+    #[test]
+    fn test_empty_tree_round_trip() {
+        let tree = PolynomialTree::bulk_load(&PolynomialConfig::default(), Vec::new()).unwrap();
+        assert!(tree.is_empty());
+        assert_eq!(tree.height(), 0);
+        assert!(tree.range_query(1.0, -10.0, 10.0).is_empty());
+
+        let loaded = PolynomialTree::from_bytes(&tree.to_bytes()).unwrap();
+        assert!(loaded.is_empty());
+    }
+
+    #[test]
+    fn test_from_bytes_detects_corruption() {
+        let tree = PolynomialTree::bulk_load(&config(4, 8), embeddings(50, 5, 2)).unwrap();
+        let mut bytes = tree.to_bytes();
+
+        // Flip a bit inside the first node's payload
+        bytes[PAGE_SIZE + NODE_HEADER_SIZE + 3] ^= 0x40;
+        assert!(matches!(
+            PolynomialTree::from_bytes(&bytes),
+            Err(PolynomialError::InvalidStructure { .. })
+        ));
+
+        assert!(PolynomialTree::from_bytes(&bytes[..PAGE_SIZE + 10]).is_err());
+    }
+
+    #[test]
+    fn test_validate_detects_bad_bounds() {
+        let mut tree = PolynomialTree::bulk_load(&config(4, 8), embeddings(100, 3, 9)).unwrap();
+        let root = tree.root.unwrap() as usize;
+        tree.nodes[root].bounds.upper[0] -= 10.0;
+
+        let err = tree.validate().unwrap_err();
+        assert!(matches!(err, PolynomialError::InvalidStructure { .. }));
+    }
+
+    #[test]
+    fn test_validate_detects_shared_child() {
+        let mut tree = PolynomialTree::bulk_load(&config(4, 4), embeddings(40, 2, 4)).unwrap();
+        let root = tree.root.unwrap() as usize;
+        if let NodeKind::Internal(children) = &mut tree.nodes[root].kind {
+            let first = children[0];
+            children[1] = first;
+        }
+        assert!(tree.validate().is_err());
+    }
+
+    #[test]
+    fn test_page_fit_is_checked() {
+        let cfg = PolynomialConfig {
+            degree: 200,
+            branching_factor: 512,
+            ..PolynomialConfig::default()
+        };
+        assert!(PolynomialTree::new(&cfg).is_err());
+    }
+}
@@ -1 +1,848 @@
-// TODO: Implement probabilistic graph index
+//! Navigable small-world graph index for approximate nearest-neighbour search
+//!
+//! Hierarchical graph in the style of HNSW: every node lives on layer 0 and
+//! on each higher layer with geometrically decreasing probability. Search
+//! descends greedily from the sparse top layer and widens into a best-first
+//! beam of `ef` candidates on layer 0.
+//!
+//! Removal tombstones the node: it keeps routing traffic but is never
+//! returned. Re-inserting an id tombstones the old node and links a new one.
+//! Once retired tombstones make up more than `max_tombstone_ratio` of the
+//! nodes, the graph is rebuilt from the nodes that are still visible.
+//!
+//! Updates and removals made at a commit timestamp (`insert_at`,
+//! `remove_at`) record when each node was superseded, so `search_as_of` can
+//! still return it to searches of earlier snapshots. `release` retires
+//! superseded nodes once no live snapshot can see them.
+//!
+//! Filtered search walks through every node but only admits matching ones to
+//! the result set, so the graph stays connected under restrictive filters.
+
+use crate::core::collection::IndexConfig;
+use crate::core::error::{MemorySubstrateError, Result};
+use crate::core::mvcc::Timestamp;
+use crate::core::{DistanceMetric, EntityId, Vector};
+use crate::index::simd::{scan_distance, SearchHit, TopK, VectorArena};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// Construction and search parameters of a graph index
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GraphIndexConfig {
+    /// Maximum links per node on upper layers (layer 0 allows twice as many)
+    pub m: usize,
+
+    /// Beam width while linking new nodes
+    pub ef_construction: usize,
+
+    /// Default beam width for queries (raised to k when smaller)
+    pub ef_search: usize,
+
+    /// Seed for the level generator, making builds reproducible
+    pub seed: u64,
+
+    /// Share of retired tombstones above which the graph is rebuilt
+    /// without them (default: 0.25)
+    pub max_tombstone_ratio: f64,
+}
+
+impl Default for GraphIndexConfig {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+            seed: 0x5eed,
+            max_tombstone_ratio: 0.25,
+        }
+    }
+}
+
+impl GraphIndexConfig {
+    /// Validate the parameters
+    ///
+    /// # Errors
+    /// * `Configuration` if `m < 2`, `ef_construction < m`, `ef_search` is
+    ///   zero or `max_tombstone_ratio` is outside (0, 1]
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: &str| Err(MemorySubstrateError::Configuration(format!("graph index {}", reason)));
+        if self.m < 2 {
+            return invalid("m must be >= 2");
+        }
+        if self.ef_construction < self.m {
+            return invalid("ef_construction must be >= m");
+        }
+        if self.ef_search == 0 {
+            return invalid("ef_search must be > 0");
+        }
+        if !(self.max_tombstone_ratio > 0.0 && self.max_tombstone_ratio <= 1.0) {
+            return invalid("max_tombstone_ratio must be in (0, 1]");
+        }
+        Ok(())
+    }
+}
+
+impl From<&IndexConfig> for GraphIndexConfig {
+    fn from(config: &IndexConfig) -> Self {
+        Self {
+            m: config.m,
+            ef_construction: config.ef_construction,
+            ef_search: config.ef_search,
+            ..Self::default()
+        }
+    }
+}
+
+/// Commit timestamps between which a node held its entity's latest vector
+///
+/// `until` is `Timestamp::MAX` while it still does. A retired node has an
+/// empty span and is visible to no snapshot.
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    from: Timestamp,
+    until: Timestamp,
+}
+
+impl Span {
+    fn is_latest(&self) -> bool {
+        self.until == Timestamp::MAX
+    }
+
+    fn is_retired(&self) -> bool {
+        self.from == self.until
+    }
+
+    fn contains(&self, ts: Timestamp) -> bool {
+        self.from <= ts && ts < self.until
+    }
+}
+
+/// Candidate node scored by its distance to the query
+#[derive(Debug, Clone, Copy)]
+struct Scored {
+    distance: f32,
+    node: u32,
+}
+
+impl PartialEq for Scored {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then_with(|| self.node.cmp(&other.node))
+    }
+}
+
+/// Hierarchical navigable small-world graph over entity vectors
+///
+/// Complexity: O(log n) expected hops per query and insert, with each hop
+/// costing up to `2m` distance computations on layer 0.
+#[derive(Debug, Clone)]
+pub struct GraphIndex {
+    metric: DistanceMetric,
+    config: GraphIndexConfig,
+    arena: VectorArena,
+    ids: Vec<EntityId>,
+    spans: Vec<Span>,
+    /// Superseded nodes that a snapshot may still see
+    superseded: Vec<u32>,
+    /// Nodes no snapshot can see, kept only for routing until compaction
+    retired: usize,
+    /// Adjacency per node, per layer (`links[node][layer]`)
+    links: Vec<Vec<Vec<u32>>>,
+    positions: HashMap<EntityId, u32>,
+    entry_point: Option<u32>,
+    level_multiplier: f64,
+    rng_state: u64,
+}
+
+impl GraphIndex {
+    /// Create an empty graph index
+    ///
+    /// # Arguments
+    /// * `dimensions` - Dimensionality of every stored vector
+    /// * `metric` - Distance metric used for search
+    /// * `config` - Graph construction and search parameters
+    ///
+    /// # Panics
+    /// If `config` does not pass `GraphIndexConfig::validate`
+    pub fn new(dimensions: usize, metric: DistanceMetric, config: GraphIndexConfig) -> Self {
+        if let Err(error) = config.validate() {
+            panic!("{}", error);
+        }
+        Self {
+            metric,
+            config,
+            arena: VectorArena::new(dimensions),
+            ids: Vec::new(),
+            spans: Vec::new(),
+            superseded: Vec::new(),
+            retired: 0,
+            links: Vec::new(),
+            positions: HashMap::new(),
+            entry_point: None,
+            level_multiplier: 1.0 / (config.m as f64).ln(),
+            rng_state: config.seed.max(1),
+        }
+    }
+
+    /// Distance metric used by this index
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    /// Graph parameters
+    pub fn config(&self) -> &GraphIndexConfig {
+        &self.config
+    }
+
+    /// Dimensionality of stored vectors
+    pub fn dimensions(&self) -> usize {
+        self.arena.dimensions()
+    }
+
+    /// Number of live (non-removed) vectors
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Whether no live vectors are indexed
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Number of graph nodes, including tombstones not yet compacted away
+    pub fn node_count(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Whether `id` is indexed
+    pub fn contains(&self, id: &EntityId) -> bool {
+        self.positions.contains_key(id)
+    }
+
+    /// Stored vector values for `id`
+    pub fn get(&self, id: &EntityId) -> Option<&[f32]> {
+        self.positions.get(id).map(|&node| self.arena.row(node as usize))
+    }
+
+    /// Insert or replace the vector for `id`
+    ///
+    /// A replaced vector is not kept for earlier snapshots.
+    pub fn insert(&mut self, id: EntityId, vector: &Vector) -> Result<()> {
+        self.insert_at(id, vector, 0)
+    }
+
+    /// Insert or replace the vector for `id` as committed at `ts`
+    ///
+    /// A replaced vector stays visible to `search_as_of` before `ts` until
+    /// `release` retires it.
+    pub fn insert_at(&mut self, id: EntityId, vector: &Vector, ts: Timestamp) -> Result<()> {
+        self.check_dimensions(vector.values.len())?;
+        self.supersede(&id, ts);
+        let span = Span {
+            from: ts,
+            until: Timestamp::MAX,
+        };
+        let node = self.add_node(id, &vector.values, vector.norm, span);
+        self.positions.insert(id, node);
+        self.maybe_compact();
+        Ok(())
+    }
+
+    /// Push a node and link it into the graph
+    fn add_node(&mut self, id: EntityId, values: &[f32], norm: f32, span: Span) -> u32 {
+        let level = self.random_level();
+        let node = self.arena.push(values, norm) as u32;
+        self.ids.push(id);
+        self.spans.push(span);
+        self.links.push(vec![Vec::new(); level + 1]);
+
+        let Some(entry) = self.entry_point else {
+            self.entry_point = Some(node);
+            return node;
+        };
+
+        let top_level = self.links[entry as usize].len() - 1;
+        let query = (values, norm);
+        let mut entry_points = vec![Scored {
+            distance: self.distance_to(query, entry),
+            node: entry,
+        }];
+
+        for layer in (level + 1..=top_level).rev() {
+            entry_points = self.search_layer(query, &entry_points, 1, layer, None::<&fn(u32) -> bool>);
+        }
+
+        for layer in (0..=level.min(top_level)).rev() {
+            let candidates = self.search_layer(
+                query,
+                &entry_points,
+                self.config.ef_construction,
+                layer,
+                None::<&fn(u32) -> bool>,
+            );
+            let neighbours = self.select_neighbours(&candidates, self.max_links(layer));
+            self.links[node as usize][layer] = neighbours.clone();
+
+            for neighbour in neighbours {
+                self.link(neighbour, node, layer);
+            }
+            entry_points = candidates;
+        }
+
+        if level > top_level {
+            self.entry_point = Some(node);
+        }
+        node
+    }
+
+    /// Remove `id` from the index, returning whether it was present
+    ///
+    /// The node stays in the graph as a routing-only tombstone.
+    pub fn remove(&mut self, id: &EntityId) -> bool {
+        self.remove_at(id, 0)
+    }
+
+    /// Remove `id` as committed at `ts`, returning whether it was present
+    ///
+    /// The vector stays visible to `search_as_of` before `ts` until
+    /// `release` retires it.
+    pub fn remove_at(&mut self, id: &EntityId, ts: Timestamp) -> bool {
+        let removed = self.supersede(id, ts);
+        self.maybe_compact();
+        removed
+    }
+
+    /// Retire superseded nodes that none of the `live` snapshot timestamps
+    /// can see, returning how many were retired
+    ///
+    /// Retired nodes only route searches from then on.
+    pub fn release(&mut self, live: &[Timestamp]) -> usize {
+        let before = self.superseded.len();
+        let spans = &mut self.spans;
+        self.superseded.retain(|&node| {
+            let span = &mut spans[node as usize];
+            let visible = live.iter().any(|&ts| span.contains(ts));
+            if !visible {
+                span.from = span.until;
+            }
+            visible
+        });
+        let released = before - self.superseded.len();
+        self.retired += released;
+        self.maybe_compact();
+        released
+    }
+
+    /// Rebuild the graph without retired nodes once they exceed
+    /// `max_tombstone_ratio` of all nodes
+    ///
+    /// Rebuilding costs about as much as inserting the remaining nodes, so
+    /// it is amortized over the removals that triggered it.
+    fn maybe_compact(&mut self) {
+        if (self.retired as f64) <= self.config.max_tombstone_ratio * self.ids.len() as f64 {
+            return;
+        }
+        let mut compacted = Self::new(self.dimensions(), self.metric, self.config);
+        compacted.rng_state = self.rng_state;
+        for node in 0..self.ids.len() as u32 {
+            let span = self.spans[node as usize];
+            if span.is_retired() {
+                continue;
+            }
+            let (values, norm) = self.row(node);
+            let id = self.ids[node as usize];
+            let moved = compacted.add_node(id, values, norm, span);
+            if span.is_latest() {
+                compacted.positions.insert(id, moved);
+            } else {
+                compacted.superseded.push(moved);
+            }
+        }
+        tracing::debug!(
+            retired = self.retired,
+            nodes = compacted.ids.len(),
+            "compacted graph index"
+        );
+        *self = compacted;
+    }
+
+    /// Find approximately the `k` nearest vectors to `query`
+    ///
+    /// # Returns
+    /// * Hits ordered from closest to farthest
+    pub fn search(&self, query: &Vector, k: usize) -> Result<Vec<SearchHit>> {
+        self.search_with_ef(query, k, self.config.ef_search)
+    }
+
+    /// `search` with an explicit beam width
+    pub fn search_with_ef(&self, query: &Vector, k: usize, ef: usize) -> Result<Vec<SearchHit>> {
+        self.check_dimensions(query.values.len())?;
+        Ok(self.search_nodes(query, k, ef, None::<&fn(u32) -> bool>))
+    }
+
+    /// Find approximately the `k` nearest vectors that satisfy `filter`
+    ///
+    /// Non-matching nodes are traversed but never returned, so the beam keeps
+    /// expanding until `ef` matching candidates are found or the reachable
+    /// graph is exhausted.
+    pub fn search_filtered<F>(&self, query: &Vector, k: usize, ef: usize, filter: F) -> Result<Vec<SearchHit>>
+    where
+        F: Fn(&EntityId) -> bool,
+    {
+        self.check_dimensions(query.values.len())?;
+        let admit = |node: u32| filter(&self.ids[node as usize]);
+        Ok(self.search_nodes(query, k, ef, Some(&admit)))
+    }
+
+    /// Find approximately the `k` nearest vectors that were latest as of
+    /// commit `ts` and satisfy `filter`
+    ///
+    /// Superseded vectors that `release` has not retired yet are searched
+    /// too, so an entity is scored by the vector it had at `ts`.
+    pub fn search_as_of<F>(&self, query: &Vector, k: usize, ef: usize, ts: Timestamp, filter: F) -> Result<Vec<SearchHit>>
+    where
+        F: Fn(&EntityId) -> bool,
+    {
+        self.check_dimensions(query.values.len())?;
+        let admit = |node: u32| self.spans[node as usize].contains(ts) && filter(&self.ids[node as usize]);
+        Ok(self.search_nodes_in(query, k, ef, &admit))
+    }
+
+    /// Exactly rank a candidate set, returning its `k` nearest to `query`
+    ///
+    /// Candidates that are not indexed are ignored.
+    pub fn rerank(&self, query: &Vector, candidates: &[EntityId], k: usize) -> Result<Vec<SearchHit>> {
+        self.check_dimensions(query.values.len())?;
+
+        let mut top = TopK::new(k);
+        for id in candidates {
+            if let Some(&node) = self.positions.get(id) {
+                top.push(*id, self.distance_to((&query.values, query.norm), node));
+            }
+        }
+        Ok(self.finish(top))
+    }
+
+    fn search_nodes<F>(&self, query: &Vector, k: usize, ef: usize, admit: Option<&F>) -> Vec<SearchHit>
+    where
+        F: Fn(u32) -> bool,
+    {
+        // Only latest, matching nodes are admitted into the result beam
+        let latest_and_matching =
+            |node: u32| self.spans[node as usize].is_latest() && admit.is_none_or(|admit| admit(node));
+        self.search_nodes_in(query, k, ef, &latest_and_matching)
+    }
+
+    /// Search every layer, admitting only nodes that pass `admit` on layer 0
+    fn search_nodes_in<F>(&self, query: &Vector, k: usize, ef: usize, admit: &F) -> Vec<SearchHit>
+    where
+        F: Fn(u32) -> bool,
+    {
+        let Some(entry) = self.entry_point else {
+            return Vec::new();
+        };
+        if k == 0 {
+            return Vec::new();
+        }
+
+        let query = (&query.values[..], query.norm);
+        let mut entry_points = vec![Scored {
+            distance: self.distance_to(query, entry),
+            node: entry,
+        }];
+        for layer in (1..self.links[entry as usize].len()).rev() {
+            entry_points = self.search_layer(query, &entry_points, 1, layer, None::<&F>);
+        }
+
+        let found = self.search_layer(query, &entry_points, ef.max(k), 0, Some(admit));
+
+        let mut top = TopK::new(k);
+        for scored in found {
+            top.push(self.ids[scored.node as usize], scored.distance);
+        }
+        self.finish(top)
+    }
+
+    /// Best-first beam search on one layer
+    ///
+    /// When `admit` is given, every reachable node is still expanded but only
+    /// admitted nodes count towards the `ef` results.
+    ///
+    /// # Returns
+    /// * Up to `ef` admitted nodes ordered from closest to farthest
+    fn search_layer<F>(
+        &self,
+        query: (&[f32], f32),
+        entry_points: &[Scored],
+        ef: usize,
+        layer: usize,
+        admit: Option<&F>,
+    ) -> Vec<Scored>
+    where
+        F: Fn(u32) -> bool,
+    {
+        let admitted = |node: u32| admit.is_none_or(|admit| admit(node));
+        let mut visited: HashSet<u32> = entry_points.iter().map(|s| s.node).collect();
+        let mut candidates: BinaryHeap<Reverse<Scored>> = entry_points.iter().copied().map(Reverse).collect();
+        let mut results: BinaryHeap<Scored> = entry_points
+            .iter()
+            .copied()
+            .filter(|s| admitted(s.node))
+            .collect();
+        while results.len() > ef {
+            results.pop();
+        }
+
+        while let Some(Reverse(current)) = candidates.pop() {
+            if results.len() >= ef && results.peek().is_some_and(|worst| current.distance > worst.distance) {
+                break;
+            }
+
+            let Some(neighbours) = self.links[current.node as usize].get(layer) else {
+                continue;
+            };
+            for &neighbour in neighbours {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+                let distance = self.distance_to(query, neighbour);
+                let worst = results.peek().map_or(f32::INFINITY, |w| w.distance);
+                if results.len() < ef || distance < worst {
+                    let scored = Scored {
+                        distance,
+                        node: neighbour,
+                    };
+                    candidates.push(Reverse(scored));
+                    if admitted(neighbour) {
+                        results.push(scored);
+                        if results.len() > ef {
+                            results.pop();
+                        }
+                    }
+                }
+            }
+        }
+
+        results.into_sorted_vec()
+    }
+
+    /// Mark the latest node of `id` superseded at `ts`
+    fn supersede(&mut self, id: &EntityId, ts: Timestamp) -> bool {
+        let Some(node) = self.positions.remove(id) else {
+            return false;
+        };
+        let span = &mut self.spans[node as usize];
+        span.until = ts.max(span.from);
+        if span.is_retired() {
+            self.retired += 1;
+        } else {
+            self.superseded.push(node);
+        }
+        true
+    }
+
+    /// Pick up to `max` diverse neighbours from candidates sorted by distance
+    ///
+    /// A candidate is kept only if it is closer to the new node than to every
+    /// neighbour already kept, which spreads links across directions. Pruned
+    /// candidates backfill any remaining slots to preserve connectivity.
+    fn select_neighbours(&self, candidates: &[Scored], max: usize) -> Vec<u32> {
+        let mut selected: Vec<Scored> = Vec::with_capacity(max);
+        let mut pruned: Vec<Scored> = Vec::new();
+
+        for &candidate in candidates {
+            if selected.len() >= max {
+                break;
+            }
+            let row = self.row(candidate.node);
+            let diverse = selected
+                .iter()
+                .all(|kept| self.distance_to(row, kept.node) > candidate.distance);
+            if diverse {
+                selected.push(candidate);
+            } else {
+                pruned.push(candidate);
+            }
+        }
+
+        for candidate in pruned {
+            if selected.len() >= max {
+                break;
+            }
+            selected.push(candidate);
+        }
+        selected.into_iter().map(|s| s.node).collect()
+    }
+
+    /// Add a link `from -> to`, shrinking `from`'s list if it overflows
+    fn link(&mut self, from: u32, to: u32, layer: usize) {
+        let max = self.max_links(layer);
+        let list = &mut self.links[from as usize][layer];
+        list.push(to);
+        if list.len() <= max {
+            return;
+        }
+
+        let origin = self.row(from);
+        let mut scored: Vec<Scored> = self.links[from as usize][layer]
+            .iter()
+            .map(|&node| Scored {
+                distance: self.distance_to(origin, node),
+                node,
+            })
+            .collect();
+        scored.sort();
+        self.links[from as usize][layer] = self.select_neighbours(&scored, max);
+    }
+
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.config.m * 2
+        } else {
+            self.config.m
+        }
+    }
+
+    /// Draw a level from the geometric distribution P(level >= l) = m^-l
+    fn random_level(&mut self) -> usize {
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 7;
+        self.rng_state ^= self.rng_state << 17;
+        let uniform = ((self.rng_state >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        ((-uniform.ln() * self.level_multiplier) as usize).min(32)
+    }
+
+    #[inline]
+    fn row(&self, node: u32) -> (&[f32], f32) {
+        (self.arena.row(node as usize), self.arena.norm(node as usize))
+    }
+
+    #[inline]
+    fn distance_to(&self, (values, norm): (&[f32], f32), node: u32) -> f32 {
+        let (row, row_norm) = self.row(node);
+        scan_distance(self.metric, values, norm, row, row_norm)
+    }
+
+    fn check_dimensions(&self, actual: usize) -> Result<()> {
+        if actual != self.dimensions() {
+            return Err(MemorySubstrateError::DimensionMismatch {
+                expected: self.dimensions(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    fn finish(&self, top: TopK) -> Vec<SearchHit> {
+        let mut hits = top.into_sorted_vec();
+        if self.metric == DistanceMetric::Euclidean {
+            for hit in &mut hits {
+                hit.distance = hit.distance.sqrt();
+            }
+        }
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::test_util::random_vectors;
+    use crate::index::FlatIndex;
+
+    /// Small beam so debug-build tests stay fast
+    fn test_config() -> GraphIndexConfig {
+        GraphIndexConfig {
+            m: 12,
+            ef_construction: 48,
+            ..GraphIndexConfig::default()
+        }
+    }
+
+    fn build(metric: DistanceMetric, count: usize) -> (GraphIndex, FlatIndex, Vec<EntityId>) {
+        let mut graph = GraphIndex::new(16, metric, test_config());
+        let mut flat = FlatIndex::new(16, metric);
+        let mut ids = Vec::new();
+        for vector in random_vectors(count, 16, 3) {
+            let id = EntityId::new();
+            graph.insert(id, &vector).unwrap();
+            flat.insert(id, &vector).unwrap();
+            ids.push(id);
+        }
+        (graph, flat, ids)
+    }
+
+    fn recall(graph: &[SearchHit], exact: &[SearchHit]) -> f64 {
+        let truth: HashSet<EntityId> = exact.iter().map(|h| h.id).collect();
+        graph.iter().filter(|h| truth.contains(&h.id)).count() as f64 / exact.len() as f64
+    }
+
+    #[test]
+    fn test_recall_against_flat_index() {
+        for metric in [DistanceMetric::Euclidean, DistanceMetric::Cosine, DistanceMetric::DotProduct] {
+            let (graph, flat, _) = build(metric, 1000);
+            let queries = random_vectors(20, 16, 99);
+
+            let mut total = 0.0;
+            for query in &queries {
+                let approx = graph.search(query, 10).unwrap();
+                let exact = flat.search(query, 10).unwrap();
+                total += recall(&approx, &exact);
+            }
+            let mean = total / queries.len() as f64;
+            assert!(mean >= 0.9, "{:?} recall {}", metric, mean);
+        }
+    }
+
+    #[test]
+    fn test_distances_match_metric() {
+        let (graph, flat, _) = build(DistanceMetric::Euclidean, 300);
+        let query = &random_vectors(1, 16, 7)[0];
+        let hits = graph.search_with_ef(query, 5, 300).unwrap();
+        let exact = flat.search(query, 5).unwrap();
+        for (a, b) in hits.iter().zip(&exact) {
+            assert_eq!(a.id, b.id);
+            assert!((a.distance - b.distance).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_remove_and_reinsert() {
+        let (mut graph, _, ids) = build(DistanceMetric::Euclidean, 500);
+        let target = ids[42];
+        let vector = Vector::new(graph.get(&target).unwrap().to_vec());
+
+        assert_eq!(graph.search(&vector, 1).unwrap()[0].id, target);
+        assert!(graph.remove(&target));
+        assert!(!graph.remove(&target));
+        assert_eq!(graph.len(), 499);
+        assert!(graph.search(&vector, 10).unwrap().iter().all(|h| h.id != target));
+
+        graph.insert(target, &vector).unwrap();
+        assert_eq!(graph.len(), 500);
+        assert_eq!(graph.node_count(), 501);
+        assert_eq!(graph.search(&vector, 1).unwrap()[0].id, target);
+    }
+
+    #[test]
+    fn test_compacts_once_tombstones_pass_the_ratio() {
+        let (mut graph, mut flat, ids) = build(DistanceMetric::Euclidean, 400);
+        for id in &ids[..100] {
+            graph.remove(id);
+            flat.remove(id);
+        }
+        assert_eq!(graph.node_count(), 400);
+
+        // The next removal pushes tombstones past a quarter of the nodes
+        graph.remove(&ids[100]);
+        flat.remove(&ids[100]);
+        assert_eq!((graph.len(), graph.node_count()), (299, 299));
+
+        // Superseded vectors a snapshot can still see survive compaction
+        let kept = Vector::new(graph.get(&ids[101]).unwrap().to_vec());
+        graph.remove_at(&ids[101], 7);
+        flat.remove(&ids[101]);
+        for id in &ids[102..180] {
+            graph.remove(id);
+            flat.remove(id);
+        }
+        // Compacted again at 75 tombstones; 3 more have accrued since
+        assert_eq!((graph.len(), graph.node_count()), (220, 224));
+        assert_eq!(graph.search_as_of(&kept, 1, 64, 6, |_| true).unwrap()[0].id, ids[101]);
+
+        let queries = random_vectors(10, 16, 21);
+        let mean = queries
+            .iter()
+            .map(|query| recall(&graph.search(query, 10).unwrap(), &flat.search(query, 10).unwrap()))
+            .sum::<f64>()
+            / queries.len() as f64;
+        assert!(mean >= 0.9, "recall after compaction {}", mean);
+    }
+
+    #[test]
+    fn test_config_rejects_degenerate_parameters() {
+        assert!(GraphIndexConfig::default().validate().is_ok());
+        for config in [
+            GraphIndexConfig { m: 1, ef_construction: 8, ..GraphIndexConfig::default() },
+            GraphIndexConfig { ef_search: 0, ..GraphIndexConfig::default() },
+            GraphIndexConfig { max_tombstone_ratio: 0.0, ..GraphIndexConfig::default() },
+        ] {
+            assert!(matches!(config.validate(), Err(MemorySubstrateError::Configuration(_))));
+        }
+        let m1 = GraphIndexConfig { m: 1, ..GraphIndexConfig::default() };
+        assert!(std::panic::catch_unwind(|| GraphIndex::new(4, DistanceMetric::Cosine, m1)).is_err());
+    }
+
+    #[test]
+    fn test_search_as_of_sees_superseded_vectors_until_released() {
+        let (mut graph, _, ids) = build(DistanceMetric::Euclidean, 300);
+        let (moved, removed) = (ids[7], ids[8]);
+        let old = Vector::new(graph.get(&moved).unwrap().to_vec());
+        let gone = Vector::new(graph.get(&removed).unwrap().to_vec());
+        graph.insert_at(moved, &Vector::new(vec![9.0; 16]), 5).unwrap();
+        assert!(graph.remove_at(&removed, 6));
+
+        let everything = |_: &EntityId| true;
+        assert_ne!(graph.search(&old, 1).unwrap()[0].id, moved);
+        assert_eq!(graph.search_as_of(&old, 1, 64, 4, everything).unwrap()[0].id, moved);
+        assert_ne!(graph.search_as_of(&old, 1, 64, 5, everything).unwrap()[0].id, moved);
+        assert_eq!(graph.search_as_of(&gone, 1, 64, 5, everything).unwrap()[0].id, removed);
+        assert_ne!(graph.search_as_of(&gone, 1, 64, 6, everything).unwrap()[0].id, removed);
+
+        // A snapshot at 5 still sees the removed vector but not the moved one
+        assert_eq!(graph.release(&[5]), 1);
+        assert_eq!(graph.search_as_of(&gone, 1, 64, 5, everything).unwrap()[0].id, removed);
+        assert_eq!(graph.release(&[]), 1);
+        assert_ne!(graph.search_as_of(&gone, 1, 64, 5, everything).unwrap()[0].id, removed);
+        assert_eq!(graph.len(), 299);
+    }
+
+    #[test]
+    fn test_filtered_search_only_returns_matches() {
+        let (graph, flat, ids) = build(DistanceMetric::Cosine, 1000);
+        let allowed: HashSet<EntityId> = ids.iter().step_by(10).copied().collect();
+        let query = &random_vectors(1, 16, 5)[0];
+
+        let hits = graph.search_filtered(query, 10, 64, |id| allowed.contains(id)).unwrap();
+        assert_eq!(hits.len(), 10);
+        assert!(hits.iter().all(|h| allowed.contains(&h.id)));
+
+        let exact = flat.search_filtered(query, 10, |id| allowed.contains(id)).unwrap();
+        assert!(recall(&hits, &exact) >= 0.8);
+    }
+
+    #[test]
+    fn test_rerank_and_dimension_checks() {
+        let (graph, flat, ids) = build(DistanceMetric::DotProduct, 200);
+        let query = &random_vectors(1, 16, 11)[0];
+        let candidates = &ids[..50];
+
+        assert_eq!(
+            graph.rerank(query, candidates, 5).unwrap(),
+            flat.rerank(query, candidates, 5).unwrap()
+        );
+        assert!(matches!(
+            graph.search(&Vector::zeros(3), 1),
+            Err(MemorySubstrateError::DimensionMismatch { expected: 16, actual: 3 })
+        ));
+        assert!(GraphIndex::new(4, DistanceMetric::Cosine, GraphIndexConfig::default())
+            .search(&Vector::zeros(4), 3)
+            .unwrap()
+            .is_empty());
+    }
+}
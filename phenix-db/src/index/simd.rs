@@ -1 +1,1080 @@
-// TODO: Implement SIMD optimizations
+//! SIMD-accelerated exact vector search
+//!
+//! Provides distance kernels (dot, squared L2, cosine) with runtime CPU-feature
+//! detection and an exact brute-force `FlatIndex` built on top of them.
+//!
+//! With the `simd` feature (on by default), the best available instruction
+//! set is selected once at startup (AVX-512 → AVX2+FMA → NEON → scalar).
+//! Built without it, the scalar kernels are used; they are written with
+//! independent accumulators so the compiler can still auto-vectorize them.
+//!
+//! The flat index is used for small collections, ground-truth evaluation of
+//! approximate indexes, and exact re-ranking of ANN candidates.
+
+use crate::core::error::{MemorySubstrateError, Result};
+use crate::core::{DistanceMetric, EntityId, Vector};
+use once_cell::sync::Lazy;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Minimum number of vectors before a scan is split across threads
+pub const DEFAULT_PARALLEL_THRESHOLD: usize = 16_384;
+
+/// Instruction set used by the distance kernels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimdLevel {
+    /// Portable scalar code (auto-vectorized where possible)
+    Scalar,
+    /// x86_64 AVX2 with fused multiply-add
+    Avx2,
+    /// x86_64 AVX-512F
+    Avx512,
+    /// aarch64 Advanced SIMD
+    Neon,
+}
+
+impl SimdLevel {
+    /// Detect the best instruction set supported by the running CPU
+    ///
+    /// Always returns `Scalar` when the `simd` feature is disabled.
+    pub fn detect() -> Self {
+        #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx512f") {
+                return SimdLevel::Avx512;
+            }
+            if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+                return SimdLevel::Avx2;
+            }
+        }
+
+        #[cfg(all(feature = "simd", target_arch = "aarch64"))]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                return SimdLevel::Neon;
+            }
+        }
+
+        SimdLevel::Scalar
+    }
+
+    /// Instruction set selected for this process
+    pub fn current() -> Self {
+        KERNELS.level
+    }
+}
+
+/// Distance kernel table selected once per process
+struct Kernels {
+    level: SimdLevel,
+    dot: fn(&[f32], &[f32]) -> f32,
+    squared_l2: fn(&[f32], &[f32]) -> f32,
+}
+
+static KERNELS: Lazy<Kernels> = Lazy::new(|| Kernels::for_level(SimdLevel::detect()));
+
+impl Kernels {
+    fn for_level(level: SimdLevel) -> Self {
+        match level {
+            #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+            SimdLevel::Avx512 => Self {
+                level,
+                dot: x86::dot_avx512,
+                squared_l2: x86::squared_l2_avx512,
+            },
+            #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+            SimdLevel::Avx2 => Self {
+                level,
+                dot: x86::dot_avx2,
+                squared_l2: x86::squared_l2_avx2,
+            },
+            #[cfg(all(feature = "simd", target_arch = "aarch64"))]
+            SimdLevel::Neon => Self {
+                level,
+                dot: neon::dot,
+                squared_l2: neon::squared_l2,
+            },
+            _ => Self {
+                level: SimdLevel::Scalar,
+                dot: scalar::dot,
+                squared_l2: scalar::squared_l2,
+            },
+        }
+    }
+}
+
+/// Compute the dot product of two equal-length slices
+///
+/// # Panics
+/// * If the slices have different lengths
+#[inline]
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    assert_eq!(a.len(), b.len(), "Vector dimensions must match for dot product");
+    (KERNELS.dot)(a, b)
+}
+
+/// Compute the squared Euclidean distance of two equal-length slices
+///
+/// # Panics
+/// * If the slices have different lengths
+#[inline]
+pub fn squared_l2(a: &[f32], b: &[f32]) -> f32 {
+    assert_eq!(
+        a.len(),
+        b.len(),
+        "Vector dimensions must match for distance calculation"
+    );
+    (KERNELS.squared_l2)(a, b)
+}
+
+/// Compute cosine similarity using precomputed norms
+///
+/// Returns 0.0 if either norm is zero, matching `Vector::cosine_similarity`.
+#[inline]
+pub fn cosine_similarity(a: &[f32], a_norm: f32, b: &[f32], b_norm: f32) -> f32 {
+    if a_norm == 0.0 || b_norm == 0.0 {
+        return 0.0;
+    }
+    dot(a, b) / (a_norm * b_norm)
+}
+
+/// Compute the distance between two slices under `metric`
+///
+/// Norms are only used by the cosine metric.
+#[inline]
+pub fn distance(metric: DistanceMetric, a: &[f32], a_norm: f32, b: &[f32], b_norm: f32) -> f32 {
+    match metric {
+        DistanceMetric::Euclidean => squared_l2(a, b).sqrt(),
+        DistanceMetric::Cosine => 1.0 - cosine_similarity(a, a_norm, b, b_norm),
+        DistanceMetric::DotProduct => -dot(a, b),
+    }
+}
+
+/// Comparable distance used while scanning
+///
+/// Euclidean scans compare squared distances and take the square root only for
+/// the final top-k, which preserves ordering and saves a sqrt per vector.
+#[inline]
+pub(crate) fn scan_distance(metric: DistanceMetric, a: &[f32], a_norm: f32, b: &[f32], b_norm: f32) -> f32 {
+    match metric {
+        DistanceMetric::Euclidean => squared_l2(a, b),
+        _ => distance(metric, a, a_norm, b, b_norm),
+    }
+}
+
+mod scalar {
+    const LANES: usize = 8;
+
+    pub(super) fn dot(a: &[f32], b: &[f32]) -> f32 {
+        let mut acc = [0.0f32; LANES];
+        let chunks = a.len() / LANES;
+
+        for c in 0..chunks {
+            let base = c * LANES;
+            for lane in 0..LANES {
+                acc[lane] += a[base + lane] * b[base + lane];
+            }
+        }
+
+        let mut sum: f32 = acc.iter().sum();
+        for i in chunks * LANES..a.len() {
+            sum += a[i] * b[i];
+        }
+        sum
+    }
+
+    pub(super) fn squared_l2(a: &[f32], b: &[f32]) -> f32 {
+        let mut acc = [0.0f32; LANES];
+        let chunks = a.len() / LANES;
+
+        for c in 0..chunks {
+            let base = c * LANES;
+            for lane in 0..LANES {
+                let diff = a[base + lane] - b[base + lane];
+                acc[lane] += diff * diff;
+            }
+        }
+
+        let mut sum: f32 = acc.iter().sum();
+        for i in chunks * LANES..a.len() {
+            let diff = a[i] - b[i];
+            sum += diff * diff;
+        }
+        sum
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    pub(super) fn dot_avx2(a: &[f32], b: &[f32]) -> f32 {
+        // SAFETY: only selected after `is_x86_feature_detected!("avx2")` and `("fma")`
+        unsafe { dot_avx2_impl(a, b) }
+    }
+
+    pub(super) fn squared_l2_avx2(a: &[f32], b: &[f32]) -> f32 {
+        // SAFETY: only selected after `is_x86_feature_detected!("avx2")` and `("fma")`
+        unsafe { squared_l2_avx2_impl(a, b) }
+    }
+
+    pub(super) fn dot_avx512(a: &[f32], b: &[f32]) -> f32 {
+        // SAFETY: only selected after `is_x86_feature_detected!("avx512f")`
+        unsafe { dot_avx512_impl(a, b) }
+    }
+
+    pub(super) fn squared_l2_avx512(a: &[f32], b: &[f32]) -> f32 {
+        // SAFETY: only selected after `is_x86_feature_detected!("avx512f")`
+        unsafe { squared_l2_avx512_impl(a, b) }
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    unsafe fn hsum256(v: __m256) -> f32 {
+        let hi = _mm256_extractf128_ps(v, 1);
+        let lo = _mm256_castps256_ps128(v);
+        let sum = _mm_add_ps(lo, hi);
+        let shuf = _mm_movehdup_ps(sum);
+        let sums = _mm_add_ps(sum, shuf);
+        let shuf = _mm_movehl_ps(shuf, sums);
+        _mm_cvtss_f32(_mm_add_ss(sums, shuf))
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    unsafe fn dot_avx2_impl(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len();
+        let (pa, pb) = (a.as_ptr(), b.as_ptr());
+        let mut acc0 = _mm256_setzero_ps();
+        let mut acc1 = _mm256_setzero_ps();
+        let mut i = 0;
+
+        while i + 16 <= n {
+            acc0 = _mm256_fmadd_ps(_mm256_loadu_ps(pa.add(i)), _mm256_loadu_ps(pb.add(i)), acc0);
+            acc1 = _mm256_fmadd_ps(
+                _mm256_loadu_ps(pa.add(i + 8)),
+                _mm256_loadu_ps(pb.add(i + 8)),
+                acc1,
+            );
+            i += 16;
+        }
+        if i + 8 <= n {
+            acc0 = _mm256_fmadd_ps(_mm256_loadu_ps(pa.add(i)), _mm256_loadu_ps(pb.add(i)), acc0);
+            i += 8;
+        }
+
+        let mut sum = hsum256(_mm256_add_ps(acc0, acc1));
+        while i < n {
+            sum += *pa.add(i) * *pb.add(i);
+            i += 1;
+        }
+        sum
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    unsafe fn squared_l2_avx2_impl(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len();
+        let (pa, pb) = (a.as_ptr(), b.as_ptr());
+        let mut acc0 = _mm256_setzero_ps();
+        let mut acc1 = _mm256_setzero_ps();
+        let mut i = 0;
+
+        while i + 16 <= n {
+            let d0 = _mm256_sub_ps(_mm256_loadu_ps(pa.add(i)), _mm256_loadu_ps(pb.add(i)));
+            let d1 = _mm256_sub_ps(_mm256_loadu_ps(pa.add(i + 8)), _mm256_loadu_ps(pb.add(i + 8)));
+            acc0 = _mm256_fmadd_ps(d0, d0, acc0);
+            acc1 = _mm256_fmadd_ps(d1, d1, acc1);
+            i += 16;
+        }
+        if i + 8 <= n {
+            let d0 = _mm256_sub_ps(_mm256_loadu_ps(pa.add(i)), _mm256_loadu_ps(pb.add(i)));
+            acc0 = _mm256_fmadd_ps(d0, d0, acc0);
+            i += 8;
+        }
+
+        let mut sum = hsum256(_mm256_add_ps(acc0, acc1));
+        while i < n {
+            let diff = *pa.add(i) - *pb.add(i);
+            sum += diff * diff;
+            i += 1;
+        }
+        sum
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn dot_avx512_impl(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len();
+        let (pa, pb) = (a.as_ptr(), b.as_ptr());
+        let mut acc = _mm512_setzero_ps();
+        let mut i = 0;
+
+        while i + 16 <= n {
+            acc = _mm512_fmadd_ps(_mm512_loadu_ps(pa.add(i)), _mm512_loadu_ps(pb.add(i)), acc);
+            i += 16;
+        }
+        if i < n {
+            // Masked tail load avoids a scalar remainder loop
+            let mask: __mmask16 = (1u16 << (n - i)) - 1;
+            let va = _mm512_maskz_loadu_ps(mask, pa.add(i));
+            let vb = _mm512_maskz_loadu_ps(mask, pb.add(i));
+            acc = _mm512_fmadd_ps(va, vb, acc);
+        }
+
+        _mm512_reduce_add_ps(acc)
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn squared_l2_avx512_impl(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len();
+        let (pa, pb) = (a.as_ptr(), b.as_ptr());
+        let mut acc = _mm512_setzero_ps();
+        let mut i = 0;
+
+        while i + 16 <= n {
+            let d = _mm512_sub_ps(_mm512_loadu_ps(pa.add(i)), _mm512_loadu_ps(pb.add(i)));
+            acc = _mm512_fmadd_ps(d, d, acc);
+            i += 16;
+        }
+        if i < n {
+            let mask: __mmask16 = (1u16 << (n - i)) - 1;
+            let d = _mm512_sub_ps(
+                _mm512_maskz_loadu_ps(mask, pa.add(i)),
+                _mm512_maskz_loadu_ps(mask, pb.add(i)),
+            );
+            acc = _mm512_fmadd_ps(d, d, acc);
+        }
+
+        _mm512_reduce_add_ps(acc)
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "aarch64"))]
+mod neon {
+    use std::arch::aarch64::*;
+
+    pub(super) fn dot(a: &[f32], b: &[f32]) -> f32 {
+        // SAFETY: only selected after `is_aarch64_feature_detected!("neon")`
+        unsafe { dot_impl(a, b) }
+    }
+
+    pub(super) fn squared_l2(a: &[f32], b: &[f32]) -> f32 {
+        // SAFETY: only selected after `is_aarch64_feature_detected!("neon")`
+        unsafe { squared_l2_impl(a, b) }
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn dot_impl(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len();
+        let (pa, pb) = (a.as_ptr(), b.as_ptr());
+        let mut acc0 = vdupq_n_f32(0.0);
+        let mut acc1 = vdupq_n_f32(0.0);
+        let mut i = 0;
+
+        while i + 8 <= n {
+            acc0 = vfmaq_f32(acc0, vld1q_f32(pa.add(i)), vld1q_f32(pb.add(i)));
+            acc1 = vfmaq_f32(acc1, vld1q_f32(pa.add(i + 4)), vld1q_f32(pb.add(i + 4)));
+            i += 8;
+        }
+
+        let mut sum = vaddvq_f32(vaddq_f32(acc0, acc1));
+        while i < n {
+            sum += *pa.add(i) * *pb.add(i);
+            i += 1;
+        }
+        sum
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn squared_l2_impl(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len();
+        let (pa, pb) = (a.as_ptr(), b.as_ptr());
+        let mut acc0 = vdupq_n_f32(0.0);
+        let mut acc1 = vdupq_n_f32(0.0);
+        let mut i = 0;
+
+        while i + 8 <= n {
+            let d0 = vsubq_f32(vld1q_f32(pa.add(i)), vld1q_f32(pb.add(i)));
+            let d1 = vsubq_f32(vld1q_f32(pa.add(i + 4)), vld1q_f32(pb.add(i + 4)));
+            acc0 = vfmaq_f32(acc0, d0, d0);
+            acc1 = vfmaq_f32(acc1, d1, d1);
+            i += 8;
+        }
+
+        let mut sum = vaddvq_f32(vaddq_f32(acc0, acc1));
+        while i < n {
+            let diff = *pa.add(i) - *pb.add(i);
+            sum += diff * diff;
+            i += 1;
+        }
+        sum
+    }
+}
+
+/// A single search result: entity and its distance to the query
+///
+/// Lower distance means closer under the index's `DistanceMetric`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchHit {
+    /// Matching entity
+    pub id: EntityId,
+
+    /// Distance to the query
+    pub distance: f32,
+}
+
+impl Eq for SearchHit {}
+
+impl PartialOrd for SearchHit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SearchHit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then_with(|| self.id.as_uuid().cmp(other.id.as_uuid()))
+    }
+}
+
+/// Bounded max-heap that keeps the `k` closest hits seen so far
+#[derive(Debug, Clone)]
+pub struct TopK {
+    k: usize,
+    heap: BinaryHeap<SearchHit>,
+}
+
+impl TopK {
+    /// Create an empty collector for the `k` closest hits
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            heap: BinaryHeap::with_capacity(k.saturating_add(1).min(4096)),
+        }
+    }
+
+    /// Offer a candidate, keeping it only if it is among the `k` closest
+    #[inline]
+    pub fn push(&mut self, id: EntityId, distance: f32) {
+        if self.k == 0 {
+            return;
+        }
+        if self.heap.len() < self.k {
+            self.heap.push(SearchHit { id, distance });
+        } else if let Some(worst) = self.heap.peek() {
+            if distance < worst.distance {
+                self.heap.pop();
+                self.heap.push(SearchHit { id, distance });
+            }
+        }
+    }
+
+    /// Distance a candidate must beat to enter the heap
+    ///
+    /// Returns `f32::INFINITY` until `k` hits have been collected.
+    pub fn threshold(&self) -> f32 {
+        if self.heap.len() < self.k {
+            f32::INFINITY
+        } else {
+            self.heap.peek().map_or(f32::INFINITY, |h| h.distance)
+        }
+    }
+
+    /// Number of hits currently held
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Whether no hits have been collected
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// Merge another collector into this one
+    pub fn merge(&mut self, other: TopK) {
+        for hit in other.heap {
+            self.push(hit.id, hit.distance);
+        }
+    }
+
+    /// Consume the collector, returning hits ordered from closest to farthest
+    pub fn into_sorted_vec(self) -> Vec<SearchHit> {
+        self.heap.into_sorted_vec()
+    }
+}
+
+/// Contiguous row-major storage for fixed-dimension vectors
+///
+/// Rows are stored back-to-back so scans stream through memory linearly.
+/// Norms are cached per row for cosine distance.
+#[derive(Debug, Clone)]
+pub struct VectorArena {
+    dimensions: usize,
+    data: Vec<f32>,
+    norms: Vec<f32>,
+}
+
+impl VectorArena {
+    /// Create an empty arena for vectors of `dimensions` values
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions,
+            data: Vec::new(),
+            norms: Vec::new(),
+        }
+    }
+
+    /// Number of dimensions per row
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// Number of rows
+    pub fn len(&self) -> usize {
+        self.norms.len()
+    }
+
+    /// Whether the arena holds no rows
+    pub fn is_empty(&self) -> bool {
+        self.norms.is_empty()
+    }
+
+    /// Append a row, returning its position
+    ///
+    /// # Panics
+    /// * If `values.len()` differs from the arena dimensions
+    pub fn push(&mut self, values: &[f32], norm: f32) -> usize {
+        assert_eq!(values.len(), self.dimensions, "Arena row dimension mismatch");
+        self.data.extend_from_slice(values);
+        self.norms.push(norm);
+        self.norms.len() - 1
+    }
+
+    /// Overwrite the row at `position`
+    pub fn set(&mut self, position: usize, values: &[f32], norm: f32) {
+        let start = position * self.dimensions;
+        self.data[start..start + self.dimensions].copy_from_slice(values);
+        self.norms[position] = norm;
+    }
+
+    /// Row values at `position`
+    #[inline]
+    pub fn row(&self, position: usize) -> &[f32] {
+        let start = position * self.dimensions;
+        &self.data[start..start + self.dimensions]
+    }
+
+    /// Cached L2 norm of the row at `position`
+    #[inline]
+    pub fn norm(&self, position: usize) -> f32 {
+        self.norms[position]
+    }
+
+    /// Remove the row at `position` by moving the last row into its place
+    ///
+    /// Returns the previous position of the moved row, if any.
+    pub fn swap_remove(&mut self, position: usize) -> Option<usize> {
+        let last = self.len() - 1;
+        if position != last {
+            let (head, tail) = self.data.split_at_mut(last * self.dimensions);
+            let start = position * self.dimensions;
+            head[start..start + self.dimensions].copy_from_slice(&tail[..self.dimensions]);
+        }
+        self.data.truncate(last * self.dimensions);
+        self.norms.swap_remove(position);
+        (position != last).then_some(last)
+    }
+}
+
+/// Exact brute-force vector index
+///
+/// Every query is compared against every stored vector, so results are exact.
+/// Scans over large arenas are split across threads with per-thread top-k
+/// heaps that are merged at the end.
+///
+/// Complexity: O(n·d) per query, O(1) amortized insert, O(d) remove.
+#[derive(Debug, Clone)]
+pub struct FlatIndex {
+    metric: DistanceMetric,
+    arena: VectorArena,
+    ids: Vec<EntityId>,
+    positions: HashMap<EntityId, usize>,
+    threads: usize,
+    parallel_threshold: usize,
+}
+
+impl FlatIndex {
+    /// Create an empty flat index
+    ///
+    /// # Arguments
+    /// * `dimensions` - Dimensionality of every stored vector
+    /// * `metric` - Distance metric used for search
+    pub fn new(dimensions: usize, metric: DistanceMetric) -> Self {
+        let threads = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+
+        Self {
+            metric,
+            arena: VectorArena::new(dimensions),
+            ids: Vec::new(),
+            positions: HashMap::new(),
+            threads,
+            parallel_threshold: DEFAULT_PARALLEL_THRESHOLD,
+        }
+    }
+
+    /// Configure scan parallelism
+    ///
+    /// # Arguments
+    /// * `threads` - Maximum scan threads (1 disables parallel scans)
+    /// * `parallel_threshold` - Minimum vectors before a scan is parallelized
+    pub fn with_parallelism(mut self, threads: usize, parallel_threshold: usize) -> Self {
+        self.threads = threads.max(1);
+        self.parallel_threshold = parallel_threshold;
+        self
+    }
+
+    /// Distance metric used by this index
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    /// Dimensionality of stored vectors
+    pub fn dimensions(&self) -> usize {
+        self.arena.dimensions()
+    }
+
+    /// Number of indexed vectors
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Whether the index is empty
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Whether `id` is indexed
+    pub fn contains(&self, id: &EntityId) -> bool {
+        self.positions.contains_key(id)
+    }
+
+    /// Stored vector values for `id`
+    pub fn get(&self, id: &EntityId) -> Option<&[f32]> {
+        self.positions.get(id).map(|&pos| self.arena.row(pos))
+    }
+
+    /// Insert or replace the vector for `id`
+    pub fn insert(&mut self, id: EntityId, vector: &Vector) -> Result<()> {
+        self.check_dimensions(vector.values.len())?;
+
+        match self.positions.get(&id) {
+            Some(&pos) => self.arena.set(pos, &vector.values, vector.norm),
+            None => {
+                let pos = self.arena.push(&vector.values, vector.norm);
+                self.ids.push(id);
+                self.positions.insert(id, pos);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove `id` from the index, returning whether it was present
+    pub fn remove(&mut self, id: &EntityId) -> bool {
+        let Some(pos) = self.positions.remove(id) else {
+            return false;
+        };
+
+        self.ids.swap_remove(pos);
+        if self.arena.swap_remove(pos).is_some() {
+            self.positions.insert(self.ids[pos], pos);
+        }
+        true
+    }
+
+    /// Find the `k` nearest vectors to `query`
+    ///
+    /// # Returns
+    /// * Hits ordered from closest to farthest
+    pub fn search(&self, query: &Vector, k: usize) -> Result<Vec<SearchHit>> {
+        self.search_filtered(query, k, |_| true)
+    }
+
+    /// Find the `k` nearest vectors to `query` that satisfy `filter`
+    ///
+    /// The predicate is evaluated before the distance computation, so rejected
+    /// entities cost only a lookup.
+    pub fn search_filtered<F>(&self, query: &Vector, k: usize, filter: F) -> Result<Vec<SearchHit>>
+    where
+        F: Fn(&EntityId) -> bool + Sync,
+    {
+        self.check_dimensions(query.values.len())?;
+        let top = self.scan(&query.values, query.norm, k, &filter);
+        Ok(self.finish(top))
+    }
+
+    /// Exactly re-rank a candidate set, returning its `k` nearest to `query`
+    ///
+    /// Candidates that are not indexed are ignored.
+    pub fn rerank(&self, query: &Vector, candidates: &[EntityId], k: usize) -> Result<Vec<SearchHit>> {
+        self.check_dimensions(query.values.len())?;
+
+        let mut top = TopK::new(k);
+        for id in candidates {
+            if let Some(&pos) = self.positions.get(id) {
+                let d = scan_distance(
+                    self.metric,
+                    &query.values,
+                    query.norm,
+                    self.arena.row(pos),
+                    self.arena.norm(pos),
+                );
+                top.push(*id, d);
+            }
+        }
+        Ok(self.finish(top))
+    }
+
+    /// Run several queries in one pass over the arena
+    ///
+    /// Each stored row is loaded once and compared against every query while it
+    /// is still in cache, which is faster than issuing the queries one by one.
+    pub fn batch_search(&self, queries: &[Vector], k: usize) -> Result<Vec<Vec<SearchHit>>> {
+        for query in queries {
+            self.check_dimensions(query.values.len())?;
+        }
+
+        let tops = self.batch_scan(queries, k);
+        Ok(tops.into_iter().map(|top| self.finish(top)).collect())
+    }
+
+    fn check_dimensions(&self, actual: usize) -> Result<()> {
+        if actual != self.dimensions() {
+            return Err(MemorySubstrateError::DimensionMismatch {
+                expected: self.dimensions(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    fn worker_count(&self) -> usize {
+        if self.len() < self.parallel_threshold {
+            1
+        } else {
+            self.threads.min(self.len()).max(1)
+        }
+    }
+
+    fn scan<F>(&self, query: &[f32], query_norm: f32, k: usize, filter: &F) -> TopK
+    where
+        F: Fn(&EntityId) -> bool + Sync,
+    {
+        let workers = self.worker_count();
+        if workers == 1 {
+            return self.scan_range(query, query_norm, k, filter, 0, self.len());
+        }
+
+        let chunk = self.len().div_ceil(workers);
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|w| {
+                    let start = w * chunk;
+                    let end = ((w + 1) * chunk).min(self.len());
+                    scope.spawn(move || self.scan_range(query, query_norm, k, filter, start, end))
+                })
+                .collect();
+
+            let mut top = TopK::new(k);
+            for handle in handles {
+                top.merge(handle.join().expect("Flat index scan worker panicked"));
+            }
+            top
+        })
+    }
+
+    fn scan_range<F>(
+        &self,
+        query: &[f32],
+        query_norm: f32,
+        k: usize,
+        filter: &F,
+        start: usize,
+        end: usize,
+    ) -> TopK
+    where
+        F: Fn(&EntityId) -> bool + Sync,
+    {
+        let mut top = TopK::new(k);
+        for pos in start..end {
+            let id = &self.ids[pos];
+            if !filter(id) {
+                continue;
+            }
+            let d = scan_distance(
+                self.metric,
+                query,
+                query_norm,
+                self.arena.row(pos),
+                self.arena.norm(pos),
+            );
+            top.push(*id, d);
+        }
+        top
+    }
+
+    fn batch_scan(&self, queries: &[Vector], k: usize) -> Vec<TopK> {
+        let workers = self.worker_count();
+        if workers == 1 {
+            return self.batch_scan_range(queries, k, 0, self.len());
+        }
+
+        let chunk = self.len().div_ceil(workers);
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|w| {
+                    let start = w * chunk;
+                    let end = ((w + 1) * chunk).min(self.len());
+                    scope.spawn(move || self.batch_scan_range(queries, k, start, end))
+                })
+                .collect();
+
+            let mut tops: Vec<TopK> = queries.iter().map(|_| TopK::new(k)).collect();
+            for handle in handles {
+                let partial = handle.join().expect("Flat index scan worker panicked");
+                for (top, part) in tops.iter_mut().zip(partial) {
+                    top.merge(part);
+                }
+            }
+            tops
+        })
+    }
+
+    fn batch_scan_range(&self, queries: &[Vector], k: usize, start: usize, end: usize) -> Vec<TopK> {
+        let mut tops: Vec<TopK> = queries.iter().map(|_| TopK::new(k)).collect();
+        for pos in start..end {
+            let row = self.arena.row(pos);
+            let norm = self.arena.norm(pos);
+            for (query, top) in queries.iter().zip(tops.iter_mut()) {
+                let d = scan_distance(self.metric, &query.values, query.norm, row, norm);
+                top.push(self.ids[pos], d);
+            }
+        }
+        tops
+    }
+
+    fn finish(&self, top: TopK) -> Vec<SearchHit> {
+        let mut hits = top.into_sorted_vec();
+        if self.metric == DistanceMetric::Euclidean {
+            for hit in &mut hits {
+                hit.distance = hit.distance.sqrt();
+            }
+        }
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::test_util::random_vectors;
+
+    fn brute_force(
+        metric: DistanceMetric,
+        data: &[(EntityId, Vector)],
+        query: &Vector,
+        k: usize,
+    ) -> Vec<EntityId> {
+        let mut scored: Vec<(f32, EntityId)> = data
+            .iter()
+            .map(|(id, v)| (metric.distance(query, v), *id))
+            .collect();
+        scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+        scored.into_iter().take(k).map(|(_, id)| id).collect()
+    }
+
+    #[test]
+    fn test_kernels_match_scalar() {
+        // Odd dimensions exercise the remainder handling of every kernel
+        for dims in [1, 7, 8, 15, 16, 33, 128, 130] {
+            let vs = random_vectors(2, dims, dims as u64);
+            let (a, b) = (&vs[0].values, &vs[1].values);
+
+            assert!((dot(a, b) - scalar::dot(a, b)).abs() < 1e-3);
+            assert!((squared_l2(a, b) - scalar::squared_l2(a, b)).abs() < 1e-3);
+            assert!((dot(a, b) - vs[0].dot(&vs[1])).abs() < 1e-3);
+        }
+    }
+
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    #[test]
+    fn test_every_supported_level_matches_scalar() {
+        let mut levels = vec![SimdLevel::Scalar];
+        if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+            levels.push(SimdLevel::Avx2);
+        }
+        if is_x86_feature_detected!("avx512f") {
+            levels.push(SimdLevel::Avx512);
+        }
+
+        for level in levels {
+            let kernels = Kernels::for_level(level);
+            assert_eq!(kernels.level, level);
+            for dims in [3, 8, 17, 31, 64, 100] {
+                let vs = random_vectors(2, dims, 17 + dims as u64);
+                let (a, b) = (&vs[0].values, &vs[1].values);
+                assert!(((kernels.dot)(a, b) - scalar::dot(a, b)).abs() < 1e-3);
+                assert!(((kernels.squared_l2)(a, b) - scalar::squared_l2(a, b)).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn test_distance_matches_vector_methods() {
+        let vs = random_vectors(2, 64, 7);
+        for metric in [DistanceMetric::Euclidean, DistanceMetric::Cosine, DistanceMetric::DotProduct] {
+            let fast = distance(metric, &vs[0].values, vs[0].norm, &vs[1].values, vs[1].norm);
+            assert!((fast - metric.distance(&vs[0], &vs[1])).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_cosine_zero_norm() {
+        let zero = Vector::zeros(4);
+        let v = Vector::new(vec![1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(cosine_similarity(&zero.values, zero.norm, &v.values, v.norm), 0.0);
+    }
+
+    #[test]
+    fn test_top_k_keeps_closest() {
+        let mut top = TopK::new(3);
+        let ids: Vec<EntityId> = (0..6).map(|_| EntityId::new()).collect();
+        for (i, id) in ids.iter().enumerate() {
+            top.push(*id, (6 - i) as f32);
+        }
+
+        let hits = top.into_sorted_vec();
+        let distances: Vec<f32> = hits.iter().map(|h| h.distance).collect();
+        assert_eq!(distances, vec![1.0, 2.0, 3.0]);
+        assert_eq!(hits[0].id, ids[5]);
+    }
+
+    #[test]
+    fn test_flat_index_exact_results() {
+        let vectors = random_vectors(500, 32, 42);
+        let queries = random_vectors(5, 32, 1234);
+
+        for metric in [DistanceMetric::Euclidean, DistanceMetric::Cosine, DistanceMetric::DotProduct] {
+            let mut index = FlatIndex::new(32, metric);
+            let data: Vec<(EntityId, Vector)> =
+                vectors.iter().map(|v| (EntityId::new(), v.clone())).collect();
+            for (id, v) in &data {
+                index.insert(*id, v).unwrap();
+            }
+
+            for query in &queries {
+                let hits = index.search(query, 10).unwrap();
+                let got: Vec<EntityId> = hits.iter().map(|h| h.id).collect();
+                assert_eq!(got, brute_force(metric, &data, query, 10));
+                assert!(hits.windows(2).all(|w| w[0].distance <= w[1].distance));
+            }
+        }
+    }
+
+    #[test]
+    fn test_parallel_scan_matches_sequential() {
+        let vectors = random_vectors(2000, 16, 99);
+        let query = &random_vectors(1, 16, 5)[0];
+
+        let mut sequential = FlatIndex::new(16, DistanceMetric::Euclidean).with_parallelism(1, 0);
+        let mut parallel = FlatIndex::new(16, DistanceMetric::Euclidean).with_parallelism(4, 100);
+        for v in &vectors {
+            let id = EntityId::new();
+            sequential.insert(id, v).unwrap();
+            parallel.insert(id, v).unwrap();
+        }
+
+        assert_eq!(
+            sequential.search(query, 25).unwrap(),
+            parallel.search(query, 25).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_batch_search_matches_single() {
+        let vectors = random_vectors(300, 24, 11);
+        let queries = random_vectors(4, 24, 12);
+
+        let mut index = FlatIndex::new(24, DistanceMetric::Cosine).with_parallelism(3, 50);
+        for v in &vectors {
+            index.insert(EntityId::new(), v).unwrap();
+        }
+
+        let batched = index.batch_search(&queries, 7).unwrap();
+        for (query, hits) in queries.iter().zip(batched) {
+            assert_eq!(hits, index.search(query, 7).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_filtered_search_and_rerank() {
+        let vectors = random_vectors(100, 8, 3);
+        let mut index = FlatIndex::new(8, DistanceMetric::Euclidean);
+        let ids: Vec<EntityId> = vectors
+            .iter()
+            .map(|v| {
+                let id = EntityId::new();
+                index.insert(id, v).unwrap();
+                id
+            })
+            .collect();
+
+        let allowed: std::collections::HashSet<EntityId> = ids.iter().step_by(3).copied().collect();
+        let hits = index
+            .search_filtered(&vectors[1], 5, |id| allowed.contains(id))
+            .unwrap();
+        assert_eq!(hits.len(), 5);
+        assert!(hits.iter().all(|h| allowed.contains(&h.id)));
+
+        // Re-ranking a candidate set containing the query itself puts it first
+        let reranked = index.rerank(&vectors[4], &ids[..10], 3).unwrap();
+        assert_eq!(reranked[0].id, ids[4]);
+        assert!(reranked[0].distance.abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_upsert_and_remove() {
+        let mut index = FlatIndex::new(2, DistanceMetric::Euclidean);
+        let a = EntityId::new();
+        let b = EntityId::new();
+        let c = EntityId::new();
+        index.insert(a, &Vector::new(vec![0.0, 0.0])).unwrap();
+        index.insert(b, &Vector::new(vec![1.0, 0.0])).unwrap();
+        index.insert(c, &Vector::new(vec![5.0, 5.0])).unwrap();
+
+        // Replacing keeps the count and moves the vector
+        index.insert(a, &Vector::new(vec![9.0, 9.0])).unwrap();
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.get(&a), Some(&[9.0, 9.0][..]));
+
+        // Removing from the middle relocates the last row
+        assert!(index.remove(&b));
+        assert!(!index.remove(&b));
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.get(&c), Some(&[5.0, 5.0][..]));
+
+        let hits = index.search(&Vector::new(vec![5.0, 5.0]), 1).unwrap();
+        assert_eq!(hits[0].id, c);
+    }
+
+    #[test]
+    fn test_dimension_mismatch() {
+        let mut index = FlatIndex::new(4, DistanceMetric::Cosine);
+        let result = index.insert(EntityId::new(), &Vector::new(vec![1.0, 2.0]));
+        assert!(matches!(
+            result,
+            Err(MemorySubstrateError::DimensionMismatch { expected: 4, actual: 2 })
+        ));
+        assert!(index.search(&Vector::new(vec![1.0]), 1).is_err());
+    }
+}
@@ -0,0 +1,22 @@
+//! Deterministic test data shared by the index tests
+
+use crate::core::Vector;
+
+/// Xorshift generator of values in [-1, 1), reproducible from `seed`
+pub fn uniform(seed: u64) -> impl FnMut() -> f64 {
+    let mut state = seed.max(1);
+    move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % 2000) as f64 / 1000.0 - 1.0
+    }
+}
+
+/// `count` pseudo-random vectors with coordinates in [-1, 1)
+pub fn random_vectors(count: usize, dimensions: usize, seed: u64) -> Vec<Vector> {
+    let mut next = uniform(seed);
+    (0..count)
+        .map(|_| Vector::new((0..dimensions).map(|_| next() as f32).collect()))
+        .collect()
+}
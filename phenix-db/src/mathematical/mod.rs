@@ -1,6 +1,9 @@
 // Mathematical foundation modules
 //
 // This module will be fully implemented in Phase 2.
-// Placeholder for now to allow compilation.
+// Implemented so far:
+// - Polynomial: Polynomial embeddings and evaluation (Al-Karaji, Euler)
+// - Entropy: Shannon entropy of probability distributions (Shannon)
 
-// Placeholder - will be implemented in Phase 2
+pub mod polynomial;
+pub mod entropy;
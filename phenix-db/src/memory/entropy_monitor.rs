@@ -1,19 +1,108 @@
 //! Entropy Monitor implementation
+//!
+//! Tracks the normalized access entropy of every collection against an
+//! exponential moving average of its own history. A sharp fall below that
+//! baseline means reads are concentrating on a few entities, which is worth
+//! surfacing before it turns into a hot spot.
+
+use crate::core::collection::CollectionStats;
+use std::collections::BTreeMap;
+
+/// Weight of the newest sample in the baseline
+const SMOOTHING: f64 = 0.2;
+
+/// Fall below the baseline reported as a concentration
+const DROP_ALERT: f64 = 0.25;
 
 /// Entropy Monitor for information density optimization
+#[derive(Debug, Clone, Default)]
 pub struct EntropyMonitor {
-    // TODO: Implement Entropy Monitor data structures
+    baselines: BTreeMap<String, f64>,
+}
+
+/// A collection whose reads have concentrated since the last samples
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntropyDrop {
+    /// Collection name
+    pub collection: String,
+
+    /// Latest normalized access entropy
+    pub entropy: f64,
+
+    /// Baseline before this sample
+    pub baseline: f64,
 }
 
 impl EntropyMonitor {
     /// Create a new Entropy Monitor
     pub fn new() -> Self {
-        Self {}
+        Self::default()
+    }
+
+    /// Fold a sample of every collection into the baselines
+    ///
+    /// Collections nobody has read yet are skipped, and baselines of
+    /// collections missing from `stats` are forgotten.
+    pub fn observe(&mut self, stats: &[CollectionStats]) -> Vec<EntropyDrop> {
+        self.baselines.retain(|name, _| stats.iter().any(|stats| &stats.name == name));
+        let mut drops = Vec::new();
+        for sample in stats.iter().filter(|stats| stats.access_entropy > 0.0) {
+            let entropy = sample.access_entropy;
+            match self.baselines.get_mut(&sample.name) {
+                Some(baseline) => {
+                    if *baseline - entropy > DROP_ALERT {
+                        drops.push(EntropyDrop {
+                            collection: sample.name.clone(),
+                            entropy,
+                            baseline: *baseline,
+                        });
+                    }
+                    *baseline += SMOOTHING * (entropy - *baseline);
+                }
+                None => {
+                    self.baselines.insert(sample.name.clone(), entropy);
+                }
+            }
+        }
+        drops
+    }
+
+    /// Baseline access entropy of a collection, once it has been read
+    pub fn baseline(&self, collection: &str) -> Option<f64> {
+        self.baselines.get(collection).copied()
     }
 }
 
-impl Default for EntropyMonitor {
-    fn default() -> Self {
-        Self::new()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(name: &str, access_entropy: f64) -> CollectionStats {
+        CollectionStats {
+            name: name.to_string(),
+            entity_count: 10,
+            tiers: Default::default(),
+            edge_count: 0,
+            edge_labels: 0,
+            access_entropy,
+        }
+    }
+
+    #[test]
+    fn test_reports_concentrating_reads() {
+        let mut monitor = EntropyMonitor::new();
+        assert!(monitor.observe(&[sample("docs", 0.9), sample("idle", 0.0)]).is_empty());
+        assert_eq!(monitor.baseline("docs"), Some(0.9));
+        assert_eq!(monitor.baseline("idle"), None);
+
+        assert!(monitor.observe(&[sample("docs", 0.8)]).is_empty());
+        let baseline = monitor.baseline("docs").unwrap();
+        assert!((baseline - 0.88).abs() < 1e-9);
+
+        let drops = monitor.observe(&[sample("docs", 0.3)]);
+        assert_eq!(drops, vec![EntropyDrop { collection: "docs".into(), entropy: 0.3, baseline }]);
+
+        monitor.observe(&[]);
+        assert_eq!(monitor.baseline("docs"), None);
     }
 }
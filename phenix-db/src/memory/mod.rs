@@ -1,10 +1,17 @@
 // Memory substrate components
 //
 // This module will be fully implemented in Phases 3-6.
-// Placeholder for now to allow compilation.
+// Implemented so far:
+// - PGM: Edge reinforcement on co-access, pruning and renormalization
+// - Entropy monitor: Access entropy baselines and concentration alerts
+
+pub mod pgm;
+pub mod entropy_monitor;
+
+pub use entropy_monitor::{EntropyDrop, EntropyMonitor};
+pub use pgm::ProbabilisticGraphMemory;
 
 // Placeholder types for lib.rs re-exports
 pub struct RecursivePolynomialIndex;
-pub struct ProbabilisticGraphMemory;
 pub struct BellmanOptimizer;
 pub struct KolmogorovCompressionEngine;
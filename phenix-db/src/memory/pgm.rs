@@ -1,14 +1,82 @@
 //! Probabilistic Graph Memory (PGM) implementation
+//!
+//! The outgoing edges of an entity form a probability distribution over
+//! where a traversal goes next. Edges to entities read together with the
+//! source are reinforced, edges that have faded below the pruning threshold
+//! and seen no use for the inactivity period are dropped, and the rest are
+//! renormalized whenever their sum drifts from 1 beyond the tolerance.
+
+use crate::core::config::PGMConfig;
+use crate::core::{AccessStatistics, Edge};
+
+/// Milliseconds per day, the unit of the inactivity period
+const MS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
 
 /// Probabilistic Graph Memory
+#[derive(Debug, Clone)]
 pub struct ProbabilisticGraphMemory {
-    // TODO: Implement PGM data structures
+    config: PGMConfig,
 }
 
 impl ProbabilisticGraphMemory {
-    /// Create a new PGM
+    /// Create a new PGM with default parameters
     pub fn new() -> Self {
-        Self {}
+        Self::with_config(PGMConfig::default())
+    }
+
+    /// Create a PGM with the given learning and pruning parameters
+    pub fn with_config(config: PGMConfig) -> Self {
+        Self { config }
+    }
+
+    /// Learning and pruning parameters
+    pub fn config(&self) -> &PGMConfig {
+        &self.config
+    }
+
+    /// Prune faded, inactive edges and renormalize the rest at `now`
+    /// (Unix epoch milliseconds), returning whether anything changed
+    ///
+    /// Edges whose probabilities sum to zero are given a uniform
+    /// distribution.
+    pub fn normalize(&self, edges: &mut Vec<Edge>, now: u64) -> bool {
+        let inactivity = self.config.inactivity_period_days.saturating_mul(MS_PER_DAY);
+        let before = edges.len();
+        edges.retain(|edge| {
+            edge.probability() >= self.config.pruning_threshold
+                || now.saturating_sub(edge.get_last_accessed()) < inactivity
+        });
+        let pruned = edges.len() != before;
+        if edges.is_empty() {
+            return pruned;
+        }
+
+        let sum: f32 = edges.iter().map(Edge::probability).sum();
+        if (sum - 1.0).abs() <= self.config.probability_tolerance {
+            return pruned;
+        }
+        let uniform = 1.0 / edges.len() as f32;
+        for edge in edges.iter() {
+            edge.set_probability(if sum > 0.0 { edge.probability() / sum } else { uniform });
+        }
+        true
+    }
+
+    /// Reinforce edges to entities co-accessed with their source since the
+    /// edge was last reinforced, returning whether any edge changed
+    ///
+    /// Each reinforced edge moves `learning_rate` of the way towards 1;
+    /// [`normalize`](Self::normalize) afterwards restores the distribution.
+    pub fn reinforce(&self, edges: &[Edge], stats: &AccessStatistics, learning_rate: f32) -> bool {
+        let mut changed = false;
+        for edge in edges {
+            if stats.last_access > edge.get_last_accessed() && stats.co_access_entities.contains(&edge.target_id) {
+                edge.update_probability(1.0, learning_rate);
+                edge.record_access();
+                changed = true;
+            }
+        }
+        changed
     }
 }
 
@@ -17,3 +85,64 @@ impl Default for ProbabilisticGraphMemory {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::EntityId;
+    use std::sync::atomic::Ordering;
+
+    fn edge(source: EntityId, target: EntityId, probability: f32, last_accessed: u64) -> Edge {
+        let edge = Edge::new(source, target, "related".to_string(), probability, None);
+        edge.last_accessed.store(last_accessed, Ordering::SeqCst);
+        edge
+    }
+
+    #[test]
+    fn test_normalize_prunes_and_restores_the_distribution() {
+        let pgm = ProbabilisticGraphMemory::new();
+        let now = 100 * MS_PER_DAY;
+        let source = EntityId::new();
+        let mut edges = vec![
+            edge(source, EntityId::new(), 0.6, now),
+            edge(source, EntityId::new(), 0.6, now),
+            // Faded but recently used: kept
+            edge(source, EntityId::new(), 0.001, now - MS_PER_DAY),
+            // Faded and unused for the inactivity period: pruned
+            edge(source, EntityId::new(), 0.001, now - 31 * MS_PER_DAY),
+        ];
+        assert!(pgm.normalize(&mut edges, now));
+        assert_eq!(edges.len(), 3);
+        let sum: f32 = edges.iter().map(Edge::probability).sum();
+        assert!((sum - 1.0).abs() < 1e-6);
+        assert!((edges[0].probability() - edges[1].probability()).abs() < 1e-6);
+
+        // Already normalized: nothing to do
+        assert!(!pgm.normalize(&mut edges, now));
+
+        let mut silent = vec![edge(source, EntityId::new(), 0.0, now), edge(source, EntityId::new(), 0.0, now)];
+        assert!(pgm.normalize(&mut silent, now));
+        assert!(silent.iter().all(|edge| edge.probability() == 0.5));
+    }
+
+    #[test]
+    fn test_reinforce_follows_new_co_access() {
+        let pgm = ProbabilisticGraphMemory::new();
+        let source = EntityId::new();
+        let (read_with, unrelated) = (EntityId::new(), EntityId::new());
+        let edges = vec![edge(source, read_with, 0.5, 1000), edge(source, unrelated, 0.5, 1000)];
+        let stats = AccessStatistics {
+            last_access: 2000,
+            co_access_entities: vec![read_with],
+            ..AccessStatistics::new()
+        };
+
+        assert!(pgm.reinforce(&edges, &stats, 0.1));
+        assert!((edges[0].probability() - 0.55).abs() < 1e-6);
+        assert_eq!(edges[1].probability(), 0.5);
+        assert_eq!(edges[0].get_access_count(), 1);
+
+        // The same co-access is not counted twice
+        assert!(!pgm.reinforce(&edges, &stats, 0.1));
+    }
+}
@@ -1 +1,322 @@
-// TODO: Implement Prometheus metrics
+//! Prometheus metrics
+//!
+//! [`Metrics`] collects pass counters from the background workers and the
+//! latest collection statistics sampled by the entropy monitor, so a scrape
+//! never walks the collections itself. [`serve`] exposes them in the
+//! Prometheus text format at `GET /metrics`.
+
+use crate::api::service::{ApiService, ClusterStatus};
+use crate::core::collection::CollectionStats;
+use crate::core::error::{MemorySubstrateError, Result};
+use hyper::header::CONTENT_TYPE;
+use hyper::server::conn::AddrIncoming;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+/// Content type of the text exposition format
+const TEXT_FORMAT: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Outcomes of one background worker's passes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorkerCounters {
+    /// Completed passes
+    pub passes: u64,
+
+    /// Entities changed across all passes
+    pub changes: u64,
+
+    /// Passes that failed
+    pub failures: u64,
+}
+
+/// Collection statistics with the entropy baseline tracked for them
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectionSample {
+    /// Latest statistics
+    pub stats: CollectionStats,
+
+    /// Access entropy baseline, once the collection has been read
+    pub entropy_baseline: Option<f64>,
+}
+
+/// Server metrics registry
+#[derive(Debug, Default)]
+pub struct Metrics {
+    workers: Mutex<BTreeMap<&'static str, WorkerCounters>>,
+    collections: Mutex<Vec<CollectionSample>>,
+}
+
+impl Metrics {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a pass of `worker` that changed `outcome` entities, or failed
+    pub fn record_pass<E>(&self, worker: &'static str, outcome: &std::result::Result<usize, E>) {
+        let mut workers = self.workers.lock();
+        let counters = workers.entry(worker).or_default();
+        counters.passes += 1;
+        match outcome {
+            Ok(changes) => counters.changes += *changes as u64,
+            Err(_) => counters.failures += 1,
+        }
+    }
+
+    /// Counters of `worker`, if it has run
+    pub fn worker(&self, worker: &str) -> Option<WorkerCounters> {
+        self.workers.lock().get(worker).copied()
+    }
+
+    /// Replace the collection samples
+    pub fn set_collections(&self, samples: Vec<CollectionSample>) {
+        *self.collections.lock() = samples;
+    }
+
+    /// Render every metric in the Prometheus text format
+    pub fn render(&self, status: &ClusterStatus) -> String {
+        let mut out = String::new();
+        let version = escape(&status.version);
+        family(&mut out, "phenix_build_info", "gauge", "Server version", [(format!("version=\"{}\"", version), 1.0)]);
+        family(
+            &mut out,
+            "phenix_uptime_seconds",
+            "gauge",
+            "Time since the server started",
+            [(String::new(), status.uptime_ms as f64 / 1000.0)],
+        );
+        family(
+            &mut out,
+            "phenix_collections",
+            "gauge",
+            "Number of collections",
+            [(String::new(), status.collections as f64)],
+        );
+
+        let collections = self.collections.lock();
+        let per_collection = |value: fn(&CollectionSample) -> Option<f64>| {
+            collections
+                .iter()
+                .filter_map(move |sample| {
+                    let labels = format!("collection=\"{}\"", escape(&sample.stats.name));
+                    value(sample).map(|value| (labels, value))
+                })
+                .collect::<Vec<_>>()
+        };
+        let tiers = collections.iter().flat_map(|sample| {
+            let name = escape(&sample.stats.name);
+            let tiers = sample.stats.tiers;
+            [("hot", tiers.hot), ("warm", tiers.warm), ("cold", tiers.cold)]
+                .map(|(tier, count)| (format!("collection=\"{}\",tier=\"{}\"", name, tier), count as f64))
+        });
+        family(&mut out, "phenix_entities", "gauge", "Entities per collection and memory tier", tiers);
+        family(
+            &mut out,
+            "phenix_edges",
+            "gauge",
+            "Outgoing edges per collection",
+            per_collection(|sample| Some(sample.stats.edge_count as f64)),
+        );
+        family(
+            &mut out,
+            "phenix_edge_labels",
+            "gauge",
+            "Distinct edge labels per collection",
+            per_collection(|sample| Some(sample.stats.edge_labels as f64)),
+        );
+        family(
+            &mut out,
+            "phenix_access_entropy",
+            "gauge",
+            "Normalized Shannon entropy of reads over entities",
+            per_collection(|sample| Some(sample.stats.access_entropy)),
+        );
+        family(
+            &mut out,
+            "phenix_access_entropy_baseline",
+            "gauge",
+            "Moving average of the access entropy",
+            per_collection(|sample| sample.entropy_baseline),
+        );
+        drop(collections);
+
+        let workers = self.workers.lock();
+        let per_worker = |value: fn(&WorkerCounters) -> u64| {
+            workers
+                .iter()
+                .map(|(name, counters)| (format!("worker=\"{}\"", name), value(counters) as f64))
+                .collect::<Vec<_>>()
+        };
+        family(
+            &mut out,
+            "phenix_worker_passes_total",
+            "counter",
+            "Background worker passes",
+            per_worker(|counters| counters.passes),
+        );
+        family(
+            &mut out,
+            "phenix_worker_changes_total",
+            "counter",
+            "Entities changed by background workers",
+            per_worker(|counters| counters.changes),
+        );
+        family(
+            &mut out,
+            "phenix_worker_failures_total",
+            "counter",
+            "Failed background worker passes",
+            per_worker(|counters| counters.failures),
+        );
+        out
+    }
+}
+
+/// Append one metric family; empty families are left out
+fn family(out: &mut String, name: &str, kind: &str, help: &str, samples: impl IntoIterator<Item = (String, f64)>) {
+    let mut samples = samples.into_iter().peekable();
+    if samples.peek().is_none() {
+        return;
+    }
+    // Writing to a String cannot fail
+    let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+    for (labels, value) in samples {
+        let _ = if labels.is_empty() {
+            writeln!(out, "{} {}", name, value)
+        } else {
+            writeln!(out, "{}{{{}}} {}", name, labels, value)
+        };
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Serve `GET /metrics` on `addr` until `shutdown` resolves
+pub async fn serve(
+    metrics: Arc<Metrics>,
+    service: Arc<ApiService>,
+    addr: &str,
+    shutdown: impl Future<Output = ()> + Send,
+) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    serve_with_listener(metrics, service, listener, shutdown).await
+}
+
+/// Serve on an already bound listener until `shutdown` resolves
+pub async fn serve_with_listener(
+    metrics: Arc<Metrics>,
+    service: Arc<ApiService>,
+    listener: TcpListener,
+    shutdown: impl Future<Output = ()> + Send,
+) -> Result<()> {
+    let address = listener.local_addr()?;
+    let incoming = AddrIncoming::from_listener(listener)
+        .map_err(|error| MemorySubstrateError::Internal(format!("cannot accept on {}: {}", address, error)))?;
+    let make_service = make_service_fn(move |_connection| {
+        let metrics = metrics.clone();
+        let service = service.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                let response = respond(&metrics, &service, &request);
+                async move { Ok::<_, Infallible>(response) }
+            }))
+        }
+    });
+
+    tracing::info!(%address, "metrics endpoint listening");
+    hyper::Server::builder(incoming)
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown)
+        .await
+        .map_err(|error| MemorySubstrateError::Internal(format!("metrics endpoint failed: {}", error)))?;
+    tracing::info!(%address, "metrics endpoint stopped");
+    Ok(())
+}
+
+fn respond(metrics: &Metrics, service: &ApiService, request: &Request<Body>) -> Response<Body> {
+    let (status, content_type, body) = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/metrics") => (StatusCode::OK, TEXT_FORMAT, metrics.render(&service.cluster_status())),
+        (_, "/metrics") => (StatusCode::METHOD_NOT_ALLOWED, "text/plain", "use GET\n".to_string()),
+        _ => (StatusCode::NOT_FOUND, "text/plain", "not found; metrics are served at /metrics\n".to_string()),
+    };
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, content_type.parse().expect("static content type"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::collection::TierSizes;
+    use crate::core::NodeId;
+    use crate::storage::catalog::Catalog;
+
+    #[tokio::test]
+    async fn test_renders_and_serves_prometheus_text() {
+        let metrics = Arc::new(Metrics::new());
+        metrics.record_pass("tiering", &Ok::<_, ()>(3));
+        metrics.record_pass("tiering", &Err(()));
+        metrics.set_collections(vec![CollectionSample {
+            stats: CollectionStats {
+                name: "docs".into(),
+                entity_count: 6,
+                tiers: TierSizes { hot: 1, warm: 2, cold: 3 },
+                edge_count: 4,
+                edge_labels: 2,
+                access_entropy: 0.5,
+            },
+            entropy_baseline: None,
+        }]);
+        assert_eq!(
+            metrics.worker("tiering"),
+            Some(WorkerCounters { passes: 2, changes: 3, failures: 1 })
+        );
+
+        let service = Arc::new(ApiService::new(NodeId::new(), Catalog::in_memory(), 4));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let shutdown = tokio_util::sync::CancellationToken::new();
+        let server = tokio::spawn(serve_with_listener(
+            metrics,
+            service,
+            listener,
+            shutdown.clone().cancelled_owned(),
+        ));
+
+        let client = hyper::Client::new();
+        let response = client.get(format!("http://{}/metrics", address).parse().unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        for line in [
+            "# TYPE phenix_entities gauge",
+            "phenix_entities{collection=\"docs\",tier=\"cold\"} 3",
+            "phenix_access_entropy{collection=\"docs\"} 0.5",
+            "phenix_worker_passes_total{worker=\"tiering\"} 2",
+            "phenix_worker_failures_total{worker=\"tiering\"} 1",
+            "phenix_collections 0",
+        ] {
+            assert!(text.lines().any(|candidate| candidate == line), "missing {}\n{}", line, text);
+        }
+        // No baseline yet, so the family is left out entirely
+        assert!(!text.contains("phenix_access_entropy_baseline"));
+
+        let missing = client.get(format!("http://{}/", address).parse().unwrap()).await.unwrap();
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+
+        shutdown.cancel();
+        server.await.unwrap().unwrap();
+    }
+}
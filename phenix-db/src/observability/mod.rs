@@ -1,6 +1,9 @@
 // Observability
 //
 // This module will be fully implemented in Phase 21.
-// Placeholder for now to allow compilation.
+// Implemented so far:
+// - Metrics: Prometheus text endpoint for collection and worker statistics
 
-// Placeholder - will be implemented in Phase 21
+pub mod metrics;
+
+pub use metrics::Metrics;
@@ -0,0 +1,583 @@
+//! Bulk import and export
+//!
+//! Loads and dumps whole collections in three file formats:
+//! - NDJSON: one entity per line, shaped like the REST entity document
+//! - Parquet: an id column, a list-of-float vector column and one column per
+//!   top-level metadata field
+//! - NumPy: a 2-D float `.npy` array of vectors plus an optional NDJSON
+//!   sidecar with the id, metadata, edges and tier of each row
+//!
+//! Loading does not log per-entity upserts. Decoded rows are validated
+//! against the collection and gathered into segments; each segment is
+//! logged as a single write-ahead log record (one fsync) and committed
+//! under one timestamp. The index is not built from a segment directly:
+//! its entities are still inserted one by one, as any commit does. Rows that cannot be decoded or do
+//! not fit the collection are rejected and reported without stopping the
+//! load; a file that cannot be read at all fails it, keeping the segments
+//! committed so far.
+
+pub mod ndjson;
+pub mod npy;
+pub mod parquet;
+mod thrift;
+
+use crate::core::config::BulkConfig;
+use crate::core::error::{MemorySubstrateError, Result};
+use crate::core::{Edge, Entity, EntityId, MemoryTier, Vector};
+use crate::storage::catalog::Catalog;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// File read by a load or written by an export
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BulkFile {
+    /// Newline-delimited JSON entities
+    Ndjson(PathBuf),
+
+    /// Parquet table
+    Parquet(PathBuf),
+
+    /// NumPy vector array with an optional NDJSON sidecar
+    Npy {
+        /// 2-D float array, one row per entity
+        vectors: PathBuf,
+        /// One line per array row; without it ids are generated on load
+        /// and only vectors are exported
+        sidecar: Option<PathBuf>,
+    },
+}
+
+impl BulkFile {
+    /// Name of the format, for reports and errors
+    pub fn format(&self) -> &'static str {
+        match self {
+            Self::Ndjson(_) => ndjson::FORMAT,
+            Self::Parquet(_) => parquet::FORMAT,
+            Self::Npy { .. } => npy::FORMAT,
+        }
+    }
+}
+
+/// Outgoing edge of a bulk row
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BulkEdge {
+    /// Target entity
+    pub target_id: EntityId,
+
+    /// Relationship label
+    pub label: String,
+
+    /// Static weight in [0, 1]
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+
+    /// Edge metadata
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+/// One entity as exchanged by the bulk formats
+///
+/// Unknown fields are ignored, so entities exported by the REST API load
+/// unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BulkRow {
+    /// Entity id; generated when absent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<EntityId>,
+
+    /// Vector embedding
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub vector: Vec<f32>,
+
+    /// Metadata document
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+
+    /// Outgoing edges
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub edges: Vec<BulkEdge>,
+
+    /// Memory tier; new entities start hot
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tier: Option<MemoryTier>,
+}
+
+impl From<&Entity> for BulkRow {
+    fn from(entity: &Entity) -> Self {
+        Self {
+            id: Some(entity.id),
+            vector: entity.vector.as_ref().map(|v| v.values.clone()).unwrap_or_default(),
+            metadata: entity.metadata.clone(),
+            edges: entity
+                .edges
+                .iter()
+                .flatten()
+                .map(|edge| BulkEdge {
+                    target_id: edge.target_id,
+                    label: edge.label.clone(),
+                    weight: edge.weight,
+                    metadata: edge.metadata.clone(),
+                })
+                .collect(),
+            tier: Some(entity.tier),
+        }
+    }
+}
+
+impl BulkRow {
+    /// Build the domain entity, generating an id if none was given
+    pub fn into_entity(self) -> Entity {
+        let id = self.id.unwrap_or_default();
+        let edges: Vec<_> = self
+            .edges
+            .into_iter()
+            .map(|edge| Edge::new(id, edge.target_id, edge.label, edge.weight, edge.metadata))
+            .collect();
+        let mut entity = Entity::new(
+            (!self.vector.is_empty()).then(|| Vector::new(self.vector)),
+            self.metadata,
+            (!edges.is_empty()).then_some(edges),
+        );
+        entity.id = id;
+        entity.tier = self.tier.unwrap_or(MemoryTier::Hot);
+        entity
+    }
+}
+
+/// A decoded row, or why the row could not be decoded
+pub type RowResult = std::result::Result<BulkRow, String>;
+
+/// Rows of a bulk file; an `Err` item means the file itself is unreadable
+pub type Rows = Box<dyn Iterator<Item = Result<RowResult>>>;
+
+/// Open a bulk file for reading
+///
+/// # Errors
+/// * `Io` if the file cannot be opened
+/// * `Bulk(Malformed)` / `Bulk(Unsupported)` if its header or metadata
+///   cannot be decoded
+pub fn read(file: &BulkFile, config: &BulkConfig) -> Result<Rows> {
+    match file {
+        BulkFile::Ndjson(path) => ndjson::read(path),
+        BulkFile::Parquet(path) => parquet::read(path, config),
+        BulkFile::Npy { vectors, sidecar } => npy::read(vectors, sidecar.as_deref(), config),
+    }
+}
+
+/// A row left out of a load
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RejectedRow {
+    /// Position of the row in the file, starting at 0
+    pub row: usize,
+
+    /// Entity id, if the row got far enough to have one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity_id: Option<EntityId>,
+
+    /// Why it was rejected
+    pub reason: String,
+}
+
+/// Outcome of a bulk load
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LoadReport {
+    /// File format
+    pub format: String,
+
+    /// Rows read from the file
+    pub rows: usize,
+
+    /// Entities inserted or replaced
+    pub loaded: usize,
+
+    /// Rows left out
+    pub rejected: usize,
+
+    /// The first rejected rows, up to `max_reported_rejections`
+    pub rejections: Vec<RejectedRow>,
+
+    /// Segments committed
+    pub segments: usize,
+
+    /// Wall-clock duration in milliseconds
+    pub elapsed_ms: u64,
+
+    /// Rows read per second
+    pub rows_per_second: f64,
+}
+
+/// Outcome of a bulk export
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExportReport {
+    /// File format
+    pub format: String,
+
+    /// Entities written
+    pub rows: usize,
+
+    /// Bytes written, sidecar included
+    pub bytes: u64,
+
+    /// Wall-clock duration in milliseconds
+    pub elapsed_ms: u64,
+
+    /// Entities written per second
+    pub rows_per_second: f64,
+}
+
+/// Load a bulk file into a collection
+///
+/// See [`load_with`].
+pub fn load(catalog: &mut Catalog, collection: &str, file: &BulkFile, config: &BulkConfig) -> Result<LoadReport> {
+    load_with(catalog, collection, file, config, |_| {})
+}
+
+/// Load a bulk file into a collection, calling `on_segment` with the
+/// entities of every committed segment
+///
+/// Versions and timestamps are stamped like single upserts: replacing an
+/// entity keeps its creation time and increments its version. When a row
+/// repeats an id of the same segment, the later row wins.
+///
+/// # Errors
+/// * `Collection(NotFound)` if the collection does not resolve
+/// * `Io` / `Bulk` if the file cannot be read; segments committed before
+///   the failure stay loaded
+pub fn load_with(
+    catalog: &mut Catalog,
+    collection: &str,
+    file: &BulkFile,
+    config: &BulkConfig,
+    mut on_segment: impl FnMut(&[Entity]),
+) -> Result<LoadReport> {
+    let started = Instant::now();
+    catalog.resolve(collection)?;
+    let rows = read(file, config)?;
+
+    let mut report = LoadReport {
+        format: file.format().to_string(),
+        ..LoadReport::default()
+    };
+    let mut segment = Segment::default();
+    for (row, decoded) in rows.enumerate() {
+        report.rows += 1;
+        let reject = |report: &mut LoadReport, entity_id: Option<EntityId>, reason: String| {
+            report.rejected += 1;
+            if report.rejections.len() < config.max_reported_rejections {
+                report.rejections.push(RejectedRow { row, entity_id, reason });
+            }
+        };
+
+        let mut entity = match decoded? {
+            Ok(decoded) => decoded.into_entity(),
+            Err(reason) => {
+                reject(&mut report, None, reason);
+                continue;
+            }
+        };
+        let stored = catalog.collection(collection)?;
+        match stored.validate_entity(&entity) {
+            Ok(()) => {}
+            Err(
+                error @ (MemorySubstrateError::Collection { .. }
+                | MemorySubstrateError::Metadata { .. }
+                | MemorySubstrateError::DimensionMismatch { .. }),
+            ) => {
+                reject(&mut report, Some(entity.id), error.to_string());
+                continue;
+            }
+            Err(error) => return Err(error),
+        }
+
+        let now = now_ms();
+        let previous = match segment.get(&entity.id) {
+            Some(previous) => Some((previous.created_at, previous.version)),
+            None => stored.get(&entity.id).map(|previous| (previous.created_at, previous.version)),
+        };
+        (entity.created_at, entity.version) = match previous {
+            Some((created_at, version)) => (created_at, version + 1),
+            None => (now, 1),
+        };
+        entity.updated_at = now;
+        segment.push(entity);
+
+        if segment.entities.len() >= config.segment_entities || segment.bytes >= config.segment_bytes {
+            commit(catalog, collection, &mut segment, &mut report, &mut on_segment)?;
+        }
+    }
+    commit(catalog, collection, &mut segment, &mut report, &mut on_segment)?;
+
+    (report.elapsed_ms, report.rows_per_second) = throughput(started, report.rows);
+    tracing::info!(
+        collection,
+        format = report.format,
+        rows = report.rows,
+        loaded = report.loaded,
+        rejected = report.rejected,
+        segments = report.segments,
+        rows_per_second = report.rows_per_second,
+        "bulk load finished"
+    );
+    Ok(report)
+}
+
+/// Write every entity of a collection to a bulk file, ordered by id
+///
+/// # Errors
+/// * `Collection(NotFound)` if the collection does not resolve
+/// * `Io` if the file cannot be written
+/// * `Bulk(Unsupported)` if the Parquet export cannot represent the
+///   metadata (see [`parquet::write`])
+pub fn export(catalog: &Catalog, collection: &str, file: &BulkFile, config: &BulkConfig) -> Result<ExportReport> {
+    let started = Instant::now();
+    let stored = catalog.collection(collection)?;
+    let mut entities = stored.entities();
+    entities.sort_by_key(|entity| entity.id);
+    let rows: Vec<BulkRow> = entities.iter().map(|entity| BulkRow::from(&**entity)).collect();
+
+    let bytes = match file {
+        BulkFile::Ndjson(path) => ndjson::write(path, &rows)?,
+        BulkFile::Parquet(path) => parquet::write(path, &rows, config)?,
+        BulkFile::Npy { vectors, sidecar } => npy::write(vectors, sidecar.as_deref(), &rows, stored.config().dimension)?,
+    };
+
+    let (elapsed_ms, rows_per_second) = throughput(started, rows.len());
+    let report = ExportReport {
+        format: file.format().to_string(),
+        rows: rows.len(),
+        bytes,
+        elapsed_ms,
+        rows_per_second,
+    };
+    tracing::info!(
+        collection,
+        format = report.format,
+        rows = report.rows,
+        bytes = report.bytes,
+        rows_per_second = report.rows_per_second,
+        "bulk export finished"
+    );
+    Ok(report)
+}
+
+/// Entities waiting to be committed, deduplicated by id
+#[derive(Debug, Default)]
+struct Segment {
+    entities: Vec<Entity>,
+    positions: HashMap<EntityId, usize>,
+    bytes: usize,
+}
+
+impl Segment {
+    fn get(&self, id: &EntityId) -> Option<&Entity> {
+        self.positions.get(id).map(|&position| &self.entities[position])
+    }
+
+    fn push(&mut self, entity: Entity) {
+        self.bytes += approximate_size(&entity);
+        match self.positions.get(&entity.id) {
+            Some(&position) => self.entities[position] = entity,
+            None => {
+                self.positions.insert(entity.id, self.entities.len());
+                self.entities.push(entity);
+            }
+        }
+    }
+
+    fn take(&mut self) -> Vec<Entity> {
+        self.positions.clear();
+        self.bytes = 0;
+        std::mem::take(&mut self.entities)
+    }
+}
+
+fn commit(
+    catalog: &mut Catalog,
+    collection: &str,
+    segment: &mut Segment,
+    report: &mut LoadReport,
+    on_segment: &mut impl FnMut(&[Entity]),
+) -> Result<()> {
+    if segment.entities.is_empty() {
+        return Ok(());
+    }
+    let entities = segment.take();
+    let count = entities.len();
+    catalog.load_segment(collection, entities.clone())?;
+    on_segment(&entities);
+    report.loaded += count;
+    report.segments += 1;
+    Ok(())
+}
+
+/// Upper estimate of an entity's size in the log, without serializing its
+/// vector
+fn approximate_size(entity: &Entity) -> usize {
+    let json = |value: &Option<Value>| value.as_ref().map_or(0, |value| value.to_string().len());
+    let vector = entity.vector.as_ref().map_or(0, |vector| vector.values.len() * 16);
+    let edges: usize = entity
+        .edges
+        .iter()
+        .flatten()
+        .map(|edge| 256 + edge.label.len() + json(&edge.metadata))
+        .sum();
+    512 + vector + json(&entity.metadata) + edges
+}
+
+fn throughput(started: Instant, rows: usize) -> (u64, f64) {
+    let elapsed = started.elapsed();
+    let seconds = elapsed.as_secs_f64();
+    let rate = if seconds > 0.0 { rows as f64 / seconds } else { 0.0 };
+    (elapsed.as_millis() as u64, rate)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::collection::CollectionConfig;
+    use crate::core::metadata::{FieldSchema, FieldType, MetadataSchema};
+    use serde_json::json;
+
+    fn docs(catalog: &mut Catalog) {
+        let schema = MetadataSchema::new().field(FieldSchema::new("lang".parse().unwrap(), FieldType::String));
+        catalog
+            .create_collection("docs", CollectionConfig::new(2).with_schema(schema))
+            .unwrap();
+    }
+
+    fn write_lines(path: &std::path::Path, lines: &[Value]) {
+        let text: Vec<String> = lines.iter().map(Value::to_string).collect();
+        std::fs::write(path, text.join("\n") + "\n").unwrap();
+    }
+
+    #[test]
+    fn test_load_commits_segments_and_reports_rejections() {
+        let dir = tempfile::tempdir().unwrap();
+        let wal = dir.path().join("catalog.wal");
+        let input = dir.path().join("docs.ndjson");
+        let kept = EntityId::new();
+        write_lines(
+            &input,
+            &[
+                json!({"id": kept, "vector": [1.0, 0.0], "metadata": {"lang": "en"}}),
+                json!({"vector": [1.0, 0.0, 0.0]}),
+                json!({"vector": [0.0, 1.0], "metadata": {"lang": 7}}),
+                json!({"vector": [0.5, 0.5], "tier": "Warm"}),
+                json!({"id": kept, "vector": [0.0, 1.0], "metadata": {"lang": "fr"}}),
+                json!({"vector": [0.2, 0.8]}),
+            ],
+        );
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&input)
+            .and_then(|mut file| std::io::Write::write_all(&mut file, b"{not json\n\n"))
+            .unwrap();
+
+        let config = BulkConfig {
+            segment_entities: 2,
+            max_reported_rejections: 2,
+            ..BulkConfig::default()
+        };
+        let mut committed = Vec::new();
+        {
+            let mut catalog = Catalog::open(&wal).unwrap();
+            docs(&mut catalog);
+            let report = load_with(&mut catalog, "docs", &BulkFile::Ndjson(input.clone()), &config, |segment| {
+                committed.push(segment.len())
+            })
+            .unwrap();
+
+            assert_eq!((report.rows, report.loaded, report.rejected), (7, 4, 3));
+            assert_eq!(report.rejections.len(), 2);
+            assert_eq!(report.rejections[0].row, 1);
+            assert!(report.rejections[0].reason.contains("dimensions"));
+            assert_eq!(report.rejections[1].row, 2);
+            // The repeated id replaces the stored entity in the second segment
+            assert_eq!(report.segments, 2);
+            assert_eq!(committed, vec![2, 2]);
+            assert_eq!(report.format, "NDJSON");
+
+            let stored = catalog.get("docs", &kept).unwrap().unwrap();
+            assert_eq!((stored.version, stored.metadata.clone()), (2, Some(json!({"lang": "fr"}))));
+        }
+
+        // Segments replay like any other record
+        let catalog = Catalog::open(&wal).unwrap();
+        assert_eq!(catalog.describe_collection("docs").unwrap().entity_count, 3);
+        let stored = catalog.collection("docs").unwrap().get(&kept).unwrap();
+        assert_eq!(stored.vector.as_ref().unwrap().values, vec![0.0, 1.0]);
+
+        // An unreadable file fails the load
+        let bad = dir.path().join("bad.npy");
+        std::fs::write(&bad, b"not numpy").unwrap();
+        let mut catalog = catalog;
+        let file = BulkFile::Npy { vectors: bad, sidecar: None };
+        assert!(matches!(
+            load(&mut catalog, "docs", &file, &config),
+            Err(MemorySubstrateError::Bulk { .. })
+        ));
+    }
+
+    #[test]
+    fn test_export_round_trips_every_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut catalog = Catalog::in_memory();
+        docs(&mut catalog);
+        let (a, b) = (EntityId::new(), EntityId::new());
+        let mut first = Entity::new(Some(Vector::new(vec![1.0, 0.0])), Some(json!({"lang": "en", "n": 3})), None);
+        first.id = a;
+        first.edges = Some(vec![Edge::new(a, b, "cites".to_string(), 0.5, None)]);
+        let mut second = Entity::new(Some(Vector::new(vec![0.25, -1.5])), Some(json!({"lang": "fr"})), None);
+        second.id = b;
+        second.tier = MemoryTier::Cold;
+        catalog.upsert("docs", first).unwrap();
+        catalog.upsert("docs", second).unwrap();
+
+        let config = BulkConfig::default();
+        let files = [
+            BulkFile::Ndjson(dir.path().join("docs.ndjson")),
+            BulkFile::Parquet(dir.path().join("docs.parquet")),
+            BulkFile::Npy {
+                vectors: dir.path().join("docs.npy"),
+                sidecar: Some(dir.path().join("docs.sidecar.ndjson")),
+            },
+        ];
+        for (index, file) in files.iter().enumerate() {
+            let report = export(&catalog, "docs", file, &config).unwrap();
+            assert_eq!(report.rows, 2);
+            assert!(report.bytes > 0);
+
+            let target = format!("copy{}", index);
+            catalog.create_collection(&target, catalog.describe_collection("docs").unwrap().config).unwrap();
+            let loaded = load(&mut catalog, &target, file, &config).unwrap();
+            assert_eq!((loaded.loaded, loaded.rejected), (2, 0), "{}", file.format());
+
+            for id in [a, b] {
+                let original = catalog.collection("docs").unwrap().get(&id).unwrap();
+                let copy = catalog.collection(&target).unwrap().get(&id).unwrap();
+                assert_eq!(copy.vector.as_ref().unwrap().values, original.vector.as_ref().unwrap().values);
+                assert_eq!(copy.metadata, original.metadata, "{}", file.format());
+            }
+            // Parquet carries ids, vectors and metadata only
+            let copy = catalog.collection(&target).unwrap().get(&a).unwrap();
+            let expect_edges = !matches!(file, BulkFile::Parquet(_));
+            assert_eq!(copy.edges.is_some(), expect_edges, "{}", file.format());
+        }
+    }
+}
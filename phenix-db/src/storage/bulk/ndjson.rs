@@ -0,0 +1,47 @@
+//! Newline-delimited JSON
+//!
+//! Each non-blank line is one [`BulkRow`]. Lines that are not valid JSON or
+//! do not describe an entity are rejected individually.
+
+use super::{BulkRow, RowResult, Rows};
+use crate::core::error::{MemorySubstrateError, Result};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Format name used in reports and errors
+pub const FORMAT: &str = "NDJSON";
+
+/// Read rows from the file at `path`
+pub fn read(path: &Path) -> Result<Rows> {
+    Ok(Box::new(lines(path)?.map(|line| Ok(parse(&line?)))))
+}
+
+/// Non-blank lines of the file at `path`
+pub(super) fn lines(path: &Path) -> Result<impl Iterator<Item = Result<String>>> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(reader
+        .lines()
+        .map(|line| line.map_err(MemorySubstrateError::from))
+        .filter(|line| !matches!(line, Ok(text) if text.trim().is_empty())))
+}
+
+/// Decode one line
+pub(super) fn parse(line: &str) -> RowResult {
+    serde_json::from_str(line).map_err(|error| format!("invalid {} row: {}", FORMAT, error))
+}
+
+/// Write `rows` to `path`, one per line, returning the bytes written
+pub fn write(path: &Path, rows: &[BulkRow]) -> Result<u64> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let mut bytes = 0;
+    for row in rows {
+        let mut line = serde_json::to_vec(row).map_err(|error| MemorySubstrateError::Serialization(error.to_string()))?;
+        line.push(b'\n');
+        writer.write_all(&line)?;
+        bytes += line.len() as u64;
+    }
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    Ok(bytes)
+}
@@ -0,0 +1,462 @@
+//! NumPy `.npy` vector arrays with an NDJSON sidecar
+//!
+//! The array must be 2-D with one row per entity and `float32` or `float64`
+//! elements of either byte order, in C or Fortran order. Line `i` of the
+//! optional sidecar is a [`BulkRow`] without a vector that supplies the id,
+//! metadata, edges and tier of array row `i`; blank lines are skipped. A
+//! sidecar with more or fewer lines than the array has rows fails the load.
+//!
+//! The shape must fit in the file, so rows are read without trusting it to
+//! size allocations. A Fortran-order array is read whole, since its rows are
+//! not contiguous, and must decode within `max_decode_bytes`.
+//!
+//! Exports are little-endian `float32`, C order, format version 1.0.
+
+use super::{ndjson, BulkRow, RowResult, Rows};
+use crate::core::config::BulkConfig;
+use crate::core::error::{BulkError, MemorySubstrateError, Result};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::path::Path;
+
+/// Format name used in reports and errors
+pub const FORMAT: &str = "NumPy";
+
+const MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+/// Largest accepted header; real headers are well under 1 KiB
+const MAX_HEADER_LEN: usize = 64 * 1024;
+
+fn malformed(reason: impl Into<String>) -> MemorySubstrateError {
+    BulkError::Malformed {
+        format: FORMAT.to_string(),
+        reason: reason.into(),
+    }
+    .into()
+}
+
+fn unsupported(feature: impl Into<String>) -> MemorySubstrateError {
+    BulkError::Unsupported {
+        format: FORMAT.to_string(),
+        feature: feature.into(),
+    }
+    .into()
+}
+
+/// Decoded array header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    item_size: usize,
+    big_endian: bool,
+    fortran_order: bool,
+    rows: usize,
+    columns: usize,
+}
+
+impl Header {
+    fn parse(text: &str) -> Result<Self> {
+        let value_of = |key: &str| -> Result<&str> {
+            let quoted = format!("'{}'", key);
+            let start = text
+                .find(&quoted)
+                .ok_or_else(|| malformed(format!("header has no {}", quoted)))?;
+            let rest = text[start + quoted.len()..].trim_start();
+            rest.strip_prefix(':')
+                .map(str::trim_start)
+                .ok_or_else(|| malformed(format!("header key {} has no value", quoted)))
+        };
+
+        let descr = value_of("descr")?;
+        let descr = descr
+            .strip_prefix('\'')
+            .and_then(|rest| rest.split('\'').next())
+            .ok_or_else(|| malformed("descr is not a string"))?;
+        let (big_endian, kind) = match descr.split_at(descr.len().min(1)) {
+            ("<" | "|" | "=", kind) => (false, kind),
+            (">", kind) => (true, kind),
+            _ => (false, descr),
+        };
+        let item_size = match kind {
+            "f4" => 4,
+            "f8" => 8,
+            other => return Err(unsupported(format!("element type '{}' (expected f4 or f8)", other))),
+        };
+
+        let fortran_order = match value_of("fortran_order")? {
+            order if order.starts_with("True") => true,
+            order if order.starts_with("False") => false,
+            _ => return Err(malformed("fortran_order is not a boolean")),
+        };
+
+        let shape = value_of("shape")?;
+        let shape = shape
+            .strip_prefix('(')
+            .and_then(|rest| rest.split(')').next())
+            .ok_or_else(|| malformed("shape is not a tuple"))?;
+        let dimensions = shape
+            .split(',')
+            .map(str::trim)
+            .filter(|dimension| !dimension.is_empty())
+            .map(|dimension| {
+                dimension
+                    .parse::<usize>()
+                    .map_err(|_| malformed(format!("invalid shape dimension '{}'", dimension)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let [rows, columns] = dimensions[..] else {
+            return Err(unsupported(format!("{}-D array (expected 2-D)", dimensions.len())));
+        };
+        if columns == 0 && rows > 0 {
+            return Err(malformed("rows have no columns"));
+        }
+        columns
+            .checked_mul(item_size)
+            .and_then(|row| row.checked_mul(rows))
+            .ok_or_else(|| malformed(format!("shape ({}, {}) overflows", rows, columns)))?;
+
+        Ok(Self {
+            item_size,
+            big_endian,
+            fortran_order,
+            rows,
+            columns,
+        })
+    }
+
+    fn read(reader: &mut impl Read) -> Result<Self> {
+        let mut preamble = [0u8; 8];
+        reader
+            .read_exact(&mut preamble)
+            .map_err(|_| malformed("file is too short"))?;
+        if &preamble[..6] != MAGIC {
+            return Err(malformed("missing .npy magic"));
+        }
+        let header_len = match preamble[6] {
+            1 => {
+                let mut len = [0u8; 2];
+                reader.read_exact(&mut len).map_err(|_| malformed("truncated header"))?;
+                u16::from_le_bytes(len) as usize
+            }
+            2 | 3 => {
+                let mut len = [0u8; 4];
+                reader.read_exact(&mut len).map_err(|_| malformed("truncated header"))?;
+                u32::from_le_bytes(len) as usize
+            }
+            version => return Err(unsupported(format!("format version {}", version))),
+        };
+        if header_len > MAX_HEADER_LEN {
+            return Err(malformed(format!("header of {} bytes", header_len)));
+        }
+        let mut header = vec![0u8; header_len];
+        reader.read_exact(&mut header).map_err(|_| malformed("truncated header"))?;
+        let text = String::from_utf8(header).map_err(|_| malformed("header is not text"))?;
+        Self::parse(&text)
+    }
+
+    /// Bytes of array data; the shape was checked not to overflow
+    fn data_len(&self) -> usize {
+        self.rows * self.columns * self.item_size
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Vec<f32> {
+        bytes
+            .chunks_exact(self.item_size)
+            .map(|item| match (self.item_size, self.big_endian) {
+                (4, false) => f32::from_le_bytes(item.try_into().expect("4-byte item")),
+                (4, true) => f32::from_be_bytes(item.try_into().expect("4-byte item")),
+                (_, false) => f64::from_le_bytes(item.try_into().expect("8-byte item")) as f32,
+                (_, true) => f64::from_be_bytes(item.try_into().expect("8-byte item")) as f32,
+            })
+            .collect()
+    }
+}
+
+/// Rows of an array and its sidecar
+struct NpyRows {
+    header: Header,
+    reader: BufReader<File>,
+    /// Whole array, for Fortran order
+    columns: Option<Vec<f32>>,
+    sidecar: Option<Box<dyn Iterator<Item = Result<String>>>>,
+    row: usize,
+}
+
+impl NpyRows {
+    fn vector(&mut self) -> Result<Vec<f32>> {
+        let header = self.header;
+        if header.fortran_order {
+            if self.columns.is_none() {
+                let mut bytes = vec![0u8; header.data_len()];
+                self.reader
+                    .read_exact(&mut bytes)
+                    .map_err(|_| malformed("array data is truncated"))?;
+                self.columns = Some(header.decode(&bytes));
+            }
+            let values = self.columns.as_ref().expect("array was just read");
+            return Ok((0..header.columns)
+                .map(|column| values[column * header.rows + self.row])
+                .collect());
+        }
+
+        let mut bytes = vec![0u8; header.columns * header.item_size];
+        self.reader
+            .read_exact(&mut bytes)
+            .map_err(|_| malformed("array data is truncated"))?;
+        Ok(header.decode(&bytes))
+    }
+
+    fn next_row(&mut self) -> Result<RowResult> {
+        let vector = self.vector()?;
+        let row = match &mut self.sidecar {
+            None => Ok(BulkRow::default()),
+            Some(lines) => {
+                let line = lines
+                    .next()
+                    .ok_or_else(|| malformed(format!("sidecar ends before array row {}", self.row)))??;
+                ndjson::parse(&line).and_then(|row| {
+                    if row.vector.is_empty() {
+                        Ok(row)
+                    } else {
+                        Err("sidecar rows must not carry a vector".to_string())
+                    }
+                })
+            }
+        };
+        Ok(row.map(|row| BulkRow { vector, ..row }))
+    }
+}
+
+impl Iterator for NpyRows {
+    type Item = Result<RowResult>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.row == self.header.rows {
+            let extra = self.sidecar.as_mut().and_then(Iterator::next);
+            self.sidecar = None;
+            return extra.map(|_| Err(malformed("sidecar has more lines than the array has rows")));
+        }
+        let row = self.next_row();
+        self.row += 1;
+        if row.is_err() {
+            // Nothing after a broken file is trustworthy
+            self.row = self.header.rows;
+            self.sidecar = None;
+        }
+        Some(row)
+    }
+}
+
+/// Read rows from the array at `vectors` and the optional `sidecar`
+///
+/// # Errors
+/// * `Bulk(Malformed)` if the header cannot be decoded or its shape does
+///   not fit in the file
+/// * `Bulk(Unsupported)` for other element types or dimensions, and for
+///   Fortran-order arrays that decode to more than `config.max_decode_bytes`
+pub fn read(vectors: &Path, sidecar: Option<&Path>, config: &BulkConfig) -> Result<Rows> {
+    let file = File::open(vectors)?;
+    let file_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let header = Header::read(&mut reader)?;
+    let data_len = header.data_len() as u64;
+    if data_len > file_len.saturating_sub(reader.stream_position()?) {
+        return Err(malformed(format!("array of {} bytes does not fit in the file", data_len)));
+    }
+    // Raw bytes and decoded values are held together
+    let decoded = header.rows * header.columns * std::mem::size_of::<f32>();
+    if header.fortran_order && header.data_len().saturating_add(decoded) > config.max_decode_bytes {
+        return Err(unsupported(format!(
+            "Fortran-order arrays decoding to more than {} bytes",
+            config.max_decode_bytes
+        )));
+    }
+    let sidecar = match sidecar {
+        Some(path) => Some(Box::new(ndjson::lines(path)?) as Box<dyn Iterator<Item = Result<String>>>),
+        None => None,
+    };
+    Ok(Box::new(NpyRows {
+        header,
+        reader,
+        columns: None,
+        sidecar,
+        row: 0,
+    }))
+}
+
+/// Write the vectors of `rows` to `vectors` and, if given, everything else
+/// to `sidecar`, returning the bytes written
+///
+/// # Errors
+/// * `DimensionMismatch` if a vector does not have `dimension` values
+pub fn write(vectors: &Path, sidecar: Option<&Path>, rows: &[BulkRow], dimension: usize) -> Result<u64> {
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        rows.len(),
+        dimension
+    );
+    // Pad so the data starts on a 64-byte boundary, as numpy does
+    let unpadded = MAGIC.len() + 4 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    let mut writer = BufWriter::new(File::create(vectors)?);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    let mut bytes = (MAGIC.len() + 4 + header.len()) as u64;
+    for row in rows {
+        if row.vector.len() != dimension {
+            return Err(MemorySubstrateError::DimensionMismatch {
+                expected: dimension,
+                actual: row.vector.len(),
+            });
+        }
+        for value in &row.vector {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        bytes += (dimension * 4) as u64;
+    }
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+
+    if let Some(sidecar) = sidecar {
+        let rest: Vec<BulkRow> = rows
+            .iter()
+            .map(|row| BulkRow {
+                vector: Vec::new(),
+                ..row.clone()
+            })
+            .collect();
+        bytes += ndjson::write(sidecar, &rest)?;
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn array(descr: &str, fortran: bool, shape: &str, data: &[u8]) -> Vec<u8> {
+        let header = format!(
+            "{{'descr': '{}', 'fortran_order': {}, 'shape': {}, }}\n",
+            descr,
+            if fortran { "True" } else { "False" },
+            shape
+        );
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[1, 0]);
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn vectors(rows: Rows) -> Vec<Vec<f32>> {
+        rows.map(|row| row.unwrap().unwrap().vector).collect()
+    }
+
+    #[test]
+    fn test_reads_byte_orders_and_layouts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("v.npy");
+
+        // 2x3 float64, big endian, Fortran order: columns are contiguous
+        let columns = [1.0f64, 4.0, 2.0, 5.0, 3.0, 6.0];
+        let data: Vec<u8> = columns.iter().flat_map(|value| value.to_be_bytes()).collect();
+        std::fs::write(&path, array(">f8", true, "(2, 3)", &data)).unwrap();
+        assert_eq!(
+            vectors(read(&path, None, &BulkConfig::default()).unwrap()),
+            vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]
+        );
+
+        std::fs::write(&path, array("<i8", false, "(2, 3)", &data)).unwrap();
+        assert!(matches!(
+            read(&path, None, &BulkConfig::default()).map(|_| ()),
+            Err(MemorySubstrateError::Bulk { error: BulkError::Unsupported { .. }, .. })
+        ));
+        std::fs::write(&path, array("<f4", false, "(6,)", &data)).unwrap();
+        assert!(read(&path, None, &BulkConfig::default()).is_err());
+
+    }
+
+    #[test]
+    fn test_shape_must_fit_the_file_and_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("v.npy");
+        let malformed = |shape: &str, fortran: bool, data: &[u8], config: &BulkConfig| {
+            std::fs::write(&path, array("<f4", fortran, shape, data)).unwrap();
+            match read(&path, None, config).map(|_| ()) {
+                Err(MemorySubstrateError::Bulk { error: BulkError::Malformed { reason, .. }, .. }) => reason,
+                other => panic!("{}: {:?}", shape, other),
+            }
+        };
+        let config = BulkConfig::default();
+        let data: Vec<u8> = [1.0f32, 2.0, 3.0].iter().flat_map(|value| value.to_le_bytes()).collect();
+
+        // Overflowing and wrapping shapes, a shape beyond the data, no columns
+        let max = usize::MAX;
+        assert!(malformed(&format!("({}, {})", max, max), false, &data, &config).contains("overflows"));
+        assert!(malformed(&format!("(1, {})", 1usize << 62), false, &data, &config).contains("overflows"));
+        assert!(malformed("(2, 2)", false, &data, &config).contains("does not fit"));
+        assert!(malformed("(1000000, 1000000)", true, &data, &config).contains("does not fit"));
+        assert!(malformed(&format!("({}, 0)", max), false, &[], &config).contains("no columns"));
+        assert!(malformed("(2, x)", false, &data, &config).contains("invalid shape"));
+
+        // An empty array is fine
+        std::fs::write(&path, array("<f4", false, "(0, 3)", &[])).unwrap();
+        assert_eq!(read(&path, None, &config).unwrap().count(), 0);
+
+        // A whole Fortran-order array must decode within the budget
+        std::fs::write(&path, array("<f4", true, "(1, 3)", &data)).unwrap();
+        assert_eq!(vectors(read(&path, None, &config).unwrap()), vec![vec![1.0, 2.0, 3.0]]);
+        let small = BulkConfig {
+            max_decode_bytes: 23,
+            ..BulkConfig::default()
+        };
+        assert!(matches!(
+            read(&path, None, &small).map(|_| ()),
+            Err(MemorySubstrateError::Bulk { error: BulkError::Unsupported { .. }, .. })
+        ));
+        std::fs::write(&path, array("<f4", false, "(1, 3)", &data)).unwrap();
+        assert_eq!(vectors(read(&path, None, &small).unwrap()), vec![vec![1.0, 2.0, 3.0]]);
+    }
+
+    #[test]
+    fn test_sidecar_must_match_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("v.npy");
+        let sidecar = dir.path().join("v.ndjson");
+        let rows = vec![
+            BulkRow {
+                vector: vec![1.0, 0.0],
+                metadata: Some(serde_json::json!({"n": 1})),
+                ..BulkRow::default()
+            },
+            BulkRow {
+                vector: vec![0.0, 1.0],
+                ..BulkRow::default()
+            },
+        ];
+        write(&path, Some(&sidecar), &rows, 2).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!((bytes.len() - 16) % 64, 0, "data is 64-byte aligned");
+        let read_back: Vec<BulkRow> = read(&path, Some(&sidecar), &BulkConfig::default()).unwrap().map(|row| row.unwrap().unwrap()).collect();
+        assert_eq!(read_back, rows);
+
+        std::fs::write(&sidecar, "{}\n{}\n{}\n").unwrap();
+        let results: Vec<_> = read(&path, Some(&sidecar), &BulkConfig::default()).unwrap().collect();
+        assert_eq!(results.len(), 3);
+        assert!(results[2].is_err());
+
+        std::fs::write(&sidecar, "{\"vector\": [1.0, 2.0]}\n").unwrap();
+        let results: Vec<_> = read(&path, Some(&sidecar), &BulkConfig::default()).unwrap().collect();
+        assert!(results[0].as_ref().unwrap().is_err());
+        assert!(results[1].is_err());
+
+        assert!(matches!(
+            write(&path, None, &rows, 3),
+            Err(MemorySubstrateError::DimensionMismatch { expected: 3, actual: 2 })
+        ));
+    }
+}
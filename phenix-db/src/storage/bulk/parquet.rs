@@ -0,0 +1,1506 @@
+//! Apache Parquet tables
+//!
+//! Reading supports flat schemas: every top-level field is either a
+//! primitive column or a list of primitives (standard three-level `LIST`
+//! groups, legacy two-level lists and bare repeated fields alike). The
+//! configured id column holds UUIDs as strings or 16-byte values, the
+//! vector column holds a list of numbers, and every other column becomes a
+//! metadata field; nulls are omitted and JSON-annotated strings are parsed.
+//! Pages may be v1 or v2, `PLAIN` or dictionary encoded, uncompressed or
+//! Snappy compressed. Anything else is refused as unsupported rather than
+//! guessed at.
+//!
+//! Sizes and counts in a file are not trusted to size allocations. The
+//! footer is capped in bytes and in decoded values, and everything decoded
+//! for a row group (column chunks, pages, levels, values and rows) is
+//! charged against `max_decode_bytes` before it is allocated, so a few
+//! bytes of run-length encoding cannot claim gigabytes.
+//!
+//! Exports write one uncompressed `PLAIN` page per column and row group.
+//! Metadata column types are inferred from the values: booleans, 64-bit
+//! integers, doubles and strings map to the matching Parquet types, and
+//! fields with mixed or nested values are written as JSON strings. Edges
+//! and tiers are not exported.
+
+use super::thrift::{self, Decoder, Encoder, Struct, Value as Thrift};
+use super::{BulkRow, RowResult, Rows};
+use crate::core::config::BulkConfig;
+use crate::core::error::{BulkError, MemorySubstrateError, Result};
+use crate::core::EntityId;
+use serde_json::{Map, Number, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::mem::size_of;
+use std::path::Path;
+
+/// Format name used in reports and errors
+pub const FORMAT: &str = "Parquet";
+
+const MAGIC: &[u8; 4] = b"PAR1";
+
+/// Largest accepted footer
+const MAX_METADATA_LEN: u64 = 64 * 1024 * 1024;
+
+/// Deepest accepted schema
+const MAX_SCHEMA_DEPTH: usize = 16;
+
+// Physical types
+const BOOLEAN: i32 = 0;
+const INT32: i32 = 1;
+const INT64: i32 = 2;
+const INT96: i32 = 3;
+const FLOAT: i32 = 4;
+const DOUBLE: i32 = 5;
+const BYTE_ARRAY: i32 = 6;
+const FIXED_LEN_BYTE_ARRAY: i32 = 7;
+
+// Field repetition
+const REQUIRED: i32 = 0;
+const OPTIONAL: i32 = 1;
+const REPEATED: i32 = 2;
+
+// Converted types and logical type union fields
+const CONVERTED_UTF8: i32 = 0;
+const CONVERTED_LIST: i32 = 3;
+const CONVERTED_ENUM: i32 = 4;
+const CONVERTED_DECIMAL: i32 = 5;
+const CONVERTED_JSON: i32 = 19;
+const LOGICAL_STRING: i16 = 1;
+const LOGICAL_LIST: i16 = 3;
+const LOGICAL_ENUM: i16 = 4;
+const LOGICAL_DECIMAL: i16 = 5;
+const LOGICAL_JSON: i16 = 12;
+const LOGICAL_UUID: i16 = 14;
+
+// Encodings
+const PLAIN: i32 = 0;
+const PLAIN_DICTIONARY: i32 = 2;
+const RLE: i32 = 3;
+const RLE_DICTIONARY: i32 = 8;
+
+// Compression codecs
+const UNCOMPRESSED: i32 = 0;
+const SNAPPY: i32 = 1;
+
+// Page types
+const DATA_PAGE: i32 = 0;
+const DICTIONARY_PAGE: i32 = 2;
+const DATA_PAGE_V2: i32 = 3;
+
+fn malformed(reason: impl Into<String>) -> MemorySubstrateError {
+    BulkError::Malformed {
+        format: FORMAT.to_string(),
+        reason: reason.into(),
+    }
+    .into()
+}
+
+fn unsupported(feature: impl Into<String>) -> MemorySubstrateError {
+    BulkError::Unsupported {
+        format: FORMAT.to_string(),
+        feature: feature.into(),
+    }
+    .into()
+}
+
+fn int(fields: &Struct, id: i16) -> Option<i64> {
+    fields.get(&id).and_then(Thrift::as_i64)
+}
+
+fn required_int(fields: &Struct, id: i16, what: &str) -> Result<i64> {
+    int(fields, id).ok_or_else(|| malformed(format!("{} is missing", what)))
+}
+
+fn required_struct<'a>(fields: &'a Struct, id: i16, what: &str) -> Result<&'a Struct> {
+    fields
+        .get(&id)
+        .and_then(Thrift::as_struct)
+        .ok_or_else(|| malformed(format!("{} is missing", what)))
+}
+
+fn structs_of(fields: &Struct, id: i16) -> impl Iterator<Item = Option<&Struct>> {
+    fields
+        .get(&id)
+        .and_then(Thrift::as_list)
+        .unwrap_or_default()
+        .iter()
+        .map(Thrift::as_struct)
+}
+
+/// Non-negative size or offset
+fn size(value: i64, what: &str) -> Result<usize> {
+    usize::try_from(value).map_err(|_| malformed(format!("{} is negative", what)))
+}
+
+/// Memory a row group may still decode into
+struct Budget {
+    limit: usize,
+    left: usize,
+}
+
+impl Budget {
+    fn new(limit: usize) -> Self {
+        Self { limit, left: limit }
+    }
+
+    /// Account for `count` items of `width` bytes before allocating them
+    fn charge(&mut self, count: usize, width: usize) -> Result<()> {
+        self.left = count
+            .checked_mul(width)
+            .and_then(|bytes| self.left.checked_sub(bytes))
+            .ok_or_else(|| unsupported(format!("row groups decoding to more than {} bytes", self.limit)))?;
+        Ok(())
+    }
+}
+
+/// How the bytes of a column are meant to be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Annotation {
+    None,
+    Text,
+    Json,
+    Uuid,
+    Decimal,
+}
+
+impl Annotation {
+    fn of(element: &Struct) -> Self {
+        let logical = element
+            .get(&10)
+            .and_then(Thrift::as_struct)
+            .and_then(|union| union.keys().next().copied());
+        match (int(element, 6).map(|converted| converted as i32), logical) {
+            (Some(CONVERTED_JSON), _) | (_, Some(LOGICAL_JSON)) => Self::Json,
+            (Some(CONVERTED_UTF8 | CONVERTED_ENUM), _) | (_, Some(LOGICAL_STRING | LOGICAL_ENUM)) => Self::Text,
+            (Some(CONVERTED_DECIMAL), _) | (_, Some(LOGICAL_DECIMAL)) => Self::Decimal,
+            (_, Some(LOGICAL_UUID)) => Self::Uuid,
+            _ => Self::None,
+        }
+    }
+}
+
+/// A primitive column and its level structure
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Leaf {
+    path: Vec<String>,
+    physical: i32,
+    type_length: usize,
+    annotation: Annotation,
+    max_def: u32,
+    max_rep: u32,
+    /// Definition level of the innermost repeated ancestor
+    list_def: u32,
+}
+
+/// Flatten the schema into its leaves, in column order
+fn leaves(schema: &[Struct]) -> Result<Vec<Leaf>> {
+    let root = schema.first().ok_or_else(|| malformed("schema is empty"))?;
+    let mut leaves = Vec::new();
+    let mut next = 1;
+    for _ in 0..int(root, 5).unwrap_or(0) {
+        walk(schema, &mut next, &[], (0, 0, 0), &mut leaves)?;
+    }
+    if next != schema.len() {
+        return Err(malformed("schema has elements outside the tree"));
+    }
+    Ok(leaves)
+}
+
+fn walk(
+    schema: &[Struct],
+    next: &mut usize,
+    parent: &[String],
+    (def, rep, list_def): (u32, u32, u32),
+    leaves: &mut Vec<Leaf>,
+) -> Result<()> {
+    let element = schema.get(*next).ok_or_else(|| malformed("schema ends early"))?;
+    *next += 1;
+    if parent.len() == MAX_SCHEMA_DEPTH {
+        return Err(unsupported("schemas nested this deeply"));
+    }
+    let name = element
+        .get(&4)
+        .and_then(Thrift::as_bytes)
+        .and_then(|name| std::str::from_utf8(name).ok())
+        .ok_or_else(|| malformed("schema element has no name"))?;
+    let mut path = parent.to_vec();
+    path.push(name.to_string());
+    let levels = match int(element, 3).unwrap_or(i64::from(REQUIRED)) as i32 {
+        OPTIONAL => (def + 1, rep, list_def),
+        REPEATED => (def + 1, rep + 1, def + 1),
+        _ => (def, rep, list_def),
+    };
+
+    match int(element, 5) {
+        Some(children) if children > 0 => {
+            for _ in 0..children {
+                walk(schema, next, &path, levels, leaves)?;
+            }
+        }
+        _ => leaves.push(Leaf {
+            physical: required_int(element, 1, "column type")? as i32,
+            type_length: int(element, 2).unwrap_or(0).max(0) as usize,
+            annotation: Annotation::of(element),
+            max_def: levels.0,
+            max_rep: levels.1,
+            list_def: levels.2,
+            path,
+        }),
+    }
+    Ok(())
+}
+
+/// What a column contributes to a row
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Role {
+    Id,
+    Vector,
+    Field(String),
+}
+
+/// Assign a role to every leaf, refusing shapes that do not map to rows
+fn roles(leaves: &[Leaf], config: &BulkConfig) -> Result<Vec<Role>> {
+    let mut seen = BTreeSet::new();
+    let mut roles = Vec::with_capacity(leaves.len());
+    for leaf in leaves {
+        let name = &leaf.path[0];
+        if !seen.insert(name.clone()) {
+            return Err(unsupported(format!("nested column '{}'", name)));
+        }
+        if leaf.max_rep > 1 {
+            return Err(unsupported(format!("nested lists in column '{}'", name)));
+        }
+        if leaf.physical == INT96 {
+            return Err(unsupported(format!("INT96 column '{}'", name)));
+        }
+        if leaf.annotation == Annotation::Decimal {
+            return Err(unsupported(format!("DECIMAL column '{}'", name)));
+        }
+
+        let role = if *name == config.id_column {
+            if leaf.max_rep > 0 || !matches!(leaf.physical, BYTE_ARRAY | FIXED_LEN_BYTE_ARRAY) {
+                return Err(unsupported(format!("id column '{}' that is not a string or UUID", name)));
+            }
+            Role::Id
+        } else if *name == config.vector_column {
+            if leaf.max_rep == 0 || !matches!(leaf.physical, INT32 | INT64 | FLOAT | DOUBLE) {
+                return Err(unsupported(format!("vector column '{}' that is not a list of numbers", name)));
+            }
+            Role::Vector
+        } else {
+            Role::Field(name.clone())
+        };
+        roles.push(role);
+    }
+    Ok(roles)
+}
+
+/// Outcome of converting one value of a row
+type Conversion<T> = std::result::Result<T, String>;
+
+/// Decoded primitive value
+#[derive(Debug, Clone, PartialEq)]
+enum Scalar {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Bytes(Vec<u8>),
+}
+
+/// Value of one column in one row
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+    Null,
+    One(Scalar),
+    List(Vec<Option<Scalar>>),
+}
+
+fn varint(data: &[u8], position: &mut usize) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *data.get(*position).ok_or_else(|| malformed("encoded run ends early"))?;
+        *position += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(malformed("varint is too long"))
+}
+
+/// Bits needed to store levels up to `max`
+fn bit_width(max: u32) -> u32 {
+    32 - max.leading_zeros()
+}
+
+/// Decode `count` values of the RLE / bit-packing hybrid encoding
+fn hybrid(data: &[u8], bit_width: u32, count: usize) -> Result<Vec<u32>> {
+    if bit_width > 32 {
+        return Err(malformed(format!("bit width {}", bit_width)));
+    }
+    let width = bit_width as usize;
+    let mut values = Vec::with_capacity(count.min(1 << 16));
+    let mut position = 0;
+    while values.len() < count {
+        let header = varint(data, &mut position)?;
+        let remaining = count - values.len();
+        if header & 1 == 0 {
+            let run = usize::try_from(header >> 1).unwrap_or(usize::MAX);
+            let bytes = data
+                .get(position..position + width.div_ceil(8))
+                .ok_or_else(|| malformed("encoded run ends early"))?;
+            position += bytes.len();
+            let value = bytes
+                .iter()
+                .enumerate()
+                .fold(0u32, |value, (index, &byte)| value | u32::from(byte) << (8 * index));
+            values.extend(std::iter::repeat_n(value, run.min(remaining)));
+        } else {
+            let groups = usize::try_from(header >> 1).unwrap_or(usize::MAX);
+            let len = groups
+                .checked_mul(width)
+                .filter(|&len| len <= data.len() - position)
+                .ok_or_else(|| malformed("bit-packed run ends early"))?;
+            let bytes = &data[position..position + len];
+            position += len;
+            for index in 0..groups.saturating_mul(8).min(remaining) {
+                let value = (0..width).fold(0u32, |value, bit| {
+                    let offset = index * width + bit;
+                    value | u32::from(bytes[offset / 8] >> (offset % 8) & 1) << bit
+                });
+                values.push(value);
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Decompress a Snappy block
+fn snappy(input: &[u8], expected: usize) -> Result<Vec<u8>> {
+    let mut position = 0;
+    let len = varint(input, &mut position)?;
+    if len != expected as u64 {
+        return Err(malformed("Snappy length does not match the page header"));
+    }
+    let take = |position: &mut usize, len: usize| -> Result<&[u8]> {
+        let bytes = input
+            .get(*position..*position + len)
+            .ok_or_else(|| malformed("Snappy block ends early"))?;
+        *position += len;
+        Ok(bytes)
+    };
+    let little_endian = |bytes: &[u8]| bytes.iter().rev().fold(0usize, |value, &byte| value << 8 | byte as usize);
+
+    let mut output = Vec::with_capacity(expected.min(input.len().saturating_mul(64)));
+    while position < input.len() {
+        let tag = input[position];
+        position += 1;
+        let (len, offset) = match tag & 3 {
+            0 => {
+                let mut literal = (tag >> 2) as usize;
+                if literal >= 60 {
+                    literal = little_endian(take(&mut position, literal - 59)?);
+                }
+                let bytes = take(&mut position, literal + 1)?;
+                output.extend_from_slice(bytes);
+                if output.len() > expected {
+                    break;
+                }
+                continue;
+            }
+            1 => {
+                let low = take(&mut position, 1)?[0] as usize;
+                (((tag >> 2) & 7) as usize + 4, (tag as usize >> 5) << 8 | low)
+            }
+            2 => ((tag >> 2) as usize + 1, little_endian(take(&mut position, 2)?)),
+            _ => ((tag >> 2) as usize + 1, little_endian(take(&mut position, 4)?)),
+        };
+        if offset == 0 || offset > output.len() {
+            return Err(malformed("Snappy copy reaches before the block"));
+        }
+        if output.len() + len > expected {
+            break;
+        }
+        // Copies may overlap their own output
+        for _ in 0..len {
+            output.push(output[output.len() - offset]);
+        }
+    }
+    if output.len() != expected {
+        return Err(malformed("Snappy block does not match its length"));
+    }
+    Ok(output)
+}
+
+fn decompress(codec: i32, data: &[u8], uncompressed: usize) -> Result<Vec<u8>> {
+    match codec {
+        UNCOMPRESSED if data.len() == uncompressed => Ok(data.to_vec()),
+        UNCOMPRESSED => Err(malformed("uncompressed page size does not match its header")),
+        SNAPPY => snappy(data, uncompressed),
+        2 => Err(unsupported("GZIP compression")),
+        3 => Err(unsupported("LZO compression")),
+        4 => Err(unsupported("BROTLI compression")),
+        5 | 7 => Err(unsupported("LZ4 compression")),
+        6 => Err(unsupported("ZSTD compression")),
+        other => Err(malformed(format!("unknown codec {}", other))),
+    }
+}
+
+/// Decode `count` `PLAIN` values
+fn plain(leaf: &Leaf, data: &[u8], count: usize) -> Result<Vec<Scalar>> {
+    let fixed = |width: usize| -> Result<std::slice::ChunksExact<'_, u8>> {
+        if width == 0 || count.checked_mul(width).is_none_or(|len| len > data.len()) {
+            return Err(malformed("page ends early"));
+        }
+        Ok(data[..count * width].chunks_exact(width))
+    };
+    Ok(match leaf.physical {
+        BOOLEAN => {
+            if count.div_ceil(8) > data.len() {
+                return Err(malformed("page ends early"));
+            }
+            (0..count).map(|index| Scalar::Bool(data[index / 8] >> (index % 8) & 1 == 1)).collect()
+        }
+        INT32 => fixed(4)?
+            .map(|bytes| Scalar::Int(i32::from_le_bytes(bytes.try_into().expect("4 bytes")).into()))
+            .collect(),
+        INT64 => fixed(8)?
+            .map(|bytes| Scalar::Int(i64::from_le_bytes(bytes.try_into().expect("8 bytes"))))
+            .collect(),
+        FLOAT => fixed(4)?
+            .map(|bytes| Scalar::Float(f32::from_le_bytes(bytes.try_into().expect("4 bytes")).into()))
+            .collect(),
+        DOUBLE => fixed(8)?
+            .map(|bytes| Scalar::Float(f64::from_le_bytes(bytes.try_into().expect("8 bytes"))))
+            .collect(),
+        FIXED_LEN_BYTE_ARRAY => fixed(leaf.type_length)?.map(|bytes| Scalar::Bytes(bytes.to_vec())).collect(),
+        BYTE_ARRAY => {
+            let mut values = Vec::with_capacity(count.min(data.len() / 4));
+            let mut position = 0;
+            for _ in 0..count {
+                let len = data
+                    .get(position..position + 4)
+                    .map(|len| u32::from_le_bytes(len.try_into().expect("4 bytes")) as usize)
+                    .ok_or_else(|| malformed("page ends early"))?;
+                let bytes = data
+                    .get(position + 4..position + 4 + len)
+                    .ok_or_else(|| malformed("page ends early"))?;
+                values.push(Scalar::Bytes(bytes.to_vec()));
+                position += 4 + len;
+            }
+            values
+        }
+        other => return Err(unsupported(format!("physical type {}", other))),
+    })
+}
+
+/// Decode the `count` non-null values of a data page
+///
+/// The caller has charged `count` scalars; the bytes that dictionary
+/// lookups copy are charged here.
+fn values(
+    leaf: &Leaf,
+    encoding: i32,
+    data: &[u8],
+    count: usize,
+    dictionary: Option<&[Scalar]>,
+    budget: &mut Budget,
+) -> Result<Vec<Scalar>> {
+    match encoding {
+        PLAIN => plain(leaf, data, count),
+        PLAIN_DICTIONARY | RLE_DICTIONARY => {
+            let dictionary = dictionary.ok_or_else(|| malformed("dictionary page is missing"))?;
+            let (&width, indices) = data.split_first().ok_or_else(|| malformed("page ends early"))?;
+            hybrid(indices, width.into(), count)?
+                .into_iter()
+                .map(|index| {
+                    let value = dictionary
+                        .get(index as usize)
+                        .ok_or_else(|| malformed("dictionary index out of range"))?;
+                    if let Scalar::Bytes(bytes) = value {
+                        budget.charge(bytes.len(), 1)?;
+                    }
+                    Ok(value.clone())
+                })
+                .collect()
+        }
+        RLE if leaf.physical == BOOLEAN => {
+            let len = data
+                .get(..4)
+                .map(|len| u32::from_le_bytes(len.try_into().expect("4 bytes")) as usize)
+                .ok_or_else(|| malformed("page ends early"))?;
+            let bits = data.get(4..4 + len).ok_or_else(|| malformed("page ends early"))?;
+            Ok(hybrid(bits, 1, count)?.into_iter().map(|bit| Scalar::Bool(bit == 1)).collect())
+        }
+        other => Err(unsupported(format!("encoding {}", other))),
+    }
+}
+
+/// Split a length-prefixed v1 level run off the front of a page
+fn levels_v1(data: &mut &[u8], max: u32, count: usize, encoding: i64) -> Result<Vec<u32>> {
+    if max == 0 {
+        return Ok(vec![0; count]);
+    }
+    if encoding != i64::from(RLE) {
+        return Err(unsupported("BIT_PACKED levels"));
+    }
+    let len = data
+        .get(..4)
+        .map(|len| u32::from_le_bytes(len.try_into().expect("4 bytes")) as usize)
+        .ok_or_else(|| malformed("page ends early"))?;
+    let encoded = data.get(4..4 + len).ok_or_else(|| malformed("page ends early"))?;
+    *data = &data[4 + len..];
+    hybrid(encoded, bit_width(max), count)
+}
+
+/// Decode a column chunk into one cell per row
+fn read_chunk<R: Read + Seek>(
+    source: &mut R,
+    file_len: u64,
+    leaf: &Leaf,
+    chunk: &Struct,
+    rows: usize,
+    budget: &mut Budget,
+) -> Result<Vec<Cell>> {
+    let meta = required_struct(chunk, 3, "column metadata")?;
+    let codec = required_int(meta, 4, "codec")? as i32;
+    let num_values = size(required_int(meta, 5, "value count")?, "value count")?;
+    let len = required_int(meta, 7, "column size")? as u64;
+    let data_offset = required_int(meta, 9, "data page offset")? as u64;
+    let start = int(meta, 11)
+        .map(|offset| offset as u64)
+        .filter(|&offset| offset > 0 && offset < data_offset)
+        .unwrap_or(data_offset);
+    if start.checked_add(len).is_none_or(|end| end > file_len) {
+        return Err(malformed(format!("column '{}' lies outside the file", leaf.path.join("."))));
+    }
+    budget.charge(len as usize, 1)?;
+    let mut bytes = vec![0u8; len as usize];
+    source.seek(SeekFrom::Start(start))?;
+    source.read_exact(&mut bytes)?;
+
+    let (mut rep, mut def, mut decoded) = (Vec::new(), Vec::new(), Vec::new());
+    let mut dictionary = None;
+    let mut position = 0;
+    while def.len() < num_values && position < bytes.len() {
+        let mut decoder = Decoder::new(&bytes[position..]);
+        let header = decoder.read_struct().map_err(malformed)?;
+        position += decoder.position();
+        let compressed = size(required_int(&header, 3, "page size")?, "page size")?;
+        let uncompressed = size(required_int(&header, 2, "page size")?, "page size")?;
+        let body = bytes
+            .get(position..position + compressed)
+            .ok_or_else(|| malformed("page ends after its column"))?;
+        position += compressed;
+        budget.charge(uncompressed, 1)?;
+
+        match required_int(&header, 1, "page type")? as i32 {
+            DICTIONARY_PAGE => {
+                let page = required_struct(&header, 7, "dictionary page header")?;
+                let count = size(required_int(page, 1, "dictionary size")?, "dictionary size")?;
+                budget.charge(count, size_of::<Scalar>())?;
+                let body = decompress(codec, body, uncompressed)?;
+                dictionary = Some(plain(leaf, &body, count)?);
+            }
+            DATA_PAGE => {
+                let page = required_struct(&header, 5, "data page header")?;
+                let count = size(required_int(page, 1, "page value count")?, "page value count")?;
+                // Two level runs, then at most one value and one cell per level
+                budget.charge(count, 2 * size_of::<u32>() + 2 * size_of::<Scalar>())?;
+                let body = decompress(codec, body, uncompressed)?;
+                let mut rest = &body[..];
+                let page_rep = levels_v1(&mut rest, leaf.max_rep, count, int(page, 4).unwrap_or(RLE.into()))?;
+                let page_def = levels_v1(&mut rest, leaf.max_def, count, int(page, 3).unwrap_or(RLE.into()))?;
+                let present = page_def.iter().filter(|&&level| level == leaf.max_def).count();
+                let encoding = required_int(page, 2, "page encoding")? as i32;
+                decoded.extend(values(leaf, encoding, rest, present, dictionary.as_deref(), budget)?);
+                rep.extend(page_rep);
+                def.extend(page_def);
+            }
+            DATA_PAGE_V2 => {
+                let page = required_struct(&header, 8, "data page header")?;
+                let count = size(required_int(page, 1, "page value count")?, "page value count")?;
+                budget.charge(count, 2 * size_of::<u32>() + 2 * size_of::<Scalar>())?;
+                let def_len = size(required_int(page, 5, "level size")?, "level size")?;
+                let rep_len = size(required_int(page, 6, "level size")?, "level size")?;
+                if rep_len + def_len > body.len() || rep_len + def_len > uncompressed {
+                    return Err(malformed("levels exceed their page"));
+                }
+                let (rep_bytes, rest) = body.split_at(rep_len);
+                let (def_bytes, rest) = rest.split_at(def_len);
+                let levels = |bytes: &[u8], max: u32| match max {
+                    0 => Ok(vec![0; count]),
+                    max => hybrid(bytes, bit_width(max), count),
+                };
+                let page_rep = levels(rep_bytes, leaf.max_rep)?;
+                let page_def = levels(def_bytes, leaf.max_def)?;
+                let rest = match page.get(&7) {
+                    Some(Thrift::Bool(false)) => rest.to_vec(),
+                    _ => decompress(codec, rest, uncompressed - rep_len - def_len)?,
+                };
+                let present = page_def.iter().filter(|&&level| level == leaf.max_def).count();
+                let encoding = required_int(page, 4, "page encoding")? as i32;
+                decoded.extend(values(leaf, encoding, &rest, present, dictionary.as_deref(), budget)?);
+                rep.extend(page_rep);
+                def.extend(page_def);
+            }
+            // Index pages carry nothing a load needs
+            _ => {}
+        }
+    }
+
+    let cells = cells(leaf, &rep, &def, decoded)?;
+    if cells.len() != rows {
+        return Err(malformed(format!(
+            "column '{}' has {} rows, its row group {}",
+            leaf.path.join("."),
+            cells.len(),
+            rows
+        )));
+    }
+    Ok(cells)
+}
+
+/// Reassemble rows from levels and values
+fn cells(leaf: &Leaf, rep: &[u32], def: &[u32], values: Vec<Scalar>) -> Result<Vec<Cell>> {
+    let mut values = values.into_iter();
+    let mut cells = Vec::new();
+    for (&rep, &def) in rep.iter().zip(def) {
+        let value = match def == leaf.max_def {
+            true => Some(values.next().ok_or_else(|| malformed("levels outnumber values"))?),
+            false => None,
+        };
+        if leaf.max_rep == 0 {
+            cells.push(value.map_or(Cell::Null, Cell::One));
+            continue;
+        }
+        if rep == 0 {
+            cells.push(match def + 1 < leaf.list_def {
+                true => Cell::Null,
+                false => Cell::List(Vec::new()),
+            });
+        }
+        if def >= leaf.list_def {
+            match cells.last_mut() {
+                Some(Cell::List(items)) => items.push(value),
+                _ => return Err(malformed("list element outside a list")),
+            }
+        } else if rep != 0 {
+            return Err(malformed("invalid repetition levels"));
+        }
+    }
+    Ok(cells)
+}
+
+fn entity_id(leaf: &Leaf, scalar: Scalar) -> Conversion<EntityId> {
+    let Scalar::Bytes(bytes) = scalar else {
+        return Err("id is not a string".to_string());
+    };
+    if bytes.len() == 16 && (leaf.physical == FIXED_LEN_BYTE_ARRAY || leaf.annotation == Annotation::Uuid) {
+        return Ok(EntityId::from_uuid(uuid::Uuid::from_slice(&bytes).expect("16 bytes")));
+    }
+    let text = String::from_utf8_lossy(&bytes);
+    uuid::Uuid::parse_str(&text)
+        .map(EntityId::from_uuid)
+        .map_err(|_| format!("invalid id '{}'", text))
+}
+
+fn scalar_json(leaf: &Leaf, scalar: Scalar) -> Conversion<Value> {
+    let name = &leaf.path[0];
+    Ok(match scalar {
+        Scalar::Bool(value) => Value::Bool(value),
+        Scalar::Int(value) => Value::from(value),
+        Scalar::Float(value) => Number::from_f64(value)
+            .map(Value::Number)
+            .ok_or_else(|| format!("column '{}' holds a non-finite number", name))?,
+        Scalar::Bytes(bytes) => match leaf.annotation {
+            Annotation::Json => {
+                serde_json::from_slice(&bytes).map_err(|error| format!("column '{}' is not JSON: {}", name, error))?
+            }
+            Annotation::Uuid if bytes.len() == 16 => {
+                Value::String(uuid::Uuid::from_slice(&bytes).expect("16 bytes").to_string())
+            }
+            _ => String::from_utf8(bytes)
+                .map(Value::String)
+                .map_err(|_| format!("column '{}' is not UTF-8", name))?,
+        },
+    })
+}
+
+/// Build a row from one cell of every column
+fn row(leaves: &[Leaf], roles: &[Role], cells: Vec<Cell>) -> RowResult {
+    let mut row = BulkRow::default();
+    let mut metadata = Map::new();
+    for ((leaf, role), cell) in leaves.iter().zip(roles).zip(cells) {
+        match (role, cell) {
+            (_, Cell::Null) => {}
+            (Role::Id, Cell::One(scalar)) => row.id = Some(entity_id(leaf, scalar)?),
+            (Role::Vector, Cell::List(items)) => {
+                row.vector = items
+                    .into_iter()
+                    .map(|item| match item {
+                        Some(Scalar::Float(value)) => Ok(value as f32),
+                        Some(Scalar::Int(value)) => Ok(value as f32),
+                        _ => Err("vector has a null element".to_string()),
+                    })
+                    .collect::<Conversion<_>>()?;
+            }
+            (Role::Field(name), Cell::One(scalar)) => {
+                metadata.insert(name.clone(), scalar_json(leaf, scalar)?);
+            }
+            (Role::Field(name), Cell::List(items)) => {
+                let items = items
+                    .into_iter()
+                    .map(|item| item.map_or(Ok(Value::Null), |scalar| scalar_json(leaf, scalar)))
+                    .collect::<Conversion<_>>()?;
+                metadata.insert(name.clone(), Value::Array(items));
+            }
+            // Roles are assigned by shape, so nothing else can occur
+            _ => unreachable!("column shape checked when roles were assigned"),
+        }
+    }
+    row.metadata = (!metadata.is_empty()).then_some(Value::Object(metadata));
+    Ok(row)
+}
+
+/// Rows of a file, decoded one row group at a time
+struct ParquetRows<R> {
+    source: R,
+    file_len: u64,
+    max_row_group_bytes: usize,
+    leaves: Vec<Leaf>,
+    roles: Vec<Role>,
+    row_groups: std::vec::IntoIter<Struct>,
+    pending: std::vec::IntoIter<RowResult>,
+}
+
+impl<R: Read + Seek> ParquetRows<R> {
+    fn load(&mut self, row_group: &Struct) -> Result<Vec<RowResult>> {
+        let mut budget = Budget::new(self.max_row_group_bytes);
+        let rows = size(required_int(row_group, 3, "row count")?, "row count")?;
+        budget.charge(rows, size_of::<RowResult>())?;
+        let chunks: Vec<_> = structs_of(row_group, 1).collect();
+        if chunks.len() != self.leaves.len() {
+            return Err(malformed("row group does not match the schema"));
+        }
+        let mut columns = Vec::with_capacity(chunks.len());
+        for (leaf, chunk) in self.leaves.iter().zip(chunks) {
+            let chunk = chunk.ok_or_else(|| malformed("column chunk is not a struct"))?;
+            columns.push(read_chunk(&mut self.source, self.file_len, leaf, chunk, rows, &mut budget)?.into_iter());
+        }
+        Ok((0..rows)
+            .map(|_| {
+                let cells = columns.iter_mut().map(|column| column.next().expect("row count checked")).collect();
+                row(&self.leaves, &self.roles, cells)
+            })
+            .collect())
+    }
+}
+
+impl<R: Read + Seek> Iterator for ParquetRows<R> {
+    type Item = Result<RowResult>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.pending.next() {
+                return Some(Ok(row));
+            }
+            let row_group = self.row_groups.next()?;
+            match self.load(&row_group) {
+                Ok(rows) => self.pending = rows.into_iter(),
+                Err(error) => {
+                    // Nothing after a broken row group is trustworthy
+                    self.row_groups = Vec::new().into_iter();
+                    return Some(Err(error));
+                }
+            }
+        }
+    }
+}
+
+/// Read rows from the file at `path`
+///
+/// # Errors
+/// * `Bulk(Malformed)` if the footer cannot be decoded
+/// * `Bulk(Unsupported)` if the schema does not map to rows
+pub fn read(path: &Path, config: &BulkConfig) -> Result<Rows> {
+    read_from(File::open(path)?, config)
+}
+
+/// Read rows from a seekable source holding a whole file
+///
+/// # Errors
+/// As for `read`; row groups that would decode into more than
+/// `config.max_decode_bytes` yield `Bulk(Unsupported)`
+pub fn read_from<R: Read + Seek + 'static>(mut source: R, config: &BulkConfig) -> Result<Rows> {
+    let file_len = source.seek(SeekFrom::End(0))?;
+    if file_len < 12 {
+        return Err(malformed("file is too short"));
+    }
+    let mut head = [0u8; 4];
+    source.seek(SeekFrom::Start(0))?;
+    source.read_exact(&mut head)?;
+    let mut tail = [0u8; 8];
+    source.seek(SeekFrom::End(-8))?;
+    source.read_exact(&mut tail)?;
+    if &head != MAGIC || &tail[4..] != MAGIC {
+        return Err(malformed("missing PAR1 magic"));
+    }
+    let metadata_len = u64::from(u32::from_le_bytes(tail[..4].try_into().expect("4 bytes")));
+    if metadata_len > MAX_METADATA_LEN || metadata_len + 12 > file_len {
+        return Err(malformed(format!("footer of {} bytes", metadata_len)));
+    }
+    let mut metadata = vec![0u8; metadata_len as usize];
+    source.seek(SeekFrom::End(-8 - metadata_len as i64))?;
+    source.read_exact(&mut metadata)?;
+    let metadata = Decoder::new(&metadata).read_struct().map_err(malformed)?;
+
+    let schema = structs_of(&metadata, 2)
+        .map(|element| element.cloned().ok_or_else(|| malformed("schema element is not a struct")))
+        .collect::<Result<Vec<_>>>()?;
+    let leaves = leaves(&schema)?;
+    let roles = roles(&leaves, config)?;
+    let row_groups = structs_of(&metadata, 4)
+        .map(|row_group| row_group.cloned().ok_or_else(|| malformed("row group is not a struct")))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Box::new(ParquetRows {
+        source,
+        file_len,
+        max_row_group_bytes: config.max_decode_bytes,
+        leaves,
+        roles,
+        row_groups: row_groups.into_iter(),
+        pending: Vec::new().into_iter(),
+    }))
+}
+
+/// Parquet type chosen for a metadata field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Boolean,
+    Int64,
+    Double,
+    Text,
+    Json,
+}
+
+impl Kind {
+    fn of(value: &Value) -> Option<Self> {
+        match value {
+            Value::Null => None,
+            Value::Bool(_) => Some(Self::Boolean),
+            Value::Number(number) if number.is_i64() => Some(Self::Int64),
+            Value::Number(_) => Some(Self::Double),
+            Value::String(_) => Some(Self::Text),
+            _ => Some(Self::Json),
+        }
+    }
+
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Self::Int64 | Self::Double, Self::Int64 | Self::Double) => Self::Double,
+            _ => Self::Json,
+        }
+    }
+
+    fn element(self, name: &str) -> Thrift {
+        let (physical, converted, logical) = match self {
+            Self::Boolean => (BOOLEAN, None, None),
+            Self::Int64 => (INT64, None, None),
+            Self::Double => (DOUBLE, None, None),
+            Self::Text => (BYTE_ARRAY, Some(CONVERTED_UTF8), Some(LOGICAL_STRING)),
+            Self::Json => (BYTE_ARRAY, Some(CONVERTED_JSON), Some(LOGICAL_JSON)),
+        };
+        let mut fields = vec![
+            (1, Thrift::I32(physical)),
+            (3, Thrift::I32(OPTIONAL)),
+            (4, Thrift::Binary(name.as_bytes().to_vec())),
+        ];
+        if let (Some(converted), Some(logical)) = (converted, logical) {
+            fields.push((6, Thrift::I32(converted)));
+            fields.push((10, thrift::fields([(logical, thrift::fields([]))])));
+        }
+        thrift::fields(fields)
+    }
+
+    fn physical(self) -> i32 {
+        match self {
+            Self::Boolean => BOOLEAN,
+            Self::Int64 => INT64,
+            Self::Double => DOUBLE,
+            Self::Text | Self::Json => BYTE_ARRAY,
+        }
+    }
+}
+
+/// Metadata columns of an export, by name
+fn infer(rows: &[BulkRow], config: &BulkConfig) -> Result<BTreeMap<String, Option<Kind>>> {
+    let mut kinds: BTreeMap<String, Option<Kind>> = BTreeMap::new();
+    for row in rows {
+        let fields = match &row.metadata {
+            None => continue,
+            Some(Value::Object(fields)) => fields,
+            Some(_) => return Err(unsupported("metadata that is not a JSON object")),
+        };
+        for (name, value) in fields {
+            if *name == config.id_column || *name == config.vector_column {
+                return Err(unsupported(format!("metadata field '{}' named like the id or vector column", name)));
+            }
+            let kind = kinds.entry(name.clone()).or_default();
+            *kind = match (*kind, Kind::of(value)) {
+                (Some(kind), Some(other)) => Some(kind.merge(other)),
+                (kind, other) => kind.or(other),
+            };
+        }
+    }
+    Ok(kinds)
+}
+
+/// Levels and values of one column chunk, written as a single v1 page
+#[derive(Debug, Default)]
+struct Page {
+    rep: Vec<u32>,
+    def: Vec<u32>,
+    values: Vec<u8>,
+    bits: Vec<bool>,
+}
+
+impl Page {
+    fn bytes(&mut self, value: &[u8]) {
+        self.values.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        self.values.extend_from_slice(value);
+    }
+
+    /// Body of the page: repetition levels, definition levels, values
+    fn body(mut self, max_rep: u32, max_def: u32) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (levels, max) in [(&self.rep, max_rep), (&self.def, max_def)] {
+            if max > 0 {
+                let runs = rle(levels, bit_width(max));
+                body.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+                body.extend_from_slice(&runs);
+            }
+        }
+        for bits in self.bits.chunks(8) {
+            let byte = bits.iter().enumerate().fold(0u8, |byte, (index, &bit)| byte | u8::from(bit) << index);
+            self.values.push(byte);
+        }
+        body.extend_from_slice(&self.values);
+        body
+    }
+}
+
+/// Encode levels as RLE runs of the hybrid encoding
+fn rle(levels: &[u32], bit_width: u32) -> Vec<u8> {
+    let mut runs = Vec::new();
+    for run in levels.chunk_by(|a, b| a == b) {
+        let mut header = (run.len() as u64) << 1;
+        while header >= 0x80 {
+            runs.push(header as u8 | 0x80);
+            header >>= 7;
+        }
+        runs.push(header as u8);
+        runs.extend_from_slice(&run[0].to_le_bytes()[..bit_width.div_ceil(8) as usize]);
+    }
+    runs
+}
+
+/// Buffered writer that tracks its offset in the file
+struct Output {
+    writer: BufWriter<File>,
+    offset: u64,
+}
+
+impl Output {
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer.write_all(bytes)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Write a column chunk of one page, returning its `ColumnChunk`
+    fn chunk(&mut self, path: &[&str], physical: i32, page: Page, (max_rep, max_def): (u32, u32)) -> Result<Thrift> {
+        let num_values = page.def.len().max(page.rep.len());
+        let body = page.body(max_rep, max_def);
+        let header = thrift::fields([
+            (1, Thrift::I32(DATA_PAGE)),
+            (2, Thrift::I32(body.len() as i32)),
+            (3, Thrift::I32(body.len() as i32)),
+            (
+                5,
+                thrift::fields([
+                    (1, Thrift::I32(num_values as i32)),
+                    (2, Thrift::I32(PLAIN)),
+                    (3, Thrift::I32(RLE)),
+                    (4, Thrift::I32(RLE)),
+                ]),
+            ),
+        ]);
+        let mut encoder = Encoder::default();
+        if let Thrift::Struct(header) = &header {
+            encoder.write_struct(header);
+        }
+        let header = encoder.into_bytes();
+
+        let offset = self.offset as i64;
+        self.write(&header)?;
+        self.write(&body)?;
+        let size = (header.len() + body.len()) as i64;
+        Ok(thrift::fields([
+            (2, Thrift::I64(offset)),
+            (
+                3,
+                thrift::fields([
+                    (1, Thrift::I32(physical)),
+                    (2, thrift::i32s([PLAIN, RLE])),
+                    (3, thrift::strings(path.iter().copied())),
+                    (4, Thrift::I32(UNCOMPRESSED)),
+                    (5, Thrift::I64(num_values as i64)),
+                    (6, Thrift::I64(size)),
+                    (7, Thrift::I64(size)),
+                    (9, Thrift::I64(offset)),
+                ]),
+            ),
+        ]))
+    }
+}
+
+/// Write `rows` to `path`, returning the bytes written
+///
+/// # Errors
+/// * `Bulk(Unsupported)` if a row's metadata is not a JSON object or has a
+///   field named like the id or vector column
+pub fn write(path: &Path, rows: &[BulkRow], config: &BulkConfig) -> Result<u64> {
+    let kinds = infer(rows, config)?;
+    let (id, vector) = (config.id_column.as_str(), config.vector_column.as_str());
+
+    let mut schema = vec![
+        thrift::fields([
+            (4, Thrift::Binary(b"schema".to_vec())),
+            (5, Thrift::I32(2 + kinds.len() as i32)),
+        ]),
+        Kind::Text.element(id),
+        thrift::fields([
+            (3, Thrift::I32(REQUIRED)),
+            (4, Thrift::Binary(vector.as_bytes().to_vec())),
+            (5, Thrift::I32(1)),
+            (6, Thrift::I32(CONVERTED_LIST)),
+            (10, thrift::fields([(LOGICAL_LIST, thrift::fields([]))])),
+        ]),
+        thrift::fields([
+            (3, Thrift::I32(REPEATED)),
+            (4, Thrift::Binary(b"list".to_vec())),
+            (5, Thrift::I32(1)),
+        ]),
+        thrift::fields([
+            (1, Thrift::I32(FLOAT)),
+            (3, Thrift::I32(REQUIRED)),
+            (4, Thrift::Binary(b"element".to_vec())),
+        ]),
+    ];
+    // Columns with only nulls still need a type
+    let kinds: Vec<(&str, Kind)> = kinds
+        .iter()
+        .map(|(name, kind)| (name.as_str(), kind.unwrap_or(Kind::Json)))
+        .collect();
+    schema.extend(kinds.iter().map(|(name, kind)| kind.element(name)));
+
+    let mut output = Output {
+        writer: BufWriter::new(File::create(path)?),
+        offset: 0,
+    };
+    output.write(MAGIC)?;
+    let mut row_groups = Vec::new();
+    for group in rows.chunks(config.parquet_row_group_rows) {
+        let start = output.offset;
+        let mut chunks = Vec::with_capacity(2 + kinds.len());
+
+        let mut page = Page::default();
+        for row in group {
+            page.def.push(u32::from(row.id.is_some()));
+            if let Some(id) = row.id {
+                page.bytes(id.to_string().as_bytes());
+            }
+        }
+        chunks.push(output.chunk(&[id], BYTE_ARRAY, page, (0, 1))?);
+
+        let mut page = Page::default();
+        for row in group {
+            if row.vector.is_empty() {
+                page.rep.push(0);
+                page.def.push(0);
+            }
+            for (index, value) in row.vector.iter().enumerate() {
+                page.rep.push(u32::from(index > 0));
+                page.def.push(1);
+                page.values.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        chunks.push(output.chunk(&[vector, "list", "element"], FLOAT, page, (1, 1))?);
+
+        for &(name, kind) in &kinds {
+            let mut page = Page::default();
+            for row in group {
+                let value = row
+                    .metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.get(name))
+                    .filter(|value| !value.is_null());
+                page.def.push(u32::from(value.is_some()));
+                match (kind, value) {
+                    (_, None) => {}
+                    (Kind::Boolean, Some(value)) => page.bits.push(value.as_bool().unwrap_or_default()),
+                    (Kind::Int64, Some(value)) => {
+                        page.values.extend_from_slice(&value.as_i64().unwrap_or_default().to_le_bytes())
+                    }
+                    (Kind::Double, Some(value)) => {
+                        page.values.extend_from_slice(&value.as_f64().unwrap_or_default().to_le_bytes())
+                    }
+                    (Kind::Text, Some(value)) => page.bytes(value.as_str().unwrap_or_default().as_bytes()),
+                    (Kind::Json, Some(value)) => page.bytes(value.to_string().as_bytes()),
+                }
+            }
+            chunks.push(output.chunk(&[name], kind.physical(), page, (0, 1))?);
+        }
+
+        row_groups.push(thrift::fields([
+            (1, thrift::structs(chunks)),
+            (2, Thrift::I64((output.offset - start) as i64)),
+            (3, Thrift::I64(group.len() as i64)),
+        ]));
+    }
+
+    let metadata = thrift::fields([
+        (1, Thrift::I32(1)),
+        (2, thrift::structs(schema)),
+        (3, Thrift::I64(rows.len() as i64)),
+        (4, thrift::structs(row_groups)),
+        (6, Thrift::Binary(format!("phenix-db {}", crate::VERSION).into_bytes())),
+    ]);
+    let mut encoder = Encoder::default();
+    if let Thrift::Struct(metadata) = &metadata {
+        encoder.write_struct(metadata);
+    }
+    let metadata = encoder.into_bytes();
+    output.write(&metadata)?;
+    output.write(&(metadata.len() as u32).to_le_bytes())?;
+    output.write(MAGIC)?;
+    output.writer.flush()?;
+    output.writer.get_ref().sync_all()?;
+    Ok(output.offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn encode(value: &Thrift) -> Vec<u8> {
+        let mut encoder = Encoder::default();
+        encoder.write_struct(value.as_struct().unwrap());
+        encoder.into_bytes()
+    }
+
+    fn element(pairs: Vec<(i16, Thrift)>) -> Thrift {
+        thrift::fields(pairs)
+    }
+
+    fn name(name: &str) -> Thrift {
+        Thrift::Binary(name.as_bytes().to_vec())
+    }
+
+    /// Path, physical type, codec and pre-encoded pages of a column chunk
+    type Chunk<'a> = (Vec<&'a str>, i32, i32, Vec<(Thrift, Vec<u8>)>);
+
+    /// Assemble a file of one row group
+    fn assemble(schema: Vec<Thrift>, rows: i64, columns: Vec<Chunk<'_>>) -> Vec<u8> {
+        let mut file = MAGIC.to_vec();
+        let mut chunks = Vec::new();
+        for (path, physical, codec, pages) in columns {
+            let start = file.len() as i64;
+            let mut data_offset = start;
+            let mut values = 0;
+            for (header, body) in pages {
+                let fields = header.as_struct().unwrap();
+                if int(fields, 1) == Some(DICTIONARY_PAGE.into()) {
+                    data_offset = start + (encode(&header).len() + body.len()) as i64;
+                } else {
+                    let page = fields.get(&5).or_else(|| fields.get(&8)).unwrap().as_struct().unwrap();
+                    values += int(page, 1).unwrap();
+                }
+                file.extend(encode(&header));
+                file.extend(body);
+            }
+            let size = file.len() as i64 - start;
+            chunks.push(thrift::fields([
+                (2, Thrift::I64(start)),
+                (
+                    3,
+                    thrift::fields([
+                        (1, Thrift::I32(physical)),
+                        (2, thrift::i32s([PLAIN])),
+                        (3, thrift::strings(path)),
+                        (4, Thrift::I32(codec)),
+                        (5, Thrift::I64(values)),
+                        (6, Thrift::I64(size)),
+                        (7, Thrift::I64(size)),
+                        (9, Thrift::I64(data_offset)),
+                        (11, Thrift::I64(start)),
+                    ]),
+                ),
+            ]));
+        }
+        let metadata = encode(&thrift::fields([
+            (1, Thrift::I32(1)),
+            (2, thrift::structs(schema)),
+            (3, Thrift::I64(rows)),
+            (
+                4,
+                thrift::structs(vec![thrift::fields([
+                    (1, thrift::structs(chunks)),
+                    (2, Thrift::I64(0)),
+                    (3, Thrift::I64(rows)),
+                ])]),
+            ),
+        ]));
+        file.extend(&metadata);
+        file.extend((metadata.len() as u32).to_le_bytes());
+        file.extend(MAGIC);
+        file
+    }
+
+    fn v1_header(len: usize, values: i32, encoding: i32) -> Thrift {
+        thrift::fields([
+            (1, Thrift::I32(DATA_PAGE)),
+            (2, Thrift::I32(len as i32)),
+            (3, Thrift::I32(len as i32)),
+            (
+                5,
+                thrift::fields([
+                    (1, Thrift::I32(values)),
+                    (2, Thrift::I32(encoding)),
+                    (3, Thrift::I32(RLE)),
+                    (4, Thrift::I32(RLE)),
+                ]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_decodes_hybrid_runs_and_snappy() {
+        // RLE run of four 2s, then a bit-packed group of 0..8 at width 3
+        let data = [8, 2, 3, 0b1000_1000, 0b1100_0110, 0b1111_1010];
+        assert_eq!(hybrid(&data, 3, 12).unwrap(), vec![2, 2, 2, 2, 0, 1, 2, 3, 4, 5, 6, 7]);
+        assert!(hybrid(&data[..4], 3, 12).is_err());
+
+        // "abcd" then an overlapping copy of eight bytes at offset 4
+        let compressed = [12, 3 << 2, b'a', b'b', b'c', b'd', (4 << 2) | 1, 4];
+        assert_eq!(snappy(&compressed, 12).unwrap(), b"abcdabcdabcd");
+        assert!(snappy(&compressed, 13).is_err());
+        assert!(snappy(&[4, (4 << 2) | 1, 9], 4).is_err());
+    }
+
+    #[test]
+    fn test_reads_dictionary_v2_and_snappy_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.parquet");
+        let a = EntityId::new();
+
+        let schema = vec![
+            element(vec![(4, name("schema")), (5, Thrift::I32(3))]),
+            element(vec![
+                (1, Thrift::I32(BYTE_ARRAY)),
+                (3, Thrift::I32(OPTIONAL)),
+                (4, name("id")),
+                (6, Thrift::I32(CONVERTED_UTF8)),
+            ]),
+            element(vec![
+                (3, Thrift::I32(OPTIONAL)),
+                (4, name("vector")),
+                (5, Thrift::I32(1)),
+                (6, Thrift::I32(CONVERTED_LIST)),
+            ]),
+            element(vec![(3, Thrift::I32(REPEATED)), (4, name("list")), (5, Thrift::I32(1))]),
+            element(vec![(1, Thrift::I32(DOUBLE)), (3, Thrift::I32(OPTIONAL)), (4, name("element"))]),
+            element(vec![(1, Thrift::I32(BYTE_ARRAY)), (3, Thrift::I32(OPTIONAL)), (4, name("tag"))]),
+        ];
+
+        // id: v1 page, definition levels [1, 0, 1] bit-packed
+        let mut id = vec![2, 0, 0, 0, 3, 0b101];
+        for value in [a.to_string(), "nope".to_string()] {
+            id.extend((value.len() as u32).to_le_bytes());
+            id.extend(value.as_bytes());
+        }
+
+        // vector: v2 page with Snappy values; rows [1, 2], [1, null], null
+        let rep = [2, 0, 2, 1, 2, 0, 2, 1, 2, 0];
+        let def = [6, 3, 2, 2, 2, 0];
+        let doubles: Vec<u8> = [1.0f64, 2.0, 1.0].iter().flat_map(|value| value.to_le_bytes()).collect();
+        let mut compressed = vec![24, 15 << 2];
+        compressed.extend(&doubles[..16]);
+        compressed.extend([(4 << 2) | 1, 16]);
+        let mut vector = rep.to_vec();
+        vector.extend(def);
+        vector.extend(&compressed);
+        let vector_header = thrift::fields([
+            (1, Thrift::I32(DATA_PAGE_V2)),
+            (2, Thrift::I32((rep.len() + def.len() + 24) as i32)),
+            (3, Thrift::I32(vector.len() as i32)),
+            (
+                8,
+                thrift::fields([
+                    (1, Thrift::I32(5)),
+                    (2, Thrift::I32(2)),
+                    (3, Thrift::I32(3)),
+                    (4, Thrift::I32(PLAIN)),
+                    (5, Thrift::I32(def.len() as i32)),
+                    (6, Thrift::I32(rep.len() as i32)),
+                ]),
+            ),
+        ]);
+
+        // tag: dictionary ["x", "y"], indices [0, 1, 0] at width 1
+        let dictionary = vec![1, 0, 0, 0, b'x', 1, 0, 0, 0, b'y'];
+        let dictionary_header = thrift::fields([
+            (1, Thrift::I32(DICTIONARY_PAGE)),
+            (2, Thrift::I32(dictionary.len() as i32)),
+            (3, Thrift::I32(dictionary.len() as i32)),
+            (7, thrift::fields([(1, Thrift::I32(2)), (2, Thrift::I32(PLAIN))])),
+        ]);
+        let tag = vec![2, 0, 0, 0, 6, 1, 1, 3, 0b010];
+
+        let file = assemble(
+            schema,
+            3,
+            vec![
+                (vec!["id"], BYTE_ARRAY, UNCOMPRESSED, vec![(v1_header(id.len(), 3, PLAIN), id)]),
+                (vec!["vector", "list", "element"], DOUBLE, SNAPPY, vec![(vector_header, vector)]),
+                (
+                    vec!["tag"],
+                    BYTE_ARRAY,
+                    UNCOMPRESSED,
+                    vec![(dictionary_header, dictionary), (v1_header(tag.len(), 3, RLE_DICTIONARY), tag)],
+                ),
+            ],
+        );
+        std::fs::write(&path, file).unwrap();
+
+        let rows: Vec<RowResult> = read(&path, &BulkConfig::default()).unwrap().map(|row| row.unwrap()).collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            rows[0],
+            Ok(BulkRow {
+                id: Some(a),
+                vector: vec![1.0, 2.0],
+                metadata: Some(json!({"tag": "x"})),
+                ..BulkRow::default()
+            })
+        );
+        assert!(rows[1].as_ref().unwrap_err().contains("null element"));
+        assert!(rows[2].as_ref().unwrap_err().contains("invalid id 'nope'"));
+    }
+
+    #[test]
+    fn test_bounds_allocations_by_the_row_group_budget() {
+        let unsupported = |result: Option<Result<RowResult>>| match result {
+            Some(Err(MemorySubstrateError::Bulk {
+                error: BulkError::Unsupported { feature, .. },
+                ..
+            })) => feature.starts_with("row groups decoding to more than"),
+            _ => false,
+        };
+
+        // A page claiming 2^31 values behind one five-byte run of levels
+        let schema = vec![
+            element(vec![(4, name("schema")), (5, Thrift::I32(1))]),
+            element(vec![(1, Thrift::I32(BYTE_ARRAY)), (3, Thrift::I32(OPTIONAL)), (4, name("tag"))]),
+        ];
+        let mut body = 5u32.to_le_bytes().to_vec();
+        body.extend([0xfe, 0xff, 0xff, 0xff, 0x0f, 0]);
+        let file = assemble(
+            schema,
+            1,
+            vec![(vec!["tag"], BYTE_ARRAY, UNCOMPRESSED, vec![(v1_header(body.len(), i32::MAX, PLAIN), body)])],
+        );
+        let mut rows = read_from(std::io::Cursor::new(file), &BulkConfig::default()).unwrap();
+        assert!(unsupported(rows.next()));
+
+        // Rows of a schema without columns still cost memory
+        let schema = vec![element(vec![(4, name("schema")), (5, Thrift::I32(0))])];
+        let file = assemble(schema, i64::MAX, Vec::new());
+        let mut rows = read_from(std::io::Cursor::new(file), &BulkConfig::default()).unwrap();
+        assert!(unsupported(rows.next()));
+
+        // No single corrupted byte makes a valid file panic or overspend
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.parquet");
+        let config = BulkConfig {
+            max_decode_bytes: 1 << 20,
+            ..BulkConfig::default()
+        };
+        let rows: Vec<BulkRow> = (0..3)
+            .map(|index| BulkRow {
+                id: Some(EntityId::new()),
+                vector: vec![index as f32, 1.0],
+                metadata: Some(json!({"n": index, "tag": "x"})),
+                ..BulkRow::default()
+            })
+            .collect();
+        write(&path, &rows, &config).unwrap();
+        let file = std::fs::read(&path).unwrap();
+        for index in 0..file.len() {
+            for byte in [0x00, 0x7f, 0xff, file[index] ^ 0x01] {
+                let mut corrupted = file.clone();
+                corrupted[index] = byte;
+                if let Ok(rows) = read_from(std::io::Cursor::new(corrupted), &config) {
+                    rows.for_each(drop);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_infers_column_types() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.parquet");
+        let config = BulkConfig {
+            parquet_row_group_rows: 2,
+            ..BulkConfig::default()
+        };
+        let rows: Vec<BulkRow> = [
+            json!({"flag": true, "n": 1, "x": 1, "mixed": "a", "nested": {"k": [1]}}),
+            json!({"flag": false, "n": 2, "x": 2.5, "mixed": 3}),
+            json!({"nested": null}),
+        ]
+        .into_iter()
+        .map(|metadata| BulkRow {
+            id: Some(EntityId::new()),
+            vector: vec![0.5, -0.5],
+            metadata: Some(metadata),
+            ..BulkRow::default()
+        })
+        .collect();
+        write(&path, &rows, &config).unwrap();
+
+        let read_back: Vec<BulkRow> = read(&path, &config).unwrap().map(|row| row.unwrap().unwrap()).collect();
+        assert_eq!(read_back.len(), 3);
+        // Integers in a column with fractions come back as doubles
+        let mut first = rows[0].clone();
+        first.metadata.as_mut().unwrap()["x"] = json!(1.0);
+        assert_eq!(read_back[0], first);
+        assert_eq!(read_back[1].metadata, Some(json!({"flag": false, "n": 2, "x": 2.5, "mixed": 3})));
+        assert_eq!(read_back[2].metadata, None, "null fields are omitted");
+
+        let clash = BulkRow {
+            metadata: Some(json!({"vector": 1})),
+            ..BulkRow::default()
+        };
+        assert!(matches!(
+            write(&path, &[clash], &config),
+            Err(MemorySubstrateError::Bulk { error: BulkError::Unsupported { .. }, .. })
+        ));
+    }
+}
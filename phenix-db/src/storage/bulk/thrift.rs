@@ -0,0 +1,400 @@
+//! Thrift compact protocol, as used by Parquet metadata
+//!
+//! Only what Parquet needs: structs are decoded generically into field-id
+//! maps and the reader picks the fields it knows, skipping the rest.
+//! Unions decode as structs with a single field.
+
+use std::collections::BTreeMap;
+
+const STOP: u8 = 0;
+const TRUE: u8 = 1;
+const FALSE: u8 = 2;
+const BYTE: u8 = 3;
+const I16: u8 = 4;
+const I32: u8 = 5;
+const I64: u8 = 6;
+const DOUBLE: u8 = 7;
+const BINARY: u8 = 8;
+const LIST: u8 = 9;
+const SET: u8 = 10;
+const MAP: u8 = 11;
+const STRUCT: u8 = 12;
+
+/// Nesting limit; Parquet metadata is at most a handful of levels deep
+const MAX_DEPTH: usize = 32;
+
+/// Most values one decoder produces; a value may take a single byte to
+/// encode but a few dozen in memory
+const MAX_VALUES: usize = 1 << 22;
+
+/// Decoded value
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Value {
+    Bool(bool),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    Double(f64),
+    Binary(Vec<u8>),
+    /// Element type and elements; sets decode as lists
+    List(u8, Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    Struct(Struct),
+}
+
+/// Struct fields by id
+pub(super) type Struct = BTreeMap<i16, Value>;
+
+/// Decoding failure, described for a `Malformed` error
+pub(super) type DecodeResult<T> = std::result::Result<T, String>;
+
+impl Value {
+    fn type_code(&self) -> u8 {
+        match self {
+            Self::Bool(true) => TRUE,
+            Self::Bool(false) => FALSE,
+            Self::I8(_) => BYTE,
+            Self::I16(_) => I16,
+            Self::I32(_) => I32,
+            Self::I64(_) => I64,
+            Self::Double(_) => DOUBLE,
+            Self::Binary(_) => BINARY,
+            Self::List(..) => LIST,
+            Self::Map(_) => MAP,
+            Self::Struct(_) => STRUCT,
+        }
+    }
+
+    /// Integer value of any width
+    pub(super) fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Self::I8(value) => Some(value.into()),
+            Self::I16(value) => Some(value.into()),
+            Self::I32(value) => Some(value.into()),
+            Self::I64(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Binary(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Self::List(_, values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_struct(&self) -> Option<&Struct> {
+        match self {
+            Self::Struct(fields) => Some(fields),
+            _ => None,
+        }
+    }
+}
+
+/// Reads compact-protocol values from a byte slice
+pub(super) struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    values: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub(super) fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            position: 0,
+            values: 0,
+        }
+    }
+
+    /// Bytes consumed so far
+    pub(super) fn position(&self) -> usize {
+        self.position
+    }
+
+    fn byte(&mut self) -> DecodeResult<u8> {
+        let byte = *self.bytes.get(self.position).ok_or("unexpected end of metadata")?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn take(&mut self, len: usize) -> DecodeResult<&'a [u8]> {
+        if len > self.bytes.len() - self.position {
+            return Err("unexpected end of metadata".to_string());
+        }
+        let taken = &self.bytes[self.position..self.position + len];
+        self.position += len;
+        Ok(taken)
+    }
+
+    fn varint(&mut self) -> DecodeResult<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("varint is too long".to_string())
+    }
+
+    fn zigzag(&mut self) -> DecodeResult<i64> {
+        let value = self.varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    fn size(&mut self) -> DecodeResult<usize> {
+        let size = self.varint()? as usize;
+        // Every element takes at least one byte, except bools in a header
+        if size > self.bytes.len() - self.position {
+            return Err(format!("size {} exceeds the metadata", size));
+        }
+        Ok(size)
+    }
+
+    /// Count `count` values about to be decoded, before allocating them
+    fn reserve(&mut self, count: usize) -> DecodeResult<()> {
+        self.values = self
+            .values
+            .checked_add(count)
+            .filter(|&values| values <= MAX_VALUES)
+            .ok_or_else(|| format!("metadata holds more than {} values", MAX_VALUES))?;
+        Ok(())
+    }
+
+    /// Decode a struct
+    pub(super) fn read_struct(&mut self) -> DecodeResult<Struct> {
+        self.struct_at(0)
+    }
+
+    fn struct_at(&mut self, depth: usize) -> DecodeResult<Struct> {
+        if depth > MAX_DEPTH {
+            return Err("metadata is nested too deeply".to_string());
+        }
+        let mut fields = Struct::new();
+        let mut last_id = 0i16;
+        loop {
+            let header = self.byte()?;
+            if header == STOP {
+                return Ok(fields);
+            }
+            self.reserve(1)?;
+            let type_code = header & 0x0f;
+            let delta = header >> 4;
+            let id = if delta == 0 {
+                self.zigzag()? as i16
+            } else {
+                last_id.wrapping_add(delta.into())
+            };
+            let value = match type_code {
+                TRUE => Value::Bool(true),
+                FALSE => Value::Bool(false),
+                _ => self.value(type_code, depth)?,
+            };
+            fields.insert(id, value);
+            last_id = id;
+        }
+    }
+
+    fn value(&mut self, type_code: u8, depth: usize) -> DecodeResult<Value> {
+        Ok(match type_code {
+            // Inside collections a bool is a whole byte
+            TRUE | FALSE => Value::Bool(self.byte()? == TRUE),
+            BYTE => Value::I8(self.byte()? as i8),
+            I16 => Value::I16(self.zigzag()? as i16),
+            I32 => Value::I32(self.zigzag()? as i32),
+            I64 => Value::I64(self.zigzag()?),
+            DOUBLE => Value::Double(f64::from_le_bytes(self.take(8)?.try_into().expect("8 bytes"))),
+            BINARY => {
+                let len = self.size()?;
+                Value::Binary(self.take(len)?.to_vec())
+            }
+            LIST | SET => {
+                let header = self.byte()?;
+                let element = header & 0x0f;
+                let size = match header >> 4 {
+                    15 => self.size()?,
+                    size => size.into(),
+                };
+                self.reserve(size)?;
+                let values = (0..size)
+                    .map(|_| self.value(element, depth + 1))
+                    .collect::<DecodeResult<_>>()?;
+                Value::List(element, values)
+            }
+            MAP => {
+                let size = self.size()?;
+                self.reserve(size.saturating_mul(2))?;
+                let mut entries = Vec::with_capacity(size);
+                if size > 0 {
+                    let types = self.byte()?;
+                    for _ in 0..size {
+                        let key = self.value(types >> 4, depth + 1)?;
+                        let value = self.value(types & 0x0f, depth + 1)?;
+                        entries.push((key, value));
+                    }
+                }
+                Value::Map(entries)
+            }
+            STRUCT => Value::Struct(self.struct_at(depth + 1)?),
+            other => return Err(format!("unknown field type {}", other)),
+        })
+    }
+}
+
+/// Writes compact-protocol values
+#[derive(Debug, Default)]
+pub(super) struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    pub(super) fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn zigzag(&mut self, value: i64) {
+        self.varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    /// Encode a struct, fields in id order
+    pub(super) fn write_struct(&mut self, fields: &Struct) {
+        let mut last_id = 0i16;
+        for (&id, value) in fields {
+            let type_code = value.type_code();
+            match id.checked_sub(last_id) {
+                Some(delta @ 1..=15) => self.bytes.push((delta as u8) << 4 | type_code),
+                _ => {
+                    self.bytes.push(type_code);
+                    self.zigzag(id.into());
+                }
+            }
+            if !matches!(value, Value::Bool(_)) {
+                self.value(value);
+            }
+            last_id = id;
+        }
+        self.bytes.push(STOP);
+    }
+
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Bool(value) => self.bytes.push(if *value { TRUE } else { FALSE }),
+            Value::I8(value) => self.bytes.push(*value as u8),
+            Value::I16(value) => self.zigzag((*value).into()),
+            Value::I32(value) => self.zigzag((*value).into()),
+            Value::I64(value) => self.zigzag(*value),
+            Value::Double(value) => self.bytes.extend_from_slice(&value.to_le_bytes()),
+            Value::Binary(bytes) => {
+                self.varint(bytes.len() as u64);
+                self.bytes.extend_from_slice(bytes);
+            }
+            Value::List(element, values) => {
+                if values.len() < 15 {
+                    self.bytes.push((values.len() as u8) << 4 | element);
+                } else {
+                    self.bytes.push(0xf0 | element);
+                    self.varint(values.len() as u64);
+                }
+                values.iter().for_each(|value| self.value(value));
+            }
+            Value::Map(entries) => {
+                self.varint(entries.len() as u64);
+                if let Some((key, value)) = entries.first() {
+                    self.bytes.push(key.type_code() << 4 | value.type_code());
+                }
+                for (key, value) in entries {
+                    self.value(key);
+                    self.value(value);
+                }
+            }
+            Value::Struct(fields) => self.write_struct(fields),
+        }
+    }
+}
+
+/// Build a struct from `(id, value)` pairs
+pub(super) fn fields(pairs: impl IntoIterator<Item = (i16, Value)>) -> Value {
+    Value::Struct(pairs.into_iter().collect())
+}
+
+/// Build a list of structs
+pub(super) fn structs(values: Vec<Value>) -> Value {
+    Value::List(STRUCT, values)
+}
+
+/// Build a list of i32 values
+pub(super) fn i32s(values: impl IntoIterator<Item = i32>) -> Value {
+    Value::List(I32, values.into_iter().map(Value::I32).collect())
+}
+
+/// Build a list of strings
+pub(super) fn strings<'a>(values: impl IntoIterator<Item = &'a str>) -> Value {
+    Value::List(BINARY, values.into_iter().map(|value| Value::Binary(value.as_bytes().to_vec())).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips_structs() {
+        let nested = fields([(1, Value::I32(-7)), (40, Value::Binary(b"x".to_vec()))]);
+        let original = match fields([
+            (1, Value::Bool(true)),
+            (2, Value::I64(1 << 40)),
+            (3, Value::Double(0.5)),
+            (4, i32s([1, -1, 300])),
+            (5, structs(vec![nested.clone(); 20])),
+            (6, Value::Map(vec![(Value::Binary(b"k".to_vec()), Value::Bool(false))])),
+            (7, Value::List(TRUE, vec![Value::Bool(true), Value::Bool(false)])),
+            (300, Value::I16(-2)),
+        ]) {
+            Value::Struct(fields) => fields,
+            _ => unreachable!(),
+        };
+        let mut encoder = Encoder::default();
+        encoder.write_struct(&original);
+        let bytes = encoder.into_bytes();
+
+        let mut decoder = Decoder::new(&bytes);
+        assert_eq!(decoder.read_struct().unwrap(), original);
+        assert_eq!(decoder.position(), bytes.len());
+
+        // Truncation and absurd sizes are errors, not panics
+        for len in 0..bytes.len() {
+            assert!(Decoder::new(&bytes[..len]).read_struct().is_err());
+        }
+        assert!(Decoder::new(&[0x18, 0xff, 0xff, 0xff, 0x0f]).read_struct().is_err());
+
+        // A list of one-byte elements is capped by count, not only by size
+        let mut many = vec![0x19, 0xf3];
+        let mut count = MAX_VALUES as u64 + 1;
+        while count >= 0x80 {
+            many.push(count as u8 | 0x80);
+            count >>= 7;
+        }
+        many.push(count as u8);
+        many.resize(many.len() + MAX_VALUES + 1, 0);
+        many.push(STOP);
+        let error = Decoder::new(&many).read_struct().unwrap_err();
+        assert!(error.contains("more than"), "{}", error);
+    }
+}
@@ -0,0 +1,1103 @@
+//! Collection catalog
+//!
+//! The catalog maps collection names and aliases to collections and routes
+//! entity operations to the collection they are scoped to. Every mutation is
+//! validated, appended to the write-ahead log, and only then applied, so
+//! `Catalog::open` can rebuild the exact state by replaying the log.
+//!
+//! Entity operations only need `&self`: each collection serializes its own
+//! commits, and the log is locked just for the append, so readers and
+//! writers of other collections proceed while a commit waits on an fsync.
+//! Creating and dropping collections or aliases needs `&mut self`.
+//!
+//! Names and aliases share one namespace. Reads and entity operations accept
+//! either; dropping a collection requires its primary name so that a stale
+//! alias can never remove the wrong dataset.
+//!
+//! Every logged or replayed record is also fed to the catalog's
+//! [`ChangeCapture`], which turns it into change events for consumers. It is
+//! captured before it applies, so the collections themselves show the state
+//! the events are diffed against.
+//!
+//! A durable catalog writes a [`Checkpoint`] when asked to on shutdown: a
+//! snapshot of every collection, after which the log is reset. Opening
+//! restores the snapshot and replays only the records logged after it, and
+//! refuses a log that does not continue the checkpoint. The checkpoint is
+//! the last thing a catalog writes: mutations after it are refused, so a
+//! straggling writer can never outrun it.
+
+use crate::concurrency::access_tracker::AccessBatch;
+use crate::concurrency::transaction_coordinator::{Transaction, TransactionCoordinator};
+use crate::core::collection::{validate_name, CollectionConfig, CollectionInfo, CollectionStats};
+use crate::core::config::CdcConfig;
+use crate::core::error::{CollectionError, MemorySubstrateError, Result};
+use crate::core::error::ErrorContext;
+use crate::core::mvcc::{MvccWrite, Timestamp};
+use crate::core::query::{GraphQuery, TraversalStep, VectorQuery};
+use crate::core::{AccessStatistics, CollectionId, Entity, EntityId};
+use crate::index::filtered::FilteredSearchResult;
+use crate::storage::cdc::{ChangeCapture, EntityShape, PriorState};
+use crate::storage::checkpoint::{CatalogSnapshot, Checkpoint, CollectionSnapshot};
+use crate::storage::collection::{Collection, PriorEntities};
+use crate::storage::wal::{Wal, WalOp, WalRecord};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Registry of collections and aliases backed by an optional WAL
+#[derive(Debug, Default)]
+pub struct Catalog {
+    collections: HashMap<CollectionId, Collection>,
+    names: HashMap<String, CollectionId>,
+    aliases: HashMap<String, CollectionId>,
+    journal: Mutex<Journal>,
+}
+
+/// Where mutations are recorded before they are applied
+///
+/// Kept apart from the collections so a collection can be borrowed while
+/// its commit appends to the log, and locked only for the append.
+#[derive(Debug, Default)]
+struct Journal {
+    wal: Option<Wal>,
+    changes: Arc<ChangeCapture>,
+    closed: bool,
+}
+
+impl Catalog {
+    /// Create an empty catalog that does not persist mutations
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Create a non-persistent catalog whose change capture uses `cdc`
+    ///
+    /// Without a log, consumers can only resume within the retained events.
+    pub fn in_memory_with_cdc(cdc: &CdcConfig) -> Self {
+        Self {
+            journal: Mutex::new(Journal {
+                changes: Arc::new(ChangeCapture::new(cdc, None)),
+                ..Journal::default()
+            }),
+            ..Self::default()
+        }
+    }
+
+    /// Open a durable catalog, replaying the write-ahead log at `wal_path`
+    pub fn open(wal_path: impl AsRef<Path>) -> Result<Self> {
+        Self::open_with_cdc(wal_path, &CdcConfig::default())
+    }
+
+    /// Open a durable catalog whose change capture uses `cdc`
+    ///
+    /// The last checkpoint's snapshot is restored and the records logged
+    /// after it are replayed. Replayed records are captured too, so change
+    /// sequence numbers are the same after every restart. The log is checked
+    /// against the checkpoint before anything is written to it: a torn tail
+    /// is only truncated when it lies past the checkpointed position.
+    ///
+    /// # Errors
+    /// * `InvariantViolation` if the intact log does not continue the last
+    ///   checkpoint, or was reset and the checkpoint is missing; the log
+    ///   file is left as found
+    pub fn open_with_cdc(wal_path: impl AsRef<Path>, cdc: &CdcConfig) -> Result<Self> {
+        let wal_path = wal_path.as_ref();
+        let checkpoint = Checkpoint::read(&Checkpoint::path_for(wal_path))?;
+        let (wal, records) = Wal::open_checked(wal_path, |scan| match &checkpoint {
+            Some(checkpoint) => checkpoint.verify(scan),
+            None if scan.base_lsn > 1 => Err(MemorySubstrateError::InvariantViolation {
+                message: format!(
+                    "write-ahead log {} starts at LSN {} but its checkpoint is missing",
+                    wal_path.display(),
+                    scan.base_lsn
+                ),
+                context: Box::new(
+                    ErrorContext::new("catalog", "recover").with_detail("recovered_base_lsn", scan.base_lsn.to_string()),
+                ),
+            }),
+            None => Ok(()),
+        })?;
+
+        let mut catalog = Self {
+            journal: Mutex::new(Journal {
+                changes: Arc::new(ChangeCapture::new(cdc, Some(wal.path().to_path_buf()))),
+                ..Journal::default()
+            }),
+            ..Self::default()
+        };
+        let changes = catalog.journal.get_mut().changes.clone();
+        let mut replay_from = 1;
+        if let Some(checkpoint) = checkpoint {
+            changes.resume_at(&checkpoint);
+            replay_from = checkpoint.next_lsn;
+            TransactionCoordinator::advance_ids(checkpoint.next_txn_id.saturating_sub(1));
+            catalog.restore(checkpoint.state)?;
+        }
+        let mut replayed = 0;
+        for record in records.into_iter().filter(|record| record.lsn >= replay_from) {
+            changes.capture(&record, &catalog.collections);
+            catalog.replay(record)?;
+            replayed += 1;
+        }
+        tracing::info!(
+            collections = catalog.collections.len(),
+            replayed,
+            next_lsn = wal.next_lsn(),
+            "catalog recovered from checkpoint and write-ahead log"
+        );
+
+        catalog.journal.get_mut().wal = Some(wal);
+        Ok(catalog)
+    }
+
+    /// Create a collection
+    ///
+    /// # Errors
+    /// * `InvalidName` / `InvalidConfig` if validation fails
+    /// * `AlreadyExists` if the name is taken by a collection or alias
+    pub fn create_collection(&mut self, name: &str, config: CollectionConfig) -> Result<CollectionInfo> {
+        validate_name(name)?;
+        config.validate()?;
+        self.ensure_name_free(name)?;
+
+        let id = CollectionId::new();
+        let created_at = now_ms();
+        self.log(
+            id,
+            WalOp::CreateCollection {
+                name: name.to_string(),
+                config: config.clone(),
+                created_at,
+            },
+        )?;
+        self.insert_collection(id, name.to_string(), config, created_at);
+        Ok(self.info(id))
+    }
+
+    /// Drop a collection, its entities and every alias pointing at it
+    ///
+    /// # Errors
+    /// * `NotFound` if no collection has this primary name
+    pub fn drop_collection(&mut self, name: &str) -> Result<()> {
+        let id = *self.names.get(name).ok_or_else(|| not_found(name))?;
+        self.log(id, WalOp::DropCollection)?;
+        self.remove_collection(id);
+        Ok(())
+    }
+
+    /// Describe every collection, ordered by name
+    pub fn list_collections(&self) -> Vec<CollectionInfo> {
+        let mut infos: Vec<_> = self.collections.keys().map(|id| self.info(*id)).collect();
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        infos
+    }
+
+    /// Tier sizes, edge counts and access entropy of every collection,
+    /// ordered by name
+    pub fn collection_stats(&self) -> Vec<CollectionStats> {
+        let mut stats: Vec<_> = self.collections.values().map(Collection::stats).collect();
+        stats.sort_by(|a, b| a.name.cmp(&b.name));
+        stats
+    }
+
+    /// Describe a collection by name or alias
+    pub fn describe_collection(&self, name: &str) -> Result<CollectionInfo> {
+        Ok(self.info(self.resolve(name)?))
+    }
+
+    /// Resolve a collection name or alias to its id
+    pub fn resolve(&self, name: &str) -> Result<CollectionId> {
+        self.names
+            .get(name)
+            .or_else(|| self.aliases.get(name))
+            .copied()
+            .ok_or_else(|| not_found(name).into())
+    }
+
+    /// Access a collection by name or alias
+    pub fn collection(&self, name: &str) -> Result<&Collection> {
+        let id = self.resolve(name)?;
+        Ok(&self.collections[&id])
+    }
+
+    /// Change capture fed by every committed mutation
+    pub fn changes(&self) -> Arc<ChangeCapture> {
+        Arc::clone(&self.journal.lock().changes)
+    }
+
+    /// Point `alias` at the collection named `target`
+    ///
+    /// An existing alias is re-pointed atomically, which allows swapping a
+    /// rebuilt collection in behind a stable name.
+    ///
+    /// # Errors
+    /// * `InvalidName` if the alias is malformed
+    /// * `AlreadyExists` if the alias is the name of a collection
+    /// * `NotFound` if `target` does not resolve
+    pub fn set_alias(&mut self, alias: &str, target: &str) -> Result<()> {
+        validate_name(alias)?;
+        if self.names.contains_key(alias) {
+            return Err(CollectionError::AlreadyExists {
+                name: alias.to_string(),
+            }
+            .into());
+        }
+        let id = self.resolve(target)?;
+        self.log(
+            id,
+            WalOp::SetAlias {
+                alias: alias.to_string(),
+            },
+        )?;
+        self.aliases.insert(alias.to_string(), id);
+        Ok(())
+    }
+
+    /// Remove an alias
+    ///
+    /// # Errors
+    /// * `NotFound` if the alias does not exist
+    pub fn drop_alias(&mut self, alias: &str) -> Result<()> {
+        let id = *self.aliases.get(alias).ok_or_else(|| not_found(alias))?;
+        self.log(
+            id,
+            WalOp::DropAlias {
+                alias: alias.to_string(),
+            },
+        )?;
+        self.aliases.remove(alias);
+        Ok(())
+    }
+
+    /// Insert or replace an entity in a collection, returning its commit
+    /// timestamp
+    ///
+    /// # Errors
+    /// * `NotFound` if the collection does not resolve
+    /// * `InvalidEntity` / `Metadata` if the entity does not fit the
+    ///   collection; nothing is logged in that case
+    pub fn upsert(&self, collection: &str, entity: Entity) -> Result<Timestamp> {
+        let id = self.resolve(collection)?;
+        let op = WalOp::Upsert {
+            entity: Box::new(entity.clone()),
+        };
+        self.commit_logged(id, vec![MvccWrite::Put(Box::new(entity))], op)
+    }
+
+    /// Insert or replace a segment of entities with one log record
+    ///
+    /// Every entity is validated before anything is logged, so the segment
+    /// is applied entirely or not at all. With a durable log this costs a
+    /// single fsync for the whole segment.
+    ///
+    /// # Errors
+    /// * `NotFound` if the collection does not resolve
+    /// * `InvalidEntity` / `Metadata` if an entity does not fit the
+    ///   collection
+    pub fn load_segment(&self, collection: &str, entities: Vec<Entity>) -> Result<()> {
+        let id = self.resolve(collection)?;
+        if entities.is_empty() {
+            return Ok(());
+        }
+        let op = WalOp::LoadSegment {
+            entities: entities.clone(),
+        };
+        let writes = entities.into_iter().map(|entity| MvccWrite::Put(Box::new(entity))).collect();
+        self.commit_logged(id, writes, op)?;
+        Ok(())
+    }
+
+    /// Delete an entity from a collection, returning whether it existed
+    pub fn delete(&self, collection: &str, entity_id: &EntityId) -> Result<bool> {
+        let id = self.resolve(collection)?;
+        if !self.collections[&id].contains(entity_id) {
+            return Ok(false);
+        }
+        let op = WalOp::Delete {
+            entity_id: *entity_id,
+        };
+        self.commit_logged(id, vec![MvccWrite::Delete(*entity_id)], op)?;
+        Ok(true)
+    }
+
+    /// Commit a transaction begun on a collection's coordinator
+    ///
+    /// The write set is validated against the entities' current versions,
+    /// appended to the log as one `WalOp::Transaction` record and applied
+    /// under one commit timestamp, so recovery replays all of it or none.
+    ///
+    /// # Errors
+    /// * `NotFound` if the collection no longer exists
+    /// * `TransactionConflict` if the transaction conflicts, is not active
+    ///   or was begun on another collection
+    /// * `InvalidEntity` / `Metadata` if a written entity does not fit the
+    ///   collection; nothing is logged and the transaction is aborted
+    pub fn commit_transaction(&self, collection: CollectionId, txn: &mut Transaction) -> Result<Timestamp> {
+        let stored = self.collections.get(&collection).ok_or_else(|| CollectionError::NotFound {
+            name: collection.to_string(),
+        })?;
+        self.commit_logged_transaction(stored, txn)
+    }
+
+    /// Run `body` in a transaction on a collection, retrying it on conflicts
+    ///
+    /// Every attempt is committed like `commit_transaction`, so the attempt
+    /// that succeeds is logged as one record. `body` is re-run from a fresh
+    /// snapshot after a conflict, with the collection's retry backoff.
+    ///
+    /// # Returns
+    /// * What `body` returned on the committed attempt, with the commit
+    ///   timestamp
+    ///
+    /// # Errors
+    /// * `NotFound` if the collection does not resolve
+    /// * `RetryLimitExceeded` once every attempt has conflicted
+    /// * Any other error from `body` or the commit; nothing is applied
+    pub fn run_transaction<T, F>(&self, collection: &str, body: F) -> Result<(T, Timestamp)>
+    where
+        F: FnMut(&mut Transaction) -> Result<T>,
+    {
+        let id = self.resolve(collection)?;
+        let stored = &self.collections[&id];
+        stored
+            .transactions()
+            .run_with(body, |txn| self.commit_logged_transaction(stored, txn))
+    }
+
+    /// Look up an entity in a collection, recording the access
+    pub fn get(&self, collection: &str, entity_id: &EntityId) -> Result<Option<Arc<Entity>>> {
+        let collection = self.collection(collection)?;
+        let entity = collection.get(entity_id);
+        if entity.is_some() {
+            collection.access().record_access(*entity_id);
+        }
+        Ok(entity)
+    }
+
+    /// Run a k-NN query against a snapshot of a collection, returning the
+    /// hits with the entity versions they were scored against
+    ///
+    /// Every hit is recorded as accessed, and as co-accessed with the
+    /// closest hit.
+    pub fn search(&self, collection: &str, query: &VectorQuery) -> Result<(FilteredSearchResult, Vec<Arc<Entity>>)> {
+        let collection = self.collection(collection)?;
+        let (result, entities) = collection.view().search(query)?;
+        if let Some(best) = result.hits.first() {
+            let mut batch = AccessBatch::new();
+            for hit in &result.hits {
+                batch.record(hit.id);
+                batch.record_co_access(best.id, hit.id);
+            }
+            collection.access().apply(&mut batch);
+        }
+        Ok((result, entities))
+    }
+
+    /// Traverse edges within a snapshot of a collection, returning the
+    /// steps with the entity version reached at each
+    ///
+    /// Every reached entity is recorded as accessed, and as co-accessed with
+    /// the entity it was reached from.
+    pub fn traverse(&self, collection: &str, query: &GraphQuery) -> Result<(Vec<TraversalStep>, Vec<Arc<Entity>>)> {
+        let collection = self.collection(collection)?;
+        let (steps, entities) = collection.view().traverse(query);
+        let mut batch = AccessBatch::new();
+        for step in &steps {
+            batch.record(step.id);
+            if let Some(parent) = step.parent {
+                batch.record_co_access(parent, step.id);
+            }
+        }
+        collection.access().apply(&mut batch);
+        Ok((steps, entities))
+    }
+
+    /// Access statistics of an entity, if it has been read
+    pub fn access_statistics(&self, collection: &str, entity_id: &EntityId) -> Result<Option<AccessStatistics>> {
+        Ok(self.collection(collection)?.access().stats(entity_id))
+    }
+
+    /// Flush the write-ahead log to stable storage
+    pub fn sync(&self) -> Result<()> {
+        match &mut self.journal.lock().wal {
+            Some(wal) => wal.sync(),
+            None => Ok(()),
+        }
+    }
+
+    /// Flush the log, record a checkpoint next to it, reset the log and
+    /// close the catalog to further mutations
+    ///
+    /// The log is only reset once the checkpoint is durable; a crash in
+    /// between leaves records the next open skips.
+    ///
+    /// Returns `None` for an in-memory catalog, which has nothing to
+    /// checkpoint.
+    pub fn checkpoint(&mut self) -> Result<Option<Checkpoint>> {
+        if self.journal.get_mut().wal.is_none() {
+            return Ok(None);
+        }
+        let collections = self.collection_stats();
+        let state = self.snapshot();
+        let journal = self.journal.get_mut();
+        let next_sequence = journal.changes.head() + 1;
+        let Some(wal) = &mut journal.wal else {
+            return Ok(None);
+        };
+        wal.sync()?;
+        let checkpoint = Checkpoint {
+            next_lsn: wal.next_lsn(),
+            next_sequence,
+            next_txn_id: TransactionCoordinator::next_id(),
+            created_at: now_ms(),
+            collections,
+            state,
+        };
+        checkpoint.write(&Checkpoint::path_for(wal.path()))?;
+        wal.reset()?;
+        journal.closed = true;
+        Ok(Some(checkpoint))
+    }
+
+    /// Every collection with its latest entities, and the aliases
+    fn snapshot(&self) -> CatalogSnapshot {
+        let mut collections: Vec<_> = self
+            .collections
+            .values()
+            .map(|collection| {
+                let mut entities: Vec<Entity> = collection.entities().iter().map(|e| Entity::clone(e)).collect();
+                entities.sort_by_key(|entity| entity.id);
+                CollectionSnapshot {
+                    id: collection.id(),
+                    name: collection.name().to_string(),
+                    config: collection.config().clone(),
+                    created_at: collection.created_at(),
+                    entities,
+                }
+            })
+            .collect();
+        collections.sort_by(|a, b| a.name.cmp(&b.name));
+        CatalogSnapshot {
+            collections,
+            aliases: self.aliases.iter().map(|(alias, id)| (alias.clone(), *id)).collect(),
+        }
+    }
+
+    /// Load a checkpoint's snapshot into an empty catalog
+    fn restore(&mut self, snapshot: CatalogSnapshot) -> Result<()> {
+        for collection in snapshot.collections {
+            let id = collection.id;
+            self.insert_collection(id, collection.name, collection.config, collection.created_at);
+            if !collection.entities.is_empty() {
+                self.collections[&id].load_segment(collection.entities)?;
+            }
+        }
+        self.aliases.extend(snapshot.aliases);
+        Ok(())
+    }
+
+    fn log(&mut self, collection: CollectionId, op: WalOp) -> Result<()> {
+        self.journal.get_mut().append(collection, op, &self.collections)
+    }
+
+    /// Log `op` and apply `writes` to a collection as one commit
+    fn commit_logged(&self, collection: CollectionId, writes: Vec<MvccWrite>, op: WalOp) -> Result<Timestamp> {
+        let stored = &self.collections[&collection];
+        stored.commit_logged(writes, |_, prior| {
+            self.journal
+                .lock()
+                .append(collection, op, &Committing { collection: stored, prior })
+        })
+    }
+
+    /// Commit a transaction on `collection`, logging its write set as one
+    /// record before it is applied
+    fn commit_logged_transaction(&self, collection: &Collection, txn: &mut Transaction) -> Result<Timestamp> {
+        let txn_id = txn.id();
+        collection.commit_transaction(txn, |writes, prior| {
+            self.journal.lock().append(
+                collection.id(),
+                WalOp::Transaction {
+                    txn_id,
+                    writes: writes.to_vec(),
+                },
+                &Committing { collection, prior },
+            )
+        })
+    }
+
+    /// Apply a logged record without logging it again
+    fn replay(&mut self, record: WalRecord) -> Result<()> {
+        let id = record.collection;
+        match record.op {
+            WalOp::CreateCollection {
+                name,
+                config,
+                created_at,
+            } => {
+                self.insert_collection(id, name, config, created_at);
+            }
+            WalOp::DropCollection => self.remove_collection(id),
+            WalOp::SetAlias { alias } => {
+                self.aliases.insert(alias, id);
+            }
+            WalOp::DropAlias { alias } => {
+                self.aliases.remove(&alias);
+            }
+            WalOp::Upsert { entity } => {
+                if let Some(collection) = self.collections.get(&id) {
+                    collection.upsert(*entity)?;
+                }
+            }
+            WalOp::Delete { entity_id } => {
+                if let Some(collection) = self.collections.get(&id) {
+                    collection.delete(&entity_id);
+                }
+            }
+            WalOp::Transaction { txn_id, writes } => {
+                if let Some(collection) = self.collections.get(&id) {
+                    collection.commit(writes)?;
+                    TransactionCoordinator::advance_ids(txn_id);
+                }
+            }
+            WalOp::LoadSegment { entities } => {
+                if let Some(collection) = self.collections.get(&id) {
+                    collection.load_segment(entities)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn ensure_name_free(&self, name: &str) -> Result<()> {
+        if self.names.contains_key(name) || self.aliases.contains_key(name) {
+            return Err(CollectionError::AlreadyExists {
+                name: name.to_string(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    fn insert_collection(&mut self, id: CollectionId, name: String, config: CollectionConfig, created_at: u64) {
+        self.names.insert(name.clone(), id);
+        self.collections
+            .insert(id, Collection::new(id, name, config, created_at));
+    }
+
+    fn remove_collection(&mut self, id: CollectionId) {
+        if let Some(collection) = self.collections.remove(&id) {
+            self.names.remove(collection.name());
+        }
+        self.aliases.retain(|_, target| *target != id);
+    }
+
+    fn info(&self, id: CollectionId) -> CollectionInfo {
+        let mut aliases: Vec<_> = self
+            .aliases
+            .iter()
+            .filter(|(_, target)| **target == id)
+            .map(|(alias, _)| alias.clone())
+            .collect();
+        aliases.sort();
+        self.collections[&id].info(aliases)
+    }
+}
+
+impl Journal {
+    /// Log `op` and feed it to change capture, diffing against `prior`
+    fn append(&mut self, collection: CollectionId, op: WalOp, prior: &dyn PriorState) -> Result<()> {
+        if self.closed {
+            return Err(MemorySubstrateError::Internal(
+                "catalog is closed after its shutdown checkpoint".to_string(),
+            ));
+        }
+        let record = match &mut self.wal {
+            Some(wal) => wal.append_record(collection, op)?,
+            None => WalRecord {
+                lsn: self.changes.next_lsn(),
+                collection,
+                timestamp: now_ms(),
+                op,
+            },
+        };
+        self.changes.capture(&record, prior);
+        Ok(())
+    }
+}
+
+impl PriorState for HashMap<CollectionId, Collection> {
+    fn collection_name(&self, collection: CollectionId) -> Option<String> {
+        self.get(&collection).map(|c| c.name().to_string())
+    }
+
+    fn entity(&self, collection: CollectionId, id: &EntityId) -> Option<EntityShape> {
+        self.get(&collection)?.get(id).map(|entity| EntityShape::from(&*entity))
+    }
+
+    fn entity_ids(&self, collection: CollectionId) -> Vec<EntityId> {
+        self.get(&collection)
+            .map_or_else(Vec::new, |c| c.entities().iter().map(|entity| entity.id).collect())
+    }
+}
+
+/// A collection in the middle of a commit, which holds its commit lock;
+/// the entities written are read from what the commit replaces
+struct Committing<'a> {
+    collection: &'a Collection,
+    prior: &'a PriorEntities,
+}
+
+impl PriorState for Committing<'_> {
+    fn collection_name(&self, collection: CollectionId) -> Option<String> {
+        (collection == self.collection.id()).then(|| self.collection.name().to_string())
+    }
+
+    fn entity(&self, collection: CollectionId, id: &EntityId) -> Option<EntityShape> {
+        if collection != self.collection.id() {
+            return None;
+        }
+        self.prior.get(id).map(|entity| EntityShape::from(&**entity))
+    }
+
+    fn entity_ids(&self, _collection: CollectionId) -> Vec<EntityId> {
+        // Commits only write entities; dropping a collection is not one
+        Vec::new()
+    }
+}
+
+fn not_found(name: &str) -> CollectionError {
+    CollectionError::NotFound {
+        name: name.to_string(),
+    }
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::collection::{ConcurrencyMode, IndexConfig};
+    use crate::core::error::MemorySubstrateError;
+    use crate::core::{DistanceMetric, Vector};
+    use serde_json::json;
+
+    fn config(dimension: usize) -> CollectionConfig {
+        CollectionConfig::new(dimension).with_index(IndexConfig {
+            m: 8,
+            ef_construction: 32,
+            ef_search: 32,
+        })
+    }
+
+    fn entity(values: Vec<f32>) -> Entity {
+        Entity::new(Some(Vector::new(values)), Some(json!({"tag": "x"})), None)
+    }
+
+    fn is_not_found(result: Result<impl std::fmt::Debug>) -> bool {
+        matches!(
+            result,
+            Err(MemorySubstrateError::Collection {
+                error: CollectionError::NotFound { .. },
+                ..
+            })
+        )
+    }
+
+    #[test]
+    fn test_create_list_describe_drop() {
+        let mut catalog = Catalog::in_memory();
+        catalog.create_collection("images", config(4)).unwrap();
+        catalog
+            .create_collection("docs", config(2).with_metric(DistanceMetric::Euclidean))
+            .unwrap();
+
+        assert!(matches!(
+            catalog.create_collection("docs", config(2)),
+            Err(MemorySubstrateError::Collection {
+                error: CollectionError::AlreadyExists { .. },
+                ..
+            })
+        ));
+        assert!(catalog.create_collection("bad name", config(2)).is_err());
+        assert!(catalog.create_collection("zero", config(0)).is_err());
+
+        let names: Vec<_> = catalog.list_collections().into_iter().map(|c| c.name).collect();
+        assert_eq!(names, vec!["docs", "images"]);
+
+        let docs = catalog.describe_collection("docs").unwrap();
+        assert_eq!(docs.config.dimension, 2);
+        assert_eq!(docs.config.metric, DistanceMetric::Euclidean);
+
+        catalog.drop_collection("docs").unwrap();
+        assert!(is_not_found(catalog.describe_collection("docs")));
+        assert!(is_not_found(catalog.drop_collection("docs")));
+        assert_eq!(catalog.list_collections().len(), 1);
+    }
+
+    #[test]
+    fn test_entities_are_scoped_to_collections() {
+        let mut catalog = Catalog::in_memory();
+        catalog.create_collection("a", config(2)).unwrap();
+        catalog.create_collection("b", config(3)).unwrap();
+
+        let e = entity(vec![1.0, 0.0]);
+        let id = e.id;
+        catalog.upsert("a", e.clone()).unwrap();
+        assert!(catalog.upsert("b", e).is_err());
+
+        assert!(catalog.get("a", &id).unwrap().is_some());
+        assert!(catalog.get("b", &id).unwrap().is_none());
+        assert_eq!(catalog.describe_collection("a").unwrap().entity_count, 1);
+        assert_eq!(catalog.describe_collection("b").unwrap().entity_count, 0);
+
+        let hits = catalog
+            .search("a", &VectorQuery::new(Vector::new(vec![1.0, 0.0]), 5))
+            .unwrap()
+            .0
+            .hits;
+        assert_eq!(hits.len(), 1);
+        assert!(catalog
+            .search("b", &VectorQuery::new(Vector::new(vec![1.0, 0.0]), 5))
+            .is_err());
+
+        assert!(!catalog.delete("b", &id).unwrap());
+        assert!(catalog.delete("a", &id).unwrap());
+        assert!(is_not_found(catalog.upsert("missing", entity(vec![1.0, 0.0]))));
+    }
+
+    #[test]
+    fn test_reads_record_access_statistics() {
+        let mut catalog = Catalog::in_memory();
+        catalog.create_collection("docs", config(2)).unwrap();
+        let (near, far) = (entity(vec![1.0, 0.0]), entity(vec![0.0, 1.0]));
+        let (near_id, far_id) = (near.id, far.id);
+        catalog.upsert("docs", near).unwrap();
+        catalog.upsert("docs", far).unwrap();
+        assert!(catalog.access_statistics("docs", &near_id).unwrap().is_none());
+
+        catalog.get("docs", &near_id).unwrap();
+        catalog
+            .search("docs", &VectorQuery::new(Vector::new(vec![1.0, 0.1]), 2))
+            .unwrap();
+
+        let stats = catalog.access_statistics("docs", &near_id).unwrap().unwrap();
+        assert_eq!(stats.total_accesses, 2);
+        assert_eq!(stats.co_access_entities, vec![far_id]);
+        let stats = catalog.access_statistics("docs", &far_id).unwrap().unwrap();
+        assert_eq!(stats.total_accesses, 1);
+
+        catalog.delete("docs", &near_id).unwrap();
+        assert!(catalog.access_statistics("docs", &near_id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut catalog = Catalog::in_memory();
+        catalog.create_collection("docs_v1", config(2)).unwrap();
+        catalog.create_collection("docs_v2", config(2)).unwrap();
+
+        catalog.set_alias("docs", "docs_v1").unwrap();
+        catalog.upsert("docs", entity(vec![1.0, 0.0])).unwrap();
+        assert_eq!(catalog.describe_collection("docs_v1").unwrap().entity_count, 1);
+        assert_eq!(catalog.describe_collection("docs_v1").unwrap().aliases, vec!["docs"]);
+
+        // Alias and collection names share a namespace
+        assert!(catalog.set_alias("docs_v2", "docs_v1").is_err());
+        assert!(catalog.create_collection("docs", config(2)).is_err());
+
+        // Re-point and drop
+        catalog.set_alias("docs", "docs_v2").unwrap();
+        assert_eq!(catalog.describe_collection("docs").unwrap().name, "docs_v2");
+        assert!(is_not_found(catalog.drop_collection("docs")));
+        catalog.drop_alias("docs").unwrap();
+        assert!(is_not_found(catalog.drop_alias("docs")));
+
+        // Dropping a collection removes its aliases
+        catalog.set_alias("latest", "docs_v2").unwrap();
+        catalog.drop_collection("docs_v2").unwrap();
+        assert!(is_not_found(catalog.resolve("latest")));
+    }
+
+    #[test]
+    fn test_recovery_from_wal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("catalog.wal");
+
+        let kept = entity(vec![0.0, 1.0]);
+        let deleted = entity(vec![1.0, 0.0]);
+        let created_at;
+        {
+            let mut catalog = Catalog::open(&path).unwrap();
+            created_at = catalog.create_collection("docs", config(2)).unwrap().created_at;
+            catalog.create_collection("tmp", config(2)).unwrap();
+            catalog.set_alias("current", "docs").unwrap();
+            catalog.upsert("current", kept.clone()).unwrap();
+            catalog.upsert("docs", deleted.clone()).unwrap();
+            catalog.delete("docs", &deleted.id).unwrap();
+            catalog.upsert("tmp", entity(vec![1.0, 1.0])).unwrap();
+            catalog.drop_collection("tmp").unwrap();
+
+            // Rejected operations must not reach the log
+            assert!(catalog.upsert("docs", entity(vec![1.0])).is_err());
+        }
+
+        let mut catalog = Catalog::open(&path).unwrap();
+        let infos = catalog.list_collections();
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].name, "docs");
+        assert_eq!(infos[0].aliases, vec!["current"]);
+        assert_eq!(infos[0].entity_count, 1);
+        assert_eq!(infos[0].created_at, created_at);
+        assert!(catalog.get("docs", &kept.id).unwrap().is_some());
+        assert!(catalog.get("docs", &deleted.id).unwrap().is_none());
+
+        // Recreating a dropped name works and survives another restart
+        catalog.create_collection("tmp", config(3)).unwrap();
+        drop(catalog);
+        let catalog = Catalog::open(&path).unwrap();
+        assert_eq!(catalog.describe_collection("tmp").unwrap().config.dimension, 3);
+        assert_eq!(catalog.describe_collection("tmp").unwrap().entity_count, 0);
+    }
+
+    #[test]
+    fn test_transactions_are_logged_all_or_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("catalog.wal");
+        let (a, b, c) = (entity(vec![1.0, 0.0]), entity(vec![0.0, 1.0]), entity(vec![1.0, 1.0]));
+        let first_id;
+        {
+            let mut catalog = Catalog::open(&path).unwrap();
+            catalog.create_collection("docs", config(2)).unwrap();
+            let id = catalog.resolve("docs").unwrap();
+
+            let mut txn = catalog.collection("docs").unwrap().transactions().begin();
+            first_id = txn.id();
+            txn.put(a.clone()).unwrap();
+            txn.put(b.clone()).unwrap();
+            catalog.commit_transaction(id, &mut txn).unwrap();
+
+            // An invalid write aborts the whole transaction before logging
+            let mut txn = catalog.collection("docs").unwrap().transactions().begin();
+            txn.delete(a.id).unwrap();
+            txn.put(entity(vec![1.0])).unwrap();
+            assert!(catalog.commit_transaction(id, &mut txn).is_err());
+            assert!(catalog.get("docs", &a.id).unwrap().is_some());
+
+            let mut txn = catalog.collection("docs").unwrap().transactions().begin();
+            txn.delete(a.id).unwrap();
+            txn.put(c.clone()).unwrap();
+            catalog.commit_transaction(id, &mut txn).unwrap();
+            assert_eq!(catalog.collection("docs").unwrap().len(), 2);
+        }
+
+        // Crash mid-commit of the second transaction: its frame is torn
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(file.metadata().unwrap().len() - 7).unwrap();
+
+        let catalog = Catalog::open(&path).unwrap();
+        assert!(catalog.get("docs", &a.id).unwrap().is_some());
+        assert!(catalog.get("docs", &b.id).unwrap().is_some());
+        assert!(catalog.get("docs", &c.id).unwrap().is_none());
+
+        // New transactions get fresh ids after recovery
+        let txn = catalog.collection("docs").unwrap().transactions().begin();
+        assert!(txn.id() > first_id);
+    }
+
+    #[test]
+    fn test_retried_transactions_are_logged() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("catalog.wal");
+        let counter = entity(vec![0.0, 1.0]);
+        {
+            let mut catalog = Catalog::open(&path).unwrap();
+            catalog.create_collection("docs", config(2)).unwrap();
+            catalog.upsert("docs", counter.clone()).unwrap();
+            let engine = Arc::clone(catalog.collection("docs").unwrap().engine());
+
+            let mut attempts = 0;
+            let (seen, commit_ts) = catalog
+                .run_transaction("docs", |txn| {
+                    attempts += 1;
+                    let mut current = txn.get(&counter.id).unwrap();
+                    if attempts == 1 {
+                        // A concurrent writer commits before the first attempt does
+                        engine.put(current.clone());
+                    }
+                    current.version += 1;
+                    txn.put(current)?;
+                    Ok(attempts)
+                })
+                .unwrap();
+            assert_eq!(seen, 2);
+            assert_eq!(catalog.get("docs", &counter.id).unwrap().unwrap().commit_ts, commit_ts);
+
+            assert!(matches!(
+                catalog.run_transaction("missing", |_| Ok(())),
+                Err(MemorySubstrateError::Collection { .. })
+            ));
+            // Crash without a checkpoint
+        }
+
+        let catalog = Catalog::open(&path).unwrap();
+        assert_eq!(catalog.get("docs", &counter.id).unwrap().unwrap().version, 2);
+    }
+
+    #[test]
+    fn test_pessimistic_collections_serialize_contended_updates() {
+        let mut catalog = Catalog::in_memory();
+        let pessimistic = ConcurrencyMode::Pessimistic { lock_timeout_ms: 5_000 };
+        catalog.create_collection("hub", config(2).with_concurrency(pessimistic)).unwrap();
+        catalog.create_collection("docs", config(2)).unwrap();
+        assert!(catalog.collection("docs").unwrap().transactions().lock_manager().is_none());
+        let counter = entity(vec![0.0, 1.0]);
+        catalog.upsert("hub", counter.clone()).unwrap();
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..25 {
+                        // Every attempt takes the lock first, so none has to retry
+                        let (attempts, _) = catalog
+                            .run_transaction("hub", |txn| {
+                                let mut current = txn.get_for_update(&counter.id)?.unwrap();
+                                current.version += 1;
+                                txn.put(current)?;
+                                Ok(1)
+                            })
+                            .unwrap();
+                        assert_eq!(attempts, 1);
+                    }
+                });
+            }
+        });
+        assert_eq!(catalog.get("hub", &counter.id).unwrap().unwrap().version, counter.version + 100);
+    }
+
+    #[test]
+    fn test_checkpoint_guards_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("catalog.wal");
+        assert!(Catalog::in_memory().checkpoint().unwrap().is_none());
+
+        let mut catalog = Catalog::open(&path).unwrap();
+        catalog.create_collection("docs", config(2)).unwrap();
+        catalog.upsert("docs", entity(vec![0.0, 1.0])).unwrap();
+        let covered = std::fs::read(&path).unwrap();
+        let checkpoint = catalog.checkpoint().unwrap().unwrap();
+        assert_eq!(checkpoint.next_lsn, 3);
+        assert_eq!(checkpoint.collections[0].entity_count, 1);
+        assert!(matches!(
+            catalog.upsert("docs", entity(vec![1.0, 1.0])),
+            Err(MemorySubstrateError::Internal(_))
+        ));
+        drop(catalog);
+
+        let read = Checkpoint::read(&Checkpoint::path_for(&path)).unwrap().unwrap();
+        assert_eq!((read.next_lsn, read.created_at), (checkpoint.next_lsn, checkpoint.created_at));
+        assert_eq!(read.next_txn_id, checkpoint.next_txn_id);
+        assert_eq!(read.state.collections[0].entities.len(), 1);
+
+        // The log was reset; the snapshot alone restores the catalog
+        let reset = std::fs::read(&path).unwrap();
+        assert!(Wal::read_file(&path).unwrap().is_empty());
+        assert_eq!(Catalog::open(&path).unwrap().collection("docs").unwrap().len(), 1);
+
+        let tear = |bytes: u64| {
+            let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+            let len = file.metadata().unwrap().len();
+            file.set_len(len - bytes).unwrap();
+            len - bytes
+        };
+
+        // A torn record written after the checkpoint is truncated as usual
+        let catalog = Catalog::open(&path).unwrap();
+        catalog.upsert("docs", entity(vec![1.0, 0.0])).unwrap();
+        drop(catalog);
+        tear(1);
+        let catalog = Catalog::open(&path).unwrap();
+        assert_eq!(catalog.collection("docs").unwrap().len(), 1);
+        drop(catalog);
+
+        // A crash before the reset leaves records the snapshot already holds
+        std::fs::write(&path, &covered).unwrap();
+        let catalog = Catalog::open(&path).unwrap();
+        assert_eq!(catalog.list_collections().len(), 1);
+        catalog.upsert("docs", entity(vec![1.0, 1.0])).unwrap();
+        assert_eq!(catalog.collection("docs").unwrap().len(), 2);
+        drop(catalog);
+
+        // Losing an acknowledged record is detected before the log is touched
+        std::fs::write(&path, &covered).unwrap();
+        let torn_len = tear(1);
+        for _ in 0..2 {
+            assert!(matches!(
+                Catalog::open(&path),
+                Err(MemorySubstrateError::InvariantViolation { .. })
+            ));
+            assert_eq!(std::fs::metadata(&path).unwrap().len(), torn_len);
+        }
+
+        // So is a reset log whose checkpoint is gone
+        std::fs::write(&path, &reset).unwrap();
+        std::fs::remove_file(Checkpoint::path_for(&path)).unwrap();
+        assert!(matches!(
+            Catalog::open(&path),
+            Err(MemorySubstrateError::InvariantViolation { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_changes_resume_from_the_checkpoint() {
+        use crate::core::error::CdcError;
+        use crate::storage::cdc::Change;
+        use futures::StreamExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("catalog.wal");
+        let cdc = CdcConfig {
+            retained_events: 1,
+            subscriber_buffer: 16,
+        };
+        let (a, b) = (entity(vec![1.0, 0.0]), entity(vec![0.0, 1.0]));
+        {
+            let mut catalog = Catalog::open_with_cdc(&path, &cdc).unwrap();
+            catalog.create_collection("docs", config(2)).unwrap();
+            catalog.upsert("docs", a.clone()).unwrap();
+            catalog.upsert("docs", b.clone()).unwrap();
+            assert_eq!(catalog.checkpoint().unwrap().unwrap().next_sequence, 3);
+        }
+
+        let catalog = Catalog::open_with_cdc(&path, &cdc).unwrap();
+        assert_eq!(catalog.changes().head(), 2);
+        catalog.upsert("docs", entity(vec![1.0, 1.0])).unwrap();
+        catalog.delete("docs", &a.id).unwrap();
+        catalog.delete("docs", &b.id).unwrap();
+        let changes = catalog.changes();
+        assert_eq!((changes.head(), changes.oldest_retained()), (5, Some(5)));
+
+        // Events before the checkpoint went with the log it reset
+        assert!(matches!(
+            changes.subscribe(0),
+            Err(MemorySubstrateError::Cdc {
+                error: CdcError::Expired { requested: 1, oldest: 3 },
+                ..
+            })
+        ));
+
+        // Later ones are decoded from the log against the snapshot, which
+        // knows the entities deleted after it
+        let events: Vec<_> = changes.subscribe(2).unwrap().take(3).collect().await;
+        let events: Vec<_> = events.into_iter().map(|event| event.unwrap()).collect();
+        assert_eq!(events.iter().map(|e| e.sequence).collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert!(matches!(events[1].change, Change::EntityDelete { entity_id } if entity_id == a.id));
+        assert!(matches!(events[2].change, Change::EntityDelete { entity_id } if entity_id == b.id));
+    }
+}
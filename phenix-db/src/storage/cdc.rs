@@ -0,0 +1,757 @@
+//! Change data capture
+//!
+//! Turns write-ahead log records into an ordered stream of fine-grained
+//! change events for external consumers such as analytics pipelines.
+//!
+//! Edges and tiers are part of the entity state in the log, so the decoder
+//! diffs each upsert against the entity's tier and outgoing edges just
+//! before the record applies. The catalog captures every record before
+//! applying it and serves that [`PriorState`] from its collections, so no
+//! second copy of the entities is kept:
+//! - every upsert emits `EntityUpsert` with the full new state, followed by
+//!   `EdgePrune` for edges that disappeared, `EdgeCreate` for new edges,
+//!   `EdgeUpdate` for edges whose weight, probability or metadata changed,
+//!   and `TierMove` if the tier changed
+//! - deletes emit `EntityDelete`; the entity's outgoing edges go with it
+//! - bulk load segments are decoded like one upsert per entity
+//! - dropping a collection emits `EntityDelete` for each of its entities
+//!
+//! Sequence numbers start at 1 and increase by one per event. Decoding is
+//! deterministic, so replaying the same log always yields the same numbers
+//! and a consumer can resume after the last sequence it processed. Recent
+//! events are served from memory; older ones are decoded again from the log,
+//! starting from the last checkpoint's snapshot and sequence number. Events
+//! from before that checkpoint went with the log it reset, and resuming
+//! before it fails with `Expired`.
+
+use crate::core::config::CdcConfig;
+use crate::core::error::{CdcError, Result};
+use crate::core::mvcc::MvccWrite;
+use crate::core::{CollectionId, Edge, Entity, EntityId, MemoryTier};
+use crate::storage::checkpoint::{CatalogSnapshot, Checkpoint};
+use crate::storage::wal::{Lsn, Wal, WalOp, WalRecord};
+use futures::stream::{self, Stream, StreamExt};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// A captured mutation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Change {
+    /// Entity created or replaced
+    EntityUpsert {
+        /// New entity state
+        entity: Box<Entity>,
+    },
+
+    /// Entity removed, with its outgoing edges
+    EntityDelete {
+        /// Removed entity
+        entity_id: EntityId,
+    },
+
+    /// Edge added to its source entity
+    EdgeCreate {
+        /// New edge
+        edge: Edge,
+    },
+
+    /// Weight, probability or metadata of an existing edge changed
+    EdgeUpdate {
+        /// New edge state
+        edge: Edge,
+    },
+
+    /// Edge removed from its source entity
+    EdgePrune {
+        /// Source entity
+        source_id: EntityId,
+        /// Target entity
+        target_id: EntityId,
+        /// Relationship label
+        label: String,
+    },
+
+    /// Entity moved between memory tiers
+    TierMove {
+        /// Moved entity
+        entity_id: EntityId,
+        /// Previous tier
+        from: MemoryTier,
+        /// New tier
+        to: MemoryTier,
+    },
+}
+
+/// A change event with its position in the change sequence
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CdcEvent {
+    /// Position in the change sequence, starting at 1
+    pub sequence: u64,
+
+    /// Log record the change was derived from
+    pub lsn: Lsn,
+
+    /// Collection the change applies to
+    pub collection_id: CollectionId,
+
+    /// Primary name of the collection
+    pub collection: String,
+
+    /// Commit time (Unix epoch milliseconds)
+    pub timestamp: u64,
+
+    /// What changed
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+/// Ordered stream of change events; ends with an error item if the
+/// consumer falls behind
+pub type CdcStream = Pin<Box<dyn Stream<Item = Result<CdcEvent>> + Send>>;
+
+/// Tier and outgoing edges of an entity, the state events are diffed on
+#[derive(Debug, Clone)]
+pub struct EntityShape {
+    /// Memory tier
+    pub tier: MemoryTier,
+
+    /// Outgoing edges
+    pub edges: Vec<Edge>,
+}
+
+impl From<&Entity> for EntityShape {
+    fn from(entity: &Entity) -> Self {
+        Self {
+            tier: entity.tier,
+            edges: entity.edges.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// Catalog state just before a record applies
+pub trait PriorState {
+    /// Primary name of a collection, if it exists
+    fn collection_name(&self, collection: CollectionId) -> Option<String>;
+
+    /// Tier and edges of an entity, if it exists
+    fn entity(&self, collection: CollectionId, id: &EntityId) -> Option<EntityShape>;
+
+    /// Every entity of a collection, in arbitrary order
+    fn entity_ids(&self, collection: CollectionId) -> Vec<EntityId>;
+}
+
+/// Derives change events from log records in LSN order
+#[derive(Debug)]
+pub struct ChangeDecoder {
+    next_sequence: u64,
+}
+
+impl Default for ChangeDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChangeDecoder {
+    /// Create a decoder for a log read from its first record
+    pub fn new() -> Self {
+        Self::starting_at(1)
+    }
+
+    /// Create a decoder whose first event receives `next_sequence`
+    pub fn starting_at(next_sequence: u64) -> Self {
+        Self { next_sequence }
+    }
+
+    /// Sequence number the next event will receive
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+
+    /// Decode one record into zero or more events, diffing against `prior`
+    ///
+    /// Catalog-only records (collection creation, aliases) produce no
+    /// events; records for unknown collections are ignored, as replay
+    /// ignores them.
+    pub fn decode(&mut self, record: &WalRecord, prior: &dyn PriorState) -> Vec<CdcEvent> {
+        let id = record.collection;
+        let Some(name) = prior.collection_name(id) else {
+            return Vec::new();
+        };
+        let mut diff = Diff {
+            prior,
+            collection: id,
+            touched: HashMap::new(),
+            changes: Vec::new(),
+        };
+        match &record.op {
+            WalOp::CreateCollection { .. } | WalOp::SetAlias { .. } | WalOp::DropAlias { .. } => {}
+            WalOp::DropCollection => {
+                let mut ids = prior.entity_ids(id);
+                ids.sort();
+                diff.changes
+                    .extend(ids.into_iter().map(|entity_id| Change::EntityDelete { entity_id }));
+            }
+            WalOp::Upsert { entity } => diff.upsert(entity),
+            WalOp::Delete { entity_id } => diff.delete(*entity_id),
+            WalOp::Transaction { writes, .. } => {
+                for write in writes {
+                    match write {
+                        MvccWrite::Put(entity) => diff.upsert(entity),
+                        MvccWrite::Delete(entity_id) => diff.delete(*entity_id),
+                    }
+                }
+            }
+            WalOp::LoadSegment { entities } => {
+                for entity in entities {
+                    diff.upsert(entity);
+                }
+            }
+        }
+
+        diff.changes
+            .into_iter()
+            .map(|change| {
+                let sequence = self.next_sequence;
+                self.next_sequence += 1;
+                CdcEvent {
+                    sequence,
+                    lsn: record.lsn,
+                    collection_id: id,
+                    collection: name.clone(),
+                    timestamp: record.timestamp,
+                    change,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Changes of one record; entities it writes twice are diffed against
+/// their earlier write
+struct Diff<'a> {
+    prior: &'a dyn PriorState,
+    collection: CollectionId,
+    touched: HashMap<EntityId, Option<EntityShape>>,
+    changes: Vec<Change>,
+}
+
+impl Diff<'_> {
+    fn previous(&self, id: &EntityId) -> Option<EntityShape> {
+        match self.touched.get(id) {
+            Some(shape) => shape.clone(),
+            None => self.prior.entity(self.collection, id),
+        }
+    }
+
+    fn upsert(&mut self, entity: &Entity) {
+        let shape = EntityShape::from(entity);
+        let previous = self.previous(&entity.id).unwrap_or(EntityShape {
+            tier: entity.tier,
+            edges: Vec::new(),
+        });
+        self.touched.insert(entity.id, Some(shape.clone()));
+        self.changes.push(Change::EntityUpsert {
+            entity: Box::new(entity.clone()),
+        });
+
+        let same_key = |a: &Edge, b: &Edge| a.target_id == b.target_id && a.label == b.label;
+        for old in &previous.edges {
+            if !shape.edges.iter().any(|edge| same_key(edge, old)) {
+                self.changes.push(Change::EdgePrune {
+                    source_id: entity.id,
+                    target_id: old.target_id,
+                    label: old.label.clone(),
+                });
+            }
+        }
+        for edge in shape.edges {
+            match previous.edges.iter().find(|old| same_key(old, &edge)) {
+                None => self.changes.push(Change::EdgeCreate { edge }),
+                Some(old)
+                    if old.weight != edge.weight
+                        || old.probability() != edge.probability()
+                        || old.metadata != edge.metadata =>
+                {
+                    self.changes.push(Change::EdgeUpdate { edge })
+                }
+                Some(_) => {}
+            }
+        }
+        if previous.tier != entity.tier {
+            self.changes.push(Change::TierMove {
+                entity_id: entity.id,
+                from: previous.tier,
+                to: entity.tier,
+            });
+        }
+    }
+
+    fn delete(&mut self, entity_id: EntityId) {
+        if self.previous(&entity_id).is_some() {
+            self.touched.insert(entity_id, None);
+            self.changes.push(Change::EntityDelete { entity_id });
+        }
+    }
+}
+
+/// Tiers and edges rebuilt from a checkpoint, for decoding the log again
+/// when a consumer resumes before the retained events
+#[derive(Debug, Default)]
+struct Shadow {
+    collections: HashMap<CollectionId, (String, HashMap<EntityId, EntityShape>)>,
+}
+
+impl Shadow {
+    fn from_snapshot(snapshot: &CatalogSnapshot) -> Self {
+        let collections = snapshot
+            .collections
+            .iter()
+            .map(|collection| {
+                let entities = collection.entities.iter().map(|e| (e.id, EntityShape::from(e))).collect();
+                (collection.id, (collection.name.clone(), entities))
+            })
+            .collect();
+        Self { collections }
+    }
+
+    /// Apply a record the way catalog replay does
+    fn apply(&mut self, record: &WalRecord) {
+        let id = record.collection;
+        if let WalOp::CreateCollection { name, .. } = &record.op {
+            self.collections.insert(id, (name.clone(), HashMap::new()));
+            return;
+        }
+        if let WalOp::DropCollection = &record.op {
+            self.collections.remove(&id);
+            return;
+        }
+        let Some((_, entities)) = self.collections.get_mut(&id) else {
+            return;
+        };
+        let mut put = |entity: &Entity| {
+            entities.insert(entity.id, EntityShape::from(entity));
+        };
+        match &record.op {
+            WalOp::Upsert { entity } => put(entity),
+            WalOp::LoadSegment { entities: loaded } => loaded.iter().for_each(put),
+            WalOp::Delete { entity_id } => {
+                entities.remove(entity_id);
+            }
+            WalOp::Transaction { writes, .. } => {
+                for write in writes {
+                    match write {
+                        MvccWrite::Put(entity) => {
+                            entities.insert(entity.id, EntityShape::from(&**entity));
+                        }
+                        MvccWrite::Delete(entity_id) => {
+                            entities.remove(entity_id);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl PriorState for Shadow {
+    fn collection_name(&self, collection: CollectionId) -> Option<String> {
+        self.collections.get(&collection).map(|(name, _)| name.clone())
+    }
+
+    fn entity(&self, collection: CollectionId, id: &EntityId) -> Option<EntityShape> {
+        self.collections.get(&collection)?.1.get(id).cloned()
+    }
+
+    fn entity_ids(&self, collection: CollectionId) -> Vec<EntityId> {
+        self.collections
+            .get(&collection)
+            .map_or_else(Vec::new, |(_, entities)| entities.keys().copied().collect())
+    }
+}
+
+#[derive(Debug)]
+struct CaptureState {
+    decoder: ChangeDecoder,
+    retained: VecDeque<CdcEvent>,
+    next_lsn: Lsn,
+}
+
+/// Decodes committed log records and fans the events out to consumers
+///
+/// The catalog feeds every record it logs (or replays) through `capture`.
+#[derive(Debug)]
+pub struct ChangeCapture {
+    state: Mutex<CaptureState>,
+    sender: broadcast::Sender<CdcEvent>,
+    retained_events: usize,
+    wal_path: Option<PathBuf>,
+}
+
+impl Default for ChangeCapture {
+    fn default() -> Self {
+        Self::new(&CdcConfig::default(), None)
+    }
+}
+
+impl ChangeCapture {
+    /// Capture changes, re-reading the log at `wal_path` for consumers
+    /// resuming before the retained events
+    pub fn new(config: &CdcConfig, wal_path: Option<PathBuf>) -> Self {
+        let (sender, _) = broadcast::channel(config.subscriber_buffer.max(1));
+        Self {
+            state: Mutex::new(CaptureState {
+                decoder: ChangeDecoder::new(),
+                retained: VecDeque::new(),
+                next_lsn: 1,
+            }),
+            sender,
+            retained_events: config.retained_events,
+            wal_path,
+        }
+    }
+
+    /// LSN following the last captured record
+    pub fn next_lsn(&self) -> Lsn {
+        self.state.lock().next_lsn
+    }
+
+    /// Sequence number of the last event, 0 before the first
+    pub fn head(&self) -> u64 {
+        self.state.lock().decoder.next_sequence() - 1
+    }
+
+    /// Oldest sequence number served from memory, if any is retained
+    pub fn oldest_retained(&self) -> Option<u64> {
+        self.state.lock().retained.front().map(|event| event.sequence)
+    }
+
+    /// Continue the change sequence of a checkpoint
+    ///
+    /// Called before any record is captured, when the catalog was restored
+    /// from a checkpoint instead of replaying its log from the start.
+    pub fn resume_at(&self, checkpoint: &Checkpoint) {
+        let mut state = self.state.lock();
+        state.decoder = ChangeDecoder::starting_at(checkpoint.next_sequence);
+        state.next_lsn = checkpoint.next_lsn;
+    }
+
+    /// Decode a committed record and publish its events
+    ///
+    /// `prior` must show the catalog just before the record applies.
+    pub fn capture(&self, record: &WalRecord, prior: &dyn PriorState) {
+        let mut state = self.state.lock();
+        state.next_lsn = record.lsn + 1;
+        for event in state.decoder.decode(record, prior) {
+            if self.retained_events > 0 {
+                if state.retained.len() == self.retained_events {
+                    state.retained.pop_front();
+                }
+                state.retained.push_back(event.clone());
+            }
+            // Sending only fails when nobody is subscribed.
+            let _ = self.sender.send(event);
+        }
+    }
+
+    /// Stream every event after sequence `after`, then follow new ones
+    ///
+    /// Pass 0 to start from the beginning, or the last sequence processed
+    /// to resume.
+    ///
+    /// # Errors
+    /// * `Cdc(Expired)` if the events are no longer retained and there is
+    ///   no log to decode them from, or they precede the last checkpoint
+    /// * `Io` / `Serialization` if the log cannot be read
+    pub fn subscribe(&self, after: u64) -> Result<CdcStream> {
+        // Subscribing under the lock means nothing falls between the
+        // backlog and the live events
+        let (receiver, retained, head, oldest) = {
+            let state = self.state.lock();
+            let head = state.decoder.next_sequence() - 1;
+            let oldest = state.retained.front().map_or(head + 1, |event| event.sequence);
+            let retained = (after + 1 >= oldest).then(|| {
+                state
+                    .retained
+                    .iter()
+                    .filter(|event| event.sequence > after)
+                    .cloned()
+                    .collect::<Vec<_>>()
+            });
+            (self.sender.subscribe(), retained, head, oldest)
+        };
+
+        let backlog = match (retained, &self.wal_path) {
+            (Some(events), _) => events,
+            (None, Some(path)) => decode_log(path, after, head)?,
+            (None, None) => {
+                return Err(CdcError::Expired {
+                    requested: after + 1,
+                    oldest,
+                }
+                .into())
+            }
+        };
+
+        let floor = after.max(head);
+        let live = stream::unfold(Some(receiver), move |receiver| async move {
+            let mut receiver = receiver?;
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if event.sequence > floor => return Some((Ok(event), Some(receiver))),
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(missed)) => {
+                        return Some((Err(CdcError::Lagged { missed }.into()), None));
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+        Ok(stream::iter(backlog.into_iter().map(Ok)).chain(live).boxed())
+    }
+}
+
+/// Decode the events in `after + 1..=head` from the log at `path`,
+/// starting from the snapshot of its last checkpoint
+fn decode_log(path: &Path, after: u64, head: u64) -> Result<Vec<CdcEvent>> {
+    let checkpoint_path = Checkpoint::path_for(path);
+    let mut checkpoint = Checkpoint::read(&checkpoint_path)?;
+    let scan = Wal::scan(path)?;
+    if checkpoint.as_ref().is_some_and(|c| scan.base_lsn > c.next_lsn) {
+        // A checkpoint reset the log after the first read; it covers the gap
+        checkpoint = Checkpoint::read(&checkpoint_path)?;
+    }
+    let (mut shadow, mut decoder, from_lsn) = match &checkpoint {
+        Some(checkpoint) if scan.base_lsn <= checkpoint.next_lsn => (
+            Shadow::from_snapshot(&checkpoint.state),
+            ChangeDecoder::starting_at(checkpoint.next_sequence),
+            checkpoint.next_lsn,
+        ),
+        None if scan.base_lsn == 1 => (Shadow::default(), ChangeDecoder::new(), 1),
+        _ => {
+            return Err(CdcError::Expired {
+                requested: after + 1,
+                oldest: head + 1,
+            }
+            .into())
+        }
+    };
+    if after + 1 < decoder.next_sequence() {
+        return Err(CdcError::Expired {
+            requested: after + 1,
+            oldest: decoder.next_sequence(),
+        }
+        .into());
+    }
+
+    let mut events = Vec::new();
+    for record in scan.records.iter().filter(|record| record.lsn >= from_lsn) {
+        if decoder.next_sequence() > head {
+            break;
+        }
+        events.extend(
+            decoder
+                .decode(record, &shadow)
+                .into_iter()
+                .filter(|event| event.sequence > after && event.sequence <= head),
+        );
+        shadow.apply(record);
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::collection::CollectionConfig;
+    use crate::core::error::MemorySubstrateError;
+    use crate::core::Vector;
+
+    fn kinds(events: &[CdcEvent]) -> Vec<&'static str> {
+        events
+            .iter()
+            .map(|event| match event.change {
+                Change::EntityUpsert { .. } => "entity_upsert",
+                Change::EntityDelete { .. } => "entity_delete",
+                Change::EdgeCreate { .. } => "edge_create",
+                Change::EdgeUpdate { .. } => "edge_update",
+                Change::EdgePrune { .. } => "edge_prune",
+                Change::TierMove { .. } => "tier_move",
+            })
+            .collect()
+    }
+
+    fn create(wal: &mut Wal, name: &str) -> (CollectionId, WalRecord) {
+        let id = CollectionId::new();
+        let record = wal
+            .append_record(
+                id,
+                WalOp::CreateCollection {
+                    name: name.to_string(),
+                    config: CollectionConfig::new(2),
+                    created_at: 0,
+                },
+            )
+            .unwrap();
+        (id, record)
+    }
+
+    /// Decode a record against the records decoded before it
+    fn decode(decoder: &mut ChangeDecoder, shadow: &mut Shadow, record: &WalRecord) -> Vec<CdcEvent> {
+        let events = decoder.decode(record, shadow);
+        shadow.apply(record);
+        events
+    }
+
+    /// Capture a record the way the catalog does, before applying it
+    fn feed(capture: &ChangeCapture, shadow: &mut Shadow, record: &WalRecord) {
+        capture.capture(record, shadow);
+        shadow.apply(record);
+    }
+
+    fn upsert_op(entity: &Entity) -> WalOp {
+        WalOp::Upsert {
+            entity: Box::new(entity.clone()),
+        }
+    }
+
+    #[test]
+    fn test_decoder_diffs_edges_and_tiers() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut wal = Wal::open(dir.path().join("cdc.wal")).unwrap();
+        let (docs, created) = create(&mut wal, "docs");
+        let (mut decoder, mut shadow) = (ChangeDecoder::new(), Shadow::default());
+        assert!(decode(&mut decoder, &mut shadow, &created).is_empty());
+
+        let (a, b, c) = (EntityId::new(), EntityId::new(), EntityId::new());
+        let mut entity = Entity::new(Some(Vector::new(vec![1.0, 0.0])), None, None);
+        entity.id = a;
+        entity.edges = Some(vec![
+            Edge::new(a, b, "cites".to_string(), 0.5, None),
+            Edge::new(a, c, "cites".to_string(), 0.5, None),
+        ]);
+        let events = decode(&mut decoder, &mut shadow, &wal.append_record(docs, upsert_op(&entity)).unwrap());
+        assert_eq!(kinds(&events), vec!["entity_upsert", "edge_create", "edge_create"]);
+        assert_eq!(events.iter().map(|e| e.sequence).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert!(events.iter().all(|e| e.collection == "docs" && e.lsn == 2));
+
+        // Reweigh the edge to b, drop the one to c, link d, and demote
+        let d = EntityId::new();
+        entity.edges = Some(vec![
+            Edge::new(a, b, "cites".to_string(), 0.9, None),
+            Edge::new(a, d, "mentions".to_string(), 0.5, None),
+        ]);
+        entity.tier = MemoryTier::Warm;
+        let events = decode(&mut decoder, &mut shadow, &wal.append_record(docs, upsert_op(&entity)).unwrap());
+        assert_eq!(
+            kinds(&events),
+            vec!["entity_upsert", "edge_prune", "edge_update", "edge_create", "tier_move"]
+        );
+        match &events[1].change {
+            Change::EdgePrune { source_id, target_id, .. } => assert_eq!((*source_id, *target_id), (a, c)),
+            other => panic!("unexpected {:?}", other),
+        }
+        match &events[4].change {
+            Change::TierMove { from, to, .. } => assert_eq!((*from, *to), (MemoryTier::Hot, MemoryTier::Warm)),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // Unchanged state only re-announces the entity
+        let events = decode(&mut decoder, &mut shadow, &wal.append_record(docs, upsert_op(&entity)).unwrap());
+        assert_eq!(kinds(&events), vec!["entity_upsert"]);
+
+        let transaction = WalOp::Transaction {
+            txn_id: 7,
+            writes: vec![MvccWrite::Delete(a), MvccWrite::Delete(EntityId::new())],
+        };
+        let events = decode(&mut decoder, &mut shadow, &wal.append_record(docs, transaction).unwrap());
+        assert_eq!(kinds(&events), vec!["entity_delete"]);
+        assert_eq!(decoder.next_sequence(), 11);
+
+        // Records of unknown collections are skipped like replay skips them
+        let stray = wal.append_record(CollectionId::new(), upsert_op(&entity)).unwrap();
+        assert!(decode(&mut decoder, &mut shadow, &stray).is_empty());
+
+        decode(&mut decoder, &mut shadow, &wal.append_record(docs, upsert_op(&entity)).unwrap());
+        let events = decode(&mut decoder, &mut shadow, &wal.append_record(docs, WalOp::DropCollection).unwrap());
+        assert_eq!(kinds(&events), vec!["entity_delete"]);
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_memory_and_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cdc.wal");
+        let mut wal = Wal::open(&path).unwrap();
+        let config = CdcConfig {
+            retained_events: 2,
+            subscriber_buffer: 4,
+        };
+        let capture = ChangeCapture::new(&config, Some(path.clone()));
+        let mut shadow = Shadow::default();
+        let (docs, created) = create(&mut wal, "docs");
+        feed(&capture, &mut shadow, &created);
+        for i in 0..4 {
+            let entity = Entity::new(Some(Vector::new(vec![i as f32, 1.0])), None, None);
+            feed(&capture, &mut shadow, &wal.append_record(docs, upsert_op(&entity)).unwrap());
+        }
+        assert_eq!((capture.head(), capture.oldest_retained()), (4, Some(3)));
+
+        let sequences = |events: Vec<Result<CdcEvent>>| -> Vec<u64> {
+            events.into_iter().map(|event| event.unwrap().sequence).collect()
+        };
+
+        // Retained events come from memory, older ones from the log
+        let resumed = capture.subscribe(2).unwrap().take(2).collect::<Vec<_>>().await;
+        assert_eq!(sequences(resumed), vec![3, 4]);
+        let mut stream = capture.subscribe(0).unwrap();
+        let replayed = (&mut stream).take(4).collect::<Vec<_>>().await;
+        assert_eq!(sequences(replayed), vec![1, 2, 3, 4]);
+
+        // Then live events follow without a gap
+        let entity = Entity::new(Some(Vector::new(vec![9.0, 1.0])), None, None);
+        feed(&capture, &mut shadow, &wal.append_record(docs, upsert_op(&entity)).unwrap());
+        assert_eq!(stream.next().await.unwrap().unwrap().sequence, 5);
+
+        // A consumer that stops reading is cut off once its buffer overflows
+        for i in 0..6 {
+            let entity = Entity::new(Some(Vector::new(vec![i as f32, 2.0])), None, None);
+            feed(&capture, &mut shadow, &wal.append_record(docs, upsert_op(&entity)).unwrap());
+        }
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(MemorySubstrateError::Cdc {
+                error: CdcError::Lagged { .. },
+                ..
+            }))
+        ));
+        assert!(stream.next().await.is_none());
+
+        // Without a log, evicted events are gone
+        let memory_only = ChangeCapture::new(&config, None);
+        let mut memory_shadow = Shadow::default();
+        feed(&memory_only, &mut memory_shadow, &created);
+        for i in 0..3 {
+            let entity = Entity::new(Some(Vector::new(vec![i as f32, 3.0])), None, None);
+            feed(&memory_only, &mut memory_shadow, &WalRecord {
+                lsn: memory_only.next_lsn(),
+                collection: docs,
+                timestamp: 0,
+                op: upsert_op(&entity),
+            });
+        }
+        assert!(matches!(
+            memory_only.subscribe(0),
+            Err(MemorySubstrateError::Cdc {
+                error: CdcError::Expired { requested: 1, oldest: 2 },
+                ..
+            })
+        ));
+        assert!(memory_only.subscribe(1).is_ok());
+    }
+}
@@ -0,0 +1,137 @@
+//! Shutdown checkpoints
+//!
+//! A checkpoint records how far the write-ahead log reached when the catalog
+//! was last flushed on a clean shutdown, together with a snapshot of every
+//! collection at that point. It is written next to the log, atomically by
+//! renaming a synced temporary file, and the log is then reset: recovery
+//! restores the snapshot and replays only the records logged after it.
+//!
+//! On open the log must continue the checkpoint without a gap. It may not
+//! start after the checkpointed position, and it may not end before it: a
+//! shorter log means acknowledged records were lost, which torn-tail
+//! recovery alone can never explain.
+
+use crate::core::collection::{CollectionConfig, CollectionStats};
+use crate::core::error::{ErrorContext, MemorySubstrateError, Result};
+use crate::core::transaction::TransactionId;
+use crate::core::{CollectionId, Entity};
+use crate::storage::wal::{Lsn, WalScan};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Durable state of a catalog at a clean shutdown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// LSN the next log record would have received
+    pub next_lsn: Lsn,
+
+    /// Sequence number the next change event would have received
+    pub next_sequence: u64,
+
+    /// Identifier the next transaction would have received
+    pub next_txn_id: TransactionId,
+
+    /// When the checkpoint was taken (Unix epoch milliseconds)
+    pub created_at: u64,
+
+    /// Per-collection summary, by name
+    pub collections: Vec<CollectionStats>,
+
+    /// Catalog contents covering every record before `next_lsn`
+    pub state: CatalogSnapshot,
+}
+
+/// Collections and aliases of a catalog
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CatalogSnapshot {
+    /// Every collection, by name
+    pub collections: Vec<CollectionSnapshot>,
+
+    /// Alias to collection
+    pub aliases: BTreeMap<String, CollectionId>,
+}
+
+/// Configuration and latest entities of one collection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionSnapshot {
+    /// Stable identifier
+    pub id: CollectionId,
+
+    /// Primary name
+    pub name: String,
+
+    /// Creation configuration
+    pub config: CollectionConfig,
+
+    /// Creation timestamp (Unix epoch milliseconds)
+    pub created_at: u64,
+
+    /// Latest state of every entity
+    pub entities: Vec<Entity>,
+}
+
+impl Checkpoint {
+    /// Path of the checkpoint belonging to the log at `wal_path`
+    pub fn path_for(wal_path: &Path) -> PathBuf {
+        wal_path.with_extension("checkpoint")
+    }
+
+    /// Read the checkpoint at `path`, if one has been written
+    pub fn read(path: &Path) -> Result<Option<Self>> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| MemorySubstrateError::Serialization(format!("invalid checkpoint {}: {}", path.display(), e)))
+    }
+
+    /// Replace the checkpoint at `path`
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let bytes = serde_json::to_vec(self).map_err(|e| MemorySubstrateError::Serialization(e.to_string()))?;
+        let staged = path.with_extension("checkpoint.tmp");
+        let mut file = File::create(&staged)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        std::fs::rename(&staged, path)?;
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            // Make the rename itself durable
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Check that the recovered log `scan` continues this checkpoint
+    /// without a gap
+    ///
+    /// # Errors
+    /// * `InvariantViolation` if the log starts after the checkpoint or
+    ///   ends before it
+    pub fn verify(&self, scan: &WalScan) -> Result<()> {
+        let next_lsn = scan.next_lsn();
+        let gap = if scan.base_lsn > self.next_lsn {
+            format!("starts at LSN {}", scan.base_lsn)
+        } else if next_lsn < self.next_lsn {
+            format!("ends at LSN {}", next_lsn)
+        } else {
+            return Ok(());
+        };
+        Err(MemorySubstrateError::InvariantViolation {
+            message: format!(
+                "write-ahead log {} but the checkpoint of {} reached LSN {}",
+                gap, self.created_at, self.next_lsn
+            ),
+            context: Box::new(
+                ErrorContext::new("catalog", "recover")
+                    .with_detail("recovered_base_lsn", scan.base_lsn.to_string())
+                    .with_detail("recovered_next_lsn", next_lsn.to_string())
+                    .with_detail("checkpoint_next_lsn", self.next_lsn.to_string()),
+            ),
+        })
+    }
+}
@@ -0,0 +1,785 @@
+//! Collection storage
+//!
+//! A `Collection` owns the entities of one dataset together with the
+//! filtered vector index built from its configuration. Mutations here are
+//! not logged; the `Catalog` writes them to the WAL before applying them.
+//! Access statistics are kept in an `AccessTracker` beside the entities, so
+//! recording a read only needs `&self`.
+//!
+//! Entities live in an `MvccEngine`: every mutation commits a new version,
+//! and reads go through a `CollectionView` pinned at one snapshot. Writers
+//! only need `&self`, so views stay valid while they commit. Commits to one
+//! collection are serialized by its own commit lock, which is the only lock
+//! held while the catalog logs them: readers of the collection, and writers
+//! of other collections, never wait for a log write. The index keeps
+//! superseded vectors, stamped with the commit that replaced them, until no
+//! live snapshot can see them, so a view searches the index as of its own
+//! snapshot. Hits and edges are resolved against the snapshot too; a search
+//! or traversal never mixes versions.
+
+use crate::concurrency::access_tracker::AccessTracker;
+use crate::concurrency::lock_manager::{LockManager, LockManagerConfig};
+use crate::concurrency::mvcc_engine::{MvccEngine, Snapshot};
+use crate::concurrency::transaction_coordinator::{Transaction, TransactionCoordinator};
+use crate::core::collection::{CollectionConfig, CollectionInfo, CollectionStats, ConcurrencyMode, TierSizes};
+use crate::core::error::{CollectionError, ConcurrencyError, MemorySubstrateError, Result};
+use crate::core::metadata::MetadataIndex;
+use crate::core::mvcc::{MvccWrite, Timestamp};
+use crate::core::query::{GraphQuery, TraversalStep, VectorQuery};
+use crate::core::{CollectionId, Entity, EntityId, MemoryTier};
+use crate::index::filtered::{FilteredIndex, FilteredSearchResult};
+use crate::index::probabilistic_graph::GraphIndexConfig;
+use crate::index::simd::SearchHit;
+use crate::mathematical::entropy::{normalize_entropy, shannon_entropy};
+use parking_lot::{Mutex, MutexGuard, RwLock, RwLockReadGuard};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Latest state of the entities a commit writes, read just before it
+/// applies; entities that do not exist yet are absent
+pub type PriorEntities = HashMap<EntityId, Arc<Entity>>;
+
+/// Entities and indexes of a single collection
+#[derive(Debug)]
+pub struct Collection {
+    id: CollectionId,
+    name: String,
+    config: CollectionConfig,
+    created_at: u64,
+    /// Latest state only
+    index: RwLock<FilteredIndex>,
+    /// Serializes commits, from validation until they apply
+    commits: Mutex<()>,
+    /// Serializes callers that read an entity and write it back
+    updates: Mutex<()>,
+    engine: Arc<MvccEngine>,
+    transactions: TransactionCoordinator,
+    access: AccessTracker,
+}
+
+impl Collection {
+    /// Create an empty collection
+    ///
+    /// The configuration is expected to have been validated by the caller.
+    pub fn new(id: CollectionId, name: String, config: CollectionConfig, created_at: u64) -> Self {
+        let metadata = match &config.schema {
+            Some(schema) => MetadataIndex::from_schema(schema.clone()),
+            None => MetadataIndex::new(),
+        };
+        let index = FilteredIndex::new(
+            config.dimension,
+            config.metric,
+            GraphIndexConfig::from(&config.index),
+            metadata,
+        );
+
+        let engine = Arc::new(MvccEngine::new());
+        let mut transactions = TransactionCoordinator::new(Arc::clone(&engine));
+        if let ConcurrencyMode::Pessimistic { lock_timeout_ms } = config.concurrency {
+            let locks = LockManager::with_config(LockManagerConfig {
+                timeout: Duration::from_millis(lock_timeout_ms),
+            });
+            transactions = transactions.with_lock_manager(Arc::new(locks));
+        }
+        Self {
+            id,
+            name,
+            config,
+            created_at,
+            index: RwLock::new(index),
+            commits: Mutex::new(()),
+            updates: Mutex::new(()),
+            transactions,
+            engine,
+            access: AccessTracker::new(),
+        }
+    }
+
+    /// Stable identifier
+    pub fn id(&self) -> CollectionId {
+        self.id
+    }
+
+    /// Primary name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Creation configuration
+    pub fn config(&self) -> &CollectionConfig {
+        &self.config
+    }
+
+    /// Creation timestamp (Unix epoch milliseconds)
+    pub fn created_at(&self) -> u64 {
+        self.created_at
+    }
+
+    /// Vector and metadata index, as of the latest commit
+    pub fn index(&self) -> RwLockReadGuard<'_, FilteredIndex> {
+        self.index.read()
+    }
+
+    /// Multi-version store holding the entities
+    pub fn engine(&self) -> &Arc<MvccEngine> {
+        &self.engine
+    }
+
+    /// Coordinator that begins transactions on this collection
+    ///
+    /// Commit them with `Catalog::commit_transaction`, or run them with
+    /// `Catalog::run_transaction`, so they are logged.
+    pub fn transactions(&self) -> &TransactionCoordinator {
+        &self.transactions
+    }
+
+    /// Access statistics of the stored entities
+    pub fn access(&self) -> &AccessTracker {
+        &self.access
+    }
+
+    /// Hold off other read-modify-write updates of this collection
+    ///
+    /// Callers that derive a write from the latest state (stamping versions,
+    /// checking existence) hold this across the read and the commit. Plain
+    /// commits and reads do not take it.
+    pub fn lock_updates(&self) -> MutexGuard<'_, ()> {
+        self.updates.lock()
+    }
+
+    /// Pin a read view at the latest commit
+    pub fn view(&self) -> CollectionView<'_> {
+        CollectionView {
+            collection: self,
+            snapshot: self.engine.snapshot(),
+        }
+    }
+
+    /// Number of stored entities
+    pub fn len(&self) -> usize {
+        self.engine.len()
+    }
+
+    /// Whether the collection is empty
+    pub fn is_empty(&self) -> bool {
+        self.engine.is_empty()
+    }
+
+    /// Whether an entity is stored
+    pub fn contains(&self, id: &EntityId) -> bool {
+        self.engine.latest_version(id) != 0
+    }
+
+    /// Look up the latest committed state of an entity
+    pub fn get(&self, id: &EntityId) -> Option<Arc<Entity>> {
+        self.engine.latest(id)
+    }
+
+    /// Every stored entity as of the latest commit, in arbitrary order
+    pub fn entities(&self) -> Vec<Arc<Entity>> {
+        self.view().entities()
+    }
+
+    /// Check that an entity can be stored without modifying the collection
+    ///
+    /// # Errors
+    /// * `Collection(InvalidEntity)` if the vector is missing or has the
+    ///   wrong dimension
+    /// * `Metadata(SchemaViolation)` if the metadata violates the schema
+    pub fn validate_entity(&self, entity: &Entity) -> Result<()> {
+        let invalid = |reason: String| {
+            MemorySubstrateError::from(CollectionError::InvalidEntity {
+                entity_id: entity.id.to_string(),
+                reason,
+            })
+        };
+
+        let vector = entity
+            .vector
+            .as_ref()
+            .ok_or_else(|| invalid("collection entities require a vector".to_string()))?;
+        if vector.values.len() != self.config.dimension {
+            return Err(invalid(format!(
+                "vector has {} dimensions, collection expects {}",
+                vector.values.len(),
+                self.config.dimension
+            )));
+        }
+        self.index.read().metadata().validate(entity.metadata.as_ref())?;
+        Ok(())
+    }
+
+    /// Insert or replace an entity, returning its commit timestamp
+    pub fn upsert(&self, entity: Entity) -> Result<Timestamp> {
+        self.commit(vec![MvccWrite::Put(Box::new(entity))])
+    }
+
+    /// Insert or replace a segment of entities under one commit timestamp
+    ///
+    /// Every entity is validated first, so the segment is applied entirely
+    /// or not at all.
+    pub fn load_segment(&self, entities: Vec<Entity>) -> Result<Timestamp> {
+        self.commit(entities.into_iter().map(|entity| MvccWrite::Put(Box::new(entity))).collect())
+    }
+
+    /// Delete an entity, returning its last state if it was present
+    pub fn delete(&self, id: &EntityId) -> Option<Arc<Entity>> {
+        let entity = self.engine.latest(id)?;
+        self.commit(vec![MvccWrite::Delete(*id)])
+            .expect("deletes need no validation");
+        Some(entity)
+    }
+
+    /// Apply a batch of writes under one commit timestamp
+    ///
+    /// Every written entity is validated first, so the batch is applied
+    /// entirely or not at all. Later writes to the same entity win.
+    ///
+    /// # Errors
+    /// * `Collection(InvalidEntity)` / `Metadata` if an entity does not fit
+    pub fn commit(&self, writes: Vec<MvccWrite>) -> Result<Timestamp> {
+        self.commit_validated(&[], writes, |_, _| Ok(()))
+    }
+
+    /// Apply a batch of writes like `commit`, calling `durable` first
+    ///
+    /// `durable` receives the validated writes and the state they replace;
+    /// an error from it aborts the commit with nothing applied.
+    pub(crate) fn commit_logged<F>(&self, writes: Vec<MvccWrite>, durable: F) -> Result<Timestamp>
+    where
+        F: FnOnce(&[MvccWrite], &PriorEntities) -> Result<()>,
+    {
+        self.commit_validated(&[], writes, durable)
+    }
+
+    /// Commit a transaction begun from `transactions`
+    ///
+    /// `durable` is called with the validated write set, and the state it
+    /// replaces, before anything is applied; an error from it aborts the
+    /// transaction.
+    ///
+    /// # Errors
+    /// * `TransactionConflict` if the transaction conflicts or is not active
+    /// * `Collection(InvalidEntity)` / `Metadata` if a written entity does
+    ///   not fit; the transaction is aborted
+    pub(crate) fn commit_transaction<F>(&self, txn: &mut Transaction, durable: F) -> Result<Timestamp>
+    where
+        F: FnOnce(&[MvccWrite], &PriorEntities) -> Result<()>,
+    {
+        self.transactions
+            .commit_with(txn, |expected, writes| self.commit_validated(expected, writes, durable))
+    }
+
+    /// Validate entities and versions, call `durable`, then apply `writes`
+    fn commit_validated<F>(
+        &self,
+        expected: &[(EntityId, Timestamp)],
+        writes: Vec<MvccWrite>,
+        durable: F,
+    ) -> Result<Timestamp>
+    where
+        F: FnOnce(&[MvccWrite], &PriorEntities) -> Result<()>,
+    {
+        for write in &writes {
+            if let MvccWrite::Put(entity) = write {
+                self.validate_entity(entity)?;
+            }
+        }
+        let ids: Vec<EntityId> = writes.iter().map(MvccWrite::id).collect();
+        let ts = {
+            // Nothing else commits until this one applies, so the versions
+            // checked and the prior state stay current while `durable` runs
+            // without blocking readers
+            let _commit = self.commits.lock();
+            for (id, expected) in expected {
+                let actual = self.engine.latest_version(id);
+                if actual != *expected {
+                    return Err(ConcurrencyError::VersionConflict {
+                        expected: *expected,
+                        actual,
+                    }
+                    .into());
+                }
+            }
+            let prior: PriorEntities = ids
+                .iter()
+                .filter_map(|id| Some((*id, self.engine.latest(id)?)))
+                .collect();
+            durable(&writes, &prior)?;
+
+            let mut index = self.index.write();
+            let ts = self.engine.commit_validated(expected, writes, |writes| {
+                // Commits are serialized, so this is the timestamp the batch gets
+                let ts = self.engine.last_commit() + 1;
+                for write in writes {
+                    match write {
+                        MvccWrite::Put(entity) => match &entity.vector {
+                            Some(vector) => index.insert_at(entity.id, vector, entity.metadata.clone(), ts)?,
+                            None => {
+                                index.remove_at(&entity.id, ts);
+                            }
+                        },
+                        MvccWrite::Delete(id) => {
+                            index.remove_at(id, ts);
+                            self.access.remove(id);
+                        }
+                    }
+                }
+                Ok(())
+            })?;
+            index.release(&self.engine.live_snapshots());
+            ts
+        };
+        self.engine.prune(&ids);
+        Ok(ts)
+    }
+
+    /// Reclaim entity versions and superseded vectors that no live snapshot
+    /// can observe
+    ///
+    /// # Returns
+    /// * Number of entity versions removed
+    pub fn gc(&self) -> usize {
+        let mut index = self.index.write();
+        let removed = self.engine.gc();
+        index.release(&self.engine.live_snapshots());
+        removed
+    }
+
+    /// Run a k-NN query over the latest commit
+    pub fn search(&self, query: &VectorQuery) -> Result<FilteredSearchResult> {
+        self.view().search(query).map(|(result, _)| result)
+    }
+
+    /// Walk edges breadth-first over the latest commit
+    ///
+    /// See [`CollectionView::traverse`].
+    pub fn traverse(&self, query: &GraphQuery) -> Vec<TraversalStep> {
+        self.view().traverse(query).0
+    }
+
+    /// Describe the collection
+    pub fn info(&self, aliases: Vec<String>) -> CollectionInfo {
+        CollectionInfo {
+            id: self.id,
+            name: self.name.clone(),
+            aliases,
+            config: self.config.clone(),
+            created_at: self.created_at,
+            entity_count: self.len(),
+        }
+    }
+
+    /// Count entities per tier and edges, and measure how evenly reads are
+    /// spread over the entities
+    pub fn stats(&self) -> CollectionStats {
+        let entities = self.entities();
+        let mut tiers = TierSizes::default();
+        let mut labels = HashSet::new();
+        let mut edge_count = 0;
+        let mut accesses = Vec::with_capacity(entities.len());
+        for entity in &entities {
+            match entity.tier {
+                MemoryTier::Hot => tiers.hot += 1,
+                MemoryTier::Warm => tiers.warm += 1,
+                MemoryTier::Cold => tiers.cold += 1,
+            }
+            for edge in entity.edges.iter().flatten() {
+                edge_count += 1;
+                labels.insert(edge.label.as_str());
+            }
+            let total = self.access.stats(&entity.id).map_or(0, |stats| stats.total_accesses);
+            accesses.push(total);
+        }
+
+        let total: u64 = accesses.iter().sum();
+        let access_entropy = if total == 0 {
+            0.0
+        } else {
+            let probabilities: Vec<f32> = accesses.iter().map(|&count| (count as f64 / total as f64) as f32).collect();
+            normalize_entropy(shannon_entropy(&probabilities), accesses.len()).clamp(0.0, 1.0)
+        };
+
+        CollectionStats {
+            name: self.name.clone(),
+            entity_count: entities.len(),
+            tiers,
+            edge_count,
+            edge_labels: labels.len(),
+            access_entropy,
+        }
+    }
+}
+
+/// Read view of a collection pinned at one MVCC snapshot
+///
+/// Every read through a view sees the entities exactly as committed at its
+/// snapshot. Holding a view keeps those versions from being reclaimed.
+#[derive(Debug)]
+pub struct CollectionView<'a> {
+    collection: &'a Collection,
+    snapshot: Snapshot,
+}
+
+impl<'a> CollectionView<'a> {
+    /// Collection the view reads from
+    pub fn collection(&self) -> &'a Collection {
+        self.collection
+    }
+
+    /// Snapshot the view is pinned at
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    /// Number of entities visible to the view
+    pub fn len(&self) -> usize {
+        let engine = &self.collection.engine;
+        if engine.last_commit() == self.snapshot.timestamp() {
+            return engine.len();
+        }
+        engine.scan(&self.snapshot).len()
+    }
+
+    /// Whether no entity is visible to the view
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether an entity is visible to the view
+    pub fn contains(&self, id: &EntityId) -> bool {
+        self.collection.engine.is_visible(&self.snapshot, id)
+    }
+
+    /// Look up an entity as of the view's snapshot
+    pub fn get(&self, id: &EntityId) -> Option<Arc<Entity>> {
+        self.collection.engine.get(&self.snapshot, id)
+    }
+
+    /// Every entity visible to the view, in arbitrary order
+    pub fn entities(&self) -> Vec<Arc<Entity>> {
+        self.collection.engine.scan(&self.snapshot)
+    }
+
+    /// Run a k-NN query, returning the hits with the entity versions they
+    /// were scored against
+    ///
+    /// A view still at the latest commit searches the index directly. An
+    /// older view searches it as of its snapshot, over the vectors that were
+    /// latest then, with the filter evaluated on the metadata visible to the
+    /// snapshot. Either way hits are resolved against the snapshot, so
+    /// distances and entities come from the visible versions.
+    pub fn search(&self, query: &VectorQuery) -> Result<(FilteredSearchResult, Vec<Arc<Entity>>)> {
+        let mut result = {
+            let index = self.collection.index.read();
+            let engine = &self.collection.engine;
+            if engine.last_commit() == self.snapshot.timestamp() {
+                index.search(query)?
+            } else {
+                index.search_as_of(query, self.snapshot.timestamp(), |id| {
+                    query.filter.as_ref().is_none_or(|filter| {
+                        engine
+                            .get(&self.snapshot, id)
+                            .is_some_and(|entity| filter.matches(entity.metadata.as_ref()))
+                    })
+                })?
+            }
+        };
+        let resolved = self.collection.engine.resolve_hits(
+            &self.snapshot,
+            &query.vector,
+            self.collection.config.metric,
+            &result.hits,
+        );
+        let (hits, entities): (Vec<SearchHit>, Vec<Arc<Entity>>) = resolved
+            .into_iter()
+            .filter(|(_, entity)| {
+                query
+                    .filter
+                    .as_ref()
+                    .is_none_or(|filter| filter.matches(entity.metadata.as_ref()))
+            })
+            .unzip();
+        result.hits = hits;
+        Ok((result, entities))
+    }
+
+    /// Walk edges breadth-first from the query's start entities, returning
+    /// the steps with the entity version reached at each
+    ///
+    /// Each entity is reported once, at the depth it is first reached.
+    /// Start entities that are not visible and edges leading outside the
+    /// collection are skipped.
+    pub fn traverse(&self, query: &GraphQuery) -> (Vec<TraversalStep>, Vec<Arc<Entity>>) {
+        let mut steps = Vec::new();
+        let mut entities = Vec::new();
+        let mut seen = HashSet::new();
+        let mut frontier = VecDeque::new();
+        for id in &query.start {
+            if seen.insert(*id) {
+                if let Some(entity) = self.get(id) {
+                    let step = TraversalStep {
+                        id: *id,
+                        depth: 0,
+                        parent: None,
+                        label: None,
+                        path_probability: 1.0,
+                    };
+                    frontier.push_back((step, entity));
+                }
+            }
+        }
+
+        while let Some((step, entity)) = frontier.pop_front() {
+            if steps.len() >= query.limit {
+                break;
+            }
+            if step.depth < query.max_depth {
+                for edge in entity.edges.iter().flatten() {
+                    let probability = edge.probability();
+                    let followed = (query.labels.is_empty() || query.labels.contains(&edge.label))
+                        && probability >= query.min_probability
+                        && !seen.contains(&edge.target_id);
+                    if !followed {
+                        continue;
+                    }
+                    if let Some(target) = self.get(&edge.target_id) {
+                        seen.insert(edge.target_id);
+                        let next = TraversalStep {
+                            id: edge.target_id,
+                            depth: step.depth + 1,
+                            parent: Some(step.id),
+                            label: Some(edge.label.clone()),
+                            path_probability: step.path_probability * probability,
+                        };
+                        frontier.push_back((next, target));
+                    }
+                }
+            }
+            steps.push(step);
+            entities.push(entity);
+        }
+        (steps, entities)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::collection::IndexConfig;
+    use crate::core::metadata::{FieldSchema, FieldType, MetadataFilter, MetadataSchema};
+    use crate::core::{Edge, Vector};
+    use serde_json::json;
+
+    fn small_index() -> IndexConfig {
+        IndexConfig {
+            m: 8,
+            ef_construction: 32,
+            ef_search: 32,
+        }
+    }
+
+    fn entity(values: Vec<f32>, metadata: serde_json::Value) -> Entity {
+        Entity::new(Some(Vector::new(values)), Some(metadata), None)
+    }
+
+    #[test]
+    fn test_upsert_get_delete() {
+        let config = CollectionConfig::new(2).with_index(small_index());
+        let collection = Collection::new(CollectionId::new(), "docs".to_string(), config, 0);
+
+        let mut e = entity(vec![1.0, 0.0], json!({"lang": "en"}));
+        let id = e.id;
+        collection.upsert(e.clone()).unwrap();
+        assert_eq!(collection.len(), 1);
+
+        e.metadata = Some(json!({"lang": "de"}));
+        collection.upsert(e).unwrap();
+        assert_eq!(collection.len(), 1);
+        assert_eq!(collection.get(&id).unwrap().metadata, Some(json!({"lang": "de"})));
+
+        let query = VectorQuery::new(Vector::new(vec![1.0, 0.0]), 1)
+            .with_filter(MetadataFilter::eq("lang".parse().unwrap(), json!("en")));
+        assert!(collection.search(&query).unwrap().hits.is_empty());
+
+        assert!(collection.delete(&id).is_some());
+        assert!(collection.delete(&id).is_none());
+        assert!(collection.is_empty());
+        assert_eq!(collection.index().len(), 0);
+    }
+
+    #[test]
+    fn test_reads_proceed_while_a_commit_is_logged() {
+        let config = CollectionConfig::new(2).with_index(small_index());
+        let collection = Collection::new(CollectionId::new(), "docs".to_string(), config, 0);
+        let a = entity(vec![1.0, 0.0], json!({"lang": "en"}));
+        collection.upsert(a.clone()).unwrap();
+
+        let mut moved = a.clone();
+        moved.vector = Some(Vector::new(vec![0.0, 1.0]));
+        let query = VectorQuery::new(Vector::new(vec![1.0, 0.0]), 1);
+        collection
+            .commit_logged(vec![MvccWrite::Put(Box::new(moved))], |_, prior| {
+                // Standing in for a slow fsync: the collection is still
+                // readable at its previous state
+                assert!(prior.contains_key(&a.id));
+                assert_eq!(collection.get(&a.id).unwrap().vector, a.vector);
+                assert!(collection.search(&query)?.hits[0].distance.abs() < 1e-6);
+                Ok(())
+            })
+            .unwrap();
+        assert!(collection.search(&query).unwrap().hits[0].distance > 0.5);
+    }
+
+    #[test]
+    fn test_views_read_one_snapshot() {
+        let config = CollectionConfig::new(2).with_index(small_index());
+        let collection = Collection::new(CollectionId::new(), "docs".to_string(), config, 0);
+        let mut a = entity(vec![1.0, 0.0], json!({"lang": "en"}));
+        let b = entity(vec![0.0, 1.0], json!({"lang": "en"}));
+        a.edges = Some(vec![Edge::new(a.id, b.id, "cites".to_string(), 1.0, None)]);
+        let first = collection.upsert(a.clone()).unwrap();
+        collection.upsert(b.clone()).unwrap();
+
+        // Pin a view, then move `a` away from the query, re-tag it, drop its
+        // edge and delete `b`
+        let pinned = collection.view();
+        let mut moved = a.clone();
+        moved.vector = Some(Vector::new(vec![-1.0, 0.0]));
+        moved.metadata = Some(json!({"lang": "de"}));
+        moved.edges = None;
+        let second = collection.upsert(moved).unwrap();
+        collection.delete(&b.id);
+        assert!(second > first);
+
+        let query = VectorQuery::new(Vector::new(vec![1.0, 0.0]), 2);
+        assert_eq!(pinned.len(), 2);
+        assert_eq!(pinned.get(&a.id).unwrap().commit_ts, first);
+        // The index answers as of the pinned snapshot: `a` is found by its
+        // old vector and `b` is still there
+        let (result, entities) = pinned.search(&query).unwrap();
+        assert_eq!(result.hits.iter().map(|hit| hit.id).collect::<Vec<_>>(), vec![a.id, b.id]);
+        assert!(result.hits[0].distance.abs() < 1e-6);
+        assert_eq!(entities[0].commit_ts, first);
+        // The filter sees the pinned metadata, not the re-tagged one
+        let english = query
+            .clone()
+            .with_filter(MetadataFilter::eq("lang".parse().unwrap(), json!("en")));
+        let (result, _) = pinned.search(&english).unwrap();
+        assert_eq!(result.hits.iter().map(|hit| hit.id).collect::<Vec<_>>(), vec![a.id, b.id]);
+        assert!(collection.search(&english).unwrap().hits.is_empty());
+        assert_eq!(collection.search(&query).unwrap().hits.len(), 1);
+        let (steps, entities) = pinned.traverse(&GraphQuery::new(vec![a.id], 1));
+        assert_eq!(steps.iter().map(|step| step.id).collect::<Vec<_>>(), vec![a.id, b.id]);
+        assert_eq!(entities[1].id, b.id);
+
+        // The latest state has moved on
+        assert_eq!(collection.len(), 1);
+        assert!(!collection.contains(&b.id));
+        assert_eq!(collection.get(&a.id).unwrap().commit_ts, second);
+        assert_eq!(collection.traverse(&GraphQuery::new(vec![a.id], 1)).len(), 1);
+
+        // Versions only the view could see are reclaimed once it is gone
+        assert_eq!(collection.engine().stats().versions, 4);
+        drop(pinned);
+        assert_eq!(collection.gc(), 3);
+        assert_eq!(collection.engine().stats().versions, 1);
+        assert_eq!(collection.view().search(&query).unwrap().0.hits.len(), 1);
+    }
+
+    #[test]
+    fn test_rejects_invalid_entities() {
+        let schema = MetadataSchema::new().field(
+            FieldSchema::new("year".parse().unwrap(), FieldType::Int).required(),
+        );
+        let config = CollectionConfig::new(3)
+            .with_index(small_index())
+            .with_schema(schema);
+        let collection = Collection::new(CollectionId::new(), "papers".to_string(), config, 0);
+
+        let no_vector = Entity::new(None, Some(json!({"year": 2020})), None);
+        assert!(matches!(
+            collection.upsert(no_vector),
+            Err(MemorySubstrateError::Collection {
+                error: CollectionError::InvalidEntity { .. },
+                ..
+            })
+        ));
+
+        let wrong_dim = entity(vec![1.0, 2.0], json!({"year": 2020}));
+        assert!(collection.upsert(wrong_dim).is_err());
+
+        let missing_field = entity(vec![1.0, 2.0, 3.0], json!({"title": "x"}));
+        assert!(matches!(
+            collection.upsert(missing_field),
+            Err(MemorySubstrateError::Metadata { .. })
+        ));
+
+        assert!(collection.is_empty());
+        collection.upsert(entity(vec![1.0, 2.0, 3.0], json!({"year": 2020}))).unwrap();
+        assert_eq!(collection.len(), 1);
+    }
+
+    #[test]
+    fn test_traverse_follows_labels_and_probability() {
+        let config = CollectionConfig::new(2).with_index(small_index());
+        let collection = Collection::new(CollectionId::new(), "graph".to_string(), config, 0);
+
+        // a -cites(0.9)-> b -cites(0.8)-> c, a -mentions(0.2)-> d, c -> a
+        let mut nodes: Vec<Entity> = (0..4).map(|i| entity(vec![i as f32, 0.0], json!({}))).collect();
+        let ids: Vec<EntityId> = nodes.iter().map(|e| e.id).collect();
+        let edge = |from: usize, to: usize, label: &str, weight: f32| Edge::new(ids[from], ids[to], label.to_string(), weight, None);
+        nodes[0].edges = Some(vec![edge(0, 1, "cites", 0.9), edge(0, 3, "mentions", 0.2)]);
+        nodes[1].edges = Some(vec![edge(1, 2, "cites", 0.8), edge(1, 0, "cites", 0.5)]);
+        nodes[2].edges = Some(vec![edge(2, 0, "cites", 1.0), edge(2, 0, "cites", 1.0)]);
+        for node in nodes {
+            collection.upsert(node).unwrap();
+        }
+
+        let steps = collection.traverse(&GraphQuery::new(vec![ids[0]], 2));
+        let reached: Vec<(EntityId, usize)> = steps.iter().map(|s| (s.id, s.depth)).collect();
+        assert_eq!(reached, vec![(ids[0], 0), (ids[1], 1), (ids[3], 1), (ids[2], 2)]);
+        assert_eq!(steps[3].parent, Some(ids[1]));
+        assert!((steps[3].path_probability - 0.72).abs() < 1e-6);
+
+        let query = GraphQuery::new(vec![ids[0]], 5)
+            .with_labels(vec!["cites".to_string()])
+            .with_min_probability(0.5);
+        let reached: Vec<EntityId> = collection.traverse(&query).iter().map(|s| s.id).collect();
+        assert_eq!(reached, vec![ids[0], ids[1], ids[2]]);
+
+        assert_eq!(collection.traverse(&query.with_limit(2)).len(), 2);
+        assert!(collection.traverse(&GraphQuery::new(vec![EntityId::new()], 3)).is_empty());
+    }
+
+    #[test]
+    fn test_stats_count_tiers_edges_and_access_entropy() {
+        let config = CollectionConfig::new(2).with_index(small_index());
+        let collection = Collection::new(CollectionId::new(), "docs".to_string(), config, 0);
+        let mut nodes: Vec<Entity> = (0..3).map(|i| entity(vec![i as f32, 1.0], json!({}))).collect();
+        let ids: Vec<EntityId> = nodes.iter().map(|e| e.id).collect();
+        nodes[0].edges = Some(vec![
+            Edge::new(ids[0], ids[1], "cites".to_string(), 0.5, None),
+            Edge::new(ids[0], ids[2], "mentions".to_string(), 0.5, None),
+        ]);
+        nodes[1].edges = Some(vec![Edge::new(ids[1], ids[2], "cites".to_string(), 0.5, None)]);
+        nodes[2].tier = MemoryTier::Cold;
+        for node in nodes {
+            collection.upsert(node).unwrap();
+        }
+
+        let stats = collection.stats();
+        assert_eq!(stats.entity_count, 3);
+        assert_eq!(stats.tiers, TierSizes { hot: 2, warm: 0, cold: 1 });
+        assert_eq!((stats.edge_count, stats.edge_labels), (3, 2));
+        assert_eq!(stats.access_entropy, 0.0);
+
+        // Reads split evenly over two of three entities: 1 bit of log2(3)
+        for id in [ids[0], ids[0], ids[1], ids[1]] {
+            collection.access().record_access(id);
+        }
+        let entropy = collection.stats().access_entropy;
+        assert!((entropy - 1.0 / 3f64.log2()).abs() < 1e-6, "{}", entropy);
+    }
+}
@@ -1,6 +1,27 @@
 // Storage layer
 //
 // This module will be fully implemented in Phase 19.
-// Placeholder for now to allow compilation.
+// Implemented so far:
+// - WAL: Checksummed, collection-scoped write-ahead log with torn-tail recovery
+// - Collection: Entities and filtered vector index of one collection
+// - Catalog: Collection and alias registry, WAL-logged and replayed on open
+// - CDC: Entity, edge and tier change events decoded from the WAL
+// - Bulk: NDJSON, Parquet and NumPy import/export in WAL-logged segments
+// - Checkpoint: Log position and collection snapshot recorded on clean shutdown
+// - Tiering: Access-driven hot/warm/cold placement policy
 
-// Placeholder - will be implemented in Phase 19
+pub mod wal;
+pub mod collection;
+pub mod catalog;
+pub mod cdc;
+pub mod bulk;
+pub mod checkpoint;
+pub mod tiering;
+
+pub use bulk::{BulkFile, BulkRow, ExportReport, LoadReport};
+pub use catalog::Catalog;
+pub use cdc::{CdcEvent, CdcStream, Change, ChangeCapture, ChangeDecoder};
+pub use checkpoint::Checkpoint;
+pub use collection::Collection;
+pub use tiering::TierPolicy;
+pub use wal::{Lsn, Wal, WalOp, WalRecord};
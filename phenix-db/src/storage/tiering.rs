@@ -1 +1,197 @@
-// TODO: Implement tiering logic
+//! Tier placement policy
+//!
+//! Entities move one tier at a time. Promotion follows the access frequency
+//! moving average alone; demotion additionally requires the entity to have
+//! gone unread for the tier's demotion period, so a single quiet hour does
+//! not push a hot entity out. Access statistics are not persisted, so an
+//! entity nobody has read since startup counts as idle from its last write.
+//!
+//! Promotions are capped by the configured tier size shares, granting the
+//! most frequently read entities first.
+
+use crate::core::config::TieringConfig;
+use crate::core::{AccessStatistics, Entity, EntityId, MemoryTier};
+use crate::storage::collection::Collection;
+
+/// Milliseconds per hour, the unit of the demotion periods
+const MS_PER_HOUR: f64 = 60.0 * 60.0 * 1000.0;
+
+/// Decides which tier each entity belongs in
+#[derive(Debug, Clone)]
+pub struct TierPolicy {
+    config: TieringConfig,
+}
+
+impl TierPolicy {
+    /// Policy with the thresholds and tier shares of `config`
+    pub fn new(config: TieringConfig) -> Self {
+        Self { config }
+    }
+
+    /// Tier `entity` should move to at `now` (Unix epoch milliseconds),
+    /// ignoring tier capacity
+    pub fn target(&self, entity: &Entity, stats: Option<&AccessStatistics>, now: u64) -> MemoryTier {
+        let config = &self.config;
+        let frequency = stats.map_or(0.0, |stats| stats.access_frequency);
+        let last_read = stats.map_or(0, |stats| stats.last_access).max(entity.updated_at);
+        let idle_hours = now.saturating_sub(last_read) as f64 / MS_PER_HOUR;
+
+        match entity.tier {
+            MemoryTier::Cold | MemoryTier::Warm if frequency >= config.hot_promotion_threshold => {
+                if entity.tier == MemoryTier::Cold {
+                    MemoryTier::Warm
+                } else {
+                    MemoryTier::Hot
+                }
+            }
+            MemoryTier::Cold if frequency >= config.warm_promotion_threshold => MemoryTier::Warm,
+            MemoryTier::Hot
+                if frequency < config.hot_demotion_threshold
+                    && idle_hours >= config.hot_demotion_period_hours as f64 =>
+            {
+                MemoryTier::Warm
+            }
+            MemoryTier::Warm
+                if frequency < config.warm_demotion_threshold
+                    && idle_hours >= config.warm_demotion_period_hours as f64 =>
+            {
+                MemoryTier::Cold
+            }
+            tier => tier,
+        }
+    }
+
+    /// Entities of `collection` to move at `now`, with their new tier
+    pub fn plan(&self, collection: &Collection, now: u64) -> Vec<(EntityId, MemoryTier)> {
+        let access = collection.access();
+        let entities = collection.entities();
+        self.plan_entities(entities.iter().map(|entity| (&**entity, access.stats(&entity.id))), now)
+    }
+
+    fn plan_entities<'a>(
+        &self,
+        entities: impl Iterator<Item = (&'a Entity, Option<AccessStatistics>)>,
+        now: u64,
+    ) -> Vec<(EntityId, MemoryTier)> {
+        let mut total = 0;
+        let mut hot = 0;
+        let mut warm = 0;
+        let mut moves = Vec::new();
+        let mut promotions = Vec::new();
+        for (entity, stats) in entities {
+            let target = self.target(entity, stats.as_ref(), now);
+            total += 1;
+            match entity.tier {
+                MemoryTier::Hot => hot += 1,
+                MemoryTier::Warm => warm += 1,
+                MemoryTier::Cold => {}
+            }
+            match (entity.tier, target) {
+                (from, to) if from == to => {}
+                (MemoryTier::Hot, to) => {
+                    hot -= 1;
+                    warm += usize::from(to == MemoryTier::Warm);
+                    moves.push((entity.id, to));
+                }
+                (MemoryTier::Warm, MemoryTier::Cold) => {
+                    warm -= 1;
+                    moves.push((entity.id, MemoryTier::Cold));
+                }
+                (_, to) => {
+                    let frequency = stats.map_or(0.0, |stats| stats.access_frequency);
+                    promotions.push((frequency, entity.id, entity.tier, to));
+                }
+            }
+        }
+
+        // Shares are f32, so allow for 0.1 * 10 landing just below 1
+        let capacity = |share: f32| ((share as f64 * total as f64 + 1e-6).floor() as usize).max(1);
+        promotions.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let (hot_capacity, warm_capacity) =
+            (capacity(self.config.hot_tier_size_pct), capacity(self.config.warm_tier_size_pct));
+        for (_, id, from, to) in promotions {
+            let granted = match to {
+                MemoryTier::Hot => hot < hot_capacity,
+                _ => warm < warm_capacity,
+            };
+            if granted {
+                match to {
+                    MemoryTier::Hot => hot += 1,
+                    _ => warm += 1,
+                }
+                warm -= usize::from(from == MemoryTier::Warm);
+                moves.push((id, to));
+            }
+        }
+        moves
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Vector;
+
+    const HOUR: u64 = 60 * 60 * 1000;
+
+    fn entity(tier: MemoryTier, updated_at: u64) -> Entity {
+        let mut entity = Entity::new(Some(Vector::new(vec![1.0, 0.0])), None, None);
+        entity.tier = tier;
+        entity.updated_at = updated_at;
+        entity
+    }
+
+    fn stats(access_frequency: f32, last_access: u64) -> AccessStatistics {
+        AccessStatistics {
+            access_frequency,
+            last_access,
+            ..AccessStatistics::new()
+        }
+    }
+
+    #[test]
+    fn test_targets_follow_frequency_and_idle_time() {
+        let policy = TierPolicy::new(TieringConfig::default());
+        let now = 1000 * HOUR;
+
+        // One tier at a time, upwards on frequency alone
+        assert_eq!(policy.target(&entity(MemoryTier::Cold, now), Some(&stats(500.0, now)), now), MemoryTier::Warm);
+        assert_eq!(policy.target(&entity(MemoryTier::Warm, now), Some(&stats(500.0, now)), now), MemoryTier::Hot);
+        assert_eq!(policy.target(&entity(MemoryTier::Cold, now), Some(&stats(20.0, now)), now), MemoryTier::Warm);
+        assert_eq!(policy.target(&entity(MemoryTier::Warm, now), Some(&stats(20.0, now)), now), MemoryTier::Warm);
+
+        // Downwards only after the demotion period without reads
+        let quiet = stats(0.5, now - 2 * HOUR);
+        assert_eq!(policy.target(&entity(MemoryTier::Hot, 0), Some(&quiet), now), MemoryTier::Hot);
+        let idle = stats(0.5, now - 25 * HOUR);
+        assert_eq!(policy.target(&entity(MemoryTier::Hot, 0), Some(&idle), now), MemoryTier::Warm);
+        assert_eq!(policy.target(&entity(MemoryTier::Warm, 0), Some(&idle), now), MemoryTier::Warm);
+        assert_eq!(policy.target(&entity(MemoryTier::Warm, 0), None, now), MemoryTier::Cold);
+
+        // Unread since startup counts from the last write
+        assert_eq!(policy.target(&entity(MemoryTier::Hot, now - HOUR), None, now), MemoryTier::Hot);
+    }
+
+    #[test]
+    fn test_plan_caps_promotions_by_tier_share() {
+        let now = 1000 * HOUR;
+        let entities: Vec<Entity> = (0..10).map(|_| entity(MemoryTier::Warm, now)).collect();
+        // Hot share is 10% of 10 entities; two qualify, the busier one wins
+        let frequencies = [150.0, 400.0, 50.0];
+        let entries = entities.iter().enumerate().map(|(index, entity)| {
+            (entity, frequencies.get(index).map(|&frequency| stats(frequency, now)))
+        });
+        let moves = TierPolicy::new(TieringConfig::default()).plan_entities(entries, now);
+        assert_eq!(moves, vec![(entities[1].id, MemoryTier::Hot)]);
+
+        // A demotion from hot frees a slot in the same pass
+        let mut entities = entities;
+        entities[9].tier = MemoryTier::Hot;
+        entities[9].updated_at = 0;
+        let entries = entities.iter().enumerate().map(|(index, entity)| {
+            (entity, frequencies.get(index).map(|&frequency| stats(frequency, now)))
+        });
+        let moves = TierPolicy::new(TieringConfig::default()).plan_entities(entries, now);
+        assert_eq!(moves, vec![(entities[9].id, MemoryTier::Warm), (entities[1].id, MemoryTier::Hot)]);
+    }
+}
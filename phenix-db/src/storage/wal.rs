@@ -1 +1,748 @@
-// TODO: Implement WAL
+//! Write-ahead log
+//!
+//! Every catalog and entity mutation is appended here before it is applied,
+//! so state can be rebuilt by replaying the log after a restart. Records are
+//! scoped to a collection: each carries the `CollectionId` it applies to.
+//!
+//! File layout (little endian):
+//! - 8-byte magic `PHXWAL02`
+//! - LSN of the first record the file can hold (u64)
+//! - Frames: payload length (u32), payload checksum (u64, truncated BLAKE3),
+//!   JSON-encoded `WalRecord`
+//!
+//! Once a checkpoint holds everything logged so far, `reset` replaces the
+//! file with an empty one starting at the next LSN, so the log only grows
+//! between checkpoints. Files with the older `PHXWAL01` magic have no base
+//! LSN and start at 1.
+//!
+//! A frame that is truncated or fails its checksum at the end of the file is
+//! the tail of a write interrupted by a crash, and `open` truncates it away
+//! before new records are appended. A damaged frame followed by intact ones
+//! is corruption, not a crash: the log is refused and left untouched rather
+//! than dropping every record after it. Only the bytes after the damaged
+//! frame are searched for intact ones, and only when they fit in a single
+//! frame; anything longer cannot be the tail of one interrupted append. A
+//! failed append is rolled back the same way, so it never leaves a frame
+//! behind that a later append would bury.
+
+use crate::core::collection::CollectionConfig;
+use crate::core::error::{ErrorContext, MemorySubstrateError, Result};
+use crate::core::mvcc::MvccWrite;
+use crate::core::transaction::TransactionId;
+use crate::core::{CollectionId, Entity, EntityId};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Log sequence number: position of a record in the log, starting at 1
+pub type Lsn = u64;
+
+const MAGIC: &[u8; 8] = b"PHXWAL02";
+
+/// Magic of logs written before they could be reset; they start at LSN 1
+const MAGIC_V1: &[u8; 8] = b"PHXWAL01";
+
+/// Magic followed by the base LSN
+const HEADER_SIZE: usize = 16;
+const FRAME_HEADER_SIZE: usize = 12;
+
+/// How every serialized `WalRecord` begins
+const RECORD_PREFIX: &[u8] = b"{\"lsn\":";
+
+/// Largest accepted frame payload; anything bigger is treated as corruption
+pub const MAX_RECORD_SIZE: usize = 64 * 1024 * 1024;
+
+/// Mutation recorded in the log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WalOp {
+    /// Collection created with the record's collection id
+    CreateCollection {
+        /// Primary name
+        name: String,
+        /// Creation configuration
+        config: CollectionConfig,
+        /// Creation timestamp (Unix epoch milliseconds)
+        created_at: u64,
+    },
+
+    /// Collection and all its entities dropped
+    DropCollection,
+
+    /// Alias created or re-pointed at the record's collection
+    SetAlias {
+        /// Alias name
+        alias: String,
+    },
+
+    /// Alias removed
+    DropAlias {
+        /// Alias name
+        alias: String,
+    },
+
+    /// Entity inserted or replaced
+    Upsert {
+        /// Full entity state
+        entity: Box<Entity>,
+    },
+
+    /// Entity deleted
+    Delete {
+        /// Deleted entity
+        entity_id: EntityId,
+    },
+
+    /// Transaction committed; all writes apply atomically
+    Transaction {
+        /// Committing transaction
+        txn_id: TransactionId,
+        /// Writes in the transaction
+        writes: Vec<MvccWrite>,
+    },
+
+    /// Segment of a bulk load; every entity is inserted or replaced
+    LoadSegment {
+        /// Full entity states
+        entities: Vec<Entity>,
+    },
+}
+
+/// One log entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalRecord {
+    /// Sequence number
+    pub lsn: Lsn,
+
+    /// Collection the mutation applies to
+    pub collection: CollectionId,
+
+    /// Append time (Unix epoch milliseconds)
+    pub timestamp: u64,
+
+    /// The mutation
+    pub op: WalOp,
+}
+
+/// Contents of a log file, read without modifying it
+#[derive(Debug, Clone)]
+pub struct WalScan {
+    /// Intact records in LSN order
+    pub records: Vec<WalRecord>,
+
+    /// LSN of the first record the file can hold
+    pub base_lsn: Lsn,
+
+    /// Byte length of the intact prefix
+    pub valid_len: u64,
+
+    /// Byte length of the file; 0 if it does not exist yet
+    pub file_len: u64,
+}
+
+impl WalScan {
+    /// LSN the next appended record will receive
+    pub fn next_lsn(&self) -> Lsn {
+        self.records.last().map_or(self.base_lsn, |r| r.lsn + 1)
+    }
+
+    /// Whether the file ends in a torn frame that opening will truncate
+    pub fn is_torn(&self) -> bool {
+        self.file_len > 0 && self.valid_len < self.file_len
+    }
+}
+
+/// Append-only, checksummed write-ahead log file
+#[derive(Debug)]
+pub struct Wal {
+    path: PathBuf,
+    file: File,
+    len: u64,
+    next_lsn: Lsn,
+    sync_on_append: bool,
+    poisoned: bool,
+}
+
+impl Wal {
+    /// Open or create the log at `path`
+    ///
+    /// Existing frames are verified to find the next LSN; a torn tail left by
+    /// a crash is truncated.
+    ///
+    /// # Errors
+    /// * `InvariantViolation` if a damaged frame is followed by intact ones
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::open_checked(path, |_| Ok(()))?.0)
+    }
+
+    /// Open or create the log at `path` once `check` accepts its contents,
+    /// returning the log and its intact records
+    ///
+    /// `check` sees the file exactly as found: nothing is created or
+    /// truncated unless it succeeds.
+    ///
+    /// # Errors
+    /// * `InvariantViolation` if a damaged frame is followed by intact ones
+    /// * Whatever `check` returns
+    pub fn open_checked(
+        path: impl AsRef<Path>,
+        check: impl FnOnce(&WalScan) -> Result<()>,
+    ) -> Result<(Self, Vec<WalRecord>)> {
+        let path = path.as_ref().to_path_buf();
+        let scan = Self::scan(&path)?;
+        check(&scan)?;
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let mut len = file.metadata()?.len();
+        if len != scan.file_len {
+            return Err(MemorySubstrateError::Internal(format!(
+                "write-ahead log {} changed while it was being opened",
+                path.display()
+            )));
+        }
+        if len == 0 {
+            file.write_all(&header(scan.base_lsn))?;
+            file.sync_all()?;
+            len = HEADER_SIZE as u64;
+        } else if scan.is_torn() {
+            tracing::warn!(
+                path = %path.display(),
+                valid_len = scan.valid_len,
+                torn_bytes = scan.file_len - scan.valid_len,
+                "truncating torn write-ahead log tail"
+            );
+            file.set_len(scan.valid_len)?;
+            file.sync_all()?;
+            len = scan.valid_len;
+        }
+        file.seek(SeekFrom::End(0))?;
+
+        let wal = Self {
+            path,
+            file,
+            len,
+            next_lsn: scan.next_lsn(),
+            sync_on_append: true,
+            poisoned: false,
+        };
+        Ok((wal, scan.records))
+    }
+
+    /// Read the log at `path` without opening it for writing; a missing
+    /// file is an empty log
+    ///
+    /// # Errors
+    /// * `InvariantViolation` if a damaged frame is followed by intact ones
+    pub fn scan(path: impl AsRef<Path>) -> Result<WalScan> {
+        let path = path.as_ref();
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(WalScan {
+                    records: Vec::new(),
+                    base_lsn: 1,
+                    valid_len: 0,
+                    file_len: 0,
+                })
+            }
+            Err(error) => return Err(error.into()),
+        };
+        let file_len = file.metadata()?.len();
+        if file_len == 0 {
+            return Ok(WalScan {
+                records: Vec::new(),
+                base_lsn: 1,
+                valid_len: 0,
+                file_len: 0,
+            });
+        }
+        decode_frames(path, BufReader::new(file), file_len)
+    }
+
+    /// Control whether every append is followed by an fsync (default: true)
+    ///
+    /// Disabling it trades durability of the latest records for throughput;
+    /// call `sync` at commit points instead.
+    pub fn with_sync_on_append(mut self, sync: bool) -> Self {
+        self.sync_on_append = sync;
+        self
+    }
+
+    /// Path of the log file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// LSN the next appended record will receive
+    pub fn next_lsn(&self) -> Lsn {
+        self.next_lsn
+    }
+
+    /// Append a mutation for `collection`, returning its LSN
+    pub fn append(&mut self, collection: CollectionId, op: WalOp) -> Result<Lsn> {
+        Ok(self.append_record(collection, op)?.lsn)
+    }
+
+    /// Append a mutation for `collection`, returning the record as written
+    ///
+    /// A failed write or sync is rolled back by truncating the file to its
+    /// length before the append, and the LSN is not consumed.
+    ///
+    /// # Errors
+    /// * `Io` if the frame cannot be written or synced
+    /// * `Internal` if an earlier failure could not be rolled back; the
+    ///   log then refuses every further append
+    pub fn append_record(&mut self, collection: CollectionId, op: WalOp) -> Result<WalRecord> {
+        if self.poisoned {
+            return Err(MemorySubstrateError::Internal(format!(
+                "write-ahead log {} is unusable after a failed append; reopen it to recover",
+                self.path.display()
+            )));
+        }
+        let record = WalRecord {
+            lsn: self.next_lsn,
+            collection,
+            timestamp: now_ms(),
+            op,
+        };
+        let payload = serde_json::to_vec(&record)
+            .map_err(|e| MemorySubstrateError::Serialization(e.to_string()))?;
+        if payload.len() > MAX_RECORD_SIZE {
+            return Err(MemorySubstrateError::Serialization(format!(
+                "WAL record of {} bytes exceeds limit of {}",
+                payload.len(),
+                MAX_RECORD_SIZE
+            )));
+        }
+
+        let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&checksum(&payload).to_le_bytes());
+        frame.extend_from_slice(&payload);
+        let written = self.file.write_all(&frame).and_then(|()| {
+            if self.sync_on_append {
+                self.file.sync_data()
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(error) = written {
+            self.roll_back();
+            return Err(error.into());
+        }
+
+        self.len += frame.len() as u64;
+        self.next_lsn += 1;
+        Ok(record)
+    }
+
+    /// Drop whatever part of a failed append reached the file
+    fn roll_back(&mut self) {
+        let restored = self
+            .file
+            .set_len(self.len)
+            .and_then(|()| self.file.sync_all())
+            .and_then(|()| self.file.seek(SeekFrom::Start(self.len)).map(drop));
+        if let Err(error) = restored {
+            tracing::error!(path = %self.path.display(), %error, "cannot roll back failed write-ahead log append");
+            self.poisoned = true;
+        }
+    }
+
+    /// Flush appended records to stable storage
+    pub fn sync(&mut self) -> Result<()> {
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    /// Drop every record, keeping the LSN sequence
+    ///
+    /// Call once a checkpoint holds everything logged so far. The empty
+    /// log is written beside the old one and renamed over it, so a crash
+    /// leaves one or the other; the next append still receives `next_lsn`.
+    pub fn reset(&mut self) -> Result<()> {
+        let staged = self.path.with_extension("wal.tmp");
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&staged)?;
+        file.write_all(&header(self.next_lsn))?;
+        file.sync_all()?;
+        std::fs::rename(&staged, &self.path)?;
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            // Make the rename itself durable
+            File::open(dir)?.sync_all()?;
+        }
+        file.seek(SeekFrom::End(0))?;
+        self.file = file;
+        self.len = HEADER_SIZE as u64;
+        self.poisoned = false;
+        Ok(())
+    }
+
+    /// Read every intact record in LSN order
+    pub fn read_all(&self) -> Result<Vec<WalRecord>> {
+        Self::read_file(&self.path)
+    }
+
+    /// Read every intact record of the log at `path` without opening it
+    /// for writing
+    pub fn read_file(path: impl AsRef<Path>) -> Result<Vec<WalRecord>> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        Ok(decode_frames(path, BufReader::new(file), file_len)?.records)
+    }
+}
+
+/// File header of a log whose first record will be `base_lsn`
+fn header(base_lsn: Lsn) -> [u8; HEADER_SIZE] {
+    let mut header = [0u8; HEADER_SIZE];
+    header[..MAGIC.len()].copy_from_slice(MAGIC);
+    header[MAGIC.len()..].copy_from_slice(&base_lsn.to_le_bytes());
+    header
+}
+
+/// Intact frame starting at `offset`: its total length and payload
+fn frame_at(bytes: &[u8], offset: usize) -> Option<(usize, &[u8])> {
+    let header = bytes.get(offset..offset + FRAME_HEADER_SIZE)?;
+    let len = u32::from_le_bytes(header[..4].try_into().ok()?) as usize;
+    let sum = u64::from_le_bytes(header[4..].try_into().ok()?);
+    if len > MAX_RECORD_SIZE {
+        return None;
+    }
+    let start = offset + FRAME_HEADER_SIZE;
+    let payload = bytes.get(start..start + len)?;
+    (checksum(payload) == sum).then_some((FRAME_HEADER_SIZE + len, payload))
+}
+
+/// Offset of the first intact frame in `tail` after its first byte
+///
+/// Every payload is a JSON `WalRecord` opening with its LSN, so only
+/// offsets followed by that prefix are checksummed, which keeps the search
+/// linear in the length of `tail`.
+fn find_intact_frame(tail: &[u8]) -> Option<usize> {
+    tail.windows(RECORD_PREFIX.len())
+        .enumerate()
+        .filter(|(start, window)| *start > FRAME_HEADER_SIZE && *window == RECORD_PREFIX)
+        .map(|(start, _)| start - FRAME_HEADER_SIZE)
+        .find(|&offset| frame_at(tail, offset).is_some())
+}
+
+/// Decode the frames of a log file of `file_len` bytes
+///
+/// Decoding stops at the first damaged frame. A crash can only tear the
+/// frame that was being appended, so what follows is a torn tail only if it
+/// fits in one frame and no intact frame starts anywhere inside it.
+fn decode_frames(path: &Path, mut reader: impl Read, file_len: u64) -> Result<WalScan> {
+    let foreign = || MemorySubstrateError::Serialization("not a Phenix-DB write-ahead log".to_string());
+    let mut magic = [0u8; MAGIC.len()];
+    if file_len < MAGIC.len() as u64 || reader.read_exact(&mut magic).is_err() {
+        return Err(foreign());
+    }
+    let (base_lsn, mut offset) = if &magic == MAGIC_V1 {
+        (1, MAGIC.len() as u64)
+    } else if &magic == MAGIC && file_len >= HEADER_SIZE as u64 {
+        let mut base = [0u8; 8];
+        reader.read_exact(&mut base)?;
+        (u64::from_le_bytes(base), HEADER_SIZE as u64)
+    } else {
+        return Err(foreign());
+    };
+
+    let mut records = Vec::new();
+    // Bytes of the damaged frame already consumed once decoding stops
+    let mut damaged = Vec::new();
+    while file_len - offset >= FRAME_HEADER_SIZE as u64 {
+        let mut header = [0u8; FRAME_HEADER_SIZE];
+        reader.read_exact(&mut header)?;
+        let len = u32::from_le_bytes(header[..4].try_into().expect("4-byte length")) as usize;
+        let sum = u64::from_le_bytes(header[4..].try_into().expect("8-byte checksum"));
+        damaged.extend_from_slice(&header);
+        if len > MAX_RECORD_SIZE || (FRAME_HEADER_SIZE + len) as u64 > file_len - offset {
+            break;
+        }
+        let mut payload = vec![0u8; len];
+        reader.read_exact(&mut payload)?;
+        if checksum(&payload) != sum {
+            damaged.extend_from_slice(&payload);
+            break;
+        }
+        damaged.clear();
+
+        let record = serde_json::from_slice::<WalRecord>(&payload).map_err(|e| {
+            MemorySubstrateError::Serialization(format!(
+                "undecodable write-ahead log record at byte {} of {}: {}",
+                offset,
+                path.display(),
+                e
+            ))
+        })?;
+        records.push(record);
+        offset += (FRAME_HEADER_SIZE + len) as u64;
+    }
+
+    let tail_len = file_len - offset;
+    let intact = if tail_len == 0 {
+        None
+    } else if tail_len > (FRAME_HEADER_SIZE + MAX_RECORD_SIZE) as u64 {
+        // More than one interrupted append can leave behind
+        Some("unknown".to_string())
+    } else {
+        reader.read_to_end(&mut damaged)?;
+        find_intact_frame(&damaged).map(|start| (offset + start as u64).to_string())
+    };
+    if let Some(intact) = intact {
+        return Err(MemorySubstrateError::InvariantViolation {
+            message: format!(
+                "write-ahead log {} is corrupt at byte {} but holds intact records from byte {}",
+                path.display(),
+                offset,
+                intact
+            ),
+            context: Box::new(
+                ErrorContext::new("wal", "open")
+                    .with_detail("corrupt_offset", offset.to_string())
+                    .with_detail("intact_offset", intact)
+                    .with_detail("last_lsn", records.last().map_or(0, |r: &WalRecord| r.lsn).to_string()),
+            ),
+        });
+    }
+
+    Ok(WalScan {
+        records,
+        base_lsn,
+        valid_len: offset,
+        file_len,
+    })
+}
+
+fn checksum(bytes: &[u8]) -> u64 {
+    let hash = blake3::hash(bytes);
+    let mut first = [0u8; 8];
+    first.copy_from_slice(&hash.as_bytes()[..8]);
+    u64::from_le_bytes(first)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Vector;
+
+    fn upsert(values: Vec<f32>) -> WalOp {
+        WalOp::Upsert {
+            entity: Box::new(Entity::new(Some(Vector::new(values)), Some(serde_json::json!({"k": 1})), None)),
+        }
+    }
+
+    #[test]
+    fn test_append_and_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("phenix.wal");
+        let a = CollectionId::new();
+        let b = CollectionId::new();
+
+        {
+            let mut wal = Wal::open(&path).unwrap();
+            assert_eq!(wal.next_lsn(), 1);
+            wal.append(a, WalOp::CreateCollection {
+                name: "a".to_string(),
+                config: CollectionConfig::new(2),
+                created_at: 0,
+            })
+            .unwrap();
+            wal.append(a, upsert(vec![1.0, 2.0])).unwrap();
+            assert_eq!(wal.append(b, WalOp::DropCollection).unwrap(), 3);
+        }
+
+        let wal = Wal::open(&path).unwrap();
+        assert_eq!(wal.next_lsn(), 4);
+        let records = wal.read_all().unwrap();
+        assert_eq!(records.iter().map(|r| r.lsn).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(records[1].collection, a);
+        assert_eq!(records[2].collection, b);
+        match &records[1].op {
+            WalOp::Upsert { entity } => assert_eq!(entity.vector.as_ref().unwrap().values, vec![1.0, 2.0]),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_torn_tail_is_truncated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("phenix.wal");
+        let collection = CollectionId::new();
+
+        {
+            let mut wal = Wal::open(&path).unwrap().with_sync_on_append(false);
+            wal.append(collection, upsert(vec![1.0])).unwrap();
+            wal.append(collection, upsert(vec![2.0])).unwrap();
+            wal.sync().unwrap();
+        }
+
+        // Chop the last frame in half, as a crash mid-write would
+        let len = std::fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 10).unwrap();
+
+        let mut wal = Wal::open(&path).unwrap();
+        assert_eq!(wal.read_all().unwrap().len(), 1);
+        assert_eq!(wal.append(collection, upsert(vec![3.0])).unwrap(), 2);
+        assert_eq!(wal.read_all().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_corrupt_frame_ends_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("phenix.wal");
+        let collection = CollectionId::new();
+
+        {
+            let mut wal = Wal::open(&path).unwrap();
+            for i in 0..3 {
+                wal.append(collection, upsert(vec![i as f32])).unwrap();
+            }
+        }
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 5;
+        bytes[last] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let wal = Wal::open(&path).unwrap();
+        assert_eq!(wal.read_all().unwrap().len(), 2);
+        assert_eq!(wal.next_lsn(), 3);
+    }
+
+    #[test]
+    fn test_corruption_before_intact_frames_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("phenix.wal");
+        let collection = CollectionId::new();
+
+        {
+            let mut wal = Wal::open(&path).unwrap();
+            for i in 0..3 {
+                wal.append(collection, upsert(vec![i as f32])).unwrap();
+            }
+        }
+
+        // Damage the first frame's payload; the two after it are intact
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[HEADER_SIZE + FRAME_HEADER_SIZE + 4] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+
+        for result in [Wal::scan(&path).map(drop), Wal::open(&path).map(drop)] {
+            assert!(matches!(result, Err(MemorySubstrateError::InvariantViolation { .. })));
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), bytes, "a refused log is left untouched");
+
+        // A damaged length claiming the rest of the file is refused too
+        bytes[HEADER_SIZE + FRAME_HEADER_SIZE + 4] ^= 0xff;
+        let rest = (bytes.len() - HEADER_SIZE - FRAME_HEADER_SIZE) as u32;
+        bytes[HEADER_SIZE..HEADER_SIZE + 4].copy_from_slice(&rest.to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(matches!(Wal::open(&path), Err(MemorySubstrateError::InvariantViolation { .. })));
+    }
+
+    #[test]
+    fn test_failed_append_is_rolled_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("phenix.wal");
+        let collection = CollectionId::new();
+
+        let mut wal = Wal::open(&path).unwrap();
+        wal.append(collection, upsert(vec![1.0])).unwrap();
+
+        // Half a frame reached the file before the write failed
+        wal.file.write_all(&[7; 20]).unwrap();
+        wal.roll_back();
+        assert_eq!(wal.append(collection, upsert(vec![2.0])).unwrap(), 2);
+        drop(wal);
+
+        let wal = Wal::open(&path).unwrap();
+        assert_eq!(wal.read_all().unwrap().iter().map(|r| r.lsn).collect::<Vec<_>>(), vec![1, 2]);
+
+        let mut poisoned = wal;
+        poisoned.poisoned = true;
+        assert!(matches!(
+            poisoned.append(collection, upsert(vec![3.0])),
+            Err(MemorySubstrateError::Internal(_))
+        ));
+    }
+
+    #[test]
+    fn test_replays_records_logged_before_the_access_tracker() {
+        // Frames exactly as written when entities still carried their own
+        // access statistics
+        let records = [
+            r#"{"lsn":1,"collection":"01a1502a-3546-7654-a937-55bf562d74f7","timestamp":1792346305862,"op":{"type":"create_collection","name":"docs","config":{"dimension":2,"metric":"Cosine","index":{"m":16,"ef_construction":200,"ef_search":64},"tiering":{"hot_promotion_threshold":100.0,"warm_promotion_threshold":10.0,"hot_demotion_threshold":1.0,"warm_demotion_threshold":0.1,"hot_demotion_period_hours":24,"warm_demotion_period_hours":168,"hot_tier_size_pct":0.1,"warm_tier_size_pct":0.3}},"created_at":1700000000000}}"#,
+            r#"{"lsn":2,"collection":"01a1502a-3546-7654-a937-55bf562d74f7","timestamp":1792346305862,"op":{"type":"upsert","entity":{"id":"01a1502a-3546-7654-a937-55c090f206c4","vector":{"dimensions":2,"values":[1.0,0.5],"norm":1.118034},"metadata":{"lang":"en"},"created_at":1792346305862,"updated_at":1792346305862,"version":1,"tier":"Hot","access_statistics":{"total_accesses":1,"last_access":1792346305862,"access_frequency":0.0,"co_access_entities":[]}}}}"#,
+        ];
+        let mut bytes = MAGIC_V1.to_vec();
+        for record in records {
+            bytes.extend_from_slice(&(record.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&checksum(record.as_bytes()).to_le_bytes());
+            bytes.extend_from_slice(record.as_bytes());
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("phenix.wal");
+        std::fs::write(&path, &bytes).unwrap();
+
+        let catalog = crate::storage::catalog::Catalog::open(&path).unwrap();
+        let id: EntityId = serde_json::from_str(r#""01a1502a-3546-7654-a937-55c090f206c4""#).unwrap();
+        let entity = catalog.get("docs", &id).unwrap().unwrap();
+        assert_eq!(entity.metadata, Some(serde_json::json!({"lang": "en"})));
+        catalog.delete("docs", &id).unwrap();
+        drop(catalog);
+        assert_eq!(Wal::open(&path).unwrap().next_lsn(), 4);
+    }
+
+    #[test]
+    fn test_reset_keeps_the_lsn_sequence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("phenix.wal");
+        let collection = CollectionId::new();
+
+        let mut wal = Wal::open(&path).unwrap();
+        wal.append(collection, upsert(vec![1.0])).unwrap();
+        wal.append(collection, upsert(vec![2.0])).unwrap();
+        wal.reset().unwrap();
+        assert!(wal.read_all().unwrap().is_empty());
+        assert_eq!(wal.append(collection, upsert(vec![3.0])).unwrap(), 3);
+        drop(wal);
+
+        let scan = Wal::scan(&path).unwrap();
+        assert_eq!(scan.base_lsn, 3);
+        assert_eq!(scan.records.iter().map(|r| r.lsn).collect::<Vec<_>>(), vec![3]);
+
+        // An emptied log still continues the sequence after a restart
+        let mut wal = Wal::open(&path).unwrap();
+        wal.reset().unwrap();
+        drop(wal);
+        assert_eq!(Wal::open(&path).unwrap().next_lsn(), 4);
+    }
+
+    #[test]
+    fn test_rejects_foreign_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("not-a-wal");
+        std::fs::write(&path, b"hello world").unwrap();
+        assert!(matches!(Wal::open(&path), Err(MemorySubstrateError::Serialization(_))));
+    }
+}